@@ -0,0 +1,133 @@
+//! Compares how long a frame takes to read the same buffer from four views at
+//! once, when the buffer is shared behind a `Mutex` versus an `RwLock`, while the
+//! audio thread keeps enqueueing into it.
+//!
+//! Run it using `cargo bench --bench shared_read`.
+
+use cyma::prelude::*;
+use cyma::utils::PeakBuffer;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Barrier, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SAMPLE_RATE: f32 = 48_000.;
+const BLOCK_SIZE: usize = 64;
+// A graph, a meter, a readout and a sparkline
+const READERS: usize = 4;
+const FRAMES: usize = 600;
+
+struct Results {
+    // The time between the start of a frame and the last view finishing its read
+    mean_frame: Duration,
+    max_frame: Duration,
+}
+
+fn main() {
+    let mutex = run(
+        Arc::new(Mutex::new(new_buffer())),
+        |shared: &Arc<Mutex<PeakBuffer>>, block: &[f32]| {
+            let mut buffer = shared.lock().unwrap();
+            for sample in block {
+                buffer.enqueue(*sample);
+            }
+        },
+    );
+    let rw_lock = run(
+        Arc::new(RwLock::new(new_buffer())),
+        |shared: &Arc<RwLock<PeakBuffer>>, block: &[f32]| {
+            let mut buffer = shared.write().unwrap();
+            for sample in block {
+                buffer.enqueue(*sample);
+            }
+        },
+    );
+
+    println!("mutex:");
+    print(&mutex, &mutex);
+    println!("rw lock:");
+    print(&rw_lock, &mutex);
+}
+
+fn new_buffer() -> PeakBuffer {
+    let mut buffer = PeakBuffer::new(4000, 10.0, 50.0);
+    buffer.set_sample_rate(SAMPLE_RATE);
+    buffer
+}
+
+fn print(results: &Results, mutex: &Results) {
+    println!(
+        "  mean frame: {:?} ({:.1}% of mutex), longest frame: {:?}",
+        results.mean_frame,
+        100. * results.mean_frame.as_secs_f64() / mutex.mean_frame.as_secs_f64(),
+        results.max_frame,
+    );
+}
+
+/// Simulates the audio thread processing blocks while the views read the buffer at
+/// the start of each frame, all at once.
+fn run<S>(shared: S, enqueue: fn(&S, &[f32])) -> Results
+where
+    S: SharedRead<Value = PeakBuffer> + Send + Sync + 'static,
+{
+    let done = Arc::new(AtomicBool::new(false));
+    let audio = {
+        let shared = shared.clone();
+        let done = done.clone();
+        thread::spawn(move || {
+            let block = (0..BLOCK_SIZE)
+                .map(|i| (i as f32 * 0.05).sin())
+                .collect::<Vec<_>>();
+            while !done.load(Ordering::Relaxed) {
+                enqueue(&shared, &block);
+                thread::sleep(Duration::from_secs_f32(BLOCK_SIZE as f32 / SAMPLE_RATE));
+            }
+        })
+    };
+
+    // The views wait for each other at the start and the end of every frame
+    let frame = Arc::new(Barrier::new(READERS + 1));
+    let readers = (0..READERS)
+        .map(|_| {
+            let shared = shared.clone();
+            let frame = frame.clone();
+            thread::spawn(move || {
+                for _ in 0..FRAMES {
+                    frame.wait();
+                    {
+                        let buffer = shared.read_shared(|buffer| buffer.refresh());
+                        let mut sum = 0.;
+                        for i in 0..buffer.len() {
+                            sum += buffer[i];
+                        }
+                        std::hint::black_box(sum);
+                    }
+                    frame.wait();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut total = Duration::ZERO;
+    let mut max_frame = Duration::ZERO;
+    for _ in 0..FRAMES {
+        frame.wait();
+        let start = Instant::now();
+        frame.wait();
+        let elapsed = start.elapsed();
+        total += elapsed;
+        max_frame = max_frame.max(elapsed);
+        thread::sleep(Duration::from_millis(16));
+    }
+
+    for reader in readers {
+        reader.join().unwrap();
+    }
+    done.store(true, Ordering::Relaxed);
+    audio.join().unwrap();
+
+    Results {
+        mean_frame: total / FRAMES as u32,
+        max_frame,
+    }
+}
@@ -0,0 +1,130 @@
+//! Compares how often the shared buffer is locked when enqueueing into it
+//! directly, versus through a `StagedBuffer`, while an editor thread reads it at
+//! roughly 60 Hz.
+//!
+//! Run it using `cargo bench --bench staged_publish`.
+
+use cyma::prelude::*;
+use cyma::utils::{PeakBuffer, StagedBuffer};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SAMPLE_RATE: f32 = 48_000.;
+const BLOCK_SIZE: usize = 64;
+// 30 seconds of audio
+const BLOCKS: usize = 30 * SAMPLE_RATE as usize / BLOCK_SIZE;
+
+struct Results {
+    locks: usize,
+    max_wait: Duration,
+    total: Duration,
+}
+
+fn main() {
+    let direct = run(|shared| {
+        let mut locks = 0;
+        let mut max_wait = Duration::ZERO;
+
+        move |block: &[f32]| {
+            let start = Instant::now();
+            let mut buffer = shared.lock().unwrap();
+            max_wait = max_wait.max(start.elapsed());
+            locks += 1;
+
+            for sample in block {
+                buffer.enqueue(*sample);
+            }
+            (locks, max_wait)
+        }
+    });
+
+    for divider in [1, 4, 16] {
+        let staged = run(|shared| {
+            let mut staged = StagedBuffer::new(shared, BLOCK_SIZE * divider);
+            staged.set_divider(divider);
+            let mut max_wait = Duration::ZERO;
+
+            move |block: &[f32]| {
+                let start = Instant::now();
+                {
+                    let mut staged_block = staged.begin_block();
+                    for sample in block {
+                        staged_block.enqueue(*sample);
+                    }
+                }
+                max_wait = max_wait.max(start.elapsed());
+                (staged.publish_count(), max_wait)
+            }
+        });
+
+        println!("staged (divider {divider}):");
+        print(&staged, &direct);
+    }
+
+    println!("direct:");
+    print(&direct, &direct);
+}
+
+fn print(results: &Results, direct: &Results) {
+    println!(
+        "  {:>6} locks ({:.1}% of direct), longest block: {:?}, total: {:?}",
+        results.locks,
+        100. * results.locks as f32 / direct.locks as f32,
+        results.max_wait,
+        results.total
+    );
+}
+
+/// Simulates the audio thread processing blocks while an editor thread reads the
+/// buffer at ~60 Hz.
+fn run<F, P>(setup: F) -> Results
+where
+    F: FnOnce(Arc<Mutex<PeakBuffer>>) -> P,
+    P: FnMut(&[f32]) -> (usize, Duration),
+{
+    let mut buffer = PeakBuffer::new(800, 10.0, 50.0);
+    buffer.set_sample_rate(SAMPLE_RATE);
+    let shared = Arc::new(Mutex::new(buffer));
+
+    let done = Arc::new(AtomicBool::new(false));
+    let editor = {
+        let shared = shared.clone();
+        let done = done.clone();
+        thread::spawn(move || {
+            while !done.load(Ordering::Relaxed) {
+                {
+                    let buffer = shared.lock().unwrap();
+                    let mut sum = 0.;
+                    for i in 0..buffer.len() {
+                        sum += buffer[i];
+                    }
+                    std::hint::black_box(sum);
+                }
+                thread::sleep(Duration::from_millis(16));
+            }
+        })
+    };
+
+    let mut process = setup(shared);
+    let block = (0..BLOCK_SIZE)
+        .map(|i| (i as f32 * 0.05).sin())
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+    let mut result = (0, Duration::ZERO);
+    for _ in 0..BLOCKS {
+        result = process(&block);
+    }
+    let total = start.elapsed();
+
+    done.store(true, Ordering::Relaxed);
+    editor.join().unwrap();
+
+    Results {
+        locks: result.0,
+        max_wait: result.1,
+        total,
+    }
+}
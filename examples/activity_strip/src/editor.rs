@@ -0,0 +1,61 @@
+use cyma::prelude::*;
+use nih_plug::editor::Editor;
+use nih_plug_vizia::{assets, create_vizia_editor, vizia::prelude::*, ViziaState, ViziaTheming};
+use std::sync::{Arc, Mutex};
+
+#[derive(Lens, Clone)]
+pub(crate) struct Data {
+    peak_buffer: Arc<Mutex<PeakBuffer>>,
+    note_buffer: Arc<Mutex<NoteEventBuffer>>,
+}
+
+impl Data {
+    pub(crate) fn new(
+        peak_buffer: Arc<Mutex<PeakBuffer>>,
+        note_buffer: Arc<Mutex<NoteEventBuffer>>,
+    ) -> Self {
+        Self {
+            peak_buffer,
+            note_buffer,
+        }
+    }
+}
+
+impl Model for Data {}
+
+pub(crate) fn default_state() -> Arc<ViziaState> {
+    ViziaState::new(|| (800, 500))
+}
+
+pub(crate) fn create(editor_data: Data, editor_state: Arc<ViziaState>) -> Option<Box<dyn Editor>> {
+    create_vizia_editor(editor_state, ViziaTheming::default(), move |cx, _| {
+        assets::register_noto_sans_light(cx);
+        editor_data.clone().build(cx);
+
+        VStack::new(cx, |cx| {
+            ZStack::new(cx, |cx| {
+                Grid::new(
+                    cx,
+                    ValueScaling::Linear,
+                    (-32., 8.),
+                    vec![6.0, 0.0, -6.0, -12.0, -18.0, -24.0, -30.0],
+                    Orientation::Horizontal,
+                )
+                .color(Color::rgb(60, 60, 60));
+
+                Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+                    .color(Color::rgba(255, 255, 255, 160))
+                    .background_color(Color::rgba(255, 255, 255, 60));
+            })
+            .background_color(Color::rgb(16, 16, 16));
+
+            // Two octaves below and above middle C
+            ActivityStrip::new(cx, Data::note_buffer, (36, 84))
+                .color(Color::rgb(255, 160, 60))
+                .background_color(Color::rgb(16, 16, 16))
+                .height(Pixels(120.));
+        })
+        .row_between(Pixels(8.))
+        .background_color(Color::rgb(0, 0, 0));
+    })
+}
@@ -0,0 +1,223 @@
+use cyma::prelude::*;
+use nih_plug::prelude::*;
+use nih_plug_vizia::ViziaState;
+use std::f32::consts::TAU;
+use std::sync::{Arc, Mutex};
+
+mod editor;
+
+// Both buffers share the same slots, so that the notes scroll in sync with the peaks
+const SIZE: usize = 800;
+const DURATION: f32 = 5.0;
+
+pub struct ActivityStripPlugin {
+    params: Arc<DemoParams>,
+    peak_buffer: Arc<Mutex<PeakBuffer>>,
+    note_buffer: Arc<Mutex<NoteEventBuffer>>,
+
+    sample_rate: f32,
+    phase: f32,
+    frequency: f32,
+    // The currently playing note, if any
+    note: Option<u8>,
+    gain: f32,
+    target_gain: f32,
+}
+
+#[derive(Params)]
+struct DemoParams {
+    #[persist = "editor-state"]
+    editor_state: Arc<ViziaState>,
+}
+
+impl Default for ActivityStripPlugin {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(DemoParams::default()),
+            peak_buffer: Arc::new(Mutex::new(PeakBuffer::new(SIZE, DURATION, 50.0))),
+            note_buffer: Arc::new(Mutex::new(NoteEventBuffer::new(SIZE, DURATION, 512))),
+
+            sample_rate: 1.0,
+            phase: 0.0,
+            frequency: 440.0,
+            note: None,
+            gain: 0.0,
+            target_gain: 0.0,
+        }
+    }
+}
+
+impl Default for DemoParams {
+    fn default() -> Self {
+        Self {
+            editor_state: editor::default_state(),
+        }
+    }
+}
+
+impl Plugin for ActivityStripPlugin {
+    const NAME: &'static str = "CymaActivityStrip";
+    const VENDOR: &'static str = "223230";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "223230@pm.me";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
+        main_input_channels: None,
+        main_output_channels: NonZeroU32::new(2),
+
+        aux_input_ports: &[],
+        aux_output_ports: &[],
+
+        names: PortNames::const_default(),
+    }];
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::None;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(
+            editor::Data::new(self.peak_buffer.clone(), self.note_buffer.clone()),
+            self.params.editor_state.clone(),
+        )
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.sample_rate = buffer_config.sample_rate;
+
+        match self.peak_buffer.lock() {
+            Ok(mut buffer) => {
+                buffer.set_sample_rate(buffer_config.sample_rate);
+            }
+            Err(_) => return false,
+        }
+        match self.note_buffer.lock() {
+            Ok(mut buffer) => {
+                buffer.set_sample_rate(buffer_config.sample_rate);
+            }
+            Err(_) => return false,
+        }
+
+        true
+    }
+
+    fn reset(&mut self) {
+        self.phase = 0.0;
+        self.note = None;
+        self.gain = 0.0;
+        self.target_gain = 0.0;
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut nih_plug::buffer::Buffer,
+        _: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        let editor_open = self.params.editor_state.is_open();
+        let mut note_buffer = self.note_buffer.lock().unwrap();
+
+        let mut next_event = context.next_event();
+        for (sample_id, channel_samples) in buffer.iter_samples().enumerate() {
+            while let Some(event) = next_event {
+                if event.timing() > sample_id as u32 {
+                    break;
+                }
+
+                match event {
+                    NoteEvent::NoteOn {
+                        timing,
+                        note,
+                        velocity,
+                        ..
+                    } => {
+                        self.note = Some(note);
+                        self.frequency = util::midi_note_to_freq(note);
+                        self.target_gain = velocity;
+
+                        if editor_open {
+                            note_buffer.enqueue_note(timing, note, velocity, true);
+                        }
+                    }
+                    NoteEvent::NoteOff {
+                        timing,
+                        note,
+                        velocity,
+                        ..
+                    } => {
+                        if self.note == Some(note) {
+                            self.note = None;
+                            self.target_gain = 0.0;
+                        }
+
+                        if editor_open {
+                            note_buffer.enqueue_note(timing, note, velocity, false);
+                        }
+                    }
+                    _ => (),
+                }
+
+                next_event = context.next_event();
+            }
+
+            // Smooth out the gain to avoid clicks
+            self.gain += (self.target_gain - self.gain) * 0.002;
+
+            let sample = (self.phase * TAU).sin() * self.gain * 0.5;
+            self.phase = (self.phase + self.frequency / self.sample_rate).fract();
+
+            for output in channel_samples {
+                *output = sample;
+            }
+        }
+
+        // Append to the visualizers' respective buffers, only if the editor is currently open.
+        if editor_open {
+            note_buffer.advance(buffer.samples());
+            self.peak_buffer
+                .lock()
+                .unwrap()
+                .enqueue_buffer(buffer, None);
+        }
+
+        ProcessStatus::KeepAlive
+    }
+}
+
+impl ClapPlugin for ActivityStripPlugin {
+    const CLAP_ID: &'static str = "org.cyma.activity_strip";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("A sine synth with a note activity strip built using Cyma");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::Instrument,
+        ClapFeature::Synthesizer,
+        ClapFeature::Mono,
+    ];
+}
+
+impl Vst3Plugin for ActivityStripPlugin {
+    const VST3_CLASS_ID: [u8; 16] = *b"CYMA00ACTIVSTRIP";
+
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Instrument, Vst3SubCategory::Synth];
+}
+
+nih_export_clap!(ActivityStripPlugin);
+nih_export_vst3!(ActivityStripPlugin);
@@ -0,0 +1,6 @@
+use activity_strip::ActivityStripPlugin;
+use nih_plug::prelude::*;
+
+fn main() {
+    nih_export_standalone::<ActivityStripPlugin>();
+}
@@ -0,0 +1,79 @@
+use cyma::prelude::*;
+use nih_plug::editor::Editor;
+use nih_plug_vizia::{assets, create_vizia_editor, vizia::prelude::*, ViziaState, ViziaTheming};
+use std::sync::{Arc, Mutex};
+
+#[derive(Lens, Clone)]
+pub(crate) struct Data {
+    waveform_buffer: Arc<Mutex<WaveformBuffer>>,
+}
+
+impl Data {
+    pub(crate) fn new(waveform_buffer: Arc<Mutex<WaveformBuffer>>) -> Self {
+        Self { waveform_buffer }
+    }
+}
+
+enum DataEvent {
+    Rearm,
+}
+
+impl Model for Data {
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|data_event, _| match data_event {
+            DataEvent::Rearm => crate::arm(&mut self.waveform_buffer.lock().unwrap()),
+        });
+    }
+}
+
+pub(crate) fn default_state() -> Arc<ViziaState> {
+    ViziaState::new(|| (800, 500))
+}
+
+pub(crate) fn create(editor_data: Data, editor_state: Arc<ViziaState>) -> Option<Box<dyn Editor>> {
+    create_vizia_editor(editor_state, ViziaTheming::default(), move |cx, _| {
+        assets::register_noto_sans_light(cx);
+        editor_data.clone().build(cx);
+
+        VStack::new(cx, |cx| {
+            HStack::new(cx, |cx| {
+                ZStack::new(cx, |cx| {
+                    Grid::new(
+                        cx,
+                        ValueScaling::Linear,
+                        (-1.2, 1.2),
+                        vec![1.0, 0.5, 0.0, -0.5, -1.0],
+                        Orientation::Horizontal,
+                    )
+                    .color(Color::rgb(60, 60, 60));
+
+                    Oscilloscope::new(cx, Data::waveform_buffer, (-1.2, 1.2), ValueScaling::Linear)
+                        .color(Color::rgba(255, 255, 255, 160))
+                        .background_color(Color::rgba(255, 255, 255, 60));
+                })
+                .background_color(Color::rgb(16, 16, 16));
+
+                UnitRuler::new(
+                    cx,
+                    (-1.2, 1.2),
+                    ValueScaling::Linear,
+                    vec![(1.0, "0db"), (0.5, "-6db"), (-0.5, "-6db"), (-1.0, "0db")],
+                    Orientation::Vertical,
+                )
+                .font_size(12.)
+                .color(Color::rgb(160, 160, 160))
+                .width(Pixels(48.));
+            })
+            .col_between(Pixels(8.));
+
+            Button::new(
+                cx,
+                |cx| cx.emit(DataEvent::Rearm),
+                |cx| Label::new(cx, "Capture the next clip"),
+            )
+            .height(Pixels(32.));
+        })
+        .row_between(Pixels(8.))
+        .background_color(Color::rgb(0, 0, 0));
+    })
+}
@@ -0,0 +1,139 @@
+use cyma::prelude::*;
+use nih_plug::prelude::*;
+use nih_plug_vizia::ViziaState;
+use std::sync::{Arc, Mutex};
+
+mod editor;
+
+// The length of each take, in seconds
+const TAKE: f32 = 0.5;
+// Samples at or above 0 dBFS count as a clip
+const CLIP_THRESHOLD: f32 = 1.0;
+// Half of the take precedes the clip
+const PRE_TRIGGER: f32 = 0.5;
+
+pub struct ClipCapturePlugin {
+    params: Arc<DemoParams>,
+    waveform_buffer: Arc<Mutex<WaveformBuffer>>,
+}
+
+#[derive(Params)]
+struct DemoParams {
+    #[persist = "editor-state"]
+    editor_state: Arc<ViziaState>,
+}
+
+impl Default for ClipCapturePlugin {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(DemoParams::default()),
+            waveform_buffer: Arc::new(Mutex::new(
+                WaveformBuffer::new(800, TAKE).with_mode(BufferMode::Capture),
+            )),
+        }
+    }
+}
+
+impl Default for DemoParams {
+    fn default() -> Self {
+        Self {
+            editor_state: editor::default_state(),
+        }
+    }
+}
+
+/// Waits for the next clip, discarding the current take.
+pub(crate) fn arm(buffer: &mut WaveformBuffer) {
+    buffer.arm_on_threshold(CLIP_THRESHOLD, PRE_TRIGGER);
+}
+
+impl Plugin for ClipCapturePlugin {
+    const NAME: &'static str = "CymaClipCapture";
+    const VENDOR: &'static str = "223230";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "223230@pm.me";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
+        main_input_channels: NonZeroU32::new(2),
+        main_output_channels: NonZeroU32::new(2),
+
+        aux_input_ports: &[],
+        aux_output_ports: &[],
+
+        names: PortNames::const_default(),
+    }];
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::None;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::None;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(
+            editor::Data::new(self.waveform_buffer.clone()),
+            self.params.editor_state.clone(),
+        )
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        match self.waveform_buffer.lock() {
+            Ok(mut buffer) => {
+                buffer.set_sample_rate(buffer_config.sample_rate);
+                arm(&mut buffer);
+            }
+            Err(_) => return false,
+        }
+
+        true
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut nih_plug::buffer::Buffer,
+        _: &mut AuxiliaryBuffers,
+        _: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        // Unlike the other examples, the buffer is fed while the editor is closed as
+        // well, so that no clip is missed. Once the take is complete, enqueueing does
+        // nothing until the buffer is armed again.
+        self.waveform_buffer
+            .lock()
+            .unwrap()
+            .enqueue_buffer(buffer, None);
+        ProcessStatus::Normal
+    }
+}
+
+impl ClapPlugin for ClipCapturePlugin {
+    const CLAP_ID: &'static str = "org.cyma.clip_capture";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some(
+        "A scope that captures the half second around the first clip it sees, built using Cyma",
+    );
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::AudioEffect, ClapFeature::Analyzer];
+}
+
+impl Vst3Plugin for ClipCapturePlugin {
+    const VST3_CLASS_ID: [u8; 16] = *b"CYMA0CLIPCAPTURE";
+
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] = &[Vst3SubCategory::Analyzer];
+}
+
+nih_export_clap!(ClipCapturePlugin);
+nih_export_vst3!(ClipCapturePlugin);
@@ -0,0 +1,6 @@
+use clip_capture::ClipCapturePlugin;
+use nih_plug::prelude::*;
+
+fn main() {
+    nih_export_standalone::<ClipCapturePlugin>();
+}
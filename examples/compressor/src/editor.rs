@@ -0,0 +1,159 @@
+use cyma::prelude::*;
+use nih_plug::editor::Editor;
+use nih_plug_vizia::{assets, create_vizia_editor, vizia::prelude::*, ViziaState, ViziaTheming};
+use std::sync::{Arc, Mutex};
+
+#[derive(Lens, Clone)]
+pub(crate) struct Data {
+    // Per-frame copies of the plug-in's buffers, so that all graphs show the same instant
+    snapshots: SnapshotSet,
+    input_buffer: Arc<Mutex<PeakBuffer>>,
+    output_buffer: Arc<Mutex<PeakBuffer>>,
+    // The deepest gain reduction of each element, in positive decibels
+    reduction_buffer: Arc<Mutex<DerivedBuffer<MinimaBuffer, PeakBuffer>>>,
+}
+
+impl Data {
+    pub(crate) fn new(
+        input_buffer: Arc<Mutex<PeakBuffer>>,
+        output_buffer: Arc<Mutex<PeakBuffer>>,
+        gain_buffer: Arc<Mutex<MinimaBuffer>>,
+    ) -> Self {
+        let snapshots = SnapshotSet::new();
+        let input_buffer = snapshots.add(input_buffer);
+        let output_buffer = snapshots.add(output_buffer);
+        let gain_buffer = snapshots.add(gain_buffer);
+        // A derived buffer always reads two buffers - only the gain is used, and
+        // negated so that the reduction grows downwards from 0 dB
+        let reduction_buffer = Arc::new(Mutex::new(DerivedBuffer::decibels(
+            gain_buffer,
+            output_buffer.clone(),
+            |gain, _| -gain,
+        )));
+        Self {
+            snapshots,
+            input_buffer,
+            output_buffer,
+            reduction_buffer,
+        }
+    }
+}
+
+impl Model for Data {}
+
+pub(crate) fn default_state() -> Arc<ViziaState> {
+    ViziaState::new(|| (800, 600))
+}
+
+/// The settings of the buffers, and the grid and ruler of the levels, derived from
+/// the width of the graphs. The input, output and gain buffers share them, so that
+/// their elements line up.
+pub(crate) fn visualizer_config() -> VisualizerConfig {
+    VisualizerConfig::new(744., 10.0)
+}
+
+pub(crate) fn create(editor_data: Data, editor_state: Arc<ViziaState>) -> Option<Box<dyn Editor>> {
+    create_vizia_editor(editor_state, ViziaTheming::default(), move |cx, _| {
+        assets::register_noto_sans_light(cx);
+        editor_data.snapshots.capture_every_frame(cx);
+        editor_data.clone().build(cx);
+        let config = visualizer_config();
+
+        VStack::new(cx, |cx| {
+            HStack::new(cx, |cx| {
+                ZStack::new(cx, |cx| {
+                    Grid::new(
+                        cx,
+                        ValueScaling::Linear,
+                        config.range(),
+                        config.grid_marks(),
+                        Orientation::Horizontal,
+                    )
+                    .color(Color::rgb(60, 60, 60));
+
+                    Graph::new(
+                        cx,
+                        Data::input_buffer,
+                        config.range(),
+                        ValueScaling::Decibels,
+                    )
+                    .color(Color::rgba(255, 255, 255, 60))
+                    .background_color(Color::rgba(255, 255, 255, 30));
+                    Graph::new(
+                        cx,
+                        Data::output_buffer,
+                        config.range(),
+                        ValueScaling::Decibels,
+                    )
+                    .color(Color::rgba(255, 255, 255, 160))
+                    .background_color(Color::rgba(255, 255, 255, 60));
+                })
+                .background_color(Color::rgb(16, 16, 16));
+
+                UnitRuler::new(
+                    cx,
+                    config.range(),
+                    ValueScaling::Linear,
+                    config.ruler_marks(),
+                    Orientation::Vertical,
+                )
+                .font_size(12.)
+                .color(Color::rgb(160, 160, 160))
+                .width(Pixels(48.));
+            })
+            .col_between(Pixels(8.));
+
+            // The gain reduction hangs down from the top, in front of the output peaks
+            // rising from the bottom. It's already in decibels, so it's scaled linearly,
+            // and the grid and ruler follow its direction.
+            HStack::new(cx, |cx| {
+                ZStack::new(cx, |cx| {
+                    Grid::new(
+                        cx,
+                        ValueScaling::Linear,
+                        (0., 24.),
+                        vec![6.0, 12.0, 18.0],
+                        Orientation::Horizontal,
+                    )
+                    .direction(GraphDirection::TopDown)
+                    .color(Color::rgb(60, 60, 60));
+
+                    Graph::new(
+                        cx,
+                        Data::output_buffer,
+                        config.range(),
+                        ValueScaling::Decibels,
+                    )
+                    .color(Color::rgba(255, 255, 255, 60))
+                    .background_color(Color::rgba(255, 255, 255, 30));
+                    Graph::new(
+                        cx,
+                        Data::reduction_buffer,
+                        (0.0, 24.0),
+                        ValueScaling::Linear,
+                    )
+                    .direction(GraphDirection::TopDown)
+                    .color(Color::rgba(255, 160, 60, 200))
+                    .background_color(Color::rgba(255, 160, 60, 60));
+                })
+                .background_color(Color::rgb(16, 16, 16));
+
+                UnitRuler::new(
+                    cx,
+                    (0.0, 24.0),
+                    ValueScaling::Linear,
+                    vec![(6.0, "6db"), (12.0, "12db"), (18.0, "18db")],
+                    Orientation::Vertical,
+                )
+                .direction(GraphDirection::TopDown)
+                .font_size(12.)
+                .color(Color::rgb(160, 160, 160))
+                .width(Pixels(48.));
+            })
+            .col_between(Pixels(8.))
+            .height(Pixels(160.));
+        })
+        .row_between(Pixels(8.))
+        .background_color(Color::rgb(0, 0, 0));
+    })
+}
@@ -0,0 +1,198 @@
+use cyma::prelude::*;
+use nih_plug::prelude::*;
+use nih_plug_vizia::ViziaState;
+use std::sync::{Arc, Mutex};
+
+mod editor;
+
+const THRESHOLD: f32 = -18.0;
+const RATIO: f32 = 4.0;
+const ATTACK: f32 = 10.0;
+const RELEASE: f32 = 150.0;
+
+pub struct CompressorPlugin {
+    params: Arc<DemoParams>,
+    // The peaks before and after compressing
+    input_buffer: Arc<Mutex<PeakBuffer>>,
+    output_buffer: Arc<Mutex<PeakBuffer>>,
+    // The gain that is applied; Its minima are the deepest gain reduction
+    gain_buffer: Arc<Mutex<MinimaBuffer>>,
+
+    attack_weight: f32,
+    release_weight: f32,
+    // The current gain reduction, in decibels
+    reduction: f32,
+}
+
+#[derive(Params)]
+struct DemoParams {
+    #[persist = "editor-state"]
+    editor_state: Arc<ViziaState>,
+}
+
+impl Default for CompressorPlugin {
+    fn default() -> Self {
+        let config = editor::visualizer_config();
+        Self {
+            params: Arc::new(DemoParams::default()),
+            input_buffer: Arc::new(Mutex::new(config.peak_buffer())),
+            output_buffer: Arc::new(Mutex::new(config.peak_buffer())),
+            gain_buffer: Arc::new(Mutex::new(config.minima_buffer())),
+
+            attack_weight: 0.0,
+            release_weight: 0.0,
+            reduction: 0.0,
+        }
+    }
+}
+
+impl Default for DemoParams {
+    fn default() -> Self {
+        Self {
+            editor_state: editor::default_state(),
+        }
+    }
+}
+
+impl Plugin for CompressorPlugin {
+    const NAME: &'static str = "CymaCompressor";
+    const VENDOR: &'static str = "223230";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "223230@pm.me";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
+        main_input_channels: NonZeroU32::new(2),
+        main_output_channels: NonZeroU32::new(2),
+
+        aux_input_ports: &[],
+        aux_output_ports: &[],
+
+        names: PortNames::const_default(),
+    }];
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::None;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::None;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(
+            editor::Data::new(
+                self.input_buffer.clone(),
+                self.output_buffer.clone(),
+                self.gain_buffer.clone(),
+            ),
+            self.params.editor_state.clone(),
+        )
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        let sample_rate = buffer_config.sample_rate;
+        self.attack_weight = (-1.0 / (ATTACK / 1000.0 * sample_rate)).exp();
+        self.release_weight = (-1.0 / (RELEASE / 1000.0 * sample_rate)).exp();
+
+        for buffer in [&self.input_buffer, &self.output_buffer] {
+            match buffer.lock() {
+                Ok(mut buffer) => {
+                    buffer.set_sample_rate(sample_rate);
+                }
+                Err(_) => return false,
+            }
+        }
+        match self.gain_buffer.lock() {
+            Ok(mut buffer) => buffer.set_sample_rate(sample_rate),
+            Err(_) => return false,
+        }
+
+        true
+    }
+
+    fn reset(&mut self) {
+        self.reduction = 0.0;
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut nih_plug::buffer::Buffer,
+        _: &mut AuxiliaryBuffers,
+        _: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        let editor_open = self.params.editor_state.is_open();
+
+        if editor_open {
+            self.input_buffer
+                .lock()
+                .unwrap()
+                .enqueue_buffer(buffer, None);
+        }
+
+        let mut gain_buffer = self.gain_buffer.lock().unwrap();
+        for mut channel_samples in buffer.iter_samples() {
+            let level = channel_samples
+                .iter_mut()
+                .fold(0.0f32, |level, sample| level.max(sample.abs()));
+
+            // The gain reduction that the level calls for, smoothed by the attack and release
+            let overshoot = (util::gain_to_db(level) - THRESHOLD).max(0.0);
+            let target = overshoot * (1.0 - RATIO.recip());
+            let weight = if target > self.reduction {
+                self.attack_weight
+            } else {
+                self.release_weight
+            };
+            self.reduction = target + (self.reduction - target) * weight;
+
+            let gain = util::db_to_gain(-self.reduction);
+            if editor_open {
+                gain_buffer.enqueue(gain);
+            }
+            for sample in channel_samples {
+                *sample *= gain;
+            }
+        }
+
+        // Append to the visualizers' respective buffers, only if the editor is currently open.
+        if editor_open {
+            self.output_buffer
+                .lock()
+                .unwrap()
+                .enqueue_buffer(buffer, None);
+        }
+
+        ProcessStatus::Normal
+    }
+}
+
+impl ClapPlugin for CompressorPlugin {
+    const CLAP_ID: &'static str = "org.cyma.compressor";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("A compressor with input, output and gain reduction graphs built using Cyma");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::AudioEffect, ClapFeature::Compressor];
+}
+
+impl Vst3Plugin for CompressorPlugin {
+    const VST3_CLASS_ID: [u8; 16] = *b"CYMA0COMPRESSOR0";
+
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Fx, Vst3SubCategory::Dynamics];
+}
+
+nih_export_clap!(CompressorPlugin);
+nih_export_vst3!(CompressorPlugin);
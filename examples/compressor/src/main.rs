@@ -0,0 +1,6 @@
+use compressor::CompressorPlugin;
+use nih_plug::prelude::*;
+
+fn main() {
+    nih_export_standalone::<CompressorPlugin>();
+}
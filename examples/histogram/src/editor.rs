@@ -1,8 +1,4 @@
 use cyma::prelude::*;
-use cyma::{
-    utils::HistogramBuffer,
-    visualizers::{Grid, Histogram, UnitRuler},
-};
 use nih_plug::editor::Editor;
 use nih_plug_vizia::{assets, create_vizia_editor, vizia::prelude::*, ViziaState, ViziaTheming};
 use std::sync::{Arc, Mutex};
@@ -29,36 +25,35 @@ pub(crate) fn create(editor_data: Data, editor_state: Arc<ViziaState>) -> Option
         assets::register_noto_sans_light(cx);
         editor_data.clone().build(cx);
 
+        // Scroll over the histogram to zoom in, and drag it to move the range; The
+        // grid and the ruler follow along
+        let range = SharedRange::new((-32., 8.));
+
         HStack::new(cx, |cx| {
             ZStack::new(cx, |cx| {
                 Grid::new(
                     cx,
                     ValueScaling::Linear,
                     (-32., 8.),
-                    vec![6.0, 0.0, -6.0, -12.0, -18.0, -24.0, -30.0],
+                    Grid::marks_from_anchor(0.0, 6.0, (-96., 24.)),
                     Orientation::Horizontal,
                 )
+                .follow_shared_range(range.clone())
                 .color(Color::rgb(60, 60, 60));
 
                 Histogram::new(cx, Data::histogram_buffer, (-32.0, 8.0))
+                    .zoomable((-96., 24.))
+                    .shared_range(range.clone())
                     .color(Color::rgba(255, 255, 255, 160))
                     .background_color(Color::rgba(255, 255, 255, 60));
             })
             .background_color(Color::rgb(16, 16, 16));
 
-            UnitRuler::new(
+            UnitRuler::follow_shared(
                 cx,
-                (-32.0, 8.0),
+                range,
                 ValueScaling::Linear,
-                vec![
-                    (6.0, "6db"),
-                    (0.0, "0db"),
-                    (-6.0, "-6db"),
-                    (-12.0, "-12db"),
-                    (-18.0, "-18db"),
-                    (-24.0, "-24db"),
-                    (-30.0, "-30db"),
-                ],
+                UnitRuler::marks_from_anchor(0.0, 6.0, (-96., 24.), |db| format!("{db}db")),
                 Orientation::Vertical,
             )
             .font_size(12.)
@@ -1,5 +1,4 @@
 use cyma::prelude::*;
-use cyma::utils::HistogramBuffer;
 use nih_plug::prelude::*;
 use nih_plug_vizia::ViziaState;
 use std::sync::{Arc, Mutex};
@@ -0,0 +1,105 @@
+use cyma::prelude::*;
+use cyma::utils::{instances, Subscription};
+use nih_plug::editor::Editor;
+use nih_plug_vizia::{assets, create_vizia_editor, vizia::prelude::*, ViziaState, ViziaTheming};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The level of one instance, as shown on the bridge.
+#[derive(Lens, Data, Clone, PartialEq)]
+pub(crate) struct Source {
+    name: String,
+    // The newest peak, normalized to the meter's range
+    level: f32,
+}
+
+#[derive(Lens)]
+pub(crate) struct Bridge {
+    sources: Vec<Source>,
+    #[lens(ignore)]
+    subscription: Subscription,
+}
+
+enum BridgeEvent {
+    Poll,
+}
+
+impl Model for Bridge {
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|bridge_event, _| match bridge_event {
+            BridgeEvent::Poll => {
+                // Only does anything every 50 ms, since exporting copies the buffers
+                if self.subscription.poll() {
+                    self.sources = self
+                        .subscription
+                        .sources()
+                        .iter()
+                        .map(|source| Source {
+                            name: source
+                                .name
+                                .trim_start_matches("track-peaks-")
+                                .chars()
+                                .take(6)
+                                .collect(),
+                            level: source
+                                .snapshot
+                                .values
+                                .last()
+                                .and_then(|value| value.first())
+                                .map_or(0., |peak| {
+                                    ValueScaling::Decibels.value_to_normalized(*peak, -60., 6.)
+                                }),
+                        })
+                        .collect();
+                }
+            }
+        });
+    }
+}
+
+pub(crate) fn default_state() -> Arc<ViziaState> {
+    ViziaState::new(|| (480, 320))
+}
+
+pub(crate) fn create(editor_state: Arc<ViziaState>) -> Option<Box<dyn Editor>> {
+    create_vizia_editor(editor_state, ViziaTheming::default(), move |cx, _| {
+        assets::register_noto_sans_light(cx);
+        Bridge {
+            sources: Vec::new(),
+            subscription: instances().subscribe("track-peaks-*"),
+        }
+        .build(cx);
+
+        let bridge = cx.current();
+        let timer = cx.add_timer(Duration::from_millis(16), None, move |cx, action| {
+            if let TimerAction::Tick(_) = action {
+                cx.emit_to(bridge, BridgeEvent::Poll);
+            }
+        });
+        cx.start_timer(timer);
+
+        // One meter per instance, in the order they were loaded
+        List::new(cx, Bridge::sources, |cx, _, source| {
+            VStack::new(cx, |cx| {
+                ZStack::new(cx, |cx| {
+                    Element::new(cx)
+                        .top(Stretch(1.))
+                        .height(source.map(|source| Percentage(source.level * 100.)))
+                        .background_color(Color::rgba(255, 255, 255, 160));
+                })
+                .width(Pixels(24.))
+                .height(Stretch(1.))
+                .background_color(Color::rgb(16, 16, 16));
+
+                Label::new(cx, source.map(|source| source.name.clone()))
+                    .font_size(12.)
+                    .color(Color::rgb(160, 160, 160));
+            })
+            .width(Auto)
+            .row_between(Pixels(4.));
+        })
+        .layout_type(LayoutType::Row)
+        .col_between(Pixels(8.))
+        .space(Pixels(16.));
+    })
+}
@@ -0,0 +1,136 @@
+use cyma::prelude::*;
+use cyma::utils::{instances, Publication};
+use nih_plug::prelude::*;
+use nih_plug_vizia::ViziaState;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+
+mod editor;
+
+pub struct MeterBridgePlugin {
+    params: Arc<DemoParams>,
+    peak_buffer: Arc<Mutex<PeakBuffer>>,
+    staged_peaks: Option<StagedBuffer<PeakBuffer>>,
+    // Unpublishes the peak buffer once the instance is removed
+    publication: Option<Publication>,
+    id: u64,
+}
+
+#[derive(Params)]
+struct DemoParams {
+    #[persist = "editor-state"]
+    editor_state: Arc<ViziaState>,
+}
+
+impl Default for MeterBridgePlugin {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(DemoParams::default()),
+            peak_buffer: Arc::new(Mutex::new(PeakBuffer::new(32, 0.1, 300.0))),
+            staged_peaks: None,
+            publication: None,
+            // Tells apart the instances, which can't see each other's names
+            id: RandomState::new().build_hasher().finish(),
+        }
+    }
+}
+
+impl Default for DemoParams {
+    fn default() -> Self {
+        Self {
+            editor_state: editor::default_state(),
+        }
+    }
+}
+
+impl Plugin for MeterBridgePlugin {
+    const NAME: &'static str = "CymaMeterBridge";
+    const VENDOR: &'static str = "223230";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "223230@pm.me";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
+        main_input_channels: NonZeroU32::new(2),
+        main_output_channels: NonZeroU32::new(2),
+
+        aux_input_ports: &[],
+        aux_output_ports: &[],
+
+        names: PortNames::const_default(),
+    }];
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::None;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::None;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.editor_state.clone())
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        match self.peak_buffer.lock() {
+            Ok(mut buffer) => buffer.set_sample_rate(buffer_config.sample_rate),
+            Err(_) => return false,
+        }
+        self.staged_peaks = Some(StagedBuffer::new(
+            self.peak_buffer.clone(),
+            buffer_config.max_buffer_size as usize,
+        ));
+
+        if self.publication.is_none() {
+            self.publication = Some(
+                instances().publish_as(format!("track-peaks-{:016x}", self.id), &self.peak_buffer),
+            );
+        }
+
+        true
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut nih_plug::buffer::Buffer,
+        _: &mut AuxiliaryBuffers,
+        _: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        // Always enqueue, since other instances' editors may be showing this one
+        if let Some(staged_peaks) = &mut self.staged_peaks {
+            staged_peaks.begin_block().enqueue_buffer(buffer, None);
+        }
+        ProcessStatus::Normal
+    }
+}
+
+impl ClapPlugin for MeterBridgePlugin {
+    const CLAP_ID: &'static str = "org.cyma.meter_bridge";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("A bridge of meters across plug-in instances built using Cyma");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::AudioEffect, ClapFeature::Analyzer];
+}
+
+impl Vst3Plugin for MeterBridgePlugin {
+    const VST3_CLASS_ID: [u8; 16] = *b"CYMA00METERBRDGE";
+
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] = &[Vst3SubCategory::Analyzer];
+}
+
+nih_export_clap!(MeterBridgePlugin);
+nih_export_vst3!(MeterBridgePlugin);
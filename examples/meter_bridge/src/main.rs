@@ -0,0 +1,6 @@
+use meter_bridge::MeterBridgePlugin;
+use nih_plug::prelude::*;
+
+fn main() {
+    nih_export_standalone::<MeterBridgePlugin>();
+}
@@ -1,12 +1,14 @@
 use cyma::prelude::*;
-use cyma::{
-    utils::PeakBuffer,
-    visualizers::{Graph, Grid, UnitRuler},
-};
-use nih_plug::editor::Editor;
+use nih_plug::{editor::Editor, nih_error, nih_log};
 use nih_plug_vizia::{assets, create_vizia_editor, vizia::prelude::*, ViziaState, ViziaTheming};
+use std::fs::File;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 
+// How often the update counter was rebuilt, which stops growing while no audio is
+// coming in
+static UPDATES: AtomicU64 = AtomicU64::new(0);
+
 #[derive(Lens, Clone)]
 pub(crate) struct Data {
     peak_buffer: Arc<Mutex<PeakBuffer>>,
@@ -18,47 +20,103 @@ impl Data {
     }
 }
 
-impl Model for Data {}
+impl Model for Data {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, _| match window_event {
+            // Debug shortcut: Ctrl+Shift+E writes the peak buffer's contents to a CSV file
+            WindowEvent::KeyDown(Code::KeyE, _)
+                if cx.modifiers().contains(Modifiers::CTRL | Modifiers::SHIFT) =>
+            {
+                self.export_peak_buffer()
+            }
+            _ => {}
+        });
+    }
+}
+
+impl Data {
+    fn export_peak_buffer(&self) {
+        // The buffer is only locked while it is being copied, not while the file is written
+        let snapshot = self.peak_buffer.export();
+
+        // Written next to the binary that is running - for the standalone, that's the
+        // plug-in itself
+        let path = match std::env::current_exe() {
+            Ok(exe) => exe.with_file_name("peak_buffer.csv"),
+            Err(_) => std::env::temp_dir().join("peak_buffer.csv"),
+        };
+
+        match File::create(&path).and_then(|file| snapshot.to_csv(file)) {
+            Ok(()) => nih_log!("Exported the peak buffer to {}", path.display()),
+            Err(err) => nih_error!("Could not export the peak buffer: {}", err),
+        }
+    }
+}
 
 pub(crate) fn default_state() -> Arc<ViziaState> {
     ViziaState::new(|| (800, 500))
 }
 
+/// The settings of the peak buffer, the grid and the ruler, derived from the width of
+/// the graph.
+pub(crate) fn visualizer_config() -> VisualizerConfig {
+    VisualizerConfig::new(744., 10.0).with_ui_scale(1.0, 2.0)
+}
+
 pub(crate) fn create(editor_data: Data, editor_state: Arc<ViziaState>) -> Option<Box<dyn Editor>> {
     create_vizia_editor(editor_state, ViziaTheming::default(), move |cx, _| {
         assets::register_noto_sans_light(cx);
         editor_data.clone().build(cx);
+        let config = visualizer_config();
 
         HStack::new(cx, |cx| {
             ZStack::new(cx, |cx| {
                 Grid::new(
                     cx,
                     ValueScaling::Linear,
-                    (-32., 8.),
-                    vec![6.0, 0.0, -6.0, -12.0, -18.0, -24.0, -30.0],
+                    config.range(),
+                    config.grid_marks(),
                     Orientation::Horizontal,
                 )
                 .color(Color::rgb(60, 60, 60));
 
-                Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
-                    .color(Color::rgba(255, 255, 255, 160))
-                    .background_color(Color::rgba(255, 255, 255, 60));
+                // A line every second, which scrolls along with the graph
+                Grid::new(
+                    cx,
+                    ValueScaling::Linear,
+                    (0., 1.),
+                    vec![],
+                    Orientation::Vertical,
+                )
+                .scroll_with(Data::peak_buffer, 1.0)
+                .color(Color::rgb(40, 40, 40));
+
+                Graph::new(
+                    cx,
+                    Data::peak_buffer,
+                    config.range(),
+                    ValueScaling::Decibels,
+                )
+                .interactive(true)
+                .color(Color::rgba(255, 255, 255, 160))
+                .background_color(Color::rgba(255, 255, 255, 60));
+
+                // Only rebuilt when the peak buffer was written to
+                Binding::new(cx, Data::peak_buffer.revision(), |cx, _| {
+                    let updates = UPDATES.fetch_add(1, Ordering::Relaxed) + 1;
+                    Label::new(cx, &format!("{} updates", updates))
+                        .font_size(12.)
+                        .color(Color::rgb(100, 100, 100))
+                        .space(Pixels(8.));
+                });
             })
             .background_color(Color::rgb(16, 16, 16));
 
             UnitRuler::new(
                 cx,
-                (-32.0, 8.0),
+                config.range(),
                 ValueScaling::Linear,
-                vec![
-                    (6.0, "6db"),
-                    (0.0, "0db"),
-                    (-6.0, "-6db"),
-                    (-12.0, "-12db"),
-                    (-18.0, "-18db"),
-                    (-24.0, "-24db"),
-                    (-30.0, "-30db"),
-                ],
+                config.ruler_marks(),
                 Orientation::Vertical,
             )
             .font_size(12.)
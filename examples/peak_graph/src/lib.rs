@@ -1,5 +1,4 @@
 use cyma::prelude::*;
-use cyma::utils::PeakBuffer;
 use nih_plug::prelude::*;
 use nih_plug_vizia::ViziaState;
 use std::sync::{Arc, Mutex};
@@ -21,7 +20,7 @@ impl Default for PeakGraphPlugin {
     fn default() -> Self {
         Self {
             params: Arc::new(DemoParams::default()),
-            peak_buffer: Arc::new(Mutex::new(PeakBuffer::new(800, 10.0, 50.0))),
+            peak_buffer: Arc::new(Mutex::new(editor::visualizer_config().peak_buffer())),
         }
     }
 }
@@ -79,6 +78,7 @@ impl Plugin for PeakGraphPlugin {
         match self.peak_buffer.lock() {
             Ok(mut buffer) => {
                 buffer.set_sample_rate(buffer_config.sample_rate);
+                buffer.set_offline(buffer_config.process_mode == ProcessMode::Offline);
             }
             Err(_) => return false,
         }
@@ -0,0 +1,75 @@
+use cyma::prelude::*;
+use nih_plug::editor::Editor;
+use nih_plug_vizia::{assets, create_vizia_editor, vizia::prelude::*, ViziaState, ViziaTheming};
+use std::sync::{Arc, Mutex};
+
+#[derive(Lens, Clone)]
+pub(crate) struct Data {
+    peak_buffer: Arc<Mutex<PeakBuffer>>,
+    pitch_buffer: Arc<Mutex<RingBuffer<f32>>>,
+}
+
+impl Data {
+    pub(crate) fn new(
+        peak_buffer: Arc<Mutex<PeakBuffer>>,
+        pitch_buffer: Arc<Mutex<RingBuffer<f32>>>,
+    ) -> Self {
+        Self {
+            peak_buffer,
+            pitch_buffer,
+        }
+    }
+}
+
+impl Model for Data {}
+
+pub(crate) fn default_state() -> Arc<ViziaState> {
+    ViziaState::new(|| (800, 500))
+}
+
+pub(crate) fn create(editor_data: Data, editor_state: Arc<ViziaState>) -> Option<Box<dyn Editor>> {
+    create_vizia_editor(editor_state, ViziaTheming::default(), move |cx, _| {
+        assets::register_noto_sans_light(cx);
+        editor_data.clone().build(cx);
+
+        HStack::new(cx, |cx| {
+            ZStack::new(cx, |cx| {
+                Grid::new(
+                    cx,
+                    ValueScaling::Frequency,
+                    (50., 1000.),
+                    vec![100.0, 200.0, 500.0],
+                    Orientation::Horizontal,
+                )
+                .color(Color::rgb(60, 60, 60));
+
+                Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+                    .color(Color::rgba(255, 255, 255, 60))
+                    .background_color(Color::rgba(255, 255, 255, 30));
+
+                // Windows without a pitch hold 0, which is drawn at the bottom
+                Graph::new(
+                    cx,
+                    Data::pitch_buffer,
+                    (50.0, 1000.0),
+                    ValueScaling::Frequency,
+                )
+                .color(Color::rgb(255, 160, 60));
+            })
+            .background_color(Color::rgb(16, 16, 16));
+
+            UnitRuler::new(
+                cx,
+                (50.0, 1000.0),
+                ValueScaling::Frequency,
+                vec![(100.0, "100hz"), (200.0, "200hz"), (500.0, "500hz")],
+                Orientation::Vertical,
+            )
+            .font_size(12.)
+            .color(Color::rgb(160, 160, 160))
+            .width(Pixels(48.));
+        })
+        .col_between(Pixels(8.))
+        .background_color(Color::rgb(0, 0, 0));
+    })
+}
@@ -0,0 +1,206 @@
+use cyma::prelude::*;
+use nih_plug::prelude::*;
+use nih_plug_vizia::ViziaState;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+mod editor;
+
+// The pitch is estimated over a window of samples, once per hop
+const WINDOW: usize = 2048;
+const HOP: usize = 1024;
+// The range of pitches that are detected
+const MIN_PITCH: f32 = 50.0;
+const MAX_PITCH: f32 = 1000.0;
+
+pub struct PitchTrackerPlugin {
+    params: Arc<DemoParams>,
+    // The tap attached to this buffer mirrors the samples to the analysis thread
+    peak_buffer: Arc<Mutex<PeakBuffer>>,
+    // Filled by the analysis thread, one pitch per hop
+    pitch_buffer: Arc<Mutex<RingBuffer<f32>>>,
+    sample_rate: Arc<AtomicU32>,
+}
+
+#[derive(Params)]
+struct DemoParams {
+    #[persist = "editor-state"]
+    editor_state: Arc<ViziaState>,
+}
+
+impl Default for PitchTrackerPlugin {
+    fn default() -> Self {
+        let peak_buffer = Arc::new(Mutex::new(PeakBuffer::new(800, 10.0, 50.0)));
+        let pitch_buffer = Arc::new(Mutex::new(RingBuffer::new(400)));
+        let sample_rate = Arc::new(AtomicU32::new(44100.0f32.to_bits()));
+
+        // Roughly a third of a second at 48 kHz, after which the oldest samples are dropped
+        let (tap, consumer) = BufferTap::new(16384, TapMode::Samples);
+        peak_buffer.lock().unwrap().set_tap(Some(tap));
+        {
+            let pitch_buffer = pitch_buffer.clone();
+            let sample_rate = sample_rate.clone();
+            std::thread::spawn(move || analyze(consumer, pitch_buffer, sample_rate));
+        }
+
+        Self {
+            params: Arc::new(DemoParams::default()),
+            peak_buffer,
+            pitch_buffer,
+            sample_rate,
+        }
+    }
+}
+
+impl Default for DemoParams {
+    fn default() -> Self {
+        Self {
+            editor_state: editor::default_state(),
+        }
+    }
+}
+
+/// Runs on the analysis thread until the tap is dropped along with the plug-in.
+fn analyze(
+    mut consumer: TapConsumer,
+    pitch_buffer: Arc<Mutex<RingBuffer<f32>>>,
+    sample_rate: Arc<AtomicU32>,
+) {
+    let mut window = Vec::with_capacity(WINDOW);
+    loop {
+        match consumer.recv_timeout(Duration::from_millis(100)) {
+            Ok(sample) => window.push(sample),
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        if window.len() == WINDOW {
+            let sample_rate = f32::from_bits(sample_rate.load(Ordering::Relaxed));
+            let pitch = estimate_pitch(&window, sample_rate).unwrap_or(0.0);
+            pitch_buffer.lock().unwrap().enqueue(pitch);
+
+            window.drain(..HOP);
+        }
+    }
+}
+
+/// Estimates the pitch of the window using autocorrelation, or returns `None` if the
+/// window isn't periodic enough.
+fn estimate_pitch(window: &[f32], sample_rate: f32) -> Option<f32> {
+    let min_lag = (sample_rate / MAX_PITCH) as usize;
+    let max_lag = ((sample_rate / MIN_PITCH) as usize).min(window.len() / 2);
+
+    let correlation =
+        |lag: usize| -> f32 { window.iter().zip(&window[lag..]).map(|(a, b)| a * b).sum() };
+
+    let energy = correlation(0);
+    if energy < 1e-4 {
+        return None;
+    }
+
+    let (lag, best) = (min_lag..max_lag)
+        .map(|lag| (lag, correlation(lag)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+
+    // Weakly correlated windows are noise rather than a pitch
+    if best / energy < 0.3 {
+        return None;
+    }
+    Some(sample_rate / lag as f32)
+}
+
+impl Plugin for PitchTrackerPlugin {
+    const NAME: &'static str = "CymaPitchTracker";
+    const VENDOR: &'static str = "223230";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "223230@pm.me";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
+        main_input_channels: NonZeroU32::new(2),
+        main_output_channels: NonZeroU32::new(2),
+
+        aux_input_ports: &[],
+        aux_output_ports: &[],
+
+        names: PortNames::const_default(),
+    }];
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::None;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::None;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(
+            editor::Data::new(self.peak_buffer.clone(), self.pitch_buffer.clone()),
+            self.params.editor_state.clone(),
+        )
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.sample_rate
+            .store(buffer_config.sample_rate.to_bits(), Ordering::Relaxed);
+
+        match self.peak_buffer.lock() {
+            Ok(mut buffer) => {
+                buffer.set_sample_rate(buffer_config.sample_rate);
+            }
+            Err(_) => return false,
+        }
+
+        true
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut nih_plug::buffer::Buffer,
+        _: &mut AuxiliaryBuffers,
+        _: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        // Append to the visualizers' respective buffers, only if the editor is currently open.
+        // The tap only mirrors what is enqueued, so the pitch is only analyzed while the
+        // editor is open as well.
+        if self.params.editor_state.is_open() {
+            self.peak_buffer
+                .lock()
+                .unwrap()
+                .enqueue_buffer(buffer, None);
+        }
+        ProcessStatus::Normal
+    }
+}
+
+impl ClapPlugin for PitchTrackerPlugin {
+    const CLAP_ID: &'static str = "org.cyma.pitch_tracker";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("A pitch tracker that analyzes its input on a background thread, built using Cyma");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+
+    const CLAP_FEATURES: &'static [ClapFeature] =
+        &[ClapFeature::AudioEffect, ClapFeature::Analyzer];
+}
+
+impl Vst3Plugin for PitchTrackerPlugin {
+    const VST3_CLASS_ID: [u8; 16] = *b"CYMA00PITCHTRACK";
+
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] = &[Vst3SubCategory::Analyzer];
+}
+
+nih_export_clap!(PitchTrackerPlugin);
+nih_export_vst3!(PitchTrackerPlugin);
@@ -0,0 +1,6 @@
+use nih_plug::prelude::*;
+use pitch_tracker::PitchTrackerPlugin;
+
+fn main() {
+    nih_export_standalone::<PitchTrackerPlugin>();
+}
@@ -0,0 +1,87 @@
+//! Replays a log that a `RecordingBuffer` recorded into a fresh buffer, and prints
+//! the buffer's contents as CSV, from the oldest to the newest element.
+//!
+//! ```
+//! cargo run -p replay_tool -- peak_buffer.cyrl peak 800 10 48000 50
+//! ```
+
+use cyma::utils::{replay, MinimaBuffer, PeakBuffer, ReplayLog, VisualizerBuffer, WaveformBuffer};
+use std::process::ExitCode;
+
+const USAGE: &str =
+    "usage: replay_tool <log> <peak|minima|waveform> <size> <duration> <sample rate> [decay]";
+
+fn main() -> ExitCode {
+    match run(&std::env::args().skip(1).collect::<Vec<_>>()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let [path, kind, size, duration, sample_rate, rest @ ..] = args else {
+        return Err(USAGE.to_string());
+    };
+    let size = parse::<usize>(size, "size")?;
+    let duration = parse::<f32>(duration, "duration")?;
+    let sample_rate = parse::<f32>(sample_rate, "sample rate")?;
+    let decay = match rest {
+        [] => 0.0,
+        [decay] => parse::<f32>(decay, "decay")?,
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let bytes = std::fs::read(path).map_err(|error| format!("{}: {}", path, error))?;
+    let log = ReplayLog::parse(&bytes).map_err(|error| format!("{}: {}", path, error))?;
+    if log.is_truncated() {
+        eprintln!(
+            "{}: the log is truncated, replaying its first {} calls",
+            path,
+            log.entries().len()
+        );
+    }
+
+    match kind.as_str() {
+        "peak" => {
+            let mut buffer =
+                PeakBuffer::try_new(size, duration, decay).map_err(|e| e.to_string())?;
+            buffer.set_sample_rate(sample_rate);
+            replay(&log, &mut buffer);
+            println!("index,value");
+            for i in 0..buffer.len() {
+                println!("{},{}", i, buffer[i]);
+            }
+        }
+        "minima" => {
+            let mut buffer =
+                MinimaBuffer::try_new(size, duration, decay).map_err(|e| e.to_string())?;
+            buffer.set_sample_rate(sample_rate);
+            replay(&log, &mut buffer);
+            println!("index,value");
+            for i in 0..buffer.len() {
+                println!("{},{}", i, buffer[i]);
+            }
+        }
+        "waveform" => {
+            let mut buffer = WaveformBuffer::try_new(size, duration).map_err(|e| e.to_string())?;
+            buffer.set_sample_rate(sample_rate);
+            replay(&log, &mut buffer);
+            println!("index,min,max");
+            for i in 0..buffer.len() {
+                let (min, max) = buffer[i];
+                println!("{},{},{}", i, min, max);
+            }
+        }
+        _ => return Err(format!("unknown buffer {:?}\n{}", kind, USAGE)),
+    }
+    Ok(())
+}
+
+fn parse<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid {}: {:?}\n{}", name, value, USAGE))
+}
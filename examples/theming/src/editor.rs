@@ -0,0 +1,154 @@
+use cyma::prelude::*;
+use cyma::theme;
+use nih_plug::editor::Editor;
+use nih_plug_vizia::{assets, create_vizia_editor, vizia::prelude::*, ViziaState, ViziaTheming};
+use std::sync::{Arc, Mutex};
+
+#[derive(Lens, Clone)]
+pub(crate) struct Data {
+    peak_buffer: Arc<Mutex<PeakBuffer>>,
+    oscilloscope_buffer: Arc<Mutex<WaveformBuffer>>,
+    // Whether the dark theme is shown, or the light one
+    dark: bool,
+}
+
+impl Data {
+    pub(crate) fn new(
+        peak_buffer: Arc<Mutex<PeakBuffer>>,
+        oscilloscope_buffer: Arc<Mutex<WaveformBuffer>>,
+    ) -> Self {
+        Self {
+            peak_buffer,
+            oscilloscope_buffer,
+            dark: true,
+        }
+    }
+}
+
+enum DataEvent {
+    ToggleTheme,
+}
+
+impl Model for Data {
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|data_event, _| match data_event {
+            DataEvent::ToggleTheme => self.dark = !self.dark,
+        });
+    }
+}
+
+pub(crate) fn default_state() -> Arc<ViziaState> {
+    ViziaState::new(|| (800, 560))
+}
+
+/// The settings of the peak buffer, the grids and the ruler, derived from the width
+/// of the peak graph. The marks every 12 dB are labeled, and the ones in between
+/// are drawn as a minor grid.
+pub(crate) fn peak_config() -> VisualizerConfig {
+    VisualizerConfig::new(712., 10.0).with_marks(12.)
+}
+
+/// The settings of the oscilloscope's buffer, which spans the editor's width.
+pub(crate) fn oscilloscope_config() -> VisualizerConfig {
+    VisualizerConfig::new(800., 5.0)
+}
+
+pub(crate) fn create(editor_data: Data, editor_state: Arc<ViziaState>) -> Option<Box<dyn Editor>> {
+    create_vizia_editor(editor_state, ViziaTheming::default(), move |cx, _| {
+        assets::register_noto_sans_light(cx);
+        editor_data.clone().build(cx);
+        let config = peak_config();
+
+        // Both themes are scoped to their class, so they can be loaded side by side
+        cx.add_stylesheet(theme::dark())
+            .expect("Failed to load the dark theme");
+        cx.add_stylesheet(theme::light())
+            .expect("Failed to load the light theme");
+
+        // None of the views below set a color, so that the theme styles all of them
+        VStack::new(cx, |cx| {
+            HStack::new(cx, |cx| {
+                ZStack::new(cx, |cx| {
+                    Grid::new(
+                        cx,
+                        ValueScaling::Linear,
+                        config.range(),
+                        config.minor_grid_marks(),
+                        Orientation::Horizontal,
+                    )
+                    .minor();
+                    Grid::new(
+                        cx,
+                        ValueScaling::Linear,
+                        config.range(),
+                        config.grid_marks(),
+                        Orientation::Horizontal,
+                    );
+
+                    Graph::new(
+                        cx,
+                        Data::peak_buffer,
+                        config.range(),
+                        ValueScaling::Decibels,
+                    );
+                });
+
+                UnitRuler::new(
+                    cx,
+                    config.range(),
+                    ValueScaling::Linear,
+                    config.ruler_marks(),
+                    Orientation::Vertical,
+                )
+                .width(Pixels(48.));
+
+                Meter::new(
+                    cx,
+                    Data::peak_buffer,
+                    config.range(),
+                    ValueScaling::Decibels,
+                    Orientation::Vertical,
+                )
+                .width(Pixels(24.));
+            })
+            .col_between(Pixels(8.));
+
+            ZStack::new(cx, |cx| {
+                Grid::new(
+                    cx,
+                    ValueScaling::Linear,
+                    (-1.2, 1.2),
+                    vec![0.5, -0.5],
+                    Orientation::Horizontal,
+                )
+                .minor();
+                Grid::new(
+                    cx,
+                    ValueScaling::Linear,
+                    (-1.2, 1.2),
+                    vec![0.0],
+                    Orientation::Horizontal,
+                );
+
+                Oscilloscope::new(
+                    cx,
+                    Data::oscilloscope_buffer,
+                    (-1.2, 1.2),
+                    ValueScaling::Linear,
+                );
+            })
+            .height(Pixels(160.));
+
+            Button::new(
+                cx,
+                |cx| cx.emit(DataEvent::ToggleTheme),
+                |cx| Label::new(cx, "Switch between the dark and the light theme"),
+            )
+            .height(Pixels(32.));
+        })
+        .child_space(Pixels(16.))
+        .row_between(Pixels(16.))
+        .toggle_class(theme::DARK_CLASS, Data::dark)
+        .toggle_class(theme::LIGHT_CLASS, Data::dark.map(|dark| !dark));
+    })
+}
@@ -0,0 +1,6 @@
+use nih_plug::prelude::*;
+use theming::ThemingPlugin;
+
+fn main() {
+    nih_export_standalone::<ThemingPlugin>();
+}
@@ -1,11 +1,5 @@
+use crate::DECAYS;
 use cyma::prelude::*;
-use cyma::{
-    utils::{HistogramBuffer, PeakBuffer, RingBuffer, SpectrumOutput, WaveformBuffer},
-    visualizers::{
-        Graph, Grid, Lissajous, LissajousGrid, Meter, Oscilloscope, SpectrumAnalyzer,
-        SpectrumAnalyzerModifiers, SpectrumAnalyzerVariant, UnitRuler, Waveform,
-    },
-};
 use nih_plug::editor::Editor;
 use nih_plug_vizia::{assets, create_vizia_editor, vizia::prelude::*, ViziaState, ViziaTheming};
 use std::sync::{Arc, Mutex};
@@ -14,8 +8,17 @@ use std::sync::{Arc, Mutex};
 pub(crate) struct Data {
     pub(crate) oscilloscope_buffer: Arc<Mutex<WaveformBuffer>>,
     pub(crate) peak_buffer: Arc<Mutex<PeakBuffer>>,
+    // The newest second of the peak buffer, for the inset
+    pub(crate) newest_peaks: Arc<Mutex<WindowedBuffer<PeakBuffer>>>,
+    // Selects the channels the peak buffer reads, and the name of the selected ones
+    pub(crate) peak_source: SourceSelector,
+    pub(crate) peak_source_name: String,
     pub(crate) histogram_buffer: Arc<Mutex<HistogramBuffer>>,
     pub(crate) lissajous_buffer: Arc<Mutex<RingBuffer<(f32, f32)>>>,
+    pub(crate) decay_buffers: Vec<Arc<Mutex<PeakBuffer>>>,
+    pub(crate) left_peak_buffer: Arc<Mutex<PeakBuffer>>,
+    pub(crate) right_peak_buffer: Arc<Mutex<PeakBuffer>>,
+    pub(crate) balance_buffer: Arc<Mutex<BalanceBuffer>>,
     pub(crate) spectrum: Arc<Mutex<SpectrumOutput>>,
 
     pub(crate) waveform: Arc<Mutex<Vec<f32>>>,
@@ -27,24 +30,60 @@ impl Data {
         peak_buffer: Arc<Mutex<PeakBuffer>>,
         histogram_buffer: Arc<Mutex<HistogramBuffer>>,
         lissajous_buffer: Arc<Mutex<RingBuffer<(f32, f32)>>>,
+        decay_buffers: Vec<Arc<Mutex<PeakBuffer>>>,
+        left_peak_buffer: Arc<Mutex<PeakBuffer>>,
+        right_peak_buffer: Arc<Mutex<PeakBuffer>>,
+        balance_buffer: Arc<Mutex<BalanceBuffer>>,
         spectrum: Arc<Mutex<SpectrumOutput>>,
         waveform: Arc<Mutex<Vec<f32>>>,
     ) -> Self {
         Self {
             oscilloscope_buffer,
+            newest_peaks: Arc::new(Mutex::new(WindowedBuffer::new(peak_buffer.clone(), 1.0))),
+            peak_source: peak_buffer.lock().unwrap().source_selector(),
+            peak_source_name: PEAK_SOURCES[0].0.to_string(),
             peak_buffer,
             histogram_buffer,
             lissajous_buffer,
+            decay_buffers,
+            left_peak_buffer,
+            right_peak_buffer,
+            balance_buffer,
             spectrum,
             waveform,
         }
     }
 }
 
-impl Model for Data {}
+/// The sources the peak graph can show, by name.
+const PEAK_SOURCES: [(&str, ChannelSource); 6] = [
+    ("Mean", ChannelSource::DownmixAvg),
+    ("Loudest", ChannelSource::DownmixMax),
+    ("Mid", ChannelSource::Mid),
+    ("Side", ChannelSource::Side),
+    ("Left", ChannelSource::Channel(0)),
+    ("Right", ChannelSource::Channel(1)),
+];
+
+enum DataEvent {
+    SelectSource(usize),
+}
+
+impl Model for Data {
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|data_event, _| match data_event {
+            DataEvent::SelectSource(index) => {
+                let (name, source) = PEAK_SOURCES[*index];
+                // The audio thread picks this up on its next block, without a lock
+                self.peak_source.set(source);
+                self.peak_source_name = name.to_string();
+            }
+        });
+    }
+}
 
 pub(crate) fn default_state() -> Arc<ViziaState> {
-    ViziaState::new(|| (800, 800))
+    ViziaState::new(|| (800, 2160))
 }
 
 pub(crate) fn create(editor_data: Data, editor_state: Arc<ViziaState>) -> Option<Box<dyn Editor>> {
@@ -62,7 +101,21 @@ pub(crate) fn create(editor_data: Data, editor_state: Arc<ViziaState>) -> Option
 
             peak_graph(cx);
 
+            decay_graphs(cx);
+
+            split_graph(cx);
+
+            meter_strip(cx);
+
+            meter_bridge(cx);
+
+            balance(cx);
+
+            sparklines(cx);
+
             spectrum_analyzer(cx);
+
+            scroll_view(cx);
         })
         .child_space(Pixels(16.0))
         .row_between(Pixels(16.0))
@@ -190,7 +243,10 @@ fn peak_graph(cx: &mut Context) {
             )
             .color(Color::rgb(60, 60, 60));
 
+            // Switching the source clears the buffer, which fades out instead of
+            // blanking the graph
             Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+                .fade_clears(true)
                 .color(Color::rgba(255, 255, 255, 160))
                 .background_color(Color::rgba(255, 255, 255, 60));
 
@@ -198,6 +254,35 @@ fn peak_graph(cx: &mut Context) {
                 .color(Color::rgba(120, 120, 255, 160))
                 .background_color(Color::rgba(120, 120, 255, 100))
                 .width(Pixels(120.));
+
+            // The newest second at full resolution, from the same buffer
+            Graph::new(cx, Data::newest_peaks, (-32.0, 8.0), ValueScaling::Decibels)
+                .color(Color::rgba(255, 255, 255, 200))
+                .background_color(Color::rgba(255, 255, 255, 40))
+                .width(Pixels(160.))
+                .height(Pixels(60.))
+                .left(Stretch(1.))
+                .top(Pixels(8.))
+                .right(Pixels(8.))
+                .border_color(Color::rgb(80, 80, 80))
+                .border_width(Pixels(1.));
+
+            // Switches the channels the graph, the meter and the inset show
+            Dropdown::new(
+                cx,
+                |cx| Label::new(cx, Data::peak_source_name),
+                |cx| {
+                    for (index, (name, _)) in PEAK_SOURCES.iter().enumerate() {
+                        Label::new(cx, *name).on_press(move |cx| {
+                            cx.emit(DataEvent::SelectSource(index));
+                            cx.emit(PopupEvent::Close);
+                        });
+                    }
+                },
+            )
+            .width(Pixels(100.))
+            .left(Pixels(8.))
+            .top(Pixels(8.));
         })
         .background_color(Color::rgb(16, 16, 16));
 
@@ -235,6 +320,299 @@ fn peak_graph(cx: &mut Context) {
     .border_width(Pixels(1.));
 }
 
+/// Draws six peak graphs with different decay times as a single graph group, with a
+/// legend that shows or hides each trace when it's clicked.
+///
+/// The traces share their fill color, so the whole group is drawn using 7 draw
+/// calls instead of the 12 that six stacked graphs would take. Their stroke colors
+/// are assigned automatically, in the same order for the graphs and the legend.
+fn decay_graphs(cx: &mut Context) {
+    let styles = DECAYS
+        .iter()
+        .map(|decay| TraceStyle::new(format!("{decay} ms")).fill(Color::rgba(255, 255, 255, 12)))
+        .collect::<Vec<_>>();
+
+    VStack::new(cx, |cx| {
+        ZStack::new(cx, |cx| {
+            Grid::new(
+                cx,
+                ValueScaling::Linear,
+                (-32., 8.),
+                vec![6.0, 0.0, -6.0, -12.0, -18.0, -24.0, -30.0],
+                Orientation::Horizontal,
+            )
+            .color(Color::rgb(60, 60, 60));
+
+            styles.iter().enumerate().fold(
+                GraphGroup::new(cx, (-32.0, 8.0), ValueScaling::Decibels),
+                |group, (i, style)| {
+                    group.styled_trace(
+                        Data::decay_buffers.map(move |buffers| buffers[i].clone()),
+                        style,
+                    )
+                },
+            );
+        })
+        .background_color(Color::rgb(16, 16, 16))
+        .border_color(Color::rgb(80, 80, 80))
+        .border_width(Pixels(1.))
+        .height(Pixels(160.));
+
+        Legend::new(cx, &styles, Orientation::Horizontal)
+            .toggles_visibility(true)
+            .font_size(12.)
+            .color(Color::rgb(160, 160, 160))
+            .height(Auto);
+    })
+    .height(Auto)
+    .row_between(Pixels(8.));
+}
+
+/// Draws the peaks of the left and right channel as a split graph, with a mirrored
+/// unit ruler.
+fn split_graph(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        SplitGraph::new(
+            cx,
+            Data::left_peak_buffer,
+            Data::right_peak_buffer,
+            (-32.0, 8.0),
+            ValueScaling::Decibels,
+        )
+        .center_line(Color::rgb(80, 80, 80))
+        .color(Color::rgba(255, 255, 255, 160))
+        .background_color(Color::rgba(255, 255, 255, 60));
+
+        UnitRuler::mirrored(
+            cx,
+            (-32.0, 8.0),
+            ValueScaling::Linear,
+            vec![(6.0, "6db"), (-6.0, "-6db"), (-18.0, "-18db")],
+            Orientation::Vertical,
+        )
+        .font_size(12.)
+        .color(Color::rgb(160, 160, 160))
+        .width(Pixels(32.));
+    })
+    .col_between(Pixels(8.))
+    .background_color(Color::rgb(16, 16, 16))
+    .border_color(Color::rgb(80, 80, 80))
+    .border_width(Pixels(1.))
+    .height(Pixels(160.));
+}
+
+/// Draws the peaks of the left and right channel as a horizontal meter strip, with
+/// a ruler below it whose labels read vertically.
+///
+/// The value axis of the grid and the graphs runs to the right, and time runs
+/// downwards. The ruler shares their range, so its markers line up with the grid.
+fn meter_strip(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        ZStack::new(cx, |cx| {
+            Grid::new(
+                cx,
+                ValueScaling::Linear,
+                (-32., 8.),
+                vec![6.0, 0.0, -6.0, -12.0, -18.0, -24.0, -30.0],
+                Orientation::Horizontal,
+            )
+            .value_axis(Orientation::Horizontal)
+            .color(Color::rgb(60, 60, 60));
+
+            VStack::new(cx, |cx| {
+                Graph::new(
+                    cx,
+                    Data::left_peak_buffer,
+                    (-32.0, 8.0),
+                    ValueScaling::Decibels,
+                )
+                .value_axis(Orientation::Horizontal)
+                .color(Color::rgba(255, 255, 255, 160))
+                .background_color(Color::rgba(255, 255, 255, 60));
+                Graph::new(
+                    cx,
+                    Data::right_peak_buffer,
+                    (-32.0, 8.0),
+                    ValueScaling::Decibels,
+                )
+                .value_axis(Orientation::Horizontal)
+                .color(Color::rgba(255, 255, 255, 160))
+                .background_color(Color::rgba(255, 255, 255, 60));
+            })
+            .row_between(Pixels(4.));
+        })
+        .background_color(Color::rgb(16, 16, 16))
+        .border_color(Color::rgb(80, 80, 80))
+        .border_width(Pixels(1.))
+        .height(Pixels(96.));
+
+        UnitRuler::new(
+            cx,
+            (-32.0, 8.0),
+            ValueScaling::Linear,
+            vec![
+                (6.0, "6db"),
+                (0.0, "0db"),
+                (-6.0, "-6db"),
+                (-12.0, "-12db"),
+                (-18.0, "-18db"),
+                (-24.0, "-24db"),
+                (-30.0, "-30db"),
+            ],
+            Orientation::Horizontal,
+        )
+        .label_rotation(LabelRotation::CounterClockwise)
+        .font_size(12.)
+        .color(Color::rgb(160, 160, 160))
+        .height(Pixels(40.));
+    })
+    .height(Auto)
+    .row_between(Pixels(8.));
+}
+
+/// Draws the left channel's peaks as a trace, next to the right channel's peaks as
+/// the segments of an LED meter, in green, yellow and red bands.
+fn meter_bridge(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        Graph::new(
+            cx,
+            Data::left_peak_buffer,
+            (-32.0, 8.0),
+            ValueScaling::Decibels,
+        )
+        .color(Color::rgba(255, 255, 255, 160))
+        .background_color(Color::rgba(255, 255, 255, 60));
+
+        Graph::new(
+            cx,
+            Data::right_peak_buffer,
+            (-32.0, 8.0),
+            ValueScaling::Decibels,
+        )
+        .graph_style(GraphStyle::Segments {
+            segment_height_px: 4.,
+            gap_px: 2.,
+        })
+        .level_colors([
+            (-32.0, Color::rgb(80, 200, 120)),
+            (-12.0, Color::rgb(230, 200, 80)),
+            (0.0, Color::rgb(230, 80, 80)),
+        ])
+        .partial_segments(PartialSegments::Dim)
+        .color(Color::rgba(255, 255, 255, 160));
+    })
+    .background_color(Color::rgb(16, 16, 16))
+    .border_color(Color::rgb(80, 80, 80))
+    .border_width(Pixels(1.))
+    .col_between(Pixels(8.))
+    .height(Pixels(160.));
+}
+
+/// Draws the stereo balance over time next to a lissajous, which shows how
+/// correlated the channels are.
+///
+/// The balance is drawn as a bipolar graph around a zero line, so that energy
+/// leaning to the right fills upwards, and energy leaning to the left downwards.
+fn balance(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        lissajous(cx);
+
+        HStack::new(cx, |cx| {
+            ZStack::new(cx, |cx| {
+                Grid::new(
+                    cx,
+                    ValueScaling::Linear,
+                    (-1., 1.),
+                    vec![0.5, -0.5],
+                    Orientation::Horizontal,
+                )
+                .color(Color::rgb(40, 40, 40));
+                Graph::new(cx, Data::balance_buffer, (-1., 1.), ValueScaling::Linear)
+                    .bipolar(
+                        0.,
+                        Color::rgba(255, 160, 120, 100),
+                        Color::rgba(120, 160, 255, 100),
+                    )
+                    .zero_line(Color::rgb(80, 80, 80))
+                    .color(Color::rgba(255, 255, 255, 160));
+            });
+
+            UnitRuler::new(
+                cx,
+                (-1., 1.),
+                ValueScaling::Linear,
+                vec![(0.9, "R"), (0., "C"), (-0.9, "L")],
+                Orientation::Vertical,
+            )
+            .font_size(12.)
+            .color(Color::rgb(160, 160, 160))
+            .width(Pixels(32.));
+        })
+        .col_between(Pixels(8.))
+        .background_color(Color::rgb(16, 16, 16))
+        .border_color(Color::rgb(80, 80, 80))
+        .border_width(Pixels(1.));
+    })
+    .height(Pixels(200.))
+    .col_between(Pixels(16.0));
+}
+
+/// Draws a column of ten sparklines, like the ones a plug-in could show next to its
+/// parameters: the peaks at each decay time, the peaks of each channel, and the
+/// stereo balance.
+///
+/// Each sparkline draws at most one point per pixel, so all ten together cost less
+/// than a single full-width graph.
+fn sparklines(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        for (i, decay) in DECAYS.iter().enumerate() {
+            sparkline_row(cx, &format!("{decay} ms"), |cx| {
+                Sparkline::new(
+                    cx,
+                    Data::decay_buffers.map(move |buffers| buffers[i].clone()),
+                );
+            });
+        }
+        sparkline_row(cx, "Peak", |cx| {
+            Sparkline::new(cx, Data::peak_buffer).newest_dot(true);
+        });
+        sparkline_row(cx, "Left", |cx| {
+            Sparkline::new(cx, Data::left_peak_buffer).newest_dot(true);
+        });
+        sparkline_row(cx, "Right", |cx| {
+            Sparkline::new(cx, Data::right_peak_buffer).newest_dot(true);
+        });
+        // Leaning to the right is drawn in orange, and to the left in blue
+        sparkline_row(cx, "Balance", |cx| {
+            Sparkline::new(cx, Data::balance_buffer)
+                .baseline(0., Color::rgb(255, 160, 120), Color::rgb(120, 160, 255))
+                .newest_dot(true);
+        });
+    })
+    .height(Auto)
+    .row_between(Pixels(4.))
+    .child_space(Pixels(8.))
+    .background_color(Color::rgb(16, 16, 16))
+    .border_color(Color::rgb(80, 80, 80))
+    .border_width(Pixels(1.));
+}
+
+/// Draws a label, followed by a 60x14 sparkline.
+fn sparkline_row(cx: &mut Context, label: &str, sparkline: impl FnOnce(&mut Context)) {
+    HStack::new(cx, |cx| {
+        Label::new(cx, label)
+            .font_size(12.)
+            .color(Color::rgb(160, 160, 160))
+            .width(Pixels(64.));
+        HStack::new(cx, sparkline)
+            .width(Pixels(60.))
+            .height(Pixels(14.))
+            .color(Color::rgba(255, 255, 255, 200));
+    })
+    .height(Pixels(14.))
+    .col_between(Pixels(8.));
+}
+
 /// Draws an oscilloscope with a grid backdrop.
 fn oscilloscope(cx: &mut Context) {
     ZStack::new(cx, |cx| {
@@ -260,9 +638,37 @@ fn oscilloscope(cx: &mut Context) {
             (-1.2, 1.2),
             ValueScaling::Linear,
         )
-        .color(Color::rgba(255, 255, 255, 120));
+        .color(Color::rgba(0, 0, 0, 0))
+        // Positive and negative excursions in different colors, to spot asymmetry
+        .color_mode(ColorMode::Polarity {
+            positive: Some(Color::rgba(255, 255, 255, 120)),
+            negative: Some(Color::rgba(120, 180, 255, 120)),
+        });
     })
     .border_color(Color::rgb(80, 80, 80))
     .border_width(Pixels(1.))
     .background_color(Color::rgb(16, 16, 16));
 }
+
+/// Draws several visualizers inside a scroll view that is shorter than its content.
+///
+/// When scrolling, none of the visualizers should draw outside of the scroll view.
+fn scroll_view(cx: &mut Context) {
+    ScrollView::new(cx, 0.0, 0.0, false, true, |cx| {
+        VStack::new(cx, |cx| {
+            peak_graph(cx);
+
+            HStack::new(cx, |cx| {
+                oscilloscope(cx);
+            })
+            .height(Pixels(160.));
+
+            split_graph(cx);
+        })
+        .height(Auto)
+        .row_between(Pixels(16.0));
+    })
+    .border_color(Color::rgb(80, 80, 80))
+    .border_width(Pixels(1.))
+    .height(Pixels(200.));
+}
@@ -1,7 +1,4 @@
 use cyma::prelude::*;
-use cyma::utils::{
-    HistogramBuffer, PeakBuffer, RingBuffer, SpectrumInput, SpectrumOutput, WaveformBuffer,
-};
 use nih_plug::prelude::*;
 use nih_plug_vizia::ViziaState;
 use std::{
@@ -11,6 +8,8 @@ use std::{
 
 mod editor;
 
+pub(crate) const DECAYS: [f32; 6] = [10., 50., 150., 400., 1000., 2500.];
+
 pub struct VisualizersDemo {
     params: Arc<DemoParams>,
 
@@ -19,6 +18,13 @@ pub struct VisualizersDemo {
     peak_buffer: Arc<Mutex<PeakBuffer>>,
     lissajous_buffer: Arc<Mutex<RingBuffer<(f32, f32)>>>,
     histogram_buffer: Arc<Mutex<HistogramBuffer>>,
+    // The same peaks with different decay times, displayed together
+    decay_buffers: Vec<Arc<Mutex<PeakBuffer>>>,
+    // The peaks of each channel, displayed as a split graph
+    left_peak_buffer: Arc<Mutex<PeakBuffer>>,
+    right_peak_buffer: Arc<Mutex<PeakBuffer>>,
+    // How far the energy leans to the left or right channel
+    balance_buffer: Arc<Mutex<BalanceBuffer>>,
 
     spectrum_input: SpectrumInput,
     spectrum_output: Arc<Mutex<SpectrumOutput>>,
@@ -42,6 +48,13 @@ impl Default for VisualizersDemo {
             peak_buffer: Arc::new(Mutex::new(PeakBuffer::new(800, 10.0, 50.))),
             histogram_buffer: Arc::new(Mutex::new(HistogramBuffer::new(256, 0.5))),
             lissajous_buffer: Arc::new(Mutex::new(RingBuffer::new(2048))),
+            decay_buffers: DECAYS
+                .iter()
+                .map(|decay| Arc::new(Mutex::new(PeakBuffer::new(800, 10.0, *decay))))
+                .collect(),
+            left_peak_buffer: Arc::new(Mutex::new(PeakBuffer::new(800, 10.0, 50.))),
+            right_peak_buffer: Arc::new(Mutex::new(PeakBuffer::new(800, 10.0, 50.))),
+            balance_buffer: Arc::new(Mutex::new(BalanceBuffer::new(400, 5.0))),
 
             spectrum_input,
             spectrum_output: Arc::new(Mutex::new(spectrum_output)),
@@ -103,6 +116,10 @@ impl Plugin for VisualizersDemo {
                 self.peak_buffer.clone(),
                 self.histogram_buffer.clone(),
                 self.lissajous_buffer.clone(),
+                self.decay_buffers.clone(),
+                self.left_peak_buffer.clone(),
+                self.right_peak_buffer.clone(),
+                self.balance_buffer.clone(),
                 self.spectrum_output.clone(),
                 self.waveform.clone(),
             ),
@@ -134,6 +151,28 @@ impl Plugin for VisualizersDemo {
             }
             Err(_) => return false,
         }
+        for decay_buffer in self.decay_buffers.iter() {
+            match decay_buffer.lock() {
+                Ok(mut buffer) => {
+                    buffer.set_sample_rate(buffer_config.sample_rate);
+                }
+                Err(_) => return false,
+            }
+        }
+        for channel_buffer in [&self.left_peak_buffer, &self.right_peak_buffer] {
+            match channel_buffer.lock() {
+                Ok(mut buffer) => {
+                    buffer.set_sample_rate(buffer_config.sample_rate);
+                }
+                Err(_) => return false,
+            }
+        }
+        match self.balance_buffer.lock() {
+            Ok(mut buffer) => {
+                buffer.set_sample_rate(buffer_config.sample_rate);
+            }
+            Err(_) => return false,
+        }
 
         self.spectrum_input
             .update_sample_rate(buffer_config.sample_rate);
@@ -161,8 +200,24 @@ impl Plugin for VisualizersDemo {
                 .lock()
                 .unwrap()
                 .enqueue_buffer(buffer, None);
+            for decay_buffer in self.decay_buffers.iter() {
+                decay_buffer.lock().unwrap().enqueue_buffer(buffer, None);
+            }
 
             if buffer.channels() > 1 {
+                self.left_peak_buffer
+                    .lock()
+                    .unwrap()
+                    .enqueue_buffer(buffer, Some(0));
+                self.right_peak_buffer
+                    .lock()
+                    .unwrap()
+                    .enqueue_buffer(buffer, Some(1));
+                self.balance_buffer
+                    .lock()
+                    .unwrap()
+                    .enqueue_buffer(buffer, None);
+
                 for mut sample in buffer.iter_samples() {
                     self.lissajous_buffer
                         .lock()
@@ -0,0 +1,71 @@
+//! A global allocator for the tests, which counts the allocations and
+//! deallocations made on each thread.
+//!
+//! Views reuse their scratch vectors from one frame to the next, so that drawing
+//! doesn't allocate once they have grown to the buffer's length. Buffers never
+//! allocate or free on the audio thread at all. The tests use this to make sure
+//! that both hold.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::fmt::Display;
+
+struct CountingAllocator;
+
+thread_local! {
+    // Counted per thread, so that tests running in parallel don't interfere
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    static DEALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count(counter: &'static std::thread::LocalKey<Cell<usize>>) {
+    // The counter is gone while the thread is being torn down
+    let _ = counter.try_with(|count| count.set(count.get() + 1));
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count(&ALLOCATIONS);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        count(&DEALLOCATIONS);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count(&ALLOCATIONS);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the number of heap allocations `f` makes on the current thread.
+pub(crate) fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+/// Runs `f`, and panics if it allocated or freed any memory on the current thread.
+///
+/// `what` names the call in the panic message. It's only formatted when the
+/// assertion fails, so `format_args!()` can be passed without allocating.
+#[track_caller]
+pub(crate) fn assert_no_alloc<R>(what: impl Display, f: impl FnOnce() -> R) -> R {
+    let allocations = ALLOCATIONS.with(Cell::get);
+    let deallocations = DEALLOCATIONS.with(Cell::get);
+    let result = f();
+    let allocations = ALLOCATIONS.with(Cell::get) - allocations;
+    let deallocations = DEALLOCATIONS.with(Cell::get) - deallocations;
+    if allocations > 0 || deallocations > 0 {
+        panic!(
+            "{} allocated {} time(s) and freed {} time(s) on the audio thread",
+            what, allocations, deallocations
+        );
+    }
+    result
+}
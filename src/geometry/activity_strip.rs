@@ -0,0 +1,214 @@
+use super::Bounds;
+use crate::utils::NoteEventBuffer;
+
+/// A note drawn by an activity strip, spanning the slots from `start` to `end`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct NoteBar {
+    pub start: usize,
+    pub end: usize,
+    pub note: u8,
+    pub velocity: f32,
+}
+
+impl NoteBar {
+    /// Returns the rectangle the note covers inside an activity strip with the
+    /// given bounds, which shows `slots` slots and the notes between `low` and
+    /// `high`.
+    pub(crate) fn rect(
+        self: &Self,
+        bounds: Bounds,
+        (low, high): (u8, u8),
+        slots: usize,
+    ) -> (f32, f32, f32, f32) {
+        let Bounds { x, y, w, h } = bounds;
+        let slot_width = w / slots as f32;
+        let row_height = h / (high as usize - low as usize + 1) as f32;
+        (
+            x + slot_width * self.start as f32,
+            y + h - row_height * (self.note as f32 - low as f32 + 1.),
+            slot_width * (self.end - self.start + 1) as f32,
+            row_height,
+        )
+    }
+}
+
+/// Pairs the note-on and note-off events of a buffer in a single pass, and fills
+/// `bars` with the notes between `low` and `high`, ordered by velocity.
+///
+/// Notes that are still held last until the newest slot. A note whose note-on was
+/// evicted, or lies before the oldest slot, starts at the oldest slot and is drawn
+/// at full velocity.
+pub(crate) fn note_bars(buffer: &NoteEventBuffer, (low, high): (u8, u8), bars: &mut Vec<NoteBar>) {
+    bars.clear();
+    if buffer.is_empty() {
+        return;
+    }
+
+    // The slot and velocity each note started at, while it's held
+    let mut held = [None::<(usize, f32)>; 256];
+
+    for event in buffer.events() {
+        if event.note < low || event.note > high {
+            continue;
+        }
+        let index = buffer.slot_index(event.slot);
+        let note = &mut held[event.note as usize];
+
+        if event.on {
+            let start = index.unwrap_or(0);
+            // A retriggered note ends where it's played again
+            if let Some((previous, velocity)) = note.take() {
+                bars.push(NoteBar {
+                    start: previous,
+                    end: start,
+                    note: event.note,
+                    velocity,
+                });
+            }
+            *note = Some((start, event.velocity));
+        } else {
+            let held = note.take();
+            // Notes that ended before the oldest slot aren't visible
+            if let Some(end) = index {
+                let (start, velocity) = held.unwrap_or((0, 1.));
+                bars.push(NoteBar {
+                    start,
+                    end: end.max(start),
+                    note: event.note,
+                    velocity,
+                });
+            }
+        }
+    }
+
+    for (note, held) in held.iter().enumerate() {
+        if let Some((start, velocity)) = *held {
+            bars.push(NoteBar {
+                start,
+                end: buffer.len() - 1,
+                note: note as u8,
+                velocity,
+            });
+        }
+    }
+
+    bars.sort_unstable_by(|a, b| a.velocity.total_cmp(&b.velocity));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{note_bars, NoteBar};
+    use crate::geometry::Bounds;
+    use crate::utils::NoteEventBuffer;
+
+    fn buffer() -> NoteEventBuffer {
+        // 10 slots of 100 samples each
+        let mut buffer = NoteEventBuffer::new(10, 1.0, 8);
+        buffer.set_sample_rate(1000.0);
+        buffer
+    }
+
+    fn bars(buffer: &NoteEventBuffer, note_range: (u8, u8)) -> Vec<NoteBar> {
+        let mut bars = Vec::new();
+        note_bars(buffer, note_range, &mut bars);
+        bars
+    }
+
+    // 10 pixels per slot, and per row of the notes from 60 to 64
+    const BOUNDS: Bounds = Bounds {
+        x: 10.,
+        y: 20.,
+        w: 100.,
+        h: 50.,
+    };
+
+    fn rects(buffer: &NoteEventBuffer) -> Vec<(f32, f32, f32, f32)> {
+        bars(buffer, (60, 64))
+            .iter()
+            .map(|bar| bar.rect(BOUNDS, (60, 64), buffer.len()))
+            .collect()
+    }
+
+    fn bar(start: usize, end: usize, note: u8, velocity: f32) -> NoteBar {
+        NoteBar {
+            start,
+            end,
+            note,
+            velocity,
+        }
+    }
+
+    #[test]
+    fn empty_buffer() {
+        assert!(rects(&NoteEventBuffer::new(0, 1.0, 8)).is_empty());
+        assert!(rects(&buffer()).is_empty());
+    }
+
+    #[test]
+    fn partially_filled_buffer() {
+        // A note that started three slots ago covers the newest part of its row
+        let mut buffer = buffer();
+        buffer.enqueue_note(0, 62, 0.5, true);
+        buffer.advance(300);
+        assert_eq!(rects(&buffer), vec![(70., 40., 40., 10.)]);
+    }
+
+    #[test]
+    fn filled_buffer() {
+        // Notes that are held across the whole strip, in its lowest and highest rows
+        let mut buffer = buffer();
+        buffer.enqueue_note(0, 60, 0.5, true);
+        buffer.enqueue_note(0, 64, 1.0, true);
+        buffer.advance(900);
+        assert_eq!(
+            rects(&buffer),
+            vec![(10., 60., 100., 10.), (10., 20., 100., 10.)]
+        );
+    }
+
+    #[test]
+    fn paired_and_held_notes() {
+        let mut buffer = buffer();
+        buffer.enqueue_note(50, 60, 1.0, true);
+        buffer.enqueue_note(150, 62, 0.5, true);
+        buffer.enqueue_note(250, 60, 0.0, false);
+        buffer.advance(300);
+
+        // Ordered by velocity, and the held note lasts until the newest slot
+        assert_eq!(
+            bars(&buffer, (0, 127)),
+            vec![bar(7, 9, 62, 0.5), bar(6, 8, 60, 1.0)]
+        );
+        assert_eq!(bars(&buffer, (61, 127)), vec![bar(7, 9, 62, 0.5)]);
+    }
+
+    #[test]
+    fn evicted_note_on() {
+        let mut buffer = buffer();
+        buffer.enqueue_note(0, 60, 0.5, true);
+        buffer.enqueue_note(0, 62, 0.5, true);
+        buffer.enqueue_note(50, 62, 0.0, false);
+        buffer.advance(1000);
+        buffer.enqueue_note(0, 60, 0.0, false);
+        buffer.advance(100);
+
+        // The note-on has scrolled out, but the note is still drawn up to its note-off,
+        // while the note that ended before the oldest slot isn't
+        assert_eq!(buffer.event_count(), 1);
+        assert_eq!(bars(&buffer, (0, 127)), vec![bar(0, 8, 60, 1.0)]);
+    }
+
+    #[test]
+    fn retriggered_note() {
+        let mut buffer = buffer();
+        buffer.enqueue_note(0, 60, 0.25, true);
+        buffer.enqueue_note(300, 60, 0.75, true);
+        buffer.advance(700);
+
+        // The first note ends where the second one starts
+        assert_eq!(
+            bars(&buffer, (60, 60)),
+            vec![bar(2, 5, 60, 0.25), bar(5, 9, 60, 0.75)]
+        );
+    }
+}
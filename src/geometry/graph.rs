@@ -0,0 +1,89 @@
+use super::refill;
+use crate::utils::{NormalizedRead, VisualizerBuffer};
+
+/// Fills `points` with each point's position in time and its normalized value.
+///
+/// The smoothed values replace the buffer's values if given, and the element that
+/// is being accumulated, if any, is added as an extra point at the right edge.
+pub(crate) fn graph_points<B>(
+    points: &mut Vec<(f32, f32)>,
+    values: &NormalizedRead<B>,
+    smoothed: Option<&[f32]>,
+    partial: Option<(f32, f32)>,
+    x: f32,
+    w: f32,
+) where
+    B: VisualizerBuffer<f32, Output = f32> + ?Sized,
+{
+    let width_delta = w / values.len() as f32;
+    refill(
+        points,
+        (0..values.len())
+            .map(|i| {
+                let peak = match smoothed {
+                    Some(smoothed) => smoothed
+                        .get(i)
+                        .map_or(0., |value| values.normalize_value(*value)),
+                    None => values.normalized(i),
+                };
+                (x + width_delta * i as f32, peak)
+            })
+            .chain(partial.map(|(_, value)| (x + w, values.normalize_value(value)))),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::graph_points;
+    use crate::utils::{NormalizedRead, RingBuffer, ValueScaling};
+
+    // Reads the buffer the way the graph does, across 100 pixels
+    fn points(
+        size: usize,
+        values: &[f32],
+        smoothed: Option<&[f32]>,
+        partial: Option<(f32, f32)>,
+    ) -> Vec<(f32, f32)> {
+        let mut buffer = RingBuffer::new(size);
+        for value in values {
+            buffer.enqueue(*value);
+        }
+        let values = NormalizedRead::new(&buffer, (0., 1.), ValueScaling::Linear);
+        let mut points = Vec::new();
+        graph_points(&mut points, &values, smoothed, partial, 10., 100.);
+        points
+    }
+
+    #[test]
+    fn empty_buffer() {
+        assert!(points(0, &[], None, None).is_empty());
+    }
+
+    #[test]
+    fn partially_filled_buffer() {
+        // Slots that were never written lie at the bottom of the range, and the
+        // element that's being accumulated sits at the right edge
+        assert_eq!(
+            points(4, &[1.], None, Some((0.5, 0.5))),
+            vec![(10., 0.), (35., 0.), (60., 0.), (85., 1.), (110., 0.5)]
+        );
+    }
+
+    #[test]
+    fn filled_buffer() {
+        let points = points(5, &[0.5; 5], None, None);
+        assert_eq!(
+            points,
+            vec![(10., 0.5), (30., 0.5), (50., 0.5), (70., 0.5), (90., 0.5)]
+        );
+    }
+
+    #[test]
+    fn smoothed_values() {
+        // Smoothed values replace the buffer's, and missing ones are at the bottom
+        assert_eq!(
+            points(4, &[1.; 4], Some(&[0.25, 0.75, 0.5]), None),
+            vec![(10., 0.25), (35., 0.75), (60., 0.5), (85., 0.)]
+        );
+    }
+}
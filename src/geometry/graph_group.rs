@@ -0,0 +1,65 @@
+use super::Bounds;
+
+/// Returns the points of a trace inside a graph group with the given bounds,
+/// given its normalized values from the oldest to the newest.
+pub(crate) fn trace_points(
+    values: &[f32],
+    bounds: Bounds,
+) -> impl Iterator<Item = (f32, f32)> + Clone + '_ {
+    let Bounds { x, y, w, h } = bounds;
+    let width_delta = w / values.len() as f32;
+    values
+        .iter()
+        .enumerate()
+        .map(move |(i, peak)| (x + width_delta * i as f32, y + h * (1. - peak)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::trace_points;
+    use crate::geometry::Bounds;
+    use crate::utils::{NormalizedRead, RingBuffer, ValueScaling};
+
+    const BOUNDS: Bounds = Bounds {
+        x: 10.,
+        y: 20.,
+        w: 100.,
+        h: 100.,
+    };
+
+    // Reads the buffer the way a trace does
+    fn points(size: usize, values: &[f32]) -> Vec<(f32, f32)> {
+        let mut buffer = RingBuffer::new(size);
+        for value in values {
+            buffer.enqueue(*value);
+        }
+        let values = NormalizedRead::new(&buffer, (0., 1.), ValueScaling::Linear)
+            .normalized_iter()
+            .collect::<Vec<_>>();
+        trace_points(&values, BOUNDS).collect()
+    }
+
+    #[test]
+    fn empty_buffer() {
+        assert!(points(0, &[]).is_empty());
+    }
+
+    #[test]
+    fn partially_filled_buffer() {
+        // Slots that were never written lie at the bottom of the range
+        assert_eq!(
+            points(4, &[1.]),
+            vec![(10., 120.), (35., 120.), (60., 120.), (85., 20.)]
+        );
+    }
+
+    #[test]
+    fn filled_buffer() {
+        let points = points(5, &[0.5; 5]);
+        assert_eq!(
+            points.iter().map(|(x, _)| *x).collect::<Vec<_>>(),
+            vec![10., 30., 50., 70., 90.]
+        );
+        assert!(points.iter().all(|(_, y)| *y == 70.));
+    }
+}
@@ -0,0 +1,94 @@
+use super::Bounds;
+
+/// Returns the outline of a histogram with the given bounds, from the top of its
+/// range to the bottom.
+///
+/// The outline starts at the top edge, as far in as the share of values above the
+/// range. It's followed by one point per bin, scaled so that the largest bin spans
+/// the full width, unless all of them are empty.
+pub(crate) fn histogram_outline(
+    bounds: Bounds,
+    above_range: f32,
+    bins: &[f32],
+) -> impl Iterator<Item = (f32, f32)> + '_ {
+    let Bounds { x, y, w, h } = bounds;
+    let largest = bins.iter().copied().fold(0.0, f32::max);
+    let bins = if largest > 0.0 { bins } else { &[] };
+    let nr_bins = bins.len() as f32;
+
+    std::iter::once((x + above_range * w, y)).chain(bins.iter().enumerate().rev().map(
+        move |(i, bin)| {
+            (
+                // scale so the largest value becomes 1.
+                x + (bin / largest) * w,
+                y + h * (1. - (i as f32 + 0.5) / nr_bins),
+            )
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::histogram_outline;
+    use crate::geometry::Bounds;
+    use crate::utils::{HistogramBuffer, VisualizerBuffer};
+
+    // One re-binned bin per pixel of height
+    const BOUNDS: Bounds = Bounds {
+        x: 10.,
+        y: 20.,
+        w: 100.,
+        h: 40.,
+    };
+
+    // 24 bins of 1 dB inside the range
+    fn buffer() -> HistogramBuffer {
+        let mut buffer = HistogramBuffer::new(26, 1.0);
+        buffer.set_range((-12., 12.));
+        buffer
+    }
+
+    fn outline(buffer: &HistogramBuffer) -> Vec<(f32, f32)> {
+        let mut bins = Vec::new();
+        buffer.rebinned(BOUNDS.h as usize, &mut bins);
+        let above_range = buffer.get(buffer.len() - 1).copied().unwrap_or(0.);
+        histogram_outline(BOUNDS, above_range, &bins).collect()
+    }
+
+    #[test]
+    fn empty_buffer() {
+        // Only the start of the outline, in the top left corner
+        assert_eq!(outline(&buffer()), vec![(10., 20.)]);
+    }
+
+    #[test]
+    fn partially_filled_buffer() {
+        // A single value at 0 dB, in the middle of the range
+        let mut buffer = buffer();
+        buffer.enqueue(1.0);
+        let outline = outline(&buffer);
+        assert_eq!(outline.len(), 41);
+        assert_eq!(outline[0], (10., 20.));
+        assert!(outline.iter().any(|(x, _)| *x == 110.));
+        assert!(outline
+            .iter()
+            .filter(|(x, _)| *x > 10.)
+            .all(|(_, y)| (30. ..=50.).contains(y)));
+    }
+
+    #[test]
+    fn filled_buffer() {
+        // Values spread across the whole range, and one above it
+        let mut buffer = buffer();
+        for i in 0..=240 {
+            buffer.enqueue(nih_plug::util::db_to_gain(-12. + i as f32 * 0.1));
+        }
+        buffer.enqueue(nih_plug::util::db_to_gain(20.));
+        let outline = outline(&buffer);
+        assert_eq!(outline.len(), 41);
+        assert!(outline.iter().all(|(x, _)| *x > 10. && *x <= 110.));
+        assert!(outline.iter().any(|(x, _)| *x == 110.));
+        // From the top of the range to the bottom
+        assert!(outline.windows(2).all(|pair| pair[0].1 < pair[1].1));
+    }
+}
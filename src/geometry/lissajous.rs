@@ -0,0 +1,83 @@
+use super::Bounds;
+use crate::utils::RingBuffer;
+
+use lazy_static::lazy_static;
+use std::f32::consts::PI;
+
+// These will be used to rotate the lissajous such that it is a straight
+// vertical line for mono data and a horizontal line for fully stereo data.
+lazy_static! {
+    static ref TRANSLATE_SIN: f32 = (PI / 4.).sin();
+    static ref TRANSLATE_COS: f32 = (PI / 4.).cos();
+}
+
+/// Returns the position of each of a buffer's stereo samples inside the bounds of
+/// a lissajous, from the oldest to the newest.
+pub(crate) fn lissajous_dots(
+    bounds: Bounds,
+    buffer: &RingBuffer<(f32, f32)>,
+) -> impl Iterator<Item = (f32, f32)> + '_ {
+    let Bounds { x, y, w, h } = bounds;
+
+    buffer.iter().map(move |&(left, right)| {
+        let left = left.clamp(-1., 1.);
+        let right = right.clamp(-1., 1.);
+
+        let dot_x = left * *TRANSLATE_COS - right * *TRANSLATE_SIN;
+        let dot_y = left * *TRANSLATE_SIN + right * *TRANSLATE_COS;
+
+        (x + w / 2. - dot_x * w / PI, y + h / 2. - dot_y * h / PI)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lissajous_dots;
+    use crate::geometry::Bounds;
+    use crate::utils::RingBuffer;
+
+    const BOUNDS: Bounds = Bounds {
+        x: 0.,
+        y: 0.,
+        w: 100.,
+        h: 100.,
+    };
+
+    fn dots(buffer: &RingBuffer<(f32, f32)>) -> Vec<(f32, f32)> {
+        lissajous_dots(BOUNDS, buffer).collect()
+    }
+
+    #[test]
+    fn empty_buffer() {
+        assert!(dots(&RingBuffer::new(0)).is_empty());
+    }
+
+    #[test]
+    fn partially_filled_buffer() {
+        // Elements that were never written sit in the center
+        let mut buffer = RingBuffer::new(4);
+        buffer.enqueue((0.5, 0.5));
+        let dots = dots(&buffer);
+        assert_eq!(dots.len(), 4);
+        assert_eq!(dots[0], (50., 50.));
+
+        // Mono data lies on the vertical line, above the center
+        let (x, y) = dots[3];
+        assert!((x - 50.).abs() < 1e-4);
+        assert!(y < 50.);
+    }
+
+    #[test]
+    fn filled_buffer() {
+        let mut buffer = RingBuffer::new(4);
+        for _ in 0..4 {
+            // Fully out of phase, and past full scale
+            buffer.enqueue((2., -2.));
+        }
+        for (x, y) in dots(&buffer) {
+            // Out of phase data lies on the horizontal line, clamped to full scale
+            assert!((y - 50.).abs() < 1e-4);
+            assert!((x - (50. - 2f32.sqrt() * 100. / std::f32::consts::PI)).abs() < 1e-4);
+        }
+    }
+}
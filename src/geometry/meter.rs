@@ -0,0 +1,112 @@
+use super::{Bounds, FillFrom};
+use crate::utils::{NormalizedRead, VisualizerBuffer};
+
+/// The geometry of a meter, in the same coordinates as its bounds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct MeterShape {
+    /// The line at the current level.
+    pub level: [(f32, f32); 2],
+    /// The area between the current level and the level the meter is filled from.
+    pub fill: [(f32, f32); 4],
+}
+
+/// Returns the shape of a meter showing the newest value of a buffer, or `None` if
+/// there is nothing to draw.
+///
+/// The level rises upwards in a vertical meter, and to the right otherwise.
+pub(crate) fn meter_shape<B>(
+    bounds: Bounds,
+    values: &NormalizedRead<B>,
+    fill_from: &FillFrom,
+    vertical: bool,
+) -> Option<MeterShape>
+where
+    B: VisualizerBuffer<f32, Output = f32> + ?Sized,
+{
+    if values.is_empty() {
+        return None;
+    }
+
+    let Bounds { x, y, w, h } = bounds;
+    let level = values.normalized(values.len() - 1);
+    let fill_from = fill_from.normalized(values);
+
+    Some(if vertical {
+        let level = [(x, y + h * (1. - level)), (x + w, y + h * (1. - level))];
+        let fill_from = y + h * (1. - fill_from);
+        MeterShape {
+            level,
+            fill: [level[0], level[1], (x + w, fill_from), (x, fill_from)],
+        }
+    } else {
+        let level = [(x + w * level, y), (x + w * level, y + h)];
+        let fill_from = x + w * fill_from;
+        MeterShape {
+            level,
+            fill: [level[0], level[1], (fill_from, y + h), (fill_from, y)],
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{meter_shape, MeterShape};
+    use crate::geometry::{Bounds, FillFrom};
+    use crate::utils::{NormalizedRead, RingBuffer, ValueScaling};
+
+    const BOUNDS: Bounds = Bounds {
+        x: 10.,
+        y: 20.,
+        w: 40.,
+        h: 100.,
+    };
+
+    fn shape(buffer: &RingBuffer<f32>, fill_from: FillFrom, vertical: bool) -> Option<MeterShape> {
+        let values = NormalizedRead::new(buffer, (0., 1.), ValueScaling::Linear);
+        meter_shape(BOUNDS, &values, &fill_from, vertical)
+    }
+
+    #[test]
+    fn empty_buffer() {
+        let buffer = RingBuffer::<f32>::new(0);
+        assert_eq!(shape(&buffer, FillFrom::Bottom, true), None);
+    }
+
+    #[test]
+    fn partially_filled_buffer() {
+        // Only the newest value is shown, whether or not the rest were ever written
+        let mut buffer = RingBuffer::<f32>::new(8);
+        buffer.enqueue(0.75);
+        assert_eq!(
+            shape(&buffer, FillFrom::Bottom, true),
+            Some(MeterShape {
+                level: [(10., 45.), (50., 45.)],
+                fill: [(10., 45.), (50., 45.), (50., 120.), (10., 120.)],
+            })
+        );
+        assert_eq!(
+            shape(&buffer, FillFrom::Top, false),
+            Some(MeterShape {
+                level: [(40., 20.), (40., 120.)],
+                fill: [(40., 20.), (40., 120.), (50., 120.), (50., 20.)],
+            })
+        );
+    }
+
+    #[test]
+    fn filled_buffer() {
+        let mut buffer = RingBuffer::<f32>::new(8);
+        for i in 0..8 {
+            buffer.enqueue(i as f32 / 8.);
+        }
+        // Levels past the range are clamped to it
+        buffer.enqueue(2.);
+        assert_eq!(
+            shape(&buffer, FillFrom::Value(0.5), true),
+            Some(MeterShape {
+                level: [(10., 20.), (50., 20.)],
+                fill: [(10., 20.), (50., 20.), (50., 70.), (10., 70.)],
+            })
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! The geometry of each view: Pure functions that turn the contents of a buffer
+//! into the points, shapes and positions that the view draws.
+//!
+//! None of them touch the canvas, or depend on VIZIA at all, so they compile and
+//! are tested without the `vizia` feature - e.g. using
+//! `cargo test --no-default-features`, on a machine without a GPU. The views'
+//! `draw()` functions only turn their results into paths.
+
+mod activity_strip;
+mod graph;
+mod graph_group;
+mod histogram;
+mod lissajous;
+mod meter;
+mod oscilloscope;
+mod sparkline;
+mod spectrum_analyzer;
+mod split_graph;
+mod waveform;
+
+pub(crate) use activity_strip::{note_bars, NoteBar};
+pub(crate) use graph::graph_points;
+pub(crate) use graph_group::trace_points;
+pub(crate) use histogram::histogram_outline;
+pub(crate) use lissajous::lissajous_dots;
+pub(crate) use meter::{meter_shape, MeterShape};
+pub(crate) use oscilloscope::waveform_points;
+pub(crate) use sparkline::{normalized, sparkline_points};
+pub use spectrum_analyzer::SpectrumAnalyzerVariant;
+pub(crate) use spectrum_analyzer::{spectrum_points, SpectrumScale};
+pub(crate) use split_graph::split_points;
+pub(crate) use waveform::waveform_line;
+
+use crate::utils::NormalizedRead;
+
+/// A view's bounds, in the same coordinates as the canvas.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Bounds {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+#[cfg(feature = "vizia")]
+impl From<nih_plug_vizia::vizia::prelude::BoundingBox> for Bounds {
+    fn from(bounds: nih_plug_vizia::vizia::prelude::BoundingBox) -> Self {
+        let nih_plug_vizia::vizia::prelude::BoundingBox { x, y, w, h } = bounds;
+        Self { x, y, w, h }
+    }
+}
+
+pub(crate) enum FillFrom {
+    Top,
+    Bottom,
+    Value(f32),
+}
+
+impl FillFrom {
+    /// Returns the normalized level the fill extends to, from the bottom (0) to the
+    /// top (1) of the range.
+    ///
+    /// A value is normalized like the levels of the range, and so clamped to it. When
+    /// the range moves past the value, the fill stops at the range's edge instead of
+    /// flipping to the other side.
+    pub(crate) fn normalized<B: ?Sized>(&self, values: &NormalizedRead<B>) -> f32 {
+        match self {
+            FillFrom::Top => 1.0,
+            FillFrom::Bottom => 0.0,
+            FillFrom::Value(level) => values.normalize_level(*level),
+        }
+    }
+}
+
+// Scratch vectors keep at least this much of their capacity when they are shrunk
+const MIN_SCRATCH_CAPACITY: usize = 64;
+
+/// Replaces the contents of a view's scratch vector, which keeps its allocation
+/// from one frame to the next.
+///
+/// Once the vector has grown to the buffer's length, refilling it doesn't allocate.
+/// If the buffer shrinks to less than half of the vector's capacity, the excess is
+/// released.
+///
+/// The points, segments and slices that views compute each frame, and the gradient
+/// stops of an oscilloscope that's colored by level, all live in such vectors.
+/// Only these are allocation-free, and only these are covered by the tests. The
+/// drawing itself still allocates per frame: every `vg::Path` a view builds (the
+/// graph's stroke, fill, markers and shading, the oscilloscope's outline, fill,
+/// polarity sides and minimap, ...) grows its own command list, since femtovg has
+/// no way to reset a path and keep its storage, and femtovg keeps its own copy of
+/// a gradient's stops.
+pub(crate) fn refill<T>(scratch: &mut Vec<T>, values: impl Iterator<Item = T>) {
+    scratch.clear();
+    let len = values.size_hint().0.max(MIN_SCRATCH_CAPACITY);
+    if scratch.capacity() > 2 * len {
+        scratch.shrink_to(len);
+    }
+    scratch.extend(values);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::refill;
+    use crate::alloc_counter::allocations;
+
+    #[test]
+    fn refill_reuses_scratch() {
+        let mut scratch = Vec::new();
+        refill(&mut scratch, 0..1000);
+        assert_eq!(allocations(|| refill(&mut scratch, 1000..2000)), 0);
+        assert_eq!(scratch, (1000..2000).collect::<Vec<_>>());
+
+        // Growing allocates once, while shrinking a little keeps the capacity
+        assert_eq!(allocations(|| refill(&mut scratch, 0..1500)), 1);
+        assert_eq!(allocations(|| refill(&mut scratch, 0..1200)), 0);
+        assert!(scratch.capacity() >= 1500);
+
+        // Shrinking a lot releases the excess
+        refill(&mut scratch, 0..100);
+        assert!(scratch.capacity() < 1500);
+        assert_eq!(scratch.len(), 100);
+    }
+}
@@ -0,0 +1,90 @@
+use super::refill;
+use crate::utils::{NormalizedRead, WaveformBuffer};
+
+/// Fills `points` with each pair's x position and its normalized values.
+///
+/// The element that is being accumulated, if any, is added as an extra pair.
+pub(crate) fn waveform_points(
+    points: &mut Vec<(f32, (f32, f32))>,
+    values: &NormalizedRead<WaveformBuffer>,
+    partial: Option<(f32, (f32, f32))>,
+    x: f32,
+    width_delta: f32,
+) {
+    let partial =
+        partial.map(|(_, (min, max))| (values.normalize_value(min), values.normalize_value(max)));
+    refill(
+        points,
+        (0..values.pair_len())
+            .map(|i| values.normalized_pair(i))
+            .chain(partial)
+            .enumerate()
+            .map(|(i, pair)| (x + width_delta * i as f32, pair)),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::waveform_points;
+    use crate::utils::{NormalizedRead, ValueScaling, VisualizerBuffer, WaveformBuffer};
+
+    // 4 samples per element, read across 80 pixels
+    fn points(samples: &[f32]) -> Vec<(f32, (f32, f32))> {
+        let mut buffer = WaveformBuffer::new(4, 1.0);
+        buffer.set_sample_rate(16.);
+        for sample in samples {
+            buffer.enqueue(*sample);
+        }
+        let values = NormalizedRead::new(&buffer, (-1., 1.), ValueScaling::Linear);
+        let mut points = Vec::new();
+        waveform_points(&mut points, &values, buffer.partial(), 10., 20.);
+        points
+    }
+
+    #[test]
+    fn empty_buffer() {
+        // Pairs that were never written are silent, and lie on the center line
+        assert_eq!(
+            points(&[]),
+            vec![
+                (10., (0.5, 0.5)),
+                (30., (0.5, 0.5)),
+                (50., (0.5, 0.5)),
+                (70., (0.5, 0.5))
+            ]
+        );
+    }
+
+    #[test]
+    fn partially_filled_buffer() {
+        // One finished element, and one that's being accumulated
+        assert_eq!(
+            points(&[1., -1., 0.5, 0.5, 0.5, -0.5]),
+            vec![
+                (10., (0.5, 0.5)),
+                (30., (0.5, 0.5)),
+                (50., (0.5, 0.5)),
+                (70., (0., 1.)),
+                (90., (0.25, 0.75))
+            ]
+        );
+    }
+
+    #[test]
+    fn filled_buffer() {
+        // An element is finished by the first sample of the next one
+        let samples: Vec<f32> = (0..17)
+            .map(|i| if i % 2 == 0 { 0.5 } else { -0.5 })
+            .collect();
+        assert_eq!(
+            points(&samples),
+            vec![
+                (10., (0.25, 0.75)),
+                (30., (0.25, 0.75)),
+                (50., (0.25, 0.75)),
+                (70., (0.25, 0.75)),
+                (90., (0.75, 0.75))
+            ]
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use crate::utils::{scaled_extent, ValueScaling, VisualizerBuffer};
+
+/// Fills `points` with the trace of a sparkline, as fractions of its width and
+/// height, and returns the extent the values were mapped from - or `None` if the
+/// buffer holds no finite values.
+///
+/// The extent spans the buffer's values and the baseline, if any. Each of the
+/// `columns` gets at most one point: the value that lies farthest from the middle
+/// of the extent, so that short peaks survive the decimation. The last column always
+/// ends at the newest value.
+pub(crate) fn sparkline_points<B>(
+    points: &mut Vec<(f32, f32)>,
+    buffer: &B,
+    columns: usize,
+    baseline: Option<f32>,
+) -> Option<(f32, f32)>
+where
+    B: VisualizerBuffer<f32, Output = f32> + ?Sized,
+{
+    points.clear();
+    let len = buffer.len();
+    let value = |i: usize| buffer.get(i).copied().filter(|value| value.is_finite());
+    let (mut min, mut max) = scaled_extent((0..len).filter_map(value), ValueScaling::Linear)?;
+    if let Some(baseline) = baseline.filter(|baseline| baseline.is_finite()) {
+        min = min.min(baseline);
+        max = max.max(baseline);
+    }
+    let extent = (min, max);
+    let middle = (min + max) / 2.;
+
+    let columns = columns.clamp(1, len);
+    let last = columns - 1;
+    for column in 0..columns {
+        let picked = match column == last {
+            true => value(len - 1),
+            false => (column * len / columns..(column + 1) * len / columns)
+                .filter_map(value)
+                .max_by(|a, b| (a - middle).abs().total_cmp(&(b - middle).abs())),
+        };
+        if let Some(picked) = picked {
+            let x = match last {
+                0 => 1.,
+                _ => column as f32 / last as f32,
+            };
+            points.push((x, normalized(extent, picked)));
+        }
+    }
+    Some(extent)
+}
+
+/// Maps a value into the extent of a sparkline; A flat trace lies in its middle.
+pub(crate) fn normalized((min, max): (f32, f32), value: f32) -> f32 {
+    match max > min {
+        true => (value - min) / (max - min),
+        false => 0.5,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalized, sparkline_points};
+    use crate::alloc_counter::allocations;
+    use crate::utils::RingBuffer;
+
+    fn buffer(values: &[f32]) -> RingBuffer<f32> {
+        let mut buffer = RingBuffer::new(values.len());
+        for value in values {
+            buffer.enqueue(*value);
+        }
+        buffer
+    }
+
+    #[test]
+    fn empty_buffer() {
+        let mut points = vec![(0.5, 0.5)];
+        assert_eq!(
+            sparkline_points(&mut points, &RingBuffer::<f32>::new(0), 60, None),
+            None
+        );
+        assert!(points.is_empty());
+    }
+
+    #[test]
+    fn partially_filled_buffer() {
+        // Slots that were never written hold silence
+        let mut values = RingBuffer::<f32>::new(4);
+        values.enqueue(1.);
+        let mut points = Vec::new();
+        let extent = sparkline_points(&mut points, &values, 60, None);
+        assert_eq!(extent, Some((0., 1.)));
+        assert_eq!(
+            points,
+            vec![(0., 0.), (1. / 3., 0.), (2. / 3., 0.), (1., 1.)]
+        );
+    }
+
+    #[test]
+    fn filled_buffer() {
+        let values: Vec<f32> = (0..120).map(|i| i as f32).collect();
+        let mut points = Vec::new();
+        let extent = sparkline_points(&mut points, &buffer(&values), 60, None);
+        assert_eq!(extent, Some((0., 119.)));
+        assert_eq!(points.len(), 60);
+        assert_eq!(points.first(), Some(&(0., 0.)));
+        assert_eq!(points.last(), Some(&(1., 1.)));
+        assert!(points.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn short_buffers() {
+        let mut points = Vec::new();
+        assert_eq!(sparkline_points(&mut points, &buffer(&[]), 60, None), None);
+        assert!(points.is_empty());
+        assert_eq!(
+            sparkline_points(&mut points, &buffer(&[f32::NAN]), 60, None),
+            None
+        );
+
+        // Fewer values than columns get a point each, across the whole width
+        let extent = sparkline_points(&mut points, &buffer(&[1., 3., 2.]), 60, None);
+        assert_eq!(extent, Some((1., 3.)));
+        assert_eq!(points, vec![(0., 0.), (0.5, 1.), (1., 0.5)]);
+
+        // A single or flat value lies in the middle
+        sparkline_points(&mut points, &buffer(&[0.7]), 60, None);
+        assert_eq!(points, vec![(1., 0.5)]);
+        sparkline_points(&mut points, &buffer(&[0.7; 4]), 60, None);
+        assert!(points.iter().all(|(_, y)| *y == 0.5));
+    }
+
+    #[test]
+    fn decimation() {
+        // A single sample peak in a buffer that's far wider than the sparkline
+        let mut values = vec![0.5; 800];
+        values[333] = 1.;
+        values[799] = 0.25;
+        let mut points = Vec::new();
+        sparkline_points(&mut points, &buffer(&values), 60, None);
+
+        assert_eq!(points.len(), 60);
+        assert!(points.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(points.iter().filter(|(_, y)| *y == 1.).count(), 1);
+        assert_eq!(points.last(), Some(&(1., 0.)));
+
+        // Non-finite values are skipped, along with columns that only hold those
+        values[..400].fill(f32::NAN);
+        sparkline_points(&mut points, &buffer(&values), 60, None);
+        assert_eq!(points.len(), 30);
+        assert!(points.iter().all(|(x, y)| x.is_finite() && y.is_finite()));
+    }
+
+    #[test]
+    fn baseline() {
+        let mut points = Vec::new();
+        let extent = sparkline_points(&mut points, &buffer(&[0.2, 0.6, 0.4]), 60, Some(0.));
+        assert_eq!(extent, Some((0., 0.6)));
+        assert_eq!(normalized(extent.unwrap(), 0.), 0.);
+        assert!((points[2].1 - 2. / 3.).abs() < 1e-6);
+
+        // A baseline inside the values doesn't change the extent
+        let extent = sparkline_points(&mut points, &buffer(&[-1., 1.]), 60, Some(0.5));
+        assert_eq!(extent, Some((-1., 1.)));
+        assert_eq!(normalized(extent.unwrap(), 0.5), 0.75);
+    }
+
+    #[test]
+    fn redrawing_doesnt_allocate() {
+        let values: Vec<f32> = (0..800).map(|i| (i as f32 * 0.1).sin()).collect();
+        let buffer = buffer(&values);
+        let mut points = Vec::new();
+        sparkline_points(&mut points, &buffer, 60, Some(0.));
+        assert_eq!(
+            allocations(|| {
+                sparkline_points(&mut points, &buffer, 60, Some(0.));
+            }),
+            0
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use crate::utils::{NormalizedRead, ValueScaling};
+
+pub enum SpectrumAnalyzerVariant {
+    BAR,
+    LINE,
+}
+
+/// How the bins of a spectrum are mapped onto a spectrum analyzer.
+#[derive(Clone, Copy)]
+pub(crate) struct SpectrumScale {
+    pub frequency_scaling: ValueScaling,
+    pub frequency_range: (f32, f32),
+    pub magnitude_scaling: ValueScaling,
+    pub magnitude_range: (f32, f32),
+    /// The slope in dB/oct, if one is set.
+    pub slope: Option<f32>,
+}
+
+/// Fills `points` with the positions of a spectrum's bins, as fractions of the
+/// analyzer's width and height - measured from its bottom.
+///
+/// Bars are placed at every bin, and those outside of the frequency range end up
+/// at its edges. The line starts at the left edge, at the level of the last bin
+/// below the frequency range, and ends at the last bin inside of it.
+pub(crate) fn spectrum_points(
+    points: &mut Vec<(f32, f32)>,
+    spectrum: &[f32],
+    half_nyquist: f32,
+    variant: &SpectrumAnalyzerVariant,
+    scale: &SpectrumScale,
+) {
+    points.clear();
+    let (min_freq, max_freq) = scale.frequency_range;
+    let frequency = |freq: f32| {
+        scale
+            .frequency_scaling
+            .value_to_normalized(freq, min_freq, max_freq)
+    };
+    let magnitudes = NormalizedRead::new(&(), scale.magnitude_range, scale.magnitude_scaling);
+    let level = |magnitude: f32| magnitudes.normalize_value(magnitude);
+
+    // This will be used to normalize the magnitudes if a slope gets applied to them
+    let magnitude_slope_divisor = scale
+        .slope
+        .map_or(0., |slope| half_nyquist.log2().powf(slope) / slope);
+    let sloped = |magnitude: f32, freq: f32| match scale.slope {
+        Some(slope) => {
+            level(magnitude * ((freq + 1.).log2().powf(slope) / magnitude_slope_divisor))
+        }
+        None => level(magnitude),
+    };
+    let bin_freq = |bin_idx: usize| (bin_idx as f32 / spectrum.len() as f32) * half_nyquist;
+
+    match variant {
+        SpectrumAnalyzerVariant::BAR => {
+            points.extend(spectrum.iter().enumerate().map(|(bin_idx, m)| {
+                let freq = bin_freq(bin_idx);
+                (frequency(freq), sloped(*m, freq))
+            }));
+        }
+        SpectrumAnalyzerVariant::LINE => {
+            let Some(first) = spectrum.get(1) else {
+                return;
+            };
+            points.push((0., level(*first)));
+
+            for (bin_idx, m) in spectrum.iter().skip(1).enumerate() {
+                let freq = bin_freq(bin_idx);
+                let magnitude = sloped(*m, freq);
+
+                // Skip frequencies that are out of range
+                if freq < min_freq {
+                    points[0] = (0., magnitude);
+                    continue;
+                }
+                if freq > max_freq {
+                    break;
+                }
+
+                points.push((frequency(freq), magnitude));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{spectrum_points, SpectrumAnalyzerVariant, SpectrumScale};
+    use crate::utils::ValueScaling;
+
+    const SCALE: SpectrumScale = SpectrumScale {
+        frequency_scaling: ValueScaling::Linear,
+        frequency_range: (0., 1000.),
+        magnitude_scaling: ValueScaling::Linear,
+        magnitude_range: (0., 1.),
+        slope: None,
+    };
+
+    // A spectrum whose bins lie 250 Hz apart
+    fn points(
+        spectrum: &[f32],
+        variant: SpectrumAnalyzerVariant,
+        scale: SpectrumScale,
+    ) -> Vec<(f32, f32)> {
+        let mut points = Vec::new();
+        let half_nyquist = 250. * spectrum.len() as f32;
+        spectrum_points(&mut points, spectrum, half_nyquist, &variant, &scale);
+        points
+    }
+
+    #[test]
+    fn empty_buffer() {
+        assert!(points(&[], SpectrumAnalyzerVariant::BAR, SCALE).is_empty());
+        assert!(points(&[], SpectrumAnalyzerVariant::LINE, SCALE).is_empty());
+        // The line needs at least two bins
+        assert!(points(&[0.5], SpectrumAnalyzerVariant::LINE, SCALE).is_empty());
+    }
+
+    #[test]
+    fn partially_filled_buffer() {
+        // Only the lowest bins carry any energy yet
+        let spectrum = [0.5, 0.5, 0., 0.];
+        assert_eq!(
+            points(&spectrum, SpectrumAnalyzerVariant::BAR, SCALE),
+            vec![(0., 0.5), (0.25, 0.5), (0.5, 0.), (0.75, 0.)]
+        );
+        assert_eq!(
+            points(&spectrum, SpectrumAnalyzerVariant::LINE, SCALE),
+            vec![(0., 0.5), (0., 0.5), (0.25, 0.), (0.5, 0.)]
+        );
+    }
+
+    #[test]
+    fn filled_buffer() {
+        // The bins outside of the frequency range pin the line to the left edge, and
+        // stack the bars up at the edges
+        let scale = SpectrumScale {
+            frequency_range: (300., 600.),
+            ..SCALE
+        };
+        let spectrum = [1.; 4];
+        let line = points(&spectrum, SpectrumAnalyzerVariant::LINE, scale);
+        assert_eq!(line.len(), 2);
+        assert_eq!(line[0], (0., 1.));
+        assert!((line[1].0 - 2. / 3.).abs() < 1e-6);
+
+        let bars = points(&spectrum, SpectrumAnalyzerVariant::BAR, scale);
+        let xs: Vec<f32> = bars.iter().map(|(x, _)| *x).collect();
+        assert_eq!(xs[..2], [0., 0.]);
+        assert!((xs[2] - 2. / 3.).abs() < 1e-6);
+        assert_eq!(xs[3], 1.);
+        assert!(bars.iter().all(|(_, y)| *y == 1.));
+
+        // A slope raises the highs relative to the lows
+        let sloped = points(
+            &[0.25; 4],
+            SpectrumAnalyzerVariant::BAR,
+            SpectrumScale {
+                slope: Some(4.5),
+                ..SCALE
+            },
+        );
+        assert!(sloped.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+        assert!(sloped[3].1 > sloped[1].1);
+    }
+}
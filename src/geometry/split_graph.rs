@@ -0,0 +1,125 @@
+use super::Bounds;
+use crate::utils::{NormalizedRead, VisualizerBuffer};
+
+/// Returns the points of both halves of a split graph with the given bounds, the
+/// upper one first, or `None` if the buffers have no slots in common.
+///
+/// Only the overlapping prefix is drawn if the lengths differ.
+pub(crate) fn split_points<'a, B>(
+    bounds: Bounds,
+    upper: &'a NormalizedRead<'a, B>,
+    lower: &'a NormalizedRead<'a, B>,
+) -> Option<[impl Iterator<Item = (f32, f32)> + Clone + 'a; 2]>
+where
+    B: VisualizerBuffer<f32, Output = f32> + ?Sized,
+{
+    let len = upper.len().min(lower.len());
+    if len == 0 {
+        return None;
+    }
+
+    let center = bounds.y + bounds.h / 2.;
+    let extent = bounds.h / 2.;
+    Some([(upper, -1.), (lower, 1.)].map(|(values, direction)| {
+        half_points(
+            (0..len).map(move |i| values.normalized(i)),
+            (bounds.x, bounds.w),
+            center,
+            direction * extent,
+        )
+    }))
+}
+
+/// Maps the normalized values of one half to points.
+///
+/// The values are spread evenly across the width, and extend from the center by
+/// `extent` - negative extents go up.
+fn half_points(
+    values: impl ExactSizeIterator<Item = f32> + Clone,
+    (x, w): (f32, f32),
+    center: f32,
+    extent: f32,
+) -> impl Iterator<Item = (f32, f32)> + Clone {
+    let width_delta = w / values.len() as f32;
+    values
+        .enumerate()
+        .map(move |(i, value)| (x + width_delta * i as f32, center + extent * value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{half_points, split_points};
+    use crate::geometry::Bounds;
+    use crate::utils::{NormalizedRead, RingBuffer, ValueScaling};
+
+    const BOUNDS: Bounds = Bounds {
+        x: 10.,
+        y: 20.,
+        w: 100.,
+        h: 100.,
+    };
+
+    fn buffer(size: usize, values: &[f32]) -> RingBuffer<f32> {
+        let mut buffer = RingBuffer::new(size);
+        for value in values {
+            buffer.enqueue(*value);
+        }
+        buffer
+    }
+
+    fn halves(upper: &RingBuffer<f32>, lower: &RingBuffer<f32>) -> Option<[Vec<(f32, f32)>; 2]> {
+        let upper = NormalizedRead::new(upper, (0., 1.), ValueScaling::Linear);
+        let lower = NormalizedRead::new(lower, (0., 1.), ValueScaling::Linear);
+        split_points(BOUNDS, &upper, &lower).map(|halves| halves.map(|points| points.collect()))
+    }
+
+    #[test]
+    fn empty_buffer() {
+        assert_eq!(halves(&buffer(0, &[]), &buffer(0, &[])), None);
+        // Neither half is drawn while one of the buffers is empty
+        assert_eq!(halves(&buffer(4, &[0.5; 4]), &buffer(0, &[])), None);
+    }
+
+    #[test]
+    fn partially_filled_buffer() {
+        // Slots that were never written lie on the center line
+        let [upper, lower] = halves(&buffer(4, &[1.]), &buffer(4, &[0.5])).unwrap();
+        assert_eq!(upper, vec![(10., 70.), (35., 70.), (60., 70.), (85., 20.)]);
+        assert_eq!(lower, vec![(10., 70.), (35., 70.), (60., 70.), (85., 95.)]);
+    }
+
+    #[test]
+    fn filled_buffer() {
+        let [upper, lower] = halves(&buffer(8, &[0.5; 8]), &buffer(5, &[1.; 5])).unwrap();
+        assert_eq!(upper.len(), 5);
+        assert_eq!(lower.len(), 5);
+        assert!(upper.iter().all(|(_, y)| *y == 45.));
+        assert!(lower.iter().all(|(_, y)| *y == 120.));
+        assert_eq!(upper[4].0, 90.);
+    }
+
+    #[test]
+    fn mirrored_halves() {
+        let values = [0.0, 0.5, 1.0, 0.25];
+
+        let upper = half_points(values.iter().copied(), (0., 100.), 50., -50.).collect::<Vec<_>>();
+        assert_eq!(upper, vec![(0., 50.), (25., 25.), (50., 0.), (75., 37.5)]);
+
+        let lower = half_points(values.iter().copied(), (0., 100.), 50., 50.).collect::<Vec<_>>();
+        assert_eq!(lower, vec![(0., 50.), (25., 75.), (50., 100.), (75., 62.5)]);
+    }
+
+    #[test]
+    fn overlapping_prefix() {
+        // The longer buffer is cut off, so both halves share the same x positions
+        let long = [0.5; 8];
+        let short = [0.5; 5];
+        let len = long.len().min(short.len());
+
+        let upper = half_points(long.iter().copied().take(len), (0., 100.), 50., -50.);
+        let lower = half_points(short.iter().copied().take(len), (0., 100.), 50., 50.);
+        let pairs = upper.zip(lower).collect::<Vec<_>>();
+        assert_eq!(pairs.len(), 5);
+        assert!(pairs.iter().all(|((ux, _), (lx, _))| ux == lx));
+    }
+}
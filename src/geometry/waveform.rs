@@ -0,0 +1,61 @@
+use super::Bounds;
+
+/// Returns the position of each of a waveform's samples inside the given bounds,
+/// from the first to the last.
+pub(crate) fn waveform_line(
+    bounds: Bounds,
+    samples: &[f32],
+) -> impl Iterator<Item = (f32, f32)> + '_ {
+    let Bounds { x, y, w, h } = bounds;
+    let width_delta = w / samples.len() as f32;
+
+    samples.iter().enumerate().map(move |(i, v)| {
+        (
+            x + width_delta * i as f32,
+            y + (h / 2.) * (1. - v.clamp(-1., 1.)),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::waveform_line;
+    use crate::geometry::Bounds;
+
+    const BOUNDS: Bounds = Bounds {
+        x: 10.,
+        y: 20.,
+        w: 100.,
+        h: 50.,
+    };
+
+    fn line(samples: &[f32]) -> Vec<(f32, f32)> {
+        waveform_line(BOUNDS, samples).collect()
+    }
+
+    #[test]
+    fn empty_buffer() {
+        assert!(line(&[]).is_empty());
+    }
+
+    #[test]
+    fn partially_filled_buffer() {
+        // Samples that weren't loaded yet are silent, and lie on the center line
+        assert_eq!(
+            line(&[1., -1., 0., 0.]),
+            vec![(10., 20.), (35., 70.), (60., 45.), (85., 45.)]
+        );
+    }
+
+    #[test]
+    fn filled_buffer() {
+        let samples: Vec<f32> = (0..100).map(|i| (i as f32 * 0.1).sin() * 2.).collect();
+        let line = line(&samples);
+        assert_eq!(line.len(), 100);
+        assert_eq!(line[0], (10., 45.));
+        assert!(line.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        // Samples past full scale are clamped to the bounds
+        assert!(line.iter().all(|(_, y)| (20. ..=70.).contains(y)));
+        assert!(line.iter().any(|(_, y)| *y == 20.));
+    }
+}
@@ -1,14 +1,147 @@
 //! Flexible, composable [VIZIA](https://github.com/vizia/vizia) views that you can
 //! use to make rich [nih-plug](https://github.com/robbert-vdh/nih-plug) plug-in UIs
 //! with ease.
+//!
+//! The crate is organized into:
+//!
+//! * [`visualizers`] - Views which visualize your plug-in's audio
+//! * [`controls`] - Views which change your plug-in's state
+//! * [`utils`] - The buffers your views read from, and the enums that configure them
+//! * [`theme`] - Stylesheets that style all of the views at once
+//!
+//! Most plug-ins only need the [`prelude`], which re-exports all of the above.
+//!
+//! The views, controls and themes are only built with the `vizia` feature, which is
+//! enabled by default. Without it, the crate only contains the buffers - e.g. for
+//! filling them on the audio thread of a plug-in whose editor lives elsewhere - and
+//! the views' geometry, which is tested without linking any GPU code:
+//!
+//! ```sh
+//! cargo test --no-default-features
+//! ```
 
+#[cfg(test)]
+mod alloc_counter;
+#[cfg(feature = "vizia")]
 pub mod controls;
+#[cfg_attr(not(feature = "vizia"), allow(dead_code, unused_imports))]
+mod geometry;
+#[cfg(feature = "vizia")]
+pub mod theme;
 pub mod utils;
+#[cfg(feature = "vizia")]
 pub mod visualizers;
 
+/// Re-exports every view, every buffer, and the enums that configure them.
+///
+/// ```
+/// use cyma::prelude::*;
+/// ```
 pub mod prelude {
+    // Views and their modifiers
+    #[cfg(feature = "vizia")]
     pub use crate::controls::*;
-    pub use crate::utils::ValueScaling;
-    pub use crate::utils::VisualizerBuffer;
+    #[cfg(feature = "vizia")]
     pub use crate::visualizers::*;
+
+    // Buffers
+    pub use crate::utils::{
+        BalanceBuffer, CountBuffer, DerivedBuffer, FeatureExtractor, HistogramBuffer, MinimaBuffer,
+        MultibandPeakBuffer, NoteActivity, NoteEventBuffer, PeakBuffer, RMSBuffer, RingBuffer,
+        SharedRange, SharedTier, SourceSelector, SpectrumInput, SpectrumOutput, StagedBlock,
+        StagedBuffer, TieredBuffer, TryLockBuffer, WaveformBuffer, WindowedBuffer,
+    };
+    #[cfg(feature = "vizia")]
+    pub use crate::utils::{BufferRevision, SnapshotSet, VisualizerLens};
+    pub use crate::utils::{BufferSnapshot, BufferTap, SnapshotExport, TapConsumer};
+    pub use crate::utils::{ChunkReduce, SharedRead, SharedWrite, VisualizerBuffer};
+    pub use crate::utils::{Diagnostics, DiagnosticsSnapshot, Overload, ProducerWatch};
+    pub use crate::utils::{Heartbeat, HeartbeatReading, IdleTracker, StalenessTracker};
+    pub use crate::utils::{Marker, MarkerParseError, MarkerStore};
+    pub use crate::utils::{SlotMessage, SlotReceiver, SlotRecvError};
+
+    // Configuration
+    pub use crate::utils::{
+        Aggregation, AutoRange, BallisticsPreset, BufferMode, ChannelDownmix, ChannelSource,
+        ConfigError, Feature, Features, NormalizedRead, OffScale, RangeSegments, Smoothing,
+        TapMode, ValueScaling, VisualizerConfig,
+    };
+
+    // Formatting
+    pub use crate::utils::{DecibelFormatter, FrequencyFormatter, PlainFormatter, ValueFormatter};
+}
+
+/// The views' location in earlier releases.
+#[deprecated(
+    since = "0.1.0",
+    note = "use `cyma::visualizers` or `cyma::prelude` instead"
+)]
+#[doc(hidden)]
+#[cfg(feature = "vizia")]
+pub mod editor {
+    pub mod views {
+        pub use crate::visualizers::*;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Stops compiling if anything is accidentally removed from the prelude
+    #[test]
+    #[allow(unused_imports)]
+    fn prelude_exports() {
+        use crate::prelude::{
+            Aggregation, AutoRange, BallisticsPreset, BufferMode, ChannelDownmix, ChannelSource,
+            ConfigError, Feature, Features, NormalizedRead, OffScale, RangeSegments, Smoothing,
+            TapMode, ValueScaling, VisualizerConfig,
+        };
+        use crate::prelude::{
+            BalanceBuffer, BufferSnapshot, BufferTap, ChunkReduce, CountBuffer, DerivedBuffer,
+            Diagnostics, DiagnosticsSnapshot, FeatureExtractor, HistogramBuffer, MinimaBuffer,
+            MultibandPeakBuffer, NoteActivity, NoteEventBuffer, Overload, PeakBuffer,
+            ProducerWatch, RMSBuffer, RingBuffer, SharedRange, SharedRead, SharedTier, SharedWrite,
+            SnapshotExport, SourceSelector, SpectrumInput, SpectrumOutput, StagedBlock,
+            StagedBuffer, TapConsumer, TieredBuffer, TryLockBuffer, VisualizerBuffer,
+            WaveformBuffer, WindowedBuffer,
+        };
+        use crate::prelude::{
+            DecibelFormatter, FrequencyFormatter, PlainFormatter, ValueFormatter,
+        };
+        use crate::prelude::{Heartbeat, HeartbeatReading, IdleTracker, StalenessTracker};
+        use crate::prelude::{Marker, MarkerParseError, MarkerStore};
+        use crate::prelude::{SlotMessage, SlotReceiver, SlotRecvError};
+    }
+
+    #[test]
+    #[cfg(feature = "vizia")]
+    #[allow(unused_imports)]
+    fn prelude_view_exports() {
+        use crate::prelude::{
+            ActivityStrip, Graph, GraphGroup, Grid, Histogram, Legend, Lissajous, LissajousGrid,
+            Meter, Oscilloscope, Sparkline, SpectrumAnalyzer, SplitGraph, UnitRuler, Waveform,
+        };
+        use crate::prelude::{
+            ActivityStripModifiers, AdaptiveQualityModifiers, AutoRangeModifiers, AxisModifiers,
+            ClearFadeModifiers, FillModifiers, GraphGroupModifiers, GraphModifiers, GridModifiers,
+            HistogramModifiers, LegendModifiers, OscilloscopeModifiers, PaddingModifiers,
+            RangeModifiers, SparklineModifiers, SpectrumAnalyzerModifiers, SplitGraphModifiers,
+            ThrottleModifiers, TimeCursorModifiers, UnitRulerModifiers, VariantModifiers,
+        };
+        use crate::prelude::{AdaptiveQuality, Degradation, DegradationOverride, FrameStats};
+        use crate::prelude::{
+            BufferRevision, OverlayRegistry, OverlaySlot, SnapshotSet, TimeCursor, VisualizerLens,
+        };
+        use crate::prelude::{
+            ColorMode, GraphDirection, GraphStyle, LabelRotation, OffScaleStyle, PartialSegments,
+            ReadoutPosition, SpectrumAnalyzerVariant, TraceStyle, TraceVisibility, WaveformStyle,
+        };
+        use crate::prelude::{IdleModifiers, IdleStyle, LivenessMonitor, StaleIndicatorModifiers};
+    }
+
+    #[test]
+    #[cfg(feature = "vizia")]
+    #[allow(deprecated, unused_imports)]
+    fn deprecated_paths() {
+        use crate::editor::views::{Graph, Oscilloscope};
+    }
 }
@@ -0,0 +1,119 @@
+//! Stylesheets that style every Cyma view at once.
+//!
+//! Each theme is a stylesheet, whose rules only apply below a view with the theme's
+//! class. Add the stylesheet once, and put the class on the editor's outermost view:
+//!
+//! ```
+//! create_vizia_editor(editor_state, ViziaTheming::default(), move |cx, _| {
+//!     cx.add_stylesheet(cyma::theme::dark()).expect("Failed to load the theme");
+//!
+//!     VStack::new(cx, |cx| {
+//!         // Your visualizers
+//!     })
+//!     .class(cyma::theme::DARK_CLASS);
+//! })
+//! ```
+//!
+//! Since every theme is scoped to its class, several of them can be added at once,
+//! and switched at runtime by toggling the classes - see the `theming` example.
+//!
+//! Styles that are set on a view directly, like `.color()`, take precedence over
+//! the theme's.
+//!
+//! # Style properties
+//!
+//! The views read the following properties, which are all that a theme needs to
+//! set:
+//!
+//! | Element | `color` | `background-color` |
+//! | --- | --- | --- |
+//! | `cyma-graph`, `cyma-split-graph`, `cyma-histogram`, `cyma-spectrum-analyzer` | The trace | The fill below it |
+//! | `cyma-oscilloscope` | The outline of the waveform | Its fill |
+//! | `cyma-meter` | The level line | The fill below it |
+//! | `cyma-grid` | The lines | - |
+//! | `cyma-unit-ruler`, `cyma-legend` | The labels, along with `font-size` | - |
+//! | `cyma-lissajous` | The dots | - |
+//! | `cyma-lissajous-grid` | The lines | The diamond behind them |
+//! | `cyma-waveform`, `cyma-activity-strip` | The waveform and the notes | - |
+//!
+//! See [`elements`](crate::visualizers::elements) for the element of each view.
+//!
+//! Graphs and meters with a [variant class](crate::visualizers::VariantModifiers)
+//! are drawn in a subtler or a brighter color.
+//!
+//! Both themes also style grids with the `minor` class in a subtler color, so that a
+//! second [`Grid`](crate::visualizers::Grid) can draw the minor lines; See
+//! [`minor()`](crate::visualizers::GridModifiers::minor):
+//!
+//! ```
+//! Grid::new(cx, ValueScaling::Linear, (-32., 8.), vec![0.0, -12.0, -24.0], Orientation::Horizontal);
+//! Grid::new(cx, ValueScaling::Linear, (-32., 8.), vec![-6.0, -18.0, -30.0], Orientation::Horizontal)
+//!     .minor();
+//! ```
+
+/// The class that [`dark()`] applies below.
+pub const DARK_CLASS: &str = "cyma-dark";
+
+/// The class that [`light()`] applies below.
+pub const LIGHT_CLASS: &str = "cyma-light";
+
+/// Returns a dark "studio" theme, for views on a near-black background.
+///
+/// Its rules apply below a view with the [`DARK_CLASS`].
+pub fn dark() -> &'static str {
+    include_str!("dark.css")
+}
+
+/// Returns a light theme, for views on an off-white background.
+///
+/// Its rules apply below a view with the [`LIGHT_CLASS`].
+pub fn light() -> &'static str {
+    include_str!("light.css")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dark, light, DARK_CLASS, LIGHT_CLASS};
+    use crate::visualizers::elements;
+
+    // The elements that every theme styles
+    const ELEMENTS: [&str; 12] = [
+        elements::GRAPH,
+        elements::SPLIT_GRAPH,
+        elements::SPECTRUM_ANALYZER,
+        elements::HISTOGRAM,
+        elements::OSCILLOSCOPE,
+        elements::METER,
+        elements::GRID,
+        elements::UNIT_RULER,
+        elements::LISSAJOUS,
+        elements::LISSAJOUS_GRID,
+        elements::WAVEFORM,
+        elements::ACTIVITY_STRIP,
+    ];
+
+    #[test]
+    fn themes_style_every_view() {
+        for (theme, class) in [(dark(), DARK_CLASS), (light(), LIGHT_CLASS)] {
+            for element in ELEMENTS {
+                let selector = format!(".{} {} {{", class, element);
+                assert!(
+                    theme.contains(&selector),
+                    "{} is missing {}",
+                    class,
+                    element
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rules_are_scoped() {
+        for (theme, class) in [(dark(), DARK_CLASS), (light(), LIGHT_CLASS)] {
+            let scope = format!(".{}", class);
+            for line in theme.lines().filter(|line| line.ends_with('{')) {
+                assert!(line.starts_with(&scope), "{:?} isn't scoped", line);
+            }
+        }
+    }
+}
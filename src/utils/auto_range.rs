@@ -0,0 +1,233 @@
+use std::time::Instant;
+
+use super::ValueScaling;
+use nih_plug::util::{gain_to_db, MINUS_INFINITY_GAIN};
+
+/// Range that automatically follows the values being displayed.
+///
+/// Whenever a value lies outside of the range, the range expands to include it
+/// immediately. Once the values settle inside of a smaller range, the range slowly
+/// contracts towards them, so the view doesn't pump along with the signal.
+///
+/// The range is expressed relative to the scaling, just like the range of the views
+/// - e.g. in dB for [`ValueScaling::Decibels`].
+///
+/// An `AutoRange` is driven by a single [`Graph`](crate::visualizers::Graph), using
+/// [`auto_range()`](crate::visualizers::GraphModifiers::auto_range). Other views can
+/// follow the same range, so that grids and unit rulers line up with the graph.
+///
+/// ```
+/// // Starts at -32 to 8 dB, contracts with a time constant of 2 seconds, and snaps
+/// // to multiples of 6 dB
+/// let auto_range = Arc::new(Mutex::new(AutoRange::new((-32.0, 8.0), 2.0).with_snap(6.0)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct AutoRange {
+    range: (f32, f32),
+    release: f32,
+    snap: Option<f32>,
+    min_span: f32,
+    last_update: Option<Instant>,
+}
+
+impl AutoRange {
+    /// Creates a new `AutoRange`.
+    ///
+    /// * `initial` - The range that is displayed until there are values to follow
+    /// * `release` - The time constant of the contraction, in seconds
+    ///
+    /// The range never contracts below a tenth of the initial span; See
+    /// [`with_min_span()`](Self::with_min_span).
+    pub fn new(initial: (f32, f32), release: f32) -> Self {
+        Self {
+            range: initial,
+            release,
+            snap: None,
+            min_span: (initial.1 - initial.0) / 10.,
+            last_update: None,
+        }
+    }
+
+    /// Snaps the displayed range outwards to multiples of `step` - e.g. `6.0` for
+    /// multiples of 6 dB.
+    pub fn with_snap(mut self, step: f32) -> Self {
+        self.snap = Some(step);
+        self
+    }
+
+    /// Sets the smallest span the range contracts to.
+    pub fn with_min_span(mut self, min_span: f32) -> Self {
+        self.min_span = min_span;
+        self
+    }
+
+    /// Sets the time constant of the contraction, in seconds.
+    pub fn set_release(self: &mut Self, release: f32) {
+        self.release = release;
+    }
+
+    /// Returns the displayed range, including the snapping.
+    pub fn range(self: &Self) -> (f32, f32) {
+        match self.snap {
+            Some(step) => (
+                (self.range.0 / step).floor() * step,
+                (self.range.1 / step).ceil() * step,
+            ),
+            None => self.range,
+        }
+    }
+
+    /// Jumps to the given range, dropping the animation.
+    pub fn reset(self: &mut Self, range: (f32, f32)) {
+        self.range = range;
+        self.last_update = None;
+    }
+
+    /// Advances the range by `dt` seconds, towards the minimum and maximum of the
+    /// values that are currently displayed.
+    ///
+    /// If there are no values to follow, e.g. because the buffer only contains
+    /// silence, the range is held. Returns the displayed range.
+    pub fn update(self: &mut Self, values: Option<(f32, f32)>, dt: f32) -> (f32, f32) {
+        let Some((min, max)) = values else {
+            return self.range();
+        };
+
+        // The target is padded around its center if it's too narrow
+        let pad = ((self.min_span - (max - min)) / 2.).max(0.);
+        let target = (min - pad, max + pad);
+
+        let contraction = if self.release > 0. {
+            1. - (-dt / self.release).exp()
+        } else {
+            1.
+        };
+
+        self.range.0 = if target.0 < self.range.0 {
+            target.0
+        } else {
+            self.range.0 + (target.0 - self.range.0) * contraction
+        };
+        self.range.1 = if target.1 > self.range.1 {
+            target.1
+        } else {
+            self.range.1 + (target.1 - self.range.1) * contraction
+        };
+
+        self.range()
+    }
+
+    /// Advances the range by the time that passed since the last update.
+    #[cfg_attr(not(feature = "vizia"), allow(dead_code))]
+    pub(crate) fn update_now(self: &mut Self, values: Option<(f32, f32)>) -> (f32, f32) {
+        let now = Instant::now();
+        let dt = self
+            .last_update
+            .map_or(0., |last| now.duration_since(last).as_secs_f32());
+        self.last_update = Some(now);
+        self.update(values, dt)
+    }
+}
+
+/// Returns the minimum and maximum of the given values, relative to the scaling.
+///
+/// Values that can't be displayed, such as silence on a decibel scale, are skipped.
+pub(crate) fn scaled_extent(
+    values: impl IntoIterator<Item = f32>,
+    scaling: ValueScaling,
+) -> Option<(f32, f32)> {
+    values
+        .into_iter()
+        .filter_map(|value| match scaling {
+            ValueScaling::Decibels if value.abs() <= MINUS_INFINITY_GAIN => None,
+            ValueScaling::Decibels => Some(gain_to_db(value.abs())),
+            _ => Some(value),
+        })
+        .filter(|value| value.is_finite())
+        .fold(None, |extent, value| match extent {
+            Some((min, max)) => Some((value.min(min), value.max(max))),
+            None => Some((value, value)),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scaled_extent, AutoRange};
+    use crate::utils::ValueScaling;
+
+    fn assert_range(actual: (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-3 && (actual.1 - expected.1).abs() < 1e-3,
+            "expected {:?}, got {:?}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn expands_immediately() {
+        let mut auto_range = AutoRange::new((-30., 0.), 1.0);
+        assert_range(auto_range.update(Some((-40., 6.)), 0.01), (-40., 6.));
+        // Expanding further only affects one side
+        assert_range(auto_range.update(Some((-20., 12.)), 0.0), (-40., 12.));
+    }
+
+    #[test]
+    fn contracts_slowly() {
+        let mut auto_range = AutoRange::new((-40., 0.), 1.0);
+
+        // After one time constant, the range has covered ~63% of the way
+        for _ in 0..100 {
+            auto_range.update(Some((-20., -10.)), 0.01);
+        }
+        let contraction = 1. - (-1.0f32).exp();
+        assert_range(
+            auto_range.range(),
+            (-40. + 20. * contraction, -10. * contraction),
+        );
+
+        // Eventually, it settles on the values
+        for _ in 0..2000 {
+            auto_range.update(Some((-20., -10.)), 0.01);
+        }
+        assert_range(auto_range.range(), (-20., -10.));
+
+        // A louder value expands the range again right away
+        assert_range(auto_range.update(Some((-20., -3.)), 0.01), (-20., -3.));
+    }
+
+    #[test]
+    fn holds_without_values() {
+        let mut auto_range = AutoRange::new((-40., 0.), 1.0);
+        auto_range.update(Some((-20., -10.)), 0.5);
+        let held = auto_range.range();
+        assert_range(auto_range.update(None, 10.0), held);
+    }
+
+    #[test]
+    fn min_span() {
+        let mut auto_range = AutoRange::new((-40., 0.), 0.0).with_min_span(12.);
+        assert_range(auto_range.update(Some((-10., -10.)), 0.1), (-16., -4.));
+    }
+
+    #[test]
+    fn snapping() {
+        let mut auto_range = AutoRange::new((-40., 0.), 1.0).with_snap(6.);
+        assert_range(auto_range.range(), (-42., 0.));
+        assert_range(auto_range.update(Some((-40., 1.)), 0.0), (-42., 6.));
+    }
+
+    #[test]
+    fn extent() {
+        assert_eq!(
+            scaled_extent([0.5, -1.0, 0.0, 0.25], ValueScaling::Decibels)
+                .map(|(min, max)| (min.round(), max.round())),
+            Some((-12., 0.))
+        );
+        assert_eq!(
+            scaled_extent([0.5, -1.0, 0.25], ValueScaling::Linear),
+            Some((-1.0, 0.5))
+        );
+        assert_eq!(scaled_extent([0.0], ValueScaling::Decibels), None);
+    }
+}
@@ -0,0 +1,344 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::Diagnostics;
+
+/// Mirrors the data of a buffer to a background thread.
+///
+/// Some analyses - such as autocorrelation for pitch tracking, or long FFTs - are
+/// too expensive to run on either the audio or the UI thread. A `BufferTap` can be
+/// attached to a buffer on the audio thread, after which it also pushes the buffer's
+/// data into a bounded queue. A thread of your own reads the data using the
+/// [`TapConsumer`], and can feed its results into another buffer for the editor to
+/// display.
+///
+/// Pushing only writes to the queue - it never allocates, locks or makes a syscall.
+/// If the consumer falls behind, the oldest data is overwritten, which the consumer
+/// counts as [dropped](TapConsumer::dropped).
+///
+/// ```
+/// let (tap, mut consumer) = BufferTap::new(16384, TapMode::Samples);
+/// peak_buffer.lock().unwrap().set_tap(Some(tap));
+///
+/// std::thread::spawn(move || loop {
+///     match consumer.recv_timeout(Duration::from_millis(100)) {
+///         Ok(sample) => { /* analyze */ }
+///         Err(RecvTimeoutError::Timeout) => continue,
+///         // The tap was dropped along with the buffer
+///         Err(RecvTimeoutError::Disconnected) => break,
+///     }
+/// });
+/// ```
+pub struct BufferTap {
+    shared: Arc<Shared>,
+    mode: TapMode,
+    // The number of values that were ever pushed
+    written: usize,
+}
+
+/// Determines which data a [`BufferTap`] pushes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TapMode {
+    /// The finalized elements of the buffer, e.g. one peak per element.
+    #[default]
+    Slots,
+    /// The raw samples the buffer is fed, before they are aggregated.
+    Samples,
+}
+
+/// Reads the data pushed by a [`BufferTap`], usually on a background thread.
+pub struct TapConsumer {
+    shared: Arc<Shared>,
+    // The number of values that were read or dropped
+    read: usize,
+    dropped: u64,
+    // Counts the dropped values
+    diagnostics: Diagnostics,
+}
+
+// Each slot holds the value's bits along with the lower 32 bits of its position, so
+// that the consumer can tell whether a slot was written yet, or already overwritten
+struct Shared {
+    slots: Box<[AtomicU64]>,
+    written: AtomicUsize,
+}
+
+// How long the consumer sleeps in between checks while it waits for data
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+impl BufferTap {
+    /// Constructs a new `BufferTap` along with the consumer that reads from it.
+    ///
+    /// * `capacity` - The number of values the queue holds before the oldest ones are dropped
+    /// * `mode` - Whether the tap pushes the buffer's elements or its raw samples
+    ///
+    /// The queue is allocated up front.
+    pub fn new(capacity: usize, mode: TapMode) -> (BufferTap, TapConsumer) {
+        let shared = Arc::new(Shared {
+            // Position 0 is only ever written by the first value, so the initial slots
+            // count as not written yet
+            slots: (0..capacity.max(1))
+                .map(|i| AtomicU64::new(pack(i.wrapping_sub(capacity.max(1)), 0.)))
+                .collect(),
+            written: AtomicUsize::new(0),
+        });
+        (
+            BufferTap {
+                shared: shared.clone(),
+                mode,
+                written: 0,
+            },
+            TapConsumer {
+                shared,
+                read: 0,
+                dropped: 0,
+                diagnostics: Diagnostics::default(),
+            },
+        )
+    }
+
+    /// Returns which data the tap pushes.
+    pub fn mode(self: &Self) -> TapMode {
+        self.mode
+    }
+
+    /// Pushes a value into the queue, overwriting the oldest value if it's full.
+    pub fn push(self: &mut Self, value: f32) {
+        let slots = &self.shared.slots;
+        slots[self.written % slots.len()].store(pack(self.written, value), Ordering::Release);
+        self.written = self.written.wrapping_add(1);
+        self.shared.written.store(self.written, Ordering::Release);
+    }
+}
+
+impl TapConsumer {
+    /// Returns the next value, or `None` if the queue is empty.
+    pub fn try_recv(self: &mut Self) -> Option<f32> {
+        let slots = &self.shared.slots;
+        loop {
+            let (position, value) = unpack(slots[self.read % slots.len()].load(Ordering::Acquire));
+            // The difference between the slot's position and the one that is being read,
+            // which is negative if the slot wasn't written yet, and positive if it was
+            // overwritten
+            match position.wrapping_sub(self.read as u32) as i32 {
+                0 => {
+                    self.read = self.read.wrapping_add(1);
+                    return Some(value);
+                }
+                ahead if ahead > 0 => {
+                    // Skip to the oldest value that is still inside the queue
+                    let written = self.shared.written.load(Ordering::Acquire);
+                    let oldest = written.wrapping_sub(slots.len());
+                    // The position of the newest value may not be visible yet, in which
+                    // case the slot is skipped on its own
+                    let behind = (oldest.wrapping_sub(self.read) as isize).max(1) as usize;
+                    self.dropped += behind as u64;
+                    self.diagnostics.overwritten(behind as u64);
+                    self.read = self.read.wrapping_add(behind);
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    /// Waits for the next value, for up to `timeout`.
+    ///
+    /// Returns [`RecvTimeoutError::Disconnected`] once the [`BufferTap`] was dropped
+    /// and all of its values were read. Since pushing never wakes up the consumer,
+    /// this checks for new values every millisecond while it waits.
+    pub fn recv_timeout(self: &mut Self, timeout: Duration) -> Result<f32, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(value) = self.try_recv() {
+                return Ok(value);
+            }
+            if !self.is_connected() {
+                // Values pushed right before the tap was dropped are still read
+                return self.try_recv().ok_or(RecvTimeoutError::Disconnected);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            std::thread::sleep(POLL_INTERVAL.min(deadline - now));
+        }
+    }
+
+    /// Returns an iterator over the values that are inside the queue, without
+    /// waiting for more.
+    pub fn try_iter(self: &mut Self) -> impl Iterator<Item = f32> + '_ {
+        std::iter::from_fn(move || self.try_recv())
+    }
+
+    /// Returns the number of values that were overwritten before they could be read.
+    pub fn dropped(self: &Self) -> u64 {
+        self.dropped
+    }
+
+    /// Attaches a [`Diagnostics`] handle, which counts the dropped values as
+    /// overwritten.
+    pub fn set_diagnostics(self: &mut Self, diagnostics: Diagnostics) {
+        self.diagnostics = diagnostics;
+    }
+
+    /// Returns `true` as long as the [`BufferTap`] hasn't been dropped.
+    pub fn is_connected(self: &Self) -> bool {
+        Arc::strong_count(&self.shared) > 1
+    }
+}
+
+fn pack(position: usize, value: f32) -> u64 {
+    ((position as u32 as u64) << 32) | value.to_bits() as u64
+}
+
+fn unpack(slot: u64) -> (u32, f32) {
+    ((slot >> 32) as u32, f32::from_bits(slot as u32))
+}
+
+/// The tap attached to a buffer, if any.
+///
+/// A tap can only have a single consumer, so cloning a buffer doesn't clone its tap.
+#[derive(Default)]
+pub(crate) struct AttachedTap(Option<BufferTap>);
+
+impl AttachedTap {
+    pub(crate) fn set(self: &mut Self, tap: Option<BufferTap>) -> Option<BufferTap> {
+        std::mem::replace(&mut self.0, tap)
+    }
+
+    /// Returns `true` if a tap is attached that pushes the given kind of data.
+    pub(crate) fn pushes(self: &Self, mode: TapMode) -> bool {
+        self.0.as_ref().is_some_and(|tap| tap.mode == mode)
+    }
+
+    /// Pushes a value if a tap is attached that pushes the given kind of data.
+    pub(crate) fn push(self: &mut Self, mode: TapMode, value: f32) {
+        if let Some(tap) = &mut self.0 {
+            if tap.mode == mode {
+                tap.push(value);
+            }
+        }
+    }
+}
+
+impl Clone for AttachedTap {
+    fn clone(&self) -> Self {
+        Self(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BufferTap, TapMode};
+    use crate::utils::{PeakBuffer, VisualizerBuffer};
+    use std::sync::mpsc::RecvTimeoutError;
+    use std::time::Duration;
+
+    #[test]
+    fn in_order() {
+        let (mut tap, mut consumer) = BufferTap::new(8, TapMode::Samples);
+        assert_eq!(consumer.try_recv(), None);
+
+        for value in [1.0, 2.0, 3.0] {
+            tap.push(value);
+        }
+        assert_eq!(consumer.try_iter().collect::<Vec<_>>(), vec![1.0, 2.0, 3.0]);
+        assert_eq!(consumer.try_recv(), None);
+        assert_eq!(consumer.dropped(), 0);
+    }
+
+    #[test]
+    fn drops_oldest() {
+        let (mut tap, mut consumer) = BufferTap::new(4, TapMode::Samples);
+        for value in 0..10 {
+            tap.push(value as f32);
+        }
+        assert_eq!(
+            consumer.try_iter().collect::<Vec<_>>(),
+            vec![6.0, 7.0, 8.0, 9.0]
+        );
+        assert_eq!(consumer.dropped(), 6);
+
+        // Once caught up, nothing else is dropped
+        tap.push(10.0);
+        assert_eq!(consumer.try_recv(), Some(10.0));
+        assert_eq!(consumer.dropped(), 6);
+    }
+
+    #[test]
+    fn disconnects() {
+        let (mut tap, mut consumer) = BufferTap::new(4, TapMode::Samples);
+        tap.push(1.0);
+        assert_eq!(consumer.recv_timeout(Duration::from_millis(1)), Ok(1.0));
+        assert_eq!(
+            consumer.recv_timeout(Duration::from_millis(5)),
+            Err(RecvTimeoutError::Timeout)
+        );
+
+        // Values that were pushed before the tap was dropped are still read
+        tap.push(2.0);
+        drop(tap);
+        assert_eq!(consumer.recv_timeout(Duration::from_millis(1)), Ok(2.0));
+        assert_eq!(
+            consumer.recv_timeout(Duration::from_millis(1)),
+            Err(RecvTimeoutError::Disconnected)
+        );
+    }
+
+    #[test]
+    fn background_thread() {
+        let (mut tap, mut consumer) = BufferTap::new(1024, TapMode::Samples);
+        let reader = std::thread::spawn(move || {
+            let mut values = Vec::new();
+            loop {
+                match consumer.recv_timeout(Duration::from_millis(100)) {
+                    Ok(value) => values.push(value),
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            (values, consumer.dropped())
+        });
+
+        for value in 0..100_000 {
+            tap.push(value as f32);
+        }
+        drop(tap);
+
+        // Whatever was read arrived in order, and everything else was counted as dropped
+        let (values, dropped) = reader.join().unwrap();
+        assert!(values.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(values.last(), Some(&99_999.0));
+        assert_eq!(values.len() as u64 + dropped, 100_000);
+    }
+
+    #[test]
+    fn peak_buffer() {
+        // 4 samples per element
+        let mut buffer = PeakBuffer::new(8, 1.0, 0.0);
+        buffer.set_sample_rate(32.);
+
+        let (tap, mut slots) = BufferTap::new(64, TapMode::Slots);
+        buffer.set_tap(Some(tap));
+        for i in 0..12 {
+            buffer.enqueue(i as f32);
+        }
+        // The third element is finalized by the next sample
+        assert_eq!(slots.try_iter().collect::<Vec<_>>(), vec![3.0, 7.0]);
+
+        // Taps can be swapped at runtime
+        let (tap, mut samples) = BufferTap::new(64, TapMode::Samples);
+        assert!(buffer.set_tap(Some(tap)).is_some());
+        buffer.enqueue(-1.0);
+        assert_eq!(samples.try_iter().collect::<Vec<_>>(), vec![-1.0]);
+        assert_eq!(slots.try_recv(), None);
+        assert!(!slots.is_connected());
+
+        // Clones aren't tapped
+        let mut clone = buffer.clone();
+        clone.enqueue(0.5);
+        assert_eq!(samples.try_recv(), None);
+    }
+}
@@ -0,0 +1,413 @@
+use std::fmt::{self, Debug};
+use std::ops::{Index, IndexMut};
+
+use super::{
+    super::config_error::{check_duration, check_size, clamp_duration, ConfigError},
+    check_index,
+    downmix::{interleaved_frames, planar_frames},
+    slot_progress, BufferSnapshot, ChannelDownmix, RingBuffer, SnapshotExport, VisualizerBuffer,
+};
+
+/// Stores the balance between the left and the right channel over time.
+///
+/// For each element, the buffer sums up the energy of both channels, and stores how
+/// far it leans to either side: `(R - L) / (R + L)`, from -1 (only the left channel)
+/// through 0 (centered, or silent) to +1 (only the right channel). It's meant to be
+/// displayed by a [`bipolar()`](crate::visualizers::GraphModifiers::bipolar)
+/// [`Graph`](crate::visualizers::Graph) with a range of `(-1.0, 1.0)`.
+///
+/// Near silence, the balance would jump around wildly. The energy is compared to a
+/// floor of -80 dBFS instead, so that quiet elements lean towards the center.
+///
+/// Since the balance needs both channels, feed the buffer using
+/// [`enqueue_buffer()`](VisualizerBuffer::enqueue_buffer) or
+/// [`enqueue_stereo()`](Self::enqueue_stereo). Samples that are enqueued one by one
+/// using [`enqueue()`](VisualizerBuffer::enqueue) count as centered.
+///
+/// The `BalanceBuffer` needs to be provided a sample rate after initialization - do
+/// this inside your [`initialize()`](nih_plug::plugin::Plugin::initialize) function.
+#[derive(Clone, Default)]
+pub struct BalanceBuffer {
+    buffer: RingBuffer<f32>,
+    // Energy accumulators for the left and right channel
+    left_acc: f32,
+    right_acc: f32,
+    // The number of samples inside the accumulators
+    count: u32,
+    // The gap between elements of the buffer in samples
+    sample_delta: f32,
+    // Used to calculate the sample_delta
+    sample_rate: f32,
+    duration: f32,
+    // The current time, counts down from sample_delta to 0
+    t: f32,
+    // Whether the plug-in is rendering offline, in which case nothing is enqueued
+    offline: bool,
+}
+
+// The energy, per sample, below which the balance leans towards the center
+// (-80 dBFS)
+const ENERGY_FLOOR: f32 = 1e-8;
+
+impl BalanceBuffer {
+    /// Constructs a new `BalanceBuffer`.
+    ///
+    /// * `size` - The length of the buffer in samples; Usually, this can be kept < 2000
+    /// * `duration` - The duration (in seconds) of the audio data inside the buffer
+    ///
+    /// The buffer needs to be provided a sample rate after initialization - do this by
+    /// calling [`set_sample_rate`](Self::set_sample_rate) inside your
+    /// [`initialize()`](nih_plug::plugin::Plugin::initialize) function.
+    pub fn new(size: usize, duration: f32) -> Self {
+        Self {
+            buffer: RingBuffer::<f32>::new(size),
+            duration: clamp_duration("balance buffer", "duration", duration),
+            ..Default::default()
+        }
+    }
+
+    /// Constructs a new `BalanceBuffer`, or returns a [`ConfigError`] if the size is 0
+    /// or the duration isn't positive; See [`new()`](Self::new).
+    pub fn try_new(size: usize, duration: f32) -> Result<Self, ConfigError> {
+        check_size("balance buffer", "size", size)?;
+        check_duration("balance buffer", "duration", duration)?;
+        Ok(Self::new(size, duration))
+    }
+
+    /// Names the `BalanceBuffer`, which identifies it in panic messages and snapshots.
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.buffer.name_mut().set(name);
+        self
+    }
+
+    /// Sets the name of the `BalanceBuffer`; See [`with_name()`](Self::with_name).
+    pub fn set_name(self: &mut Self, name: String) {
+        self.buffer.name_mut().set(name);
+    }
+
+    /// Returns the name of the `BalanceBuffer`, if it has one.
+    pub fn name(self: &Self) -> Option<&str> {
+        self.buffer.name()
+    }
+
+    /// Sets the sample rate of the incoming audio.
+    ///
+    /// This function **clears** the buffer.
+    pub fn set_sample_rate(self: &mut Self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.update();
+        self.buffer.clear();
+    }
+
+    /// Sets the duration (in seconds) of the audio data inside the buffer.
+    ///
+    /// This function **clears** the buffer. A duration that isn't positive and finite
+    /// fails a debug assertion, and is clamped in release builds; Use
+    /// [`try_set_duration()`](Self::try_set_duration) to handle it instead.
+    pub fn set_duration(self: &mut Self, duration: f32) {
+        self.duration = clamp_duration("balance buffer", "duration", duration);
+        self.update();
+        self.buffer.clear();
+    }
+
+    /// Sets the duration (in seconds) of the audio data inside the buffer, or returns
+    /// a [`ConfigError`] if it isn't positive and finite.
+    ///
+    /// This function **clears** the buffer.
+    pub fn try_set_duration(self: &mut Self, duration: f32) -> Result<(), ConfigError> {
+        self.set_duration(check_duration("balance buffer", "duration", duration)?);
+        Ok(())
+    }
+
+    /// Hints whether the plug-in is rendering offline, which skips all enqueueing
+    /// while it is. See [`PeakBuffer::set_offline`](super::PeakBuffer::set_offline).
+    pub fn set_offline(self: &mut Self, offline: bool) {
+        self.offline = offline;
+    }
+
+    /// Enqueues a single stereo sample.
+    pub fn enqueue_stereo(self: &mut Self, left: f32, right: f32) {
+        if self.offline {
+            return;
+        }
+        self.t -= 1.0;
+        if self.t < 0.0 {
+            self.buffer.enqueue(self.balance());
+            self.reset_accumulators();
+            self.t += self.sample_delta;
+        }
+        self.left_acc += left * left;
+        self.right_acc += right * right;
+        self.count += 1;
+    }
+
+    // The balance of the accumulated samples
+    fn balance(self: &Self) -> f32 {
+        let floor = ENERGY_FLOOR * self.count as f32;
+        (self.right_acc - self.left_acc)
+            / (self.right_acc + self.left_acc + floor).max(f32::MIN_POSITIVE)
+    }
+
+    fn reset_accumulators(self: &mut Self) {
+        self.left_acc = 0.;
+        self.right_acc = 0.;
+        self.count = 0;
+    }
+
+    // Restarts the element that is being accumulated, since its length changed
+    fn update(self: &mut Self) {
+        self.sample_delta =
+            ((self.sample_rate as f64 * self.duration as f64) / self.buffer.len() as f64) as f32;
+        self.t = self.sample_delta;
+        self.reset_accumulators();
+    }
+}
+
+impl VisualizerBuffer<f32> for BalanceBuffer {
+    /// Enqueues a sample that is the same on both channels, i.e. centered.
+    fn enqueue(self: &mut Self, value: f32) {
+        self.enqueue_stereo(value, value);
+    }
+
+    /// Enqueues the first two channels of the buffer as the left and right channel.
+    ///
+    /// The channel is ignored, since the balance needs both. A mono buffer has no
+    /// balance, so nothing is enqueued.
+    fn enqueue_buffer(
+        self: &mut Self,
+        buffer: &mut nih_plug::buffer::Buffer,
+        _channel: Option<usize>,
+    ) {
+        debug_assert!(
+            buffer.channels() >= 2,
+            "a BalanceBuffer needs a stereo buffer, but got {} channel(s)",
+            buffer.channels()
+        );
+        if self.offline || buffer.channels() < 2 {
+            return;
+        }
+        let channels = buffer.as_slice_immutable();
+        for (left, right) in channels[0].iter().zip(channels[1].iter()) {
+            self.enqueue_stereo(*left, *right);
+        }
+    }
+
+    /// Enqueues the first two channels of each frame as the left and right channel.
+    ///
+    /// The downmix is ignored, since the balance needs both channels. Mono audio has
+    /// no balance, so nothing is enqueued.
+    fn enqueue_interleaved(self: &mut Self, data: &[f32], channels: usize, _: ChannelDownmix) {
+        debug_assert!(
+            channels >= 2,
+            "a BalanceBuffer needs stereo audio, but got {} channel(s)",
+            channels
+        );
+        let frames = interleaved_frames(data, channels);
+        if self.offline || channels < 2 {
+            return;
+        }
+        for frame in data[..frames * channels].chunks_exact(channels) {
+            self.enqueue_stereo(frame[0], frame[1]);
+        }
+    }
+
+    /// Enqueues the first two channels as the left and right channel; See
+    /// [`enqueue_interleaved()`](Self::enqueue_interleaved).
+    fn enqueue_planar(self: &mut Self, channels: &[&[f32]], _: ChannelDownmix) {
+        debug_assert!(
+            channels.len() >= 2,
+            "a BalanceBuffer needs stereo audio, but got {} channel(s)",
+            channels.len()
+        );
+        let frames = planar_frames(channels);
+        if self.offline || channels.len() < 2 {
+            return;
+        }
+        for i in 0..frames {
+            self.enqueue_stereo(channels[0][i], channels[1][i]);
+        }
+    }
+
+    fn len(self: &Self) -> usize {
+        self.buffer.len()
+    }
+
+    fn generation(self: &Self) -> Option<u64> {
+        Some(self.buffer.generation())
+    }
+
+    fn enqueued(self: &Self) -> Option<u64> {
+        Some(self.buffer.enqueued())
+    }
+
+    fn duration(self: &Self) -> Option<f32> {
+        Some(self.duration)
+    }
+
+    fn partial(self: &Self) -> Option<(f32, f32)> {
+        let progress = slot_progress(self.t, self.sample_delta)?;
+        Some((progress, self.balance()))
+    }
+
+    fn clear(self: &mut Self) {
+        self.buffer.clear();
+        self.reset_accumulators();
+    }
+
+    /// Grows the buffer, **clearing it**.
+    fn grow(self: &mut Self, size: usize) {
+        if self.buffer.len() == size {
+            return;
+        }
+        self.buffer.grow(size);
+        self.update();
+        self.buffer.clear();
+    }
+
+    /// Shrinks the buffer, **clearing it**.
+    fn shrink(self: &mut Self, size: usize) {
+        if self.buffer.len() == size {
+            return;
+        }
+        self.buffer.shrink(size);
+        self.update();
+        self.buffer.clear();
+    }
+}
+
+impl Index<usize> for BalanceBuffer {
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        check_index("balance buffer", self.name(), index, self.buffer.len());
+        &self.buffer[index]
+    }
+}
+impl IndexMut<usize> for BalanceBuffer {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        check_index("balance buffer", self.name(), index, self.buffer.len());
+        &mut self.buffer[index]
+    }
+}
+
+impl SnapshotExport for BalanceBuffer {
+    fn export(&self) -> BufferSnapshot {
+        BufferSnapshot {
+            name: self.name().map(str::to_string),
+            duration: Some(self.duration),
+            sample_rate: Some(self.sample_rate),
+            range: Some((-1., 1.)),
+            ..BufferSnapshot::new(
+                "BalanceBuffer",
+                &["balance"],
+                self.buffer.iter().map(|v| vec![*v]).collect(),
+            )
+        }
+    }
+}
+
+impl Debug for BalanceBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BalanceBuffer")
+            .field("name", &self.name())
+            .field("size", &self.buffer.len())
+            .field("duration", &self.duration)
+            .field("sample_rate", &self.sample_rate)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BalanceBuffer;
+    use crate::utils::buffers::test_support::{contents, interleave, test_signal, with_buffer};
+    use crate::utils::{ChannelDownmix, VisualizerBuffer};
+    use std::f32::consts::TAU;
+
+    // 10 elements of 100 samples each
+    fn balance(left: impl Fn(usize) -> f32, right: impl Fn(usize) -> f32) -> Vec<f32> {
+        let mut buffer = BalanceBuffer::new(10, 1.0);
+        buffer.set_sample_rate(1000.);
+        let tone = |i: usize| (i as f32 * TAU * 50. / 1000.).sin();
+        let mut channels = vec![
+            (0..1001).map(|i| left(i) * tone(i)).collect::<Vec<_>>(),
+            (0..1001).map(|i| right(i) * tone(i)).collect::<Vec<_>>(),
+        ];
+        with_buffer(&mut channels, |b| buffer.enqueue_buffer(b, None));
+        contents(&buffer)
+    }
+
+    #[test]
+    fn hard_panned() {
+        assert!(balance(|_| 0., |_| 1.).iter().all(|v| *v > 0.999));
+        assert!(balance(|_| 1., |_| 0.).iter().all(|v| *v < -0.999));
+    }
+
+    #[test]
+    fn centered() {
+        assert!(balance(|_| 0.5, |_| 0.5).iter().all(|v| v.abs() < 1e-6));
+
+        // A third of the energy on the left, two thirds on the right
+        let leaning = balance(|_| 1., |_| 2f32.sqrt());
+        assert!(leaning.iter().all(|v| (v - 1. / 3.).abs() < 1e-3));
+    }
+
+    #[test]
+    fn alternating_pan() {
+        // Pans from side to side twice a second, so every 5 elements
+        let pan = |i: usize| (i as f32 * TAU * 2. / 1000.).sin() * 0.5 + 0.5;
+        let values = balance(|i| (1. - pan(i)).sqrt(), |i| pan(i).sqrt());
+
+        let sign_changes = values.windows(2).filter(|w| w[0].signum() != w[1].signum());
+        assert!(sign_changes.count() >= 3, "{:?}", values);
+        assert!(values.iter().any(|v| *v > 0.5) && values.iter().any(|v| *v < -0.5));
+    }
+
+    #[test]
+    fn near_silence() {
+        // Hard-panned, but at -100 dB, which is below the floor
+        let values = balance(|_| 0., |_| 1e-5);
+        assert!(values.iter().all(|v| (0.0..0.01).contains(v)));
+        assert!(balance(|_| 0., |_| 0.).iter().all(|v| *v == 0.));
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn mono_is_ignored() {
+        let mut buffer = BalanceBuffer::new(10, 1.0);
+        buffer.set_sample_rate(1000.);
+        with_buffer(&mut [vec![1.; 1000]], |b| buffer.enqueue_buffer(b, None));
+        assert!(contents(&buffer).iter().all(|v| *v == 0.));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "needs a stereo buffer")]
+    fn mono_is_ignored() {
+        let mut buffer = BalanceBuffer::new(10, 1.0);
+        buffer.set_sample_rate(1000.);
+        with_buffer(&mut [vec![1.; 1000]], |b| buffer.enqueue_buffer(b, None));
+    }
+
+    #[test]
+    fn interleaved_and_planar() {
+        let new = || {
+            let mut buffer = BalanceBuffer::new(10, 1.0);
+            buffer.set_sample_rate(100.);
+            buffer
+        };
+        let mut channels = test_signal(3, 250);
+        let mut expected = new();
+        with_buffer(&mut channels, |b| expected.enqueue_buffer(b, None));
+
+        // The downmix doesn't matter, only the first two channels are read
+        let mut interleaved = new();
+        interleaved.enqueue_interleaved(&interleave(&channels), 3, ChannelDownmix::Channel(2));
+        assert_eq!(contents(&interleaved), contents(&expected));
+
+        let mut planar = new();
+        let slices = channels.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        planar.enqueue_planar(&slices, ChannelDownmix::Mean);
+        assert_eq!(contents(&planar), contents(&expected));
+    }
+}
@@ -0,0 +1,222 @@
+//! Standardized meter ballistics for the [`PeakBuffer`](super::PeakBuffer).
+//!
+//! A meter's ballistics - how quickly it rises and falls - are specified by the
+//! standard the meter follows. A [`BallisticsPreset`] configures a buffer to
+//! approximate one of them:
+//!
+//! ```
+//! let ppm_buffer = PeakBuffer::new(800, 10.0, 0.0).with_ballistics(BallisticsPreset::PpmType2);
+//! ```
+
+use super::super::decay::{decay_ms_from_db_per_second, decay_weight_per_sample};
+
+// The time constants of the PPM presets, in milliseconds, chosen so that a 5 kHz tone
+// burst as long as the standard's integration time reads 2 dB below a steady tone
+const PPM_TYPE_1_ATTACK: f32 = 1.33;
+const PPM_TYPE_2_ATTACK: f32 = 2.65;
+
+// A VU meter reaches 99% of its reading after 300 ms, which takes 4.6 time constants
+const VU_ATTACK: f32 = 300. / 4.60517;
+
+/// The ballistics of a standard meter type, applied to each sample before it is
+/// enqueued into a [`PeakBuffer`](super::PeakBuffer).
+///
+/// Every preset is a detector that rises towards louder samples with its attack
+/// time constant, and falls at its release rate otherwise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BallisticsPreset {
+    /// A digital sample-peak meter, as described by IEC 60268-18.
+    ///
+    /// Peaks are displayed immediately, and fall at `release` decibels per second.
+    DigitalPeak { release: f32 },
+    /// A Type I peak programme meter, as described by IEC 60268-10 (DIN 45406).
+    ///
+    /// Its integration time is 5 ms - a 10 ms tone burst reads 1 dB below a steady
+    /// tone. It falls back by 20 dB in 1.5 seconds.
+    PpmType1,
+    /// A Type II peak programme meter, as described by IEC 60268-10 (the BBC PPM).
+    ///
+    /// Its integration time is 10 ms - a 10 ms tone burst reads 2 dB below a steady
+    /// tone. It falls back by 24 dB in 2.8 seconds.
+    PpmType2,
+    /// A volume unit meter, as described by IEC 60268-17.
+    ///
+    /// It averages the rectified signal, reaching 99% of a steady tone's reading
+    /// after 300 ms and falling just as quickly. Unlike a real VU meter, it doesn't
+    /// overshoot. For a sine wave, it reads 2/π of the peak.
+    Vu,
+    /// Custom ballistics; See [`custom()`](Self::custom).
+    Custom { attack: f32, release: f32 },
+}
+
+impl BallisticsPreset {
+    /// Returns custom ballistics.
+    ///
+    /// * `attack` - The time constant with which the meter rises, in milliseconds; 0 rises immediately
+    /// * `release` - The rate at which the meter falls, in decibels per second
+    pub fn custom(attack: f32, release: f32) -> Self {
+        Self::Custom { attack, release }
+    }
+
+    /// Returns the time constant with which the meter rises, in milliseconds.
+    pub fn attack(&self) -> f32 {
+        match self {
+            Self::DigitalPeak { .. } => 0.,
+            Self::PpmType1 => PPM_TYPE_1_ATTACK,
+            Self::PpmType2 => PPM_TYPE_2_ATTACK,
+            Self::Vu => VU_ATTACK,
+            Self::Custom { attack, .. } => *attack,
+        }
+    }
+
+    /// Returns the rate at which the meter falls, in decibels per second.
+    pub fn release(&self) -> f32 {
+        match self {
+            Self::DigitalPeak { release } => *release,
+            Self::PpmType1 => 20. / 1.5,
+            Self::PpmType2 => 24. / 2.8,
+            // Falling with the attack's time constant
+            Self::Vu => 1000. * 20. * std::f32::consts::LOG10_E / VU_ATTACK,
+            Self::Custom { release, .. } => *release,
+        }
+    }
+}
+
+/// Applies a [`BallisticsPreset`] to the absolute values of a signal, one sample at
+/// a time.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Detector {
+    attack_weight: f32,
+    release_weight: f32,
+    level: f32,
+}
+
+impl Detector {
+    pub(crate) fn new(preset: BallisticsPreset, sample_rate: f32) -> Self {
+        let attack = preset.attack();
+        Self {
+            attack_weight: if attack > 0. {
+                (-1. / (attack / 1000. * sample_rate)).exp()
+            } else {
+                0.
+            },
+            release_weight: decay_weight_per_sample(
+                decay_ms_from_db_per_second(preset.release()),
+                sample_rate,
+            ),
+            level: 0.,
+        }
+    }
+
+    /// Feeds an absolute value into the detector, returning its new level.
+    pub(crate) fn process(self: &mut Self, value: f32) -> f32 {
+        let weight = if value > self.level {
+            self.attack_weight
+        } else {
+            self.release_weight
+        };
+        self.level = value + (self.level - value) * weight;
+        self.level
+    }
+
+    /// Returns the level after the detector is fed a number of silent samples.
+    pub(crate) fn level_after(self: &Self, samples: u64) -> f32 {
+        (self.level as f64 * (self.release_weight as f64).powf(samples as f64)) as f32
+    }
+
+    /// Feeds a number of silent samples into the detector at once.
+    pub(crate) fn release(self: &mut Self, samples: u64) {
+        self.level = self.level_after(samples);
+    }
+
+    pub(crate) fn reset(self: &mut Self) {
+        self.level = 0.;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BallisticsPreset;
+    use crate::utils::{PeakBuffer, VisualizerBuffer};
+    use nih_plug::util::gain_to_db;
+
+    const SAMPLE_RATE: f32 = 48000.;
+
+    // Plays a 5 kHz tone for `burst` milliseconds followed by silence, and returns
+    // the highest value the buffer displayed
+    fn reading(preset: BallisticsPreset, burst: f32) -> f32 {
+        let mut buffer = PeakBuffer::new(400, 2.0, 0.0).with_ballistics(preset);
+        buffer.set_sample_rate(SAMPLE_RATE);
+
+        let burst = (burst / 1000. * SAMPLE_RATE) as usize;
+        for i in 0..SAMPLE_RATE as usize {
+            let value = if i < burst {
+                (std::f32::consts::TAU * 5000. * i as f32 / SAMPLE_RATE).sin()
+            } else {
+                0.
+            };
+            buffer.enqueue(value);
+        }
+        (0..buffer.len()).map(|i| buffer[i]).fold(0., f32::max)
+    }
+
+    // The reading of a tone burst relative to a steady tone, in decibels
+    fn burst_response(preset: BallisticsPreset, burst: f32) -> f32 {
+        gain_to_db(reading(preset, burst) / reading(preset, 900.))
+    }
+
+    #[test]
+    fn ppm_tone_bursts() {
+        // IEC 60268-10 allows ±0.5 dB for a 10 ms burst
+        let type_1 = burst_response(BallisticsPreset::PpmType1, 10.);
+        assert!((type_1 + 1.).abs() <= 0.5, "Type I read {} dB", type_1);
+        let type_2 = burst_response(BallisticsPreset::PpmType2, 10.);
+        assert!((type_2 + 2.).abs() <= 0.5, "Type II read {} dB", type_2);
+
+        // The integration time of Type I is 5 ms
+        let type_1 = burst_response(BallisticsPreset::PpmType1, 5.);
+        assert!((type_1 + 2.).abs() <= 0.5, "Type I read {} dB", type_1);
+    }
+
+    #[test]
+    fn ppm_fallback() {
+        // Type I falls back by 20 dB in 1.5 seconds
+        let mut buffer = PeakBuffer::new(100, 2.0, 0.0).with_ballistics(BallisticsPreset::PpmType1);
+        buffer.set_sample_rate(SAMPLE_RATE);
+        for _ in 0..(0.1 * SAMPLE_RATE) as usize {
+            buffer.enqueue(1.0);
+        }
+        for _ in 0..(1.6 * SAMPLE_RATE) as usize {
+            buffer.enqueue(0.0);
+        }
+        // Each element spans 20 ms
+        let last = buffer.len() - 1;
+        let level = gain_to_db(buffer[last] / buffer[last - 75]);
+        assert!((level + 20.).abs() < 0.5, "fell back to {} dB", level);
+    }
+
+    #[test]
+    fn vu_rise_time() {
+        let preset = BallisticsPreset::Vu;
+        let steady = 2. / std::f32::consts::PI;
+        let rise = reading(preset, 300.) / steady;
+        assert!((rise - 0.99).abs() < 0.01, "rose to {}", rise);
+        assert!((reading(preset, 900.) / steady - 1.).abs() < 0.02);
+    }
+
+    #[test]
+    fn digital_peak() {
+        // Peaks are displayed immediately
+        let preset = BallisticsPreset::DigitalPeak { release: 12. };
+        let mut buffer = PeakBuffer::new(100, 1.0, 0.0).with_ballistics(preset);
+        buffer.set_sample_rate(SAMPLE_RATE);
+        buffer.enqueue(0.5);
+        for _ in 0..(0.5 * SAMPLE_RATE) as usize {
+            buffer.enqueue(0.0);
+        }
+        assert!((0..buffer.len()).any(|i| buffer[i] == 0.5));
+        // Half a second later, the peak has fallen by 6 dB
+        let level = gain_to_db(buffer[buffer.len() - 1] / 0.5);
+        assert!((level + 6.).abs() < 0.2, "fell to {} dB", level);
+    }
+}
@@ -0,0 +1,245 @@
+/// Determines whether a buffer scrolls continuously, or captures a single take.
+///
+/// Set using e.g. [`PeakBuffer::set_mode()`](super::PeakBuffer::set_mode).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BufferMode {
+    /// The buffer keeps scrolling, discarding its oldest elements.
+    #[default]
+    Rolling,
+    /// The buffer fills once after being armed, and then freezes.
+    ///
+    /// Until the buffer is armed, all samples are ignored. Once it is full, it
+    /// ignores all samples until it is armed again.
+    Capture,
+}
+
+/// The state of a buffer in [`BufferMode::Capture`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum CaptureState {
+    // Ignoring all samples until armed
+    #[default]
+    Idle,
+    // Scrolling until a sample reaches the threshold
+    Waiting {
+        threshold: f32,
+        post_trigger: usize,
+    },
+    // Filling the remaining elements
+    Filling {
+        remaining: usize,
+    },
+    Complete,
+}
+
+/// Drives the capture mode of a buffer; See [`BufferMode`].
+///
+/// The buffer asks it whether to accept each sample, and reports each sample it
+/// accumulated and each element it enqueued.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub(crate) struct Capture {
+    mode: BufferMode,
+    state: CaptureState,
+    // The number of elements enqueued since the buffer was armed
+    filled: usize,
+}
+
+impl Capture {
+    pub(crate) fn mode(self: &Self) -> BufferMode {
+        self.mode
+    }
+
+    pub(crate) fn set_mode(self: &mut Self, mode: BufferMode) {
+        self.mode = mode;
+        self.state = CaptureState::Idle;
+        self.filled = 0;
+    }
+
+    /// Starts filling the buffer of the given length.
+    pub(crate) fn arm(self: &mut Self, len: usize) {
+        self.filled = 0;
+        self.state = Self::filling(len);
+    }
+
+    /// Starts scrolling until a sample reaches the threshold, after which the part
+    /// of the buffer that follows the pre-trigger fraction is filled.
+    pub(crate) fn arm_on_threshold(self: &mut Self, len: usize, threshold: f32, pre_trigger: f32) {
+        let post_trigger = (len as f32 * (1. - pre_trigger.clamp(0., 1.))).round() as usize;
+        self.filled = 0;
+        self.state = CaptureState::Waiting {
+            threshold,
+            post_trigger,
+        };
+    }
+
+    fn filling(remaining: usize) -> CaptureState {
+        if remaining == 0 {
+            CaptureState::Complete
+        } else {
+            CaptureState::Filling { remaining }
+        }
+    }
+
+    /// Returns whether the buffer accepts samples.
+    pub(crate) fn accepts(self: &Self) -> bool {
+        self.mode == BufferMode::Rolling
+            || matches!(
+                self.state,
+                CaptureState::Waiting { .. } | CaptureState::Filling { .. }
+            )
+    }
+
+    /// Triggers the capture if the sample reaches the threshold.
+    ///
+    /// The buffer calls this once the sample was accumulated, so that the element
+    /// holding it is the first one after the trigger.
+    pub(crate) fn observe(self: &mut Self, value: f32) {
+        if let CaptureState::Waiting {
+            threshold,
+            post_trigger,
+        } = self.state
+        {
+            if self.mode == BufferMode::Capture && value.abs() >= threshold {
+                self.state = Self::filling(post_trigger);
+            }
+        }
+    }
+
+    /// Counts an element that was enqueued into the buffer.
+    pub(crate) fn enqueued(self: &mut Self) {
+        if self.mode == BufferMode::Rolling {
+            return;
+        }
+        self.filled += 1;
+        if let CaptureState::Filling { remaining } = self.state {
+            self.state = Self::filling(remaining - 1);
+        }
+    }
+
+    pub(crate) fn is_complete(self: &Self) -> bool {
+        self.mode == BufferMode::Capture && self.state == CaptureState::Complete
+    }
+
+    pub(crate) fn fill_count(self: &Self) -> usize {
+        self.filled
+    }
+
+    /// Returns how far the take is, from 0 to 1, while the buffer of the given length
+    /// is armed.
+    pub(crate) fn progress(self: &Self, len: usize) -> Option<f32> {
+        if self.mode == BufferMode::Rolling {
+            return None;
+        }
+        match self.state {
+            CaptureState::Waiting { .. } => Some(0.),
+            CaptureState::Filling { remaining } if len > 0 => {
+                Some(1. - remaining as f32 / len as f32)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BufferMode;
+    use crate::utils::{PeakBuffer, VisualizerBuffer, WaveformBuffer};
+
+    // 4 samples per element
+    fn peak_buffer(size: usize) -> PeakBuffer {
+        let mut buffer = PeakBuffer::new(size, 1.0, 0.0).with_mode(BufferMode::Capture);
+        buffer.set_sample_rate(4. * size as f32);
+        buffer
+    }
+
+    fn contents(buffer: &PeakBuffer) -> Vec<f32> {
+        (0..buffer.len()).map(|i| buffer[i]).collect()
+    }
+
+    #[test]
+    fn ignored_until_armed() {
+        let mut buffer = peak_buffer(4);
+        for _ in 0..32 {
+            buffer.enqueue(1.0);
+        }
+        assert_eq!(contents(&buffer), vec![0.; 4]);
+        assert!(!buffer.is_complete());
+        assert_eq!(buffer.capture_progress(), None);
+    }
+
+    #[test]
+    fn fills_once() {
+        let mut buffer = peak_buffer(4);
+        buffer.arm();
+        assert_eq!(buffer.capture_progress(), Some(0.));
+
+        for i in 0..12 {
+            buffer.enqueue(i as f32);
+        }
+        assert_eq!(buffer.fill_count(), 2);
+        assert_eq!(buffer.capture_progress(), Some(0.5));
+        assert!(!buffer.is_complete());
+
+        for i in 12..100 {
+            buffer.enqueue(i as f32);
+        }
+        assert!(buffer.is_complete());
+        assert_eq!(buffer.fill_count(), 4);
+        assert_eq!(buffer.capture_progress(), None);
+        assert_eq!(contents(&buffer), vec![3., 7., 11., 15.]);
+
+        // Re-arming clears the take
+        buffer.arm();
+        assert_eq!(contents(&buffer), vec![0.; 4]);
+        assert!(!buffer.is_complete());
+    }
+
+    #[test]
+    fn threshold_trigger() {
+        let mut buffer = peak_buffer(8);
+        // Half of the buffer precedes the trigger
+        buffer.arm_on_threshold(1.0, 0.5);
+
+        for _ in 0..64 {
+            buffer.enqueue(0.5);
+        }
+        // Waiting for the trigger, while keeping the history
+        assert!(!buffer.is_complete());
+        assert_eq!(buffer.capture_progress(), Some(0.));
+
+        buffer.enqueue(-1.0);
+        for _ in 0..64 {
+            buffer.enqueue(0.25);
+        }
+        assert!(buffer.is_complete());
+        // The clip lies in the middle of the take
+        assert_eq!(
+            contents(&buffer),
+            vec![0.5, 0.5, 0.5, 0.5, 1.0, 0.25, 0.25, 0.25]
+        );
+    }
+
+    #[test]
+    fn rolling_ignores_arming() {
+        let mut buffer = PeakBuffer::new(4, 1.0, 0.0);
+        buffer.set_sample_rate(16.);
+        buffer.arm();
+        for _ in 0..64 {
+            buffer.enqueue(1.0);
+        }
+        assert!(!buffer.is_complete());
+        assert_eq!(contents(&buffer), vec![1.; 4]);
+    }
+
+    #[test]
+    fn waveform_buffer() {
+        let mut buffer = WaveformBuffer::new(4, 1.0).with_mode(BufferMode::Capture);
+        buffer.set_sample_rate(16.);
+        buffer.arm();
+        for i in 0..100 {
+            buffer.enqueue(i as f32);
+        }
+        assert!(buffer.is_complete());
+        assert_eq!(buffer[0], (0., 3.));
+        assert_eq!(buffer[3], (12., 15.));
+    }
+}
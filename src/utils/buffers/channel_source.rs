@@ -0,0 +1,271 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use nih_plug::buffer::Buffer;
+
+/// The channels a buffer reads when it's fed a [`Buffer`] without a channel, e.g.
+/// for a meter whose channel the user picks from a dropdown.
+///
+/// Unlike the channel passed to
+/// [`enqueue_buffer()`](super::VisualizerBuffer::enqueue_buffer), the source can be
+/// changed from the editor while the plug-in is processing, using a
+/// [`SourceSelector`]. See [`PeakBuffer::set_source()`](super::PeakBuffer::set_source).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChannelSource {
+    /// A single channel; Blocks without it are silent.
+    Channel(usize),
+    /// The mid signal, `(L + R) / 2`; For a single channel, that channel.
+    Mid,
+    /// The side signal, `(L - R) / 2`; For a single channel, silence.
+    Side,
+    /// The mean of all channels, like
+    /// [`enqueue_buffer()`](super::VisualizerBuffer::enqueue_buffer) with no channel.
+    #[default]
+    DownmixAvg,
+    /// The sample with the largest absolute value, keeping its sign.
+    DownmixMax,
+}
+
+impl ChannelSource {
+    /// Combines the samples at the index of each channel into the one the source
+    /// selects.
+    pub(crate) fn mix<C: AsRef<[f32]>>(self: Self, channels: &[C], index: usize) -> f32 {
+        let sample = |channel: usize| channels.get(channel).map(|c| c.as_ref()[index]);
+        match self {
+            ChannelSource::Channel(channel) => sample(channel).unwrap_or(0.),
+            ChannelSource::Mid => match (sample(0), sample(1)) {
+                (Some(left), Some(right)) => (left + right) / 2.,
+                (left, _) => left.unwrap_or(0.),
+            },
+            ChannelSource::Side => match (sample(0), sample(1)) {
+                (Some(left), Some(right)) => (left - right) / 2.,
+                _ => 0.,
+            },
+            ChannelSource::DownmixAvg => {
+                (1. / channels.len() as f32)
+                    * channels.iter().map(|c| c.as_ref()[index]).sum::<f32>()
+            }
+            ChannelSource::DownmixMax => channels.iter().fold(0., |max: f32, c| {
+                let sample = c.as_ref()[index];
+                if sample.abs() > max.abs() {
+                    sample
+                } else {
+                    max
+                }
+            }),
+        }
+    }
+
+    // 0 to 3 for the variants without a channel, followed by the channels
+    fn pack(self: Self) -> u64 {
+        match self {
+            ChannelSource::DownmixAvg => 0,
+            ChannelSource::DownmixMax => 1,
+            ChannelSource::Mid => 2,
+            ChannelSource::Side => 3,
+            ChannelSource::Channel(channel) => 4 + channel as u64,
+        }
+    }
+
+    fn unpack(packed: u64) -> Self {
+        match packed {
+            0 => ChannelSource::DownmixAvg,
+            1 => ChannelSource::DownmixMax,
+            2 => ChannelSource::Mid,
+            3 => ChannelSource::Side,
+            channel => ChannelSource::Channel((channel - 4) as usize),
+        }
+    }
+}
+
+/// Changes the [`ChannelSource`] of a buffer from another thread, without a lock.
+///
+/// The buffer is usually locked by the audio thread while it's fed, so selecting
+/// the source through the buffer would make the editor wait for it. A selector
+/// stores the source in a single atomic instead, which the buffer reads once per
+/// block. The handle is cheap to clone, and all clones select the source of the
+/// same buffer - as do clones of the buffer itself.
+///
+/// ```
+/// // Inside the editor's model
+/// let selector = peak_buffer.lock().unwrap().source_selector();
+///
+/// // When the user picks a source
+/// selector.set(ChannelSource::Side);
+/// ```
+#[derive(Clone)]
+pub struct SourceSelector {
+    source: Arc<AtomicU64>,
+}
+
+impl SourceSelector {
+    /// Selects the source, which the buffer switches to on the next block it's fed.
+    ///
+    /// This never locks or allocates.
+    pub fn set(self: &Self, source: ChannelSource) {
+        self.source.store(source.pack(), Ordering::Release);
+    }
+
+    /// Returns the source that was selected last.
+    pub fn get(self: &Self) -> ChannelSource {
+        ChannelSource::unpack(self.source.load(Ordering::Acquire))
+    }
+}
+
+impl Default for SourceSelector {
+    fn default() -> Self {
+        Self {
+            source: Arc::new(AtomicU64::new(ChannelSource::default().pack())),
+        }
+    }
+}
+
+// Selectors are equal if they're clones of each other, so that buffers can still be
+// compared
+impl PartialEq for SourceSelector {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.source, &other.source)
+    }
+}
+
+impl std::fmt::Debug for SourceSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SourceSelector").field(&self.get()).finish()
+    }
+}
+
+/// The source of a buffer, and what happens when it changes.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SourceRouting {
+    selector: SourceSelector,
+    // The source the buffer currently holds the history of
+    current: ChannelSource,
+    clear_on_change: bool,
+}
+
+impl Default for SourceRouting {
+    fn default() -> Self {
+        Self {
+            selector: SourceSelector::default(),
+            current: ChannelSource::default(),
+            clear_on_change: true,
+        }
+    }
+}
+
+impl SourceRouting {
+    pub(crate) fn selector(self: &Self) -> SourceSelector {
+        self.selector.clone()
+    }
+
+    pub(crate) fn set_clear_on_change(self: &mut Self, clear_on_change: bool) {
+        self.clear_on_change = clear_on_change;
+    }
+
+    /// Switches to the selected source, and returns it along with whether the
+    /// buffer's history needs to be cleared.
+    pub(crate) fn update(self: &mut Self) -> (ChannelSource, bool) {
+        let source = self.selector.get();
+        let changed = source != self.current;
+        self.current = source;
+        (source, changed && self.clear_on_change)
+    }
+}
+
+/// Calls `enqueue` with each sample that the source selects from a block.
+pub(crate) fn for_each_routed(
+    buffer: &Buffer,
+    source: ChannelSource,
+    mut enqueue: impl FnMut(f32),
+) {
+    let channels = buffer.as_slice_immutable();
+    for index in 0..buffer.samples() {
+        enqueue(source.mix(channels, index));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChannelSource, SourceRouting, SourceSelector};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn mid_side() {
+        let stereo: [&[f32]; 2] = [&[1.0, 0.5, -0.25], &[0.5, 0.5, 0.75]];
+        let mixed =
+            |source: ChannelSource| (0..3).map(|i| source.mix(&stereo, i)).collect::<Vec<_>>();
+        assert_eq!(mixed(ChannelSource::Mid), vec![0.75, 0.5, 0.25]);
+        assert_eq!(mixed(ChannelSource::Side), vec![0.25, 0., -0.5]);
+        assert_eq!(mixed(ChannelSource::DownmixAvg), vec![0.75, 0.5, 0.25]);
+        assert_eq!(mixed(ChannelSource::DownmixMax), vec![1.0, 0.5, 0.75]);
+        assert_eq!(mixed(ChannelSource::Channel(1)), vec![0.5, 0.5, 0.75]);
+
+        // Mid and side recombine into the channels
+        for i in 0..3 {
+            let (mid, side) = (mixed(ChannelSource::Mid)[i], mixed(ChannelSource::Side)[i]);
+            assert_eq!((mid + side, mid - side), (stereo[0][i], stereo[1][i]));
+        }
+    }
+
+    #[test]
+    fn missing_channels() {
+        let mono: [&[f32]; 1] = [&[0.5]];
+        assert_eq!(ChannelSource::Mid.mix(&mono, 0), 0.5);
+        assert_eq!(ChannelSource::Side.mix(&mono, 0), 0.);
+        assert_eq!(ChannelSource::Channel(1).mix(&mono, 0), 0.);
+
+        let none: [&[f32]; 0] = [];
+        for source in [
+            ChannelSource::Mid,
+            ChannelSource::Side,
+            ChannelSource::DownmixMax,
+            ChannelSource::Channel(0),
+        ] {
+            assert_eq!(source.mix(&none, 0), 0., "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let selector = SourceSelector::default();
+        assert_eq!(selector.get(), ChannelSource::DownmixAvg);
+        for source in [
+            ChannelSource::DownmixMax,
+            ChannelSource::Mid,
+            ChannelSource::Side,
+            ChannelSource::Channel(0),
+            ChannelSource::Channel(17),
+            ChannelSource::DownmixAvg,
+        ] {
+            selector.set(source);
+            assert_eq!(selector.get(), source);
+        }
+    }
+
+    #[test]
+    fn visible_across_threads() {
+        let mut routing = SourceRouting::default();
+        let selector = routing.selector();
+        let selected = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let selected = selected.clone();
+            std::thread::spawn(move || {
+                selector.set(ChannelSource::Side);
+                selected.store(true, Ordering::Release);
+            })
+        };
+        while !selected.load(Ordering::Acquire) {
+            std::hint::spin_loop();
+        }
+        assert_eq!(routing.update(), (ChannelSource::Side, true));
+        assert_eq!(routing.update(), (ChannelSource::Side, false));
+        thread.join().unwrap();
+
+        // Without clearing, the history is kept
+        routing.set_clear_on_change(false);
+        routing.selector().set(ChannelSource::Channel(0));
+        assert_eq!(routing.update(), (ChannelSource::Channel(0), false));
+    }
+}
@@ -0,0 +1,452 @@
+use nih_plug::util::{db_to_gain, gain_to_db};
+use std::fmt::{self, Debug};
+use std::ops::{Index, IndexMut};
+
+use super::{
+    super::config_error::{check_duration, check_size, clamp_duration, ConfigError},
+    check_index, slot_progress, BufferSnapshot, RingBuffer, SnapshotExport, VisualizerBuffer,
+};
+
+/// Stores how densely the samples of a signal reach a threshold over time.
+///
+/// For each element, the buffer counts the samples whose absolute value reaches the
+/// threshold, and stores them as a fraction of the samples the element spans: 0
+/// if none of them did, 1 if all of them did. With a threshold of 0 dBFS, this is a
+/// clip density graph, which tells a single stray clip apart from a master that is
+/// clipped throughout. Display it on a [`Graph`](crate::visualizers::Graph) with a
+/// range of `(0.0, 1.0)` and [`ValueScaling::Linear`](crate::utils::ValueScaling).
+///
+/// The elements are spaced just like those of a [`PeakBuffer`](super::PeakBuffer) -
+/// with the same size, duration and sample rate, both buffers finalize their
+/// elements on the same samples, so the density lines up with the peaks when it's
+/// drawn below them.
+///
+/// Like the other buffers, [`enqueue_buffer()`](VisualizerBuffer::enqueue_buffer)
+/// mono-sums the channels if no channel is given. To count a frame as soon as any
+/// of its channels reaches the threshold, enqueue it using
+/// [`ChannelDownmix::MaxAbs`](super::ChannelDownmix::MaxAbs) instead.
+///
+/// The `CountBuffer` needs to be provided a sample rate after initialization - do
+/// this inside your [`initialize()`](nih_plug::plugin::Plugin::initialize) function.
+#[derive(Clone, Default)]
+pub struct CountBuffer {
+    buffer: RingBuffer<f32>,
+    // The threshold as a gain, which the element that is being accumulated counts with
+    threshold: f32,
+    // The threshold that was set last, which the next element counts with
+    next_threshold: f32,
+    // The number of samples inside the element, and how many of them reached the
+    // threshold
+    samples: u32,
+    count: u32,
+    // The gap between elements of the buffer in samples
+    sample_delta: f32,
+    // Used to calculate the sample_delta
+    sample_rate: f32,
+    duration: f32,
+    // The current time, counts down from sample_delta to 0
+    t: f32,
+    // Whether the plug-in is rendering offline, in which case nothing is enqueued
+    offline: bool,
+}
+
+impl CountBuffer {
+    /// Constructs a new `CountBuffer`.
+    ///
+    /// * `size` - The length of the buffer in samples; Usually, this can be kept < 2000
+    /// * `duration` - The duration (in seconds) of the audio data inside the buffer
+    /// * `threshold` - The absolute value a sample needs to reach to be counted, e.g. 1.0 for a clip
+    ///
+    /// The buffer needs to be provided a sample rate after initialization - do this by
+    /// calling [`set_sample_rate`](Self::set_sample_rate) inside your
+    /// [`initialize()`](nih_plug::plugin::Plugin::initialize) function.
+    pub fn new(size: usize, duration: f32, threshold: f32) -> Self {
+        Self {
+            buffer: RingBuffer::<f32>::new(size),
+            duration: clamp_duration("count buffer", "duration", duration),
+            threshold,
+            next_threshold: threshold,
+            ..Default::default()
+        }
+    }
+
+    /// Constructs a new `CountBuffer`, or returns a [`ConfigError`] if the size is 0
+    /// or the duration isn't positive; See [`new()`](Self::new).
+    pub fn try_new(size: usize, duration: f32, threshold: f32) -> Result<Self, ConfigError> {
+        check_size("count buffer", "size", size)?;
+        check_duration("count buffer", "duration", duration)?;
+        Ok(Self::new(size, duration, threshold))
+    }
+
+    /// Sets the threshold in decibels, e.g. `-0.1` for a clip density graph with a
+    /// margin for inter-sample peaks.
+    pub fn with_threshold_db(mut self, db: f32) -> Self {
+        self.set_threshold_db(db);
+        self.threshold = self.next_threshold;
+        self
+    }
+
+    /// Names the `CountBuffer`, which identifies it in panic messages and snapshots.
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.buffer.name_mut().set(name);
+        self
+    }
+
+    /// Sets the name of the `CountBuffer`; See [`with_name()`](Self::with_name).
+    pub fn set_name(self: &mut Self, name: String) {
+        self.buffer.name_mut().set(name);
+    }
+
+    /// Returns the name of the `CountBuffer`, if it has one.
+    pub fn name(self: &Self) -> Option<&str> {
+        self.buffer.name()
+    }
+
+    /// Sets the absolute value a sample needs to reach to be counted.
+    ///
+    /// This doesn't clear the buffer - the elements that were already enqueued keep
+    /// the density they were counted with, and so does the element that is being
+    /// accumulated. The new threshold applies from the next element on.
+    pub fn set_threshold(self: &mut Self, threshold: f32) {
+        self.next_threshold = threshold;
+    }
+
+    /// Sets the threshold in decibels; See [`set_threshold()`](Self::set_threshold).
+    pub fn set_threshold_db(self: &mut Self, db: f32) {
+        self.set_threshold(db_to_gain(db));
+    }
+
+    /// Returns the threshold that was set last, as a gain.
+    pub fn threshold(self: &Self) -> f32 {
+        self.next_threshold
+    }
+
+    /// Returns the threshold that was set last, in decibels.
+    pub fn threshold_db(self: &Self) -> f32 {
+        gain_to_db(self.next_threshold)
+    }
+
+    /// Sets the sample rate of the incoming audio.
+    ///
+    /// This function **clears** the buffer.
+    pub fn set_sample_rate(self: &mut Self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.update();
+        self.buffer.clear();
+    }
+
+    /// Sets the duration (in seconds) of the audio data inside the buffer.
+    ///
+    /// This function **clears** the buffer. A duration that isn't positive and finite
+    /// fails a debug assertion, and is clamped in release builds; Use
+    /// [`try_set_duration()`](Self::try_set_duration) to handle it instead.
+    pub fn set_duration(self: &mut Self, duration: f32) {
+        self.duration = clamp_duration("count buffer", "duration", duration);
+        self.update();
+        self.buffer.clear();
+    }
+
+    /// Sets the duration (in seconds) of the audio data inside the buffer, or returns
+    /// a [`ConfigError`] if it isn't positive and finite.
+    ///
+    /// This function **clears** the buffer.
+    pub fn try_set_duration(self: &mut Self, duration: f32) -> Result<(), ConfigError> {
+        self.set_duration(check_duration("count buffer", "duration", duration)?);
+        Ok(())
+    }
+
+    /// Hints whether the plug-in is rendering offline, which skips all enqueueing
+    /// while it is. See [`PeakBuffer::set_offline`](super::PeakBuffer::set_offline).
+    pub fn set_offline(self: &mut Self, offline: bool) {
+        self.offline = offline;
+    }
+
+    // The fraction of the accumulated samples that reached the threshold
+    fn density(self: &Self) -> f32 {
+        if self.samples == 0 {
+            return 0.;
+        }
+        self.count as f32 / self.samples as f32
+    }
+
+    fn reset_accumulators(self: &mut Self) {
+        self.samples = 0;
+        self.count = 0;
+        self.threshold = self.next_threshold;
+    }
+
+    // Restarts the element that is being accumulated, since its length changed
+    fn update(self: &mut Self) {
+        self.sample_delta =
+            ((self.sample_rate as f64 * self.duration as f64) / self.buffer.len() as f64) as f32;
+        self.t = self.sample_delta;
+        self.reset_accumulators();
+    }
+}
+
+impl VisualizerBuffer<f32> for CountBuffer {
+    fn enqueue(self: &mut Self, value: f32) {
+        if self.offline {
+            return;
+        }
+        // The same countdown as the `PeakBuffer`'s, so that the elements of both
+        // buffers are finalized on the same samples
+        self.t -= 1.0;
+        if self.t < 0.0 {
+            self.buffer.enqueue(self.density());
+            self.reset_accumulators();
+            self.t += self.sample_delta;
+        }
+        self.samples += 1;
+        if value.abs() >= self.threshold {
+            self.count += 1;
+        }
+    }
+
+    fn enqueue_buffer(
+        self: &mut Self,
+        buffer: &mut nih_plug::buffer::Buffer,
+        channel: Option<usize>,
+    ) {
+        if self.offline {
+            return;
+        }
+        match channel {
+            Some(channel) => {
+                for sample in buffer.as_slice_immutable()[channel].iter() {
+                    self.enqueue(*sample);
+                }
+            }
+            None => {
+                for sample in buffer.iter_samples() {
+                    self.enqueue(
+                        (1. / (&sample).len() as f32) * sample.into_iter().map(|x| *x).sum::<f32>(),
+                    );
+                }
+            }
+        }
+    }
+
+    fn len(self: &Self) -> usize {
+        self.buffer.len()
+    }
+
+    fn generation(self: &Self) -> Option<u64> {
+        Some(self.buffer.generation())
+    }
+
+    fn enqueued(self: &Self) -> Option<u64> {
+        Some(self.buffer.enqueued())
+    }
+
+    fn duration(self: &Self) -> Option<f32> {
+        Some(self.duration)
+    }
+
+    fn partial(self: &Self) -> Option<(f32, f32)> {
+        let progress = slot_progress(self.t, self.sample_delta)?;
+        Some((progress, self.density()))
+    }
+
+    fn clear(self: &mut Self) {
+        self.buffer.clear();
+        self.reset_accumulators();
+    }
+
+    /// Grows the buffer, **clearing it**.
+    fn grow(self: &mut Self, size: usize) {
+        if self.buffer.len() == size {
+            return;
+        }
+        self.buffer.grow(size);
+        self.update();
+        self.buffer.clear();
+    }
+
+    /// Shrinks the buffer, **clearing it**.
+    fn shrink(self: &mut Self, size: usize) {
+        if self.buffer.len() == size {
+            return;
+        }
+        self.buffer.shrink(size);
+        self.update();
+        self.buffer.clear();
+    }
+}
+
+impl Index<usize> for CountBuffer {
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        check_index("count buffer", self.name(), index, self.buffer.len());
+        &self.buffer[index]
+    }
+}
+impl IndexMut<usize> for CountBuffer {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        check_index("count buffer", self.name(), index, self.buffer.len());
+        &mut self.buffer[index]
+    }
+}
+
+impl SnapshotExport for CountBuffer {
+    fn export(&self) -> BufferSnapshot {
+        BufferSnapshot {
+            name: self.name().map(str::to_string),
+            duration: Some(self.duration),
+            sample_rate: Some(self.sample_rate),
+            range: Some((0., 1.)),
+            ..BufferSnapshot::new(
+                "CountBuffer",
+                &["density"],
+                self.buffer.iter().map(|v| vec![*v]).collect(),
+            )
+        }
+    }
+}
+
+impl Debug for CountBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CountBuffer")
+            .field("name", &self.name())
+            .field("size", &self.buffer.len())
+            .field("duration", &self.duration)
+            .field("sample_rate", &self.sample_rate)
+            .field("threshold", &self.next_threshold)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CountBuffer;
+    use crate::utils::buffers::test_support::{assert_enqueue_buffer_matches_enqueue, contents};
+    use crate::utils::{PeakBuffer, VisualizerBuffer};
+
+    // 10 elements of 100 samples each, counting samples that reach 0.5
+    fn new() -> CountBuffer {
+        let mut buffer = CountBuffer::new(10, 1.0, 0.5);
+        buffer.set_sample_rate(1000.);
+        buffer
+    }
+
+    // Feeds 1000 samples along with the one that finalizes the last element, where
+    // the samples for which `over` holds reach the threshold
+    fn density(over: impl Fn(usize) -> bool) -> Vec<f32> {
+        let mut buffer = new();
+        for i in 0..1001 {
+            buffer.enqueue(if over(i) { -0.9 } else { 0.1 });
+        }
+        contents(&buffer)
+    }
+
+    #[test]
+    fn ten_percent() {
+        // Every tenth sample, spread evenly
+        assert_eq!(density(|i| i % 10 == 3), vec![0.1; 10]);
+        // Ten samples in a row at the start of every element
+        assert_eq!(density(|i| i % 100 < 10), vec![0.1; 10]);
+        assert_eq!(density(|_| false), vec![0.; 10]);
+        assert_eq!(density(|_| true), vec![1.; 10]);
+    }
+
+    #[test]
+    fn straddling_elements() {
+        // Ten samples in a row that straddle every boundary between two elements, half
+        // of which fall into either element - the first element starts with the second
+        // half of such a run
+        assert_eq!(density(|i| (i + 5) % 100 < 10), vec![0.1; 10]);
+        let values = density(|i| (95..105).contains(&i));
+        assert_eq!(values[..3], [0.05, 0.05, 0.]);
+
+        // A single burst across the boundary between the third and the fourth element
+        let values = density(|i| (297..304).contains(&i));
+        assert_eq!(values[2], 0.03);
+        assert_eq!(values[3], 0.04);
+        assert!(values
+            .iter()
+            .enumerate()
+            .all(|(i, v)| i == 2 || i == 3 || *v == 0.));
+
+        // The sample that finalizes an element already counts towards the next one
+        let values = density(|i| i == 100);
+        assert_eq!(values[0], 0.);
+        assert_eq!(values[1], 0.01);
+    }
+
+    #[test]
+    fn fractional_elements() {
+        // 4.5 samples per element, so the elements alternate between 4 and 5 samples
+        let mut buffer = CountBuffer::new(10, 1.0, 0.5);
+        buffer.set_sample_rate(45.);
+        for i in 0..46 {
+            // The first sample of each element reaches the threshold
+            let first = [0, 4, 9, 13, 18, 22, 27, 31, 36, 40].contains(&i);
+            buffer.enqueue(if first { 1. } else { 0. });
+        }
+        assert_eq!(
+            contents(&buffer),
+            vec![0.25, 0.2, 0.25, 0.2, 0.25, 0.2, 0.25, 0.2, 0.25, 0.2]
+        );
+    }
+
+    #[test]
+    fn threshold_at_runtime() {
+        let mut buffer = new();
+        for _ in 0..550 {
+            buffer.enqueue(0.6);
+        }
+        // Halfway through the sixth element
+        buffer.set_threshold(0.75);
+        assert_eq!(buffer.threshold(), 0.75);
+        for _ in 550..1001 {
+            buffer.enqueue(0.6);
+        }
+        // Neither the elements that were enqueued, nor the one that was being
+        // accumulated are recounted
+        assert_eq!(
+            contents(&buffer),
+            vec![1., 1., 1., 1., 1., 1., 0., 0., 0., 0.]
+        );
+
+        // Samples that exactly reach the threshold count
+        let mut buffer = CountBuffer::new(10, 1.0, 0.).with_threshold_db(0.);
+        assert_eq!(buffer.threshold(), 1.);
+        buffer.set_sample_rate(1000.);
+        for _ in 0..101 {
+            buffer.enqueue(1.);
+        }
+        assert_eq!(buffer[9], 1.);
+    }
+
+    #[test]
+    fn aligned_with_peak_buffer() {
+        // A fractional number of samples per element
+        let mut count = CountBuffer::new(7, 1.0, 0.5);
+        let mut peak = PeakBuffer::new(7, 1.0, 0.0);
+        count.set_sample_rate(44100.);
+        peak.set_sample_rate(44100.);
+        for i in 0..20000 {
+            let sample = (i as f32 * 0.01).sin();
+            count.enqueue(sample);
+            peak.enqueue(sample);
+            assert_eq!(count.enqueued(), peak.enqueued());
+            assert_eq!(
+                count.partial().map(|(progress, _)| progress),
+                peak.partial().map(|(progress, _)| progress)
+            );
+        }
+    }
+
+    #[test]
+    fn enqueue_buffer() {
+        assert_enqueue_buffer_matches_enqueue(
+            || {
+                let mut buffer = CountBuffer::new(10, 1.0, 0.3);
+                buffer.set_sample_rate(100.);
+                buffer
+            },
+            250,
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use std::fmt::{self, Debug};
+use std::ops::{Index, IndexMut};
+use std::sync::{Arc, Mutex};
+
+use nih_plug::util::gain_to_db;
+
+use super::{check_index, VisualizerBuffer};
+
+/// Combines two buffers into a third one on the editor side.
+///
+/// Some traces can be computed from buffers the editor already has - e.g. the
+/// difference between the input and output peaks of a compressor, which shows its
+/// gain reduction. Instead of feeding a third buffer on the audio thread, a
+/// `DerivedBuffer` combines the elements of two buffers whenever a view reads it.
+///
+/// The buffer holds the same shared buffers that the editor's lenses point to. It's
+/// computed lazily, right before a view draws it, and only if either of the buffers
+/// changed since - buffers that don't report a
+/// [`generation()`](VisualizerBuffer::generation) are combined every time.
+///
+/// If the buffers differ in length, their most recent elements are combined, up to
+/// the length of the shorter one.
+///
+/// ```
+/// // Inside the editor's model
+/// let delta = Arc::new(Mutex::new(DerivedBuffer::decibels(
+///     input_peaks.clone(),
+///     output_peaks.clone(),
+///     |input, output| output - input,
+/// )));
+///
+/// // The result is in decibels, so it's displayed linearly
+/// Graph::new(cx, Data::delta, (-24.0, 0.0), ValueScaling::Linear);
+/// ```
+pub struct DerivedBuffer<A, B> {
+    a: Arc<Mutex<A>>,
+    b: Arc<Mutex<B>>,
+    combine: fn(f32, f32) -> f32,
+    // Whether the elements are converted to decibels before they are combined
+    decibels: bool,
+    values: Vec<f32>,
+    // The generations of both buffers the values were computed from
+    generations: Option<(u64, u64)>,
+    // Changes whenever the values are combined again, which is the derived buffer's
+    // own generation
+    combined: u64,
+    duration: Option<f32>,
+}
+
+impl<A, B> DerivedBuffer<A, B>
+where
+    A: VisualizerBuffer<f32, Output = f32>,
+    B: VisualizerBuffer<f32, Output = f32>,
+{
+    /// Constructs a new `DerivedBuffer` that combines the raw elements of two buffers.
+    ///
+    /// * `a` - The buffer whose elements are passed as the first argument
+    /// * `b` - The buffer whose elements are passed as the second argument
+    /// * `combine` - Combines an element of `a` with the element of `b` at the same position
+    ///
+    /// The two buffers need to be distinct, as both are locked at the same time.
+    pub fn new(a: Arc<Mutex<A>>, b: Arc<Mutex<B>>, combine: fn(f32, f32) -> f32) -> Self {
+        Self::with_decibels(a, b, combine, false)
+    }
+
+    /// Constructs a new `DerivedBuffer` that combines the elements of two buffers in
+    /// decibels.
+    ///
+    /// Each element is converted from a gain to decibels before it is passed to
+    /// `combine`, so that e.g. a subtraction yields the difference in decibels. Silence
+    /// is treated as -100 dB. The result is displayed as-is, so use
+    /// [`ValueScaling::Linear`](crate::utils::ValueScaling::Linear) with a range in
+    /// decibels to draw it.
+    pub fn decibels(a: Arc<Mutex<A>>, b: Arc<Mutex<B>>, combine: fn(f32, f32) -> f32) -> Self {
+        Self::with_decibels(a, b, combine, true)
+    }
+
+    fn with_decibels(
+        a: Arc<Mutex<A>>,
+        b: Arc<Mutex<B>>,
+        combine: fn(f32, f32) -> f32,
+        decibels: bool,
+    ) -> Self {
+        assert!(
+            Arc::as_ptr(&a) as *const () != Arc::as_ptr(&b) as *const (),
+            "A DerivedBuffer needs two distinct buffers"
+        );
+        Self {
+            a,
+            b,
+            combine,
+            decibels,
+            values: Vec::new(),
+            generations: None,
+            combined: 0,
+            duration: None,
+        }
+    }
+
+    fn scale(self: &Self, value: f32) -> f32 {
+        if self.decibels {
+            gain_to_db(value)
+        } else {
+            value
+        }
+    }
+}
+
+impl<A, B> VisualizerBuffer<f32> for DerivedBuffer<A, B>
+where
+    A: VisualizerBuffer<f32, Output = f32>,
+    B: VisualizerBuffer<f32, Output = f32>,
+{
+    /// Does nothing - the contents are derived from other buffers.
+    fn enqueue(self: &mut Self, _value: f32) {}
+
+    /// Does nothing - the contents are derived from other buffers.
+    fn enqueue_buffer(
+        self: &mut Self,
+        _buffer: &mut nih_plug::buffer::Buffer,
+        _channel: Option<usize>,
+    ) {
+    }
+
+    /// Clears the derived elements, which are computed again on the next refresh.
+    fn clear(self: &mut Self) {
+        self.values.clear();
+        self.generations = None;
+        self.combined = self.combined.wrapping_add(1);
+    }
+
+    /// Does nothing - the length follows the buffers it is derived from.
+    fn grow(self: &mut Self, _size: usize) {}
+
+    /// Does nothing - the length follows the buffers it is derived from.
+    fn shrink(self: &mut Self, _size: usize) {}
+
+    fn len(self: &Self) -> usize {
+        self.values.len()
+    }
+
+    fn duration(self: &Self) -> Option<f32> {
+        self.duration
+    }
+
+    fn generation(self: &Self) -> Option<u64> {
+        Some(self.combined)
+    }
+
+    fn refresh(self: &mut Self) {
+        // A poisoned buffer keeps the last values
+        let (Ok(a), Ok(b)) = (self.a.lock(), self.b.lock()) else {
+            return;
+        };
+
+        let generations = a.generation().zip(b.generation());
+        if generations.is_some() && generations == self.generations {
+            return;
+        }
+
+        let len = a.len().min(b.len());
+        let (a_start, b_start) = (a.len() - len, b.len() - len);
+        let values = (0..len)
+            .map(|i| (self.combine)(self.scale(a[a_start + i]), self.scale(b[b_start + i])))
+            .collect();
+        self.duration = a
+            .duration()
+            .map(|duration| duration * len as f32 / a.len() as f32);
+        drop((a, b));
+
+        self.values = values;
+        self.generations = generations;
+        self.combined = self.combined.wrapping_add(1);
+    }
+}
+
+impl<A, B> Index<usize> for DerivedBuffer<A, B> {
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        check_index("derived buffer", None, index, self.values.len());
+        &self.values[index]
+    }
+}
+impl<A, B> IndexMut<usize> for DerivedBuffer<A, B> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        check_index("derived buffer", None, index, self.values.len());
+        self.combined = self.combined.wrapping_add(1);
+        &mut self.values[index]
+    }
+}
+
+impl<A, B> Debug for DerivedBuffer<A, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DerivedBuffer")
+            .field("size", &self.values.len())
+            .field("decibels", &self.decibels)
+            .field("duration", &self.duration)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DerivedBuffer;
+    use crate::utils::{RingBuffer, VisualizerBuffer};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    fn shared(values: &[f32]) -> Arc<Mutex<RingBuffer<f32>>> {
+        let mut buffer = RingBuffer::new(values.len());
+        for value in values {
+            buffer.enqueue(*value);
+        }
+        Arc::new(Mutex::new(buffer))
+    }
+
+    fn values(buffer: &DerivedBuffer<RingBuffer<f32>, RingBuffer<f32>>) -> Vec<f32> {
+        (0..buffer.len()).map(|i| buffer[i]).collect()
+    }
+
+    #[test]
+    fn difference_in_decibels() {
+        let input = shared(&[1.0, 0.5, 0.25]);
+        let output = shared(&[0.5, 0.5, 0.5]);
+        let mut delta = DerivedBuffer::decibels(input, output, |input, output| output - input);
+        delta.refresh();
+
+        let delta = values(&delta);
+        assert!((delta[0] + 6.0206).abs() < 1e-3);
+        assert_eq!(delta[1], 0.);
+        assert!((delta[2] - 6.0206).abs() < 1e-3);
+    }
+
+    #[test]
+    fn mismatched_lengths() {
+        // The most recent elements line up
+        let a = shared(&[1.0, 2.0, 3.0, 4.0]);
+        let b = shared(&[10.0, 20.0]);
+        let mut sum = DerivedBuffer::new(a, b, |a, b| a + b);
+        sum.refresh();
+        assert_eq!(values(&sum), vec![13.0, 24.0]);
+    }
+
+    #[test]
+    fn cached_per_generation_pair() {
+        static COMBINED: AtomicUsize = AtomicUsize::new(0);
+
+        let a = shared(&[1.0, 2.0]);
+        let b = shared(&[3.0, 4.0]);
+        let mut sum = DerivedBuffer::new(a.clone(), b.clone(), |a, b| {
+            COMBINED.fetch_add(1, Ordering::Relaxed);
+            a + b
+        });
+
+        sum.refresh();
+        sum.refresh();
+        assert_eq!(COMBINED.load(Ordering::Relaxed), 2);
+
+        // Changing either buffer computes the values again
+        b.lock().unwrap().enqueue(5.0);
+        sum.refresh();
+        assert_eq!(COMBINED.load(Ordering::Relaxed), 4);
+        assert_eq!(values(&sum), vec![5.0, 7.0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn same_buffer() {
+        let a = shared(&[1.0]);
+        DerivedBuffer::new(a.clone(), a, |a, b| a - b);
+    }
+}
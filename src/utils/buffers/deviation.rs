@@ -0,0 +1,208 @@
+use super::RingBuffer;
+
+/// Records the mean and the standard deviation of the values inside each element of
+/// a buffer, so that a graph can show how unstable a measurement is around its
+/// trace.
+///
+/// The element that is being accumulated is tracked using Welford's algorithm,
+/// which only takes a count, the mean and the sum of the squared differences from
+/// it, no matter how many values an element spans. Once the element is enqueued,
+/// only its mean and deviation are kept. Like the [tags](super::tags::SlotTags),
+/// they scroll and get evicted along with the buffer's elements.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SlotDeviation {
+    slots: RingBuffer<(f32, f32)>,
+    // Welford's accumulators for the element that is being accumulated
+    count: u32,
+    mean: f64,
+    m2: f64,
+}
+
+impl SlotDeviation {
+    pub(crate) fn new(size: usize) -> Self {
+        Self {
+            slots: RingBuffer::new(size),
+            count: 0,
+            mean: 0.,
+            m2: 0.,
+        }
+    }
+
+    /// Adds a value to the element that is being accumulated.
+    pub(crate) fn observe(self: &mut Self, value: f32) {
+        self.count += 1;
+        let delta = value as f64 - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value as f64 - self.mean);
+    }
+
+    /// Adds a number of zeros to the element that is being accumulated at once.
+    ///
+    /// This merges the zeros into the accumulators using Chan's formula, which is
+    /// the same as observing them one by one.
+    pub(crate) fn observe_silence(self: &mut Self, samples: u64) {
+        if samples == 0 {
+            return;
+        }
+        let count = self.count as f64 + samples as f64;
+        let delta = -self.mean;
+        self.m2 += delta * delta * self.count as f64 * samples as f64 / count;
+        self.mean += delta * samples as f64 / count;
+        self.count = count.min(u32::MAX as f64) as u32;
+    }
+
+    /// Returns the mean and the standard deviation of the values that were observed
+    /// since the last element was enqueued.
+    ///
+    /// This is the population deviation - the values of an element are all of the
+    /// values it covers, not a sample of them.
+    pub(crate) fn window(self: &Self) -> (f32, f32) {
+        if self.count == 0 {
+            return (0., 0.);
+        }
+        (
+            self.mean as f32,
+            (self.m2 / self.count as f64).max(0.).sqrt() as f32,
+        )
+    }
+
+    /// Records the element that was just enqueued into the buffer, and starts the
+    /// next one.
+    pub(crate) fn enqueued(self: &mut Self) {
+        self.slots.enqueue(self.window());
+        self.reset_window();
+    }
+
+    /// Discards the values of the element that is being accumulated.
+    pub(crate) fn reset_window(self: &mut Self) {
+        self.count = 0;
+        self.mean = 0.;
+        self.m2 = 0.;
+    }
+
+    /// Returns the mean and the standard deviation of the element at the index.
+    pub(crate) fn get(self: &Self, index: usize) -> Option<(f32, f32)> {
+        self.slots.get(index).copied()
+    }
+
+    /// Clears the recorded elements, resizing them to the buffer's length.
+    pub(crate) fn reset(self: &mut Self, size: usize) {
+        if self.slots.len() != size {
+            self.slots = RingBuffer::new(size);
+        } else {
+            self.slots.clear();
+        }
+        self.reset_window();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SlotDeviation;
+    use crate::utils::{Aggregation, PeakBuffer, VisualizerBuffer};
+
+    // The mean and the population deviation, computed the textbook way
+    fn offline(values: &[f32]) -> (f32, f32) {
+        let n = values.len() as f64;
+        let mean = values.iter().map(|v| *v as f64).sum::<f64>() / n;
+        let variance = values
+            .iter()
+            .map(|v| (*v as f64 - mean).powi(2))
+            .sum::<f64>()
+            / n;
+        (mean as f32, variance.sqrt() as f32)
+    }
+
+    fn assert_near(actual: (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-5 && (actual.1 - expected.1).abs() < 1e-5,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn matches_offline() {
+        let windows: [&[f32]; 4] = [
+            &[2., 4., 4., 4., 5., 5., 7., 9.],
+            &[0.5; 6],
+            &[0.25],
+            // A large offset, which the naive sum of squares loses precision on
+            &[1000.1, 1000.2, 1000.3, 1000.4],
+        ];
+        let mut deviation = SlotDeviation::new(4);
+        for window in windows {
+            for value in window {
+                deviation.observe(*value);
+            }
+            deviation.enqueued();
+        }
+        for (i, window) in windows.iter().enumerate() {
+            assert_near(deviation.get(i).unwrap(), offline(window));
+        }
+        assert_eq!(deviation.get(0), Some((5., 2.)));
+        assert_eq!(deviation.get(1), Some((0.5, 0.)));
+
+        // An empty element has neither
+        deviation.enqueued();
+        assert_eq!(deviation.get(3), Some((0., 0.)));
+    }
+
+    #[test]
+    fn silence_matches_zeros() {
+        let mut merged = SlotDeviation::new(1);
+        let mut observed = SlotDeviation::new(1);
+        for value in [0.5, 0.25, 1.0] {
+            merged.observe(value);
+            observed.observe(value);
+        }
+        merged.observe_silence(5);
+        for _ in 0..5 {
+            observed.observe(0.);
+        }
+        assert_near(merged.window(), observed.window());
+
+        // Silence on its own has neither a mean nor a deviation
+        let mut silent = SlotDeviation::new(1);
+        silent.observe_silence(3);
+        assert_eq!(silent.window(), (0., 0.));
+    }
+
+    #[test]
+    fn tracked_per_element() {
+        // 10 samples per element, the first of which is finalized by the 11th sample
+        let mut buffer =
+            PeakBuffer::with_aggregation(8, 1.0, 0.0, Aggregation::Mean).with_deviation_tracking();
+        buffer.set_sample_rate(80.);
+        let signal = (0..81)
+            .map(|i| ((i * 7) % 11) as f32 / 10. - 0.5)
+            .collect::<Vec<_>>();
+        for sample in &signal {
+            buffer.enqueue(*sample);
+        }
+
+        let magnitudes = signal.iter().map(|s| s.abs()).collect::<Vec<_>>();
+        for i in 0..8 {
+            let window = &magnitudes[i * 10..(i + 1) * 10];
+            let (mean, deviation) = buffer.deviation(i).unwrap();
+            assert_near((mean, deviation), offline(window));
+            // The mean is the one the buffer aggregates
+            assert!((buffer[i] - mean).abs() < 1e-5);
+        }
+
+        // Clearing the buffer clears the deviations as well
+        buffer.clear();
+        assert_eq!(buffer.deviation(7), Some((0., 0.)));
+    }
+
+    #[test]
+    fn inert_unless_enabled() {
+        let mut buffer = PeakBuffer::with_aggregation(8, 1.0, 0.0, Aggregation::Mean);
+        buffer.set_sample_rate(80.);
+        for _ in 0..100 {
+            buffer.enqueue(0.5);
+        }
+        assert_eq!(buffer.deviation(0), None);
+    }
+}
@@ -0,0 +1,157 @@
+use std::ops::Range;
+
+/// How the channels of a frame are combined into the single value that a buffer
+/// enqueues, when feeding it
+/// [interleaved](super::VisualizerBuffer::enqueue_interleaved) or
+/// [planar](super::VisualizerBuffer::enqueue_planar) audio.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ChannelDownmix {
+    /// The mean of all channels, like
+    /// [`enqueue_buffer()`](super::VisualizerBuffer::enqueue_buffer) with no channel.
+    #[default]
+    Mean,
+    /// The sample with the largest absolute value, keeping its sign.
+    MaxAbs,
+    /// A single channel, like
+    /// [`enqueue_buffer()`](super::VisualizerBuffer::enqueue_buffer) with that
+    /// channel; Frames without it are silent.
+    Channel(usize),
+}
+
+impl ChannelDownmix {
+    /// Combines the samples of a frame into one.
+    pub(crate) fn mix(self: Self, frame: &[f32]) -> f32 {
+        match self {
+            ChannelDownmix::Mean => (1. / frame.len() as f32) * frame.iter().sum::<f32>(),
+            ChannelDownmix::MaxAbs => max_abs(frame.iter().copied()),
+            ChannelDownmix::Channel(channel) => frame.get(channel).copied().unwrap_or(0.),
+        }
+    }
+
+    /// Combines the samples at the index of each channel into one.
+    pub(crate) fn mix_planar(self: Self, channels: &[&[f32]], index: usize) -> f32 {
+        match self {
+            ChannelDownmix::Mean => {
+                (1. / channels.len() as f32)
+                    * channels.iter().map(|channel| channel[index]).sum::<f32>()
+            }
+            ChannelDownmix::MaxAbs => max_abs(channels.iter().map(|channel| channel[index])),
+            ChannelDownmix::Channel(channel) => {
+                channels.get(channel).map_or(0., |channel| channel[index])
+            }
+        }
+    }
+
+    /// Returns `true` if none of the samples that the downmix reads from the frame are
+    /// audible.
+    pub(crate) fn is_silent(self: Self, frame: &[f32]) -> bool {
+        match self {
+            ChannelDownmix::Channel(channel) => frame.get(channel).is_none_or(|s| *s == 0.0),
+            _ => frame.iter().all(|sample| *sample == 0.0),
+        }
+    }
+
+    /// Returns `true` if none of the samples that the downmix reads from the range of
+    /// indices are audible.
+    pub(crate) fn is_silent_planar(self: Self, channels: &[&[f32]], range: Range<usize>) -> bool {
+        let silent = |channel: &[f32]| channel[range.clone()].iter().all(|s| *s == 0.0);
+        match self {
+            ChannelDownmix::Channel(channel) => channels.get(channel).is_none_or(|c| silent(c)),
+            _ => channels.iter().all(|channel| silent(channel)),
+        }
+    }
+}
+
+fn max_abs(samples: impl Iterator<Item = f32>) -> f32 {
+    samples.fold(0., |max, sample| {
+        if sample.abs() > max.abs() {
+            sample
+        } else {
+            max
+        }
+    })
+}
+
+/// Returns the number of whole frames of interleaved audio.
+///
+/// A channel count of 0, or a trailing partial frame, fails a debug assertion; In
+/// release builds, only the whole frames are processed.
+pub(crate) fn interleaved_frames(data: &[f32], channels: usize) -> usize {
+    debug_assert!(channels > 0, "interleaved audio needs at least one channel");
+    if channels == 0 {
+        return 0;
+    }
+    debug_assert!(
+        data.len() % channels == 0,
+        "{} samples don't divide into frames of {} channels",
+        data.len(),
+        channels
+    );
+    data.len() / channels
+}
+
+/// Returns the number of samples that all channels of planar audio hold.
+///
+/// Channels of different lengths fail a debug assertion; In release builds, only
+/// the samples that all channels hold are processed.
+pub(crate) fn planar_frames(channels: &[&[f32]]) -> usize {
+    let frames = channels.iter().map(|channel| channel.len()).min();
+    debug_assert!(
+        channels.iter().all(|channel| Some(channel.len()) == frames),
+        "all planar channels need to have the same length"
+    );
+    frames.unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{interleaved_frames, planar_frames, ChannelDownmix};
+
+    #[test]
+    fn mix() {
+        let frame = [0.5, -0.75, 0.25];
+        let planar: Vec<&[f32]> = frame.iter().map(std::slice::from_ref).collect();
+        for (downmix, expected) in [
+            (ChannelDownmix::Mean, 0.),
+            (ChannelDownmix::MaxAbs, -0.75),
+            (ChannelDownmix::Channel(2), 0.25),
+            (ChannelDownmix::Channel(3), 0.),
+        ] {
+            assert_eq!(downmix.mix(&frame), expected, "{:?}", downmix);
+            assert_eq!(downmix.mix_planar(&planar, 0), expected, "{:?}", downmix);
+        }
+    }
+
+    #[test]
+    fn silence() {
+        let frame = [0., 0.5];
+        assert!(!ChannelDownmix::Mean.is_silent(&frame));
+        assert!(ChannelDownmix::Channel(0).is_silent(&frame));
+        assert!(!ChannelDownmix::Channel(1).is_silent(&frame));
+        let planar: [&[f32]; 2] = [&[0., 0.], &[0., 0.5]];
+        assert!(ChannelDownmix::Mean.is_silent_planar(&planar, 0..1));
+        assert!(!ChannelDownmix::Mean.is_silent_planar(&planar, 0..2));
+    }
+
+    #[test]
+    fn frames() {
+        assert_eq!(interleaved_frames(&[0.; 6], 2), 3);
+        assert_eq!(interleaved_frames(&[], 2), 0);
+        assert_eq!(planar_frames(&[&[0.; 4], &[0.; 4]]), 4);
+        assert_eq!(planar_frames(&[]), 0);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "don't divide into frames")]
+    fn partial_frame() {
+        interleaved_frames(&[0.; 5], 2);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "at least one channel")]
+    fn no_channels() {
+        interleaved_frames(&[0.; 4], 0);
+    }
+}
@@ -0,0 +1,460 @@
+//! A compact binary format for buffer snapshots, for displaying buffers outside of
+//! Rust - e.g. in a GUI that runs in a separate process.
+//!
+//! Unlike a [`BufferSnapshot`](super::BufferSnapshot), which is meant for
+//! debugging, this format is written without allocating and can be read without
+//! copying. It consists of a [`SnapshotHeader`] followed by the raw values of the
+//! buffer, from the oldest to the newest element. All fields are little-endian:
+//!
+//! | Offset | Type      | Field                                      |
+//! |--------|-----------|--------------------------------------------|
+//! | 0      | `[u8; 4]` | Magic bytes, `CYMA`                        |
+//! | 4      | `u8`      | Version, currently 1                       |
+//! | 5      | `u8`      | [`ValueFormat`]                            |
+//! | 6      | `[u8; 2]` | Reserved, 0                                |
+//! | 8      | `u32`     | Size - the number of elements              |
+//! | 12     | `f32`     | Duration in seconds, 0 if not time-based   |
+//! | 16     | `f32`     | Sample rate, 0 if unknown                  |
+//! | 20     | `f32`...  | The values                                 |
+//!
+//! This module is only available with the `ffi` feature enabled.
+
+use std::fmt;
+use std::sync::Mutex;
+
+/// The magic bytes at the start of each snapshot.
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"CYMA";
+/// The version of the snapshot format that is written.
+pub const SNAPSHOT_VERSION: u8 = 1;
+/// The length of the header, in bytes.
+pub const SNAPSHOT_HEADER_LEN: usize = 20;
+
+/// How the values of each element are stored.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueFormat {
+    /// One `f32` per element.
+    F32 = 0,
+    /// Two `f32`s per element, the minimum followed by the maximum.
+    MinMaxF32 = 1,
+}
+
+impl ValueFormat {
+    /// Returns the number of values that make up each element.
+    pub fn values_per_element(self) -> usize {
+        match self {
+            ValueFormat::F32 => 1,
+            ValueFormat::MinMaxF32 => 2,
+        }
+    }
+
+    fn from_u8(format: u8) -> Option<Self> {
+        match format {
+            0 => Some(ValueFormat::F32),
+            1 => Some(ValueFormat::MinMaxF32),
+            _ => None,
+        }
+    }
+}
+
+/// The header of a snapshot.
+///
+/// The in-memory layout matches the encoded header on little-endian platforms, so
+/// the struct can be mirrored in C.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SnapshotHeader {
+    pub magic: [u8; 4],
+    pub version: u8,
+    /// The [`ValueFormat`], as a `u8`.
+    pub format: u8,
+    pub reserved: [u8; 2],
+    /// The number of elements.
+    pub size: u32,
+    /// The duration (in seconds) of the audio data inside the buffer, or 0 if the
+    /// buffer isn't time-based.
+    pub duration: f32,
+    /// The sample rate of the audio the buffer was fed, or 0 if unknown.
+    pub sample_rate: f32,
+}
+
+impl SnapshotHeader {
+    /// Constructs the header of a snapshot with the current version.
+    pub fn new(
+        format: ValueFormat,
+        size: usize,
+        duration: Option<f32>,
+        sample_rate: Option<f32>,
+    ) -> Self {
+        Self {
+            magic: SNAPSHOT_MAGIC,
+            version: SNAPSHOT_VERSION,
+            format: format as u8,
+            reserved: [0; 2],
+            size: size as u32,
+            duration: duration.unwrap_or(0.),
+            sample_rate: sample_rate.unwrap_or(0.),
+        }
+    }
+
+    /// Writes the header, followed by the given values, replacing the contents of
+    /// `out`.
+    ///
+    /// Once `out` has grown large enough, this doesn't allocate anymore.
+    pub fn write(self: &Self, out: &mut Vec<u8>, values: impl IntoIterator<Item = f32>) {
+        out.clear();
+        let values_len = self.size as usize
+            * ValueFormat::from_u8(self.format).map_or(1, ValueFormat::values_per_element);
+        out.reserve(SNAPSHOT_HEADER_LEN + values_len * 4);
+
+        out.extend_from_slice(&self.magic);
+        out.push(self.version);
+        out.push(self.format);
+        out.extend_from_slice(&self.reserved);
+        out.extend_from_slice(&self.size.to_le_bytes());
+        out.extend_from_slice(&self.duration.to_le_bytes());
+        out.extend_from_slice(&self.sample_rate.to_le_bytes());
+
+        for value in values.into_iter().take(values_len) {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    fn read(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        if bytes.len() < SNAPSHOT_HEADER_LEN {
+            return Err(SnapshotError::LengthMismatch {
+                expected: SNAPSHOT_HEADER_LEN,
+                actual: bytes.len(),
+            });
+        }
+        let array = |offset: usize| -> [u8; 4] {
+            [
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ]
+        };
+
+        Ok(Self {
+            magic: array(0),
+            version: bytes[4],
+            format: bytes[5],
+            reserved: [bytes[6], bytes[7]],
+            size: u32::from_le_bytes(array(8)),
+            duration: f32::from_le_bytes(array(12)),
+            sample_rate: f32::from_le_bytes(array(16)),
+        })
+    }
+}
+
+/// An error that occurred while parsing a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// The bytes don't start with the [magic bytes](SNAPSHOT_MAGIC).
+    InvalidMagic,
+    /// The snapshot was written with a version of the format that can't be read.
+    UnsupportedVersion(u8),
+    /// The value format is unknown.
+    UnknownFormat(u8),
+    /// The snapshot is truncated, or followed by extra bytes.
+    LengthMismatch { expected: usize, actual: usize },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::InvalidMagic => write!(f, "not a snapshot"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "unsupported snapshot version {}", version)
+            }
+            SnapshotError::UnknownFormat(format) => write!(f, "unknown value format {}", format),
+            SnapshotError::LengthMismatch { expected, actual } => {
+                write!(f, "expected {} bytes, found {}", expected, actual)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+/// A snapshot that is read directly from its encoded bytes, without copying them.
+#[derive(Debug, Clone, Copy)]
+pub struct SnapshotRef<'a> {
+    header: SnapshotHeader,
+    format: ValueFormat,
+    values: &'a [u8],
+}
+
+impl<'a> SnapshotRef<'a> {
+    /// Parses a snapshot that was written using
+    /// [`write_snapshot()`](WriteSnapshot::write_snapshot).
+    ///
+    /// The bytes need to contain exactly one snapshot.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, SnapshotError> {
+        let header = SnapshotHeader::read(bytes)?;
+
+        if header.magic != SNAPSHOT_MAGIC {
+            return Err(SnapshotError::InvalidMagic);
+        }
+        if header.version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(header.version));
+        }
+        let format = ValueFormat::from_u8(header.format)
+            .ok_or(SnapshotError::UnknownFormat(header.format))?;
+
+        let expected = (header.size as usize)
+            .saturating_mul(format.values_per_element() * 4)
+            .saturating_add(SNAPSHOT_HEADER_LEN);
+        if bytes.len() != expected {
+            return Err(SnapshotError::LengthMismatch {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+
+        Ok(Self {
+            header,
+            format,
+            values: &bytes[SNAPSHOT_HEADER_LEN..],
+        })
+    }
+
+    /// Returns the header of the snapshot.
+    pub fn header(&self) -> &SnapshotHeader {
+        &self.header
+    }
+
+    /// Returns how the values of each element are stored.
+    pub fn format(&self) -> ValueFormat {
+        self.format
+    }
+
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize {
+        self.header.size as usize
+    }
+
+    /// Returns `true` if the snapshot contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the duration (in seconds) of the audio data inside the buffer, or
+    /// `None` if the buffer isn't time-based.
+    pub fn duration(&self) -> Option<f32> {
+        (self.header.duration != 0.).then_some(self.header.duration)
+    }
+
+    /// Returns the sample rate of the audio the buffer was fed, if known.
+    pub fn sample_rate(&self) -> Option<f32> {
+        (self.header.sample_rate != 0.).then_some(self.header.sample_rate)
+    }
+
+    /// Returns the value at the given index, counting every value of every element.
+    ///
+    /// For [`ValueFormat::MinMaxF32`], the minimum of element `i` is at index `2 * i`,
+    /// and the maximum at `2 * i + 1`.
+    pub fn value(&self, index: usize) -> Option<f32> {
+        // An index that large can't be inside the snapshot, and would overflow
+        let start = index.checked_mul(4)?;
+        let bytes = self.values.get(start..start.checked_add(4)?)?;
+        Some(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Returns an iterator over all values, from the oldest to the newest element.
+    pub fn values(&self) -> impl ExactSizeIterator<Item = f32> + 'a {
+        self.values
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+/// Allows writing a buffer as a binary snapshot.
+///
+/// Pass the same `Vec` every time, so that writing doesn't allocate once it has
+/// grown large enough:
+///
+/// ```
+/// // On the DSP side
+/// self.peak_buffer.write_snapshot(&mut self.snapshot_bytes);
+/// ipc.send(&self.snapshot_bytes);
+///
+/// // On the GUI side
+/// let snapshot = SnapshotRef::parse(&received)?;
+/// for value in snapshot.values() { ... }
+/// ```
+pub trait WriteSnapshot {
+    /// Writes a snapshot of the buffer's contents, replacing the contents of `out`.
+    fn write_snapshot(&self, out: &mut Vec<u8>);
+}
+
+impl<B: WriteSnapshot> WriteSnapshot for Mutex<B> {
+    fn write_snapshot(&self, out: &mut Vec<u8>) {
+        match self.lock() {
+            Ok(buffer) => buffer.write_snapshot(out),
+            Err(poisoned) => poisoned.into_inner().write_snapshot(out),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SnapshotError, SnapshotRef, ValueFormat, WriteSnapshot, SNAPSHOT_HEADER_LEN};
+    use crate::alloc_counter::assert_no_alloc;
+    use crate::utils::{HistogramBuffer, PeakBuffer, VisualizerBuffer, WaveformBuffer};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn peak_snapshot() -> (PeakBuffer, Vec<u8>) {
+        let mut buffer = PeakBuffer::new(16, 1.0, 50.0);
+        buffer.set_sample_rate(160.);
+        for i in 0..400 {
+            buffer.enqueue((i as f32 * 0.1).sin());
+        }
+
+        let mut bytes = Vec::new();
+        buffer.write_snapshot(&mut bytes);
+        (buffer, bytes)
+    }
+
+    #[test]
+    fn round_trip() {
+        let (buffer, bytes) = peak_snapshot();
+        assert_eq!(bytes.len(), SNAPSHOT_HEADER_LEN + 16 * 4);
+
+        let snapshot = SnapshotRef::parse(&bytes).unwrap();
+        assert_eq!(snapshot.format(), ValueFormat::F32);
+        assert_eq!(snapshot.len(), 16);
+        assert_eq!(snapshot.duration(), Some(1.0));
+        assert_eq!(snapshot.sample_rate(), Some(160.));
+        assert_eq!(
+            snapshot.values().collect::<Vec<_>>(),
+            (0..16).map(|i| buffer[i]).collect::<Vec<_>>()
+        );
+        assert_eq!(snapshot.value(3), Some(buffer[3]));
+        assert_eq!(snapshot.value(16), None);
+        assert_eq!(snapshot.value(usize::MAX / 4 + 1), None);
+        assert_eq!(snapshot.value(usize::MAX), None);
+    }
+
+    #[test]
+    fn round_trip_min_max() {
+        let mut buffer = WaveformBuffer::new(8, 1.0);
+        buffer.set_sample_rate(64.);
+        for i in 0..100 {
+            buffer.enqueue((i as f32 * 0.3).sin());
+        }
+
+        let mut bytes = Vec::new();
+        buffer.write_snapshot(&mut bytes);
+
+        let snapshot = SnapshotRef::parse(&bytes).unwrap();
+        assert_eq!(snapshot.format(), ValueFormat::MinMaxF32);
+        assert_eq!(snapshot.len(), 8);
+        assert_eq!(
+            snapshot.values().collect::<Vec<_>>(),
+            (0..8)
+                .flat_map(|i| [buffer[i].0, buffer[i].1])
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn not_time_based() {
+        let mut bytes = Vec::new();
+        HistogramBuffer::new(32, 0.1).write_snapshot(&mut bytes);
+
+        let snapshot = SnapshotRef::parse(&bytes).unwrap();
+        assert_eq!(snapshot.len(), 32);
+        assert_eq!(snapshot.duration(), None);
+    }
+
+    #[test]
+    fn reuses_allocation() {
+        let (buffer, mut bytes) = peak_snapshot();
+        let (pointer, capacity) = (bytes.as_ptr(), bytes.capacity());
+
+        for _ in 0..4 {
+            assert_no_alloc("write_snapshot()", || buffer.write_snapshot(&mut bytes));
+        }
+        assert_eq!((bytes.as_ptr(), bytes.capacity()), (pointer, capacity));
+    }
+
+    #[test]
+    fn invalid() {
+        let (_, bytes) = peak_snapshot();
+
+        let mut corrupted = bytes.clone();
+        corrupted[0] = b'X';
+        assert_eq!(
+            SnapshotRef::parse(&corrupted).unwrap_err(),
+            SnapshotError::InvalidMagic
+        );
+
+        let mut corrupted = bytes.clone();
+        corrupted[4] = 2;
+        assert_eq!(
+            SnapshotRef::parse(&corrupted).unwrap_err(),
+            SnapshotError::UnsupportedVersion(2)
+        );
+
+        let mut corrupted = bytes.clone();
+        corrupted[5] = 7;
+        assert_eq!(
+            SnapshotRef::parse(&corrupted).unwrap_err(),
+            SnapshotError::UnknownFormat(7)
+        );
+
+        let mut corrupted = bytes.clone();
+        corrupted.push(0);
+        assert!(matches!(
+            SnapshotRef::parse(&corrupted),
+            Err(SnapshotError::LengthMismatch { .. })
+        ));
+
+        // A huge size must not overflow
+        let mut corrupted = bytes.clone();
+        corrupted[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            SnapshotRef::parse(&corrupted),
+            Err(SnapshotError::LengthMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn truncated() {
+        let (_, bytes) = peak_snapshot();
+        for len in 0..bytes.len() {
+            assert!(SnapshotRef::parse(&bytes[..len]).is_err(), "length {}", len);
+        }
+    }
+
+    #[test]
+    fn corrupted() {
+        let (_, bytes) = peak_snapshot();
+        let mut rng = StdRng::seed_from_u64(3);
+
+        for _ in 0..2000 {
+            let mut corrupted = bytes.clone();
+            for _ in 0..rng.gen_range(1..4) {
+                let index = rng.gen_range(0..corrupted.len());
+                corrupted[index] = rng.gen();
+            }
+            corrupted.truncate(rng.gen_range(0..=corrupted.len()));
+
+            // Either way, reading must not panic
+            if let Ok(snapshot) = SnapshotRef::parse(&corrupted) {
+                assert_eq!(
+                    snapshot.values().count(),
+                    snapshot.len() * snapshot.format().values_per_element()
+                );
+            }
+        }
+
+        for _ in 0..2000 {
+            let len = rng.gen_range(0..64);
+            let garbage = (0..len).map(|_| rng.gen()).collect::<Vec<u8>>();
+            let _ = SnapshotRef::parse(&garbage);
+        }
+    }
+}
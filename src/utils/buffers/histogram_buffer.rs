@@ -1,6 +1,14 @@
-use super::VisualizerBuffer;
+use super::{
+    super::config_error::{
+        check_decay, check_range, check_size, clamp_decay, clamp_range, ConfigError,
+    },
+    check_index,
+    downmix::{interleaved_frames, planar_frames},
+    name::BufferName,
+    BufferSnapshot, ChannelDownmix, SnapshotExport, VisualizerBuffer, MAX_CHUNK_SIZE,
+};
 use std::fmt::Debug;
-use std::ops::{Index, IndexMut};
+use std::ops::{Index, IndexMut, Range};
 
 /// This buffer creates histogram data with variable decay from a signal.
 ///
@@ -20,6 +28,16 @@ pub struct HistogramBuffer {
     decay_weight: f32,
     edges: Vec<f32>,
     range: (f32, f32),
+    // Whether silent samples are skipped instead of advancing the decay
+    gate_silence: bool,
+    // Whether the plug-in is rendering offline, in which case nothing is enqueued
+    offline: bool,
+    name: BufferName,
+    // Changes whenever the contents of the bins change
+    generation: u64,
+    // The number of samples that were ever added to a bin, used by the registry
+    #[cfg(feature = "debug-tools")]
+    enqueued: u64,
 }
 
 impl HistogramBuffer {
@@ -32,6 +50,7 @@ impl HistogramBuffer {
     /// calling [`set_sample_rate`](Self::set_sample_rate) inside your
     /// [`initialize()`](nih_plug::plugin::Plugin::initialize) function.
     pub fn new(size: usize, decay: f32) -> Self {
+        let decay = clamp_decay("histogram buffer", decay);
         let decay_weight = Self::decay_weight(decay, 48000.);
         Self {
             size,
@@ -41,22 +60,188 @@ impl HistogramBuffer {
             decay_weight,
             edges: vec![f32::default(); size - 1],
             range: (-96., 24.),
+            gate_silence: false,
+            offline: false,
+            name: BufferName::default(),
+            generation: 0,
+            #[cfg(feature = "debug-tools")]
+            enqueued: 0,
         }
     }
 
-    pub(crate) fn set_range(&mut self, range: (f32, f32)) {
+    /// Constructs a new HistogramBuffer, or returns a [`ConfigError`] if the size is
+    /// 0 or the decay is negative; See [`new()`](Self::new).
+    pub fn try_new(size: usize, decay: f32) -> Result<Self, ConfigError> {
+        check_size("histogram buffer", "size", size)?;
+        check_decay("histogram buffer", decay)?;
+        Ok(Self::new(size, decay))
+    }
+
+    /// Names the `HistogramBuffer`, which identifies it in panic messages, snapshots
+    /// and the debug registry.
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.name.set(name);
+        self
+    }
+
+    /// Sets the name of the `HistogramBuffer`; See [`with_name()`](Self::with_name).
+    pub fn set_name(self: &mut Self, name: String) {
+        self.name.set(name);
+    }
+
+    /// Returns the name of the `HistogramBuffer`, if it has one.
+    pub fn name(self: &Self) -> Option<&str> {
+        self.name.get()
+    }
+
+    /// Constructs a new HistogramBuffer for displaying a loudness distribution.
+    ///
+    /// * `size` - The number of bins; 401 bins give a resolution of 0.1 LU
+    ///
+    /// Rather than audio samples, this buffer is meant to be fed short-term loudness
+    /// values at a fixed rate, converted to gain - e.g.
+    /// `buffer.enqueue(util::db_to_gain(short_term_lufs))` every 100 ms. The range
+    /// is set to -40 to 0 LUFS, and older values decay by -12dB over 60 seconds,
+    /// assuming 10 values per second. If you enqueue at a different rate, pass it to
+    /// [`set_sample_rate`](Self::set_sample_rate).
+    ///
+    /// The same data can then be used to display the
+    /// [`loudness_range`](Self::loudness_range).
+    pub fn loudness(size: usize) -> Self {
+        let mut buffer = Self::new(size, 60.);
+        buffer.sample_rate = 10.;
+        buffer.set_range((-40., 0.));
+        buffer
+    }
+
+    /// Sets the range of the histogram, in dB.
+    ///
+    /// A range whose bounds aren't finite, or whose minimum isn't smaller than its
+    /// maximum fails a debug assertion, and is clamped in release builds; Use
+    /// [`try_set_range()`](Self::try_set_range) to handle it instead.
+    ///
+    /// The bins keep their contents, so whatever they hold is now displayed at the
+    /// levels of the new range; Use [`zoom_range()`](Self::zoom_range) to keep the
+    /// histogram in place instead.
+    pub fn set_range(self: &mut Self, range: (f32, f32)) {
+        self.range = clamp_range("histogram buffer", range);
+        self.update();
+        // The bins now stand for different levels
+        self.touch();
+    }
+
+    /// Changes the range of the histogram, in dB, and re-bins its contents into the
+    /// new range, so that the histogram stays where it was - e.g. when zooming into a
+    /// part of it.
+    ///
+    /// Each bin inside the old range is distributed across the new bins it overlaps,
+    /// in proportion to the overlap, assuming that its contents are evenly spread
+    /// across its width. Whatever lies below or above the new range is added to the
+    /// bin below or above it, so the total is preserved. That part of the histogram
+    /// can't be told apart anymore, though: Zooming back out leaves it below or above
+    /// the range, rather than putting it back where it was.
+    ///
+    /// Like [`set_range()`](Self::set_range), an invalid range fails a debug
+    /// assertion, and is clamped in release builds.
+    pub fn zoom_range(self: &mut Self, range: (f32, f32)) {
+        let range = clamp_range("histogram buffer", range);
+        let inner = self.size.saturating_sub(2);
+        if range == self.range || inner == 0 {
+            self.range = range;
+            self.update();
+            return;
+        }
+
+        let (old_min, old_max) = (self.range.0 as f64, self.range.1 as f64);
+        let (new_min, new_max) = (range.0 as f64, range.1 as f64);
+        let old_step = (old_max - old_min) / inner as f64;
+        let new_step = (new_max - new_min) / inner as f64;
+        let last = self.size - 1;
+
+        let mut zoomed = vec![0f64; self.size];
+        zoomed[0] = self.data[0] as f64;
+        zoomed[last] = self.data[last] as f64;
+        for i in 1..=inner {
+            let value = self.data[i] as f64;
+            if value == 0. {
+                continue;
+            }
+            let (start, end) = (
+                old_min + (i - 1) as f64 * old_step,
+                old_min + i as f64 * old_step,
+            );
+            let share = |from: f64, to: f64| value * ((to.min(end) - from.max(start)) / old_step);
+
+            zoomed[0] += share(f64::NEG_INFINITY, new_min).max(0.);
+            zoomed[last] += share(new_max, f64::INFINITY).max(0.);
+            if end <= new_min || start >= new_max {
+                continue;
+            }
+            // The new bins that the old one overlaps
+            let first = ((start.max(new_min) - new_min) / new_step).floor() as usize;
+            let mut j = first.min(inner - 1);
+            while j < inner {
+                let (bin_start, bin_end) = (
+                    new_min + j as f64 * new_step,
+                    new_min + (j + 1) as f64 * new_step,
+                );
+                if bin_start >= end {
+                    break;
+                }
+                zoomed[j + 1] += share(bin_start, bin_end).max(0.);
+                j += 1;
+            }
+        }
+
+        for (bin, value) in self.data.iter_mut().zip(zoomed) {
+            *bin = value as f32;
+        }
         self.range = range;
         self.update();
+        self.touch();
+    }
+
+    /// Sets the range of the histogram, in dB, or returns a [`ConfigError`] if either
+    /// bound isn't finite, or if the minimum isn't smaller than the maximum.
+    pub fn try_set_range(self: &mut Self, range: (f32, f32)) -> Result<(), ConfigError> {
+        self.set_range(check_range("histogram buffer", range)?);
+        Ok(())
     }
 
     /// Sets the decay time of the `HistogramBuffer`.
     ///
     /// * `decay` - The time it takes for a sample inside the buffer to decrease by -12dB, in milliseconds
+    ///
+    /// A negative or NaN decay fails a debug assertion, and is clamped to 0 in release
+    /// builds; Use [`try_set_decay()`](Self::try_set_decay) to handle it instead.
     pub fn set_decay(self: &mut Self, decay: f32) {
-        self.decay = decay;
+        self.decay = clamp_decay("histogram buffer", decay);
         self.update();
     }
 
+    /// Sets the decay time of the `HistogramBuffer`, or returns a [`ConfigError`] if
+    /// it's negative or NaN.
+    pub fn try_set_decay(self: &mut Self, decay: f32) -> Result<(), ConfigError> {
+        self.set_decay(check_decay("histogram buffer", decay)?);
+        Ok(())
+    }
+
+    /// Sets whether silent samples should be skipped.
+    ///
+    /// By default, silent samples are treated like any other sample, so the
+    /// histogram keeps decaying while the input is silent. With gating enabled,
+    /// silence is ignored entirely, which freezes the histogram until the signal
+    /// returns.
+    pub fn set_silence_gating(self: &mut Self, gate_silence: bool) {
+        self.gate_silence = gate_silence;
+    }
+
+    /// Hints whether the plug-in is rendering offline, which skips all enqueueing
+    /// while it is. See [`PeakBuffer::set_offline`](super::PeakBuffer::set_offline).
+    pub fn set_offline(self: &mut Self, offline: bool) {
+        self.offline = offline;
+    }
+
     /// Sets the sample rate of the incoming audio.
     ///
     /// This function **clears** the buffer. You can call it inside your
@@ -86,6 +271,119 @@ impl HistogramBuffer {
         self.clear();
     }
 
+    /// Computes the loudness range (LRA) of the distribution, in LU.
+    ///
+    /// This is meant for buffers created using [`loudness`](Self::loudness), and
+    /// follows EBU Tech 3342: Values that are more than 20 LU below the mean loudness
+    /// are gated out, and the LRA is the difference between the 10th and the 95th
+    /// percentile of the remaining values.
+    ///
+    /// Since it's computed from the histogram, this is only an approximation of the
+    /// standard measurement:
+    ///
+    /// - Each value is quantized to the center of its bin, so the result is only as
+    ///   precise as the bin width
+    /// - Values below the range are ignored, which stands in for the -70 LUFS
+    ///   absolute gate; Values above it count as the top of the range
+    /// - Older values are weighted less due to the decay, whereas the standard
+    ///   weighs the entire programme equally
+    ///
+    /// Returns 0 if the histogram is empty.
+    pub fn loudness_range(self: &Self) -> f32 {
+        let levels = self.bin_levels();
+        let bins = || (1..self.size).map(|i| (levels[i - 1], self.data[i]));
+
+        // Relative gate, 20 LU below the power mean of all values inside the range
+        let (energy, total) = bins().fold((0.0, 0.0), |(energy, total), (level, weight)| {
+            (energy + weight * 10f32.powf(level / 10.), total + weight)
+        });
+        if total <= 0.0 {
+            return 0.0;
+        }
+        let gate = 10. * (energy / total).log10() - 20.;
+
+        let gated = || bins().filter(|(level, _)| *level >= gate);
+        let total = gated().map(|(_, weight)| weight).sum::<f32>();
+        let percentile = |p: f32| {
+            let mut cumulative = 0.0;
+            gated()
+                .find(|(_, weight)| {
+                    cumulative += weight;
+                    cumulative >= p * total
+                })
+                .map_or(0.0, |(level, _)| level)
+        };
+
+        percentile(0.95) - percentile(0.10)
+    }
+
+    /// Re-bins the bins inside the range into `out_bins` bins, which are evenly spaced
+    /// in dB from the bottom of the range to the top.
+    ///
+    /// This decouples the number of bins from the resolution they're displayed at.
+    /// When there are fewer output bins than bins inside the range, each output bin
+    /// holds the sum of the bins it spans, with bins that straddle two output bins
+    /// split between them in proportion - so the total is preserved. When there are
+    /// more, the bins are linearly interpolated between their centers, and scaled
+    /// down so that the total stays about the same.
+    ///
+    /// The bins below and above the range aren't included. `out` is cleared and
+    /// refilled, so it can be reused from one call to the next without allocating.
+    pub fn rebinned(self: &Self, out_bins: usize, out: &mut Vec<f32>) {
+        out.clear();
+        out.resize(out_bins, 0.);
+
+        let bins = self.data.get(1..self.size.saturating_sub(1)).unwrap_or(&[]);
+        if bins.is_empty() || out_bins == 0 {
+            return;
+        }
+        // The number of bins inside the range per output bin
+        let ratio = bins.len() as f64 / out_bins as f64;
+
+        if ratio >= 1. {
+            for (i, value) in bins.iter().enumerate() {
+                // The span of the bin, measured in output bins
+                let (start, end) = (i as f64 / ratio, (i + 1) as f64 / ratio);
+                let mut j = start.floor() as usize;
+                while j < out_bins && (j as f64) < end {
+                    let overlap = end.min((j + 1) as f64) - start.max(j as f64);
+                    out[j] += (*value as f64 * overlap * ratio) as f32;
+                    j += 1;
+                }
+            }
+        } else {
+            let last = bins.len() - 1;
+            for (j, out) in out.iter_mut().enumerate() {
+                // The center of the output bin, measured between the centers of the bins
+                let center = ((j as f64 + 0.5) * ratio - 0.5).clamp(0., last as f64);
+                let i = center.floor() as usize;
+                let t = (center - i as f64) as f32;
+                let value = bins[i] * (1. - t) + bins[(i + 1).min(last)] * t;
+                *out = value * ratio as f32;
+            }
+        }
+    }
+
+    // The level at the center of each bin, except for the bin below the range
+    fn bin_levels(self: &Self) -> Vec<f32> {
+        let nr_edges = self.size - 1;
+        let step = (self.range.1 - self.range.0) / (nr_edges as f32 - 1.0);
+        (1..self.size)
+            .map(|i| {
+                if i == nr_edges {
+                    // Everything above the range
+                    self.range.1
+                } else {
+                    self.range.0 + (i as f32 - 0.5) * step
+                }
+            })
+            .collect()
+    }
+
+    fn touch(self: &mut Self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
     fn decay_weight(decay: f32, sample_rate: f32) -> f32 {
         0.25f64.powf((decay as f64 * sample_rate as f64).recip()) as f32
     }
@@ -111,8 +409,8 @@ impl HistogramBuffer {
 
     // Function to find the bin for a given linear audio value
     fn find_bin(&self, value: f32) -> usize {
-        // Check if the value is smaller than the first edge
-        if value < self.edges[0] {
+        // Check if the value is smaller than the first edge, or not a number at all
+        if value.is_nan() || value < self.edges[0] {
             // if value < f32::EPSILON {
             // if value == 0.0 {
             return 0;
@@ -136,6 +434,42 @@ impl HistogramBuffer {
         // Return the bin index
         left as usize
     }
+
+    // Enqueues the values of a block in chunks of up to MAX_CHUNK_SIZE, decaying each
+    // chunk as a whole; `is_silent` tells whether a range of the block is silent
+    fn enqueue_chunks(
+        &mut self,
+        samples: usize,
+        value: impl Fn(usize) -> f32,
+        is_silent: impl Fn(Range<usize>) -> bool,
+    ) {
+        for start in (0..samples).step_by(MAX_CHUNK_SIZE) {
+            let end = (start + MAX_CHUNK_SIZE).min(samples);
+
+            // Only skip silence if gating is enabled, silent samples still take time
+            if self.gate_silence && is_silent(start..end) {
+                continue;
+            }
+
+            // "Pre-decay" all values, computed in f64 so long chunks can't underflow
+            // before the conversion
+            let decay_weight = (self.decay_weight as f64).powi((end - start) as i32) as f32;
+
+            for i in 0..self.size - 1 {
+                self.data[i] *= decay_weight;
+            }
+
+            for i in start..end {
+                let bin_index = self.find_bin(value(i).abs());
+                self.data[bin_index] += 1.0 - self.decay_weight; // Increment the count for the bin
+            }
+            self.touch();
+            #[cfg(feature = "debug-tools")]
+            {
+                self.enqueued = self.enqueued.wrapping_add((end - start) as u64);
+            }
+        }
+    }
 }
 
 impl VisualizerBuffer<f32> for HistogramBuffer {
@@ -143,57 +477,85 @@ impl VisualizerBuffer<f32> for HistogramBuffer {
     ///
     /// **Where possible, use [`enqueue_buffer`] instead!**
     fn enqueue(&mut self, value: f32) {
+        if self.offline {
+            return;
+        }
         let value = value.abs();
-        // don't enqueue silence
-        if value > 0.0 {
-            let bin_index = self.find_bin(value);
-            for i in 0..self.size - 1 {
-                // decay all values
-                self.data[i] *= self.decay_weight;
-            }
-            self.data[bin_index] += (1.0 - self.decay_weight); // Increment the count for the bin
+        // Only skip silence if gating is enabled, silent samples still take time
+        if self.gate_silence && value == 0.0 {
+            return;
+        }
+        let bin_index = self.find_bin(value);
+        for i in 0..self.size - 1 {
+            // decay all values
+            self.data[i] *= self.decay_weight;
+        }
+        self.data[bin_index] += 1.0 - self.decay_weight; // Increment the count for the bin
+        self.touch();
+        #[cfg(feature = "debug-tools")]
+        {
+            self.enqueued = self.enqueued.wrapping_add(1);
         }
     }
 
+    /// Enqueues an entire [`Buffer`](`nih_plug::buffer::Buffer`), mono-summing it if
+    /// no channel is specified.
+    ///
+    /// Large blocks are split into chunks of up to 512 samples, and each chunk is
+    /// decayed as a whole. This way, a huge block from an offline render has the
+    /// same effect as feeding its samples in blocks of 512.
     fn enqueue_buffer(&mut self, buffer: &mut nih_plug::buffer::Buffer, channel: Option<usize>) {
-        // don't enqueue silence
-        if !match channel {
-            Some(channel) => buffer.as_slice()[channel]
-                .iter()
-                .any(|sample| *sample != 0.0),
-            None => buffer
-                .as_slice()
-                .iter()
-                .any(|channel| channel.iter().any(|sample| *sample != 0.0)),
-        } {
+        if self.offline {
             return;
         }
 
-        // "Pre-decay" all values
-        let decay_weight = self.decay_weight.powi(buffer.samples() as i32);
+        let channels = buffer.as_slice_immutable();
+        let samples = channels.first().map_or(0, |channel| channel.len());
+        self.enqueue_chunks(
+            samples,
+            |i| match channel {
+                Some(channel) => channels[channel][i],
+                None => {
+                    (1. / channels.len() as f32)
+                        * channels.iter().map(|channel| channel[i]).sum::<f32>()
+                }
+            },
+            |range| match channel {
+                Some(channel) => channels[channel][range].iter().all(|sample| *sample == 0.0),
+                None => channels
+                    .iter()
+                    .all(|channel| channel[range.clone()].iter().all(|sample| *sample == 0.0)),
+            },
+        );
+    }
 
-        for i in 0..self.size - 1 {
-            self.data[i] *= decay_weight;
+    /// Enqueues interleaved audio in chunks of up to 512 frames, like
+    /// [`enqueue_buffer`](Self::enqueue_buffer).
+    fn enqueue_interleaved(&mut self, data: &[f32], channels: usize, downmix: ChannelDownmix) {
+        let frames = interleaved_frames(data, channels);
+        if self.offline {
+            return;
         }
+        let frame = |i: usize| &data[i * channels..(i + 1) * channels];
+        self.enqueue_chunks(
+            frames,
+            |i| downmix.mix(frame(i)),
+            |mut range| range.all(|i| downmix.is_silent(frame(i))),
+        );
+    }
 
-        match channel {
-            Some(channel) => {
-                for sample in buffer.as_slice()[channel].iter() {
-                    let value = (*sample).abs();
-                    let bin_index = self.find_bin(value);
-                    self.data[bin_index] += 1.0 - self.decay_weight; // Increment the count for the bin
-                }
-            }
-            None => {
-                for sample in buffer.iter_samples() {
-                    let value = (1. / sample.len() as f32)
-                        * sample.into_iter().map(|x| *x).sum::<f32>().abs();
-
-                    let bin_index = self.find_bin(value);
-                    self.data[bin_index] += 1.0 - self.decay_weight; // Increment the count for the bin
-                }
-            }
-        };
+    /// Enqueues planar audio in chunks of up to 512 samples, like
+    /// [`enqueue_buffer`](Self::enqueue_buffer).
+    fn enqueue_planar(&mut self, channels: &[&[f32]], downmix: ChannelDownmix) {
+        let frames = planar_frames(channels);
+        if self.offline {
+            return;
+        }
+        self.enqueue_chunks(
+            frames,
+            |i| downmix.mix_planar(channels, i),
+            |range| downmix.is_silent_planar(channels, range),
+        );
     }
 
     /// Resizes the buffer to the given size, **clearing it**.
@@ -209,12 +571,17 @@ impl VisualizerBuffer<f32> for HistogramBuffer {
     /// Clears the entire buffer, filling it with default values (usually 0)
     fn clear(self: &mut Self) {
         self.data.iter_mut().for_each(|x| *x = f32::default());
+        self.touch();
     }
 
     fn len(self: &Self) -> usize {
         self.size
     }
 
+    fn generation(self: &Self) -> Option<u64> {
+        Some(self.generation)
+    }
+
     /// Grows the buffer, **clearing it**.
     fn grow(self: &mut Self, size: usize) {
         self.resize(size);
@@ -230,23 +597,442 @@ impl Index<usize> for HistogramBuffer {
     type Output = f32;
 
     fn index(&self, index: usize) -> &Self::Output {
-        if index >= self.size {
-            panic!(
-                "Invalid histogram buffer access: Index {} is out of range for histogram buffer of size {}",
-                index, self.size
-            );
-        }
+        check_index("histogram buffer", self.name.get(), index, self.size);
         &self.data[index]
     }
 }
 impl IndexMut<usize> for HistogramBuffer {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        if index >= self.size {
-            panic!(
-                "Invalid histogram buffer access: Index {} is out of range for histogram buffer of size {}",
-                index, self.size
+        check_index("histogram buffer", self.name.get(), index, self.size);
+        self.touch();
+        &mut self.data[index]
+    }
+}
+
+impl SnapshotExport for HistogramBuffer {
+    fn export(&self) -> BufferSnapshot {
+        BufferSnapshot {
+            name: self.name().map(str::to_string),
+            decay: Some(self.decay),
+            sample_rate: Some(self.sample_rate),
+            range: Some(self.range),
+            ..BufferSnapshot::new(
+                "HistogramBuffer",
+                &["value"],
+                self.data.iter().map(|value| vec![*value]).collect(),
+            )
+        }
+    }
+}
+
+#[cfg(feature = "debug-tools")]
+impl super::registry::RegisteredBuffer for HistogramBuffer {
+    fn info(&self) -> super::registry::BufferInfo {
+        super::registry::BufferInfo {
+            buffer: "HistogramBuffer",
+            name: self.name().map(str::to_string),
+            size: self.size,
+            duration: None,
+            sample_rate: Some(self.sample_rate),
+            enqueued: self.enqueued,
+        }
+    }
+}
+
+#[cfg(feature = "ffi")]
+impl super::ffi::WriteSnapshot for HistogramBuffer {
+    fn write_snapshot(&self, out: &mut Vec<u8>) {
+        super::ffi::SnapshotHeader::new(
+            super::ffi::ValueFormat::F32,
+            self.size,
+            None,
+            Some(self.sample_rate),
+        )
+        .write(out, self.data.iter().copied());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HistogramBuffer;
+    use crate::utils::buffers::test_support::{
+        assert_interleaved_matches_buffer, assert_large_block_matches_small_blocks, contents,
+        test_signal, with_buffer,
+    };
+    use crate::utils::{ChannelDownmix, VisualizerBuffer};
+
+    fn histogram() -> HistogramBuffer {
+        let mut histogram = HistogramBuffer::new(16, 0.01);
+        histogram.set_sample_rate(1000.);
+        histogram
+    }
+
+    fn loudest_bin(histogram: &HistogramBuffer) -> f32 {
+        (1..histogram.len())
+            .map(|i| histogram[i])
+            .fold(0.0, f32::max)
+    }
+
+    // Fills a single bin, at the center of the bin inside the range with the given index
+    fn delta(size: usize, bin: usize) -> HistogramBuffer {
+        let mut histogram = HistogramBuffer::new(size, 0.01);
+        histogram.set_sample_rate(1000.);
+        histogram.set_range((-60., 0.));
+        let step = 60. / (size - 2) as f32;
+        let db = -60. + (bin as f32 + 0.5) * step;
+        histogram.enqueue(10f32.powf(db / 20.));
+        histogram
+    }
+
+    #[test]
+    fn rebinning_preserves_mass() {
+        let mut histogram = HistogramBuffer::new(1026, 0.01);
+        histogram.set_sample_rate(1000.);
+        histogram.set_range((-60., 0.));
+        let mut channels = test_signal(1, 2000);
+        with_buffer(&mut channels, |b| histogram.enqueue_buffer(b, None));
+
+        let total = (1..histogram.len() - 1).map(|i| histogram[i]).sum::<f32>();
+        assert!(total > 0.);
+        let mut out = Vec::new();
+        for out_bins in [1024, 512, 300, 256, 37, 1] {
+            histogram.rebinned(out_bins, &mut out);
+            assert_eq!(out.len(), out_bins);
+            let rebinned = out.iter().sum::<f32>();
+            assert!(
+                (rebinned - total).abs() < total * 1e-4,
+                "{out_bins} bins: {rebinned} instead of {total}"
+            );
+        }
+    }
+
+    #[test]
+    fn rebinning_delta() {
+        let mut out = Vec::new();
+        for (size, bin) in [(1026, 700), (1026, 3), (258, 250), (66, 13), (34, 20)] {
+            let histogram = delta(size, bin);
+            let in_range = size - 2;
+            for out_bins in [16, 100, 250, 777, 2000] {
+                histogram.rebinned(out_bins, &mut out);
+                let loudest = (0..out_bins)
+                    .max_by(|a, b| out[*a].total_cmp(&out[*b]))
+                    .unwrap();
+                // The output bin that contains the center of the filled bin
+                let expected = ((bin as f32 + 0.5) / in_range as f32 * out_bins as f32) as usize;
+                assert_eq!(loudest, expected, "{size} bins to {out_bins}, bin {bin}");
+            }
+        }
+    }
+
+    #[test]
+    fn rebinning_without_bins_in_range() {
+        let mut out = vec![1.0; 4];
+        HistogramBuffer::new(2, 0.01).rebinned(8, &mut out);
+        assert_eq!(out, vec![0.0; 8]);
+    }
+
+    // 32 bins inside the range, 2 dB each, with the given contents, and 1 below and
+    // above the range
+    fn filled(bins: impl Fn(usize) -> f32) -> HistogramBuffer {
+        let mut histogram = HistogramBuffer::new(34, 0.01);
+        histogram.set_range((-64., 0.));
+        histogram[0] = 1.;
+        histogram[33] = 1.;
+        for i in 1..33 {
+            histogram[i] = bins(i);
+        }
+        histogram
+    }
+
+    fn total(histogram: &HistogramBuffer) -> f64 {
+        (0..histogram.len()).map(|i| histogram[i] as f64).sum()
+    }
+
+    #[test]
+    fn zooming_moves_mass_outside_the_range() {
+        let mut histogram = filled(|_| 1.);
+        histogram.zoom_range((-32., -16.));
+
+        // 16 bins lie below the new range, and 8 above it; The 8 bins inside it are
+        // spread across all 32
+        assert_eq!(histogram[0], 17.);
+        assert_eq!(histogram[33], 9.);
+        assert!((1..33).all(|i| histogram[i] == 0.25));
+        assert_eq!(total(&histogram), 34.);
+
+        // What lies outside stays there when zooming back out
+        histogram.zoom_range((-64., 0.));
+        assert_eq!(histogram[0], 17.);
+        assert_eq!(histogram[33], 9.);
+        assert!((1..17).all(|i| histogram[i] == 0.));
+        assert!((17..25).all(|i| histogram[i] == 1.));
+        assert!((25..33).all(|i| histogram[i] == 0.));
+    }
+
+    #[test]
+    fn zooming_aligned_bins() {
+        let mut histogram = filled(|i| i as f32);
+        histogram.zoom_range((-128., 0.));
+        // Each pair of bins is merged into the upper half of the range
+        assert!((1..17).all(|i| histogram[i] == 0.));
+        assert!((17..33).all(|i| histogram[i] == (4 * (i - 17) + 3) as f32));
+
+        // Zooming back in splits them evenly
+        histogram.zoom_range((-64., 0.));
+        for i in 1..33 {
+            let pair = (i - 1) / 2;
+            assert_eq!(histogram[i], (4 * pair + 3) as f32 / 2., "{i}");
+        }
+        assert_eq!(total(&histogram), 2. + (1..33).sum::<usize>() as f64);
+    }
+
+    #[test]
+    fn zoom_round_trip() {
+        let mut histogram = HistogramBuffer::new(258, 0.01);
+        histogram.set_sample_rate(1000.);
+        histogram.set_range((-60., 0.));
+        let mut channels = test_signal(1, 2000);
+        with_buffer(&mut channels, |b| histogram.enqueue_buffer(b, None));
+        let expected = total(&histogram);
+        assert!(expected > 0.);
+
+        let ranges = [
+            (-37.3, -11.9),
+            (-61.7, 4.2),
+            (-20., -19.5),
+            (-96., 24.),
+            (-48.1, -2.),
+            (-60., 0.),
+        ];
+        for _ in 0..20 {
+            for range in ranges {
+                histogram.zoom_range(range);
+                let zoomed = total(&histogram);
+                assert!(
+                    (zoomed - expected).abs() < expected * 1e-5,
+                    "{range:?}: {zoomed} instead of {expected}"
                 );
+                assert!((0..histogram.len()).all(|i| histogram[i] >= 0.));
+            }
         }
-        &mut self.data[index]
+
+        // Zooming into the same range changes nothing
+        let before = histogram.clone();
+        histogram.zoom_range((-60., 0.));
+        assert_eq!(histogram, before);
+    }
+
+    #[test]
+    fn silence_decays() {
+        let mut histogram = HistogramBuffer::new(64, 0.01);
+        histogram.set_sample_rate(1000.);
+
+        for _ in 0..100 {
+            histogram.enqueue(0.5);
+        }
+        let before = loudest_bin(&histogram);
+        assert!(before > 0.0);
+
+        // Time keeps flowing during silence, so the histogram should decay
+        for _ in 0..100 {
+            histogram.enqueue(0.0);
+        }
+        assert!(loudest_bin(&histogram) < before);
+    }
+
+    #[test]
+    fn silence_gating() {
+        let mut histogram = HistogramBuffer::new(64, 0.01);
+        histogram.set_sample_rate(1000.);
+        histogram.set_silence_gating(true);
+
+        for _ in 0..100 {
+            histogram.enqueue(0.5);
+        }
+        let before = loudest_bin(&histogram);
+
+        // With gating enabled, silence is skipped entirely
+        for _ in 0..100 {
+            histogram.enqueue(0.0);
+        }
+        assert_eq!(loudest_bin(&histogram), before);
+    }
+
+    #[test]
+    fn enqueue_buffer_downmix() {
+        let mut stereo = test_signal(2, 100);
+        let mut mono = vec![(0..100)
+            .map(|i| (stereo[0][i] + stereo[1][i]) / 2.)
+            .collect::<Vec<_>>()];
+
+        let mut downmixed = histogram();
+        with_buffer(&mut stereo, |b| downmixed.enqueue_buffer(b, None));
+        let mut expected = histogram();
+        with_buffer(&mut mono, |b| expected.enqueue_buffer(b, Some(0)));
+
+        assert_eq!(contents(&downmixed), contents(&expected));
+    }
+
+    #[test]
+    fn interleaved_and_planar() {
+        assert_interleaved_matches_buffer(histogram, 1500);
+
+        // Chunks that are silent on the selected channel are gated
+        let gated = || {
+            let mut histogram = histogram();
+            histogram.set_silence_gating(true);
+            histogram
+        };
+        let mut stereo = test_signal(2, 1500);
+        stereo[1][..1024].fill(0.);
+        let mut expected = gated();
+        with_buffer(&mut stereo, |b| expected.enqueue_buffer(b, Some(1)));
+        let mut buffer = gated();
+        let slices = stereo.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        buffer.enqueue_planar(&slices, ChannelDownmix::Channel(1));
+        assert_eq!(contents(&buffer), contents(&expected));
+    }
+
+    #[test]
+    fn enqueue_buffer_channel() {
+        let mut stereo = test_signal(2, 100);
+        let mut mono = vec![stereo[1].clone()];
+
+        let mut selected = histogram();
+        with_buffer(&mut stereo, |b| selected.enqueue_buffer(b, Some(1)));
+        let mut expected = histogram();
+        with_buffer(&mut mono, |b| expected.enqueue_buffer(b, Some(0)));
+
+        assert_eq!(contents(&selected), contents(&expected));
+    }
+
+    #[test]
+    fn enqueue_buffer_pre_decay() {
+        let mut histogram = histogram();
+        for _ in 0..100 {
+            histogram.enqueue(1.0);
+        }
+        let loud = (0..histogram.len())
+            .max_by(|a, b| histogram[*a].total_cmp(&histogram[*b]))
+            .unwrap();
+        let before = histogram[loud];
+
+        // Bins that aren't hit by the block decay once for every sample inside it
+        let mut quiet = vec![vec![0.05; 40]];
+        with_buffer(&mut quiet, |b| histogram.enqueue_buffer(b, None));
+        let expected = before * histogram.decay_weight.powi(40);
+        assert!((histogram[loud] - expected).abs() < 1e-6);
+
+        // Empty blocks don't change anything
+        let before = contents(&histogram);
+        with_buffer(&mut [vec![], vec![]], |b| {
+            histogram.enqueue_buffer(b, None);
+            histogram.enqueue_buffer(b, Some(1));
+        });
+        assert_eq!(contents(&histogram), before);
+    }
+
+    #[test]
+    fn enqueue_buffer_larger_than_histogram() {
+        let mut histogram = histogram();
+        let mut channels = test_signal(2, 1000);
+        with_buffer(&mut channels, |b| histogram.enqueue_buffer(b, None));
+
+        assert!(contents(&histogram)
+            .iter()
+            .all(|value| value.is_finite() && *value >= 0.0));
+        assert!(loudest_bin(&histogram) > 0.0);
+    }
+
+    #[test]
+    fn enqueue_buffer_silence_gating() {
+        let mut histogram = histogram();
+        for _ in 0..100 {
+            histogram.enqueue(0.5);
+        }
+        let before = contents(&histogram);
+
+        let mut silence = vec![vec![0.0; 64]; 2];
+        histogram.set_silence_gating(true);
+        with_buffer(&mut silence, |b| histogram.enqueue_buffer(b, None));
+        assert_eq!(contents(&histogram), before);
+
+        histogram.set_silence_gating(false);
+        with_buffer(&mut silence, |b| histogram.enqueue_buffer(b, None));
+        assert!(loudest_bin(&histogram) < before.iter().copied().fold(0.0, f32::max));
+    }
+
+    #[test]
+    fn enqueue_buffer_large_block() {
+        assert_large_block_matches_small_blocks(histogram);
+
+        // With a longer decay, older values should still be visible after a single
+        // large block
+        let new = || {
+            let mut histogram = HistogramBuffer::new(16, 60.);
+            histogram.set_sample_rate(48000.);
+            histogram
+        };
+        assert_large_block_matches_small_blocks(new);
+    }
+
+    #[test]
+    fn offline() {
+        let mut histogram = histogram();
+        histogram.set_offline(true);
+
+        let mut channels = test_signal(2, 1000);
+        with_buffer(&mut channels, |b| histogram.enqueue_buffer(b, None));
+        histogram.enqueue(0.5);
+        assert!(contents(&histogram).iter().all(|value| *value == 0.0));
+    }
+
+    fn enqueue_loudness(histogram: &mut HistogramBuffer, pattern: &[f32], repeats: usize) {
+        for _ in 0..repeats {
+            for lufs in pattern {
+                histogram.enqueue(10f32.powf(lufs / 20.));
+            }
+        }
+    }
+
+    #[test]
+    fn loudness_range_two_levels() {
+        let mut histogram = HistogramBuffer::loudness(401);
+        assert_eq!(histogram.loudness_range(), 0.0);
+
+        // Half the time at -30 LUFS, half the time at -20 LUFS
+        enqueue_loudness(&mut histogram, &[-30., -20.], 300);
+        assert!((histogram.loudness_range() - 10.).abs() < 0.15);
+    }
+
+    #[test]
+    fn loudness_range_relative_gate() {
+        let mut histogram = HistogramBuffer::loudness(401);
+
+        // The quiet passages are more than 20 LU below the mean and don't count,
+        // otherwise the 10th percentile would be at -39 LUFS
+        enqueue_loudness(&mut histogram, &[-15., -25., -15., -25., -39.], 120);
+        assert!((histogram.loudness_range() - 10.).abs() < 0.15);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Invalid histogram buffer range maximum")]
+    fn set_range_descending() {
+        HistogramBuffer::new(16, 0.01).set_range((0., -40.));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Invalid histogram buffer range minimum")]
+    fn set_range_infinite() {
+        HistogramBuffer::new(16, 0.01).set_range((f32::NEG_INFINITY, 0.));
+    }
+
+    #[test]
+    #[should_panic(expected = "histogram buffer \"loudness\" of size 16")]
+    fn invalid_access_named() {
+        let histogram = HistogramBuffer::new(16, 0.01).with_name("loudness");
+        histogram[16];
     }
 }
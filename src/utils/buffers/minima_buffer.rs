@@ -1,8 +1,19 @@
 use nih_plug::audio_setup::{AudioIOLayout, BufferConfig};
 use nih_plug::prelude::InitContext;
+use std::fmt::{self, Debug};
 use std::ops::{Index, IndexMut};
 
-use super::{RingBuffer, VisualizerBuffer};
+use super::{
+    super::config_error::{
+        check_decay, check_density, check_duration, check_size, clamp_decay, clamp_density,
+        clamp_duration, ConfigError,
+    },
+    super::decay::{decay_weight, decay_weight_per_slot, TempoDecay},
+    channel_source::{for_each_routed, ChannelSource, SourceRouting, SourceSelector},
+    check_index, density_size,
+    playback_rate::{retime, PlaybackRate},
+    rescale_elements, slot_progress, BufferSnapshot, RingBuffer, SnapshotExport, VisualizerBuffer,
+};
 
 /// Analogous to the [`PeakBuffer`](super::PeakBuffer), save for the fact that it
 /// stores the minimum absolute values instead of the maximum absolute values of a
@@ -12,6 +23,8 @@ use super::{RingBuffer, VisualizerBuffer};
 /// visualizers that are meant to display peak information, such as peak graphs, do
 /// use a `PeakBuffer`.
 ///
+/// Like the `PeakBuffer`, this buffer keeps advancing while the input is silent.
+///
 /// The `MinimaBuffer` needs to be provided a sample rate after initialization - do
 /// this inside your [`initialize()`](nih_plug::plugin::Plugin::initialize)
 /// function.
@@ -25,12 +38,27 @@ pub struct MinimaBuffer {
     // Used to calculate the sample_delta
     sample_rate: f32,
     duration: f32,
+    // The elements per second that the size follows when the duration changes, if any
+    density: Option<f32>,
     // The current time, counts down from sample_delta to 0
     t: f32,
+    // The number of samples in the element that is being accumulated; Since the
+    // sample_delta usually isn't a whole number, elements span different numbers of
+    // samples
+    slot_samples: u32,
     /// The decay time for the peak amplitude to halve.
     decay: f32,
-    // This is set `set_sample_rate()` based on the sample_delta
+    // The weight for an element of the nominal duration, used until a sample rate is
+    // set
     decay_weight: f32,
+    // The decay in beats, if it's given in beats, and the tempo it follows
+    tempo: TempoDecay,
+    // The rate the host plays back at, which the duration follows
+    playback: PlaybackRate,
+    // The channels that are read from a buffer that's enqueued without a channel
+    routing: SourceRouting,
+    // Whether the plug-in is rendering offline, in which case nothing is enqueued
+    offline: bool,
 }
 
 impl MinimaBuffer {
@@ -44,27 +72,165 @@ impl MinimaBuffer {
     /// calling [`set_sample_rate`](Self::set_sample_rate) inside your
     /// [`initialize()`](nih_plug::plugin::Plugin::initialize) function.
     pub fn new(size: usize, duration: f32, decay: f32) -> Self {
-        let decay_weight = Self::decay_weight(decay, size, duration);
+        let duration = clamp_duration("minima buffer", "duration", duration);
+        let decay = clamp_decay("minima buffer", decay);
+        let decay_weight = decay_weight(decay, size, duration);
         Self {
             buffer: RingBuffer::<f32>::new(size),
             min_acc: f32::MAX,
             sample_delta: 0.,
             sample_rate: 0.,
             duration,
+            density: None,
             t: 0.,
+            slot_samples: 0,
             decay,
             decay_weight,
+            tempo: TempoDecay::default(),
+            playback: PlaybackRate::default(),
+            routing: SourceRouting::default(),
+            offline: false,
+        }
+    }
+
+    /// Constructs a new `MinimaBuffer`, or returns a [`ConfigError`] if the size is
+    /// 0, the duration isn't positive, or the decay is negative; See [`new()`](Self::new).
+    pub fn try_new(size: usize, duration: f32, decay: f32) -> Result<Self, ConfigError> {
+        check_size("minima buffer", "size", size)?;
+        check_duration("minima buffer", "duration", duration)?;
+        check_decay("minima buffer", decay)?;
+        Ok(Self::new(size, duration, decay))
+    }
+
+    /// Constructs a new `MinimaBuffer` that holds a number of elements per second; See
+    /// [`PeakBuffer::with_density`](super::PeakBuffer::with_density).
+    ///
+    /// * `points_per_second` - The number of elements per second of audio data
+    /// * `duration` - The duration (in seconds) of the audio data inside the buffer
+    /// * `decay` - The time it takes for a sample inside the buffer to decrease by -12dB, in milliseconds
+    pub fn with_density(points_per_second: f32, duration: f32, decay: f32) -> Self {
+        let density = clamp_density("minima buffer", points_per_second);
+        let duration = clamp_duration("minima buffer", "duration", duration);
+        Self {
+            density: Some(density),
+            ..Self::new(density_size(density, duration), duration, decay)
         }
     }
 
+    /// Constructs a new `MinimaBuffer` that holds a number of elements per second, or
+    /// returns a [`ConfigError`] if the density or the duration isn't positive, or
+    /// the decay is negative; See [`with_density()`](Self::with_density).
+    pub fn try_with_density(
+        points_per_second: f32,
+        duration: f32,
+        decay: f32,
+    ) -> Result<Self, ConfigError> {
+        check_density("minima buffer", points_per_second)?;
+        check_duration("minima buffer", "duration", duration)?;
+        check_decay("minima buffer", decay)?;
+        Ok(Self::with_density(points_per_second, duration, decay))
+    }
+
+    /// Returns the number of elements per second of audio data.
+    pub fn density(self: &Self) -> f32 {
+        self.density
+            .unwrap_or(self.buffer.len() as f32 / self.duration)
+    }
+
+    /// Names the `MinimaBuffer`, which identifies it in panic messages, snapshots and the
+    /// debug registry.
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.buffer.name_mut().set(name);
+        self
+    }
+
+    /// Sets the name of the `MinimaBuffer`; See [`with_name()`](Self::with_name).
+    pub fn set_name(self: &mut Self, name: String) {
+        self.buffer.name_mut().set(name);
+    }
+
+    /// Returns the name of the `MinimaBuffer`, if it has one.
+    pub fn name(self: &Self) -> Option<&str> {
+        self.buffer.name()
+    }
+
     /// Sets the decay time of the `MinimaBuffer`.
     ///
     /// * `decay` - The time it takes for a sample inside the buffer to decrease by -12dB, in milliseconds
+    ///
+    /// A negative or NaN decay fails a debug assertion, and is clamped to 0 in release
+    /// builds; Use [`try_set_decay()`](Self::try_set_decay) to handle it instead.
     pub fn set_decay(self: &mut Self, decay: f32) {
-        self.decay = decay;
+        self.decay = clamp_decay("minima buffer", decay);
+        self.tempo.clear_beats();
         self.update();
     }
 
+    /// Sets the decay time of the `MinimaBuffer`, or returns a [`ConfigError`] if it's
+    /// negative or NaN.
+    pub fn try_set_decay(self: &mut Self, decay: f32) -> Result<(), ConfigError> {
+        self.set_decay(check_decay("minima buffer", decay)?);
+        Ok(())
+    }
+
+    /// Sets the decay of the `MinimaBuffer` in beats, so that it follows the tempo
+    /// of the project; See
+    /// [`PeakBuffer::set_decay_beats()`](super::PeakBuffer::set_decay_beats).
+    pub fn set_decay_beats(self: &mut Self, beats: f32) {
+        self.decay = self.tempo.set_beats(clamp_decay("minima buffer", beats));
+        self.decay_weight = decay_weight(self.decay, self.buffer.len(), self.duration);
+    }
+
+    /// Returns the decay of the `MinimaBuffer` in beats, if it follows the tempo.
+    pub fn decay_beats(self: &Self) -> Option<f32> {
+        self.tempo.beats()
+    }
+
+    /// Reports the tempo of the project, in BPM, which a decay given in beats
+    /// follows; See [`PeakBuffer::set_tempo()`](super::PeakBuffer::set_tempo).
+    pub fn set_tempo(self: &mut Self, bpm: f64) {
+        if let Some(decay) = self.tempo.set_tempo(bpm) {
+            self.decay = decay;
+            self.decay_weight = decay_weight(self.decay, self.buffer.len(), self.duration);
+        }
+    }
+
+    /// Reports the rate at which the host plays the project back, so that the buffer
+    /// keeps spanning its duration of what's heard; See
+    /// [`PeakBuffer::set_playback_rate()`](super::PeakBuffer::set_playback_rate).
+    pub fn set_playback_rate(self: &mut Self, rate: f32) {
+        if self.playback.set(rate) {
+            let sample_delta =
+                Self::sample_delta(self.buffer.len(), self.samples_per_second(), self.duration);
+            self.t = retime(self.t, self.sample_delta, sample_delta);
+            self.sample_delta = sample_delta;
+        }
+    }
+
+    /// Returns the playback rate that was reported last.
+    pub fn playback_rate(self: &Self) -> f32 {
+        self.playback.rate()
+    }
+
+    /// Sets the channels that are read from a [`Buffer`](nih_plug::buffer::Buffer)
+    /// that's enqueued without a channel; See
+    /// [`PeakBuffer::set_source()`](super::PeakBuffer::set_source).
+    pub fn set_source(self: &mut Self, source: ChannelSource) {
+        self.routing.selector().set(source);
+    }
+
+    /// Returns a handle that selects the source of the `MinimaBuffer` without locking
+    /// it; See [`set_source()`](Self::set_source).
+    pub fn source_selector(self: &Self) -> SourceSelector {
+        self.routing.selector()
+    }
+
+    /// Sets whether the `MinimaBuffer` is cleared when its source changes, which it is
+    /// by default.
+    pub fn set_clear_on_source_change(self: &mut Self, clear: bool) {
+        self.routing.set_clear_on_change(clear);
+    }
+
     /// Sets the sample rate of the incoming audio.
     ///
     /// This function **clears** the buffer. You can call it inside your
@@ -94,49 +260,131 @@ impl MinimaBuffer {
         self.buffer.clear();
     }
 
+    /// Hints whether the plug-in is rendering offline, which skips all enqueueing
+    /// while it is. See [`PeakBuffer::set_offline`](super::PeakBuffer::set_offline).
+    pub fn set_offline(self: &mut Self, offline: bool) {
+        self.offline = offline;
+    }
+
     /// Sets the duration (in seconds) of the incoming audio.
     ///
-    /// This function **clears** the buffer.
+    /// This function **clears** the buffer. A duration that isn't positive and finite
+    /// fails a debug assertion, and is clamped in release builds; Use
+    /// [`try_set_duration()`](Self::try_set_duration) to handle it instead.
+    ///
+    /// A buffer that keeps a [density](Self::with_density) is resized to it.
     pub fn set_duration(self: &mut Self, duration: f32) {
-        self.duration = duration;
+        self.duration = clamp_duration("minima buffer", "duration", duration);
+        if let Some(density) = self.density {
+            self.buffer.resize(density_size(density, self.duration));
+        }
         self.update();
         self.buffer.clear();
     }
 
-    fn sample_delta(size: usize, sample_rate: f32, duration: f32) -> f32 {
-        ((sample_rate as f64 * duration as f64) / size as f64) as f32
+    /// Sets the duration (in seconds) of the incoming audio, or returns a
+    /// [`ConfigError`] if it isn't positive and finite.
+    ///
+    /// This function **clears** the buffer.
+    pub fn try_set_duration(self: &mut Self, duration: f32) -> Result<(), ConfigError> {
+        self.set_duration(check_duration("minima buffer", "duration", duration)?);
+        Ok(())
     }
 
-    fn decay_weight(decay: f32, size: usize, duration: f32) -> f32 {
-        0.25f64.powf((decay as f64 / 1000. * (size as f64 / duration as f64)).recip()) as f32
+    /// Sets the duration (in seconds) of the incoming audio, keeping the buffer's
+    /// history; See [`PeakBuffer::rescale_duration`](super::PeakBuffer::rescale_duration).
+    ///
+    /// A longer duration merges groups of elements, taking their minimum. A buffer
+    /// that keeps a [density](Self::with_density) is resized instead.
+    pub fn rescale_duration(self: &mut Self, duration: f32) {
+        let duration = clamp_duration("minima buffer", "duration", duration);
+        let len = match self.density {
+            Some(density) => {
+                let len = density_size(density, duration);
+                self.buffer.resize(len);
+                len
+            }
+            None => {
+                let len = self.buffer.len();
+                rescale_elements(&mut self.buffer, len, self.duration, duration, |elements| {
+                    elements.iter().copied().fold(f32::MAX, f32::min)
+                });
+                len
+            }
+        };
+
+        self.duration = duration;
+        self.decay_weight = decay_weight(self.decay, len, duration);
+        self.sample_delta = Self::sample_delta(len, self.samples_per_second(), duration);
+        if (self.slot_samples as f32) < self.sample_delta {
+            self.t = self.sample_delta - self.slot_samples as f32;
+        } else {
+            self.t = self.sample_delta;
+            self.slot_samples = 0;
+            self.min_acc = f32::MAX;
+        }
+    }
+
+    fn sample_delta(size: usize, sample_rate: f32, duration: f32) -> f32 {
+        ((sample_rate as f64 * duration as f64) / size as f64) as f32
     }
 
     fn update(self: &mut Self) {
-        self.decay_weight = Self::decay_weight(self.decay, self.buffer.len(), self.duration);
-        self.sample_delta = Self::sample_delta(self.buffer.len(), self.sample_rate, self.duration);
+        self.decay_weight = decay_weight(self.decay, self.buffer.len(), self.duration);
+        self.sample_delta =
+            Self::sample_delta(self.buffer.len(), self.samples_per_second(), self.duration);
         self.t = self.sample_delta;
+        self.slot_samples = 0;
+    }
+
+    /// Returns the weight by which an element that spans the given number of samples
+    /// decays.
+    ///
+    /// The weight follows from the time the element actually spans, so values fall at
+    /// the rate the decay describes, even when elements are many milliseconds long
+    /// and alternate between two lengths.
+    fn slot_weight(self: &Self, samples: u32) -> f32 {
+        if self.sample_rate > 0. {
+            decay_weight_per_slot(
+                self.decay,
+                samples as f64 / self.samples_per_second() as f64,
+            )
+        } else {
+            self.decay_weight
+        }
+    }
+
+    // The number of samples for each second of the duration
+    fn samples_per_second(self: &Self) -> f32 {
+        self.playback.samples_per_second(self.sample_rate)
     }
 }
 
 impl VisualizerBuffer<f32> for MinimaBuffer {
     fn enqueue(self: &mut Self, value: f32) {
+        if self.offline {
+            return;
+        }
         let value = value.abs();
         self.t -= 1.0;
         if self.t < 0.0 {
             let last_peak = self.buffer.peek();
-            let mut peak = self.min_acc;
+            let peak = self.min_acc;
 
             // If the current peak is less than the last one, we immediately enqueue it. If it's greater than
             // the last one, we weigh the previous into the current one, analogous to how peak meters work.
             self.buffer.enqueue(if peak <= last_peak {
                 peak
             } else {
-                (last_peak * self.decay_weight) + (peak * (1.0 - self.decay_weight))
+                let weight = self.slot_weight(self.slot_samples);
+                (last_peak * weight) + (peak * (1.0 - weight))
             });
 
             self.t += self.sample_delta;
             self.min_acc = f32::MAX;
+            self.slot_samples = 0;
         }
+        self.slot_samples += 1;
         if value < self.min_acc {
             self.min_acc = value
         }
@@ -147,6 +395,9 @@ impl VisualizerBuffer<f32> for MinimaBuffer {
         buffer: &mut nih_plug::buffer::Buffer,
         channel: Option<usize>,
     ) {
+        if self.offline {
+            return;
+        }
         match channel {
             Some(channel) => {
                 for sample in buffer.as_slice()[channel].into_iter() {
@@ -154,10 +405,20 @@ impl VisualizerBuffer<f32> for MinimaBuffer {
                 }
             }
             None => {
-                for sample in buffer.iter_samples() {
-                    self.enqueue(
-                        (1. / (&sample).len() as f32) * sample.into_iter().map(|x| *x).sum::<f32>(),
-                    );
+                let (source, changed) = self.routing.update();
+                if changed {
+                    self.clear();
+                }
+                match source {
+                    ChannelSource::DownmixAvg => {
+                        for sample in buffer.iter_samples() {
+                            self.enqueue(
+                                (1. / (&sample).len() as f32)
+                                    * sample.into_iter().map(|x| *x).sum::<f32>(),
+                            );
+                        }
+                    }
+                    source => for_each_routed(buffer, source, |sample| self.enqueue(sample)),
                 }
             }
         }
@@ -167,6 +428,39 @@ impl VisualizerBuffer<f32> for MinimaBuffer {
         self.buffer.len()
     }
 
+    fn generation(self: &Self) -> Option<u64> {
+        Some(self.buffer.generation())
+    }
+
+    fn enqueued(self: &Self) -> Option<u64> {
+        Some(self.buffer.enqueued())
+    }
+
+    fn clears(self: &Self) -> Option<u64> {
+        Some(self.buffer.clears())
+    }
+
+    fn duration(self: &Self) -> Option<f32> {
+        Some(self.duration)
+    }
+
+    /// Returns the element that is currently being accumulated.
+    ///
+    /// The provisional value is the largest value the element can end up with, so it
+    /// only ever falls towards the final value.
+    fn partial(self: &Self) -> Option<(f32, f32)> {
+        let progress = slot_progress(self.t, self.sample_delta)?;
+        if self.min_acc == f32::MAX {
+            return None;
+        }
+        // The longest an element can be weighs the previous one the least
+        let weight = self.slot_weight(self.sample_delta.ceil() as u32);
+        let value = self
+            .min_acc
+            .min(self.buffer.peek() * weight + self.min_acc * (1.0 - weight));
+        Some((progress, value))
+    }
+
     fn clear(self: &mut Self) {
         self.buffer.clear();
     }
@@ -177,6 +471,7 @@ impl VisualizerBuffer<f32> for MinimaBuffer {
             return;
         };
         self.buffer.grow(size);
+        self.density = None;
         self.update();
         self.buffer.clear();
     }
@@ -187,6 +482,7 @@ impl VisualizerBuffer<f32> for MinimaBuffer {
             return;
         };
         self.buffer.shrink(size);
+        self.density = None;
         self.update();
         self.buffer.clear();
     }
@@ -196,11 +492,181 @@ impl Index<usize> for MinimaBuffer {
     type Output = f32;
 
     fn index(&self, index: usize) -> &Self::Output {
-        self.buffer.index(index)
+        check_index("minima buffer", self.name(), index, self.buffer.len());
+        &self.buffer[index]
     }
 }
 impl IndexMut<usize> for MinimaBuffer {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        self.buffer.index_mut(index)
+        check_index("minima buffer", self.name(), index, self.buffer.len());
+        &mut self.buffer[index]
+    }
+}
+
+impl SnapshotExport for MinimaBuffer {
+    fn export(&self) -> BufferSnapshot {
+        BufferSnapshot {
+            name: self.name().map(str::to_string),
+            duration: Some(self.duration),
+            decay: Some(self.decay),
+            sample_rate: Some(self.sample_rate),
+            ..BufferSnapshot::new(
+                "MinimaBuffer",
+                &["value"],
+                (0..self.buffer.len())
+                    .map(|i| vec![self.buffer[i]])
+                    .collect(),
+            )
+        }
+    }
+}
+
+impl Debug for MinimaBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MinimaBuffer")
+            .field("name", &self.name())
+            .field("size", &self.buffer.len())
+            .field("duration", &self.duration)
+            .field("sample_rate", &self.sample_rate)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "debug-tools")]
+impl super::registry::RegisteredBuffer for MinimaBuffer {
+    fn info(&self) -> super::registry::BufferInfo {
+        super::registry::BufferInfo {
+            buffer: "MinimaBuffer",
+            name: self.name().map(str::to_string),
+            size: self.buffer.len(),
+            duration: Some(self.duration),
+            sample_rate: Some(self.sample_rate),
+            enqueued: self.buffer.enqueued(),
+        }
+    }
+}
+
+#[cfg(feature = "ffi")]
+impl super::ffi::WriteSnapshot for MinimaBuffer {
+    fn write_snapshot(&self, out: &mut Vec<u8>) {
+        super::ffi::SnapshotHeader::new(
+            super::ffi::ValueFormat::F32,
+            self.buffer.len(),
+            Some(self.duration),
+            Some(self.sample_rate),
+        )
+        .write(out, (0..self.buffer.len()).map(|i| self.buffer[i]));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MinimaBuffer;
+    use crate::utils::buffers::test_support::{
+        assert_density_kept, assert_enqueue_buffer_matches_enqueue, assert_rescale_matches_offline,
+    };
+    use crate::utils::VisualizerBuffer;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[test]
+    fn enqueue_buffer() {
+        // 8 slots of 4 samples each, so 100 samples wrap around the buffer
+        let new = || {
+            let mut buffer = MinimaBuffer::new(8, 1.0, 50.0);
+            buffer.set_sample_rate(32.);
+            buffer
+        };
+        assert_enqueue_buffer_matches_enqueue(new, 100);
+    }
+
+    #[test]
+    fn density() {
+        let new = || {
+            let mut buffer = MinimaBuffer::with_density(10., 10., 0.0);
+            buffer.set_sample_rate(100.);
+            buffer
+        };
+        assert_density_kept(
+            new,
+            MinimaBuffer::set_duration,
+            MinimaBuffer::rescale_duration,
+        );
+        assert_eq!(new().density(), 10.);
+    }
+
+    #[test]
+    fn rescale_duration() {
+        let new = |duration| {
+            let mut buffer = MinimaBuffer::new(100, duration, 0.0);
+            buffer.set_sample_rate(100.);
+            buffer
+        };
+        assert_rescale_matches_offline(new, MinimaBuffer::rescale_duration);
+    }
+
+    #[test]
+    fn partial() {
+        // 4 samples per element
+        let mut buffer = MinimaBuffer::new(8, 1.0, 200.0);
+        buffer.set_sample_rate(32.);
+        assert_eq!(buffer.partial(), None);
+
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut provisional: Vec<f32> = Vec::new();
+        let mut last_progress = 0.;
+        for _ in 0..200 {
+            buffer.enqueue(rng.gen_range(-1.0..1.0));
+            let (progress, value) = buffer.partial().unwrap();
+
+            // A new element was started, so the last one was finished
+            if progress < last_progress {
+                // The provisional value only ever falls towards the finished element
+                let slot = buffer.buffer.peek();
+                assert!(provisional.windows(2).all(|w| w[0] >= w[1]));
+                assert!(provisional.iter().all(|value| *value >= slot));
+                provisional.clear();
+            }
+            last_progress = progress;
+            provisional.push(value);
+        }
+    }
+
+    #[test]
+    fn release_matches_exponential() {
+        // 6615 and 661.5 samples per element, so the finer buffer's elements
+        // alternate between 661 and 662 samples
+        let sample_rate = 44_100.;
+        let decay = 1000.;
+        let step = 6 * 44_100;
+
+        for size in [200, 2000] {
+            let mut buffer = MinimaBuffer::new(size, 30.0, decay);
+            buffer.set_sample_rate(sample_rate);
+
+            // Full gain reduction, which is released instantly after 6 seconds; Each
+            // element is compared to the exponential at the time it ends
+            let mut checked = 0;
+            for n in 0..30 * 44_100 {
+                let enqueued = buffer.buffer.enqueued();
+                buffer.enqueue(if n < step { 0.25 } else { 1.0 });
+                if buffer.buffer.enqueued() == enqueued || n <= step {
+                    continue;
+                }
+
+                let elapsed = (n - step) as f32 / sample_rate;
+                let expected = 1.0 - 0.75 * 0.25f32.powf(elapsed / (decay / 1000.));
+                let value = buffer.buffer.peek();
+                assert!(
+                    (value - expected).abs() < 1e-4,
+                    "{} elements, {} s after the release: expected {}, got {}",
+                    size,
+                    elapsed,
+                    expected,
+                    value
+                );
+                checked += 1;
+            }
+            assert!(checked >= size * 3 / 4);
+        }
     }
 }
@@ -1,19 +1,208 @@
+//! Buffers that store the data your visualizers display.
+//!
+//! Every buffer implements [`VisualizerBuffer`], and is shared between the audio
+//! thread and the editor as an `Arc<Mutex<B>>`.
+
+mod balance_buffer;
+pub mod ballistics;
+mod capture;
+mod channel_source;
+mod count_buffer;
+pub mod derived_buffer;
+mod deviation;
+mod downmix;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod histogram_buffer;
 pub mod minima_buffer;
+#[cfg(test)]
+mod model_check;
+mod name;
+pub mod note_event_buffer;
+mod overload;
 pub mod peak_buffer;
+mod playback_rate;
+#[cfg(feature = "debug-tools")]
+pub mod registry;
+#[cfg(feature = "debug-tools")]
+pub mod replay;
 pub mod ring_buffer;
 mod rms_buffer;
+pub mod snapshot;
+mod tags;
+#[cfg(test)]
+pub(crate) mod test_support;
+pub mod tiered_buffer;
+mod transport;
 pub mod waveform_buffer;
+pub mod windowed_buffer;
 
 use std::ops::{Index, IndexMut};
 
+use super::config_error::{check_size, ConfigError};
+use downmix::{interleaved_frames, planar_frames};
+
+pub use balance_buffer::BalanceBuffer;
+pub use ballistics::BallisticsPreset;
+pub use capture::BufferMode;
+pub use channel_source::{ChannelSource, SourceSelector};
+pub use count_buffer::CountBuffer;
+pub use derived_buffer::DerivedBuffer;
+pub use downmix::ChannelDownmix;
+#[cfg(feature = "ffi")]
+pub use ffi::{SnapshotError, SnapshotHeader, SnapshotRef, ValueFormat, WriteSnapshot};
 pub use histogram_buffer::HistogramBuffer;
 pub use minima_buffer::MinimaBuffer;
-pub use peak_buffer::PeakBuffer;
+pub use note_event_buffer::{NoteActivity, NoteEventBuffer};
+pub use overload::Overload;
+pub use peak_buffer::{Aggregation, PeakBuffer};
+#[cfg(feature = "debug-tools")]
+pub use registry::{registry, BufferInfo, BufferStatus, RegisteredBuffer, Registry};
+#[cfg(feature = "debug-tools")]
+pub use replay::{replay, LogEntry, LoggedCall, RecordingBuffer, ReplayError, ReplayLog};
 pub use ring_buffer::RingBuffer;
 pub use rms_buffer::RMSBuffer;
+pub use snapshot::{BufferSnapshot, SnapshotExport};
+pub use tiered_buffer::{SharedTier, TierGuard, TieredBuffer};
 pub use waveform_buffer::WaveformBuffer;
+pub use windowed_buffer::WindowedBuffer;
+
+/// The largest number of samples that buffers process in one go.
+///
+/// Buffers that do some of their work once per block split larger blocks into
+/// chunks of this size, so that the block size a host uses doesn't change their
+/// contents.
+pub(crate) const MAX_CHUNK_SIZE: usize = 512;
+
+/// Panics if `index` is out of range for a buffer of length `len`.
+///
+/// All buffers check their indices using this, so that their panic messages name
+/// the kind of buffer and the buffer itself in the same way, e.g. `Invalid peak
+/// buffer access: Index 800 is out of range for peak buffer "peak_in" of size 800`.
+#[track_caller]
+pub(crate) fn check_index(kind: &str, name: Option<&str>, index: usize, len: usize) {
+    if index >= len {
+        out_of_range(kind, name, index, len);
+    }
+}
+
+#[cold]
+#[track_caller]
+fn out_of_range(kind: &str, name: Option<&str>, index: usize, len: usize) -> ! {
+    let name = name.map_or(String::new(), |name| format!(" {:?}", name));
+    panic!(
+        "Invalid {} access: Index {} is out of range for {}{} of size {}",
+        kind, index, kind, name, len
+    );
+}
+
+/// Returns how far along the element that is currently being accumulated is, for
+/// buffers that count down `t` from `sample_delta` to 0.
+pub(crate) fn slot_progress(t: f32, sample_delta: f32) -> Option<f32> {
+    (sample_delta > 0.).then(|| (1. - t / sample_delta).clamp(0., 1.))
+}
+
+/// Returns the number of newest elements that span the trailing `seconds` of a
+/// buffer whose elements are `slot_duration` seconds apart.
+///
+/// An element that lies partly inside the window counts, so the elements always
+/// cover the whole window. The window holds no more elements than the buffer does,
+/// nor more than the buffer ever `enqueued` - if it's known. Windows that aren't
+/// positive hold none, and buffers whose elements aren't spaced in time hold all of
+/// theirs.
+pub(crate) fn window_slots(
+    seconds: f32,
+    slot_duration: f64,
+    len: usize,
+    enqueued: Option<u64>,
+) -> usize {
+    let filled = enqueued.map_or(len, |enqueued| enqueued.min(len as u64) as usize);
+    if !(seconds > 0.) {
+        return 0;
+    }
+    if !(slot_duration > 0.) {
+        return filled;
+    }
+    // Allows for the rounding error of the window, so that e.g. 0.3 seconds of
+    // 10 ms elements are 30 rather than 31
+    let slots = (seconds as f64 / slot_duration * (1. - 1e-6))
+        .ceil()
+        .max(1.);
+    (slots.min(filled as f64)) as usize
+}
+
+/// Returns the number of elements a buffer needs to hold `density` elements per
+/// second across the duration, which is at least 1.
+pub(crate) fn density_size(density: f32, duration: f32) -> usize {
+    ((density as f64 * duration as f64).round() as usize).max(1)
+}
+
+/// Spreads the elements of a buffer whose duration changes across the new element
+/// spacing, keeping the history that both durations cover.
+///
+/// Each new element takes the `merge` of the old elements it overlaps, so with a
+/// longer duration, groups of old elements are merged into one, and with a shorter
+/// one, the newest old elements are stretched across the buffer. New elements that
+/// reach further back than the `written` newest old elements are left at their
+/// default value, just like after clearing the buffer.
+///
+/// Returns the number of new elements that hold history.
+pub(crate) fn rescale_elements<T: Copy + Default>(
+    buffer: &mut RingBuffer<T>,
+    written: usize,
+    old_duration: f32,
+    new_duration: f32,
+    merge: impl Fn(&[T]) -> T,
+) -> usize {
+    // Bounds that lie on the boundary between two old elements don't include the
+    // element past it
+    const TOLERANCE: f64 = 1e-6;
+
+    let old = buffer.to_vec();
+    let len = old.len();
+    let written = written.min(len);
+    let ratio = new_duration as f64 / old_duration as f64;
+    buffer.clear();
 
+    let mut rescaled = 0;
+    for age in 0..len {
+        // The old elements the new one overlaps, counted back from the newest one
+        let newest = (age as f64 * ratio + TOLERANCE).floor() as usize;
+        let oldest = (((age + 1) as f64 * ratio - TOLERANCE).ceil() as usize).min(written);
+        if newest >= oldest {
+            break;
+        }
+        buffer[len - 1 - age] = merge(&old[len - oldest..len - newest]);
+        rescaled += 1;
+    }
+    rescaled
+}
+
+/// A buffer that is fed audio on the audio thread, and read by the views.
+///
+/// # Real-time safety
+///
+/// Feeding a buffer never allocates or frees memory: [`enqueue()`](Self::enqueue),
+/// [`enqueue_buffer()`](Self::enqueue_buffer), the interleaved and planar variants,
+/// and the buffers' own audio-thread methods - like
+/// [`PeakBuffer::enqueue_slice()`], publishing a [`StagedBuffer`](super::StagedBuffer)
+/// or pushing to a [`BufferTap`](super::BufferTap) - only write to storage that was
+/// allocated when the buffer was constructed or configured. This holds for every
+/// block size, and with every optional kind of tracking enabled.
+///
+/// Methods that change a buffer's configuration, like resizing it or setting its
+/// sample rate, may allocate - call them from
+/// [`initialize()`](nih_plug::plugin::Plugin::initialize) or the editor instead.
+///
+/// The crate's tests run every audio-thread path of every buffer under an allocator
+/// that fails on the first allocation, so new features can't quietly break this.
+///
+/// # Ordering
+///
+/// Every buffer is indexed in chronological order: index 0 is the oldest element it
+/// holds, and index `len() - 1` the newest one - see [`get_newest()`](Self::get_newest).
+/// Where the buffers store their elements internally is an implementation detail,
+/// so views can read any buffer without knowing how it wraps around.
 pub trait VisualizerBuffer<T>: Index<usize> + IndexMut<usize> {
     /// Enqueues an element.
     ///
@@ -29,6 +218,38 @@ pub trait VisualizerBuffer<T>: Index<usize> + IndexMut<usize> {
         channel: Option<usize>,
     );
 
+    /// Enqueues interleaved audio, combining the channels of each frame using the
+    /// [`ChannelDownmix`].
+    ///
+    /// This is meant for audio that doesn't come from nih_plug, like a JACK or CPAL
+    /// callback, and has the same effect as enqueueing the same audio as a
+    /// [`Buffer`](`nih_plug::buffer::Buffer`). A channel count of 0, or a trailing
+    /// partial frame, fails a debug assertion; In release builds, only the whole
+    /// frames are enqueued.
+    fn enqueue_interleaved(self: &mut Self, data: &[f32], channels: usize, downmix: ChannelDownmix)
+    where
+        T: From<f32>,
+    {
+        let frames = interleaved_frames(data, channels);
+        for frame in data[..frames * channels].chunks_exact(channels) {
+            self.enqueue(T::from(downmix.mix(frame)));
+        }
+    }
+
+    /// Enqueues planar audio, one slice per channel, combining the channels using the
+    /// [`ChannelDownmix`]; See [`enqueue_interleaved()`](Self::enqueue_interleaved).
+    ///
+    /// Channels of different lengths fail a debug assertion; In release builds, only
+    /// the samples that all channels hold are enqueued.
+    fn enqueue_planar(self: &mut Self, channels: &[&[f32]], downmix: ChannelDownmix)
+    where
+        T: From<f32>,
+    {
+        for i in 0..planar_frames(channels) {
+            self.enqueue(T::from(downmix.mix_planar(channels, i)));
+        }
+    }
+
     /// Clears the entire buffer, filling it with default values (usually 0)
     fn clear(self: &mut Self);
 
@@ -52,6 +273,144 @@ pub trait VisualizerBuffer<T>: Index<usize> + IndexMut<usize> {
         self.len() == 0
     }
 
+    /// Returns the element at the given index, or `None` if the index is out of range.
+    ///
+    /// Unlike indexing, this never panics. The Cyma views read buffers this way, so
+    /// that a buffer that's resized while it's drawn can't abort the UI thread.
+    fn get(self: &Self, index: usize) -> Option<&Self::Output> {
+        (index < self.len()).then(|| &self[index])
+    }
+
+    /// Returns the element `n` places before the most recently enqueued one, or
+    /// `None` if the buffer holds `n` elements or less.
+    ///
+    /// `get_newest(0)` returns the newest element.
+    fn get_newest(self: &Self, n: usize) -> Option<&Self::Output> {
+        let len = self.len();
+        (n < len).then(|| &self[len - 1 - n])
+    }
+
+    /// Returns the duration (in seconds) of the audio data inside the buffer, or
+    /// `None` if the buffer isn't time-based.
+    fn duration(self: &Self) -> Option<f32> {
+        None
+    }
+
+    /// Returns the element that is currently being accumulated, or `None` if the
+    /// buffer doesn't accumulate samples into elements.
+    ///
+    /// The element is returned as its progress, from 0 (just started) to 1 (about to
+    /// be enqueued), along with its provisional value. Views use it to scroll
+    /// smoothly in between elements.
+    fn partial(self: &Self) -> Option<(f32, Self::Output)>
+    where
+        Self::Output: Sized,
+    {
+        None
+    }
+
+    /// Returns a counter that changes whenever the contents of the buffer change, or
+    /// `None` if the buffer doesn't keep track of this.
+    ///
+    /// Readers that derive something from a buffer can use it to skip the work while
+    /// the buffer is unchanged. The counter only ever needs to differ from its
+    /// previous value - it may wrap around.
+    fn generation(self: &Self) -> Option<u64> {
+        None
+    }
+
+    /// Returns the number of elements that were ever enqueued, or `None` if the
+    /// buffer doesn't keep track of this.
+    ///
+    /// Together with the progress of the [`partial()`](Self::partial) element, this
+    /// tells how far the buffer has scrolled, which views use to scroll along with
+    /// it - see [`GridModifiers::scroll_with()`](crate::visualizers::GridModifiers::scroll_with).
+    /// The count isn't reset by clearing or resizing the buffer.
+    fn enqueued(self: &Self) -> Option<u64> {
+        None
+    }
+
+    /// Returns the number of samples that the buffer has ever taken in, along with
+    /// the sample rate they're counted at, or `None` if the buffer doesn't keep
+    /// track of this.
+    ///
+    /// Unlike the [`enqueued()`](Self::enqueued) count, which counts elements, this
+    /// pins down a moment to the sample, which [markers](crate::utils::MarkerStore)
+    /// are anchored to. The count is never reset; When the sample rate changes, it's
+    /// rescaled to the new rate, so that it still measures the same amount of time.
+    fn sample_clock(self: &Self) -> Option<(u64, f32)> {
+        None
+    }
+
+    /// Returns the number of times the buffer was cleared, or `None` if the buffer
+    /// doesn't keep track of this.
+    ///
+    /// Views that [fade out clears](crate::visualizers::ClearFadeModifiers) compare it
+    /// from one frame to the next. Like the [`enqueued()`](Self::enqueued) count, it
+    /// only needs to differ from its previous value.
+    fn clears(self: &Self) -> Option<u64> {
+        None
+    }
+
+    /// Brings the contents of the buffer up to date before they are read.
+    ///
+    /// Views call this right after locking a buffer. Buffers that are fed audio don't
+    /// need to do anything here, but buffers whose contents are derived from other
+    /// buffers - like the [`DerivedBuffer`] - compute them.
+    fn refresh(self: &mut Self) {}
+
+    /// Returns how far the buffer is through capturing a take, from 0 to 1, or `None`
+    /// if it isn't capturing one.
+    ///
+    /// Only buffers in [`BufferMode::Capture`] capture takes. While an armed buffer
+    /// waits for its trigger, the progress is 0. Views use this to show that a take
+    /// is being captured.
+    fn capture_progress(self: &Self) -> Option<f32> {
+        None
+    }
+
+    /// Returns whether the host's transport was playing during the element at the
+    /// index, or `None` if the buffer doesn't track the transport.
+    ///
+    /// See [`PeakBuffer::with_transport_tracking()`].
+    fn transport_playing(self: &Self, _index: usize) -> Option<bool> {
+        None
+    }
+
+    /// Returns the tag that was set during the element at the index, or `None` if the
+    /// buffer doesn't track tags.
+    ///
+    /// See [`PeakBuffer::with_tag_tracking()`].
+    fn tag(self: &Self, _index: usize) -> Option<u8> {
+        None
+    }
+
+    /// Returns the mean and the standard deviation of the values inside the element
+    /// at the index, or `None` if the buffer doesn't track them.
+    ///
+    /// See [`PeakBuffer::with_deviation_tracking()`].
+    fn deviation(self: &Self, _index: usize) -> Option<(f32, f32)> {
+        None
+    }
+
+    /// Returns whether the element at the index was restored from a summary, and
+    /// hasn't been overwritten by new data yet.
+    ///
+    /// See [`PeakBuffer::restore_from_summary()`].
+    fn is_restored(self: &Self, _index: usize) -> bool {
+        false
+    }
+
+    /// Resizes the buffer to the given size, or returns a [`ConfigError`] if it's 0.
+    ///
+    /// [`resize()`](Self::resize) accepts a size of 0, which empties the buffer
+    /// entirely. Use this instead when the size comes from a setting, where an empty
+    /// buffer - which views can't display anything from - is a mistake.
+    fn try_resize(self: &mut Self, size: usize) -> Result<(), ConfigError> {
+        self.resize(check_size("buffer", "size", size)?);
+        Ok(())
+    }
+
     /// Resizes the buffer to the given size.
     ///
     /// Internally, this either calls [`shrink()`](`Buffer::shrink()`), or
@@ -0,0 +1,406 @@
+//! Drives buffers through random sequences of operations, comparing them to slow
+//! reference models after every step.
+//!
+//! The models store their elements in a plain `Vec`, with the oldest element
+//! first, and spell out the behavior that the buffers implement in a less obvious
+//! way - wrapping around, resizing, and accumulating samples into elements. Every
+//! run is seeded, so a failing run can be replayed by its seed, which the panic
+//! message includes along with the operations that led up to it.
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::super::decay::decay_weight_per_slot;
+use super::test_support::{contents, with_buffer};
+use super::{PeakBuffer, RingBuffer, VisualizerBuffer, WaveformBuffer};
+
+const RUNS: u64 = 200;
+const STEPS: usize = 60;
+
+#[derive(Clone, Debug)]
+enum Op {
+    Enqueue(Vec<f32>),
+    EnqueueBuffer(Vec<f32>),
+    Clear,
+    Resize(usize),
+    SetSampleRate(f32),
+    SetDuration(f32),
+    SetDecay(f32),
+    Read(usize),
+}
+
+fn random_op(rng: &mut StdRng) -> Op {
+    // Multiples of 1/8, so that no rounding is involved
+    let samples = |rng: &mut StdRng| {
+        let len = match rng.gen_range(0..4) {
+            0 => 0,
+            1 => rng.gen_range(1..4),
+            _ => rng.gen_range(4..300),
+        };
+        (0..len)
+            .map(|_| rng.gen_range(-8i32..=8) as f32 / 8.)
+            .collect()
+    };
+    match rng.gen_range(0..20) {
+        0..=6 => Op::Enqueue(samples(rng)),
+        7..=10 => Op::EnqueueBuffer(samples(rng)),
+        11 => Op::Clear,
+        12 | 13 => Op::Resize(rng.gen_range(0..40)),
+        // Fractional numbers of samples per element are where boundaries go wrong
+        14 => Op::SetSampleRate([0., 32., 100., 441., 1000.][rng.gen_range(0..5)]),
+        15 => Op::SetDuration([0.5, 1., 1.37][rng.gen_range(0..3)]),
+        16 => Op::SetDecay([0., 20., 250.][rng.gen_range(0..3)]),
+        _ => Op::Read(rng.gen_range(0..45)),
+    }
+}
+
+/// Runs `RUNS` random sequences of operations against a buffer and its model.
+///
+/// `apply` applies an operation to both, and `check` asserts that they hold the
+/// same contents.
+fn run<B, M>(
+    new: impl Fn() -> (B, M),
+    apply: impl Fn(&mut B, &mut M, &Op),
+    check: impl Fn(&B, &M) -> Result<(), String>,
+) {
+    for seed in 0..RUNS {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (mut buffer, mut model) = new();
+        let mut history = Vec::new();
+        for _ in 0..STEPS {
+            let op = random_op(&mut rng);
+            apply(&mut buffer, &mut model, &op);
+            history.push(op);
+            if let Err(message) = check(&buffer, &model) {
+                panic!("seed {}: {}\nafter {:#?}", seed, message, history);
+            }
+        }
+    }
+}
+
+fn compare<T: PartialEq + std::fmt::Debug>(actual: &[T], expected: &[T]) -> Result<(), String> {
+    match actual == expected {
+        true => Ok(()),
+        false => Err(format!("expected {:?}, got {:?}", expected, actual)),
+    }
+}
+
+/// A `RingBuffer`, as a `Vec` that only ever has its oldest elements removed.
+#[derive(Default)]
+struct RingModel {
+    elements: Vec<f32>,
+}
+
+impl RingModel {
+    fn new(size: usize) -> Self {
+        Self {
+            elements: vec![0.; size],
+        }
+    }
+
+    fn enqueue(&mut self, value: f32) {
+        if !self.elements.is_empty() {
+            self.elements.remove(0);
+            self.elements.push(value);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.elements.iter_mut().for_each(|element| *element = 0.);
+    }
+
+    // Keeps the newest elements, or adds zeros before the oldest one
+    fn resize(&mut self, size: usize) {
+        let len = self.elements.len();
+        if size < len {
+            self.elements.drain(..len - size);
+        } else {
+            self.elements.splice(0..0, vec![0.; size - len]);
+        }
+    }
+
+    fn newest(&self) -> f32 {
+        self.elements.last().copied().unwrap_or(0.)
+    }
+}
+
+#[test]
+fn ring_buffer() {
+    run(
+        || (RingBuffer::<f32>::new(8), RingModel::new(8)),
+        |buffer, model, op| match op {
+            Op::Enqueue(samples) | Op::EnqueueBuffer(samples) => {
+                for sample in samples {
+                    buffer.enqueue(*sample);
+                    model.enqueue(*sample);
+                }
+            }
+            Op::Clear => {
+                buffer.clear();
+                model.clear();
+            }
+            Op::Resize(size) => {
+                buffer.resize(*size);
+                model.resize(*size);
+            }
+            _ => {}
+        },
+        |buffer, model| {
+            compare(&buffer.to_vec(), &model.elements)?;
+            compare(&[buffer.peek()], &[model.newest()])?;
+            // Reading past the end never panics
+            compare(&[buffer.get(buffer.len())], &[None])
+        },
+    );
+}
+
+/// The timing shared by the buffers that accumulate samples into elements.
+///
+/// Each element spans `sample_delta` samples, which may be fractional. The sample
+/// that crosses a boundary finishes the element before it, and is the first sample
+/// of the next one. Changing how long elements are restarts the current one.
+#[derive(Default)]
+struct Slots {
+    sample_delta: f32,
+    t: f32,
+}
+
+impl Slots {
+    fn restart(&mut self, sample_delta: f32) {
+        self.sample_delta = sample_delta;
+        self.t = sample_delta;
+    }
+
+    // Returns whether the sample finishes an element
+    fn advance(&mut self) -> bool {
+        self.t -= 1.;
+        let finished = self.t < 0.;
+        if finished {
+            self.t += self.sample_delta;
+        }
+        finished
+    }
+
+    // Returns the number of elements that begin during the sample, for elements
+    // that span a sample or less
+    fn advance_raw(&mut self) -> usize {
+        self.t -= 1.;
+        let mut begun = 0;
+        while self.t <= 0. {
+            self.t += self.sample_delta;
+            begun += 1;
+        }
+        begun
+    }
+}
+
+/// A `PeakBuffer` that holds the maximum of each element.
+struct PeakModel {
+    ring: RingModel,
+    slots: Slots,
+    samples: Vec<f32>,
+    sample_rate: f32,
+    duration: f32,
+    decay: f32,
+}
+
+impl PeakModel {
+    fn new(size: usize, duration: f32, decay: f32) -> Self {
+        let mut model = Self {
+            ring: RingModel::new(size),
+            slots: Slots::default(),
+            samples: Vec::new(),
+            sample_rate: 0.,
+            duration,
+            decay,
+        };
+        model.reconfigure();
+        model
+    }
+
+    fn reconfigure(&mut self) {
+        let size = self.ring.elements.len();
+        let sample_delta = ((self.sample_rate as f64 * self.duration as f64) / size as f64) as f32;
+        self.slots.restart(sample_delta);
+        self.samples.clear();
+        self.ring.clear();
+    }
+
+    fn decay_weight(&self) -> f32 {
+        let slot_duration = match self.sample_rate > 0. {
+            true => self.slots.sample_delta as f64 / self.sample_rate as f64,
+            false => self.duration as f64 / self.ring.elements.len() as f64,
+        };
+        decay_weight_per_slot(self.decay, slot_duration)
+    }
+
+    fn enqueue(&mut self, sample: f32) {
+        if self.slots.advance() {
+            let peak = self.samples.drain(..).fold(0., f32::max);
+            let decayed = self.ring.newest() * self.decay_weight();
+            self.ring.enqueue(peak.max(decayed));
+        }
+        self.samples.push(sample.abs());
+    }
+}
+
+#[test]
+fn peak_buffer() {
+    run(
+        || (PeakBuffer::new(8, 1.0, 0.), PeakModel::new(8, 1.0, 0.)),
+        |buffer, model, op| match op {
+            Op::Enqueue(samples) => {
+                for sample in samples {
+                    buffer.enqueue(*sample);
+                    model.enqueue(*sample);
+                }
+            }
+            Op::EnqueueBuffer(samples) => {
+                with_buffer(&mut [samples.clone()], |b| buffer.enqueue_buffer(b, None));
+                samples.iter().for_each(|sample| model.enqueue(*sample));
+            }
+            Op::Clear => {
+                buffer.clear();
+                model.ring.clear();
+                model.samples.clear();
+            }
+            Op::Resize(size) => {
+                buffer.resize(*size);
+                if *size != model.ring.elements.len() {
+                    model.ring.resize(*size);
+                    model.reconfigure();
+                }
+            }
+            Op::SetSampleRate(sample_rate) => {
+                buffer.set_sample_rate(*sample_rate);
+                model.sample_rate = *sample_rate;
+                model.reconfigure();
+            }
+            Op::SetDuration(duration) => {
+                buffer.set_duration(*duration);
+                model.duration = *duration;
+                model.reconfigure();
+            }
+            Op::SetDecay(decay) => {
+                buffer.set_decay(*decay);
+                model.decay = *decay;
+            }
+            Op::Read(index) => {
+                let expected = model.ring.elements.get(*index);
+                assert_eq!(buffer.get(*index), expected);
+            }
+        },
+        |buffer, model| compare(&contents(buffer), &model.ring.elements),
+    );
+}
+
+/// A `WaveformBuffer` that holds the minimum and maximum of each element, or the
+/// raw samples once elements span a sample or less.
+struct WaveformModel {
+    elements: Vec<(f32, f32)>,
+    slots: Slots,
+    raw: bool,
+    samples: Vec<f32>,
+    sample_rate: f32,
+    duration: f32,
+}
+
+impl WaveformModel {
+    fn new(size: usize, duration: f32) -> Self {
+        let mut model = Self {
+            elements: vec![(0., 0.); size],
+            slots: Slots::default(),
+            raw: false,
+            samples: Vec::new(),
+            sample_rate: 0.,
+            duration,
+        };
+        model.reconfigure();
+        model
+    }
+
+    fn reconfigure(&mut self) {
+        let sample_delta = (self.sample_rate * self.duration) / self.elements.len() as f32;
+        // Raw samples are stored from 1 sample per element down, and until elements
+        // span more than 1.25 samples
+        let boundary = if self.raw { 1.25 } else { 1. };
+        self.raw = sample_delta > 0. && sample_delta <= boundary;
+        self.slots.restart(sample_delta);
+        self.samples.clear();
+        self.clear();
+    }
+
+    fn clear(&mut self) {
+        self.elements.iter_mut().for_each(|e| *e = (0., 0.));
+    }
+
+    fn enqueue(&mut self, sample: f32) {
+        if self.raw {
+            for _ in 0..self.slots.advance_raw() {
+                if !self.elements.is_empty() {
+                    self.elements.remove(0);
+                    self.elements.push((sample, sample));
+                }
+            }
+            return;
+        }
+        if self.slots.advance() {
+            // An element without any samples is silent
+            let min = self.samples.iter().copied().reduce(f32::min).unwrap_or(0.);
+            let max = self.samples.iter().copied().reduce(f32::max).unwrap_or(0.);
+            self.samples.clear();
+            if !self.elements.is_empty() {
+                self.elements.remove(0);
+                self.elements.push((min, max));
+            }
+        }
+        self.samples.push(sample);
+    }
+}
+
+#[test]
+fn waveform_buffer() {
+    run(
+        || (WaveformBuffer::new(8, 1.0), WaveformModel::new(8, 1.0)),
+        |buffer, model, op| match op {
+            Op::Enqueue(samples) => {
+                for sample in samples {
+                    buffer.enqueue(*sample);
+                    model.enqueue(*sample);
+                }
+            }
+            Op::EnqueueBuffer(samples) => {
+                with_buffer(&mut [samples.clone()], |b| buffer.enqueue_buffer(b, None));
+                samples.iter().for_each(|sample| model.enqueue(*sample));
+            }
+            Op::Clear => {
+                buffer.clear();
+                model.clear();
+                model.samples.clear();
+            }
+            Op::Resize(size) => {
+                buffer.resize(*size);
+                if *size != model.elements.len() {
+                    // Resizing clears the buffer, so where the zeros go doesn't matter
+                    model.elements.resize(*size, (0., 0.));
+                    model.reconfigure();
+                }
+            }
+            Op::SetSampleRate(sample_rate) => {
+                buffer.set_sample_rate(*sample_rate);
+                model.sample_rate = *sample_rate;
+                model.reconfigure();
+            }
+            Op::SetDuration(duration) => {
+                buffer.set_duration(*duration);
+                model.duration = *duration;
+                model.reconfigure();
+            }
+            Op::SetDecay(_) => {}
+            Op::Read(index) => {
+                let expected = model.elements.get(*index);
+                assert_eq!(buffer.get(*index), expected);
+            }
+        },
+        |buffer, model| compare(&contents(buffer), &model.elements),
+    );
+}
@@ -0,0 +1,25 @@
+use std::borrow::Cow;
+use std::fmt::{self, Debug};
+
+/// The optional name of a buffer, which identifies it in logs, panic messages and
+/// snapshots.
+///
+/// Naming a buffer doesn't allocate if the name is a `&'static str`.
+#[derive(Clone, PartialEq, Eq, Default, Hash)]
+pub(crate) struct BufferName(Option<Cow<'static, str>>);
+
+impl BufferName {
+    pub(crate) fn get(self: &Self) -> Option<&str> {
+        self.0.as_deref()
+    }
+
+    pub(crate) fn set(self: &mut Self, name: impl Into<Cow<'static, str>>) {
+        self.0 = Some(name.into());
+    }
+}
+
+impl Debug for BufferName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.get().fmt(f)
+    }
+}
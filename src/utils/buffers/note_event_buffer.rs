@@ -0,0 +1,335 @@
+use std::collections::VecDeque;
+
+use super::super::config_error::{check_duration, check_size, clamp_duration, ConfigError};
+
+/// A note event that was stored inside a [`NoteEventBuffer`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoteActivity {
+    /// The slot the event happened in, counted from when the buffer was last cleared.
+    pub slot: u64,
+    /// The MIDI note number.
+    pub note: u8,
+    /// The velocity of the event, between 0 and 1.
+    pub velocity: f32,
+    /// `true` for note-on events, `false` for note-off events.
+    pub on: bool,
+}
+
+/// Stores recent note events over time.
+///
+/// This buffer is the note counterpart to the [`PeakBuffer`](super::PeakBuffer).
+/// It divides time into the same slots - `size` slots spanning `duration`
+/// seconds - so that a view drawing its events scrolls in sync with a graph of
+/// the same size and duration.
+///
+/// Feed it the note events from your `process()` function using
+/// [`enqueue_note()`](Self::enqueue_note), and let it know how much time has
+/// passed by calling [`advance()`](Self::advance) at the end of each block:
+///
+/// ```
+/// while let Some(event) = context.next_event() {
+///     match event {
+///         NoteEvent::NoteOn { timing, note, velocity, .. } => {
+///             note_buffer.enqueue_note(timing, note, velocity, true)
+///         }
+///         NoteEvent::NoteOff { timing, note, velocity, .. } => {
+///             note_buffer.enqueue_note(timing, note, velocity, false)
+///         }
+///         _ => {}
+///     }
+/// }
+/// note_buffer.advance(buffer.samples());
+/// ```
+///
+/// Events that are older than the buffer's duration are evicted. At most
+/// `max_events` events are stored - when more arrive, the oldest ones are
+/// dropped. The storage is allocated up front, so enqueueing never allocates.
+///
+/// The `NoteEventBuffer` needs to be provided a sample rate after initialization -
+/// do this inside your [`initialize()`](nih_plug::plugin::Plugin::initialize)
+/// function.
+#[derive(Debug, Default)]
+pub struct NoteEventBuffer {
+    events: VecDeque<NoteActivity>,
+    max_events: usize,
+    // The number of slots inside the buffer
+    size: usize,
+    // The gap between slots in samples
+    sample_delta: f64,
+    // Used to calculate the sample_delta
+    sample_rate: f32,
+    duration: f32,
+    // The number of samples that have passed since the buffer was cleared
+    position: f64,
+    // Whether the plug-in is rendering offline, in which case nothing is enqueued
+    offline: bool,
+}
+
+impl NoteEventBuffer {
+    /// Constructs a new `NoteEventBuffer`.
+    ///
+    /// * `size` - The number of slots; Match this with the size of the buffer you want to scroll in sync with
+    /// * `duration` - The duration (in seconds) of the events inside the buffer
+    /// * `max_events` - The maximum number of events that can be stored at once
+    ///
+    /// The buffer needs to be provided a sample rate after initialization - do this by
+    /// calling [`set_sample_rate`](Self::set_sample_rate) inside your
+    /// [`initialize()`](nih_plug::plugin::Plugin::initialize) function.
+    pub fn new(size: usize, duration: f32, max_events: usize) -> Self {
+        Self {
+            events: VecDeque::with_capacity(max_events),
+            max_events,
+            size,
+            sample_delta: 0.,
+            sample_rate: 0.,
+            duration: clamp_duration("note event buffer", "duration", duration),
+            position: 0.,
+            offline: false,
+        }
+    }
+
+    /// Constructs a new `NoteEventBuffer`, or returns a [`ConfigError`] if the size is
+    /// 0 or the duration isn't positive; See [`new()`](Self::new).
+    pub fn try_new(size: usize, duration: f32, max_events: usize) -> Result<Self, ConfigError> {
+        check_size("note event buffer", "size", size)?;
+        check_duration("note event buffer", "duration", duration)?;
+        Ok(Self::new(size, duration, max_events))
+    }
+
+    /// Sets the sample rate of the incoming audio.
+    ///
+    /// This function **clears** the buffer.
+    pub fn set_sample_rate(self: &mut Self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.update();
+        self.clear();
+    }
+
+    /// Hints whether the plug-in is rendering offline, in which case events are
+    /// neither enqueued nor advanced. See
+    /// [`PeakBuffer::set_offline`](super::PeakBuffer::set_offline).
+    pub fn set_offline(self: &mut Self, offline: bool) {
+        self.offline = offline;
+    }
+
+    /// Sets the duration (in seconds) of the events inside the buffer.
+    ///
+    /// This function **clears** the buffer. A duration that isn't positive and finite
+    /// fails a debug assertion, and is clamped in release builds; Use
+    /// [`try_set_duration()`](Self::try_set_duration) to handle it instead.
+    pub fn set_duration(self: &mut Self, duration: f32) {
+        self.duration = clamp_duration("note event buffer", "duration", duration);
+        self.update();
+        self.clear();
+    }
+
+    /// Sets the duration (in seconds) of the events inside the buffer, or returns
+    /// a [`ConfigError`] if it isn't positive and finite.
+    ///
+    /// This function **clears** the buffer.
+    pub fn try_set_duration(self: &mut Self, duration: f32) -> Result<(), ConfigError> {
+        self.set_duration(check_duration("note event buffer", "duration", duration)?);
+        Ok(())
+    }
+
+    /// Resizes the buffer to the given number of slots.
+    ///
+    /// This function **clears** the buffer.
+    pub fn resize(self: &mut Self, size: usize) {
+        self.size = size;
+        self.update();
+        self.clear();
+    }
+
+    /// Resizes the buffer to the given number of slots, or returns a [`ConfigError`]
+    /// if it's 0.
+    ///
+    /// This function **clears** the buffer.
+    pub fn try_resize(self: &mut Self, size: usize) -> Result<(), ConfigError> {
+        self.resize(check_size("note event buffer", "size", size)?);
+        Ok(())
+    }
+
+    /// Enqueues a note event.
+    ///
+    /// * `timing` - The event's offset (in samples) from the start of the current block
+    /// * `note` - The MIDI note number
+    /// * `velocity` - The velocity, between 0 and 1
+    /// * `on` - Whether this is a note-on or a note-off event
+    pub fn enqueue_note(self: &mut Self, timing: u32, note: u8, velocity: f32, on: bool) {
+        if self.offline || self.max_events == 0 {
+            return;
+        }
+        if self.events.len() == self.max_events {
+            self.events.pop_front();
+        }
+
+        let slot = self.slot_at(self.position + timing as f64);
+        self.events.push_back(NoteActivity {
+            slot,
+            note,
+            velocity: velocity.clamp(0., 1.),
+            on,
+        });
+    }
+
+    /// Advances the buffer's time by a number of samples, evicting events that
+    /// have become too old.
+    ///
+    /// Call this once per block, after enqueueing the block's events.
+    pub fn advance(self: &mut Self, samples: usize) {
+        if self.offline {
+            return;
+        }
+        self.position += samples as f64;
+
+        let current_slot = self.current_slot();
+        while let Some(event) = self.events.front() {
+            if current_slot - event.slot.min(current_slot) < self.size as u64 {
+                break;
+            }
+            self.events.pop_front();
+        }
+    }
+
+    /// Returns the slot that is currently being filled.
+    ///
+    /// The events in this slot are at the newest end of the buffer.
+    pub fn current_slot(self: &Self) -> u64 {
+        self.slot_at(self.position)
+    }
+
+    /// Returns the index of a slot within the buffer, where 0 is the oldest slot
+    /// and `len() - 1` is the current one.
+    ///
+    /// Returns `None` if the slot lies outside of the buffer.
+    pub fn slot_index(self: &Self, slot: u64) -> Option<usize> {
+        let age = self.current_slot().checked_sub(slot)?;
+        (age < self.size as u64).then(|| self.size - 1 - age as usize)
+    }
+
+    /// Returns an iterator over all stored events, from the oldest to the newest.
+    pub fn events(self: &Self) -> impl DoubleEndedIterator<Item = &NoteActivity> + '_ {
+        self.events.iter()
+    }
+
+    /// Returns the number of stored events.
+    pub fn event_count(self: &Self) -> usize {
+        self.events.len()
+    }
+
+    /// Removes all events and resets the buffer's time.
+    pub fn clear(self: &mut Self) {
+        self.events.clear();
+        self.position = 0.;
+    }
+
+    /// Returns the number of slots inside the buffer.
+    pub fn len(self: &Self) -> usize {
+        self.size
+    }
+
+    /// Returns the duration (in seconds) that the buffer's slots span.
+    pub fn duration(self: &Self) -> f32 {
+        self.duration
+    }
+
+    /// Returns `true` if the buffer has no slots.
+    pub fn is_empty(self: &Self) -> bool {
+        self.size == 0
+    }
+
+    fn slot_at(self: &Self, position: f64) -> u64 {
+        if self.sample_delta > 0. {
+            (position / self.sample_delta) as u64
+        } else {
+            0
+        }
+    }
+
+    fn update(self: &mut Self) {
+        self.sample_delta = (self.sample_rate as f64 * self.duration as f64) / self.size as f64;
+    }
+}
+
+// A derived clone would only reserve room for the events that are stored right now,
+// so enqueueing into the clone could allocate
+impl Clone for NoteEventBuffer {
+    fn clone(&self) -> Self {
+        let mut events = VecDeque::with_capacity(self.max_events);
+        events.extend(self.events.iter().copied());
+        Self { events, ..*self }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NoteEventBuffer;
+
+    fn buffer() -> NoteEventBuffer {
+        // 10 slots of 100 samples each
+        let mut buffer = NoteEventBuffer::new(10, 1.0, 8);
+        buffer.set_sample_rate(1000.0);
+        buffer
+    }
+
+    #[test]
+    fn slots() {
+        let mut buffer = buffer();
+
+        buffer.enqueue_note(50, 60, 1.0, true);
+        buffer.enqueue_note(250, 60, 0.5, false);
+        buffer.advance(300);
+
+        let slots = buffer.events().map(|e| e.slot).collect::<Vec<_>>();
+        assert_eq!(slots, vec![0, 2]);
+        assert_eq!(buffer.current_slot(), 3);
+        assert_eq!(buffer.slot_index(3), Some(9));
+        assert_eq!(buffer.slot_index(0), Some(6));
+        assert_eq!(buffer.slot_index(4), None);
+    }
+
+    #[test]
+    fn simultaneous_events() {
+        let mut buffer = buffer();
+
+        for note in 60..64 {
+            buffer.enqueue_note(10, note, 1.0, true);
+        }
+        buffer.advance(100);
+
+        assert_eq!(buffer.event_count(), 4);
+        assert!(buffer.events().all(|e| e.slot == 0));
+    }
+
+    #[test]
+    fn eviction() {
+        let mut buffer = buffer();
+
+        buffer.enqueue_note(0, 60, 1.0, true);
+        buffer.advance(500);
+        buffer.enqueue_note(0, 62, 1.0, true);
+
+        // The first event is still inside the window
+        buffer.advance(499);
+        assert_eq!(buffer.event_count(), 2);
+
+        // Now it's 10 slots old, which is outside of the window
+        buffer.advance(1);
+        assert_eq!(buffer.event_count(), 1);
+        assert_eq!(buffer.events().next().unwrap().note, 62);
+        assert_eq!(buffer.slot_index(0), None);
+    }
+
+    #[test]
+    fn max_events() {
+        let mut buffer = buffer();
+
+        for note in 0..12 {
+            buffer.enqueue_note(0, note, 1.0, true);
+        }
+
+        assert_eq!(buffer.event_count(), 8);
+        assert_eq!(buffer.events().next().unwrap().note, 4);
+    }
+}
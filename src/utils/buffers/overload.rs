@@ -0,0 +1,119 @@
+use nih_plug::util::{db_to_gain, gain_to_db};
+
+/// The worst overload since a [`PeakBuffer`](super::PeakBuffer)'s overload latch was
+/// last reset; See
+/// [`set_overload_threshold()`](super::PeakBuffer::set_overload_threshold).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Overload {
+    /// The absolute value of the worst sample.
+    pub peak: f32,
+    /// The number of samples the buffer had processed before the worst sample.
+    pub sample: u64,
+    /// The same position, in seconds at the buffer's sample rate.
+    pub time: f32,
+}
+
+impl Overload {
+    /// Returns the level of the worst sample, in decibels.
+    pub fn peak_db(self: &Self) -> f32 {
+        gain_to_db(self.peak)
+    }
+}
+
+/// Latches samples that reach a threshold, until it's reset.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct OverloadLatch {
+    // The threshold as a gain, if the latch is enabled
+    threshold: Option<f32>,
+    // The time after the last overload that the latch clears itself after, in seconds
+    auto_reset: Option<f32>,
+    // The worst sample and its position, while latched
+    worst: Option<(f32, u64)>,
+    // The position of the last sample that reached the threshold
+    last: u64,
+}
+
+impl OverloadLatch {
+    pub(crate) fn set_threshold(self: &mut Self, db: Option<f32>) {
+        self.threshold = db.map(db_to_gain);
+        self.reset();
+    }
+
+    pub(crate) fn threshold_db(self: &Self) -> Option<f32> {
+        self.threshold.map(gain_to_db)
+    }
+
+    pub(crate) fn set_auto_reset(self: &mut Self, seconds: Option<f32>) {
+        self.auto_reset = seconds;
+    }
+
+    pub(crate) fn auto_reset(self: &Self) -> Option<f32> {
+        self.auto_reset
+    }
+
+    pub(crate) fn is_enabled(self: &Self) -> bool {
+        self.threshold.is_some()
+    }
+
+    pub(crate) fn reset(self: &mut Self) {
+        self.worst = None;
+    }
+
+    /// Latches the absolute value of the sample at the position, if it reaches the
+    /// threshold.
+    pub(crate) fn observe(self: &mut Self, value: f32, sample: u64, sample_rate: f32) {
+        let Some(threshold) = self.threshold else {
+            return;
+        };
+        if !(value >= threshold) {
+            return;
+        }
+        if self.is_expired(sample, sample_rate) {
+            self.worst = None;
+        }
+        if self.worst.is_none_or(|(peak, _)| value > peak) {
+            self.worst = Some((value, sample));
+        }
+        self.last = sample;
+    }
+
+    /// Latches the samples, the first of which is at the position.
+    pub(crate) fn observe_slice(self: &mut Self, samples: &[f32], first: u64, sample_rate: f32) {
+        if self.threshold.is_none() {
+            return;
+        }
+        for (i, sample) in samples.iter().enumerate() {
+            self.observe(sample.abs(), first + i as u64, sample_rate);
+        }
+    }
+
+    /// Returns the worst overload, if latched, once `processed` samples were
+    /// processed.
+    pub(crate) fn state(self: &Self, processed: u64, sample_rate: f32) -> Option<Overload> {
+        if self.is_expired(processed, sample_rate) {
+            return None;
+        }
+        let (peak, sample) = self.worst?;
+        Some(Overload {
+            peak,
+            sample,
+            time: if sample_rate > 0. {
+                (sample as f64 / sample_rate as f64) as f32
+            } else {
+                0.
+            },
+        })
+    }
+
+    // Whether the auto-reset time passed since the last overload, once `processed`
+    // samples were processed
+    fn is_expired(self: &Self, processed: u64, sample_rate: f32) -> bool {
+        match (self.worst, self.auto_reset) {
+            (Some(_), Some(seconds)) if sample_rate > 0. => {
+                let since = processed.saturating_sub(self.last + 1);
+                since as f64 >= seconds as f64 * sample_rate as f64
+            }
+            _ => false,
+        }
+    }
+}
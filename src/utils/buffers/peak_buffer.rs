@@ -1,6 +1,29 @@
+use std::fmt::{self, Debug};
 use std::ops::{Index, IndexMut};
 
-use super::{RingBuffer, VisualizerBuffer};
+use super::{
+    super::buffer_tap::{AttachedTap, BufferTap, TapMode},
+    super::config_error::{
+        check_decay, check_density, check_duration, check_size, clamp_decay, clamp_density,
+        clamp_duration, ConfigError,
+    },
+    super::decay::{decay_ms_from_db_per_second, decay_weight_per_slot, TempoDecay},
+    super::diagnostics::Diagnostics,
+    super::liveness::Heartbeat,
+    super::markers::rescale_samples,
+    super::slot_subscription::{SlotPublisher, SlotReceiver},
+    ballistics::{BallisticsPreset, Detector},
+    capture::{BufferMode, Capture},
+    channel_source::{for_each_routed, ChannelSource, SourceRouting, SourceSelector},
+    check_index, density_size,
+    deviation::SlotDeviation,
+    overload::{Overload, OverloadLatch},
+    playback_rate::{retime, PlaybackRate},
+    rescale_elements, slot_progress,
+    tags::SlotTags,
+    transport::TransportFlags,
+    window_slots, BufferSnapshot, RingBuffer, SnapshotExport, VisualizerBuffer,
+};
 
 /// Stores peak information.
 ///
@@ -8,25 +31,81 @@ use super::{RingBuffer, VisualizerBuffer};
 /// It can apply a decay to them, making it useful for peak visualizers such as peak
 /// graphs and meters.
 ///
+/// Silent samples are treated like any other sample - the buffer keeps advancing
+/// while the input is silent, so older peaks scroll away at the same rate as
+/// they would during playback.
+///
+/// By default, each element of the buffer holds the maximum of the samples it
+/// spans. Other ways of aggregating the samples can be chosen using an
+/// [`Aggregation`]. To follow the ballistics of a standard meter type instead, use a
+/// [`BallisticsPreset`].
+///
 /// The `PeakBuffer` needs to be provided a sample rate after initialization - do
 /// this inside your [`initialize()`](nih_plug::plugin::Plugin::initialize)
 /// function.
 #[derive(Clone, Default)]
 pub struct PeakBuffer {
     buffer: RingBuffer<f32>,
-    // Minimum and maximum accumulators
+    aggregation: Aggregation,
+    // Maximum accumulator
     max_acc: f32,
+    // Sum and count accumulators, used for the mean
+    sum_acc: f32,
+    count: u32,
+    // A random subset of the absolute values inside the current window, used for percentiles
+    reservoir: [f32; RESERVOIR_SIZE],
+    // State of the generator used to pick which values end up in the reservoir
+    rng_state: u32,
     // The gap between elements of the buffer in samples
     sample_delta: f32,
     // Used to calculate the sample_delta
     sample_rate: f32,
     duration: f32,
+    // The elements per second that the size follows when the duration changes, if any
+    density: Option<f32>,
     // The current time, counts down from sample_delta to 0
     t: f32,
-    /// The decay time for the peak amplitude to halve.
+    /// The time it takes for a peak to fall by 12 dB, in milliseconds.
     decay: f32,
-    // This is set `set_sample_rate()` based on the sample_delta
+    // The weight each element's predecessor is decayed by; This is set in `update()`
+    // based on the time each element spans, so it doesn't depend on the buffer's size
     decay_weight: f32,
+    // The decay in beats, if it's given in beats, and the tempo it follows
+    tempo: TempoDecay,
+    // The rate the host plays back at, which the duration follows
+    playback: PlaybackRate,
+    // The channels that are read from a buffer that's enqueued without a channel
+    routing: SourceRouting,
+    // Whether the plug-in is rendering offline, in which case nothing is enqueued
+    offline: bool,
+    // The ballistics each sample goes through before being aggregated, if any
+    ballistics: Option<BallisticsPreset>,
+    // This is set in `set_sample_rate()` based on the ballistics
+    detector: Detector,
+    // Mirrors the samples or elements to a background thread
+    tap: AttachedTap,
+    // Publishes the finalized elements to the receivers that subscribed
+    subscribers: SlotPublisher,
+    // Whether the buffer scrolls or captures takes, and the state of the take
+    capture: Capture,
+    // Whether the transport was playing during each element, if tracked
+    transport: Option<TransportFlags>,
+    // The tag that was set during each element, if tracked
+    tags: Option<SlotTags>,
+    // The mean and deviation of the values inside each element, if tracked
+    deviation: Option<SlotDeviation>,
+    // Counts the samples that were skipped
+    diagnostics: Diagnostics,
+    // The number of oldest elements that were restored from a summary, and haven't
+    // been overwritten yet
+    restored: usize,
+    // Counts the enqueued elements for the editor, if attached
+    heartbeat: Option<Heartbeat>,
+    // The number of samples processed, and whether the transport was stopped
+    processed: u64,
+    transport_stopped: bool,
+    // Latches the samples that reach the overload threshold, if set
+    overload: OverloadLatch,
 }
 
 impl PeakBuffer {
@@ -40,25 +119,339 @@ impl PeakBuffer {
     /// calling [`set_sample_rate`](Self::set_sample_rate) inside your
     /// [`initialize()`](nih_plug::plugin::Plugin::initialize) function.
     pub fn new(size: usize, duration: f32, decay: f32) -> Self {
-        let decay_weight = Self::decay_weight(decay, size, duration);
+        Self::with_aggregation(size, duration, decay, Aggregation::Max)
+    }
+
+    /// Constructs a new `PeakBuffer`, or returns a [`ConfigError`] if the size is 0,
+    /// the duration isn't positive, or the decay is negative; See [`new()`](Self::new).
+    pub fn try_new(size: usize, duration: f32, decay: f32) -> Result<Self, ConfigError> {
+        check_size("peak buffer", "size", size)?;
+        check_duration("peak buffer", "duration", duration)?;
+        check_decay("peak buffer", decay)?;
+        Ok(Self::new(size, duration, decay))
+    }
+
+    /// Constructs a new `PeakBuffer` that holds a number of elements per second,
+    /// instead of a fixed number of elements.
+    ///
+    /// * `points_per_second` - The number of elements per second of audio data
+    /// * `duration` - The duration (in seconds) of the audio data inside the buffer
+    /// * `decay` - The time it takes for a sample inside the buffer to decrease by -12dB, in milliseconds
+    ///
+    /// The size is rounded from the density, and follows it whenever the duration
+    /// changes, so the elements keep spanning the same time. Growing or shrinking
+    /// the buffer explicitly goes back to a fixed size.
+    ///
+    /// ```
+    /// // 100 elements per second across 10 seconds, so 1000 elements
+    /// let buffer = PeakBuffer::with_density(100.0, 10.0, 50.0);
+    /// ```
+    pub fn with_density(points_per_second: f32, duration: f32, decay: f32) -> Self {
+        let density = clamp_density("peak buffer", points_per_second);
+        let duration = clamp_duration("peak buffer", "duration", duration);
+        Self {
+            density: Some(density),
+            ..Self::new(density_size(density, duration), duration, decay)
+        }
+    }
+
+    /// Constructs a new `PeakBuffer` that holds a number of elements per second, or
+    /// returns a [`ConfigError`] if the density or the duration isn't positive, or
+    /// the decay is negative; See [`with_density()`](Self::with_density).
+    pub fn try_with_density(
+        points_per_second: f32,
+        duration: f32,
+        decay: f32,
+    ) -> Result<Self, ConfigError> {
+        check_density("peak buffer", points_per_second)?;
+        check_duration("peak buffer", "duration", duration)?;
+        check_decay("peak buffer", decay)?;
+        Ok(Self::with_density(points_per_second, duration, decay))
+    }
+
+    /// Returns the number of elements per second of audio data.
+    ///
+    /// For a buffer constructed [`with_density()`](Self::with_density), this is the
+    /// density it keeps, which its size is rounded from.
+    pub fn density(self: &Self) -> f32 {
+        self.density
+            .unwrap_or(self.buffer.len() as f32 / self.duration)
+    }
+
+    /// Returns the elements that hold the newest `seconds` of audio data, from the
+    /// oldest to the newest.
+    ///
+    /// The window is mapped to elements using the time each element spans, and
+    /// includes the element that lies partly inside it - so with 30 ms elements, a
+    /// window of 100 ms yields the newest 4 elements. A window that reaches back
+    /// further than the buffer holds, or further than it was filled since it was
+    /// constructed, yields all of those elements.
+    ///
+    /// To draw the window, e.g. as an inset next to a graph of the whole buffer, use
+    /// a [`WindowedBuffer`](super::WindowedBuffer).
+    pub fn newest_window(self: &Self, seconds: f32) -> impl Iterator<Item = f32> + '_ {
+        let len = self.buffer.len();
+        let slots = window_slots(
+            seconds,
+            self.slot_duration(),
+            len,
+            Some(self.buffer.enqueued()),
+        );
+        self.buffer.iter().skip(len - slots).copied()
+    }
+
+    /// Constructs a new `PeakBuffer` that aggregates the samples inside each element
+    /// using the given [`Aggregation`].
+    ///
+    /// * `size` - The length of the buffer in samples; Usually, this can be kept < 2000
+    /// * `duration` - The duration (in seconds) of the audio data inside the buffer
+    /// * `decay` - The time it takes for a sample inside the buffer to decrease by -12dB, in milliseconds
+    /// * `aggregation` - How the samples inside each element are combined
+    pub fn with_aggregation(
+        size: usize,
+        duration: f32,
+        decay: f32,
+        aggregation: Aggregation,
+    ) -> Self {
+        let duration = clamp_duration("peak buffer", "duration", duration);
+        let decay = clamp_decay("peak buffer", decay);
+        let decay_weight = decay_weight_per_slot(decay, duration as f64 / size as f64);
         Self {
             buffer: RingBuffer::<f32>::new(size),
+            aggregation,
             max_acc: 0.,
+            sum_acc: 0.,
+            count: 0,
+            reservoir: [0.; RESERVOIR_SIZE],
+            rng_state: RNG_SEED,
             sample_delta: 0.,
             sample_rate: 0.,
             duration,
+            density: None,
             t: 0.,
             decay,
             decay_weight,
+            tempo: TempoDecay::default(),
+            playback: PlaybackRate::default(),
+            routing: SourceRouting::default(),
+            offline: false,
+            ballistics: None,
+            detector: Detector::default(),
+            tap: AttachedTap::default(),
+            subscribers: SlotPublisher::default(),
+            capture: Capture::default(),
+            transport: None,
+            tags: None,
+            deviation: None,
+            diagnostics: Diagnostics::default(),
+            restored: 0,
+            heartbeat: None,
+            processed: 0,
+            transport_stopped: false,
+            overload: OverloadLatch::default(),
         }
     }
 
+    /// Names the `PeakBuffer`, which identifies it in panic messages, snapshots and the
+    /// debug registry.
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.buffer.name_mut().set(name);
+        self
+    }
+
+    /// Sets the name of the `PeakBuffer`; See [`with_name()`](Self::with_name).
+    pub fn set_name(self: &mut Self, name: String) {
+        self.buffer.name_mut().set(name);
+    }
+
+    /// Returns the name of the `PeakBuffer`, if it has one.
+    pub fn name(self: &Self) -> Option<&str> {
+        self.buffer.name()
+    }
+
+    /// Constructs a new `PeakBuffer` whose decay is given as a release, in decibels
+    /// per second.
+    ///
+    /// * `size` - The length of the buffer in samples; Usually, this can be kept < 2000
+    /// * `duration` - The duration (in seconds) of the audio data inside the buffer
+    /// * `db_per_second` - The rate at which the peaks fall, e.g. 12.0 for -12dB per second
+    ///
+    /// See [`decay_ms_from_db_per_second()`](crate::utils::decay_ms_from_db_per_second).
+    pub fn with_release(size: usize, duration: f32, db_per_second: f32) -> Self {
+        Self::new(size, duration, decay_ms_from_db_per_second(db_per_second))
+    }
+
+    /// Applies the ballistics of a standard meter type to the `PeakBuffer`.
+    ///
+    /// The preset fully determines how the buffer smoothes its input - each sample
+    /// goes through the preset's ballistics, and each element holds the highest
+    /// level they reached. This replaces the buffer's decay and aggregation.
+    ///
+    /// ```
+    /// let vu_buffer = PeakBuffer::new(800, 10.0, 0.0).with_ballistics(BallisticsPreset::Vu);
+    /// ```
+    pub fn with_ballistics(mut self, preset: BallisticsPreset) -> Self {
+        self.set_ballistics(preset);
+        self
+    }
+
+    /// Applies the ballistics of a standard meter type to the `PeakBuffer`; See
+    /// [`with_ballistics()`](Self::with_ballistics).
+    ///
+    /// This function **clears** the buffer.
+    pub fn set_ballistics(self: &mut Self, preset: BallisticsPreset) {
+        self.ballistics = Some(preset);
+        self.decay = 0.;
+        self.tempo.clear_beats();
+        self.aggregation = Aggregation::Max;
+        self.update();
+        self.clear();
+    }
+
+    /// Returns the ballistics applied to the `PeakBuffer`, if any.
+    pub fn ballistics(self: &Self) -> Option<BallisticsPreset> {
+        self.ballistics
+    }
+
     /// Sets the decay time of the `PeakBuffer`.
     ///
     /// * `decay` - The time it takes for a sample inside the buffer to decrease by -12dB, in milliseconds
+    ///
+    /// A negative or NaN decay fails a debug assertion, and is clamped to 0 in release
+    /// builds; Use [`try_set_decay()`](Self::try_set_decay) to handle it instead.
     pub fn set_decay(self: &mut Self, decay: f32) {
-        self.decay = decay;
-        self.update();
+        self.decay = clamp_decay("peak buffer", decay);
+        self.decay_weight = decay_weight_per_slot(self.decay, self.slot_duration());
+        self.tempo.clear_beats();
+    }
+
+    /// Sets the decay time of the `PeakBuffer`, or returns a [`ConfigError`] if it's
+    /// negative or NaN.
+    pub fn try_set_decay(self: &mut Self, decay: f32) -> Result<(), ConfigError> {
+        self.set_decay(check_decay("peak buffer", decay)?);
+        Ok(())
+    }
+
+    /// Sets the decay of the `PeakBuffer` as a release, in decibels per second.
+    pub fn set_release(self: &mut Self, db_per_second: f32) {
+        self.set_decay(decay_ms_from_db_per_second(db_per_second));
+    }
+
+    /// Sets the decay of the `PeakBuffer` in beats, so that it follows the tempo of
+    /// the project - e.g. a decay of `1.0` falls by 12 dB per quarter note.
+    ///
+    /// The tempo is reported using [`set_tempo()`](Self::set_tempo). Until it is,
+    /// the decay assumes a tempo of [120 BPM](crate::utils::DEFAULT_TEMPO). Setting the
+    /// decay in milliseconds, or as a release, ends following the tempo.
+    ///
+    /// A negative or NaN decay fails a debug assertion, and is clamped to 0 in release
+    /// builds.
+    pub fn set_decay_beats(self: &mut Self, beats: f32) {
+        self.decay = self.tempo.set_beats(clamp_decay("peak buffer", beats));
+        self.decay_weight = decay_weight_per_slot(self.decay, self.slot_duration());
+    }
+
+    /// Returns the decay of the `PeakBuffer` in beats, if it follows the tempo; See
+    /// [`set_decay_beats()`](Self::set_decay_beats).
+    pub fn decay_beats(self: &Self) -> Option<f32> {
+        self.tempo.beats()
+    }
+
+    /// Reports the tempo of the project, in BPM, which a decay given in
+    /// [beats](Self::set_decay_beats) follows.
+    ///
+    /// Call this inside your [`process()`](nih_plug::plugin::Plugin::process)
+    /// function, using the host's transport:
+    ///
+    /// ```
+    /// if let Some(tempo) = context.transport().tempo {
+    ///     self.peak_buffer.lock().unwrap().set_tempo(tempo);
+    /// }
+    /// ```
+    ///
+    /// The decay is only recomputed once the tempo moves by more than 0.01 BPM, so
+    /// reporting the same tempo every block costs next to nothing. Tempos that aren't
+    /// positive are ignored.
+    pub fn set_tempo(self: &mut Self, bpm: f64) {
+        if let Some(decay) = self.tempo.set_tempo(bpm) {
+            self.decay = decay;
+            self.decay_weight = decay_weight_per_slot(self.decay, self.slot_duration());
+        }
+    }
+
+    /// Reports the rate at which the host plays the project back, e.g. `0.5` while it
+    /// auditions at half speed, so that the buffer keeps spanning its duration of
+    /// what's heard.
+    ///
+    /// At a rate of 0.5, each second that's heard spans twice as many samples, so
+    /// each element does as well - an event that's heard for 2 seconds takes up 2
+    /// seconds of the buffer, rather than 4. Until this is called, the rate is 1 and
+    /// elements span the samples the sample rate and duration describe.
+    ///
+    /// Call this inside your [`process()`](nih_plug::plugin::Plugin::process)
+    /// function, with the rate your host reports. Unlike a change of the sample rate,
+    /// this doesn't clear the buffer - the element that is being accumulated keeps its
+    /// progress, and only the elements after it span a different number of samples.
+    /// The layout is only re-derived once the rate moves by more than 0.001, so
+    /// reporting the same rate every block costs next to nothing. Rates that aren't
+    /// positive are ignored.
+    pub fn set_playback_rate(self: &mut Self, rate: f32) {
+        if self.playback.set(rate) {
+            let sample_delta =
+                Self::sample_delta(self.buffer.len(), self.samples_per_second(), self.duration);
+            self.t = retime(self.t, self.sample_delta, sample_delta);
+            self.sample_delta = sample_delta;
+            self.decay_weight = decay_weight_per_slot(self.decay, self.slot_duration());
+        }
+    }
+
+    /// Returns the playback rate that was reported last; See
+    /// [`set_playback_rate()`](Self::set_playback_rate).
+    pub fn playback_rate(self: &Self) -> f32 {
+        self.playback.rate()
+    }
+
+    /// Sets the channels that are read from a [`Buffer`](nih_plug::buffer::Buffer)
+    /// that's enqueued without a channel, e.g. to switch a meter between the mid and
+    /// side signals.
+    ///
+    /// The buffer switches to the source on the next block it's fed. From the editor,
+    /// use a [`source_selector()`](Self::source_selector) instead, which doesn't
+    /// need to lock the buffer. Blocks that are enqueued with a channel, or sample by
+    /// sample, aren't affected.
+    pub fn set_source(self: &mut Self, source: ChannelSource) {
+        self.routing.selector().set(source);
+    }
+
+    /// Returns a handle that selects the source of the `PeakBuffer` without locking
+    /// it; See [`set_source()`](Self::set_source).
+    ///
+    /// ```
+    /// // Inside the editor's model
+    /// let selector = peak_buffer.lock().unwrap().source_selector();
+    ///
+    /// // When the user picks a source
+    /// selector.set(ChannelSource::Side);
+    /// ```
+    pub fn source_selector(self: &Self) -> SourceSelector {
+        self.routing.selector()
+    }
+
+    /// Sets whether the `PeakBuffer` is cleared when its source changes, which it is
+    /// by default.
+    ///
+    /// When it isn't, the history of the previous source scrolls away as the new one
+    /// is enqueued.
+    pub fn set_clear_on_source_change(self: &mut Self, clear: bool) {
+        self.routing.set_clear_on_change(clear);
+    }
+
+    /// Sets how the samples inside each element are combined.
+    ///
+    /// This function **clears** the buffer.
+    pub fn set_aggregation(self: &mut Self, aggregation: Aggregation) {
+        self.aggregation = aggregation;
+        self.clear();
     }
 
     /// Sets the sample rate of the incoming audio.
@@ -85,118 +478,1780 @@ impl PeakBuffer {
     /// }
     /// ```
     pub fn set_sample_rate(self: &mut Self, sample_rate: f32) {
+        self.processed = rescale_samples(self.processed, self.sample_rate, sample_rate);
         self.sample_rate = sample_rate;
         self.update();
-        self.buffer.clear();
+        self.clear_elements();
     }
 
-    /// Sets the duration (in seconds) of the audio data inside the buffer.
+    /// Hints whether the plug-in is rendering offline.
     ///
-    /// This function **clears** the buffer.
-    pub fn set_duration(self: &mut Self, duration: f32) {
-        self.duration = duration;
-        self.update();
-        self.buffer.clear();
+    /// While offline, enqueueing does nothing - the editor isn't visible during an
+    /// offline render, so there's no point in spending time on the buffer. Set this
+    /// inside your [`initialize()`](nih_plug::plugin::Plugin::initialize) function,
+    /// along with the sample rate:
+    ///
+    /// ```
+    /// buffer.set_sample_rate(buffer_config.sample_rate);
+    /// buffer.set_offline(buffer_config.process_mode == ProcessMode::Offline);
+    /// ```
+    pub fn set_offline(self: &mut Self, offline: bool) {
+        self.offline = offline;
     }
 
-    fn sample_delta(size: usize, sample_rate: f32, duration: f32) -> f32 {
-        ((sample_rate as f64 * duration as f64) / size as f64) as f32
+    /// Attaches a [`BufferTap`], which mirrors the buffer's samples or elements to a
+    /// background thread, or detaches it by passing `None`.
+    ///
+    /// Returns the tap that was attached before, if any, so that it can be dropped
+    /// outside of the audio thread.
+    pub fn set_tap(self: &mut Self, tap: Option<BufferTap>) -> Option<BufferTap> {
+        self.tap.set(tap)
     }
 
-    fn decay_weight(decay: f32, size: usize, duration: f32) -> f32 {
-        0.25f64.powf((decay as f64 / 1000. * (size as f64 / duration as f64)).recip()) as f32
+    /// Subscribes to the elements the buffer finalizes, for renderers of your own
+    /// that don't use any of Cyma's views.
+    ///
+    /// Each receiver gets every element that is finalized after it subscribed,
+    /// unless it falls too far behind - see [`SlotReceiver`]. Any number of
+    /// receivers can subscribe at once, and dropping one doesn't affect the buffer.
+    ///
+    /// Like the [tap](Self::set_tap), the receivers only see the elements that are
+    /// enqueued from the audio, not the ones [`catch_up()`](Self::catch_up)
+    /// finalizes, and cloning the buffer doesn't clone its subscriptions.
+    pub fn subscribe(self: &Self) -> SlotReceiver {
+        self.subscribers.subscribe()
     }
 
-    fn update(self: &mut Self) {
-        self.decay_weight = Self::decay_weight(self.decay, self.buffer.len(), self.duration);
-        self.sample_delta = Self::sample_delta(self.buffer.len(), self.sample_rate, self.duration);
-        self.t = self.sample_delta;
+    /// Attaches a [`Diagnostics`] handle, which counts the samples that the buffer
+    /// skips while it's offline or not capturing.
+    pub fn set_diagnostics(self: &mut Self, diagnostics: Diagnostics) {
+        self.diagnostics = diagnostics;
     }
-}
 
-impl VisualizerBuffer<f32> for PeakBuffer {
-    fn enqueue(self: &mut Self, value: f32) {
-        let value = value.abs();
-        self.t -= 1.0;
-        if self.t < 0.0 {
-            let last_peak = self.buffer.peek();
-            let mut peak = self.max_acc;
+    /// Sets whether the `PeakBuffer` scrolls or captures takes; See [`BufferMode`].
+    ///
+    /// ```
+    /// let capture_buffer = PeakBuffer::new(800, 0.5, 0.0).with_mode(BufferMode::Capture);
+    /// ```
+    pub fn with_mode(mut self, mode: BufferMode) -> Self {
+        self.set_mode(mode);
+        self
+    }
 
-            // If the current peak is greater than the last one, we immediately enqueue it. If it's less than
-            // the last one, we weigh the previous into the current one, analogous to how peak meters work.
-            self.buffer.enqueue(if peak >= last_peak {
-                peak
-            } else {
-                (last_peak * self.decay_weight) + (peak * (1.0 - self.decay_weight))
-            });
+    /// Sets whether the `PeakBuffer` scrolls or captures takes; See [`BufferMode`].
+    ///
+    /// A buffer that is switched to capturing ignores all samples until it is armed.
+    pub fn set_mode(self: &mut Self, mode: BufferMode) {
+        self.capture.set_mode(mode);
+    }
 
-            self.t += self.sample_delta;
-            self.max_acc = 0.;
-        }
-        if value > self.max_acc {
-            self.max_acc = value
+    /// Returns whether the `PeakBuffer` scrolls or captures takes.
+    pub fn mode(self: &Self) -> BufferMode {
+        self.capture.mode()
+    }
+
+    /// Clears the buffer and starts capturing a take, which ends once the buffer is
+    /// full.
+    ///
+    /// This only has an effect in [`BufferMode::Capture`].
+    pub fn arm(self: &mut Self) {
+        self.clear();
+        self.capture.arm(self.buffer.len());
+    }
+
+    /// Clears the buffer and starts capturing a take once a sample reaches the
+    /// threshold.
+    ///
+    /// * `threshold` - The absolute value of a sample that triggers the take, e.g. 1.0 for a clip
+    /// * `pre_trigger` - The fraction of the buffer that precedes the trigger, from 0 to 1
+    ///
+    /// Until the trigger, the buffer keeps scrolling, so that the take includes what
+    /// led up to it. Afterwards, it fills the rest of the buffer and freezes. This only
+    /// has an effect in [`BufferMode::Capture`].
+    ///
+    /// ```
+    /// // Centers the take around the first clip
+    /// buffer.arm_on_threshold(1.0, 0.5);
+    /// ```
+    pub fn arm_on_threshold(self: &mut Self, threshold: f32, pre_trigger: f32) {
+        self.clear();
+        self.capture
+            .arm_on_threshold(self.buffer.len(), threshold, pre_trigger);
+    }
+
+    /// Returns `true` once a take has been captured, until the buffer is armed again.
+    pub fn is_complete(self: &Self) -> bool {
+        self.capture.is_complete()
+    }
+
+    /// Returns the number of elements enqueued since the buffer was last armed.
+    ///
+    /// This includes the elements that scrolled by while waiting for a trigger.
+    pub fn fill_count(self: &Self) -> usize {
+        self.capture.fill_count()
+    }
+
+    /// Tracks whether the host's transport is playing, so that a
+    /// [`Graph`](crate::visualizers::Graph) can shade the elements during which it
+    /// was stopped; See
+    /// [`with_transport_shading()`](crate::visualizers::GraphModifiers::with_transport_shading).
+    ///
+    /// Without this, the buffer ignores the transport entirely.
+    pub fn with_transport_tracking(mut self) -> Self {
+        self.transport = Some(TransportFlags::new(self.buffer.len()));
+        self
+    }
+
+    /// Sets whether the host's transport is playing.
+    ///
+    /// Call this once per block, before enqueueing it. A transition is resolved to
+    /// the nearest element - an element counts as stopped if the transport was
+    /// stopped halfway through it. This does nothing unless the buffer was
+    /// constructed [with transport tracking](Self::with_transport_tracking), or
+    /// [with a heartbeat](Self::with_heartbeat).
+    ///
+    /// ```
+    /// fn process(
+    ///     &mut self,
+    ///     buffer: &mut Buffer,
+    ///     _: &mut AuxiliaryBuffers,
+    ///     context: &mut impl ProcessContext<Self>,
+    /// ) -> ProcessStatus {
+    ///     let mut peak_buffer = self.peak_buffer.lock().unwrap();
+    ///     peak_buffer.set_transport_playing(context.transport().playing);
+    ///     peak_buffer.enqueue_buffer(buffer, None);
+    ///     ProcessStatus::Normal
+    /// }
+    /// ```
+    pub fn set_transport_playing(self: &mut Self, playing: bool) {
+        self.transport_stopped = !playing;
+        if let Some(transport) = &mut self.transport {
+            transport.set_playing(playing, slot_progress(self.t, self.sample_delta));
         }
     }
 
-    fn enqueue_buffer(
-        self: &mut Self,
-        buffer: &mut nih_plug::buffer::Buffer,
-        channel: Option<usize>,
-    ) {
-        match channel {
-            Some(channel) => {
-                for sample in buffer.as_slice()[channel].into_iter() {
-                    self.enqueue(*sample);
-                }
-            }
-            None => {
-                for sample in buffer.iter_samples() {
-                    self.enqueue(
-                        (1. / (&sample).len() as f32) * sample.into_iter().map(|x| *x).sum::<f32>(),
-                    );
-                }
-            }
+    /// Tracks the tag that is set during each element, so that a
+    /// [`Graph`](crate::visualizers::Graph) can show where it changed; See
+    /// [`with_tag_tints()`](crate::visualizers::GraphModifiers::with_tag_tints).
+    ///
+    /// Without this, the buffer ignores tags entirely. The tags take a byte per
+    /// element.
+    pub fn with_tag_tracking(mut self) -> Self {
+        self.tags = Some(SlotTags::new(self.buffer.len()));
+        self
+    }
+
+    /// Sets the tag that the following elements are stamped with, e.g. whether the
+    /// plug-in is bypassed, or the value of any enum parameter.
+    ///
+    /// Call this once per block, before enqueueing it. Like the transport, a change
+    /// is resolved to the nearest element - an element is stamped with the tag that
+    /// was set halfway through it. This does nothing unless the buffer was
+    /// constructed [with tag tracking](Self::with_tag_tracking).
+    ///
+    /// ```
+    /// fn process(
+    ///     &mut self,
+    ///     buffer: &mut Buffer,
+    ///     _: &mut AuxiliaryBuffers,
+    ///     _: &mut impl ProcessContext<Self>,
+    /// ) -> ProcessStatus {
+    ///     let mut peak_buffer = self.peak_buffer.lock().unwrap();
+    ///     peak_buffer.set_tag(self.params.bypass.value() as u8);
+    ///     peak_buffer.enqueue_buffer(buffer, None);
+    ///     ProcessStatus::Normal
+    /// }
+    /// ```
+    pub fn set_tag(self: &mut Self, tag: u8) {
+        if let Some(tags) = &mut self.tags {
+            tags.set_tag(tag, slot_progress(self.t, self.sample_delta));
         }
     }
 
-    fn len(&self) -> usize {
-        self.buffer.len()
+    /// Tracks the mean and the standard deviation of the values inside each element,
+    /// so that a [`Graph`](crate::visualizers::Graph) can shade the band around
+    /// its trace that the values spread over; See
+    /// [`with_deviation_band()`](crate::visualizers::GraphModifiers::with_deviation_band).
+    ///
+    /// This is meant for the [`Aggregation::Mean`], whose elements are the means
+    /// the band is centered on. Without this, the buffer doesn't compute the
+    /// deviation at all. The deviations take 8 bytes per element, and every sample
+    /// is looked at on its own, even while the buffer takes the plain maximum.
+    ///
+    /// ```
+    /// let loudness_buffer = PeakBuffer::with_aggregation(800, 10.0, 0.0, Aggregation::Mean)
+    ///     .with_deviation_tracking();
+    /// ```
+    pub fn with_deviation_tracking(mut self) -> Self {
+        self.deviation = Some(SlotDeviation::new(self.buffer.len()));
+        self
     }
 
-    fn clear(self: &mut Self) {
-        self.buffer.clear();
+    /// Attaches a [`Heartbeat`], which tells the editor whether the buffer is still
+    /// being fed; See
+    /// [`stale_indicator()`](crate::visualizers::StaleIndicatorModifiers::stale_indicator).
+    ///
+    /// Without this, the buffer doesn't keep track of it. Call
+    /// [`set_transport_playing()`](Self::set_transport_playing) once per block, so
+    /// that a stopped transport isn't mistaken for a stalled host.
+    ///
+    /// ```
+    /// let peak_buffer = PeakBuffer::new(800, 10.0, 50.0).with_heartbeat();
+    /// let heartbeat = peak_buffer.heartbeat().unwrap();
+    /// ```
+    pub fn with_heartbeat(mut self) -> Self {
+        self.heartbeat = Some(Heartbeat::new());
+        self
     }
 
-    /// Grows the buffer, **clearing it**.
-    fn grow(self: &mut Self, size: usize) {
-        if self.buffer.len() == size {
-            return;
-        };
-        self.buffer.grow(size);
-        self.update();
-        self.buffer.clear();
+    /// Returns the buffer's [`Heartbeat`], if it was constructed
+    /// [with one](Self::with_heartbeat).
+    pub fn heartbeat(self: &Self) -> Option<Heartbeat> {
+        self.heartbeat.clone()
     }
 
-    /// Shrinks the buffer, **clearing it**.
-    fn shrink(self: &mut Self, size: usize) {
-        if self.buffer.len() == size {
+    /// Latches an overload indicator whenever a sample reaches the threshold, in
+    /// decibels; See [`set_overload_threshold()`](Self::set_overload_threshold).
+    ///
+    /// ```
+    /// let peak_buffer = PeakBuffer::new(800, 10.0, 50.0).with_overload_threshold(0.0);
+    /// ```
+    pub fn with_overload_threshold(mut self, db: f32) -> Self {
+        self.set_overload_threshold(db);
+        self
+    }
+
+    /// Latches an overload indicator whenever a sample reaches the threshold, in
+    /// decibels, like the "OVER" indicator of a broadcast meter.
+    ///
+    /// Each sample is checked before it is aggregated into an element, so even a
+    /// single sample that reaches the threshold latches the indicator. When a block
+    /// is mono-summed, the loudest of its channels is checked instead of the sum.
+    ///
+    /// The latch holds the worst overload until it's [reset](Self::reset_overload),
+    /// or until the [auto-reset](Self::set_overload_auto_reset) time passes without
+    /// another one. Clearing the buffer, or changing its sample rate, keeps the latch.
+    /// Setting the threshold resets it.
+    pub fn set_overload_threshold(self: &mut Self, db: f32) {
+        self.overload.set_threshold(Some(db));
+    }
+
+    /// Returns the overload threshold in decibels, if set.
+    pub fn overload_threshold(self: &Self) -> Option<f32> {
+        self.overload.threshold_db()
+    }
+
+    /// Sets the time after the last overload that the latch clears itself after, in
+    /// seconds, or keeps it latched until it's reset by passing `None`.
+    ///
+    /// The time is counted in processed samples rather than on the wall clock, so it
+    /// only passes while the buffer is being fed. The latch is cleared lazily - once
+    /// the time has passed, [`overload()`](Self::overload) returns `None`.
+    pub fn set_overload_auto_reset(self: &mut Self, seconds: Option<f32>) {
+        self.overload.set_auto_reset(seconds);
+    }
+
+    /// Returns the time after the last overload that the latch clears itself after,
+    /// in seconds, if any.
+    pub fn overload_auto_reset(self: &Self) -> Option<f32> {
+        self.overload.auto_reset()
+    }
+
+    /// Clears the overload latch, e.g. when the user clicks the indicator.
+    pub fn reset_overload(self: &mut Self) {
+        self.overload.reset();
+    }
+
+    /// Returns the worst overload since the latch was last reset, or `None` if it
+    /// isn't latched.
+    ///
+    /// The [`Overload`] can be compared, so views can be bound to it through a lens:
+    ///
+    /// ```
+    /// Element::new(cx)
+    ///     .background_color(Color::red())
+    ///     .visibility(Data::peak_buffer.map(|b| b.lock().unwrap().is_overloaded()));
+    /// ```
+    ///
+    /// The plug-in can read it as well, e.g. to mirror it to a value that the host
+    /// can see.
+    pub fn overload(self: &Self) -> Option<Overload> {
+        self.overload.state(self.processed, self.sample_rate)
+    }
+
+    /// Returns `true` while the overload latch is set; See
+    /// [`overload()`](Self::overload).
+    pub fn is_overloaded(self: &Self) -> bool {
+        self.overload().is_some()
+    }
+
+    /// Returns a coarse summary of the buffer's contents, with at most `max_points`
+    /// values.
+    ///
+    /// Each value of the summary is the maximum of the elements it spans, from the
+    /// oldest to the newest. The summary is small enough to be persisted along with
+    /// the plug-in's parameters, and can be restored when the editor is opened
+    /// again, using [`restore_from_summary()`](Self::restore_from_summary).
+    ///
+    /// ```
+    /// // Inside the plug-in's Params, next to the editor state
+    /// #[persist = "peak-summary"]
+    /// peak_summary: Mutex<Vec<f32>>,
+    ///
+    /// // Whenever the editor closes
+    /// *params.peak_summary.lock().unwrap() = peak_buffer.lock().unwrap().summarize(64);
+    ///
+    /// // Whenever the editor opens
+    /// peak_buffer.lock().unwrap().restore_from_summary(&params.peak_summary.lock().unwrap());
+    /// ```
+    pub fn summarize(self: &Self, max_points: usize) -> Vec<f32> {
+        let len = self.buffer.len();
+        let points = max_points.min(len);
+        let mut summary = vec![0.; points];
+        if points == 0 {
+            return summary;
+        }
+        for i in 0..len {
+            let point = &mut summary[summary_index(i, len, points)];
+            *point = point.max(self.buffer[i]);
+        }
+        summary
+    }
+
+    /// Fills the buffer with a summary returned by [`summarize()`](Self::summarize),
+    /// stretched across all elements.
+    ///
+    /// The restored elements are a low resolution backdrop, which scrolls out as new
+    /// elements are enqueued. Until then, they are flagged as restored, and a
+    /// [`Graph`](crate::visualizers::Graph) draws them dimmed. An empty summary
+    /// leaves the buffer as it is.
+    pub fn restore_from_summary(self: &mut Self, summary: &[f32]) {
+        if summary.is_empty() {
             return;
-        };
-        self.buffer.shrink(size);
-        self.update();
-        self.buffer.clear();
+        }
+        let len = self.buffer.len();
+        for i in 0..len {
+            self.buffer[i] = summary[summary_index(i, len, summary.len())];
+        }
+        self.restored = len;
     }
-}
 
-impl Index<usize> for PeakBuffer {
-    type Output = f32;
+    // Clears the elements, along with what is tracked alongside them
+    fn clear_elements(self: &mut Self) {
+        self.buffer.clear();
+        self.restored = 0;
+        if let Some(transport) = &mut self.transport {
+            transport.reset(self.buffer.len());
+        }
+        if let Some(tags) = &mut self.tags {
+            tags.reset(self.buffer.len());
+        }
+        if let Some(deviation) = &mut self.deviation {
+            deviation.reset(self.buffer.len());
+        }
+    }
 
-    fn index(&self, index: usize) -> &Self::Output {
-        self.buffer.index(index)
+    pub(crate) fn is_offline(self: &Self) -> bool {
+        self.offline
     }
-}
-impl IndexMut<usize> for PeakBuffer {
-    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        self.buffer.index_mut(index)
+
+    /// Sets the duration (in seconds) of the audio data inside the buffer.
+    ///
+    /// This function **clears** the buffer. A duration that isn't positive and finite
+    /// fails a debug assertion, and is clamped in release builds; Use
+    /// [`try_set_duration()`](Self::try_set_duration) to handle it instead.
+    ///
+    /// A buffer that keeps a [density](Self::with_density) is resized to it.
+    pub fn set_duration(self: &mut Self, duration: f32) {
+        self.duration = clamp_duration("peak buffer", "duration", duration);
+        if let Some(density) = self.density {
+            self.buffer.resize(density_size(density, self.duration));
+        }
+        self.update();
+        self.clear_elements();
+    }
+
+    /// Sets the duration (in seconds) of the audio data inside the buffer, or returns
+    /// a [`ConfigError`] if it isn't positive and finite.
+    ///
+    /// This function **clears** the buffer.
+    pub fn try_set_duration(self: &mut Self, duration: f32) -> Result<(), ConfigError> {
+        self.set_duration(check_duration("peak buffer", "duration", duration)?);
+        Ok(())
+    }
+
+    /// Sets the duration (in seconds) of the audio data inside the buffer, keeping
+    /// its history.
+    ///
+    /// Unlike [`set_duration()`](Self::set_duration), the elements are spread across
+    /// the new duration, so the history doesn't disappear when the user zooms a
+    /// graph in or out:
+    ///
+    /// * A longer duration merges groups of elements, taking their maximum - or their
+    ///   mean, for [`Aggregation::Mean`]. The older part of the new duration, which
+    ///   the buffer holds no history for yet, is empty.
+    /// * A shorter duration stretches the newest elements across the buffer, at the
+    ///   resolution they were recorded at; It sharpens as new elements come in.
+    ///
+    /// A buffer that keeps a [density](Self::with_density) is resized instead, so its
+    /// elements keep their spacing: A longer duration adds empty elements in the
+    /// past, and a shorter one drops the oldest elements.
+    ///
+    /// The element that is being accumulated carries on if it's still shorter than
+    /// the new elements, and restarts otherwise. Restored elements, the transport and
+    /// the tags are reset. This allocates, so call it from the editor rather than the
+    /// audio thread.
+    pub fn rescale_duration(self: &mut Self, duration: f32) {
+        let duration = clamp_duration("peak buffer", "duration", duration);
+        let len = match self.density {
+            Some(density) => {
+                let len = density_size(density, duration);
+                self.buffer.resize(len);
+                len
+            }
+            None => {
+                let merge: fn(&[f32]) -> f32 = match self.aggregation {
+                    Aggregation::Mean => {
+                        |elements| elements.iter().sum::<f32>() / elements.len() as f32
+                    }
+                    _ => |elements| elements.iter().copied().fold(0., f32::max),
+                };
+                let len = self.buffer.len();
+                rescale_elements(&mut self.buffer, len, self.duration, duration, merge);
+                len
+            }
+        };
+        self.restored = 0;
+        if let Some(transport) = &mut self.transport {
+            transport.reset(len);
+        }
+        if let Some(tags) = &mut self.tags {
+            tags.reset(len);
+        }
+        if let Some(deviation) = &mut self.deviation {
+            deviation.reset(len);
+        }
+
+        let accumulated = self.sample_delta - self.t;
+        self.duration = duration;
+        self.sample_delta = Self::sample_delta(len, self.samples_per_second(), duration);
+        self.decay_weight = decay_weight_per_slot(self.decay, self.slot_duration());
+        if accumulated < self.sample_delta {
+            self.t = self.sample_delta - accumulated;
+        } else {
+            self.t = self.sample_delta;
+            self.reset_accumulators();
+        }
+    }
+
+    fn sample_delta(size: usize, sample_rate: f32, duration: f32) -> f32 {
+        ((sample_rate as f64 * duration as f64) / size as f64) as f32
+    }
+
+    // The number of samples for each second of the duration
+    fn samples_per_second(self: &Self) -> f32 {
+        self.playback.samples_per_second(self.sample_rate)
+    }
+
+    // The time each element spans, in seconds
+    fn slot_duration(self: &Self) -> f64 {
+        if self.sample_rate > 0. {
+            self.sample_delta as f64 / self.samples_per_second() as f64
+        } else {
+            self.duration as f64 / self.buffer.len() as f64
+        }
+    }
+
+    // Restarts the element that is being accumulated, since its length changed
+    fn update(self: &mut Self) {
+        self.sample_delta =
+            Self::sample_delta(self.buffer.len(), self.samples_per_second(), self.duration);
+        self.decay_weight = decay_weight_per_slot(self.decay, self.slot_duration());
+        self.t = self.sample_delta;
+        self.reset_accumulators();
+        if let Some(preset) = self.ballistics {
+            self.detector = Detector::new(preset, self.sample_rate);
+        }
+    }
+
+    /// Enqueues a single sample, returning the value of the element that it
+    /// finalized, if any.
+    pub(crate) fn enqueue_slot(self: &mut Self, value: f32) -> Option<f32> {
+        if self.offline || !self.capture.accepts() {
+            self.diagnostics.skipped(1);
+            return None;
+        }
+        self.tap.push(TapMode::Samples, value);
+        self.overload
+            .observe(value.abs(), self.processed, self.sample_rate);
+        let sample = value;
+        let value = match self.ballistics {
+            Some(_) => self.detector.process(value.abs()),
+            None => value.abs(),
+        };
+        let mut finalized = None;
+        self.processed += 1;
+        self.t -= 1.0;
+        if self.t < 0.0 {
+            // Before the accumulators are reset along with the aggregate
+            if let Some(deviation) = &mut self.deviation {
+                deviation.enqueued();
+            }
+            let peak = self.take_aggregate();
+
+            let slot = match self.aggregation {
+                // The last peak decays by the same weight whether or not the current peak
+                // rises above it, analogous to how peak meters work. This way, each
+                // element falls by a fixed number of decibels per second, regardless of
+                // how many elements there are.
+                Aggregation::Max => peak.max(self.buffer.peek() * self.decay_weight),
+                // Averages and percentiles already smooth out the signal, so they aren't decayed
+                _ => peak,
+            };
+            self.buffer.enqueue(slot);
+            self.restored = self.restored.saturating_sub(1);
+            self.capture.enqueued();
+            if let Some(transport) = &mut self.transport {
+                transport.enqueued();
+            }
+            if let Some(tags) = &mut self.tags {
+                tags.enqueued();
+            }
+            self.tap.push(TapMode::Slots, slot);
+            self.subscribers.publish(slot);
+            if let Some(heartbeat) = &self.heartbeat {
+                heartbeat.beat(self.processed, !self.transport_stopped);
+            }
+            finalized = Some(slot);
+
+            self.t += self.sample_delta;
+        }
+        self.accumulate(value);
+        self.capture.observe(sample);
+        finalized
+    }
+
+    /// Enqueues a slice of samples, just like enqueueing each of them in turn.
+    ///
+    /// While the buffer takes the plain maximum, the samples that fall inside the
+    /// element that is being accumulated are scanned in chunks, which the compiler
+    /// vectorizes. This makes it considerably faster than enqueueing the samples one
+    /// by one when each element spans many samples.
+    pub fn enqueue_slice(self: &mut Self, samples: &[f32]) {
+        if !self.scans_spans() {
+            for sample in samples {
+                self.enqueue_slot(*sample);
+            }
+            return;
+        }
+
+        let mut rest = samples;
+        while !rest.is_empty() {
+            // The samples before the one that finalizes the current element. Counting
+            // `t` down by a whole span is exact below 2^24, so the element is
+            // finalized at exactly the same sample as when counting down one by one.
+            let span = if self.t >= 1.0 && self.t < EXACT_COUNTDOWN {
+                (self.t as usize).min(rest.len())
+            } else {
+                0
+            };
+            let (span, after) = rest.split_at(span);
+            self.max_acc = abs_max(span, self.max_acc);
+            self.overload
+                .observe_slice(span, self.processed, self.sample_rate);
+            self.t -= span.len() as f32;
+            self.processed += span.len() as u64;
+
+            match after.split_first() {
+                Some((sample, after)) => {
+                    self.enqueue_slot(*sample);
+                    rest = after;
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Catches up on samples that were never enqueued, as if they had been silent.
+    ///
+    /// Plug-ins usually only enqueue while the editor is open, so the buffer stands
+    /// still while it's closed, and reopening it shows levels from minutes ago. Count
+    /// the samples while the editor is closed, and catch up on them before the next
+    /// block is enqueued:
+    ///
+    /// ```
+    /// fn process(
+    ///     &mut self,
+    ///     buffer: &mut Buffer,
+    ///     _: &mut AuxiliaryBuffers,
+    ///     _: &mut impl ProcessContext<Self>,
+    /// ) -> ProcessStatus {
+    ///     if self.params.editor_state.is_open() {
+    ///         let mut peak_buffer = self.peak_buffer.lock().unwrap();
+    ///         peak_buffer.catch_up(std::mem::take(&mut self.closed_samples));
+    ///         peak_buffer.enqueue_buffer(buffer, None);
+    ///     } else {
+    ///         self.closed_samples += buffer.samples() as u64;
+    ///     }
+    ///     ProcessStatus::Normal
+    /// }
+    /// ```
+    ///
+    /// The elements that the gap spans are enqueued as they would have been, each
+    /// decaying from the one before it, so the history looks as if the buffer had
+    /// been running the whole time. No matter how long the gap is, this takes at most
+    /// one pass over the buffer and doesn't allocate, so it's safe to call on the
+    /// audio thread.
+    ///
+    /// Elements that are only tracked from the audio - the tap, the subscriptions, the
+    /// heartbeat and the overload latch - don't see the silence, though an overload still expires
+    /// after the gap. A buffer in [`BufferMode::Capture`] holds its take, so it's
+    /// left as it is, and so is an offline buffer.
+    pub fn catch_up(self: &mut Self, elapsed_samples: u64) {
+        if self.offline || self.capture.mode() != BufferMode::Rolling || elapsed_samples == 0 {
+            return;
+        }
+        self.processed += elapsed_samples;
+        let t = self.t as f64;
+        let elapsed = elapsed_samples as f64;
+        if elapsed <= t {
+            // The gap ends inside the element that is being accumulated
+            if self.ballistics.is_some() && self.aggregation == Aggregation::Max {
+                self.max_acc = self.max_acc.max(self.detector.level_after(1));
+            }
+            self.accumulate_silence(elapsed_samples);
+            self.detector.release(elapsed_samples);
+            self.t = (t - elapsed) as f32;
+            return;
+        }
+
+        // Just like `enqueue_slot()` counts down, the element that is being
+        // accumulated takes the silence up to the sample that finalizes it, which
+        // then starts the next element
+        let aggregation = self.aggregation;
+        let delta = self.sample_delta as f64;
+        let pending = t.max(0.).floor() as u64;
+        let finalized = ((elapsed - t) / delta).ceil() as u64;
+        // The number of silent samples before the element, counted from the start
+        // of the gap
+        let start = |element: u64| pending + ((element - 1) as f64 * delta).round() as u64;
+        // With ballistics, the silence keeps releasing the detector
+        let ballistic = self.ballistics.is_some() && aggregation == Aggregation::Max;
+        let aggregate = |buffer: &Self, element: u64| {
+            if ballistic {
+                buffer.detector.level_after(start(element) + 1)
+            } else {
+                0.
+            }
+        };
+        let decay = |slot: f32, aggregate: f32, weight: f32| match aggregation {
+            Aggregation::Max => aggregate.max(slot * weight),
+            _ => aggregate,
+        };
+
+        if ballistic && pending > 0 {
+            self.max_acc = self.max_acc.max(self.detector.level_after(1));
+        }
+        self.accumulate_silence(pending);
+        // Only the newest elements of the gap are still inside the buffer
+        let len = self.buffer.len() as u64;
+        let first = finalized.saturating_sub(len);
+        if first == 0 {
+            if let Some(deviation) = &mut self.deviation {
+                deviation.enqueued();
+            }
+        }
+        let peak = self.take_aggregate();
+        let mut slot = decay(self.buffer.peek(), peak, self.decay_weight);
+        if first == 0 {
+            self.enqueue_silent_slot(slot);
+        } else if aggregation == Aggregation::Max {
+            // The elements that would have scrolled out only pass on their decay
+            slot *= (self.decay_weight as f64).powf((first - 1) as f64) as f32;
+        }
+        for element in first.max(1)..finalized {
+            slot = decay(slot, aggregate(self, element), self.decay_weight);
+            if let Some(deviation) = &mut self.deviation {
+                deviation.enqueued();
+            }
+            self.enqueue_silent_slot(slot);
+        }
+
+        // The silence after the last finalized element, including the sample that
+        // finalized it
+        let rest = elapsed_samples - start(finalized);
+        if ballistic {
+            self.max_acc = aggregate(self, finalized);
+        }
+        self.accumulate_silence(rest);
+        self.detector.release(elapsed_samples);
+        self.t = (t - elapsed + finalized as f64 * delta) as f32;
+    }
+
+    // Enqueues an element that `catch_up()` finalized, along with what is tracked
+    // alongside it
+    fn enqueue_silent_slot(self: &mut Self, slot: f32) {
+        self.buffer.enqueue(slot);
+        self.restored = self.restored.saturating_sub(1);
+        if let Some(transport) = &mut self.transport {
+            transport.enqueued();
+        }
+        if let Some(tags) = &mut self.tags {
+            tags.enqueued();
+        }
+    }
+
+    // Whether the samples inside an element only affect the maximum accumulator, so
+    // that they can be scanned in one go
+    fn scans_spans(self: &Self) -> bool {
+        !self.offline
+            && self.aggregation == Aggregation::Max
+            && self.ballistics.is_none()
+            && self.capture.mode() == BufferMode::Rolling
+            && !self.tap.pushes(TapMode::Samples)
+            && self.deviation.is_none()
+    }
+
+    fn accumulate(self: &mut Self, value: f32) {
+        if let Some(deviation) = &mut self.deviation {
+            deviation.observe(value);
+        }
+        match self.aggregation {
+            Aggregation::Max => {
+                if value > self.max_acc {
+                    self.max_acc = value
+                }
+            }
+            Aggregation::Mean => {
+                self.sum_acc += value;
+                self.count += 1;
+            }
+            Aggregation::AbsPercentile(_) => self.sample_reservoir(value),
+        }
+    }
+
+    // Adds silence to the element that is being accumulated, as if the samples had
+    // been enqueued one by one
+    fn accumulate_silence(self: &mut Self, samples: u64) {
+        if samples == 0 {
+            return;
+        }
+        if let Some(deviation) = &mut self.deviation {
+            deviation.observe_silence(samples);
+        }
+        match self.aggregation {
+            // Silence doesn't raise the maximum
+            Aggregation::Max => {}
+            Aggregation::Mean => {
+                self.count = self
+                    .count
+                    .saturating_add(samples.min(u32::MAX as u64) as u32)
+            }
+            // Once the reservoir is full, more silence only displaces its values
+            // with a probability that the percentile is approximate about anyway
+            Aggregation::AbsPercentile(_) => {
+                for _ in 0..samples.min(RESERVOIR_SIZE as u64) {
+                    self.sample_reservoir(0.);
+                }
+            }
+        }
+    }
+
+    // Reservoir sampling: the first values fill the reservoir, after which each value
+    // replaces a random one with decreasing probability
+    fn sample_reservoir(self: &mut Self, value: f32) {
+        if (self.count as usize) < RESERVOIR_SIZE {
+            self.reservoir[self.count as usize] = value;
+        } else {
+            let j = self.next_random() as usize % (self.count as usize + 1);
+            if j < RESERVOIR_SIZE {
+                self.reservoir[j] = value;
+            }
+        }
+        self.count += 1;
+    }
+
+    /// Returns the aggregate of the current window and resets the accumulators.
+    fn take_aggregate(self: &mut Self) -> f32 {
+        let aggregate = match self.aggregation {
+            Aggregation::Max => self.max_acc,
+            Aggregation::Mean => {
+                if self.count > 0 {
+                    self.sum_acc / self.count as f32
+                } else {
+                    0.
+                }
+            }
+            Aggregation::AbsPercentile(percentile) => {
+                let values = &mut self.reservoir[..(self.count as usize).min(RESERVOIR_SIZE)];
+                if values.is_empty() {
+                    0.
+                } else {
+                    values.sort_unstable_by(f32::total_cmp);
+                    let rank = (percentile.clamp(0., 100.) / 100. * (values.len() - 1) as f32)
+                        .round() as usize;
+                    values[rank]
+                }
+            }
+        };
+        self.reset_accumulators();
+        aggregate
+    }
+
+    fn reset_accumulators(self: &mut Self) {
+        self.max_acc = 0.;
+        self.sum_acc = 0.;
+        self.count = 0;
+        if let Some(deviation) = &mut self.deviation {
+            deviation.reset_window();
+        }
+    }
+
+    // xorshift32, which is plenty for picking reservoir slots
+    fn next_random(self: &mut Self) -> u32 {
+        // A zero state would only ever produce zeros
+        let mut x = if self.rng_state == 0 {
+            RNG_SEED
+        } else {
+            self.rng_state
+        };
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.rng_state = x;
+        x
+    }
+}
+
+// The point of a summary with `points` values that the element at the index falls into
+fn summary_index(index: usize, len: usize, points: usize) -> usize {
+    index * points / len
+}
+
+/// Returns the larger of `max` and the absolute values of the samples.
+///
+/// The samples are compared in the same way as one by one, so the result is the
+/// same - the chunks only let the compiler compare several of them at once.
+fn abs_max(samples: &[f32], max: f32) -> f32 {
+    const LANES: usize = 8;
+
+    let chunks = samples.chunks_exact(LANES);
+    let remainder = chunks.remainder();
+    let mut lanes = [max; LANES];
+    for chunk in chunks {
+        for (lane, sample) in lanes.iter_mut().zip(chunk) {
+            // A select rather than a branch, which compiles to a single `maxps` per
+            // four lanes on x86. `f32::max()` would also vectorize, but its handling of
+            // NaN takes three more instructions per four lanes.
+            let value = sample.abs();
+            *lane = if value > *lane { value } else { *lane };
+        }
+    }
+
+    lanes
+        .into_iter()
+        .chain(remainder.iter().map(|sample| sample.abs()))
+        .fold(max, |max, value| if value > max { value } else { max })
+}
+
+// Above this, counting down by a whole span can round differently than counting
+// down one sample at a time
+const EXACT_COUNTDOWN: f32 = 16_777_216.0;
+
+const RESERVOIR_SIZE: usize = 32;
+const RNG_SEED: u32 = 0x9e37_79b9;
+
+/// Determines how the samples inside each element of a [`PeakBuffer`] are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Aggregation {
+    /// The maximum of the absolute values, i.e. the peak.
+    ///
+    /// This is the only mode that the buffer's decay is applied to.
+    #[default]
+    Max,
+    /// The mean of the absolute values.
+    Mean,
+    /// A percentile of the absolute values, from 0 to 100.
+    ///
+    /// This is **approximate** - up to 32 values per element are sampled at random,
+    /// and the percentile is taken from those. If an element spans 32 samples or
+    /// less, the result is exact.
+    AbsPercentile(f32),
+}
+
+impl VisualizerBuffer<f32> for PeakBuffer {
+    fn enqueue(self: &mut Self, value: f32) {
+        self.enqueue_slot(value);
+    }
+
+    fn enqueue_buffer(
+        self: &mut Self,
+        buffer: &mut nih_plug::buffer::Buffer,
+        channel: Option<usize>,
+    ) {
+        if self.offline {
+            self.diagnostics.skipped(buffer.samples());
+            return;
+        }
+        match channel {
+            Some(channel) => self.enqueue_slice(&buffer.as_slice()[channel]),
+            None => {
+                let (source, changed) = self.routing.update();
+                if changed {
+                    self.clear();
+                }
+                match source {
+                    ChannelSource::DownmixAvg => {
+                        for mut sample in buffer.iter_samples() {
+                            if self.overload.is_enabled() && self.capture.accepts() {
+                                // The sum can cancel out a channel that overloads on its own
+                                let peak = sample.iter_mut().fold(0f32, |max, x| max.max(x.abs()));
+                                self.overload
+                                    .observe(peak, self.processed, self.sample_rate);
+                            }
+                            self.enqueue(
+                                (1. / (&sample).len() as f32)
+                                    * sample.into_iter().map(|x| *x).sum::<f32>(),
+                            );
+                        }
+                    }
+                    ChannelSource::Channel(channel) if channel < buffer.channels() => {
+                        self.enqueue_slice(&buffer.as_slice()[channel])
+                    }
+                    source => for_each_routed(buffer, source, |sample| self.enqueue(sample)),
+                }
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    fn generation(self: &Self) -> Option<u64> {
+        Some(self.buffer.generation())
+    }
+
+    fn enqueued(self: &Self) -> Option<u64> {
+        Some(self.buffer.enqueued())
+    }
+
+    fn sample_clock(self: &Self) -> Option<(u64, f32)> {
+        Some((self.processed, self.sample_rate))
+    }
+
+    fn clears(self: &Self) -> Option<u64> {
+        Some(self.buffer.clears())
+    }
+
+    fn duration(self: &Self) -> Option<f32> {
+        Some(self.duration)
+    }
+
+    /// Returns the element that is currently being accumulated.
+    ///
+    /// For the maximum, the provisional value is the smallest value the element can
+    /// end up with, so it only ever rises towards the final value. Percentiles
+    /// aren't known until the element is finished, so they return `None`.
+    fn partial(self: &Self) -> Option<(f32, f32)> {
+        let progress = slot_progress(self.t, self.sample_delta)?;
+        let value = match self.aggregation {
+            Aggregation::Max => self.max_acc.max(self.buffer.peek() * self.decay_weight),
+            Aggregation::Mean if self.count > 0 => self.sum_acc / self.count as f32,
+            Aggregation::Mean => 0.,
+            Aggregation::AbsPercentile(_) => return None,
+        };
+        Some((progress, value))
+    }
+
+    fn capture_progress(self: &Self) -> Option<f32> {
+        self.capture.progress(self.buffer.len())
+    }
+
+    fn transport_playing(self: &Self, index: usize) -> Option<bool> {
+        self.transport.as_ref()?.is_playing(index)
+    }
+
+    fn tag(self: &Self, index: usize) -> Option<u8> {
+        self.tags.as_ref()?.get(index)
+    }
+
+    fn deviation(self: &Self, index: usize) -> Option<(f32, f32)> {
+        self.deviation.as_ref()?.get(index)
+    }
+
+    fn is_restored(self: &Self, index: usize) -> bool {
+        index < self.restored
+    }
+
+    fn clear(self: &mut Self) {
+        self.clear_elements();
+        self.reset_accumulators();
+        self.detector.reset();
+    }
+
+    /// Grows the buffer, **clearing it**.
+    fn grow(self: &mut Self, size: usize) {
+        if self.buffer.len() == size {
+            return;
+        };
+        self.buffer.grow(size);
+        self.density = None;
+        self.update();
+        self.clear_elements();
+    }
+
+    /// Shrinks the buffer, **clearing it**.
+    fn shrink(self: &mut Self, size: usize) {
+        if self.buffer.len() == size {
+            return;
+        };
+        self.buffer.shrink(size);
+        self.density = None;
+        self.update();
+        self.clear_elements();
+    }
+}
+
+impl Index<usize> for PeakBuffer {
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        check_index("peak buffer", self.name(), index, self.buffer.len());
+        &self.buffer[index]
+    }
+}
+impl IndexMut<usize> for PeakBuffer {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        check_index("peak buffer", self.name(), index, self.buffer.len());
+        &mut self.buffer[index]
+    }
+}
+
+impl SnapshotExport for PeakBuffer {
+    fn export(&self) -> BufferSnapshot {
+        BufferSnapshot {
+            name: self.name().map(str::to_string),
+            duration: Some(self.duration),
+            decay: Some(self.decay),
+            sample_rate: Some(self.sample_rate),
+            ..BufferSnapshot::new(
+                "PeakBuffer",
+                &["value"],
+                (0..self.buffer.len())
+                    .map(|i| vec![self.buffer[i]])
+                    .collect(),
+            )
+        }
+    }
+}
+
+impl Debug for PeakBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PeakBuffer")
+            .field("name", &self.name())
+            .field("size", &self.buffer.len())
+            .field("duration", &self.duration)
+            .field("sample_rate", &self.sample_rate)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "debug-tools")]
+impl super::registry::RegisteredBuffer for PeakBuffer {
+    fn info(&self) -> super::registry::BufferInfo {
+        super::registry::BufferInfo {
+            buffer: "PeakBuffer",
+            name: self.name().map(str::to_string),
+            size: self.buffer.len(),
+            duration: Some(self.duration),
+            sample_rate: Some(self.sample_rate),
+            enqueued: self.buffer.enqueued(),
+        }
+    }
+}
+
+#[cfg(feature = "ffi")]
+impl super::ffi::WriteSnapshot for PeakBuffer {
+    fn write_snapshot(&self, out: &mut Vec<u8>) {
+        super::ffi::SnapshotHeader::new(
+            super::ffi::ValueFormat::F32,
+            self.buffer.len(),
+            Some(self.duration),
+            Some(self.sample_rate),
+        )
+        .write(out, (0..self.buffer.len()).map(|i| self.buffer[i]));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Aggregation, BallisticsPreset, BufferMode, ChannelSource, PeakBuffer};
+    use crate::utils::buffers::test_support::{
+        assert_density_kept, assert_enqueue_buffer_matches_enqueue,
+        assert_interleaved_matches_buffer, assert_large_block_matches_small_blocks,
+        assert_rescale_matches_offline, contents, test_signal, with_buffer,
+    };
+    use crate::utils::VisualizerBuffer;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    /// Feeds random windows into a buffer and returns each window's samples along
+    /// with the value the buffer stored for it.
+    fn random_windows(aggregation: Aggregation, window: usize) -> Vec<(Vec<f32>, f32)> {
+        let size = 16;
+        // No decay, so that maxima can be compared directly
+        let mut buffer = PeakBuffer::with_aggregation(size, 1.0, 0.0, aggregation);
+        buffer.set_sample_rate((size * window) as f32);
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let windows = (0..size)
+            .map(|_| {
+                (0..window)
+                    .map(|_| rng.gen_range(-1.0f32..1.0))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        for sample in windows.iter().flatten() {
+            buffer.enqueue(*sample);
+        }
+        // Finalizes the last window
+        buffer.enqueue(0.0);
+
+        windows
+            .into_iter()
+            .enumerate()
+            .map(|(i, w)| (w.into_iter().map(f32::abs).collect(), buffer[i]))
+            .collect()
+    }
+
+    fn percentile(values: &[f32], percentile: f32) -> f32 {
+        let mut values = values.to_vec();
+        values.sort_unstable_by(f32::total_cmp);
+        values[(percentile / 100. * (values.len() - 1) as f32).round() as usize]
+    }
+
+    #[test]
+    fn aggregation_max() {
+        for (window, value) in random_windows(Aggregation::Max, 50) {
+            assert_eq!(value, window.iter().copied().fold(0., f32::max));
+        }
+    }
+
+    #[test]
+    fn aggregation_mean() {
+        for (window, value) in random_windows(Aggregation::Mean, 50) {
+            let mean = window.iter().sum::<f32>() / window.len() as f32;
+            assert!((value - mean).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn aggregation_percentile() {
+        // Windows that fit inside the reservoir are exact
+        for (window, value) in random_windows(Aggregation::AbsPercentile(95.), 20) {
+            assert_eq!(value, percentile(&window, 95.));
+        }
+
+        // Larger windows are approximate, but stay close to the actual percentile
+        for (window, value) in random_windows(Aggregation::AbsPercentile(50.), 200) {
+            assert!(window.contains(&value));
+            assert!(value >= percentile(&window, 25.) && value <= percentile(&window, 75.));
+        }
+    }
+
+    #[test]
+    fn set_aggregation_clears() {
+        let mut buffer = PeakBuffer::new(10, 1.0, 50.0);
+        buffer.set_sample_rate(100.);
+        for _ in 0..100 {
+            buffer.enqueue(1.0);
+        }
+        assert!((0..buffer.len()).any(|i| buffer[i] > 0.));
+
+        buffer.set_aggregation(Aggregation::Mean);
+        assert!((0..buffer.len()).all(|i| buffer[i] == 0.));
+    }
+
+    #[test]
+    fn silence_advances() {
+        // 100 slots over 10 seconds at 1kHz, so each slot spans 100 samples
+        let mut buffer = PeakBuffer::new(100, 10.0, 50.0);
+        buffer.set_sample_rate(1000.);
+
+        // 5 seconds of silence, followed by a burst that fills one slot
+        for _ in 0..5000 {
+            buffer.enqueue(0.0);
+        }
+        for _ in 0..100 {
+            buffer.enqueue(1.0);
+        }
+        // Another second of silence, which should push the burst back by 10 slots
+        for _ in 0..1000 {
+            buffer.enqueue(0.0);
+        }
+
+        // The burst's slot is finalized by the first silent sample after it, which
+        // is followed by another 9 slots of silence
+        let burst = buffer.len() - 10;
+        assert_eq!(buffer[burst], 1.0);
+        assert!((0..burst).all(|i| buffer[i] == 0.0));
+        assert!((burst + 1..buffer.len()).all(|i| buffer[i] < buffer[i - 1]));
+    }
+
+    #[test]
+    fn summary_round_trip() {
+        // 8 samples per element, no decay
+        let mut buffer = PeakBuffer::new(256, 1.0, 0.0);
+        buffer.set_sample_rate(2048.);
+        for i in 0..2048 {
+            buffer.enqueue((i as f32 / 100.).sin());
+        }
+        let summary = buffer.summarize(64);
+        assert_eq!(summary.len(), 64);
+
+        // A reopened editor at a different resolution keeps the coarse shape
+        for size in [256, 512, 1000] {
+            let mut restored = PeakBuffer::new(size, 1.0, 0.0);
+            restored.restore_from_summary(&summary);
+            assert_eq!(restored.summarize(64), summary);
+        }
+
+        // A summary never has more values than the buffer has elements
+        assert_eq!(PeakBuffer::new(16, 1.0, 0.0).summarize(64).len(), 16);
+    }
+
+    #[test]
+    fn restored_elements_scroll_out() {
+        // 4 samples per element
+        let mut buffer = PeakBuffer::new(8, 1.0, 0.0);
+        buffer.set_sample_rate(32.);
+        buffer.restore_from_summary(&[0.25, 0.5]);
+        assert_eq!(
+            contents(&buffer),
+            vec![0.25, 0.25, 0.25, 0.25, 0.5, 0.5, 0.5, 0.5]
+        );
+        assert!((0..8).all(|i| buffer.is_restored(i)));
+
+        // Each new element overwrites the oldest restored one
+        for _ in 0..13 {
+            buffer.enqueue(1.0);
+        }
+        assert_eq!(
+            (0..8).map(|i| buffer.is_restored(i)).collect::<Vec<_>>(),
+            vec![true, true, true, true, true, false, false, false]
+        );
+        for _ in 0..20 {
+            buffer.enqueue(1.0);
+        }
+        assert!((0..8).all(|i| !buffer.is_restored(i)));
+
+        // Clearing drops the flags as well
+        buffer.restore_from_summary(&[0.5]);
+        buffer.clear();
+        assert!(!buffer.is_restored(0));
+    }
+
+    #[test]
+    fn decay_independent_of_size() {
+        // The same impulse through buffers of the same duration, at 240, 60 and 15
+        // samples per slot
+        let decay = 50.0;
+        let values = [200, 800, 3200].map(|size| {
+            let mut buffer = PeakBuffer::new(size, 1.0, decay);
+            buffer.set_sample_rate(48_000.);
+            buffer.enqueue(1.0);
+            for _ in 0..24_000 {
+                buffer.enqueue(0.0);
+            }
+
+            let impulse = (0..size).find(|i| buffer[*i] == 1.0).unwrap();
+            let slots_per_decay = (decay / 1000. * size as f32).round() as usize;
+            buffer[impulse + slots_per_decay]
+        });
+
+        for value in values {
+            assert!((value - 0.25).abs() < 1e-3, "{:?}", values);
+        }
+    }
+
+    #[test]
+    fn newest_window() {
+        // 10 ms elements, each holding its index
+        let mut buffer = PeakBuffer::new(100, 1.0, 0.0);
+        buffer.set_sample_rate(1000.);
+        let window =
+            |buffer: &PeakBuffer, seconds: f32| buffer.newest_window(seconds).collect::<Vec<_>>();
+        let ramp = |range: std::ops::Range<usize>| range.map(|i| i as f32).collect::<Vec<_>>();
+
+        // Each element is finalized by the first sample of the next one; Partially
+        // filled, so only the elements that were enqueued are returned
+        for i in 0..4 {
+            buffer.enqueue_slice(&[i as f32; 10]);
+        }
+        assert_eq!(window(&buffer, 0.02), ramp(1..3));
+        assert_eq!(window(&buffer, 0.5), ramp(0..3));
+
+        for i in 4..151 {
+            buffer.enqueue_slice(&[i as f32; 10]);
+        }
+        assert_eq!(window(&buffer, 0.05), ramp(145..150));
+        assert_eq!(window(&buffer, 0.3), ramp(120..150));
+        // Windows that don't divide evenly into elements include the oldest one
+        // that's partly inside
+        assert_eq!(window(&buffer, 0.025), ramp(147..150));
+        assert_eq!(window(&buffer, 0.001), ramp(149..150));
+        assert_eq!(window(&buffer, 0.1234), ramp(137..150));
+        // Longer than the buffer
+        assert_eq!(window(&buffer, 1.0), ramp(50..150));
+        assert_eq!(window(&buffer, 60.0), ramp(50..150));
+        assert!(window(&buffer, 0.0).is_empty());
+        assert!(window(&buffer, f32::NAN).is_empty());
+    }
+
+    #[test]
+    fn source_routing() {
+        // 4 samples per element; The side signal is 0.5, and the mean is 0.25
+        let mut buffer = PeakBuffer::new(8, 1.0, 0.0);
+        buffer.set_sample_rate(32.);
+        let mut stereo = vec![vec![0.75; 32], vec![-0.25; 32]];
+        with_buffer(&mut stereo, |b| buffer.enqueue_buffer(b, None));
+        assert_eq!(contents(&buffer)[7], 0.25);
+
+        // Switching the source clears the history of the previous one
+        let selector = buffer.source_selector();
+        selector.set(ChannelSource::Side);
+        with_buffer(&mut stereo, |b| buffer.enqueue_buffer(b, None));
+        let side = contents(&buffer);
+        assert_eq!((side[0], side[7]), (0., 0.5));
+        assert!(!side.contains(&0.25));
+
+        // Unless it's told not to
+        buffer.set_clear_on_source_change(false);
+        buffer.set_source(ChannelSource::Channel(0));
+        let mut left = vec![vec![0.75; 16]];
+        with_buffer(&mut left, |b| buffer.enqueue_buffer(b, None));
+        let channel = contents(&buffer);
+        assert!(channel.contains(&0.5));
+        assert_eq!(channel[7], 0.75);
+
+        // A mono block has no side signal
+        selector.set(ChannelSource::Side);
+        let mut mono = vec![vec![0.75; 32]];
+        with_buffer(&mut mono, |b| buffer.enqueue_buffer(b, None));
+        assert_eq!(contents(&buffer)[7], 0.);
+    }
+
+    #[test]
+    fn playback_rate() {
+        // 10 seconds of 100 elements, so each element spans 10 samples at 1x
+        let mut buffer = PeakBuffer::new(100, 10.0, 0.0);
+        buffer.set_sample_rate(100.);
+        let feed = |buffer: &mut PeakBuffer, value: f32, samples: usize| {
+            for _ in 0..samples {
+                buffer.enqueue(value);
+            }
+        };
+        feed(&mut buffer, 0., 100);
+
+        // During a 0.5x section, an event that's heard for 2 seconds spans 400 samples
+        buffer.set_playback_rate(0.5);
+        feed(&mut buffer, 0.5, 400);
+        buffer.set_playback_rate(1.);
+        feed(&mut buffer, 0., 100);
+        let event = contents(&buffer).iter().filter(|v| **v == 0.5).count();
+        assert_eq!(event, 20, "2 of the 10 seconds");
+
+        // Without the hint, the same samples span 4 seconds
+        let mut unhinted = PeakBuffer::new(100, 10.0, 0.0);
+        unhinted.set_sample_rate(100.);
+        feed(&mut unhinted, 0.5, 400);
+        feed(&mut unhinted, 0., 1);
+        assert_eq!(
+            contents(&unhinted).iter().filter(|v| **v == 0.5).count(),
+            40
+        );
+
+        // The element that is being accumulated keeps its progress, so half of it
+        // is left to go
+        feed(&mut buffer, 0.25, 5);
+        buffer.set_playback_rate(0.5);
+        assert_eq!(buffer.playback_rate(), 0.5);
+        feed(&mut buffer, 0.25, 10);
+        assert_eq!(contents(&buffer)[99], 0.);
+        feed(&mut buffer, 0.25, 1);
+        assert_eq!(contents(&buffer)[99], 0.25);
+
+        // Invalid rates are ignored, and the decay keeps following what's heard
+        let mut buffer = PeakBuffer::new(100, 1.0, 50.0);
+        buffer.set_sample_rate(48_000.);
+        let weight = buffer.decay_weight;
+        buffer.set_playback_rate(0.);
+        buffer.set_playback_rate(f32::NAN);
+        assert_eq!(buffer.playback_rate(), 1.);
+        buffer.set_playback_rate(2.);
+        assert!((buffer.decay_weight - weight).abs() < 1e-6);
+    }
+
+    #[test]
+    fn decay_follows_tempo() {
+        let mut buffer = PeakBuffer::new(100, 1.0, 50.0);
+        buffer.set_sample_rate(48_000.);
+        // A quarter note at 120 BPM, until a tempo is reported
+        buffer.set_decay_beats(1.0);
+        assert_eq!((buffer.decay, buffer.decay_beats()), (500., Some(1.0)));
+        let weight = buffer.decay_weight;
+
+        // Twice the tempo falls twice as many decibels per element
+        buffer.set_tempo(240.);
+        assert_eq!(buffer.decay, 250.);
+        let ratio = buffer.decay_weight.ln() / weight.ln();
+        assert!((ratio - 2.).abs() < 1e-4, "{}", ratio);
+
+        // Jitter doesn't change the decay
+        buffer.set_tempo(240.005);
+        assert_eq!(buffer.decay, 250.);
+
+        // A decay in milliseconds ends following the tempo
+        buffer.set_decay(100.);
+        buffer.set_tempo(60.);
+        assert_eq!((buffer.decay, buffer.decay_beats()), (100., None));
+    }
+
+    #[test]
+    fn heartbeat() {
+        // 10 samples per element
+        let mut buffer = PeakBuffer::new(10, 1.0, 0.0).with_heartbeat();
+        buffer.set_sample_rate(100.);
+        let heartbeat = buffer.heartbeat().unwrap();
+
+        buffer.set_transport_playing(true);
+        buffer.enqueue_slice(&[0.5; 25]);
+        let reading = heartbeat.read();
+        assert_eq!((reading.enqueued, reading.last_sample), (2, 21));
+        assert!(reading.playing);
+
+        buffer.set_transport_playing(false);
+        buffer.enqueue_slice(&[0.5; 10]);
+        assert!(!heartbeat.read().playing);
+        assert_eq!(heartbeat.read().enqueued, 3);
+
+        // Without a heartbeat, nothing is tracked
+        assert!(PeakBuffer::new(10, 1.0, 0.0).heartbeat().is_none());
+    }
+
+    #[test]
+    fn density() {
+        let new = || {
+            let mut buffer = PeakBuffer::with_density(10., 10., 0.0);
+            buffer.set_sample_rate(100.);
+            buffer
+        };
+        assert_density_kept(new, PeakBuffer::set_duration, PeakBuffer::rescale_duration);
+
+        // Resizing the buffer explicitly goes back to a fixed size
+        let mut buffer = new();
+        assert_eq!(buffer.density(), 10.);
+        buffer.grow(200);
+        assert_eq!(buffer.density(), 20.);
+        buffer.set_duration(5.);
+        assert_eq!(buffer.len(), 200);
+
+        assert!(PeakBuffer::try_with_density(0., 10., 0.0).is_err());
+    }
+
+    #[test]
+    fn rescale_duration() {
+        // No decay, so that merged maxima match the ones recorded at that duration
+        let new = |duration| {
+            let mut buffer = PeakBuffer::new(100, duration, 0.0);
+            buffer.set_sample_rate(100.);
+            buffer
+        };
+        assert_rescale_matches_offline(new, PeakBuffer::rescale_duration);
+
+        // Restored elements are replaced by the rescaled history
+        let mut buffer = new(10.);
+        buffer.restore_from_summary(&[0.5]);
+        buffer.rescale_duration(20.);
+        assert!(!buffer.is_restored(buffer.len() - 1));
+        assert_eq!(buffer[49], 0.);
+        assert_eq!(buffer[50], 0.5);
+    }
+
+    #[test]
+    fn enqueue_buffer() {
+        // 8 slots of 4 samples each, so 100 samples wrap around the buffer
+        let new = || {
+            let mut buffer = PeakBuffer::new(8, 1.0, 50.0);
+            buffer.set_sample_rate(32.);
+            buffer
+        };
+        assert_enqueue_buffer_matches_enqueue(new, 100);
+        assert_interleaved_matches_buffer(new, 100);
+
+        for aggregation in [Aggregation::Mean, Aggregation::AbsPercentile(90.)] {
+            let new = || {
+                let mut buffer = PeakBuffer::with_aggregation(8, 1.0, 50.0, aggregation);
+                buffer.set_sample_rate(32.);
+                buffer
+            };
+            assert_enqueue_buffer_matches_enqueue(new, 100);
+        }
+    }
+
+    #[test]
+    fn overload_latch() {
+        // 10 slots of 12.8 samples each, so most samples are scanned in spans
+        let mut buffer = PeakBuffer::new(10, 1.0, 0.0).with_overload_threshold(0.0);
+        buffer.set_sample_rate(128.);
+        buffer.enqueue_slice(&[0.5; 30]);
+        assert_eq!(buffer.overload(), None);
+
+        // A single sample latches it, and the worst one is kept
+        let mut samples = [0.5; 30];
+        samples[5] = 1.25;
+        samples[12] = -1.5;
+        samples[20] = 1.0;
+        buffer.enqueue_slice(&samples);
+        let overload = buffer.overload().unwrap();
+        assert_eq!((overload.peak, overload.sample), (1.5, 42));
+        assert_eq!(overload.time, 42. / 128.);
+
+        // Clearing the buffer keeps the latch, unlike resetting it
+        buffer.clear();
+        assert_eq!(buffer.overload(), Some(overload));
+        buffer.reset_overload();
+        assert!(!buffer.is_overloaded());
+
+        // The channels of a mono-summed block are checked on their own
+        with_buffer(&mut vec![vec![0.5, 2.0], vec![-0.5, -2.0]], |b| {
+            buffer.enqueue_buffer(b, None)
+        });
+        assert_eq!(buffer.overload().unwrap().peak, 2.0);
+    }
+
+    #[test]
+    fn overload_auto_reset() {
+        let mut buffer = PeakBuffer::new(10, 1.0, 0.0).with_overload_threshold(-6.0);
+        buffer.set_sample_rate(128.);
+        // Half a second, so 64 samples after the last overload
+        buffer.set_overload_auto_reset(Some(0.5));
+
+        buffer.enqueue_slice(&[1.0, 0.75, 0.0]);
+        buffer.enqueue_slice(&[0.0; 61]);
+        assert_eq!(buffer.overload().unwrap().peak, 1.0);
+        // The time counts from the last overload, rather than the worst one
+        buffer.enqueue(0.0);
+        assert_eq!(buffer.overload().unwrap().sample, 0);
+        buffer.enqueue(0.0);
+        assert_eq!(buffer.overload(), None);
+
+        // A later overload latches it again, without the old worst one
+        buffer.enqueue_slice(&[0.0, -0.625]);
+        let overload = buffer.overload().unwrap();
+        assert_eq!((overload.peak, overload.sample), (0.625, 67));
+
+        // Without an auto-reset, it stays latched
+        buffer.set_overload_auto_reset(None);
+        buffer.enqueue_slice(&[0.0; 1000]);
+        assert!(buffer.is_overloaded());
+    }
+
+    #[test]
+    fn enqueue_buffer_large_block() {
+        // A fractional number of samples per slot, so slot boundaries fall inside
+        // the blocks
+        assert_large_block_matches_small_blocks(|| {
+            let mut buffer = PeakBuffer::new(800, 1.0, 50.0);
+            buffer.set_sample_rate(44100.);
+            buffer
+        });
+    }
+
+    #[test]
+    fn enqueue_slice_matches_enqueue() {
+        let mut rng = StdRng::seed_from_u64(651);
+        for _ in 0..200 {
+            // Anywhere from less than one to a few hundred samples per element
+            let size = rng.gen_range(1..=64);
+            let sample_rate = rng.gen_range(8.0..48_000.0);
+            let mut scalar = PeakBuffer::new(size, rng.gen_range(0.01..0.5), 50.0);
+            scalar.set_sample_rate(sample_rate);
+            let mut sliced = scalar.clone();
+
+            for _ in 0..20 {
+                let block = (0..rng.gen_range(0..1024))
+                    .map(|_| rng.gen_range(-4.0..4.0))
+                    .collect::<Vec<f32>>();
+                for sample in &block {
+                    scalar.enqueue(*sample);
+                }
+                sliced.enqueue_slice(&block);
+
+                let bits = |buffer: &PeakBuffer| {
+                    let partial = buffer.partial().map(|(p, v)| (p.to_bits(), v.to_bits()));
+                    let elements = contents(buffer).into_iter().map(f32::to_bits);
+                    (elements.collect::<Vec<_>>(), partial)
+                };
+                assert_eq!(bits(&scalar), bits(&sliced));
+            }
+        }
+    }
+
+    #[test]
+    fn offline() {
+        let mut buffer = PeakBuffer::new(8, 1.0, 50.0);
+        buffer.set_sample_rate(32.);
+        buffer.set_offline(true);
+
+        let mut channels = test_signal(2, 100);
+        with_buffer(&mut channels, |b| buffer.enqueue_buffer(b, None));
+        buffer.enqueue(1.0);
+        assert!(contents(&buffer).iter().all(|value| *value == 0.0));
+
+        // Once back in realtime, enqueueing continues as usual
+        buffer.set_offline(false);
+        with_buffer(&mut channels, |b| buffer.enqueue_buffer(b, None));
+        assert!(contents(&buffer).iter().any(|value| *value > 0.0));
+    }
+
+    #[test]
+    fn partial() {
+        // 4 samples per element
+        let mut buffer = PeakBuffer::new(8, 1.0, 200.0);
+        buffer.set_sample_rate(32.);
+
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut provisional: Vec<(f32, f32)> = Vec::new();
+        for _ in 0..200 {
+            if let Some(slot) = buffer.enqueue_slot(rng.gen_range(-1.0..1.0)) {
+                // The provisional value only ever rises towards the finished element
+                assert!(provisional
+                    .windows(2)
+                    .all(|w| w[0].0 < w[1].0 && w[0].1 <= w[1].1));
+                assert!(provisional.iter().all(|(_, value)| *value <= slot));
+                provisional.clear();
+            }
+            let (progress, value) = buffer.partial().unwrap();
+            assert!((0.0..=1.0).contains(&progress));
+            provisional.push((progress, value));
+        }
+
+        // Percentiles aren't known until the element is finished
+        buffer.set_aggregation(Aggregation::AbsPercentile(50.));
+        buffer.enqueue(0.5);
+        assert_eq!(buffer.partial(), None);
+    }
+
+    #[test]
+    fn get() {
+        // 4 samples per element
+        let mut buffer = PeakBuffer::new(4, 1.0, 0.0);
+        buffer.set_sample_rate(16.);
+        assert_eq!(buffer.get(0), Some(&0.));
+        assert_eq!(buffer.get(4), None);
+
+        for value in [0.25, 0.5, 0.75, 1.0, 0.5, 0.25] {
+            for _ in 0..4 {
+                buffer.enqueue(value);
+            }
+        }
+        // The last element is finalized by the next sample
+        buffer.enqueue(0.);
+        assert_eq!(buffer.get(0), Some(&0.75));
+        assert_eq!(buffer.get(3), Some(&0.25));
+        assert_eq!(buffer.get(4), None);
+        assert_eq!(buffer.get_newest(0), Some(&0.25));
+        assert_eq!(buffer.get_newest(4), None);
+    }
+
+    #[test]
+    fn catch_up_matches_silence() {
+        // 25 samples per element, so that both count down exactly
+        let configurations: [fn() -> PeakBuffer; 4] = [
+            || PeakBuffer::new(40, 1.0, 100.0),
+            || {
+                PeakBuffer::new(40, 1.0, 100.0)
+                    .with_transport_tracking()
+                    .with_tag_tracking()
+            },
+            || {
+                PeakBuffer::with_aggregation(40, 1.0, 0.0, Aggregation::Mean)
+                    .with_deviation_tracking()
+            },
+            || PeakBuffer::new(40, 1.0, 0.0).with_ballistics(BallisticsPreset::PpmType1),
+        ];
+        let assert_matches = |continuous: &PeakBuffer, caught_up: &PeakBuffer, gap: u64| {
+            let near = |a: f32, b: f32| (a - b).abs() <= 1e-6 + a.abs() * 1e-3;
+            for i in 0..continuous.len() {
+                assert!(
+                    near(continuous[i], caught_up[i]),
+                    "element {} after a gap of {}: {} != {}",
+                    i,
+                    gap,
+                    continuous[i],
+                    caught_up[i]
+                );
+                assert_eq!(continuous.tag(i), caught_up.tag(i));
+                assert_eq!(
+                    continuous.transport_playing(i),
+                    caught_up.transport_playing(i)
+                );
+                if let (Some(a), Some(b)) = (continuous.deviation(i), caught_up.deviation(i)) {
+                    assert!(near(a.0, b.0) && near(a.1, b.1), "{:?} != {:?}", a, b);
+                }
+            }
+            let (a, b) = (continuous.partial().unwrap(), caught_up.partial().unwrap());
+            assert!(a.0 == b.0 && near(a.1, b.1), "{:?} != {:?}", a, b);
+        };
+
+        let signal = test_signal(1, 600).remove(0);
+        // Inside the element, across a few elements, and longer than the buffer
+        for gap in [3, 10, 25, 260, 999, 5000] {
+            for new in configurations {
+                let mut continuous = new();
+                let mut caught_up = new();
+                continuous.set_sample_rate(1000.);
+                caught_up.set_sample_rate(1000.);
+                continuous.enqueue_slice(&signal[..310]);
+                caught_up.enqueue_slice(&signal[..310]);
+
+                for _ in 0..gap {
+                    continuous.enqueue(0.);
+                }
+                caught_up.catch_up(gap);
+                assert_matches(&continuous, &caught_up, gap);
+
+                // And both carry on the same
+                continuous.enqueue_slice(&signal[310..]);
+                caught_up.enqueue_slice(&signal[310..]);
+                assert_matches(&continuous, &caught_up, gap);
+            }
+        }
+    }
+
+    #[test]
+    fn catch_up_keeps_takes() {
+        let mut buffer = PeakBuffer::new(8, 1.0, 100.0).with_mode(BufferMode::Capture);
+        buffer.set_sample_rate(80.);
+        buffer.arm();
+        for _ in 0..100 {
+            buffer.enqueue(0.5);
+        }
+        assert!(buffer.is_complete());
+        let take = contents(&buffer);
+
+        buffer.catch_up(1000);
+        assert_eq!(contents(&buffer), take);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Invalid peak buffer access: Index 4 is out of range for peak buffer \"peak_in\" of size 4"
+    )]
+    fn invalid_access_named() {
+        let buffer = PeakBuffer::new(4, 1.0, 50.0).with_name("peak_in");
+        buffer[4];
     }
 }
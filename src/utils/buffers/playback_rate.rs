@@ -0,0 +1,88 @@
+/// Changes of the playback rate up to this are ignored, so that reporting the rate
+/// every block doesn't re-derive a buffer's timing for jitter.
+const RATE_EPSILON: f32 = 1e-3;
+
+/// The rate at which the host plays the project back, e.g. `0.5` while it
+/// auditions at half speed, which a time-based buffer's duration follows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PlaybackRate {
+    rate: f32,
+}
+
+impl Default for PlaybackRate {
+    fn default() -> Self {
+        Self { rate: 1. }
+    }
+}
+
+impl PlaybackRate {
+    /// Updates the rate, and returns whether the buffer's timing needs to be
+    /// re-derived.
+    ///
+    /// Rates that aren't positive and finite are ignored, as are changes of up to
+    /// [`RATE_EPSILON`] - measured from the rate the timing was last derived for, so
+    /// that a slow ramp still gets through.
+    pub(crate) fn set(self: &mut Self, rate: f32) -> bool {
+        if !(rate.is_finite() && rate > 0.) || (rate - self.rate).abs() <= RATE_EPSILON {
+            return false;
+        }
+        self.rate = rate;
+        true
+    }
+
+    /// Returns the rate.
+    pub(crate) fn rate(self: &Self) -> f32 {
+        self.rate
+    }
+
+    /// Returns the number of samples the buffer receives for each second that's
+    /// heard, which it uses in place of the sample rate to lay out its elements.
+    ///
+    /// At the default rate of 1, this is the sample rate itself.
+    pub(crate) fn samples_per_second(self: &Self, sample_rate: f32) -> f32 {
+        sample_rate / self.rate
+    }
+}
+
+/// Returns the time left inside the current element once elements span
+/// `new_delta` samples instead of `old_delta`, so that the element keeps the
+/// progress it made.
+pub(crate) fn retime(t: f32, old_delta: f32, new_delta: f32) -> f32 {
+    if old_delta > 0. {
+        t / old_delta * new_delta
+    } else {
+        new_delta
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{retime, PlaybackRate};
+
+    #[test]
+    fn set() {
+        let mut rate = PlaybackRate::default();
+        assert_eq!(rate.samples_per_second(48_000.), 48_000.);
+
+        // Invalid rates and jitter are ignored
+        for invalid in [0., -1., f32::NAN, f32::INFINITY, 1.0005] {
+            assert!(!rate.set(invalid), "{}", invalid);
+        }
+        assert_eq!(rate.rate(), 1.);
+
+        assert!(rate.set(0.5));
+        assert!(!rate.set(0.5));
+        assert_eq!(rate.samples_per_second(48_000.), 96_000.);
+
+        // A slow ramp still gets through
+        assert!(!rate.set(0.5009));
+        assert!(rate.set(0.5011));
+    }
+
+    #[test]
+    fn keeps_progress() {
+        assert_eq!(retime(25., 100., 200.), 50.);
+        assert_eq!(retime(100., 100., 50.), 50.);
+        assert_eq!(retime(0., 0., 50.), 50.);
+    }
+}
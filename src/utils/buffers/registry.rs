@@ -0,0 +1,254 @@
+//! A process-wide registry of buffers, for debugging.
+//!
+//! When a plug-in uses many buffers, it can be hard to tell which one is
+//! misbehaving. Buffers can be registered with the [`registry()`], which can then
+//! list every buffer that is still alive along with its configuration - e.g. inside
+//! a debug overlay:
+//!
+//! ```
+//! let peak_buffer = Arc::new(Mutex::new(PeakBuffer::new(800, 10.0, 50.0).with_name("peak_in")));
+//! registry().register(&peak_buffer);
+//!
+//! for status in registry().buffers() {
+//!     // peak_in: 800 slots, 10 s, 44.1 kHz, last enqueue 0.2 s ago
+//!     println!("{}", status);
+//! }
+//! ```
+//!
+//! The registry only holds weak references, so it never keeps a buffer alive.
+//! Buffers that were dropped are removed the next time the registry is read.
+//!
+//! Registering is opt-in, and the registry only exists with the `debug-tools`
+//! feature enabled - without it, buffers don't keep track of anything extra.
+
+use std::fmt::{self, Display};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+
+/// Describes the configuration of a buffer, as listed by the [`Registry`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct BufferInfo {
+    /// The type of buffer, e.g. `PeakBuffer`.
+    pub buffer: &'static str,
+    /// The name of the buffer, if it has one.
+    pub name: Option<String>,
+    /// The size of the buffer.
+    pub size: usize,
+    /// The duration (in seconds) of the audio data inside the buffer.
+    pub duration: Option<f32>,
+    /// The sample rate of the audio the buffer is fed.
+    pub sample_rate: Option<f32>,
+    /// The number of elements that were enqueued so far.
+    ///
+    /// For buffers that aggregate samples, such as the
+    /// [`PeakBuffer`](super::PeakBuffer), this counts the finished elements rather
+    /// than the samples.
+    pub enqueued: u64,
+}
+
+/// A buffer that can be listed by the [`Registry`].
+pub trait RegisteredBuffer {
+    /// Describes the buffer's current configuration.
+    fn info(&self) -> BufferInfo;
+}
+
+/// The status of a registered buffer, as returned by [`Registry::buffers()`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct BufferStatus {
+    /// The configuration of the buffer.
+    pub info: BufferInfo,
+    /// The time since the last element was enqueued, or `None` if nothing was
+    /// enqueued since the buffer was registered.
+    ///
+    /// Enqueues are noticed whenever the registry is read, so this is only as
+    /// precise as the rate at which [`Registry::buffers()`] is called.
+    pub since_enqueue: Option<Duration>,
+}
+
+struct Entry {
+    buffer: Weak<Mutex<dyn RegisteredBuffer + Send>>,
+    enqueued: u64,
+    last_enqueue: Option<Instant>,
+}
+
+/// Keeps track of registered buffers; See the [module-level documentation](self).
+pub struct Registry {
+    entries: Mutex<Vec<Entry>>,
+}
+
+static REGISTRY: Registry = Registry {
+    entries: Mutex::new(Vec::new()),
+};
+
+/// Returns the process-wide [`Registry`].
+pub fn registry() -> &'static Registry {
+    &REGISTRY
+}
+
+impl Registry {
+    /// Registers a shared buffer, without keeping it alive.
+    pub fn register<B: RegisteredBuffer + Send + 'static>(&self, buffer: &Arc<Mutex<B>>) {
+        let buffer: Arc<Mutex<dyn RegisteredBuffer + Send>> = buffer.clone();
+        let enqueued = describe(&buffer).enqueued;
+        self.entries().push(Entry {
+            buffer: Arc::downgrade(&buffer),
+            enqueued,
+            last_enqueue: None,
+        });
+    }
+
+    /// Returns the status of every registered buffer that is still alive, in the
+    /// order they were registered.
+    ///
+    /// Each buffer is locked while it is described, so this shouldn't be called more
+    /// often than the editor redraws.
+    pub fn buffers(&self) -> Vec<BufferStatus> {
+        let now = Instant::now();
+        let mut entries = self.entries();
+        entries.retain(|entry| entry.buffer.strong_count() > 0);
+
+        entries
+            .iter_mut()
+            .filter_map(|entry| {
+                let info = describe(&entry.buffer.upgrade()?);
+                if info.enqueued != entry.enqueued {
+                    entry.enqueued = info.enqueued;
+                    entry.last_enqueue = Some(now);
+                }
+                Some(BufferStatus {
+                    info,
+                    since_enqueue: entry.last_enqueue.map(|last| now.duration_since(last)),
+                })
+            })
+            .collect()
+    }
+
+    fn entries(&self) -> MutexGuard<'_, Vec<Entry>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn describe(buffer: &Mutex<dyn RegisteredBuffer + Send>) -> BufferInfo {
+    // A poisoned buffer is still worth listing
+    match buffer.lock() {
+        Ok(buffer) => buffer.info(),
+        Err(poisoned) => poisoned.into_inner().info(),
+    }
+}
+
+/// Formats the status as e.g. `peak_in: 800 slots, 10 s, 44.1 kHz, last enqueue 0.2
+/// s ago`.
+impl Display for BufferStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let info = &self.info;
+        write!(
+            f,
+            "{}: {} slots",
+            info.name.as_deref().unwrap_or(info.buffer),
+            info.size
+        )?;
+        if let Some(duration) = info.duration {
+            write!(f, ", {} s", duration)?;
+        }
+        if let Some(sample_rate) = info.sample_rate {
+            write!(f, ", {} kHz", sample_rate / 1000.)?;
+        }
+        match self.since_enqueue {
+            Some(since) => write!(f, ", last enqueue {:.1} s ago", since.as_secs_f32()),
+            None => write!(f, ", nothing enqueued"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{registry, BufferInfo, BufferStatus};
+    use crate::utils::{PeakBuffer, RingBuffer, VisualizerBuffer};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    // The registry is shared by all tests, so each test only looks at its own buffers
+    fn statuses(name: &str) -> Vec<BufferStatus> {
+        registry()
+            .buffers()
+            .into_iter()
+            .filter(|status| status.info.name.as_deref() == Some(name))
+            .collect()
+    }
+
+    #[test]
+    fn weak_references() {
+        let buffer = Arc::new(Mutex::new(RingBuffer::<f32>::new(8).with_name("weak")));
+        registry().register(&buffer);
+        assert_eq!(statuses("weak").len(), 1);
+
+        drop(buffer);
+        assert!(statuses("weak").is_empty());
+    }
+
+    #[test]
+    fn tracks_enqueues() {
+        let buffer = Arc::new(Mutex::new(
+            PeakBuffer::new(800, 10.0, 50.0).with_name("peak_in"),
+        ));
+        buffer.lock().unwrap().set_sample_rate(44100.);
+        registry().register(&buffer);
+
+        let status = &statuses("peak_in")[0];
+        assert_eq!(
+            status.info,
+            BufferInfo {
+                buffer: "PeakBuffer",
+                name: Some("peak_in".to_string()),
+                size: 800,
+                duration: Some(10.0),
+                sample_rate: Some(44100.),
+                enqueued: 0,
+            }
+        );
+        assert_eq!(status.since_enqueue, None);
+
+        // A single element spans 10 / 800 seconds
+        for _ in 0..1000 {
+            buffer.lock().unwrap().enqueue(0.5);
+        }
+        let status = &statuses("peak_in")[0];
+        assert_eq!(status.info.enqueued, 1);
+        assert!(status.since_enqueue.is_some());
+    }
+
+    #[test]
+    fn display() {
+        let status = BufferStatus {
+            info: BufferInfo {
+                buffer: "PeakBuffer",
+                name: Some("peak_in".to_string()),
+                size: 800,
+                duration: Some(10.0),
+                sample_rate: Some(44100.),
+                enqueued: 12,
+            },
+            since_enqueue: Some(Duration::from_millis(200)),
+        };
+        assert_eq!(
+            status.to_string(),
+            "peak_in: 800 slots, 10 s, 44.1 kHz, last enqueue 0.2 s ago"
+        );
+
+        let status = BufferStatus {
+            info: BufferInfo {
+                name: None,
+                duration: None,
+                sample_rate: None,
+                ..status.info
+            },
+            since_enqueue: None,
+        };
+        assert_eq!(
+            status.to_string(),
+            "PeakBuffer: 800 slots, nothing enqueued"
+        );
+    }
+}
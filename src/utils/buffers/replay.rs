@@ -0,0 +1,735 @@
+//! Recording the calls that feed a buffer, and replaying them, to reproduce bugs.
+//!
+//! Some bugs only show up for the exact sequence of values and block boundaries a
+//! host delivered. Wrapping a buffer in a [`RecordingBuffer`] tees every call that
+//! feeds it into a log, which can be saved and attached to a bug report, and then
+//! [`replay()`]ed into a fresh buffer to reproduce its contents exactly:
+//!
+//! ```
+//! let peak_buffer = Arc::new(Mutex::new(RecordingBuffer::new(
+//!     PeakBuffer::new(800, 10.0, 50.0),
+//!     16 * 1024 * 1024,
+//! )));
+//!
+//! // Later, e.g. from a debug menu
+//! std::fs::write("peak_buffer.cyrl", peak_buffer.lock().unwrap().log())?;
+//!
+//! // And inside a test
+//! let log = ReplayLog::parse(&std::fs::read("peak_buffer.cyrl")?)?;
+//! let mut buffer = PeakBuffer::new(800, 10.0, 50.0);
+//! buffer.set_sample_rate(48000.);
+//! replay(&log, &mut buffer);
+//! ```
+//!
+//! The `replay_tool` example replays a log from the command line, and prints the
+//! resulting contents as CSV.
+//!
+//! # Format
+//!
+//! A log consists of a header, followed by one record per call. All fields are
+//! little-endian:
+//!
+//! | Offset | Type      | Field                   |
+//! |--------|-----------|-------------------------|
+//! | 0      | `[u8; 4]` | Magic bytes, `CYRL`     |
+//! | 4      | `u8`      | Version, currently 1    |
+//! | 5      | `[u8; 3]` | Reserved, 0             |
+//!
+//! Each record starts with the kind of call as a `u8`, and the time since the
+//! recording started in nanoseconds as a `u64`, followed by the call's arguments:
+//!
+//! | Kind | Call                                          | Arguments                                                            |
+//! |------|-----------------------------------------------|----------------------------------------------------------------------|
+//! | 0    | [`enqueue()`](VisualizerBuffer::enqueue)      | The value, `f32`                                                     |
+//! | 1    | [`enqueue_buffer()`](VisualizerBuffer::enqueue_buffer) | The channel, `u32`, or `u32::MAX` for none; The number of channels and of samples, `u32`s; The samples of each channel, `f32`s |
+//! | 2    | [`enqueue_interleaved()`](VisualizerBuffer::enqueue_interleaved) | The number of channels, `u32`; The downmix; The number of samples, `u32`; The samples, `f32`s |
+//! | 3    | [`enqueue_planar()`](VisualizerBuffer::enqueue_planar) | The downmix; The number of channels, `u32`; For each channel, the number of samples, `u32`, and the samples, `f32`s |
+//! | 4    | [`clear()`](VisualizerBuffer::clear)          | -                                                                    |
+//! | 5    | [`grow()`](VisualizerBuffer::grow)            | The size, `u32`                                                      |
+//! | 6    | [`shrink()`](VisualizerBuffer::shrink)        | The size, `u32`                                                      |
+//!
+//! A downmix is stored as a `u8` - 0 for [`Mean`](ChannelDownmix::Mean), 1 for
+//! [`MaxAbs`](ChannelDownmix::MaxAbs) and 2 for
+//! [`Channel`](ChannelDownmix::Channel) - followed by the channel as a `u32`, which
+//! is 0 for the other downmixes.
+//!
+//! Records are only ever appended, so a log that was cut off - e.g. because the
+//! plug-in crashed while it was being written - still holds every call up to the
+//! last complete record, which [`ReplayLog::parse()`] reads.
+//!
+//! This module is only available with the `debug-tools` feature enabled.
+
+use std::fmt;
+use std::ops::{Index, IndexMut};
+use std::time::{Duration, Instant};
+
+use nih_plug::buffer::Buffer;
+
+use super::{ChannelDownmix, VisualizerBuffer};
+
+/// The magic bytes at the start of each log.
+pub const LOG_MAGIC: [u8; 4] = *b"CYRL";
+/// The version of the log format that is written.
+pub const LOG_VERSION: u8 = 1;
+/// The length of the header, in bytes.
+pub const LOG_HEADER_LEN: usize = 8;
+
+// The kinds of records
+const ENQUEUE: u8 = 0;
+const ENQUEUE_BUFFER: u8 = 1;
+const ENQUEUE_INTERLEAVED: u8 = 2;
+const ENQUEUE_PLANAR: u8 = 3;
+const CLEAR: u8 = 4;
+const GROW: u8 = 5;
+const SHRINK: u8 = 6;
+
+// The length of the kind and the time that each record starts with
+const RECORD_HEADER_LEN: usize = 9;
+// The length of an encoded downmix
+const DOWNMIX_LEN: usize = 5;
+
+/// Wraps a buffer, and records every call that feeds it into a log; See the
+/// [module documentation](self).
+///
+/// The log holds up to a fixed number of bytes, which are allocated up front, so
+/// recording doesn't allocate on the audio thread. Once a call doesn't fit anymore,
+/// recording stops, while the wrapped buffer keeps being fed - the log is always a
+/// complete prefix of the calls.
+pub struct RecordingBuffer<V> {
+    inner: V,
+    log: Vec<u8>,
+    max_bytes: usize,
+    started: Instant,
+    full: bool,
+}
+
+impl<V> RecordingBuffer<V> {
+    /// Wraps a buffer, recording its calls into a log of up to `max_bytes` bytes.
+    pub fn new(inner: V, max_bytes: usize) -> Self {
+        let max_bytes = max_bytes.max(LOG_HEADER_LEN);
+        let mut log = Vec::with_capacity(max_bytes);
+        log.extend_from_slice(&LOG_MAGIC);
+        log.push(LOG_VERSION);
+        log.extend_from_slice(&[0; 3]);
+        Self {
+            inner,
+            log,
+            max_bytes,
+            started: Instant::now(),
+            full: false,
+        }
+    }
+
+    /// Returns the log, which can be saved and parsed using [`ReplayLog::parse()`].
+    pub fn log(self: &Self) -> &[u8] {
+        &self.log
+    }
+
+    /// Returns `true` once a call didn't fit into the log anymore, and recording
+    /// stopped.
+    pub fn is_full(self: &Self) -> bool {
+        self.full
+    }
+
+    /// Returns the wrapped buffer.
+    pub fn inner(self: &Self) -> &V {
+        &self.inner
+    }
+
+    /// Returns the wrapped buffer; Calls made on it directly aren't recorded.
+    pub fn inner_mut(self: &mut Self) -> &mut V {
+        &mut self.inner
+    }
+
+    /// Unwraps the buffer, discarding the log.
+    pub fn into_inner(self) -> V {
+        self.inner
+    }
+
+    // Appends a record whose arguments take up `len` bytes, if it fits
+    fn record(self: &mut Self, kind: u8, len: usize, arguments: impl FnOnce(&mut Vec<u8>)) {
+        if self.full || self.log.len() + RECORD_HEADER_LEN + len > self.max_bytes {
+            self.full = true;
+            return;
+        }
+        let start = self.log.len();
+        let time = self.started.elapsed().as_nanos() as u64;
+        self.log.push(kind);
+        self.log.extend_from_slice(&time.to_le_bytes());
+        arguments(&mut self.log);
+        debug_assert_eq!(self.log.len() - start, RECORD_HEADER_LEN + len);
+    }
+}
+
+fn write_u32(log: &mut Vec<u8>, value: usize) {
+    log.extend_from_slice(&(value as u32).to_le_bytes());
+}
+
+fn write_samples(log: &mut Vec<u8>, samples: &[f32]) {
+    for sample in samples {
+        log.extend_from_slice(&sample.to_le_bytes());
+    }
+}
+
+fn write_downmix(log: &mut Vec<u8>, downmix: ChannelDownmix) {
+    let (kind, channel) = match downmix {
+        ChannelDownmix::Mean => (0, 0),
+        ChannelDownmix::MaxAbs => (1, 0),
+        ChannelDownmix::Channel(channel) => (2, channel),
+    };
+    log.push(kind);
+    write_u32(log, channel);
+}
+
+impl<V> VisualizerBuffer<f32> for RecordingBuffer<V>
+where
+    V: VisualizerBuffer<f32>,
+{
+    fn enqueue(self: &mut Self, value: f32) {
+        self.record(ENQUEUE, 4, |log| write_samples(log, &[value]));
+        self.inner.enqueue(value);
+    }
+
+    fn enqueue_buffer(self: &mut Self, buffer: &mut Buffer, channel: Option<usize>) {
+        let channels = buffer.as_slice_immutable();
+        let samples = channels.first().map_or(0, |channel| channel.len());
+        self.record(ENQUEUE_BUFFER, 12 + channels.len() * samples * 4, |log| {
+            write_u32(log, channel.unwrap_or(u32::MAX as usize));
+            write_u32(log, channels.len());
+            write_u32(log, samples);
+            for channel in channels.iter() {
+                write_samples(log, channel);
+            }
+        });
+        self.inner.enqueue_buffer(buffer, channel);
+    }
+
+    fn enqueue_interleaved(
+        self: &mut Self,
+        data: &[f32],
+        channels: usize,
+        downmix: ChannelDownmix,
+    ) {
+        self.record(
+            ENQUEUE_INTERLEAVED,
+            8 + DOWNMIX_LEN + data.len() * 4,
+            |log| {
+                write_u32(log, channels);
+                write_downmix(log, downmix);
+                write_u32(log, data.len());
+                write_samples(log, data);
+            },
+        );
+        self.inner.enqueue_interleaved(data, channels, downmix);
+    }
+
+    fn enqueue_planar(self: &mut Self, channels: &[&[f32]], downmix: ChannelDownmix) {
+        let samples = channels.iter().map(|channel| channel.len()).sum::<usize>();
+        let len = DOWNMIX_LEN + 4 + channels.len() * 4 + samples * 4;
+        self.record(ENQUEUE_PLANAR, len, |log| {
+            write_downmix(log, downmix);
+            write_u32(log, channels.len());
+            for channel in channels {
+                write_u32(log, channel.len());
+                write_samples(log, channel);
+            }
+        });
+        self.inner.enqueue_planar(channels, downmix);
+    }
+
+    fn clear(self: &mut Self) {
+        self.record(CLEAR, 0, |_| {});
+        self.inner.clear();
+    }
+
+    fn grow(self: &mut Self, size: usize) {
+        self.record(GROW, 4, |log| write_u32(log, size));
+        self.inner.grow(size);
+    }
+
+    fn shrink(self: &mut Self, size: usize) {
+        self.record(SHRINK, 4, |log| write_u32(log, size));
+        self.inner.shrink(size);
+    }
+
+    fn len(self: &Self) -> usize {
+        self.inner.len()
+    }
+
+    fn duration(self: &Self) -> Option<f32> {
+        self.inner.duration()
+    }
+
+    fn partial(self: &Self) -> Option<(f32, Self::Output)>
+    where
+        Self::Output: Sized,
+    {
+        self.inner.partial()
+    }
+
+    fn generation(self: &Self) -> Option<u64> {
+        self.inner.generation()
+    }
+
+    fn enqueued(self: &Self) -> Option<u64> {
+        self.inner.enqueued()
+    }
+
+    fn sample_clock(self: &Self) -> Option<(u64, f32)> {
+        self.inner.sample_clock()
+    }
+
+    fn clears(self: &Self) -> Option<u64> {
+        self.inner.clears()
+    }
+
+    fn refresh(self: &mut Self) {
+        self.inner.refresh()
+    }
+
+    fn capture_progress(self: &Self) -> Option<f32> {
+        self.inner.capture_progress()
+    }
+
+    fn transport_playing(self: &Self, index: usize) -> Option<bool> {
+        self.inner.transport_playing(index)
+    }
+
+    fn tag(self: &Self, index: usize) -> Option<u8> {
+        self.inner.tag(index)
+    }
+
+    fn deviation(self: &Self, index: usize) -> Option<(f32, f32)> {
+        self.inner.deviation(index)
+    }
+
+    fn is_restored(self: &Self, index: usize) -> bool {
+        self.inner.is_restored(index)
+    }
+}
+
+impl<V: Index<usize>> Index<usize> for RecordingBuffer<V> {
+    type Output = V::Output;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.inner[index]
+    }
+}
+
+impl<V: IndexMut<usize>> IndexMut<usize> for RecordingBuffer<V> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.inner[index]
+    }
+}
+
+/// A call that was recorded by a [`RecordingBuffer`], along with its arguments.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoggedCall {
+    Enqueue(f32),
+    /// The samples of each channel of the block, and the channel argument.
+    EnqueueBuffer {
+        channels: Vec<Vec<f32>>,
+        channel: Option<usize>,
+    },
+    EnqueueInterleaved {
+        data: Vec<f32>,
+        channels: usize,
+        downmix: ChannelDownmix,
+    },
+    EnqueuePlanar {
+        channels: Vec<Vec<f32>>,
+        downmix: ChannelDownmix,
+    },
+    Clear,
+    Grow(usize),
+    Shrink(usize),
+}
+
+/// A recorded call, along with the time it was made at.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogEntry {
+    /// The time since the recording started.
+    pub time: Duration,
+    pub call: LoggedCall,
+}
+
+/// A log that was recorded by a [`RecordingBuffer`], parsed so it can be
+/// [`replay()`]ed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplayLog {
+    entries: Vec<LogEntry>,
+    truncated: bool,
+}
+
+impl ReplayLog {
+    /// Parses a log that was recorded by a [`RecordingBuffer`].
+    ///
+    /// A log that was cut off in the middle of a record is read up to the last
+    /// complete record; See [`is_truncated()`](Self::is_truncated).
+    pub fn parse(bytes: &[u8]) -> Result<Self, ReplayError> {
+        let header = &bytes[..bytes.len().min(LOG_HEADER_LEN)];
+        if !LOG_MAGIC.starts_with(&header[..header.len().min(4)]) {
+            return Err(ReplayError::InvalidMagic);
+        }
+        if header.len() < LOG_HEADER_LEN {
+            return Ok(Self {
+                entries: Vec::new(),
+                truncated: true,
+            });
+        }
+        if header[4] != LOG_VERSION {
+            return Err(ReplayError::UnsupportedVersion(header[4]));
+        }
+
+        let mut reader = Reader {
+            bytes,
+            offset: LOG_HEADER_LEN,
+        };
+        let mut entries = Vec::new();
+        while reader.offset < bytes.len() {
+            let start = reader.offset;
+            match reader.entry() {
+                Ok(Some(entry)) => entries.push(entry),
+                Ok(None) => {
+                    return Ok(Self {
+                        entries,
+                        truncated: true,
+                    })
+                }
+                Err(kind) => {
+                    return Err(ReplayError::UnknownCall {
+                        kind,
+                        offset: start,
+                    })
+                }
+            }
+        }
+        Ok(Self {
+            entries,
+            truncated: false,
+        })
+    }
+
+    /// Returns the recorded calls, from the first to the last.
+    pub fn entries(self: &Self) -> &[LogEntry] {
+        &self.entries
+    }
+
+    /// Returns `true` if the log was cut off in the middle of a record.
+    pub fn is_truncated(self: &Self) -> bool {
+        self.truncated
+    }
+}
+
+// Reads records, returning `None` once the bytes run out in the middle of one
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(self: &mut Self, len: usize) -> Option<&'a [u8]> {
+        let end = self.offset.checked_add(len)?;
+        let bytes = self.bytes.get(self.offset..end)?;
+        self.offset = end;
+        Some(bytes)
+    }
+
+    fn u8(self: &mut Self) -> Option<u8> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+
+    fn u32(self: &mut Self) -> Option<u32> {
+        let bytes = self.take(4)?;
+        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(self: &mut Self) -> Option<u64> {
+        let mut value = [0; 8];
+        value.copy_from_slice(self.take(8)?);
+        Some(u64::from_le_bytes(value))
+    }
+
+    fn samples(self: &mut Self, len: usize) -> Option<Vec<f32>> {
+        // The length is checked against the remaining bytes before allocating
+        let bytes = self.take(len.checked_mul(4)?)?;
+        Some(
+            bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+        )
+    }
+
+    fn downmix(self: &mut Self) -> Option<Option<ChannelDownmix>> {
+        let kind = self.u8()?;
+        let channel = self.u32()? as usize;
+        Some(match kind {
+            0 => Some(ChannelDownmix::Mean),
+            1 => Some(ChannelDownmix::MaxAbs),
+            2 => Some(ChannelDownmix::Channel(channel)),
+            _ => None,
+        })
+    }
+
+    // Returns the kind of an unknown record as the error
+    fn entry(self: &mut Self) -> Result<Option<LogEntry>, u8> {
+        let Some(kind) = self.u8() else {
+            return Ok(None);
+        };
+        let Some(time) = self.u64() else {
+            return Ok(None);
+        };
+        let call = match kind {
+            ENQUEUE => self.samples(1).map(|value| LoggedCall::Enqueue(value[0])),
+            ENQUEUE_BUFFER => (|| {
+                let channel = self.u32()?;
+                let (channels, samples) = (self.u32()? as usize, self.u32()? as usize);
+                let channels = (0..channels)
+                    .map(|_| self.samples(samples))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(LoggedCall::EnqueueBuffer {
+                    channels,
+                    channel: (channel != u32::MAX).then_some(channel as usize),
+                })
+            })(),
+            ENQUEUE_INTERLEAVED => (|| {
+                let channels = self.u32()? as usize;
+                let downmix = self.downmix()?;
+                let len = self.u32()? as usize;
+                let data = self.samples(len)?;
+                Some(downmix.map(|downmix| LoggedCall::EnqueueInterleaved {
+                    data,
+                    channels,
+                    downmix,
+                }))
+            })()
+            .map(|call| call.ok_or(kind))
+            .transpose()?,
+            ENQUEUE_PLANAR => (|| {
+                let downmix = self.downmix()?;
+                let channels = (0..self.u32()?)
+                    .map(|_| {
+                        let len = self.u32()? as usize;
+                        self.samples(len)
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                Some(downmix.map(|downmix| LoggedCall::EnqueuePlanar { channels, downmix }))
+            })()
+            .map(|call| call.ok_or(kind))
+            .transpose()?,
+            CLEAR => Some(LoggedCall::Clear),
+            GROW => self.u32().map(|size| LoggedCall::Grow(size as usize)),
+            SHRINK => self.u32().map(|size| LoggedCall::Shrink(size as usize)),
+            _ => return Err(kind),
+        };
+        Ok(call.map(|call| LogEntry {
+            time: Duration::from_nanos(time),
+            call,
+        }))
+    }
+}
+
+/// An error that occurred while parsing a log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The bytes don't start with the [magic bytes](LOG_MAGIC).
+    InvalidMagic,
+    /// The log was written with a version of the format that can't be read.
+    UnsupportedVersion(u8),
+    /// A record, starting at the offset, is of an unknown kind or uses an unknown
+    /// downmix, so the log is corrupt.
+    UnknownCall { kind: u8, offset: usize },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::InvalidMagic => write!(f, "not a replay log"),
+            ReplayError::UnsupportedVersion(version) => {
+                write!(f, "unsupported replay log version {}", version)
+            }
+            ReplayError::UnknownCall { kind, offset } => {
+                write!(f, "unknown record of kind {} at byte {}", kind, offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Feeds a buffer the calls of a log, in the same order and with the same block
+/// boundaries as they were recorded.
+///
+/// The buffer needs to be configured like the recorded one - e.g. with the same
+/// size, duration and sample rate - to end up with the same contents.
+pub fn replay<V: VisualizerBuffer<f32>>(log: &ReplayLog, buffer: &mut V) {
+    for entry in log.entries() {
+        match &entry.call {
+            LoggedCall::Enqueue(value) => buffer.enqueue(*value),
+            LoggedCall::EnqueueBuffer { channels, channel } => {
+                let mut channels = channels.clone();
+                let samples = channels.first().map_or(0, |channel| channel.len());
+                let mut block = Buffer::default();
+                // SAFETY: The slices point into `channels`, which outlives the block
+                unsafe {
+                    block.set_slices(samples, |slices| {
+                        *slices = channels
+                            .iter_mut()
+                            .map(|channel| channel.as_mut_slice())
+                            .collect();
+                    });
+                }
+                buffer.enqueue_buffer(&mut block, *channel);
+            }
+            LoggedCall::EnqueueInterleaved {
+                data,
+                channels,
+                downmix,
+            } => buffer.enqueue_interleaved(data, *channels, *downmix),
+            LoggedCall::EnqueuePlanar { channels, downmix } => {
+                let channels = channels.iter().map(Vec::as_slice).collect::<Vec<_>>();
+                buffer.enqueue_planar(&channels, *downmix);
+            }
+            LoggedCall::Clear => buffer.clear(),
+            LoggedCall::Grow(size) => buffer.grow(*size),
+            LoggedCall::Shrink(size) => buffer.shrink(*size),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{replay, LoggedCall, RecordingBuffer, ReplayError, ReplayLog, LOG_HEADER_LEN};
+    use crate::utils::buffers::test_support::{contents, interleave, test_signal, with_buffer};
+    use crate::utils::{ChannelDownmix, PeakBuffer, VisualizerBuffer, WaveformBuffer};
+
+    fn peak_buffer() -> PeakBuffer {
+        let mut buffer = PeakBuffer::new(16, 1.0, 50.0);
+        buffer.set_sample_rate(100.);
+        buffer
+    }
+
+    // Feeds a buffer with every kind of call
+    fn feed<B: VisualizerBuffer<f32>>(buffer: &mut B) {
+        let mut channels = test_signal(2, 150);
+        with_buffer(&mut channels, |b| buffer.enqueue_buffer(b, None));
+        with_buffer(&mut channels, |b| buffer.enqueue_buffer(b, Some(1)));
+        for sample in &channels[0][..37] {
+            buffer.enqueue(*sample);
+        }
+        buffer.enqueue_interleaved(&interleave(&channels), 2, ChannelDownmix::MaxAbs);
+        buffer.grow(24);
+        let slices = [&channels[0][..], &channels[1][..]];
+        buffer.enqueue_planar(&slices, ChannelDownmix::Channel(1));
+        buffer.shrink(20);
+        buffer.enqueue_planar(&slices, ChannelDownmix::Mean);
+    }
+
+    #[test]
+    fn replay_matches_recording() {
+        let mut recording = RecordingBuffer::new(peak_buffer(), 1 << 20);
+        feed(&mut recording);
+        assert!(!recording.is_full());
+
+        let log = ReplayLog::parse(recording.log()).unwrap();
+        assert!(!log.is_truncated());
+        assert_eq!(log.entries().len(), 2 + 37 + 5);
+        assert!(log.entries().windows(2).all(|w| w[0].time <= w[1].time));
+
+        let mut replayed = peak_buffer();
+        replay(&log, &mut replayed);
+        assert_eq!(contents(&replayed), contents(recording.inner()));
+
+        // A log can be replayed into any compatible buffer
+        let new = || {
+            let mut buffer = WaveformBuffer::new(16, 1.0);
+            buffer.set_sample_rate(100.);
+            buffer
+        };
+        let mut expected = new();
+        feed(&mut expected);
+        let mut replayed = new();
+        replay(&log, &mut replayed);
+        assert_eq!(contents(&replayed), contents(&expected));
+    }
+
+    #[test]
+    fn truncated_logs() {
+        let mut recording = RecordingBuffer::new(peak_buffer(), 1 << 20);
+        feed(&mut recording);
+        let bytes = recording.log();
+        let full = ReplayLog::parse(bytes).unwrap();
+
+        // Cut off anywhere, a log keeps the complete records
+        for len in [
+            0,
+            3,
+            LOG_HEADER_LEN,
+            LOG_HEADER_LEN + 5,
+            bytes.len() / 2,
+            bytes.len() - 1,
+        ] {
+            let log = ReplayLog::parse(&bytes[..len]).unwrap();
+            assert_eq!(log.entries(), &full.entries()[..log.entries().len()]);
+        }
+        assert!(ReplayLog::parse(&bytes[..3]).unwrap().is_truncated());
+        assert!(!ReplayLog::parse(&bytes[..LOG_HEADER_LEN])
+            .unwrap()
+            .is_truncated());
+        let log = ReplayLog::parse(&bytes[..bytes.len() - 1]).unwrap();
+        assert!(log.is_truncated());
+        assert_eq!(log.entries().len(), full.entries().len() - 1);
+    }
+
+    #[test]
+    fn bounded_size() {
+        // The header, and three enqueued values of 13 bytes each
+        let mut recording = RecordingBuffer::new(peak_buffer(), LOG_HEADER_LEN + 13 * 3 + 12);
+        let mut expected = peak_buffer();
+        for i in 0..10 {
+            recording.enqueue(i as f32);
+            expected.enqueue(i as f32);
+        }
+        assert!(recording.is_full());
+        assert_eq!(recording.log().len(), LOG_HEADER_LEN + 13 * 3);
+        // The wrapped buffer is still fed every call
+        assert_eq!(contents(recording.inner()), contents(&expected));
+
+        // Recording stops at the first call that doesn't fit, even if later ones would
+        recording.clear();
+        let log = ReplayLog::parse(recording.log()).unwrap();
+        assert_eq!(
+            log.entries()
+                .iter()
+                .map(|e| e.call.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                LoggedCall::Enqueue(0.),
+                LoggedCall::Enqueue(1.),
+                LoggedCall::Enqueue(2.)
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_logs() {
+        assert_eq!(
+            ReplayLog::parse(b"CYMA\x01\0\0\0"),
+            Err(ReplayError::InvalidMagic)
+        );
+        assert_eq!(
+            ReplayLog::parse(b"CYRL\x02\0\0\0"),
+            Err(ReplayError::UnsupportedVersion(2))
+        );
+        let mut bytes = b"CYRL\x01\0\0\0".to_vec();
+        bytes.extend_from_slice(&[9; 13]);
+        assert_eq!(
+            ReplayLog::parse(&bytes),
+            Err(ReplayError::UnknownCall {
+                kind: 9,
+                offset: LOG_HEADER_LEN
+            })
+        );
+    }
+}
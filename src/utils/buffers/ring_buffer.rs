@@ -1,6 +1,9 @@
 use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
 use std::ops::{Index, IndexMut};
 
+use super::{check_index, name::BufferName, BufferSnapshot, SnapshotExport, VisualizerBuffer};
+
 /// A buffer that stores elements of type `T` in a First-In-First-Out manner.
 ///
 /// The `RingBuffer` struct allows enqueueing new elements onto its tail. When
@@ -8,11 +11,57 @@ use std::ops::{Index, IndexMut};
 /// oldest element is popped off the head of the buffer. Due to its fixed-size
 /// nature, the ring buffer is very fast and doesn't dynamically reallocate
 /// itself, or move any elements around when an element is added.
-#[derive(Clone, PartialEq, Eq, Default, Hash, Debug)]
+///
+/// # Ordering
+///
+/// Indices are logical positions, not positions in the storage: index 0 is always
+/// the oldest element the buffer holds, and index `len() - 1` the newest one, no
+/// matter where the buffer wrapped around internally. [`get()`](Self::get),
+/// [`iter_chronological()`](Self::iter_chronological) and
+/// [`iter_recent_first()`](Self::iter_recent_first) follow the same order, so custom
+/// views never need to know about the buffer's head.
+///
+/// ```
+/// let mut buffer = RingBuffer::<f32>::new(3);
+/// for value in [1.0, 2.0, 3.0, 4.0] {
+///     buffer.enqueue(value);
+/// }
+/// assert_eq!(buffer[0], 2.0);
+/// assert!(buffer.iter_recent_first().eq(&[4.0, 3.0, 2.0]));
+/// ```
+#[derive(Clone, Default, Debug)]
 pub struct RingBuffer<T> {
     head: usize,
     size: usize,
     data: Vec<T>,
+    name: BufferName,
+    // Changes whenever the contents change, so that readers can cache what they
+    // derive from them
+    generation: u64,
+    // The number of elements that were ever enqueued, used by the registry and by
+    // views that scroll along with the buffer
+    enqueued: u64,
+    // The number of times the buffer was cleared, used by views that fade out
+    // what they showed before a clear
+    clears: u64,
+}
+
+// Buffers are compared by their contents alone, so their name and history - how
+// often they were written to or cleared - don't make them unequal
+impl<T: PartialEq> PartialEq for RingBuffer<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.head == other.head && self.size == other.size && self.data == other.data
+    }
+}
+
+impl<T: Eq> Eq for RingBuffer<T> {}
+
+impl<T: Hash> Hash for RingBuffer<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.head.hash(state);
+        self.size.hash(state);
+        self.data.hash(state);
+    }
 }
 
 impl<T: Default + Copy> RingBuffer<T> {
@@ -22,9 +71,54 @@ impl<T: Default + Copy> RingBuffer<T> {
             head: 0,
             size,
             data: vec![T::default(); size],
+            name: BufferName::default(),
+            generation: 0,
+            enqueued: 0,
+            clears: 0,
         }
     }
 
+    /// Names the RingBuffer, which identifies it in panic messages, snapshots and
+    /// the debug registry.
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.name.set(name);
+        self
+    }
+
+    /// Sets the name of the RingBuffer; See [`with_name()`](Self::with_name).
+    pub fn set_name(self: &mut Self, name: String) {
+        self.name.set(name);
+    }
+
+    /// Returns the name of the RingBuffer, if it has one.
+    pub fn name(self: &Self) -> Option<&str> {
+        self.name.get()
+    }
+
+    // Lets the buffers that wrap a RingBuffer name it, so that their panic messages
+    // include the name
+    pub(crate) fn name_mut(self: &mut Self) -> &mut BufferName {
+        &mut self.name
+    }
+
+    /// Returns a counter that changes whenever the contents of the RingBuffer change.
+    pub fn generation(self: &Self) -> u64 {
+        self.generation
+    }
+
+    /// Returns the number of elements that were ever enqueued into the RingBuffer.
+    ///
+    /// Unlike the [`generation()`](Self::generation), this only counts enqueued
+    /// elements, and isn't reset by clearing or resizing the RingBuffer.
+    pub fn enqueued(self: &Self) -> u64 {
+        self.enqueued
+    }
+
+    /// Returns the number of times the RingBuffer was cleared.
+    pub fn clears(self: &Self) -> u64 {
+        self.clears
+    }
+
     /// Shrinks the RingBuffer to the given size.
     ///
     /// The most recently enqueued elements are preserved. This operation keeps
@@ -45,6 +139,7 @@ impl<T: Default + Copy> RingBuffer<T> {
         self.head = 0;
         self.size = size;
         self.data = data;
+        self.generation = self.generation.wrapping_add(1);
     }
 
     /// Grows the RingBuffer.
@@ -66,6 +161,7 @@ impl<T: Default + Copy> RingBuffer<T> {
         self.data = data;
         self.head = self.size;
         self.size = size;
+        self.generation = self.generation.wrapping_add(1);
     }
 
     /// Resizes the buffer to the given size.
@@ -87,23 +183,75 @@ impl<T: Default + Copy> RingBuffer<T> {
     /// Enqueues an element into the RingBuffer.
     ///
     /// Once enqueued, the value is situated at the tail of the buffer and the
-    /// oldest element is removed from the head.
+    /// oldest element is removed from the head. An empty RingBuffer discards it.
     pub fn enqueue(self: &mut Self, value: T) {
+        if self.size == 0 {
+            return;
+        }
         self.data[self.head] = value;
         self.head = (self.head + 1) % self.size;
+        self.generation = self.generation.wrapping_add(1);
+        self.enqueued = self.enqueued.wrapping_add(1);
+    }
+
+    /// Returns the element at the given index, or `None` if the index is out of range.
+    pub fn get(self: &Self, index: usize) -> Option<&T> {
+        (index < self.size).then(|| &self.data[self.position(index)])
+    }
+
+    /// Returns the element `n` places before the most recently enqueued one, or
+    /// `None` if the RingBuffer holds `n` elements or less.
+    ///
+    /// `get_newest(0)` returns the newest element.
+    pub fn get_newest(self: &Self, n: usize) -> Option<&T> {
+        self.get(self.size.checked_sub(n + 1)?)
     }
 
+    /// Returns the most recently enqueued element, or the default value if the
+    /// RingBuffer is empty.
     pub fn peek(self: &Self) -> T {
-        self.data[(self.size + self.head - 1) % self.size]
+        self.get_newest(0).copied().unwrap_or_default()
     }
 
+    /// Returns the oldest element, or the default value if the RingBuffer is empty.
     pub fn tail(self: &Self) -> T {
-        self.data[(self.size + self.head) % self.size]
+        self.get(0).copied().unwrap_or_default()
+    }
+
+    /// Returns an iterator over the elements, from the oldest to the newest; See
+    /// [`iter_chronological()`](Self::iter_chronological).
+    pub fn iter(self: &Self) -> impl DoubleEndedIterator<Item = &T> + ExactSizeIterator + '_ {
+        self.iter_chronological()
+    }
+
+    /// Returns an iterator over the elements in the order they were enqueued, from
+    /// the oldest to the newest - the same order as their indices.
+    pub fn iter_chronological(
+        self: &Self,
+    ) -> impl DoubleEndedIterator<Item = &T> + ExactSizeIterator + '_ {
+        (0..self.size).map(|index| &self.data[self.position(index)])
+    }
+
+    /// Returns an iterator over the elements from the newest to the oldest, e.g. for
+    /// views that draw the most recent audio first.
+    pub fn iter_recent_first(
+        self: &Self,
+    ) -> impl DoubleEndedIterator<Item = &T> + ExactSizeIterator + '_ {
+        self.iter_chronological().rev()
+    }
+
+    /// Returns a copy of the elements, from the oldest to the newest.
+    ///
+    /// This allocates, so it shouldn't be called on the audio thread.
+    pub fn to_vec(self: &Self) -> Vec<T> {
+        self.iter().copied().collect()
     }
 
     /// Clears the entire buffer, filling it with default values (usually 0)
     pub fn clear(self: &mut Self) {
         self.data.iter_mut().for_each(|x| *x = T::default());
+        self.generation = self.generation.wrapping_add(1);
+        self.clears = self.clears.wrapping_add(1);
     }
 
     pub fn len(self: &Self) -> usize {
@@ -111,34 +259,130 @@ impl<T: Default + Copy> RingBuffer<T> {
     }
 }
 
+impl<T> RingBuffer<T> {
+    // The position of a logical index - counted from the oldest element - inside the data
+    fn position(self: &Self, index: usize) -> usize {
+        (self.head + index) % self.size
+    }
+}
+
 impl<T> Index<usize> for RingBuffer<T> {
     type Output = T;
 
     fn index(&self, index: usize) -> &Self::Output {
-        if index >= self.size {
-            panic!(
-                "Invalid ring buffer access: Index {} is out of range for ring buffer of size {}",
-                index, self.size
-            );
-        }
-        &self.data[(self.head + index) % self.size]
+        check_index("ring buffer", self.name.get(), index, self.size);
+        &self.data[self.position(index)]
     }
 }
 impl<T> IndexMut<usize> for RingBuffer<T> {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        if index >= self.size {
-            panic!(
-                "Invalid ring buffer access: Index {} is out of range for ring buffer of size {}",
-                index, self.size
-            );
+        check_index("ring buffer", self.name.get(), index, self.size);
+        self.generation = self.generation.wrapping_add(1);
+        let position = self.position(index);
+        &mut self.data[position]
+    }
+}
+
+/// Stores every enqueued value as-is, without any downsampling.
+impl VisualizerBuffer<f32> for RingBuffer<f32> {
+    fn enqueue(self: &mut Self, value: f32) {
+        RingBuffer::enqueue(self, value);
+    }
+
+    fn enqueue_buffer(
+        self: &mut Self,
+        buffer: &mut nih_plug::buffer::Buffer,
+        channel: Option<usize>,
+    ) {
+        match channel {
+            Some(channel) => {
+                for sample in buffer.as_slice()[channel].iter() {
+                    RingBuffer::enqueue(self, *sample);
+                }
+            }
+            None => {
+                for sample in buffer.iter_samples() {
+                    RingBuffer::enqueue(
+                        self,
+                        (1. / (&sample).len() as f32) * sample.into_iter().map(|x| *x).sum::<f32>(),
+                    );
+                }
+            }
+        }
+    }
+
+    fn clear(self: &mut Self) {
+        RingBuffer::clear(self);
+    }
+
+    fn grow(self: &mut Self, size: usize) {
+        RingBuffer::grow(self, size);
+    }
+
+    fn shrink(self: &mut Self, size: usize) {
+        RingBuffer::shrink(self, size);
+    }
+
+    fn len(self: &Self) -> usize {
+        RingBuffer::len(self)
+    }
+
+    fn generation(self: &Self) -> Option<u64> {
+        Some(RingBuffer::generation(self))
+    }
+
+    fn enqueued(self: &Self) -> Option<u64> {
+        Some(RingBuffer::enqueued(self))
+    }
+
+    fn clears(self: &Self) -> Option<u64> {
+        Some(RingBuffer::clears(self))
+    }
+}
+
+impl SnapshotExport for RingBuffer<f32> {
+    fn export(&self) -> BufferSnapshot {
+        BufferSnapshot {
+            name: self.name().map(str::to_string),
+            ..BufferSnapshot::new(
+                "RingBuffer",
+                &["value"],
+                (0..self.len()).map(|i| vec![self[i]]).collect(),
+            )
+        }
+    }
+}
+
+#[cfg(feature = "debug-tools")]
+impl<T: Default + Copy> super::registry::RegisteredBuffer for RingBuffer<T> {
+    fn info(&self) -> super::registry::BufferInfo {
+        super::registry::BufferInfo {
+            buffer: "RingBuffer",
+            name: self.name().map(str::to_string),
+            size: self.len(),
+            duration: None,
+            sample_rate: None,
+            enqueued: self.enqueued(),
         }
-        &mut self.data[(self.head + index) % self.size]
+    }
+}
+
+#[cfg(feature = "ffi")]
+impl super::ffi::WriteSnapshot for RingBuffer<f32> {
+    fn write_snapshot(&self, out: &mut Vec<u8>) {
+        super::ffi::SnapshotHeader::new(super::ffi::ValueFormat::F32, self.len(), None, None)
+            .write(out, (0..self.len()).map(|i| self[i]));
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::RingBuffer;
+    use crate::utils::buffers::test_support::{
+        assert_enqueue_buffer_matches_enqueue, assert_interleaved_matches_buffer,
+    };
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
 
     #[test]
     fn basics() {
@@ -187,6 +431,44 @@ mod tests {
         assert_eq!(rb.data, vec![0; 4]);
     }
 
+    #[test]
+    fn clears_and_enqueued() {
+        let mut rb = RingBuffer::<i32>::new(4);
+        assert_eq!((rb.clears(), rb.enqueued()), (0, 0));
+
+        rb.enqueue(1);
+        rb.enqueue(2);
+        rb.enqueue(3);
+        rb.clear();
+        assert_eq!((rb.clears(), rb.enqueued()), (1, 3));
+
+        // Clearing doesn't reset the number of enqueued elements
+        rb.enqueue(4);
+        assert_eq!((rb.clears(), rb.enqueued()), (1, 4));
+    }
+
+    #[test]
+    fn equal_regardless_of_history() {
+        // Wraps around once, so that only the contents and counters differ
+        let mut cleared = RingBuffer::<i32>::new(4);
+        for value in 1..=4 {
+            cleared.enqueue(value);
+        }
+        cleared.clear();
+        let untouched = RingBuffer::<i32>::new(4);
+        assert_eq!(cleared, untouched);
+
+        let hash = |rb: &RingBuffer<i32>| {
+            let mut hasher = DefaultHasher::new();
+            rb.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&cleared), hash(&untouched));
+
+        cleared.enqueue(1);
+        assert_ne!(cleared, untouched);
+    }
+
     #[test]
     fn resize() {
         let mut rb = RingBuffer::<i32>::new(4);
@@ -277,6 +559,43 @@ mod tests {
         rb[4];
     }
 
+    #[test]
+    #[should_panic(expected = "ring buffer \"peak_in\" of size 4")]
+    fn invalid_access_named() {
+        let rb = RingBuffer::<i32>::new(4).with_name("peak_in");
+        rb[4];
+    }
+
+    #[test]
+    fn get() {
+        // Empty
+        let rb = RingBuffer::<i32>::new(0);
+        assert_eq!(rb.get(0), None);
+        assert_eq!(rb.get_newest(0), None);
+
+        // Partially filled
+        let mut rb = RingBuffer::<i32>::new(4);
+        rb.enqueue(1);
+        rb.enqueue(2);
+        assert_eq!(rb.get(0), Some(&0));
+        assert_eq!(rb.get(3), Some(&2));
+        assert_eq!(rb.get(4), None);
+        assert_eq!(rb.get_newest(0), Some(&2));
+        assert_eq!(rb.get_newest(3), Some(&0));
+        assert_eq!(rb.get_newest(4), None);
+
+        // Wrapped around
+        for value in 3..=7 {
+            rb.enqueue(value);
+        }
+        assert_eq!(rb.get(0), Some(&4));
+        assert_eq!(rb.get(3), Some(&7));
+        assert_eq!(rb.get(4), None);
+        assert_eq!(rb.get_newest(0), Some(&7));
+        assert_eq!(rb.get_newest(3), Some(&4));
+        assert_eq!(rb.get_newest(4), None);
+    }
+
     #[test]
     fn peek() {
         let mut rb = RingBuffer::<i32>::new(4);
@@ -293,4 +612,75 @@ mod tests {
         assert_eq!(rb.peek(), 7);
         assert_eq!(rb.tail(), 4);
     }
+
+    // Checks indexing and both iterators against the expected contents, from the
+    // oldest to the newest element
+    fn assert_order(rb: &RingBuffer<i32>, expected: &[i32]) {
+        let indexed: Vec<i32> = (0..rb.len()).map(|i| rb[i]).collect();
+        assert_eq!(indexed, expected);
+        assert_eq!(rb.iter_chronological().len(), expected.len());
+        assert!(rb
+            .iter_chronological()
+            .copied()
+            .eq(expected.iter().copied()));
+        assert_eq!(rb.iter_recent_first().len(), expected.len());
+        assert!(rb
+            .iter_recent_first()
+            .copied()
+            .eq(expected.iter().rev().copied()));
+        // Both ends of either iterator can be consumed
+        assert!(rb.iter_chronological().rev().eq(rb.iter_recent_first()));
+        assert_eq!(rb.iter_recent_first().next_back(), expected.first());
+        assert_eq!(rb.get_newest(0), expected.last());
+    }
+
+    #[test]
+    fn chronological_order() {
+        let mut rb = RingBuffer::<i32>::new(4);
+        assert_order(&rb, &[0, 0, 0, 0]);
+
+        // Not wrapped yet
+        rb.enqueue(1);
+        rb.enqueue(2);
+        assert_order(&rb, &[0, 0, 1, 2]);
+
+        // Exactly full, with the head back at the start of the storage
+        rb.enqueue(3);
+        rb.enqueue(4);
+        assert_eq!(rb.head, 0);
+        assert_order(&rb, &[1, 2, 3, 4]);
+
+        // Wrapped around several times, with the head in the middle of the storage
+        for value in 5..=14 {
+            rb.enqueue(value);
+        }
+        assert_eq!(rb.head, 2);
+        assert_order(&rb, &[11, 12, 13, 14]);
+
+        // Clearing keeps the head where it is
+        rb.clear();
+        assert_order(&rb, &[0, 0, 0, 0]);
+        rb.enqueue(15);
+        assert_order(&rb, &[0, 0, 0, 15]);
+
+        // Growing pads the oldest end, and shrinking keeps the newest elements
+        rb.enqueue(16);
+        rb.enqueue(17);
+        rb.grow(6);
+        assert_order(&rb, &[0, 0, 0, 15, 16, 17]);
+        rb.enqueue(18);
+        assert_order(&rb, &[0, 0, 15, 16, 17, 18]);
+        rb.shrink(3);
+        assert_order(&rb, &[16, 17, 18]);
+        rb.enqueue(19);
+        assert_order(&rb, &[17, 18, 19]);
+        rb.resize(0);
+        assert_order(&rb, &[]);
+    }
+
+    #[test]
+    fn enqueue_buffer() {
+        assert_enqueue_buffer_matches_enqueue(|| RingBuffer::<f32>::new(16), 40);
+        assert_interleaved_matches_buffer(|| RingBuffer::<f32>::new(16), 40);
+    }
 }
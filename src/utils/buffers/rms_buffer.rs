@@ -1,7 +1,12 @@
 use nih_plug::buffer::Buffer;
+use std::fmt::{self, Debug};
 use std::ops::{Index, IndexMut};
 
-use super::{RingBuffer, VisualizerBuffer};
+use super::super::config_error::{check_duration, check_size, clamp_duration, ConfigError};
+use super::super::markers::rescale_samples;
+use super::channel_source::{for_each_routed, ChannelSource, SourceRouting, SourceSelector};
+use super::playback_rate::{retime, PlaybackRate};
+use super::{check_index, BufferSnapshot, RingBuffer, SnapshotExport, VisualizerBuffer};
 
 /// Stores RMS amplitudes over time.
 ///
@@ -31,6 +36,14 @@ pub struct RMSBuffer {
     /// The buffer of squared sums - This is needed so that the squared samples can
     /// be removed from the `sum_acc`
     squared_buffer: RingBuffer<f32>,
+    /// Whether the plug-in is rendering offline, in which case nothing is enqueued
+    offline: bool,
+    /// The channels that are read from a buffer that's enqueued without a channel
+    routing: SourceRouting,
+    /// The rate the host plays back at, which the duration follows
+    playback: PlaybackRate,
+    /// The number of samples enqueued so far, which is never reset
+    processed: u64,
 }
 
 impl RMSBuffer {
@@ -42,8 +55,8 @@ impl RMSBuffer {
     pub fn new(size: usize, duration: f32, rms_duration: f32) -> Self {
         Self {
             buffer: RingBuffer::<f32>::new(size),
-            duration,
-            rms_duration,
+            duration: clamp_duration("RMS buffer", "duration", duration),
+            rms_duration: clamp_duration("RMS buffer", "RMS duration", rms_duration),
 
             // These values will be needed internally.
             sample_delta: 0.0,
@@ -51,17 +64,94 @@ impl RMSBuffer {
             sum_acc: 0.0,
             sample_rate: 0.0,
             squared_buffer: RingBuffer::<f32>::new(0),
+            offline: false,
+            routing: SourceRouting::default(),
+            playback: PlaybackRate::default(),
+            processed: 0,
         }
     }
 
+    /// Constructs a new `RMSBuffer`, or returns a [`ConfigError`] if the size is 0, or
+    /// either duration isn't positive; See [`new()`](Self::new).
+    pub fn try_new(size: usize, duration: f32, rms_duration: f32) -> Result<Self, ConfigError> {
+        check_size("RMS buffer", "size", size)?;
+        check_duration("RMS buffer", "duration", duration)?;
+        check_duration("RMS buffer", "RMS duration", rms_duration)?;
+        Ok(Self::new(size, duration, rms_duration))
+    }
+
+    /// Names the `RMSBuffer`, which identifies it in panic messages, snapshots and the
+    /// debug registry.
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.buffer.name_mut().set(name);
+        self
+    }
+
+    /// Sets the name of the `RMSBuffer`; See [`with_name()`](Self::with_name).
+    pub fn set_name(self: &mut Self, name: String) {
+        self.buffer.name_mut().set(name);
+    }
+
+    /// Returns the name of the `RMSBuffer`, if it has one.
+    pub fn name(self: &Self) -> Option<&str> {
+        self.buffer.name()
+    }
+
     pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.processed = rescale_samples(self.processed, self.sample_rate, sample_rate);
         self.sample_rate = sample_rate;
         self.update();
     }
 
+    /// Hints whether the plug-in is rendering offline, which skips all enqueueing
+    /// while it is. See [`PeakBuffer::set_offline`](super::PeakBuffer::set_offline).
+    pub fn set_offline(self: &mut Self, offline: bool) {
+        self.offline = offline;
+    }
+
+    /// Sets the channels that are read from a [`Buffer`] that's enqueued without a
+    /// channel; See [`PeakBuffer::set_source()`](super::PeakBuffer::set_source).
+    pub fn set_source(self: &mut Self, source: ChannelSource) {
+        self.routing.selector().set(source);
+    }
+
+    /// Returns a handle that selects the source of the `RMSBuffer` without locking
+    /// it; See [`set_source()`](Self::set_source).
+    pub fn source_selector(self: &Self) -> SourceSelector {
+        self.routing.selector()
+    }
+
+    /// Sets whether the `RMSBuffer` is cleared when its source changes, which it is
+    /// by default.
+    pub fn set_clear_on_source_change(self: &mut Self, clear: bool) {
+        self.routing.set_clear_on_change(clear);
+    }
+
+    /// Reports the rate at which the host plays the project back, so that the buffer
+    /// keeps spanning its duration of what's heard; See
+    /// [`PeakBuffer::set_playback_rate()`](super::PeakBuffer::set_playback_rate).
+    ///
+    /// The RMS window keeps spanning the same number of samples.
+    pub fn set_playback_rate(self: &mut Self, rate: f32) {
+        if self.playback.set(rate) {
+            let sample_delta = self.sample_delta();
+            self.t = retime(self.t, self.sample_delta, sample_delta);
+            self.sample_delta = sample_delta;
+        }
+    }
+
+    /// Returns the playback rate that was reported last.
+    pub fn playback_rate(self: &Self) -> f32 {
+        self.playback.rate()
+    }
+
+    fn sample_delta(self: &Self) -> f32 {
+        let samples_per_second = self.playback.samples_per_second(self.sample_rate);
+        ((samples_per_second as f64 * self.duration as f64) / self.buffer.len() as f64) as f32
+    }
+
     fn update(&mut self) {
-        self.sample_delta =
-            ((self.sample_rate as f64 * self.duration as f64) / self.buffer.len() as f64) as f32;
+        self.sample_delta = self.sample_delta();
 
         let rms_size = (self.sample_rate as f64 * (self.rms_duration as f64 / 1000.0)) as usize;
         self.squared_buffer.resize(rms_size);
@@ -74,18 +164,23 @@ impl Index<usize> for RMSBuffer {
     type Output = f32;
 
     fn index(&self, index: usize) -> &Self::Output {
+        check_index("RMS buffer", self.name(), index, self.buffer.len());
         &self.buffer[index]
     }
 }
-
 impl IndexMut<usize> for RMSBuffer {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        check_index("RMS buffer", self.name(), index, self.buffer.len());
         &mut self.buffer[index]
     }
 }
 
 impl VisualizerBuffer<f32> for RMSBuffer {
     fn enqueue(self: &mut Self, value: f32) {
+        if self.offline {
+            return;
+        }
+        self.processed += 1;
         let squared_value = value * value;
 
         self.sum_acc -= self.squared_buffer.tail();
@@ -106,6 +201,9 @@ impl VisualizerBuffer<f32> for RMSBuffer {
     }
 
     fn enqueue_buffer(self: &mut Self, buffer: &mut Buffer, channel: Option<usize>) {
+        if self.offline {
+            return;
+        }
         match channel {
             Some(channel) => {
                 for sample in buffer.as_slice()[channel].into_iter() {
@@ -113,10 +211,20 @@ impl VisualizerBuffer<f32> for RMSBuffer {
                 }
             }
             None => {
-                for sample in buffer.iter_samples() {
-                    self.enqueue(
-                        (1. / (&sample).len() as f32) * sample.into_iter().map(|x| *x).sum::<f32>(),
-                    );
+                let (source, changed) = self.routing.update();
+                if changed {
+                    self.clear();
+                }
+                match source {
+                    ChannelSource::DownmixAvg => {
+                        for sample in buffer.iter_samples() {
+                            self.enqueue(
+                                (1. / (&sample).len() as f32)
+                                    * sample.into_iter().map(|x| *x).sum::<f32>(),
+                            );
+                        }
+                    }
+                    source => for_each_routed(buffer, source, |sample| self.enqueue(sample)),
                 }
             }
         }
@@ -142,4 +250,80 @@ impl VisualizerBuffer<f32> for RMSBuffer {
     fn len(self: &Self) -> usize {
         self.buffer.len()
     }
+
+    fn generation(self: &Self) -> Option<u64> {
+        Some(self.buffer.generation())
+    }
+
+    fn enqueued(self: &Self) -> Option<u64> {
+        Some(self.buffer.enqueued())
+    }
+
+    fn sample_clock(self: &Self) -> Option<(u64, f32)> {
+        Some((self.processed, self.sample_rate))
+    }
+
+    fn clears(self: &Self) -> Option<u64> {
+        Some(self.buffer.clears())
+    }
+
+    fn duration(self: &Self) -> Option<f32> {
+        Some(self.duration)
+    }
+}
+
+impl SnapshotExport for RMSBuffer {
+    fn export(&self) -> BufferSnapshot {
+        BufferSnapshot {
+            name: self.name().map(str::to_string),
+            duration: Some(self.duration),
+            rms_duration: Some(self.rms_duration),
+            sample_rate: Some(self.sample_rate),
+            ..BufferSnapshot::new(
+                "RMSBuffer",
+                &["value"],
+                (0..self.buffer.len())
+                    .map(|i| vec![self.buffer[i]])
+                    .collect(),
+            )
+        }
+    }
+}
+
+impl Debug for RMSBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RMSBuffer")
+            .field("name", &self.name())
+            .field("size", &self.buffer.len())
+            .field("duration", &self.duration)
+            .field("sample_rate", &self.sample_rate)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "debug-tools")]
+impl super::registry::RegisteredBuffer for RMSBuffer {
+    fn info(&self) -> super::registry::BufferInfo {
+        super::registry::BufferInfo {
+            buffer: "RMSBuffer",
+            name: self.name().map(str::to_string),
+            size: self.buffer.len(),
+            duration: Some(self.duration),
+            sample_rate: Some(self.sample_rate),
+            enqueued: self.buffer.enqueued(),
+        }
+    }
+}
+
+#[cfg(feature = "ffi")]
+impl super::ffi::WriteSnapshot for RMSBuffer {
+    fn write_snapshot(&self, out: &mut Vec<u8>) {
+        super::ffi::SnapshotHeader::new(
+            super::ffi::ValueFormat::F32,
+            self.buffer.len(),
+            Some(self.duration),
+            Some(self.sample_rate),
+        )
+        .write(out, (0..self.buffer.len()).map(|i| self.buffer[i]));
+    }
 }
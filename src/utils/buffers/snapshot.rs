@@ -0,0 +1,267 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A copy of a buffer's contents and configuration at a point in time.
+///
+/// Snapshots are useful for analysing what a buffer actually contained, e.g.
+/// when a visualizer doesn't look the way it should. They can be written to and
+/// read from CSV files via [`to_csv()`](Self::to_csv) and
+/// [`from_csv()`](Self::from_csv).
+///
+/// Snapshots are created using [`SnapshotExport::export()`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct BufferSnapshot {
+    /// The type of buffer the snapshot was taken from, e.g. `PeakBuffer`.
+    pub buffer: String,
+    /// The name of the buffer, if it has one.
+    pub name: Option<String>,
+    /// The size of the buffer.
+    pub size: usize,
+    /// The duration (in seconds) of the audio data inside the buffer.
+    pub duration: Option<f32>,
+    /// The decay time of the buffer, in milliseconds.
+    pub decay: Option<f32>,
+    /// The time window in which RMS values are calculated, in milliseconds.
+    pub rms_duration: Option<f32>,
+    /// The sample rate of the audio the buffer was fed.
+    pub sample_rate: Option<f32>,
+    /// The range of the values inside the buffer, e.g. the bin range of a histogram.
+    pub range: Option<(f32, f32)>,
+    /// The time at which the snapshot was taken.
+    pub timestamp: SystemTime,
+    /// The names of the values that make up each element, e.g. `min` and `max`.
+    pub columns: Vec<String>,
+    /// The elements of the buffer, from the oldest to the newest.
+    pub values: Vec<Vec<f32>>,
+}
+
+/// Allows exporting a [`BufferSnapshot`] of a buffer.
+///
+/// Exporting copies the entire buffer and allocates, so it should **never** be done
+/// on the audio thread. Call it from the editor instead - it is also implemented
+/// for `Mutex<B>`, so a shared buffer is only locked for as long as it takes to copy
+/// its contents:
+///
+/// ```
+/// let snapshot = self.peak_buffer.export();
+/// snapshot.to_csv(File::create("peak_buffer.csv")?)?;
+/// ```
+pub trait SnapshotExport {
+    /// Takes a snapshot of the buffer's contents and configuration.
+    fn export(&self) -> BufferSnapshot;
+}
+
+impl<B: SnapshotExport> SnapshotExport for Mutex<B> {
+    fn export(&self) -> BufferSnapshot {
+        // A poisoned buffer is still worth looking at when triaging a bug
+        match self.lock() {
+            Ok(buffer) => buffer.export(),
+            Err(poisoned) => poisoned.into_inner().export(),
+        }
+    }
+}
+
+impl BufferSnapshot {
+    /// Constructs a new snapshot with no configuration, taken at the current time.
+    pub fn new(buffer: &str, columns: &[&str], values: Vec<Vec<f32>>) -> Self {
+        Self {
+            buffer: buffer.to_string(),
+            name: None,
+            size: values.len(),
+            duration: None,
+            decay: None,
+            rms_duration: None,
+            sample_rate: None,
+            range: None,
+            timestamp: SystemTime::now(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            values,
+        }
+    }
+
+    /// Writes the snapshot as CSV.
+    ///
+    /// The configuration is written as `#`-prefixed lines at the top of the file,
+    /// followed by a header and one row per element of the buffer.
+    pub fn to_csv(&self, writer: impl Write) -> io::Result<()> {
+        let mut writer = io::BufWriter::new(writer);
+
+        writeln!(writer, "# buffer,{}", self.buffer)?;
+        if let Some(name) = &self.name {
+            writeln!(writer, "# name,{}", name)?;
+        }
+        writeln!(writer, "# size,{}", self.size)?;
+        if let Some(duration) = self.duration {
+            writeln!(writer, "# duration,{}", duration)?;
+        }
+        if let Some(decay) = self.decay {
+            writeln!(writer, "# decay,{}", decay)?;
+        }
+        if let Some(rms_duration) = self.rms_duration {
+            writeln!(writer, "# rms_duration,{}", rms_duration)?;
+        }
+        if let Some(sample_rate) = self.sample_rate {
+            writeln!(writer, "# sample_rate,{}", sample_rate)?;
+        }
+        if let Some((min, max)) = self.range {
+            writeln!(writer, "# range,{},{}", min, max)?;
+        }
+        let timestamp = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        writeln!(
+            writer,
+            "# timestamp,{},{}",
+            timestamp.as_secs(),
+            timestamp.subsec_nanos()
+        )?;
+
+        writeln!(writer, "index,{}", self.columns.join(","))?;
+        for (i, row) in self.values.iter().enumerate() {
+            write!(writer, "{}", i)?;
+            for value in row {
+                write!(writer, ",{}", value)?;
+            }
+            writeln!(writer)?;
+        }
+
+        writer.flush()
+    }
+
+    /// Reads a snapshot that was written using [`to_csv()`](Self::to_csv).
+    pub fn from_csv(reader: impl Read) -> io::Result<Self> {
+        let mut snapshot = Self::new("", &[], vec![]);
+        snapshot.timestamp = UNIX_EPOCH;
+
+        let mut size = None;
+        let mut has_header = false;
+
+        for line in BufReader::new(reader).lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(config) = line.strip_prefix('#') {
+                let mut fields = config.trim().split(',');
+                let key = fields.next().unwrap_or_default();
+                let fields = fields.collect::<Vec<_>>();
+
+                match key {
+                    "buffer" => snapshot.buffer = fields.join(","),
+                    "name" => snapshot.name = Some(fields.join(",")),
+                    "size" => size = Some(parse_field(&fields, 0)?),
+                    "duration" => snapshot.duration = Some(parse_field(&fields, 0)?),
+                    "decay" => snapshot.decay = Some(parse_field(&fields, 0)?),
+                    "rms_duration" => snapshot.rms_duration = Some(parse_field(&fields, 0)?),
+                    "sample_rate" => snapshot.sample_rate = Some(parse_field(&fields, 0)?),
+                    "range" => {
+                        snapshot.range = Some((parse_field(&fields, 0)?, parse_field(&fields, 1)?))
+                    }
+                    "timestamp" => {
+                        snapshot.timestamp = UNIX_EPOCH
+                            + Duration::new(parse_field(&fields, 0)?, parse_field(&fields, 1)?)
+                    }
+                    // Unknown configuration is skipped, so that newer files can still be read
+                    _ => {}
+                }
+            } else if !has_header {
+                snapshot.columns = line.split(',').skip(1).map(str::to_string).collect();
+                has_header = true;
+            } else {
+                let fields = line.split(',').skip(1).collect::<Vec<_>>();
+                if fields.len() != snapshot.columns.len() {
+                    return Err(invalid_data(format!(
+                        "expected {} values per row, found {}",
+                        snapshot.columns.len(),
+                        fields.len()
+                    )));
+                }
+                snapshot.values.push(
+                    (0..fields.len())
+                        .map(|i| parse_field(&fields, i))
+                        .collect::<io::Result<_>>()?,
+                );
+            }
+        }
+
+        if !has_header {
+            return Err(invalid_data("missing header".to_string()));
+        }
+        snapshot.size = size.unwrap_or(snapshot.values.len());
+
+        Ok(snapshot)
+    }
+}
+
+fn parse_field<T: std::str::FromStr>(fields: &[&str], index: usize) -> io::Result<T> {
+    let field = fields
+        .get(index)
+        .ok_or_else(|| invalid_data("missing field".to_string()))?;
+    field
+        .trim()
+        .parse()
+        .map_err(|_| invalid_data(format!("invalid value `{}`", field)))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BufferSnapshot, SnapshotExport};
+    use crate::utils::{PeakBuffer, VisualizerBuffer, WaveformBuffer};
+    use std::sync::Mutex;
+
+    #[test]
+    fn export() {
+        let mut buffer = PeakBuffer::new(4, 1.0, 50.0);
+        buffer.set_sample_rate(8.0);
+        for i in 0..16 {
+            buffer.enqueue(i as f32);
+        }
+
+        let snapshot = Mutex::new(buffer.clone()).export();
+
+        assert_eq!(snapshot.buffer, "PeakBuffer");
+        assert_eq!(snapshot.size, 4);
+        assert_eq!(snapshot.duration, Some(1.0));
+        assert_eq!(snapshot.decay, Some(50.0));
+        assert_eq!(snapshot.sample_rate, Some(8.0));
+        assert_eq!(snapshot.columns, vec!["value"]);
+        // The values are ordered from the oldest to the newest
+        assert_eq!(
+            snapshot.values,
+            (0..4).map(|i| vec![buffer[i]]).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let mut buffer = WaveformBuffer::new(8, 1.0);
+        buffer.set_sample_rate(64.0);
+        for i in 0..64 {
+            buffer.enqueue((i as f32 * 0.3).sin());
+        }
+
+        let mut snapshot = buffer.with_name("scope, left").export();
+        snapshot.range = Some((-1.0, 1.0));
+        assert_eq!(snapshot.name.as_deref(), Some("scope, left"));
+
+        let mut csv = Vec::new();
+        snapshot.to_csv(&mut csv).unwrap();
+
+        assert_eq!(BufferSnapshot::from_csv(csv.as_slice()).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn invalid_csv() {
+        assert!(BufferSnapshot::from_csv("".as_bytes()).is_err());
+        assert!(BufferSnapshot::from_csv("index,value\n0,1,2\n".as_bytes()).is_err());
+        assert!(BufferSnapshot::from_csv("index,value\n0,abc\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use super::RingBuffer;
+
+/// Records the tag that was set during each element of a buffer, e.g. whether the
+/// plug-in was bypassed.
+///
+/// The tags are enqueued along with the buffer's elements, so they scroll and get
+/// evicted together. Like the [transport flags](super::transport::TransportFlags),
+/// each element is stamped with the tag that was set halfway through it, so a
+/// change is resolved to the nearest element boundary.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SlotTags {
+    tags: RingBuffer<u8>,
+    tag: u8,
+    // The tag of the element that is currently being accumulated
+    slot_tag: u8,
+}
+
+impl SlotTags {
+    pub(crate) fn new(size: usize) -> Self {
+        Self {
+            tags: RingBuffer::new(size),
+            tag: 0,
+            slot_tag: 0,
+        }
+    }
+
+    /// Sets the tag that the following elements are stamped with.
+    ///
+    /// * `progress` - How far along the element that is being accumulated is, from 0 to 1
+    pub(crate) fn set_tag(self: &mut Self, tag: u8, progress: Option<f32>) {
+        if tag == self.tag {
+            return;
+        }
+        self.tag = tag;
+        // Before the element's midpoint, the new tag covers most of it
+        if progress.unwrap_or(0.) < 0.5 {
+            self.slot_tag = tag;
+        }
+    }
+
+    /// Stamps the element that was just enqueued into the buffer.
+    pub(crate) fn enqueued(self: &mut Self) {
+        self.tags.enqueue(self.slot_tag);
+        self.slot_tag = self.tag;
+    }
+
+    /// Returns the tag of the element at the index.
+    pub(crate) fn get(self: &Self, index: usize) -> Option<u8> {
+        self.tags.get(index).copied()
+    }
+
+    /// Clears the tags, resizing them to the buffer's length.
+    ///
+    /// The cleared elements take the current tag, so that clearing the buffer
+    /// doesn't show a change that never happened.
+    pub(crate) fn reset(self: &mut Self, size: usize) {
+        if self.tags.len() != size {
+            self.tags = RingBuffer::new(size);
+        }
+        for i in 0..size {
+            self.tags[i] = self.tag;
+        }
+        self.slot_tag = self.tag;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{PeakBuffer, VisualizerBuffer};
+
+    // 10 samples per element
+    fn buffer() -> PeakBuffer {
+        let mut buffer = PeakBuffer::new(8, 1.0, 0.0).with_tag_tracking();
+        buffer.set_sample_rate(80.);
+        buffer
+    }
+
+    fn tags(buffer: &PeakBuffer) -> Vec<u8> {
+        (0..buffer.len()).map(|i| buffer.tag(i).unwrap()).collect()
+    }
+
+    // The indices of the elements whose tag differs from their predecessor's
+    fn transitions(buffer: &PeakBuffer) -> Vec<usize> {
+        let tags = tags(buffer);
+        (1..tags.len())
+            .filter(|i| tags[*i] != tags[i - 1])
+            .collect()
+    }
+
+    fn enqueue(buffer: &mut PeakBuffer, samples: usize) {
+        for _ in 0..samples {
+            buffer.enqueue(0.5);
+        }
+    }
+
+    #[test]
+    fn transitions_line_up_with_samples() {
+        // The first element is finalized by the 11th sample, and every following one
+        // by each 10th sample after it
+        let mut buffer = buffer();
+        enqueue(&mut buffer, 41);
+        buffer.set_tag(1);
+        enqueue(&mut buffer, 40);
+        assert_eq!(tags(&buffer), vec![0, 0, 0, 0, 1, 1, 1, 1]);
+
+        // Changing the tag 2 samples into an element stamps it, and changing it 9
+        // samples into an element stamps the next one
+        enqueue(&mut buffer, 1);
+        buffer.set_tag(2);
+        enqueue(&mut buffer, 17);
+        buffer.set_tag(1);
+        enqueue(&mut buffer, 12);
+        assert_eq!(tags(&buffer), vec![0, 1, 1, 1, 1, 2, 2, 1]);
+        assert_eq!(transitions(&buffer), vec![1, 5, 7]);
+    }
+
+    #[test]
+    fn tags_scroll_and_evict() {
+        let mut buffer = buffer();
+        enqueue(&mut buffer, 1);
+        buffer.set_tag(1);
+        enqueue(&mut buffer, 30);
+        buffer.set_tag(0);
+        enqueue(&mut buffer, 20);
+        assert_eq!(transitions(&buffer), vec![3, 6]);
+
+        // Each element that is enqueued moves the transitions one index further
+        // back, until they are evicted
+        enqueue(&mut buffer, 20);
+        assert_eq!(transitions(&buffer), vec![1, 4]);
+        enqueue(&mut buffer, 20);
+        assert_eq!(transitions(&buffer), vec![2]);
+        enqueue(&mut buffer, 20);
+        assert!(transitions(&buffer).is_empty());
+    }
+
+    #[test]
+    fn inert_unless_enabled() {
+        let mut buffer = PeakBuffer::new(8, 1.0, 0.0);
+        buffer.set_sample_rate(80.);
+        buffer.set_tag(1);
+        enqueue(&mut buffer, 80);
+        assert_eq!(buffer.tag(0), None);
+    }
+
+    #[test]
+    fn cleared_and_resized() {
+        let mut buffer = buffer();
+        enqueue(&mut buffer, 41);
+        buffer.set_tag(3);
+        enqueue(&mut buffer, 20);
+        buffer.clear();
+        assert_eq!(tags(&buffer), vec![3; 8]);
+
+        buffer.grow(12);
+        assert_eq!(tags(&buffer), vec![3; 12]);
+        buffer.shrink(4);
+        assert_eq!(tags(&buffer), vec![3; 4]);
+        buffer.rescale_duration(2.0);
+        assert_eq!(tags(&buffer), vec![3; 4]);
+    }
+}
@@ -0,0 +1,316 @@
+//! Helpers for testing buffers outside of a plug-in.
+
+use nih_plug::buffer::Buffer;
+
+use super::{ChannelDownmix, VisualizerBuffer};
+
+/// Calls `f` with a real [`Buffer`] that is backed by the given channels.
+///
+/// All channels need to have the same length, which becomes the number of
+/// samples inside the buffer.
+pub(crate) fn with_buffer<R>(channels: &mut [Vec<f32>], f: impl FnOnce(&mut Buffer) -> R) -> R {
+    let num_samples = channels.first().map_or(0, |channel| channel.len());
+    assert!(
+        channels.iter().all(|channel| channel.len() == num_samples),
+        "all channels need to have the same length"
+    );
+
+    let mut buffer = Buffer::default();
+    // SAFETY: The slices point into `channels`, which outlives the buffer
+    unsafe {
+        buffer.set_slices(num_samples, |output_slices| {
+            *output_slices = channels
+                .iter_mut()
+                .map(|channel| channel.as_mut_slice())
+                .collect();
+        });
+    }
+
+    f(&mut buffer)
+}
+
+/// Returns a deterministic signal with the given number of channels and samples.
+///
+/// All values are multiples of 1/8, so that sums and averages of them are exact.
+pub(crate) fn test_signal(channels: usize, samples: usize) -> Vec<Vec<f32>> {
+    (0..channels)
+        .map(|c| {
+            (0..samples)
+                .map(|i| ((i * 5 + c * 3) % 17) as f32 / 8. - 1.)
+                .collect()
+        })
+        .collect()
+}
+
+/// Returns the contents of a buffer, from the oldest to the newest element.
+pub(crate) fn contents<B, T>(buffer: &B) -> Vec<B::Output>
+where
+    B: VisualizerBuffer<T>,
+    B::Output: Copy + Sized,
+{
+    (0..buffer.len()).map(|i| buffer[i]).collect()
+}
+
+/// Asserts that enqueueing a [`Buffer`] has the same effect as enqueueing its
+/// samples one by one, for the mono-summed signal as well as for a single
+/// channel, and that empty blocks leave the buffer untouched.
+///
+/// `new` needs to construct an identical, initialized buffer every time it's
+/// called. Choose `samples` larger than the buffer to cover wrapping around.
+pub(crate) fn assert_enqueue_buffer_matches_enqueue<B>(new: impl Fn() -> B, samples: usize)
+where
+    B: VisualizerBuffer<f32>,
+    B::Output: Copy + Sized + PartialEq + std::fmt::Debug,
+{
+    let mut channels = test_signal(3, samples);
+
+    // Mono-summing
+    let mut buffer = new();
+    with_buffer(&mut channels, |b| buffer.enqueue_buffer(b, None));
+
+    let mut expected = new();
+    for i in 0..samples {
+        let sum = channels.iter().map(|channel| channel[i]).sum::<f32>();
+        expected.enqueue((1. / channels.len() as f32) * sum);
+    }
+    assert_eq!(contents(&buffer), contents(&expected), "mono-summed");
+
+    // Channel selection
+    let mut buffer = new();
+    with_buffer(&mut channels, |b| buffer.enqueue_buffer(b, Some(1)));
+
+    let mut expected = new();
+    for sample in channels[1].iter() {
+        expected.enqueue(*sample);
+    }
+    assert_eq!(contents(&buffer), contents(&expected), "single channel");
+
+    // Empty blocks
+    let before = contents(&buffer);
+    let mut empty = vec![vec![]; 3];
+    with_buffer(&mut empty, |b| {
+        buffer.enqueue_buffer(b, None);
+        buffer.enqueue_buffer(b, Some(2));
+    });
+    assert_eq!(contents(&buffer), before, "empty block");
+}
+
+/// Interleaves the channels into frames.
+pub(crate) fn interleave(channels: &[Vec<f32>]) -> Vec<f32> {
+    let samples = channels.first().map_or(0, |channel| channel.len());
+    (0..samples)
+        .flat_map(|i| channels.iter().map(move |channel| channel[i]))
+        .collect()
+}
+
+/// Asserts that enqueueing interleaved and planar audio has the same effect as
+/// enqueueing the same audio as a [`Buffer`], for the mono-summed signal as well as
+/// for a single channel.
+///
+/// `new` needs to construct an identical, initialized buffer every time it's
+/// called. Choose `samples` larger than the buffer to cover wrapping around.
+pub(crate) fn assert_interleaved_matches_buffer<B>(new: impl Fn() -> B, samples: usize)
+where
+    B: VisualizerBuffer<f32>,
+    B::Output: Copy + Sized + PartialEq + std::fmt::Debug,
+{
+    let mut channels = test_signal(3, samples);
+    let interleaved = interleave(&channels);
+
+    for (channel, downmix) in [
+        (None, ChannelDownmix::Mean),
+        (Some(1), ChannelDownmix::Channel(1)),
+    ] {
+        let mut expected = new();
+        with_buffer(&mut channels, |b| expected.enqueue_buffer(b, channel));
+
+        let mut buffer = new();
+        buffer.enqueue_interleaved(&interleaved, 3, downmix);
+        assert_eq!(
+            contents(&buffer),
+            contents(&expected),
+            "interleaved {:?}",
+            downmix
+        );
+
+        let mut buffer = new();
+        let slices = channels.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        buffer.enqueue_planar(&slices, downmix);
+        assert_eq!(
+            contents(&buffer),
+            contents(&expected),
+            "planar {:?}",
+            downmix
+        );
+    }
+}
+
+/// Asserts that enqueueing one huge block, like the ones some hosts use when
+/// rendering offline, has the same effect as enqueueing it in blocks of 512
+/// samples.
+pub(crate) fn assert_large_block_matches_small_blocks<B>(new: impl Fn() -> B)
+where
+    B: VisualizerBuffer<f32>,
+    B::Output: Copy + Sized + PartialEq + std::fmt::Debug,
+{
+    let mut channels = test_signal(2, 65536);
+
+    for channel in [None, Some(1)] {
+        let mut large = new();
+        with_buffer(&mut channels, |b| large.enqueue_buffer(b, channel));
+
+        let mut small = new();
+        for block in 0..128 {
+            let mut block = channels
+                .iter()
+                .map(|channel| channel[block * 512..(block + 1) * 512].to_vec())
+                .collect::<Vec<_>>();
+            with_buffer(&mut block, |b| small.enqueue_buffer(b, channel));
+        }
+
+        assert_eq!(contents(&large), contents(&small), "channel {:?}", channel);
+    }
+}
+
+/// Asserts that switching a buffer's duration from 10 to 2 seconds and back again,
+/// while it's being fed, keeps the history that both durations cover.
+///
+/// `new` needs to construct an initialized buffer of 100 elements at a sample rate
+/// of 100 Hz with the given duration, and `rescale` changes the duration while
+/// keeping the history. Right after switching to 2 seconds, each element repeats
+/// the 10 second element it lies in. Once the buffer has scrolled by, and right
+/// after switching back, its contents match a buffer that was fed the same signal
+/// at that duration all along - except for the part of the 10 seconds that the
+/// 2 second elements didn't cover, which is empty.
+pub(crate) fn assert_rescale_matches_offline<B>(
+    new: impl Fn(f32) -> B,
+    rescale: impl Fn(&mut B, f32),
+) where
+    B: VisualizerBuffer<f32>,
+    B::Output: Copy + Sized + Default + PartialEq + std::fmt::Debug,
+{
+    let signal = test_signal(1, 2302).remove(0);
+    let feed = |buffer: &mut B, samples: &[f32]| {
+        for sample in samples {
+            buffer.enqueue(*sample);
+        }
+    };
+
+    // 10 seconds to 2 seconds
+    let mut buffer = new(10.);
+    feed(&mut buffer, &signal[..1500]);
+    let coarse = contents(&buffer);
+    rescale(&mut buffer, 2.);
+    let len = coarse.len();
+    for age in 0..len {
+        assert_eq!(
+            buffer[len - 1 - age],
+            coarse[len - 1 - age / 5],
+            "stretched element {}",
+            age
+        );
+    }
+
+    feed(&mut buffer, &signal[1500..1802]);
+    let mut expected = new(2.);
+    feed(&mut expected, &signal[..1802]);
+    assert_eq!(contents(&buffer), contents(&expected), "after 2 seconds");
+
+    // And back to 10 seconds, which the last 2 seconds only cover a fifth of
+    rescale(&mut buffer, 10.);
+    let mut expected = new(10.);
+    feed(&mut expected, &signal[..1802]);
+    assert_eq!(
+        contents(&buffer)[80..],
+        contents(&expected)[80..],
+        "merged elements"
+    );
+    assert!(
+        contents(&buffer)[..80]
+            .iter()
+            .all(|element| *element == B::Output::default()),
+        "uncovered elements"
+    );
+
+    // The element that was being accumulated carries on
+    feed(&mut buffer, &signal[1802..]);
+    feed(&mut expected, &signal[1802..]);
+    assert_eq!(
+        contents(&buffer)[30..],
+        contents(&expected)[30..],
+        "after 5 seconds"
+    );
+}
+
+/// Asserts that a buffer which keeps a density follows it when its duration
+/// changes, so that its elements keep spanning the same time.
+///
+/// `new` needs to construct an initialized buffer of 10 elements per second across
+/// 10 seconds, at a sample rate of 100 Hz - so each element spans 10 samples.
+/// `set_duration` needs to resize and clear the buffer, and `rescale` needs to
+/// resize it while keeping its newest elements. Either way, the buffer's contents
+/// match a buffer that was fed the same signal at the new duration all along -
+/// except for the past that a longer duration adds, which is empty.
+pub(crate) fn assert_density_kept<B>(
+    new: impl Fn() -> B,
+    set_duration: impl Fn(&mut B, f32),
+    rescale: impl Fn(&mut B, f32),
+) where
+    B: VisualizerBuffer<f32>,
+    B::Output: Copy + Sized + Default + PartialEq + std::fmt::Debug,
+{
+    let signal = test_signal(1, 2001).remove(0);
+    let feed = |buffer: &mut B, samples: &[f32]| {
+        for sample in samples {
+            buffer.enqueue(*sample);
+        }
+    };
+    let with_duration = |duration: f32| {
+        let mut buffer = new();
+        set_duration(&mut buffer, duration);
+        buffer
+    };
+    let spacing = |buffer: &B| buffer.duration().unwrap() / buffer.len() as f32;
+
+    let mut buffer = new();
+    assert_eq!(buffer.len(), 100);
+    set_duration(&mut buffer, 2.5);
+    assert_eq!(buffer.len(), 25);
+    assert_eq!(spacing(&buffer), 0.1);
+
+    feed(&mut buffer, &signal[..1001]);
+    let mut expected = with_duration(2.5);
+    feed(&mut expected, &signal[..1001]);
+    assert_eq!(
+        contents(&buffer),
+        contents(&expected),
+        "after set_duration()"
+    );
+
+    // A longer duration adds empty elements in the past
+    rescale(&mut buffer, 5.);
+    assert_eq!(buffer.len(), 50);
+    assert_eq!(spacing(&buffer), 0.1);
+    let mut expected = with_duration(5.);
+    feed(&mut expected, &signal[..1001]);
+    assert_eq!(contents(&buffer)[25..], contents(&expected)[25..], "grown");
+    assert!(
+        contents(&buffer)[..25]
+            .iter()
+            .all(|element| *element == B::Output::default()),
+        "added elements"
+    );
+
+    // The element that was being accumulated carries on
+    feed(&mut buffer, &signal[1001..1505]);
+    feed(&mut expected, &signal[1001..1505]);
+    assert_eq!(contents(&buffer), contents(&expected), "after 5 seconds");
+
+    // A shorter duration drops the oldest elements
+    rescale(&mut buffer, 1.);
+    assert_eq!(buffer.len(), 10);
+    feed(&mut buffer, &signal[1505..]);
+    let mut expected = with_duration(1.);
+    feed(&mut expected, &signal);
+    assert_eq!(contents(&buffer), contents(&expected), "shrunk");
+}
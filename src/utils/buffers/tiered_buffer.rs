@@ -0,0 +1,527 @@
+use std::fmt::{self, Debug};
+use std::ops::{Deref, Index, IndexMut};
+
+use super::super::config_error::{check_decay, check_duration, check_size, ConfigError};
+use super::super::SharedRead;
+use super::{check_index, PeakBuffer, RingBuffer, VisualizerBuffer};
+
+/// Stores a long history of peaks at a coarse resolution, along with the most
+/// recent peaks at a fine resolution.
+///
+/// Keeping hours of history inside a single [`PeakBuffer`] at a useful resolution
+/// would take millions of elements. A `TieredBuffer` instead consists of two tiers:
+///
+/// - The *fine* tier, which works just like a `PeakBuffer` - e.g. the last 30
+///   seconds in 800 elements
+/// - The *coarse* tier, where each element holds the maximum of several finalized
+///   elements of the fine tier - e.g. the last 2 hours in 1000 elements
+///
+/// Since the coarse tier is fed by the fine tier, the samples are only ever
+/// aggregated once. All memory is allocated when the buffer is constructed.
+///
+/// Indexing the buffer - e.g. by displaying it inside a
+/// [`Graph`](crate::visualizers::Graph) - reads both tiers stitched together: first
+/// the coarse elements that are older than the fine tier, then the entire fine tier.
+/// The resolution changes at the [`seam()`](Self::seam), which is visible as a
+/// change in detail along the graph.
+///
+/// Each tier can also be displayed on its own, by reading it out of the shared
+/// `TieredBuffer` through a [`SharedTier`]; See [`fine_tier()`](Self::fine_tier)
+/// and [`coarse_tier()`](Self::coarse_tier). The tiers are only stored once, so
+/// enqueueing never locks anything besides the `TieredBuffer` itself.
+///
+/// ```
+/// // The last 30 seconds in 800 elements, and the last 2 hours in 1000 elements
+/// tiered: Arc::new(Mutex::new(TieredBuffer::new(800, 30.0, 1000, 7200.0, 50.0))),
+///
+/// // Inside the editor, the stitched tiers and the fine tier on its own
+/// Graph::new(cx, Data::tiered, (-32.0, 8.0), ValueScaling::Decibels);
+/// Graph::new(cx, Data::tiered.map(TieredBuffer::fine_tier), (-32.0, 8.0), ValueScaling::Decibels);
+/// ```
+pub struct TieredBuffer {
+    // Turns the samples into fine elements, and holds the fine tier
+    fine: PeakBuffer,
+    coarse: RingBuffer<f32>,
+    // The number of fine elements per coarse element
+    ratio: usize,
+    // Maximum of the fine elements that were finalized since the last coarse element
+    coarse_acc: f32,
+    coarse_count: usize,
+    coarse_duration: f32,
+}
+
+impl TieredBuffer {
+    /// Constructs a new `TieredBuffer`.
+    ///
+    /// * `fine_size` - The number of elements inside the fine tier
+    /// * `fine_duration` - The duration (in seconds) of the audio data inside the fine tier
+    /// * `coarse_size` - The number of elements inside the coarse tier
+    /// * `coarse_duration` - The duration (in seconds) of the audio data inside the coarse tier
+    /// * `decay` - The decay of the fine tier, in milliseconds; See [`PeakBuffer::new`]
+    ///
+    /// Each coarse element spans a whole number of fine elements, so the coarse
+    /// duration is rounded to the nearest multiple of the fine resolution.
+    ///
+    /// The buffer needs to be provided a sample rate after initialization - do this by
+    /// calling [`set_sample_rate`](Self::set_sample_rate) inside your
+    /// [`initialize()`](nih_plug::plugin::Plugin::initialize) function.
+    pub fn new(
+        fine_size: usize,
+        fine_duration: f32,
+        coarse_size: usize,
+        coarse_duration: f32,
+        decay: f32,
+    ) -> Self {
+        let fine_resolution = fine_duration / fine_size as f32;
+        let coarse_resolution = coarse_duration / coarse_size as f32;
+        let ratio = ((coarse_resolution / fine_resolution).round() as usize).max(1);
+
+        Self {
+            fine: PeakBuffer::new(fine_size, fine_duration, decay),
+            coarse: RingBuffer::new(coarse_size),
+            ratio,
+            coarse_acc: 0.,
+            coarse_count: 0,
+            coarse_duration: fine_resolution * (ratio * coarse_size) as f32,
+        }
+    }
+
+    /// Constructs a new `TieredBuffer`, or returns a [`ConfigError`] if either size is
+    /// 0, either duration isn't positive, or the decay is negative; See
+    /// [`new()`](Self::new).
+    pub fn try_new(
+        fine_size: usize,
+        fine_duration: f32,
+        coarse_size: usize,
+        coarse_duration: f32,
+        decay: f32,
+    ) -> Result<Self, ConfigError> {
+        check_size("tiered buffer", "fine size", fine_size)?;
+        check_duration("tiered buffer", "fine duration", fine_duration)?;
+        check_size("tiered buffer", "coarse size", coarse_size)?;
+        check_duration("tiered buffer", "coarse duration", coarse_duration)?;
+        check_decay("tiered buffer", decay)?;
+        Ok(Self::new(
+            fine_size,
+            fine_duration,
+            coarse_size,
+            coarse_duration,
+            decay,
+        ))
+    }
+
+    /// Names the `TieredBuffer`, which identifies it in panic messages and the debug
+    /// registry.
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.fine = self.fine.with_name(name);
+        self.coarse = self.coarse.with_name(name);
+        self
+    }
+
+    /// Sets the name of the `TieredBuffer`; See [`with_name()`](Self::with_name).
+    pub fn set_name(self: &mut Self, name: String) {
+        self.coarse.set_name(name.clone());
+        self.fine.set_name(name);
+    }
+
+    /// Returns the name of the `TieredBuffer`, if it has one.
+    pub fn name(self: &Self) -> Option<&str> {
+        self.fine.name()
+    }
+
+    /// Sets the sample rate of the incoming audio.
+    ///
+    /// This function **clears** both tiers.
+    pub fn set_sample_rate(self: &mut Self, sample_rate: f32) {
+        self.fine.set_sample_rate(sample_rate);
+        self.clear();
+    }
+
+    /// Hints whether the plug-in is rendering offline, which skips all enqueueing
+    /// while it is. See [`PeakBuffer::set_offline`].
+    pub fn set_offline(self: &mut Self, offline: bool) {
+        self.fine.set_offline(offline);
+    }
+
+    /// Sets the decay time of the fine tier; See [`PeakBuffer::set_decay()`].
+    pub fn set_decay(self: &mut Self, decay: f32) {
+        self.fine.set_decay(decay);
+    }
+
+    /// Sets the decay time of the fine tier, or returns a [`ConfigError`] if it's
+    /// negative or NaN.
+    pub fn try_set_decay(self: &mut Self, decay: f32) -> Result<(), ConfigError> {
+        self.set_decay(check_decay("tiered buffer", decay)?);
+        Ok(())
+    }
+
+    /// Returns the fine tier, which holds the most recent, high-resolution elements.
+    pub fn fine(self: &Self) -> &PeakBuffer {
+        &self.fine
+    }
+
+    /// Returns the coarse tier, which holds the long, low-resolution history.
+    pub fn coarse(self: &Self) -> &RingBuffer<f32> {
+        &self.coarse
+    }
+
+    /// Returns a handle that reads the fine tier out of a shared `TieredBuffer`, for
+    /// displaying it on its own.
+    pub fn fine_tier<S>(shared: &S) -> SharedTier<S, PeakBuffer>
+    where
+        S: SharedRead<Value = TieredBuffer>,
+    {
+        SharedTier {
+            shared: shared.clone(),
+            tier: |tiered| &tiered.fine,
+            tier_mut: |tiered| &mut tiered.fine,
+        }
+    }
+
+    /// Returns a handle that reads the coarse tier out of a shared `TieredBuffer`,
+    /// for displaying it on its own.
+    pub fn coarse_tier<S>(shared: &S) -> SharedTier<S, RingBuffer<f32>>
+    where
+        S: SharedRead<Value = TieredBuffer>,
+    {
+        SharedTier {
+            shared: shared.clone(),
+            tier: |tiered| &tiered.coarse,
+            tier_mut: |tiered| &mut tiered.coarse,
+        }
+    }
+
+    /// Returns the index at which the fine tier begins when reading the stitched
+    /// tiers.
+    pub fn seam(self: &Self) -> usize {
+        self.coarse.len() - self.overlap()
+    }
+
+    /// Returns the number of fine elements that make up a coarse element.
+    pub fn ratio(self: &Self) -> usize {
+        self.ratio
+    }
+
+    // The number of coarse elements that span the same time as the fine tier,
+    // which are left out when stitching
+    fn overlap(self: &Self) -> usize {
+        self.fine.len().div_ceil(self.ratio).min(self.coarse.len())
+    }
+
+    fn push_fine(self: &mut Self, slot: f32) {
+        self.coarse_acc = self.coarse_acc.max(slot);
+        self.coarse_count += 1;
+        if self.coarse_count == self.ratio {
+            self.coarse.enqueue(self.coarse_acc);
+            self.coarse_acc = 0.;
+            self.coarse_count = 0;
+        }
+    }
+}
+
+impl VisualizerBuffer<f32> for TieredBuffer {
+    fn enqueue(self: &mut Self, value: f32) {
+        if let Some(slot) = self.fine.enqueue_slot(value) {
+            self.push_fine(slot);
+        }
+    }
+
+    fn enqueue_buffer(
+        self: &mut Self,
+        buffer: &mut nih_plug::buffer::Buffer,
+        channel: Option<usize>,
+    ) {
+        if self.fine.is_offline() {
+            return;
+        }
+        match channel {
+            Some(channel) => {
+                for sample in buffer.as_slice()[channel].iter() {
+                    self.enqueue(*sample);
+                }
+            }
+            None => {
+                for sample in buffer.iter_samples() {
+                    self.enqueue(
+                        (1. / (&sample).len() as f32) * sample.into_iter().map(|x| *x).sum::<f32>(),
+                    );
+                }
+            }
+        }
+    }
+
+    fn clear(self: &mut Self) {
+        self.fine.clear();
+        self.coarse.clear();
+        self.coarse_acc = 0.;
+        self.coarse_count = 0;
+    }
+
+    /// Grows the fine tier, **clearing both tiers**.
+    fn grow(self: &mut Self, size: usize) {
+        self.fine.grow(size);
+        self.clear();
+    }
+
+    /// Shrinks the fine tier, **clearing both tiers**.
+    fn shrink(self: &mut Self, size: usize) {
+        self.fine.shrink(size);
+        self.clear();
+    }
+
+    /// Returns the length of both tiers stitched together.
+    fn len(self: &Self) -> usize {
+        self.seam() + self.fine.len()
+    }
+
+    fn generation(self: &Self) -> Option<u64> {
+        let fine = self.fine.generation()?;
+        Some(fine.wrapping_add(self.coarse.generation()))
+    }
+
+    fn duration(self: &Self) -> Option<f32> {
+        let fine_duration = self.fine.duration()?;
+        let coarse_resolution = self.coarse_duration / self.coarse.len() as f32;
+        Some(self.seam() as f32 * coarse_resolution + fine_duration)
+    }
+
+    /// Returns the element that is currently being accumulated by the fine tier.
+    fn partial(self: &Self) -> Option<(f32, f32)> {
+        self.fine.partial()
+    }
+}
+
+impl Index<usize> for TieredBuffer {
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        check_index("tiered buffer", self.name(), index, self.len());
+        let seam = self.seam();
+        if index < seam {
+            &self.coarse[index]
+        } else {
+            &self.fine[index - seam]
+        }
+    }
+}
+impl IndexMut<usize> for TieredBuffer {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        check_index("tiered buffer", self.name(), index, self.len());
+        let seam = self.seam();
+        if index < seam {
+            &mut self.coarse[index]
+        } else {
+            &mut self.fine[index - seam]
+        }
+    }
+}
+
+impl Debug for TieredBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TieredBuffer")
+            .field("name", &self.name())
+            .field("fine", &self.fine)
+            .field("coarse_size", &self.coarse.len())
+            .field("coarse_duration", &self.coarse_duration)
+            .finish_non_exhaustive()
+    }
+}
+
+/// One tier of a shared [`TieredBuffer`], which views can display like any other
+/// shared buffer; See [`TieredBuffer::fine_tier()`] and
+/// [`TieredBuffer::coarse_tier()`].
+///
+/// Reading the tier reads the `TieredBuffer` through its own handle, e.g. by
+/// locking its mutex.
+pub struct SharedTier<S, T> {
+    shared: S,
+    tier: fn(&TieredBuffer) -> &T,
+    tier_mut: fn(&mut TieredBuffer) -> &mut T,
+}
+
+// Deriving `Clone` would require the tier to be `Clone`
+impl<S: Clone, T> Clone for SharedTier<S, T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            tier: self.tier,
+            tier_mut: self.tier_mut,
+        }
+    }
+}
+
+impl<S, T> SharedRead for SharedTier<S, T>
+where
+    S: SharedRead<Value = TieredBuffer>,
+{
+    type Value = T;
+    type Guard<'a>
+        = TierGuard<S::Guard<'a>, T>
+    where
+        Self: 'a;
+
+    fn read_shared(self: &Self, prepare: impl FnOnce(&mut T)) -> Self::Guard<'_> {
+        let tier_mut = self.tier_mut;
+        TierGuard {
+            guard: self.shared.read_shared(|tiered| prepare(tier_mut(tiered))),
+            tier: self.tier,
+        }
+    }
+}
+
+/// Grants access to one tier of a shared [`TieredBuffer`]; See [`SharedTier`].
+pub struct TierGuard<G, T> {
+    guard: G,
+    tier: fn(&TieredBuffer) -> &T,
+}
+
+impl<G, T> Deref for TierGuard<G, T>
+where
+    G: Deref<Target = TieredBuffer>,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        (self.tier)(&self.guard)
+    }
+}
+
+#[cfg(feature = "debug-tools")]
+impl super::registry::RegisteredBuffer for TieredBuffer {
+    fn info(&self) -> super::registry::BufferInfo {
+        super::registry::BufferInfo {
+            buffer: "TieredBuffer",
+            size: self.len(),
+            duration: self.duration(),
+            ..super::registry::RegisteredBuffer::info(&self.fine)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TieredBuffer;
+    use crate::utils::buffers::test_support::{assert_enqueue_buffer_matches_enqueue, contents};
+    use crate::utils::{PeakBuffer, SharedRead, VisualizerBuffer};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::sync::{Arc, Mutex, RwLock};
+
+    // 20 fine elements per second, 4 of which make up a coarse element
+    fn tiered() -> TieredBuffer {
+        let mut tiered = TieredBuffer::new(20, 1.0, 25, 5.0, 100.0);
+        tiered.set_sample_rate(200.);
+        tiered
+    }
+
+    fn random_signal(samples: usize) -> Vec<f32> {
+        let mut rng = StdRng::seed_from_u64(2);
+        (0..samples).map(|_| rng.gen_range(-1.0f32..1.0)).collect()
+    }
+
+    #[test]
+    fn coarse_tier_is_decimated_fine_history() {
+        let mut tiered = tiered();
+        assert_eq!(tiered.ratio(), 4);
+
+        // The entire fine history, kept inside a single large buffer with the same
+        // resolution and decay
+        let mut history = PeakBuffer::new(100, 5.0, 100.0);
+        history.set_sample_rate(200.);
+
+        // 80 fine elements, plus one sample to finalize the last one
+        let signal = random_signal(10 * 80 + 1);
+        for sample in signal.iter() {
+            tiered.enqueue(*sample);
+            history.enqueue(*sample);
+        }
+
+        let history = contents(&history)[20..].to_vec();
+        let decimated = history
+            .chunks(4)
+            .map(|chunk| chunk.iter().copied().fold(0., f32::max))
+            .collect::<Vec<_>>();
+
+        let coarse = contents(tiered.coarse());
+        assert!(coarse[..5].iter().all(|value| *value == 0.));
+        assert_eq!(coarse[5..], decimated[..]);
+
+        let fine = contents(tiered.fine());
+        assert_eq!(fine[..], history[60..]);
+    }
+
+    #[test]
+    fn stitched() {
+        let mut tiered = tiered();
+        for sample in random_signal(10 * 80 + 1) {
+            tiered.enqueue(sample);
+        }
+
+        // The 5 newest coarse elements span the same time as the fine tier
+        assert_eq!(tiered.seam(), 20);
+        assert_eq!(tiered.len(), 40);
+        assert_eq!(tiered.duration(), Some(5.0));
+
+        let coarse = contents(tiered.coarse());
+        let fine = contents(tiered.fine());
+        let stitched = contents(&tiered);
+        assert_eq!(stitched[..20], coarse[..20]);
+        assert_eq!(stitched[20..], fine[..]);
+    }
+
+    #[test]
+    fn sample_rate_clears_both_tiers() {
+        let mut tiered = tiered();
+        for sample in random_signal(1000) {
+            tiered.enqueue(sample);
+        }
+
+        // At twice the sample rate, a fine element spans twice as many samples
+        tiered.set_sample_rate(400.);
+        assert!(contents(&tiered).iter().all(|value| *value == 0.));
+        assert!(contents(tiered.coarse()).iter().all(|value| *value == 0.));
+
+        // 4 fine elements, which make up the first coarse element
+        for _ in 0..20 * 4 + 1 {
+            tiered.enqueue(1.0);
+        }
+        let fine = contents(tiered.fine());
+        assert!(fine[..16].iter().all(|value| *value == 0.));
+        assert!(fine[16..].iter().all(|value| *value == 1.));
+        assert_eq!(tiered.coarse().peek(), 1.0);
+    }
+
+    #[test]
+    fn shared_tiers() {
+        let shared = Arc::new(Mutex::new(tiered()));
+        let rw_shared = Arc::new(RwLock::new(tiered()));
+        for sample in random_signal(10 * 80 + 1) {
+            shared.lock().unwrap().enqueue(sample);
+            rw_shared.write().unwrap().enqueue(sample);
+        }
+
+        let tiered = shared.lock().unwrap();
+        let (fine, coarse) = (contents(tiered.fine()), contents(tiered.coarse()));
+        drop(tiered);
+        assert_eq!(
+            contents(&*TieredBuffer::fine_tier(&shared).read_shared(|_| {})),
+            fine
+        );
+        assert_eq!(
+            contents(&*TieredBuffer::coarse_tier(&shared).read_shared(|_| {})),
+            coarse
+        );
+        assert_eq!(
+            contents(&*TieredBuffer::fine_tier(&rw_shared).read_shared(|_| {})),
+            fine
+        );
+        assert_eq!(
+            contents(&*TieredBuffer::coarse_tier(&rw_shared).read_shared(|_| {})),
+            coarse
+        );
+    }
+
+    #[test]
+    fn enqueue_buffer() {
+        assert_enqueue_buffer_matches_enqueue(tiered, 500);
+    }
+}
@@ -0,0 +1,154 @@
+use super::RingBuffer;
+
+/// Records whether the host's transport was stopped during each element of a
+/// buffer.
+///
+/// The flags are enqueued along with the buffer's elements, so they scroll and get
+/// evicted together. Each element is flagged with the state the transport was in
+/// halfway through it, so a transition is resolved to the nearest element boundary.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TransportFlags {
+    stopped: RingBuffer<bool>,
+    playing: bool,
+    // The flag of the element that is currently being accumulated
+    slot_stopped: bool,
+}
+
+impl TransportFlags {
+    pub(crate) fn new(size: usize) -> Self {
+        Self {
+            stopped: RingBuffer::new(size),
+            playing: true,
+            slot_stopped: false,
+        }
+    }
+
+    /// Updates the state of the transport.
+    ///
+    /// * `progress` - How far along the element that is being accumulated is, from 0 to 1
+    pub(crate) fn set_playing(self: &mut Self, playing: bool, progress: Option<f32>) {
+        if playing == self.playing {
+            return;
+        }
+        self.playing = playing;
+        // Before the element's midpoint, the new state covers most of it
+        if progress.unwrap_or(0.) < 0.5 {
+            self.slot_stopped = !playing;
+        }
+    }
+
+    /// Flags the element that was just enqueued into the buffer.
+    pub(crate) fn enqueued(self: &mut Self) {
+        self.stopped.enqueue(self.slot_stopped);
+        self.slot_stopped = !self.playing;
+    }
+
+    /// Returns whether the transport was playing during the element at the index.
+    pub(crate) fn is_playing(self: &Self, index: usize) -> Option<bool> {
+        self.stopped.get(index).map(|stopped| !stopped)
+    }
+
+    /// Clears the flags, resizing them to the buffer's length.
+    pub(crate) fn reset(self: &mut Self, size: usize) {
+        if self.stopped.len() == size {
+            self.stopped.clear();
+        } else {
+            self.stopped = RingBuffer::new(size);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{PeakBuffer, VisualizerBuffer};
+
+    // 10 samples per element
+    fn buffer() -> PeakBuffer {
+        let mut buffer = PeakBuffer::new(8, 1.0, 0.0).with_transport_tracking();
+        buffer.set_sample_rate(80.);
+        buffer
+    }
+
+    fn flags(buffer: &PeakBuffer) -> Vec<bool> {
+        (0..buffer.len())
+            .map(|i| buffer.transport_playing(i).unwrap())
+            .collect()
+    }
+
+    fn enqueue(buffer: &mut PeakBuffer, samples: usize) {
+        for _ in 0..samples {
+            buffer.enqueue(0.5);
+        }
+    }
+
+    #[test]
+    fn flags_scroll_with_elements() {
+        // The element that is being accumulated holds a single sample
+        let mut buffer = buffer();
+        enqueue(&mut buffer, 41);
+        buffer.set_transport_playing(false);
+        enqueue(&mut buffer, 40);
+        assert_eq!(
+            flags(&buffer),
+            vec![true, true, true, true, false, false, false, false]
+        );
+
+        // The stopped elements are evicted along with the data
+        buffer.set_transport_playing(true);
+        enqueue(&mut buffer, 60);
+        assert_eq!(
+            flags(&buffer),
+            vec![false, false, true, true, true, true, true, true]
+        );
+    }
+
+    #[test]
+    fn resolves_to_nearest_element() {
+        // The element that is being accumulated holds a single sample, and the next
+        // sample after it is full finalizes it
+        let mut buffer = buffer();
+        enqueue(&mut buffer, 81);
+
+        // Stopping 3 samples into an element flags it
+        enqueue(&mut buffer, 2);
+        buffer.set_transport_playing(false);
+        enqueue(&mut buffer, 8);
+        assert_eq!(buffer.transport_playing(7), Some(false));
+
+        // Playing 8 samples into an element doesn't
+        enqueue(&mut buffer, 7);
+        buffer.set_transport_playing(true);
+        enqueue(&mut buffer, 3);
+        assert_eq!(buffer.transport_playing(7), Some(false));
+        enqueue(&mut buffer, 10);
+        assert_eq!(buffer.transport_playing(7), Some(true));
+
+        // Neither does stopping and playing again before the midpoint
+        buffer.set_transport_playing(false);
+        enqueue(&mut buffer, 2);
+        buffer.set_transport_playing(true);
+        enqueue(&mut buffer, 8);
+        assert_eq!(buffer.transport_playing(7), Some(true));
+    }
+
+    #[test]
+    fn inert_unless_enabled() {
+        let mut buffer = PeakBuffer::new(8, 1.0, 0.0);
+        buffer.set_sample_rate(80.);
+        buffer.set_transport_playing(false);
+        enqueue(&mut buffer, 80);
+        assert_eq!(buffer.transport_playing(0), None);
+    }
+
+    #[test]
+    fn cleared_and_resized() {
+        let mut buffer = buffer();
+        buffer.set_transport_playing(false);
+        enqueue(&mut buffer, 80);
+        buffer.clear();
+        assert!(flags(&buffer).iter().all(|playing| *playing));
+
+        buffer.grow(12);
+        assert_eq!(flags(&buffer).len(), 12);
+    }
+}
@@ -1,8 +1,30 @@
 use crate::utils::ring_buffer::RingBuffer;
 
+use std::fmt::{self, Debug};
 use std::ops::{Index, IndexMut};
 
-use super::VisualizerBuffer;
+use super::{
+    super::config_error::{
+        check_density, check_duration, check_size, clamp_density, clamp_duration, ConfigError,
+    },
+    super::diagnostics::Diagnostics,
+    super::liveness::Heartbeat,
+    capture::{BufferMode, Capture},
+    channel_source::{for_each_routed, ChannelSource, SourceRouting, SourceSelector},
+    check_index, density_size,
+    playback_rate::{retime, PlaybackRate},
+    rescale_elements, slot_progress, BufferSnapshot, SnapshotExport, VisualizerBuffer,
+};
+
+// How far a bound may lie past the boundary between two elements in
+// `slice_seconds()`, as a fraction of an element, and still count as lying on it
+const TOLERANCE: f32 = 1e-3;
+
+// The buffer stores raw samples once elements span a sample or less, and only goes
+// back to decimating once they span noticeably more, so that durations near the
+// boundary don't switch back and forth
+const RAW_ENTER: f32 = 1.0;
+const RAW_EXIT: f32 = 1.25;
 
 /// A special type of ring buffer for waveform analysis.
 ///
@@ -16,7 +38,12 @@ use super::VisualizerBuffer;
 ///
 /// These values can be used to construct a zoomed-out representation of the audio
 /// data without losing peak information - which is why this buffer is used in the
-/// [`Oscilloscope`](crate::editor::views::Oscilloscope).
+/// [`Oscilloscope`](crate::visualizers::Oscilloscope).
+///
+/// When the duration is so short that each element spans a sample or less, e.g.
+/// 10 ms across 800 elements, the buffer stores raw samples instead: Each element
+/// holds the newest sample as both its minimum and its maximum, as soon as the
+/// element begins, so the waveform is drawn without latency or smearing.
 #[derive(Clone, PartialEq, Default)]
 pub struct WaveformBuffer {
     buffer: RingBuffer<(f32, f32)>,
@@ -28,8 +55,30 @@ pub struct WaveformBuffer {
     // Used to calculate the sample_delta
     sample_rate: f32,
     duration: f32,
+    // The elements per second that the size follows when the duration changes, if any
+    density: Option<f32>,
     // The current time, counts down from sample_delta to 0
     t: f32,
+    // Whether elements hold raw samples, rather than the minimum and maximum of
+    // the samples they span; This is set in `update()` based on the sample_delta
+    raw: bool,
+    // Whether the plug-in is rendering offline, in which case nothing is enqueued
+    offline: bool,
+    // The channels that are read from a buffer that's enqueued without a channel
+    routing: SourceRouting,
+    // The rate the host plays back at, which the duration follows
+    playback: PlaybackRate,
+    // Whether the buffer scrolls or captures takes, and the state of the take
+    capture: Capture,
+    // The number of elements enqueued since the buffer was cleared, up to its length
+    filled: usize,
+    // Counts the samples that were skipped
+    diagnostics: Diagnostics,
+    // Counts the enqueued elements for the editor, if attached
+    heartbeat: Option<Heartbeat>,
+    // The number of samples processed, and whether the transport was stopped
+    processed: u64,
+    transport_stopped: bool,
 }
 
 impl WaveformBuffer {
@@ -48,11 +97,76 @@ impl WaveformBuffer {
             max_acc: f32::MIN,
             sample_delta: 0.,
             sample_rate: 0.,
-            duration,
+            duration: clamp_duration("waveform buffer", "duration", duration),
+            density: None,
             t: 0.,
+            raw: false,
+            offline: false,
+            routing: SourceRouting::default(),
+            playback: PlaybackRate::default(),
+            capture: Capture::default(),
+            filled: 0,
+            diagnostics: Diagnostics::default(),
+            heartbeat: None,
+            processed: 0,
+            transport_stopped: false,
+        }
+    }
+
+    /// Constructs a new `WaveformBuffer`, or returns a [`ConfigError`] if the size is
+    /// 0 or the duration isn't positive; See [`new()`](Self::new).
+    pub fn try_new(size: usize, duration: f32) -> Result<Self, ConfigError> {
+        check_size("waveform buffer", "size", size)?;
+        check_duration("waveform buffer", "duration", duration)?;
+        Ok(Self::new(size, duration))
+    }
+
+    /// Constructs a new `WaveformBuffer` that holds a number of elements per second;
+    /// See [`PeakBuffer::with_density`](super::PeakBuffer::with_density).
+    ///
+    /// * `points_per_second` - The number of elements per second of audio data
+    /// * `duration` - The duration (in seconds) of the audio data inside the buffer
+    pub fn with_density(points_per_second: f32, duration: f32) -> Self {
+        let density = clamp_density("waveform buffer", points_per_second);
+        let duration = clamp_duration("waveform buffer", "duration", duration);
+        Self {
+            density: Some(density),
+            ..Self::new(density_size(density, duration), duration)
         }
     }
 
+    /// Constructs a new `WaveformBuffer` that holds a number of elements per second,
+    /// or returns a [`ConfigError`] if the density or the duration isn't positive;
+    /// See [`with_density()`](Self::with_density).
+    pub fn try_with_density(points_per_second: f32, duration: f32) -> Result<Self, ConfigError> {
+        check_density("waveform buffer", points_per_second)?;
+        check_duration("waveform buffer", "duration", duration)?;
+        Ok(Self::with_density(points_per_second, duration))
+    }
+
+    /// Returns the number of elements per second of audio data.
+    pub fn density(self: &Self) -> f32 {
+        self.density
+            .unwrap_or(self.buffer.len() as f32 / self.duration)
+    }
+
+    /// Names the `WaveformBuffer`, which identifies it in panic messages, snapshots and the
+    /// debug registry.
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.buffer.name_mut().set(name);
+        self
+    }
+
+    /// Sets the name of the `WaveformBuffer`; See [`with_name()`](Self::with_name).
+    pub fn set_name(self: &mut Self, name: String) {
+        self.buffer.name_mut().set(name);
+    }
+
+    /// Returns the name of the `WaveformBuffer`, if it has one.
+    pub fn name(self: &Self) -> Option<&str> {
+        self.buffer.name()
+    }
+
     /// Sets the sample rate of the incoming audio.
     ///
     /// This function **clears** the buffer. You can call it inside your
@@ -78,32 +192,292 @@ impl WaveformBuffer {
     /// ```
     pub fn set_sample_rate(self: &mut Self, sample_rate: f32) {
         self.sample_rate = sample_rate;
-        self.sample_delta = Self::sample_delta(self.buffer.len(), sample_rate, self.duration);
-        self.buffer.clear();
+        self.update();
+        self.clear_elements();
+    }
+
+    /// Hints whether the plug-in is rendering offline, which skips all enqueueing
+    /// while it is. See [`PeakBuffer::set_offline`](super::PeakBuffer::set_offline).
+    pub fn set_offline(self: &mut Self, offline: bool) {
+        self.offline = offline;
+    }
+
+    /// Sets the channels that are read from a [`Buffer`](nih_plug::buffer::Buffer) that's enqueued without a
+    /// channel; See [`PeakBuffer::set_source()`](super::PeakBuffer::set_source).
+    pub fn set_source(self: &mut Self, source: ChannelSource) {
+        self.routing.selector().set(source);
+    }
+
+    /// Returns a handle that selects the source of the `WaveformBuffer` without locking
+    /// it; See [`set_source()`](Self::set_source).
+    pub fn source_selector(self: &Self) -> SourceSelector {
+        self.routing.selector()
+    }
+
+    /// Sets whether the `WaveformBuffer` is cleared when its source changes, which it is
+    /// by default.
+    pub fn set_clear_on_source_change(self: &mut Self, clear: bool) {
+        self.routing.set_clear_on_change(clear);
+    }
+
+    /// Reports the rate at which the host plays the project back, so that the buffer
+    /// keeps spanning its duration of what's heard; See
+    /// [`PeakBuffer::set_playback_rate()`](super::PeakBuffer::set_playback_rate).
+    ///
+    /// If the new rate makes elements switch between raw samples and decimated ones,
+    /// the element that is being accumulated starts over.
+    pub fn set_playback_rate(self: &mut Self, rate: f32) {
+        if !self.playback.set(rate) {
+            return;
+        }
+        let sample_delta =
+            Self::sample_delta(self.buffer.len(), self.samples_per_second(), self.duration);
+        if Self::is_raw(self.raw, sample_delta) != self.raw {
+            self.update();
+        } else {
+            self.t = retime(self.t, self.sample_delta, sample_delta);
+            self.sample_delta = sample_delta;
+        }
+    }
+
+    /// Returns the playback rate that was reported last.
+    pub fn playback_rate(self: &Self) -> f32 {
+        self.playback.rate()
+    }
+
+    /// Attaches a [`Diagnostics`] handle, which counts the samples that the buffer
+    /// skips while it's offline or not capturing.
+    pub fn set_diagnostics(self: &mut Self, diagnostics: Diagnostics) {
+        self.diagnostics = diagnostics;
+    }
+
+    /// Sets whether the `WaveformBuffer` scrolls or captures takes; See
+    /// [`PeakBuffer::with_mode`](super::PeakBuffer::with_mode).
+    pub fn with_mode(mut self, mode: BufferMode) -> Self {
+        self.set_mode(mode);
+        self
+    }
+
+    /// Sets whether the `WaveformBuffer` scrolls or captures takes; See
+    /// [`PeakBuffer::set_mode`](super::PeakBuffer::set_mode).
+    pub fn set_mode(self: &mut Self, mode: BufferMode) {
+        self.capture.set_mode(mode);
+    }
+
+    /// Returns whether the `WaveformBuffer` scrolls or captures takes.
+    pub fn mode(self: &Self) -> BufferMode {
+        self.capture.mode()
+    }
+
+    /// Clears the buffer and starts capturing a take; See
+    /// [`PeakBuffer::arm`](super::PeakBuffer::arm).
+    pub fn arm(self: &mut Self) {
+        self.clear();
+        self.capture.arm(self.buffer.len());
+    }
+
+    /// Clears the buffer and starts capturing a take once a sample reaches the
+    /// threshold; See [`PeakBuffer::arm_on_threshold`](super::PeakBuffer::arm_on_threshold).
+    pub fn arm_on_threshold(self: &mut Self, threshold: f32, pre_trigger: f32) {
+        self.clear();
+        self.capture
+            .arm_on_threshold(self.buffer.len(), threshold, pre_trigger);
+    }
+
+    /// Returns `true` once a take has been captured, until the buffer is armed again.
+    pub fn is_complete(self: &Self) -> bool {
+        self.capture.is_complete()
+    }
+
+    /// Returns the number of elements enqueued since the buffer was last armed.
+    pub fn fill_count(self: &Self) -> usize {
+        self.capture.fill_count()
+    }
+
+    /// Attaches a [`Heartbeat`], which tells the editor whether the buffer is still
+    /// being fed; See [`PeakBuffer::with_heartbeat`](super::PeakBuffer::with_heartbeat).
+    pub fn with_heartbeat(mut self) -> Self {
+        self.heartbeat = Some(Heartbeat::new());
+        self
+    }
+
+    /// Returns the buffer's [`Heartbeat`], if it was constructed
+    /// [with one](Self::with_heartbeat).
+    pub fn heartbeat(self: &Self) -> Option<Heartbeat> {
+        self.heartbeat.clone()
+    }
+
+    /// Sets whether the host's transport is playing, which the buffer's
+    /// [`Heartbeat`] records; Call this once per block, before enqueueing it.
+    pub fn set_transport_playing(self: &mut Self, playing: bool) {
+        self.transport_stopped = !playing;
     }
 
     /// Sets the duration (in seconds) of the incoming audio.
     ///
-    /// This function **clears** the buffer.
+    /// This function **clears** the buffer. A duration that isn't positive and finite
+    /// fails a debug assertion, and is clamped in release builds; Use
+    /// [`try_set_duration()`](Self::try_set_duration) to handle it instead.
+    ///
+    /// A buffer that keeps a [density](Self::with_density) is resized to it.
     pub fn set_duration(self: &mut Self, duration: f32) {
+        self.duration = clamp_duration("waveform buffer", "duration", duration);
+        if let Some(density) = self.density {
+            self.buffer.resize(density_size(density, self.duration));
+        }
+        self.update();
+        self.clear_elements();
+    }
+
+    /// Sets the duration (in seconds) of the incoming audio, or returns
+    /// a [`ConfigError`] if it isn't positive and finite.
+    ///
+    /// This function **clears** the buffer.
+    pub fn try_set_duration(self: &mut Self, duration: f32) -> Result<(), ConfigError> {
+        self.set_duration(check_duration("waveform buffer", "duration", duration)?);
+        Ok(())
+    }
+
+    /// Sets the duration (in seconds) of the incoming audio, keeping the buffer's
+    /// history; See [`PeakBuffer::rescale_duration`](super::PeakBuffer::rescale_duration).
+    ///
+    /// A longer duration merges groups of elements into one that spans all of them,
+    /// from the lowest minimum to the highest maximum. A buffer that keeps a
+    /// [density](Self::with_density) is resized instead.
+    pub fn rescale_duration(self: &mut Self, duration: f32) {
+        let duration = clamp_duration("waveform buffer", "duration", duration);
+        self.filled = match self.density {
+            Some(density) => {
+                let len = density_size(density, duration);
+                self.buffer.resize(len);
+                self.filled.min(len)
+            }
+            None => rescale_elements(
+                &mut self.buffer,
+                self.filled,
+                self.duration,
+                duration,
+                |elements| {
+                    elements
+                        .iter()
+                        .fold((f32::MAX, f32::MIN), |(min, max), element| {
+                            (min.min(element.0), max.max(element.1))
+                        })
+                },
+            ),
+        };
+
+        let accumulated = self.sample_delta - self.t;
         self.duration = duration;
-        self.sample_delta = Self::sample_delta(self.buffer.len(), self.sample_rate, duration);
+        self.sample_delta =
+            Self::sample_delta(self.buffer.len(), self.samples_per_second(), duration);
+        self.raw = Self::is_raw(self.raw, self.sample_delta);
+        if accumulated < self.sample_delta && !self.raw {
+            self.t = self.sample_delta - accumulated;
+        } else {
+            self.t = self.sample_delta;
+            self.reset_accumulators();
+        }
+    }
+
+    /// Fills `out` with the elements that lie between `from` and `to` seconds in the
+    /// past, from the oldest to the newest.
+    ///
+    /// The newest element lies 0 seconds in the past, and the oldest one lies the
+    /// buffer's duration in the past; The order of `from` and `to` doesn't matter.
+    /// Elements that weren't enqueued since the buffer was cleared are left out, so
+    /// while the buffer fills up, `out` holds less than the span covers - the missing
+    /// elements are always the oldest ones.
+    pub fn slice_seconds(self: &Self, from: f32, to: f32, out: &mut Vec<(f32, f32)>) {
+        out.clear();
+        let len = self.buffer.len();
+        if len == 0 || self.duration <= 0. {
+            return;
+        }
+
+        // The ages of the elements, counted back from the newest one; Bounds that lie
+        // on the boundary between two elements don't include the element past it
+        let seconds_per_element = self.duration / len as f32;
+        let age = |seconds: f32| seconds / seconds_per_element;
+        let newest = (age(from.min(to)) + TOLERANCE).floor().max(0.) as usize;
+        let oldest = ((age(from.max(to)) - TOLERANCE).ceil() as usize).min(self.filled);
+        if oldest > newest {
+            out.extend((newest..oldest).rev().map(|age| self.buffer[len - 1 - age]));
+        }
+    }
+
+    fn clear_elements(self: &mut Self) {
         self.buffer.clear();
+        self.filled = 0;
     }
 
     fn sample_delta(size: usize, sample_rate: f32, duration: f32) -> f32 {
         (sample_rate * duration) / size as f32
     }
+
+    // The number of samples for each second of the duration
+    fn samples_per_second(self: &Self) -> f32 {
+        self.playback.samples_per_second(self.sample_rate)
+    }
+
+    // Whether elements that span `sample_delta` samples hold raw samples, with some
+    // hysteresis around the boundary
+    fn is_raw(raw: bool, sample_delta: f32) -> bool {
+        let boundary = if raw { RAW_EXIT } else { RAW_ENTER };
+        sample_delta > 0. && sample_delta <= boundary
+    }
+
+    // Restarts the element that is being accumulated, since its length changed
+    fn update(self: &mut Self) {
+        self.sample_delta =
+            Self::sample_delta(self.buffer.len(), self.samples_per_second(), self.duration);
+        self.raw = Self::is_raw(self.raw, self.sample_delta);
+        self.t = self.sample_delta;
+        self.reset_accumulators();
+    }
+
+    fn push(self: &mut Self, element: (f32, f32)) {
+        self.buffer.enqueue(element);
+        self.filled = (self.filled + 1).min(self.buffer.len());
+        self.capture.enqueued();
+        if let Some(heartbeat) = &self.heartbeat {
+            heartbeat.beat(self.processed, !self.transport_stopped);
+        }
+    }
+
+    fn reset_accumulators(self: &mut Self) {
+        self.min_acc = f32::MAX;
+        self.max_acc = f32::MIN;
+    }
 }
 
 impl VisualizerBuffer<f32> for WaveformBuffer {
     fn enqueue(self: &mut Self, value: f32) {
+        if self.offline || !self.capture.accepts() {
+            self.diagnostics.skipped(1);
+            return;
+        }
+        self.processed += 1;
         self.t -= 1.0;
+        if self.raw {
+            // Every element that begins during the sample holds it
+            while self.t <= 0.0 {
+                self.push((value, value));
+                self.t += self.sample_delta;
+            }
+            self.capture.observe(value);
+            return;
+        }
         if self.t < 0.0 {
-            self.buffer.enqueue((self.min_acc, self.max_acc));
+            // An element that didn't get any samples is silent, rather than holding
+            // the accumulators' initial values
+            let element = match self.min_acc <= self.max_acc {
+                true => (self.min_acc, self.max_acc),
+                false => (0., 0.),
+            };
+            self.push(element);
             self.t += self.sample_delta;
-            self.min_acc = f32::MAX;
-            self.max_acc = f32::MIN;
+            self.reset_accumulators();
         }
         if value > self.max_acc {
             self.max_acc = value
@@ -111,6 +485,7 @@ impl VisualizerBuffer<f32> for WaveformBuffer {
         if value < self.min_acc {
             self.min_acc = value
         }
+        self.capture.observe(value);
     }
 
     fn enqueue_buffer(
@@ -118,6 +493,10 @@ impl VisualizerBuffer<f32> for WaveformBuffer {
         buffer: &mut nih_plug::buffer::Buffer,
         channel: Option<usize>,
     ) {
+        if self.offline {
+            self.diagnostics.skipped(buffer.samples());
+            return;
+        }
         match channel {
             Some(channel) => {
                 for sample in buffer.as_slice()[channel].into_iter() {
@@ -125,10 +504,20 @@ impl VisualizerBuffer<f32> for WaveformBuffer {
                 }
             }
             None => {
-                for sample in buffer.iter_samples() {
-                    self.enqueue(
-                        (1. / (&sample).len() as f32) * sample.into_iter().map(|x| *x).sum::<f32>(),
-                    );
+                let (source, changed) = self.routing.update();
+                if changed {
+                    self.clear();
+                }
+                match source {
+                    ChannelSource::DownmixAvg => {
+                        for sample in buffer.iter_samples() {
+                            self.enqueue(
+                                (1. / (&sample).len() as f32)
+                                    * sample.into_iter().map(|x| *x).sum::<f32>(),
+                            );
+                        }
+                    }
+                    source => for_each_routed(buffer, source, |sample| self.enqueue(sample)),
                 }
             }
         }
@@ -138,8 +527,38 @@ impl VisualizerBuffer<f32> for WaveformBuffer {
         self.buffer.len()
     }
 
+    fn duration(self: &Self) -> Option<f32> {
+        Some(self.duration)
+    }
+
+    fn generation(self: &Self) -> Option<u64> {
+        Some(self.buffer.generation())
+    }
+
+    fn enqueued(self: &Self) -> Option<u64> {
+        Some(self.buffer.enqueued())
+    }
+
+    fn clears(self: &Self) -> Option<u64> {
+        Some(self.buffer.clears())
+    }
+
+    fn partial(self: &Self) -> Option<(f32, (f32, f32))> {
+        // Raw elements are complete as soon as they begin
+        if self.raw {
+            return None;
+        }
+        let progress = slot_progress(self.t, self.sample_delta)?;
+        (self.min_acc <= self.max_acc).then_some((progress, (self.min_acc, self.max_acc)))
+    }
+
+    fn capture_progress(self: &Self) -> Option<f32> {
+        self.capture.progress(self.buffer.len())
+    }
+
     fn clear(self: &mut Self) {
-        self.buffer.clear();
+        self.clear_elements();
+        self.reset_accumulators();
     }
 
     /// Grows the buffer, **clearing it**.
@@ -148,8 +567,9 @@ impl VisualizerBuffer<f32> for WaveformBuffer {
             return;
         }
         self.buffer.grow(size);
-        self.sample_delta = Self::sample_delta(size, self.sample_rate, self.duration);
-        self.buffer.clear();
+        self.density = None;
+        self.update();
+        self.clear_elements();
     }
 
     /// Shrinks the buffer, **clearing it**.
@@ -158,8 +578,9 @@ impl VisualizerBuffer<f32> for WaveformBuffer {
             return;
         }
         self.buffer.shrink(size);
-        self.sample_delta = Self::sample_delta(size, self.sample_rate, self.duration);
-        self.buffer.clear();
+        self.density = None;
+        self.update();
+        self.clear_elements();
     }
 }
 
@@ -167,11 +588,304 @@ impl Index<usize> for WaveformBuffer {
     type Output = (f32, f32);
 
     fn index(&self, index: usize) -> &Self::Output {
-        self.buffer.index(index)
+        check_index("waveform buffer", self.name(), index, self.buffer.len());
+        &self.buffer[index]
     }
 }
 impl IndexMut<usize> for WaveformBuffer {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        self.buffer.index_mut(index)
+        check_index("waveform buffer", self.name(), index, self.buffer.len());
+        &mut self.buffer[index]
+    }
+}
+
+impl SnapshotExport for WaveformBuffer {
+    fn export(&self) -> BufferSnapshot {
+        BufferSnapshot {
+            name: self.name().map(str::to_string),
+            duration: Some(self.duration),
+            sample_rate: Some(self.sample_rate),
+            ..BufferSnapshot::new(
+                "WaveformBuffer",
+                &["min", "max"],
+                (0..self.buffer.len())
+                    .map(|i| {
+                        let (min, max) = self.buffer[i];
+                        vec![min, max]
+                    })
+                    .collect(),
+            )
+        }
+    }
+}
+
+impl Debug for WaveformBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WaveformBuffer")
+            .field("name", &self.name())
+            .field("size", &self.buffer.len())
+            .field("duration", &self.duration)
+            .field("sample_rate", &self.sample_rate)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "debug-tools")]
+impl super::registry::RegisteredBuffer for WaveformBuffer {
+    fn info(&self) -> super::registry::BufferInfo {
+        super::registry::BufferInfo {
+            buffer: "WaveformBuffer",
+            name: self.name().map(str::to_string),
+            size: self.buffer.len(),
+            duration: Some(self.duration),
+            sample_rate: Some(self.sample_rate),
+            enqueued: self.buffer.enqueued(),
+        }
+    }
+}
+
+#[cfg(feature = "ffi")]
+impl super::ffi::WriteSnapshot for WaveformBuffer {
+    fn write_snapshot(&self, out: &mut Vec<u8>) {
+        super::ffi::SnapshotHeader::new(
+            super::ffi::ValueFormat::MinMaxF32,
+            self.buffer.len(),
+            Some(self.duration),
+            Some(self.sample_rate),
+        )
+        .write(
+            out,
+            (0..self.buffer.len()).flat_map(|i| {
+                let (min, max) = self.buffer[i];
+                [min, max]
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WaveformBuffer;
+    use crate::utils::buffers::test_support::{
+        assert_density_kept, assert_enqueue_buffer_matches_enqueue,
+        assert_interleaved_matches_buffer, assert_large_block_matches_small_blocks,
+        assert_rescale_matches_offline,
+    };
+    use crate::utils::VisualizerBuffer;
+
+    #[test]
+    fn enqueue_buffer() {
+        // 8 slots of 4 samples each, so 100 samples wrap around the buffer
+        let new = || {
+            let mut buffer = WaveformBuffer::new(8, 1.0);
+            buffer.set_sample_rate(32.);
+            buffer
+        };
+        assert_enqueue_buffer_matches_enqueue(new, 100);
+        assert_interleaved_matches_buffer(new, 100);
+    }
+
+    #[test]
+    fn enqueue_buffer_large_block() {
+        // Blocks far larger than any host's maximum, while the host plays back at a
+        // rate that makes elements span a fractional number of samples
+        assert_large_block_matches_small_blocks(|| {
+            let mut buffer = WaveformBuffer::new(800, 1.0);
+            buffer.set_sample_rate(44100.);
+            buffer.set_playback_rate(0.7);
+            buffer
+        });
+    }
+
+    #[test]
+    fn playback_rate() {
+        // 4 samples per element at 1x, and 8 at 0.5x
+        let mut buffer = WaveformBuffer::new(8, 1.0);
+        buffer.set_sample_rate(32.);
+        buffer.set_playback_rate(0.5);
+        for i in 0..65 {
+            buffer.enqueue(if i < 16 { 1. } else { 0. });
+        }
+        // The 16 loud samples take up 2 elements, rather than 4
+        let loud = (0..8).filter(|i| buffer[*i].1 == 1.).count();
+        assert_eq!(loud, 2);
+
+        // Elements that hold raw samples switch back to decimating
+        let mut buffer = WaveformBuffer::new(100, 1.0);
+        buffer.set_sample_rate(100.);
+        assert!(buffer.raw);
+        buffer.set_playback_rate(0.5);
+        assert!(!buffer.raw);
+    }
+
+    #[test]
+    fn density() {
+        let new = || {
+            let mut buffer = WaveformBuffer::with_density(10., 10.);
+            buffer.set_sample_rate(100.);
+            buffer
+        };
+        assert_density_kept(
+            new,
+            WaveformBuffer::set_duration,
+            WaveformBuffer::rescale_duration,
+        );
+        assert_eq!(new().density(), 10.);
+        assert_eq!(WaveformBuffer::new(800, 10.).density(), 80.);
+    }
+
+    #[test]
+    fn rescale_duration() {
+        let new = |duration| {
+            let mut buffer = WaveformBuffer::new(100, duration);
+            buffer.set_sample_rate(100.);
+            buffer
+        };
+        assert_rescale_matches_offline(new, WaveformBuffer::rescale_duration);
+
+        // Only the elements that hold history are sliced
+        let mut buffer = new(2.);
+        for i in 0..1000 {
+            buffer.enqueue(i as f32);
+        }
+        buffer.rescale_duration(10.);
+        let mut slice = Vec::new();
+        buffer.slice_seconds(0., 10., &mut slice);
+        assert_eq!(slice.len(), 20);
+        assert_eq!(slice[0], (798., 807.));
+        assert_eq!(slice[19], (988., 997.));
+    }
+
+    #[test]
+    fn partial() {
+        // 4 samples per element
+        let mut buffer = WaveformBuffer::new(8, 1.0);
+        buffer.set_sample_rate(32.);
+        assert_eq!(buffer.partial(), None);
+
+        for value in [0.5, -0.25, 0.75] {
+            buffer.enqueue(value);
+        }
+        assert_eq!(buffer.partial(), Some((0.75, (-0.25, 0.75))));
+
+        // The fourth sample finishes the element, the fifth one starts the next
+        buffer.enqueue(0.0);
+        buffer.enqueue(0.1);
+        assert_eq!(buffer[7], (-0.25, 0.75));
+        assert_eq!(buffer.partial(), Some((0.25, (0.1, 0.1))));
+    }
+
+    #[test]
+    fn slice_seconds() {
+        // 10 elements of 0.1 seconds, 4 samples each
+        let mut buffer = WaveformBuffer::new(10, 1.0);
+        buffer.set_sample_rate(40.);
+        let mut slice = Vec::new();
+
+        let enqueue = |buffer: &mut WaveformBuffer, samples: usize| {
+            for i in 0..samples {
+                buffer.enqueue(i as f32);
+            }
+        };
+        // The 5th, 9th and 13th sample each finish an element
+        enqueue(&mut buffer, 13);
+
+        // Only the filled elements are sliced, even if the span covers more
+        buffer.slice_seconds(0., 1., &mut slice);
+        assert_eq!(slice, vec![buffer[7], buffer[8], buffer[9]]);
+        assert_eq!(slice, vec![(0., 3.), (4., 7.), (8., 11.)]);
+        buffer.slice_seconds(0.5, 1., &mut slice);
+        assert!(slice.is_empty());
+
+        enqueue(&mut buffer, 80);
+        buffer.slice_seconds(0., 1., &mut slice);
+        assert_eq!(slice.len(), 10);
+
+        // The order of the bounds doesn't matter, and spans past the history are
+        // clamped to it
+        buffer.slice_seconds(0.25, 0.1, &mut slice);
+        assert_eq!(slice, vec![buffer[7], buffer[8]]);
+        buffer.slice_seconds(0.85, 5., &mut slice);
+        assert_eq!(slice, vec![buffer[0], buffer[1]]);
+
+        // Clearing the buffer empties the history
+        buffer.clear();
+        buffer.slice_seconds(0., 1., &mut slice);
+        assert!(slice.is_empty());
+    }
+
+    #[test]
+    fn raw_samples_reproduce_sine() {
+        // A 1 kHz sine at 48 kHz, inside of 10 ms
+        const SAMPLE_RATE: f32 = 48000.;
+        let sine = |n: f32| (std::f32::consts::TAU * 1000. * n / SAMPLE_RATE).sin();
+        // The furthest the sine moves within one sample
+        let max_step = std::f32::consts::TAU * 1000. / SAMPLE_RATE;
+
+        // 1 sample per element, and 0.6 samples per element
+        for size in [480, 800] {
+            let mut buffer = WaveformBuffer::new(size, 0.01);
+            buffer.set_sample_rate(SAMPLE_RATE);
+            assert!(buffer.raw);
+            let samples = 2000;
+            for n in 0..samples {
+                buffer.enqueue(sine(n as f32));
+            }
+            assert_eq!(buffer.partial(), None);
+
+            // Each element holds the sample at the time it begins, counted back from
+            // the newest sample
+            let sample_delta = SAMPLE_RATE * 0.01 / size as f32;
+            for age in 0..size {
+                let (min, max) = buffer[size - 1 - age];
+                assert_eq!(min, max);
+                let time = (samples - 1) as f32 - age as f32 * sample_delta;
+                assert!(
+                    (min - sine(time)).abs() <= max_step + 1e-4,
+                    "element {} of {} is out of phase",
+                    age,
+                    size
+                );
+            }
+        }
+
+        // With exactly 1 sample per element, the elements are the samples
+        let mut buffer = WaveformBuffer::new(480, 0.01);
+        buffer.set_sample_rate(SAMPLE_RATE);
+        for n in 0..1000 {
+            buffer.enqueue(sine(n as f32));
+        }
+        for age in 0..480 {
+            assert_eq!(buffer[479 - age].0, sine((999 - age) as f32));
+        }
+    }
+
+    #[test]
+    fn raw_mode_hysteresis() {
+        // 1 sample per element at a duration of 0.1 seconds
+        let mut buffer = WaveformBuffer::new(100, 0.5);
+        buffer.set_sample_rate(1000.);
+        assert!(!buffer.raw);
+
+        buffer.set_duration(0.1);
+        assert!(buffer.raw);
+        // Slightly longer elements keep storing raw samples, until they span more
+        // than 1.25 samples
+        buffer.set_duration(0.12);
+        assert!(buffer.raw);
+        buffer.rescale_duration(0.13);
+        assert!(!buffer.raw);
+        buffer.set_duration(0.11);
+        assert!(!buffer.raw);
+        buffer.rescale_duration(0.1);
+        assert!(buffer.raw);
+
+        // Decimating elements are enqueued once they are finished, raw ones right away
+        buffer.set_duration(0.2);
+        buffer.enqueue(0.5);
+        assert_eq!(buffer[99], (0., 0.));
+        buffer.set_duration(0.1);
+        buffer.enqueue(0.5);
+        assert_eq!(buffer[99], (0.5, 0.5));
     }
 }
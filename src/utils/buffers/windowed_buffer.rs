@@ -0,0 +1,237 @@
+use std::fmt::{self, Debug};
+use std::ops::{Index, IndexMut};
+use std::sync::{Arc, Mutex};
+
+use super::{check_index, window_slots, VisualizerBuffer};
+
+/// Shows the newest part of another buffer, e.g. for a zoomed-in inset next to a
+/// graph of the whole buffer.
+///
+/// A `WindowedBuffer` holds the same shared buffer that the editor's lenses point
+/// to, and copies the elements that hold its newest `seconds` of audio data whenever
+/// a view reads it - so a graph of the whole buffer and a graph of the window can't
+/// disagree. The window is mapped to elements the same way as in
+/// [`PeakBuffer::newest_window()`](super::PeakBuffer::newest_window), using the
+/// buffer's [`duration()`](VisualizerBuffer::duration): The element that lies partly
+/// inside the window is included, and a window that reaches back further than the
+/// buffer holds, or was filled, holds all of those elements. Buffers that aren't
+/// time-based are shown in full, as far as they were filled.
+///
+/// ```
+/// // Inside the editor's model
+/// let newest = Arc::new(Mutex::new(WindowedBuffer::new(peak_buffer.clone(), 2.0)));
+///
+/// // The whole minute, with the newest two seconds at full resolution below it
+/// Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels);
+/// Graph::new(cx, Data::newest, (-32.0, 8.0), ValueScaling::Decibels)
+///     .height(Pixels(80.0));
+/// ```
+pub struct WindowedBuffer<B> {
+    buffer: Arc<Mutex<B>>,
+    seconds: f32,
+    values: Vec<f32>,
+    // The generation of the buffer the values were copied from
+    generation: Option<u64>,
+    // Changes whenever the values are copied again, which is the windowed buffer's
+    // own generation
+    copies: u64,
+    duration: Option<f32>,
+    enqueued: Option<u64>,
+    clears: Option<u64>,
+    partial: Option<(f32, f32)>,
+}
+
+impl<B> WindowedBuffer<B>
+where
+    B: VisualizerBuffer<f32, Output = f32>,
+{
+    /// Constructs a new `WindowedBuffer` that shows the newest `seconds` of a buffer.
+    pub fn new(buffer: Arc<Mutex<B>>, seconds: f32) -> Self {
+        Self {
+            buffer,
+            seconds,
+            values: Vec::new(),
+            generation: None,
+            copies: 0,
+            duration: None,
+            enqueued: None,
+            clears: None,
+            partial: None,
+        }
+    }
+
+    /// Sets the length of the window, in seconds.
+    pub fn set_window(self: &mut Self, seconds: f32) {
+        self.seconds = seconds;
+        self.generation = None;
+    }
+
+    /// Returns the length of the window, in seconds.
+    pub fn window(self: &Self) -> f32 {
+        self.seconds
+    }
+}
+
+impl<B> VisualizerBuffer<f32> for WindowedBuffer<B>
+where
+    B: VisualizerBuffer<f32, Output = f32>,
+{
+    /// Does nothing - the contents are copied from another buffer.
+    fn enqueue(self: &mut Self, _value: f32) {}
+
+    /// Does nothing - the contents are copied from another buffer.
+    fn enqueue_buffer(
+        self: &mut Self,
+        _buffer: &mut nih_plug::buffer::Buffer,
+        _channel: Option<usize>,
+    ) {
+    }
+
+    /// Clears the copied elements, which are copied again on the next refresh.
+    fn clear(self: &mut Self) {
+        self.values.clear();
+        self.generation = None;
+        self.copies = self.copies.wrapping_add(1);
+    }
+
+    /// Does nothing - the length follows the window.
+    fn grow(self: &mut Self, _size: usize) {}
+
+    /// Does nothing - the length follows the window.
+    fn shrink(self: &mut Self, _size: usize) {}
+
+    fn len(self: &Self) -> usize {
+        self.values.len()
+    }
+
+    fn duration(self: &Self) -> Option<f32> {
+        self.duration
+    }
+
+    fn partial(self: &Self) -> Option<(f32, f32)> {
+        self.partial
+    }
+
+    fn generation(self: &Self) -> Option<u64> {
+        Some(self.copies)
+    }
+
+    fn enqueued(self: &Self) -> Option<u64> {
+        self.enqueued
+    }
+
+    fn clears(self: &Self) -> Option<u64> {
+        self.clears
+    }
+
+    fn refresh(self: &mut Self) {
+        // A poisoned buffer keeps the last values
+        let Ok(mut buffer) = self.buffer.lock() else {
+            return;
+        };
+        buffer.refresh();
+        self.partial = buffer.partial();
+        self.enqueued = buffer.enqueued();
+        self.clears = buffer.clears();
+
+        let generation = buffer.generation();
+        if generation.is_some() && generation == self.generation {
+            return;
+        }
+
+        let len = buffer.len();
+        let slot_duration = buffer
+            .duration()
+            .map_or(0., |duration| duration as f64 / len as f64);
+        let slots = window_slots(self.seconds, slot_duration, len, self.enqueued);
+        self.values.clear();
+        self.values.extend((len - slots..len).map(|i| buffer[i]));
+        self.duration = buffer
+            .duration()
+            .map(|duration| duration * slots as f32 / len as f32);
+        self.generation = generation;
+        self.copies = self.copies.wrapping_add(1);
+    }
+}
+
+impl<B> Index<usize> for WindowedBuffer<B> {
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        check_index("windowed buffer", None, index, self.values.len());
+        &self.values[index]
+    }
+}
+impl<B> IndexMut<usize> for WindowedBuffer<B> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        check_index("windowed buffer", None, index, self.values.len());
+        self.copies = self.copies.wrapping_add(1);
+        &mut self.values[index]
+    }
+}
+
+impl<B> Debug for WindowedBuffer<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WindowedBuffer")
+            .field("seconds", &self.seconds)
+            .field("size", &self.values.len())
+            .field("duration", &self.duration)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WindowedBuffer;
+    use crate::utils::{PeakBuffer, RingBuffer, VisualizerBuffer};
+    use std::sync::{Arc, Mutex};
+
+    fn values<B>(buffer: &WindowedBuffer<B>) -> Vec<f32> {
+        (0..buffer.values.len()).map(|i| buffer[i]).collect()
+    }
+
+    #[test]
+    fn matches_newest_window() {
+        // 10 ms elements, each holding its index
+        let mut peaks = PeakBuffer::new(100, 1.0, 0.0);
+        peaks.set_sample_rate(1000.);
+        let peaks = Arc::new(Mutex::new(peaks));
+        let mut newest = WindowedBuffer::new(peaks.clone(), 0.025);
+
+        for i in 0..151 {
+            peaks.lock().unwrap().enqueue_slice(&[i as f32; 10]);
+        }
+        newest.refresh();
+        assert_eq!(values(&newest), vec![147., 148., 149.]);
+        assert!((newest.duration().unwrap() - 0.03).abs() < 1e-6);
+
+        for seconds in [0.001, 0.3, 0.1234, 2.0] {
+            newest.set_window(seconds);
+            newest.refresh();
+            let expected = peaks
+                .lock()
+                .unwrap()
+                .newest_window(seconds)
+                .collect::<Vec<_>>();
+            assert_eq!(values(&newest), expected, "{}", seconds);
+        }
+
+        // Follows the buffer as it scrolls
+        newest.set_window(0.02);
+        peaks.lock().unwrap().enqueue_slice(&[151.; 10]);
+        newest.refresh();
+        assert_eq!(values(&newest), vec![149., 150.]);
+    }
+
+    #[test]
+    fn without_duration() {
+        // A buffer that isn't time-based is shown in full, as far as it was filled
+        let mut ring = RingBuffer::new(4);
+        ring.enqueue(1.);
+        ring.enqueue(2.);
+        let mut newest = WindowedBuffer::new(Arc::new(Mutex::new(ring)), 0.5);
+        newest.refresh();
+        assert_eq!(values(&newest), vec![1., 2.]);
+        assert_eq!(newest.duration(), None);
+    }
+}
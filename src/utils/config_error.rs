@@ -0,0 +1,430 @@
+use std::error::Error;
+use std::fmt;
+
+/// An invalid setting that was passed to a buffer.
+///
+/// Returned by the fallible constructors and setters of the buffers, like
+/// [`PeakBuffer::try_new()`](super::PeakBuffer::try_new) or
+/// [`PeakBuffer::try_set_duration()`](super::PeakBuffer::try_set_duration). Its
+/// message names the buffer, the setting and the value, e.g. `Invalid peak buffer
+/// duration: -1 is too small, it needs to be greater than 0`.
+///
+/// The infallible versions of the same functions fail a debug assertion with this
+/// message instead, and clamp the setting to the nearest valid value in release
+/// builds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigError {
+    /// The value is NaN, or infinite where only finite values make sense.
+    NonFinite {
+        /// The kind of buffer, e.g. `peak buffer`.
+        buffer: &'static str,
+        /// The setting, e.g. `duration`.
+        setting: &'static str,
+        value: f32,
+    },
+    /// The value lies outside of the range from `min` to `max`, inclusive.
+    OutOfRange {
+        /// The kind of buffer, e.g. `peak buffer`.
+        buffer: &'static str,
+        /// The setting, e.g. `decay`.
+        setting: &'static str,
+        value: f32,
+        min: f32,
+        max: f32,
+    },
+    /// The value isn't greater than `min`.
+    TooSmall {
+        /// The kind of buffer, e.g. `peak buffer`.
+        buffer: &'static str,
+        /// The setting, e.g. `size`.
+        setting: &'static str,
+        value: f32,
+        min: f32,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ConfigError::NonFinite {
+                buffer,
+                setting,
+                value,
+            } => write!(
+                f,
+                "Invalid {} {}: {} is not a finite number",
+                buffer, setting, value
+            ),
+            ConfigError::OutOfRange {
+                buffer,
+                setting,
+                value,
+                min,
+                max,
+            } if max == f32::INFINITY => write!(
+                f,
+                "Invalid {} {}: {} is out of range, it needs to be at least {}",
+                buffer, setting, value, min
+            ),
+            ConfigError::OutOfRange {
+                buffer,
+                setting,
+                value,
+                min,
+                max,
+            } => write!(
+                f,
+                "Invalid {} {}: {} is out of range, it needs to be between {} and {}",
+                buffer, setting, value, min, max
+            ),
+            ConfigError::TooSmall {
+                buffer,
+                setting,
+                value,
+                min,
+            } => write!(
+                f,
+                "Invalid {} {}: {} is too small, it needs to be greater than {}",
+                buffer, setting, value, min
+            ),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+// The rules every buffer validates its settings with, so that they can't diverge
+// between buffers. Each check returns the valid value, and each clamp returns the
+// nearest valid value, which the infallible setters use in release builds.
+
+/// Checks a duration, in seconds or milliseconds, which needs to be finite and
+/// positive.
+pub(crate) fn check_duration(
+    buffer: &'static str,
+    setting: &'static str,
+    duration: f32,
+) -> Result<f32, ConfigError> {
+    if !duration.is_finite() {
+        return Err(ConfigError::NonFinite {
+            buffer,
+            setting,
+            value: duration,
+        });
+    }
+    if duration <= 0. {
+        return Err(ConfigError::TooSmall {
+            buffer,
+            setting,
+            value: duration,
+            min: 0.,
+        });
+    }
+    Ok(duration)
+}
+
+/// Checks a density, in elements per second, which needs to be finite and positive.
+pub(crate) fn check_density(buffer: &'static str, density: f32) -> Result<f32, ConfigError> {
+    check_duration(buffer, "density", density)
+}
+
+/// Checks a decay time, in milliseconds, which can't be negative.
+///
+/// A decay of 0 makes values fall immediately, and an infinite decay holds them
+/// forever.
+pub(crate) fn check_decay(buffer: &'static str, decay: f32) -> Result<f32, ConfigError> {
+    if decay.is_nan() {
+        return Err(ConfigError::NonFinite {
+            buffer,
+            setting: "decay",
+            value: decay,
+        });
+    }
+    if decay < 0. {
+        return Err(ConfigError::OutOfRange {
+            buffer,
+            setting: "decay",
+            value: decay,
+            min: 0.,
+            max: f32::INFINITY,
+        });
+    }
+    Ok(decay)
+}
+
+/// Checks the number of elements of a buffer, which can't be 0.
+pub(crate) fn check_size(
+    buffer: &'static str,
+    setting: &'static str,
+    size: usize,
+) -> Result<usize, ConfigError> {
+    if size == 0 {
+        return Err(ConfigError::TooSmall {
+            buffer,
+            setting,
+            value: 0.,
+            min: 0.,
+        });
+    }
+    Ok(size)
+}
+
+/// Checks a range of values, whose ends need to be finite and ascending.
+pub(crate) fn check_range(
+    buffer: &'static str,
+    range: (f32, f32),
+) -> Result<(f32, f32), ConfigError> {
+    for (setting, value) in [("range minimum", range.0), ("range maximum", range.1)] {
+        if !value.is_finite() {
+            return Err(ConfigError::NonFinite {
+                buffer,
+                setting,
+                value,
+            });
+        }
+    }
+    if range.1 <= range.0 {
+        return Err(ConfigError::TooSmall {
+            buffer,
+            setting: "range maximum",
+            value: range.1,
+            min: range.0,
+        });
+    }
+    Ok(range)
+}
+
+pub(crate) fn clamp_duration(buffer: &'static str, setting: &'static str, duration: f32) -> f32 {
+    clamped(check_duration(buffer, setting, duration), || {
+        finite_or(duration, MIN_DURATION).max(MIN_DURATION)
+    })
+}
+
+pub(crate) fn clamp_density(buffer: &'static str, density: f32) -> f32 {
+    clamp_duration(buffer, "density", density)
+}
+
+pub(crate) fn clamp_decay(buffer: &'static str, decay: f32) -> f32 {
+    clamped(check_decay(buffer, decay), || {
+        if decay.is_nan() {
+            0.
+        } else {
+            decay.max(0.)
+        }
+    })
+}
+
+pub(crate) fn clamp_range(buffer: &'static str, range: (f32, f32)) -> (f32, f32) {
+    clamped(check_range(buffer, range), || {
+        let min = finite_or(range.0, 0.);
+        let max = finite_or(range.1, 1.);
+        if max > min {
+            (min, max)
+        } else if max < min {
+            (max, min)
+        } else {
+            (min, min + 1.)
+        }
+    })
+}
+
+// The smallest duration a clamped duration is set to
+const MIN_DURATION: f32 = f32::MIN_POSITIVE;
+
+/// Returns the checked value, or fails a debug assertion and returns the clamped
+/// value if it's invalid.
+fn clamped<T>(result: Result<T, ConfigError>, clamp: impl FnOnce() -> T) -> T {
+    result.unwrap_or_else(|error| {
+        debug_assert!(false, "{}", error);
+        clamp()
+    })
+}
+
+// Replaces NaN with a fallback, and infinities with the largest finite values
+fn finite_or(value: f32, fallback: f32) -> f32 {
+    if value.is_nan() {
+        fallback
+    } else {
+        value.clamp(f32::MIN, f32::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_decay, check_duration, check_range, check_size, ConfigError};
+    use crate::utils::{
+        BalanceBuffer, CountBuffer, HistogramBuffer, MinimaBuffer, NoteEventBuffer, PeakBuffer,
+        RMSBuffer, RingBuffer, TieredBuffer, VisualizerBuffer, WaveformBuffer,
+    };
+
+    const DURATIONS: [f32; 5] = [f32::NAN, f32::INFINITY, f32::NEG_INFINITY, 0., -1.];
+    const DECAYS: [f32; 3] = [f32::NAN, f32::NEG_INFINITY, -1.];
+    const RANGES: [(f32, f32); 5] = [
+        (f32::NAN, 0.),
+        (-40., f32::INFINITY),
+        (f32::NEG_INFINITY, 0.),
+        (0., 0.),
+        (0., -40.),
+    ];
+
+    // Asserts that a setting was rejected, and that the error names it
+    #[track_caller]
+    fn assert_rejects<T>(result: Result<T, ConfigError>, expected: &str) {
+        match result {
+            Ok(_) => panic!("expected the {} to be rejected", expected),
+            Err(
+                ConfigError::NonFinite { setting, .. }
+                | ConfigError::OutOfRange { setting, .. }
+                | ConfigError::TooSmall { setting, .. },
+            ) => assert!(setting.contains(expected), "{} isn't {}", setting, expected),
+        }
+    }
+
+    #[test]
+    fn messages() {
+        assert_eq!(
+            check_duration("peak buffer", "duration", -1.)
+                .unwrap_err()
+                .to_string(),
+            "Invalid peak buffer duration: -1 is too small, it needs to be greater than 0"
+        );
+        assert_eq!(
+            check_decay("minima buffer", -5.).unwrap_err().to_string(),
+            "Invalid minima buffer decay: -5 is out of range, it needs to be at least 0"
+        );
+        assert_eq!(
+            check_range("histogram buffer", (0., -40.))
+                .unwrap_err()
+                .to_string(),
+            "Invalid histogram buffer range maximum: -40 is too small, it needs to be greater than 0"
+        );
+        assert_eq!(
+            check_size("waveform buffer", "size", 0)
+                .unwrap_err()
+                .to_string(),
+            "Invalid waveform buffer size: 0 is too small, it needs to be greater than 0"
+        );
+        assert_eq!(
+            check_duration("RMS buffer", "RMS duration", f32::NAN)
+                .unwrap_err()
+                .to_string(),
+            "Invalid RMS buffer RMS duration: NaN is not a finite number"
+        );
+    }
+
+    #[test]
+    fn valid_values() {
+        assert_eq!(check_duration("peak buffer", "duration", 0.5), Ok(0.5));
+        assert_eq!(check_decay("peak buffer", 0.), Ok(0.));
+        assert_eq!(check_decay("peak buffer", f32::INFINITY), Ok(f32::INFINITY));
+        assert_eq!(check_size("peak buffer", "size", 1), Ok(1));
+        assert_eq!(check_range("histogram buffer", (-1., 1.)), Ok((-1., 1.)));
+        assert!(matches!(
+            check_range("histogram buffer", (f32::NAN, 1.)),
+            Err(ConfigError::NonFinite {
+                setting: "range minimum",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn invalid_sizes() {
+        assert_rejects(PeakBuffer::try_new(0, 1., 0.), "size");
+        assert_rejects(MinimaBuffer::try_new(0, 1., 0.), "size");
+        assert_rejects(WaveformBuffer::try_new(0, 1.), "size");
+        assert_rejects(BalanceBuffer::try_new(0, 1.), "size");
+        assert_rejects(CountBuffer::try_new(0, 1., 1.), "size");
+        assert_rejects(RMSBuffer::try_new(0, 1., 10.), "size");
+        assert_rejects(HistogramBuffer::try_new(0, 1.), "size");
+        assert_rejects(NoteEventBuffer::try_new(0, 1., 16), "size");
+        assert_rejects(TieredBuffer::try_new(0, 1., 8, 10., 0.), "fine size");
+        assert_rejects(TieredBuffer::try_new(8, 1., 0, 10., 0.), "coarse size");
+
+        assert_rejects(PeakBuffer::new(8, 1., 0.).try_resize(0), "size");
+        assert_rejects(MinimaBuffer::new(8, 1., 0.).try_resize(0), "size");
+        assert_rejects(WaveformBuffer::new(8, 1.).try_resize(0), "size");
+        assert_rejects(BalanceBuffer::new(8, 1.).try_resize(0), "size");
+        assert_rejects(CountBuffer::new(8, 1., 1.).try_resize(0), "size");
+        assert_rejects(RMSBuffer::new(8, 1., 10.).try_resize(0), "size");
+        assert_rejects(HistogramBuffer::new(8, 1.).try_resize(0), "size");
+        assert_rejects(NoteEventBuffer::new(8, 1., 16).try_resize(0), "size");
+        assert_rejects(TieredBuffer::new(8, 1., 8, 10., 0.).try_resize(0), "size");
+        assert_rejects(RingBuffer::<f32>::new(8).try_resize(0), "size");
+
+        // A rejected size leaves the buffer untouched
+        let mut buffer = PeakBuffer::new(8, 1., 0.);
+        assert!(buffer.try_resize(0).is_err());
+        assert_eq!(buffer.len(), 8);
+        assert_eq!(buffer.try_resize(16), Ok(()));
+        assert_eq!(buffer.len(), 16);
+    }
+
+    #[test]
+    fn invalid_durations() {
+        for duration in DURATIONS {
+            assert_rejects(PeakBuffer::try_new(8, duration, 0.), "duration");
+            assert_rejects(MinimaBuffer::try_new(8, duration, 0.), "duration");
+            assert_rejects(WaveformBuffer::try_new(8, duration), "duration");
+            assert_rejects(BalanceBuffer::try_new(8, duration), "duration");
+            assert_rejects(CountBuffer::try_new(8, duration, 1.), "duration");
+            assert_rejects(RMSBuffer::try_new(8, duration, 10.), "duration");
+            assert_rejects(RMSBuffer::try_new(8, 1., duration), "RMS duration");
+            assert_rejects(NoteEventBuffer::try_new(8, duration, 16), "duration");
+            assert_rejects(
+                TieredBuffer::try_new(8, duration, 8, 10., 0.),
+                "fine duration",
+            );
+            assert_rejects(
+                TieredBuffer::try_new(8, 1., 8, duration, 0.),
+                "coarse duration",
+            );
+
+            let mut peak = PeakBuffer::new(8, 1., 0.);
+            assert_rejects(peak.try_set_duration(duration), "duration");
+            assert_eq!(peak.duration(), Some(1.));
+            let mut minima = MinimaBuffer::new(8, 1., 0.);
+            assert_rejects(minima.try_set_duration(duration), "duration");
+            assert_eq!(minima.duration(), Some(1.));
+            let mut waveform = WaveformBuffer::new(8, 1.);
+            assert_rejects(waveform.try_set_duration(duration), "duration");
+            assert_eq!(waveform.duration(), Some(1.));
+            let mut balance = BalanceBuffer::new(8, 1.);
+            assert_rejects(balance.try_set_duration(duration), "duration");
+            assert_eq!(balance.duration(), Some(1.));
+            let mut count = CountBuffer::new(8, 1., 1.);
+            assert_rejects(count.try_set_duration(duration), "duration");
+            assert_eq!(count.duration(), Some(1.));
+            assert_rejects(
+                NoteEventBuffer::new(8, 1., 16).try_set_duration(duration),
+                "duration",
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_decays() {
+        for decay in DECAYS {
+            assert_rejects(PeakBuffer::try_new(8, 1., decay), "decay");
+            assert_rejects(MinimaBuffer::try_new(8, 1., decay), "decay");
+            assert_rejects(HistogramBuffer::try_new(8, decay), "decay");
+            assert_rejects(TieredBuffer::try_new(8, 1., 8, 10., decay), "decay");
+
+            assert_rejects(PeakBuffer::new(8, 1., 0.).try_set_decay(decay), "decay");
+            assert_rejects(MinimaBuffer::new(8, 1., 0.).try_set_decay(decay), "decay");
+            assert_rejects(HistogramBuffer::new(8, 1.).try_set_decay(decay), "decay");
+            assert_rejects(
+                TieredBuffer::new(8, 1., 8, 10., 0.).try_set_decay(decay),
+                "decay",
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_ranges() {
+        for range in RANGES {
+            let mut histogram = HistogramBuffer::new(8, 1.);
+            assert_rejects(histogram.try_set_range(range), "range");
+            assert_eq!(histogram.try_set_range((-40., 0.)), Ok(()));
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! The decay used by the peak buffers, and conversions from other ways of
+//! expressing it.
+//!
+//! Decay times in Cyma are the time it takes for a value to decrease by 12 dB, in
+//! milliseconds - precisely, the time it takes to fall to a quarter, which is
+//! 12.04 dB. This is the same convention that nih-plug's own peak meters use, so
+//! a single constant can drive both:
+//!
+//! ```
+//! const PEAK_METER_DECAY_MS: f32 = 150.0;
+//!
+//! // Inside the plug-in's peak meter, which decays once per sample
+//! self.peak_meter_decay_weight = decay_weight_per_sample(PEAK_METER_DECAY_MS, sample_rate);
+//!
+//! // Inside the editor's peak graph, which decays once per element
+//! let peak_buffer = PeakBuffer::new(800, 10.0, PEAK_METER_DECAY_MS);
+//! ```
+
+/// Returns the weight by which a [`PeakBuffer`](super::PeakBuffer) or
+/// [`MinimaBuffer`](super::MinimaBuffer) multiplies the previous element when
+/// decaying.
+///
+/// * `decay` - The time it takes for a value to decrease by 12 dB, in milliseconds
+/// * `size` - The number of elements inside the buffer
+/// * `duration` - The duration (in seconds) of the audio data inside the buffer
+///
+/// The buffer enqueues `size / duration` elements per second, so after `decay`
+/// milliseconds, the weight has been applied `decay / 1000 * size / duration` times,
+/// which multiplies the value by 0.25 (-12 dB). Since the weight is applied once per
+/// element, the value falls by the same number of decibels every second.
+///
+/// With a decay of 0, values fall immediately.
+pub fn decay_weight(decay: f32, size: usize, duration: f32) -> f32 {
+    decay_weight_per_slot(decay, duration as f64 / size as f64)
+}
+
+/// Returns the weight for a decay that is applied once per element, given the time
+/// that each element spans.
+///
+/// * `decay` - The time it takes for a value to decrease by 12 dB, in milliseconds
+/// * `slot_duration` - The time between two elements, in seconds
+///
+/// The weight only depends on the time between elements, so a value falls at the
+/// same rate no matter how many elements the buffer holds.
+pub(crate) fn decay_weight_per_slot(decay: f32, slot_duration: f64) -> f32 {
+    0.25f64.powf(slot_duration / (decay as f64 / 1000.)) as f32
+}
+
+/// Returns the weight for a decay that is applied once per sample, like in
+/// nih-plug's peak meters.
+///
+/// * `decay` - The time it takes for a value to decrease by 12 dB, in milliseconds
+/// * `sample_rate` - The sample rate of the audio
+///
+/// This is [`decay_weight()`] for a buffer that holds one element per sample.
+pub fn decay_weight_per_sample(decay: f32, sample_rate: f32) -> f32 {
+    decay_weight_per_slot(decay, (sample_rate as f64).recip())
+}
+
+/// Converts a release, in decibels per second, into a decay time, in milliseconds.
+///
+/// A decaying value falls by the same number of decibels every second, so the
+/// 12.04 dB of the decay time take `12.04 / db_per_second` seconds.
+pub fn decay_ms_from_db_per_second(db_per_second: f32) -> f32 {
+    1000. * DECAY_DB / db_per_second
+}
+
+// The decrease in decibels over the decay time, i.e. a factor of 0.25
+const DECAY_DB: f32 = 40. * std::f32::consts::LOG10_2;
+
+/// The tempo that a decay given in beats assumes until the plug-in reports one, in
+/// BPM; See [`PeakBuffer::set_decay_beats()`](super::PeakBuffer::set_decay_beats).
+pub const DEFAULT_TEMPO: f64 = 120.;
+
+// How far the tempo needs to move, in BPM, before the decay follows it; Hosts
+// report tempo ramps and automation every block, and often with some jitter
+const TEMPO_EPSILON: f64 = 0.01;
+
+/// Converts a decay in beats into milliseconds, at a tempo in BPM.
+pub(crate) fn decay_ms_from_beats(beats: f32, tempo: f64) -> f32 {
+    (beats as f64 * 60_000. / tempo) as f32
+}
+
+/// The tempo a buffer's decay follows, and its decay in beats, if it's given in
+/// beats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct TempoDecay {
+    beats: Option<f32>,
+    // The tempo the decay was last computed for
+    tempo: f64,
+}
+
+impl Default for TempoDecay {
+    fn default() -> Self {
+        Self {
+            beats: None,
+            tempo: DEFAULT_TEMPO,
+        }
+    }
+}
+
+impl TempoDecay {
+    /// Gives the decay in beats from now on, and returns it in milliseconds.
+    pub(crate) fn set_beats(self: &mut Self, beats: f32) -> f32 {
+        self.beats = Some(beats);
+        decay_ms_from_beats(beats, self.tempo)
+    }
+
+    /// Gives the decay in milliseconds from now on.
+    pub(crate) fn clear_beats(self: &mut Self) {
+        self.beats = None;
+    }
+
+    /// Returns the decay in beats, if it's given in beats.
+    pub(crate) fn beats(self: &Self) -> Option<f32> {
+        self.beats
+    }
+
+    /// Updates the tempo, and returns the decay in milliseconds if it needs to be
+    /// recomputed.
+    ///
+    /// Tempos that aren't positive and finite are ignored, as are changes of up to
+    /// [`TEMPO_EPSILON`] - measured from the tempo the decay was last computed for,
+    /// so that a slow ramp still gets through.
+    pub(crate) fn set_tempo(self: &mut Self, tempo: f64) -> Option<f32> {
+        if !(tempo.is_finite() && tempo > 0.) || (tempo - self.tempo).abs() <= TEMPO_EPSILON {
+            return None;
+        }
+        self.tempo = tempo;
+        self.beats.map(|beats| decay_ms_from_beats(beats, tempo))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        decay_ms_from_db_per_second, decay_weight, decay_weight_per_sample, TempoDecay,
+        DEFAULT_TEMPO,
+    };
+    use nih_plug::util::gain_to_db;
+
+    fn assert_approx(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() <= expected.abs() * 1e-5,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn pinned_weights() {
+        // 80 elements per second, so 50 ms span 4 elements
+        assert_approx(decay_weight(50., 800, 10.), 0.70710677);
+        // 20 elements per second, so 300 ms span 6 elements
+        assert_approx(decay_weight(300., 200, 10.), 0.7937005);
+        // 100 elements per second, so 1 s spans 100 elements
+        assert_approx(decay_weight(1000., 100, 1.), 0.986233);
+        // Values fall immediately
+        assert_eq!(decay_weight(0., 800, 10.), 0.);
+    }
+
+    #[test]
+    fn decreases_by_12_db() {
+        for (decay, size, duration) in [(50., 800, 10.), (300., 200, 10.), (1234., 1000, 30.)] {
+            let elements = decay / 1000. * size as f32 / duration;
+            let weight = decay_weight(decay, size, duration);
+            assert!((weight.powf(elements) - 0.25).abs() < 1e-4);
+            assert!((gain_to_db(weight.powf(elements)) + 12.).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn per_sample() {
+        // One element per sample
+        assert_eq!(
+            decay_weight_per_sample(150., 48_000.),
+            decay_weight(150., 48_000, 1.)
+        );
+        let weight = decay_weight_per_sample(150., 48_000.);
+        assert!((weight.powf(0.15 * 48_000.) - 0.25).abs() < 1e-3);
+    }
+
+    #[test]
+    fn db_per_second() {
+        assert_approx(decay_ms_from_db_per_second(12.0412), 1000.);
+        assert_approx(
+            decay_ms_from_db_per_second(24.),
+            decay_ms_from_db_per_second(12.) / 2.,
+        );
+
+        // At 24 dB/s, one second of 100 elements falls by 24 dB
+        let weight = decay_weight(decay_ms_from_db_per_second(24.), 100, 1.);
+        assert!((gain_to_db(weight.powi(100)) + 24.).abs() < 0.01);
+    }
+
+    #[test]
+    fn tempo() {
+        let mut tempo = TempoDecay::default();
+        // A quarter note at the default tempo
+        assert_eq!(DEFAULT_TEMPO, 120.);
+        assert_eq!(tempo.set_beats(1.), 500.);
+        assert_eq!(tempo.set_tempo(240.), Some(250.));
+        assert_eq!(tempo.set_tempo(60.), Some(1000.));
+
+        // Invalid tempos are ignored
+        for invalid in [0., -120., f64::NAN, f64::INFINITY] {
+            assert_eq!(tempo.set_tempo(invalid), None);
+        }
+        assert_eq!(tempo.set_beats(0.5), 500.);
+
+        // A tempo that's reported without a decay in beats is kept for later
+        tempo.clear_beats();
+        assert_eq!(tempo.set_tempo(150.), None);
+        assert_eq!(tempo.set_beats(1.), 400.);
+    }
+
+    #[test]
+    fn tempo_jitter() {
+        let mut tempo = TempoDecay::default();
+        tempo.set_beats(1.);
+        // Reports a tempo once per block, and counts how often the decay changes
+        let mut recomputations = |tempos: &mut dyn Iterator<Item = f64>| {
+            tempos.filter(|bpm| tempo.set_tempo(*bpm).is_some()).count()
+        };
+
+        // Jitter around a steady tempo
+        let mut jitter = (0..10_000).map(|block| if block % 2 == 0 { 120.004 } else { 119.996 });
+        assert_eq!(recomputations(&mut jitter), 0);
+
+        // A slow ramp, which adds up past the epsilon every third block
+        let mut ramp = (0..100).map(|block| 120. + block as f64 * 0.004);
+        assert_eq!(recomputations(&mut ramp), 33);
+    }
+}
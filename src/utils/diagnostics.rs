@@ -0,0 +1,275 @@
+#[cfg(feature = "diagnostics")]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "diagnostics")]
+use std::sync::{Arc, TryLockError};
+use std::sync::{LockResult, Mutex, MutexGuard};
+use std::time::Duration;
+#[cfg(feature = "diagnostics")]
+use std::time::Instant;
+
+/// Counts the events on the audio thread that could cause glitches, for a single
+/// shared buffer.
+///
+/// When the UI is open, the audio thread competes with the editor for the lock of
+/// each shared buffer. A `Diagnostics` handle is attached to the parts of a buffer
+/// that can tell whether this is a problem, and counts:
+///
+/// * Locks that had to wait for the editor, through [`lock()`](Self::lock) or a
+///   [`StagedBuffer`](super::StagedBuffer), along with the longest wait
+/// * Samples that a buffer skipped, because it was offline or not capturing
+/// * Values that were overwritten before they were read, by a
+///   [`TapConsumer`](super::TapConsumer) or a [`SpectrumInput`](super::SpectrumInput)
+/// * Blocks that a [`TryLockBuffer`](super::TryLockBuffer) dropped, because its
+///   overflow was full
+///
+/// The handle is cheap to clone, and all clones share the same counters. The
+/// counters are relaxed atomics, so counting costs next to nothing.
+///
+/// Counting is only enabled with the `diagnostics` feature. Without it, the handle
+/// holds no data, all counting compiles to nothing, and the snapshots are empty.
+///
+/// ```
+/// let diagnostics = Diagnostics::new();
+/// peak_buffer.lock().unwrap().set_diagnostics(diagnostics.clone());
+///
+/// // Inside process(), instead of peak_buffer.lock()
+/// diagnostics.lock(&peak_buffer).unwrap().enqueue_buffer(buffer, None);
+///
+/// // Anywhere else
+/// nih_log!("{:?}", diagnostics.snapshot());
+/// ```
+#[derive(Clone, Default)]
+pub struct Diagnostics {
+    #[cfg(feature = "diagnostics")]
+    counters: Arc<Counters>,
+}
+
+#[cfg(feature = "diagnostics")]
+#[derive(Default)]
+struct Counters {
+    contended_locks: AtomicU64,
+    max_wait_nanos: AtomicU64,
+    skipped_samples: AtomicU64,
+    overwritten: AtomicU64,
+    dropped_blocks: AtomicU64,
+}
+
+/// The counters of a [`Diagnostics`] handle at one point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DiagnosticsSnapshot {
+    /// The number of locks that had to wait, because the lock was held elsewhere.
+    pub contended_locks: u64,
+    /// The longest time a lock had to wait.
+    pub max_wait: Duration,
+    /// The number of samples that were skipped, because the buffer was offline or not
+    /// capturing.
+    pub skipped_samples: u64,
+    /// The number of values that were overwritten before they were read.
+    pub overwritten: u64,
+    /// The number of blocks that were dropped, because the lock was held for too long.
+    pub dropped_blocks: u64,
+}
+
+impl Diagnostics {
+    /// Constructs a new `Diagnostics` handle, with all counters at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `true` if the `diagnostics` feature is enabled, and events are counted.
+    pub const fn is_enabled() -> bool {
+        cfg!(feature = "diagnostics")
+    }
+
+    /// Returns the current value of all counters.
+    pub fn snapshot(self: &Self) -> DiagnosticsSnapshot {
+        #[cfg(feature = "diagnostics")]
+        {
+            let counters = &self.counters;
+            DiagnosticsSnapshot {
+                contended_locks: counters.contended_locks.load(Ordering::Relaxed),
+                max_wait: Duration::from_nanos(counters.max_wait_nanos.load(Ordering::Relaxed)),
+                skipped_samples: counters.skipped_samples.load(Ordering::Relaxed),
+                overwritten: counters.overwritten.load(Ordering::Relaxed),
+                dropped_blocks: counters.dropped_blocks.load(Ordering::Relaxed),
+            }
+        }
+        #[cfg(not(feature = "diagnostics"))]
+        DiagnosticsSnapshot::default()
+    }
+
+    /// Resets all counters to zero.
+    pub fn reset(self: &Self) {
+        #[cfg(feature = "diagnostics")]
+        {
+            let counters = &self.counters;
+            counters.contended_locks.store(0, Ordering::Relaxed);
+            counters.max_wait_nanos.store(0, Ordering::Relaxed);
+            counters.skipped_samples.store(0, Ordering::Relaxed);
+            counters.overwritten.store(0, Ordering::Relaxed);
+            counters.dropped_blocks.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Locks a mutex, counting whether the lock had to wait and for how long.
+    ///
+    /// The lock is tried first, so an uncontended lock doesn't read the clock.
+    /// Without the `diagnostics` feature, this is a plain [`Mutex::lock()`].
+    pub fn lock<'a, T>(self: &Self, mutex: &'a Mutex<T>) -> LockResult<MutexGuard<'a, T>> {
+        #[cfg(feature = "diagnostics")]
+        {
+            match mutex.try_lock() {
+                Ok(guard) => return Ok(guard),
+                Err(TryLockError::Poisoned(poisoned)) => return Err(poisoned),
+                Err(TryLockError::WouldBlock) => {}
+            }
+            let start = Instant::now();
+            let result = mutex.lock();
+            self.waited(start.elapsed());
+            result
+        }
+        #[cfg(not(feature = "diagnostics"))]
+        mutex.lock()
+    }
+
+    /// Counts a lock that had to wait for the given time.
+    #[inline]
+    #[cfg_attr(not(feature = "diagnostics"), allow(unused_variables))]
+    pub(crate) fn waited(self: &Self, wait: Duration) {
+        #[cfg(feature = "diagnostics")]
+        {
+            let counters = &self.counters;
+            counters.contended_locks.fetch_add(1, Ordering::Relaxed);
+            let nanos = wait.as_nanos().min(u64::MAX as u128) as u64;
+            counters.max_wait_nanos.fetch_max(nanos, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts samples that a buffer skipped.
+    #[inline]
+    #[cfg_attr(not(feature = "diagnostics"), allow(unused_variables))]
+    pub(crate) fn skipped(self: &Self, samples: usize) {
+        #[cfg(feature = "diagnostics")]
+        self.counters
+            .skipped_samples
+            .fetch_add(samples as u64, Ordering::Relaxed);
+    }
+
+    /// Counts values that were overwritten before they were read.
+    #[inline]
+    #[cfg_attr(not(feature = "diagnostics"), allow(unused_variables))]
+    pub(crate) fn overwritten(self: &Self, values: u64) {
+        #[cfg(feature = "diagnostics")]
+        self.counters
+            .overwritten
+            .fetch_add(values, Ordering::Relaxed);
+    }
+
+    /// Counts blocks that were dropped.
+    #[inline]
+    #[cfg_attr(not(feature = "diagnostics"), allow(unused_variables))]
+    pub(crate) fn dropped_blocks(self: &Self, blocks: u64) {
+        #[cfg(feature = "diagnostics")]
+        self.counters
+            .dropped_blocks
+            .fetch_add(blocks, Ordering::Relaxed);
+    }
+}
+
+// Buffers that derive `PartialEq` hold a handle; The handle doesn't take part in
+// their comparison
+impl PartialEq for Diagnostics {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl std::fmt::Debug for Diagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.snapshot().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Diagnostics;
+    use crate::utils::{PeakBuffer, VisualizerBuffer};
+
+    #[test]
+    #[cfg(not(feature = "diagnostics"))]
+    fn disabled() {
+        assert_eq!(std::mem::size_of::<Diagnostics>(), 0);
+
+        let diagnostics = Diagnostics::new();
+        let mut buffer = PeakBuffer::new(8, 1.0, 0.0);
+        buffer.set_diagnostics(diagnostics.clone());
+        buffer.set_offline(true);
+        buffer.enqueue(1.0);
+        assert_eq!(diagnostics.snapshot(), Default::default());
+    }
+
+    #[test]
+    #[cfg(feature = "diagnostics")]
+    fn skipped_and_overwritten() {
+        use crate::utils::{BufferTap, TapMode};
+
+        let diagnostics = Diagnostics::new();
+        let mut buffer = PeakBuffer::new(8, 1.0, 0.0);
+        buffer.set_sample_rate(32.);
+        buffer.set_diagnostics(diagnostics.clone());
+
+        buffer.enqueue(1.0);
+        buffer.set_offline(true);
+        for _ in 0..10 {
+            buffer.enqueue(1.0);
+        }
+        assert_eq!(diagnostics.snapshot().skipped_samples, 10);
+
+        let (mut tap, mut consumer) = BufferTap::new(4, TapMode::Samples);
+        consumer.set_diagnostics(diagnostics.clone());
+        for value in 0..10 {
+            tap.push(value as f32);
+        }
+        assert_eq!(consumer.try_iter().count(), 4);
+        assert_eq!(diagnostics.snapshot().overwritten, 6);
+
+        diagnostics.reset();
+        assert_eq!(diagnostics.snapshot(), Default::default());
+    }
+
+    #[test]
+    #[cfg(feature = "diagnostics")]
+    fn contention() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
+
+        let diagnostics = Diagnostics::new();
+        let shared = Arc::new(Mutex::new(PeakBuffer::new(800, 1.0, 0.0)));
+        let done = Arc::new(AtomicBool::new(false));
+
+        // Stands in for an editor that holds the lock while it draws
+        let editor = {
+            let (shared, done) = (shared.clone(), done.clone());
+            std::thread::spawn(move || {
+                while !done.load(Ordering::Relaxed) {
+                    let _buffer = shared.lock().unwrap();
+                    std::thread::sleep(Duration::from_micros(200));
+                }
+            })
+        };
+
+        // Stands in for the audio thread
+        for _ in 0..200 {
+            diagnostics.lock(&shared).unwrap().enqueue(0.5);
+            std::thread::sleep(Duration::from_micros(50));
+        }
+        done.store(true, Ordering::Relaxed);
+        editor.join().unwrap();
+
+        let snapshot = diagnostics.snapshot();
+        assert!(snapshot.contended_locks > 0);
+        assert!(snapshot.max_wait > Duration::ZERO);
+        assert_eq!(snapshot.skipped_samples, 0);
+    }
+}
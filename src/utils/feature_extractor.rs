@@ -0,0 +1,502 @@
+use std::sync::{Arc, Mutex};
+
+use super::config_error::{check_duration, check_size, clamp_duration, ConfigError};
+use super::RingBuffer;
+
+/// A feature of the signal that a [`FeatureExtractor`] can compute per element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Feature {
+    /// The highest absolute sample.
+    Peak,
+    /// The root mean square of the samples.
+    Rms,
+    /// The ratio between the peak and the RMS - about 1.414 for a sine, and 1 for a
+    /// square wave. Silent elements have a crest factor of 0.
+    CrestFactor,
+    /// How often the signal crosses zero, in crossings per second - twice the
+    /// frequency for a sine.
+    ZeroCrossingRate,
+    /// The mean of the samples.
+    DcOffset,
+}
+
+impl Feature {
+    /// All features, in the order of their declaration.
+    pub const ALL: [Feature; 5] = [
+        Feature::Peak,
+        Feature::Rms,
+        Feature::CrestFactor,
+        Feature::ZeroCrossingRate,
+        Feature::DcOffset,
+    ];
+
+    fn bit(self: Self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// A set of [`Feature`]s.
+///
+/// ```
+/// let features = Features::from([Feature::CrestFactor, Feature::ZeroCrossingRate]);
+/// assert!(features.contains(Feature::CrestFactor));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Features(u8);
+
+impl Features {
+    /// The set without any features.
+    pub const NONE: Features = Features(0);
+
+    /// Returns the set of all features.
+    pub fn all() -> Self {
+        Self::from(Feature::ALL)
+    }
+
+    /// Returns the set with the feature added.
+    pub fn with(self: Self, feature: Feature) -> Self {
+        Self(self.0 | feature.bit())
+    }
+
+    /// Returns whether the set contains the feature.
+    pub fn contains(self: &Self, feature: Feature) -> bool {
+        self.0 & feature.bit() != 0
+    }
+
+    /// Returns whether the set contains any of the features.
+    pub fn contains_any(self: &Self, features: &[Feature]) -> bool {
+        features.iter().any(|feature| self.contains(*feature))
+    }
+
+    /// Returns an iterator over the features inside the set, in the order of their
+    /// declaration.
+    pub fn iter(self: &Self) -> impl Iterator<Item = Feature> + '_ {
+        Feature::ALL
+            .into_iter()
+            .filter(|feature| self.contains(*feature))
+    }
+}
+
+impl From<Feature> for Features {
+    fn from(feature: Feature) -> Self {
+        Self::NONE.with(feature)
+    }
+}
+
+impl<const N: usize> From<[Feature; N]> for Features {
+    fn from(features: [Feature; N]) -> Self {
+        features.into_iter().collect()
+    }
+}
+
+impl FromIterator<Feature> for Features {
+    fn from_iter<I: IntoIterator<Item = Feature>>(features: I) -> Self {
+        features.into_iter().fold(Self::NONE, Self::with)
+    }
+}
+
+/// Computes features of the signal, like its crest factor or zero-crossing rate, and
+/// feeds a buffer per feature.
+///
+/// The extractor counts elements the same way a [`PeakBuffer`](super::PeakBuffer)
+/// does, and computes each enabled feature over the samples of an element once it is
+/// finalized. So, when it's constructed with the same size and duration as a
+/// `PeakBuffer` and fed the same samples, its elements line up with the
+/// `PeakBuffer`'s.
+///
+/// Only the accumulators that the enabled features need are kept up to date, so
+/// disabled features cost nothing. Features that need a spectrum, like the spectral
+/// flatness, aren't computed by the extractor.
+///
+/// The editor reads each feature through the buffer returned by
+/// [`feature()`](Self::feature), just like any other shared buffer.
+///
+/// ```
+/// // Inside the plug-in's constructor
+/// let features = FeatureExtractor::new(
+///     800,
+///     10.0,
+///     [Feature::CrestFactor, Feature::ZeroCrossingRate],
+/// );
+///
+/// // Inside the editor, a graph of the crest factor
+/// Graph::new(cx, Data::crest_factor, (1.0, 4.0), ValueScaling::Linear);
+/// ```
+#[derive(Debug)]
+pub struct FeatureExtractor {
+    features: Features,
+    // The buffer of each feature, indexed by the feature; `None` if it's disabled
+    buffers: [Option<Arc<Mutex<RingBuffer<f32>>>>; Feature::ALL.len()],
+    size: usize,
+    duration: f32,
+    sample_rate: f32,
+    // The gap between elements in samples
+    sample_delta: f32,
+    // The current time, counts down from sample_delta to 0
+    t: f32,
+    // Whether the plug-in is rendering offline, in which case nothing is enqueued
+    offline: bool,
+
+    // Which accumulators the enabled features need
+    needs_peak: bool,
+    needs_energy: bool,
+    needs_sum: bool,
+    needs_crossings: bool,
+
+    // The accumulators of the element that is being accumulated
+    peak: f32,
+    energy: f32,
+    sum: f32,
+    crossings: u32,
+    count: u32,
+    // Whether the previous sample was negative, or `None` before the first sample
+    negative: Option<bool>,
+}
+
+impl FeatureExtractor {
+    /// Constructs a new `FeatureExtractor`.
+    ///
+    /// * `size` - The length of each feature's buffer in elements
+    /// * `duration` - The duration (in seconds) of the audio data inside the buffers
+    /// * `features` - The features to compute
+    ///
+    /// The extractor needs to be provided a sample rate after initialization - do
+    /// this by calling [`set_sample_rate`](Self::set_sample_rate) inside your
+    /// [`initialize()`](nih_plug::plugin::Plugin::initialize) function.
+    pub fn new(size: usize, duration: f32, features: impl Into<Features>) -> Self {
+        let features = features.into();
+        let mut buffers: [Option<Arc<Mutex<RingBuffer<f32>>>>; Feature::ALL.len()] =
+            Default::default();
+        for feature in features.iter() {
+            buffers[feature as usize] = Some(Arc::new(Mutex::new(RingBuffer::new(size))));
+        }
+        Self {
+            features,
+            buffers,
+            size,
+            duration: clamp_duration("feature extractor", "duration", duration),
+            sample_rate: 0.,
+            sample_delta: 0.,
+            t: 0.,
+            offline: false,
+
+            needs_peak: features.contains_any(&[Feature::Peak, Feature::CrestFactor]),
+            needs_energy: features.contains_any(&[Feature::Rms, Feature::CrestFactor]),
+            needs_sum: features.contains(Feature::DcOffset),
+            needs_crossings: features.contains(Feature::ZeroCrossingRate),
+
+            peak: 0.,
+            energy: 0.,
+            sum: 0.,
+            crossings: 0,
+            count: 0,
+            negative: None,
+        }
+    }
+
+    /// Constructs a new `FeatureExtractor`, or returns a [`ConfigError`] if the size
+    /// is 0 or the duration isn't positive; See [`new()`](Self::new).
+    pub fn try_new(
+        size: usize,
+        duration: f32,
+        features: impl Into<Features>,
+    ) -> Result<Self, ConfigError> {
+        check_size("feature extractor", "size", size)?;
+        check_duration("feature extractor", "duration", duration)?;
+        Ok(Self::new(size, duration, features))
+    }
+
+    /// Returns the features that the extractor computes.
+    pub fn features(self: &Self) -> Features {
+        self.features
+    }
+
+    /// Returns the shared buffer of the feature, or `None` if the feature isn't
+    /// computed.
+    pub fn feature(self: &Self, feature: Feature) -> Option<Arc<Mutex<RingBuffer<f32>>>> {
+        self.buffers[feature as usize].clone()
+    }
+
+    /// Returns the feature's value for the most recently finalized element, or `None`
+    /// if the feature isn't computed.
+    ///
+    /// This locks the feature's buffer.
+    pub fn latest(self: &Self, feature: Feature) -> Option<f32> {
+        let buffer = self.buffers[feature as usize].as_ref()?;
+        buffer.lock().ok().map(|buffer| buffer.peek())
+    }
+
+    /// Sets the sample rate of the incoming audio.
+    ///
+    /// This function **clears** all buffers.
+    pub fn set_sample_rate(self: &mut Self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.sample_delta =
+            ((self.sample_rate as f64 * self.duration as f64) / self.size as f64) as f32;
+        self.clear();
+    }
+
+    /// Hints whether the plug-in is rendering offline, which skips all enqueueing.
+    pub fn set_offline(self: &mut Self, offline: bool) {
+        self.offline = offline;
+    }
+
+    /// Enqueues a single sample.
+    ///
+    /// The buffer of each enabled feature is locked whenever an element is finalized.
+    pub fn enqueue(self: &mut Self, value: f32) {
+        if self.offline {
+            return;
+        }
+        self.t -= 1.0;
+        if self.t < 0.0 {
+            self.finalize();
+            self.t += self.sample_delta;
+        }
+
+        if self.needs_peak {
+            self.peak = self.peak.max(value.abs());
+        }
+        if self.needs_energy {
+            self.energy += value * value;
+        }
+        if self.needs_sum {
+            self.sum += value;
+        }
+        if self.needs_crossings {
+            let negative = value < 0.;
+            if self.negative.is_some_and(|last| last != negative) {
+                self.crossings += 1;
+            }
+            self.negative = Some(negative);
+        }
+        self.count += 1;
+    }
+
+    /// Enqueues an entire [`Buffer`](`nih_plug::buffer::Buffer`), mono-summing it if
+    /// no channel is specified.
+    pub fn enqueue_buffer(
+        self: &mut Self,
+        buffer: &mut nih_plug::buffer::Buffer,
+        channel: Option<usize>,
+    ) {
+        if self.offline {
+            return;
+        }
+        match channel {
+            Some(channel) => {
+                for value in buffer.as_slice_immutable()[channel].iter() {
+                    self.enqueue(*value);
+                }
+            }
+            None => {
+                for sample in buffer.iter_samples() {
+                    self.enqueue(
+                        (1. / (&sample).len() as f32) * sample.into_iter().map(|x| *x).sum::<f32>(),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Clears all buffers, along with the element that is being accumulated.
+    pub fn clear(self: &mut Self) {
+        for buffer in self.buffers.iter().flatten() {
+            if let Ok(mut buffer) = buffer.lock() {
+                buffer.clear();
+            }
+        }
+        self.t = self.sample_delta;
+        self.negative = None;
+        self.reset_accumulators();
+    }
+
+    // Enqueues the value of every enabled feature into its buffer
+    fn finalize(self: &mut Self) {
+        for feature in self.features.iter() {
+            let value = self.value(feature);
+            if let Some(Ok(mut buffer)) = self.buffers[feature as usize].as_ref().map(|b| b.lock())
+            {
+                buffer.enqueue(value);
+            }
+        }
+        self.reset_accumulators();
+    }
+
+    // The value of the feature over the accumulated samples
+    fn value(self: &Self, feature: Feature) -> f32 {
+        if self.count == 0 {
+            return 0.;
+        }
+        let count = self.count as f32;
+        let rms = (self.energy / count).sqrt();
+        match feature {
+            Feature::Peak => self.peak,
+            Feature::Rms => rms,
+            Feature::CrestFactor if rms > 0. => self.peak / rms,
+            Feature::CrestFactor => 0.,
+            Feature::ZeroCrossingRate => self.crossings as f32 / count * self.sample_rate,
+            Feature::DcOffset => self.sum / count,
+        }
+    }
+
+    fn reset_accumulators(self: &mut Self) {
+        self.peak = 0.;
+        self.energy = 0.;
+        self.sum = 0.;
+        self.crossings = 0;
+        self.count = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Feature, FeatureExtractor, Features};
+    use crate::utils::buffers::test_support::with_buffer;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::f32::consts::{PI, SQRT_2};
+
+    const SAMPLE_RATE: f32 = 48000.;
+    const SIZE: usize = 10;
+    // The samples per element, at a duration of 1 s
+    const SPAN: usize = 4800;
+
+    fn extractor(features: impl Into<Features>) -> FeatureExtractor {
+        let mut extractor = FeatureExtractor::new(SIZE, 1.0, features);
+        extractor.set_sample_rate(SAMPLE_RATE);
+        extractor
+    }
+
+    // Fills every element, plus the sample that finalizes the last one
+    fn feed(extractor: &mut FeatureExtractor, signal: impl Fn(usize) -> f32) -> Vec<f32> {
+        let samples: Vec<f32> = (0..SIZE * SPAN + 1).map(signal).collect();
+        for sample in samples.iter() {
+            extractor.enqueue(*sample);
+        }
+        samples
+    }
+
+    fn contents(extractor: &FeatureExtractor, feature: Feature) -> Vec<f32> {
+        extractor.feature(feature).unwrap().lock().unwrap().to_vec()
+    }
+
+    // The crest factor and zero-crossing rate of each element, computed over all
+    // samples at once
+    fn offline(samples: &[f32]) -> Vec<(f64, f64)> {
+        (0..SIZE)
+            .map(|element| {
+                let range = element * SPAN..(element + 1) * SPAN;
+                let window = &samples[range.clone()];
+                let peak = window.iter().fold(0f64, |peak, x| peak.max(x.abs() as f64));
+                let rms =
+                    (window.iter().map(|x| (*x as f64).powi(2)).sum::<f64>() / SPAN as f64).sqrt();
+                // A crossing belongs to the element of the sample after it
+                let crossings = range
+                    .filter(|i| *i > 0 && (samples[i - 1] < 0.) != (samples[*i] < 0.))
+                    .count();
+                (
+                    peak / rms,
+                    crossings as f64 / SPAN as f64 * SAMPLE_RATE as f64,
+                )
+            })
+            .collect()
+    }
+
+    fn assert_matches_offline(extractor: &FeatureExtractor, samples: &[f32]) {
+        let crest = contents(extractor, Feature::CrestFactor);
+        let zcr = contents(extractor, Feature::ZeroCrossingRate);
+        for (element, (crest_factor, crossing_rate)) in offline(samples).into_iter().enumerate() {
+            assert!(
+                (crest[element] as f64 - crest_factor).abs() < 1e-3 * crest_factor,
+                "crest factor of element {}: {} vs. {}",
+                element,
+                crest[element],
+                crest_factor
+            );
+            assert!(
+                (zcr[element] as f64 - crossing_rate).abs() < 1e-3 * crossing_rate,
+                "zero-crossing rate of element {}: {} vs. {}",
+                element,
+                zcr[element],
+                crossing_rate
+            );
+        }
+    }
+
+    #[test]
+    fn sine_features() {
+        let mut extractor = extractor([Feature::CrestFactor, Feature::ZeroCrossingRate]);
+        // A phase offset keeps the samples off zero
+        let samples = feed(&mut extractor, |i| {
+            (2. * PI * 1000. * i as f32 / SAMPLE_RATE + 0.1).sin()
+        });
+        assert_matches_offline(&extractor, &samples);
+
+        for crest in contents(&extractor, Feature::CrestFactor) {
+            assert!((crest - SQRT_2).abs() < 0.01, "{}", crest);
+        }
+        for zcr in contents(&extractor, Feature::ZeroCrossingRate) {
+            assert!((zcr - 2000.).abs() < 20., "{}", zcr);
+        }
+    }
+
+    #[test]
+    fn white_noise_features() {
+        let mut extractor = extractor(Features::all());
+        let mut rng = StdRng::seed_from_u64(1);
+        let noise: Vec<f32> = (0..SIZE * SPAN + 1)
+            .map(|_| rng.gen_range(-1.0..1.0))
+            .collect();
+        let samples = feed(&mut extractor, |i| noise[i]);
+        assert_matches_offline(&extractor, &samples);
+
+        // Uniform noise has a crest factor of √3, and crosses zero every other sample
+        let crest = extractor.latest(Feature::CrestFactor).unwrap();
+        assert!((crest - 3f32.sqrt()).abs() < 0.05, "{}", crest);
+        let zcr = extractor.latest(Feature::ZeroCrossingRate).unwrap();
+        assert!(
+            (zcr - SAMPLE_RATE / 2.).abs() < SAMPLE_RATE * 0.02,
+            "{}",
+            zcr
+        );
+        let dc = extractor.latest(Feature::DcOffset).unwrap();
+        assert!(dc.abs() < 0.05, "{}", dc);
+    }
+
+    #[test]
+    fn only_enabled_features() {
+        let extractor = extractor(Feature::Rms);
+        assert_eq!(extractor.features(), Features::from(Feature::Rms));
+        for feature in Feature::ALL {
+            assert_eq!(
+                extractor.feature(feature).is_some(),
+                feature == Feature::Rms
+            );
+        }
+        assert_eq!(extractor.latest(Feature::Peak), None);
+    }
+
+    #[test]
+    fn enqueue_buffer_matches_enqueue() {
+        let signal = |i: usize| (i as f32 * 0.37).sin() * 0.8;
+        let mut expected = extractor(Features::all());
+        feed(&mut expected, signal);
+
+        let mut extractor = extractor(Features::all());
+        let mut channels = vec![(0..SIZE * SPAN + 1).map(signal).collect::<Vec<f32>>(); 2];
+        with_buffer(&mut channels, |b| extractor.enqueue_buffer(b, None));
+        for feature in Feature::ALL {
+            assert_eq!(contents(&extractor, feature), contents(&expected, feature));
+        }
+    }
+
+    #[test]
+    fn clear() {
+        let mut extractor = extractor(Features::all());
+        feed(&mut extractor, |i| (i as f32 * 0.1).sin());
+        extractor.clear();
+        for feature in Feature::ALL {
+            assert!(contents(&extractor, feature).iter().all(|x| *x == 0.));
+        }
+    }
+}
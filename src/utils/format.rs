@@ -0,0 +1,370 @@
+use nih_plug::util::gain_to_db;
+use std::fmt::Write;
+
+use super::ValueScaling;
+
+/// Turns a value into the text that a view displays for it, such as the labels of a
+/// [`UnitRuler`](crate::visualizers::UnitRuler) or the readout of a
+/// [`Graph`](crate::visualizers::Graph).
+///
+/// Values are passed in the units the view's range is given in - so a view that
+/// uses [`ValueScaling::Decibels`] passes its values in decibels, not as gains.
+///
+/// The text is appended to `out`, which views reuse from one update to the next, so
+/// that formatting doesn't allocate once it has grown large enough. Any closure that
+/// takes a value and a `&mut String` is a formatter, as is a
+/// `Box<dyn ValueFormatter>`.
+///
+/// ```
+/// // A signed gain, e.g. for a gain reduction readout
+/// Graph::new(cx, Data::gain_reduction, (-24.0, 0.0), ValueScaling::Linear)
+///     .readout(ReadoutPosition::TopRight, 8.0)
+///     .value_formatter(|value: f32, out: &mut String| {
+///         let _ = write!(out, "{:+.1} dB", value);
+///     });
+/// ```
+pub trait ValueFormatter {
+    /// Appends the text for `value` to `out`.
+    fn format(self: &Self, value: f32, out: &mut String);
+}
+
+impl<F> ValueFormatter for F
+where
+    F: Fn(f32, &mut String),
+{
+    fn format(self: &Self, value: f32, out: &mut String) {
+        self(value, out)
+    }
+}
+
+impl ValueFormatter for Box<dyn ValueFormatter> {
+    fn format(self: &Self, value: f32, out: &mut String) {
+        (**self).format(value, out)
+    }
+}
+
+/// Formats a level in decibels, with a fixed number of decimals, e.g. "-14.0 LUFS"
+/// or "-3.2 dB".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecibelFormatter {
+    /// The number of decimals.
+    pub precision: usize,
+    /// Appended to the number, e.g. `" dB"`.
+    pub suffix: &'static str,
+}
+
+impl Default for DecibelFormatter {
+    fn default() -> Self {
+        Self {
+            precision: 1,
+            suffix: " dB",
+        }
+    }
+}
+
+impl ValueFormatter for DecibelFormatter {
+    fn format(self: &Self, value: f32, out: &mut String) {
+        write_fixed(out, value, self.precision);
+        out.push_str(self.suffix);
+    }
+}
+
+/// Formats a frequency in hertz, with a `k` prefix from 1000 Hz onwards, e.g. "440"
+/// or "2.5k".
+///
+/// Frequencies below 1000 Hz are rounded to whole hertz, and higher ones to a tenth
+/// of a kilohertz - without the decimal if it is zero, so that 2000 Hz reads "2k".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrequencyFormatter {
+    /// Appended right after the number and its prefix, e.g. `"Hz"` for "2.5kHz".
+    pub suffix: &'static str,
+}
+
+impl ValueFormatter for FrequencyFormatter {
+    fn format(self: &Self, value: f32, out: &mut String) {
+        if value.abs().round() < 1000. {
+            write_fixed(out, value, 0);
+        } else {
+            write_fixed(out, value / 1000., 1);
+            if out.ends_with(".0") {
+                out.truncate(out.len() - 2);
+            }
+            out.push('k');
+        }
+        out.push_str(self.suffix);
+    }
+}
+
+/// Formats a number with a fixed number of decimals, and optionally with its
+/// thousands separated, e.g. "0.25" or "48,000 samples".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlainFormatter {
+    /// The number of decimals.
+    pub precision: usize,
+    /// Separates each group of three digits before the decimal point, if any.
+    pub thousands_separator: Option<char>,
+    /// Appended to the number.
+    pub suffix: &'static str,
+}
+
+impl Default for PlainFormatter {
+    fn default() -> Self {
+        Self {
+            precision: 2,
+            thousands_separator: None,
+            suffix: "",
+        }
+    }
+}
+
+impl ValueFormatter for PlainFormatter {
+    fn format(self: &Self, value: f32, out: &mut String) {
+        let start = out.len();
+        write_fixed(out, value, self.precision);
+        if let Some(separator) = self.thousands_separator {
+            group_thousands(out, start, separator);
+        }
+        out.push_str(self.suffix);
+    }
+}
+
+/// Formats a buffer's value, converting gains to decibels if the view uses
+/// [`ValueScaling::Decibels`]; See [`ValueFormatter`].
+#[cfg_attr(not(feature = "vizia"), allow(dead_code))]
+pub(crate) fn format_scaled(
+    formatter: &dyn ValueFormatter,
+    scaling: ValueScaling,
+    value: f32,
+    out: &mut String,
+) {
+    let value = match scaling {
+        ValueScaling::Decibels => gain_to_db(value),
+        _ => value,
+    };
+    formatter.format(value, out);
+}
+
+static DECIBELS: DecibelFormatter = DecibelFormatter {
+    precision: 1,
+    suffix: " dB",
+};
+static HERTZ: PlainFormatter = PlainFormatter {
+    precision: 0,
+    thousands_separator: None,
+    suffix: " Hz",
+};
+static PLAIN: PlainFormatter = PlainFormatter {
+    precision: 2,
+    thousands_separator: None,
+    suffix: "",
+};
+
+/// Returns the formatter views use for a scaling, unless they are given another one.
+#[cfg_attr(not(feature = "vizia"), allow(dead_code))]
+pub(crate) fn default_formatter(scaling: ValueScaling) -> &'static dyn ValueFormatter {
+    match scaling {
+        ValueScaling::Decibels => &DECIBELS,
+        ValueScaling::Frequency => &HERTZ,
+        ValueScaling::Linear | ValueScaling::Power(_) => &PLAIN,
+    }
+}
+
+/// Appends a number with a fixed number of decimals, without a sign if it rounds to
+/// zero - so that both -0.0 and -0.04 read "0.0".
+fn write_fixed(out: &mut String, value: f32, precision: usize) {
+    let start = out.len();
+    let _ = write!(out, "{:.*}", precision, value);
+    let negative_zero =
+        out[start..].starts_with('-') && out[start + 1..].bytes().all(|b| b == b'0' || b == b'.');
+    if negative_zero {
+        out.remove(start);
+    }
+}
+
+/// Separates the groups of three digits before the decimal point of the number
+/// that starts at `start`.
+fn group_thousands(out: &mut String, start: usize, separator: char) {
+    let digits = match out[start..].starts_with('-') {
+        true => start + 1,
+        false => start,
+    };
+    let end = out[digits..].find('.').map_or(out.len(), |i| digits + i);
+    // Neither NaN nor the infinities have digits to group
+    if !out[digits..end].bytes().all(|b| b.is_ascii_digit()) {
+        return;
+    }
+    let mut position = end;
+    while position > digits + 3 {
+        position -= 3;
+        out.insert(position, separator);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        default_formatter, format_scaled, DecibelFormatter, FrequencyFormatter, PlainFormatter,
+        ValueFormatter,
+    };
+    use crate::utils::ValueScaling;
+
+    fn formatted(formatter: &dyn ValueFormatter, value: f32) -> String {
+        let mut out = String::new();
+        formatter.format(value, &mut out);
+        out
+    }
+
+    fn assert_table(formatter: &dyn ValueFormatter, table: &[(f32, &str)]) {
+        for (value, expected) in table {
+            assert_eq!(formatted(formatter, *value), *expected, "{}", value);
+        }
+    }
+
+    #[test]
+    fn decibels() {
+        assert_table(
+            &DecibelFormatter::default(),
+            &[
+                (0., "0.0 dB"),
+                (-0., "0.0 dB"),
+                (-0.04, "0.0 dB"),
+                (0.04, "0.0 dB"),
+                (-0.05, "-0.1 dB"),
+                (-3.2, "-3.2 dB"),
+                (6., "6.0 dB"),
+                (-96.26, "-96.3 dB"),
+                (f32::NEG_INFINITY, "-inf dB"),
+            ],
+        );
+        assert_table(
+            &DecibelFormatter {
+                precision: 1,
+                suffix: " LUFS",
+            },
+            &[(-14., "-14.0 LUFS"), (-23.04, "-23.0 LUFS")],
+        );
+        assert_table(
+            &DecibelFormatter {
+                precision: 0,
+                suffix: "db",
+            },
+            &[(-0.4, "0db"), (-12., "-12db"), (5.6, "6db")],
+        );
+    }
+
+    #[test]
+    fn frequencies() {
+        assert_table(
+            &FrequencyFormatter::default(),
+            &[
+                (0., "0"),
+                (-0.2, "0"),
+                (20., "20"),
+                (440., "440"),
+                (999.4, "999"),
+                (999.6, "1k"),
+                (1000., "1k"),
+                (2500., "2.5k"),
+                (12340., "12.3k"),
+                (19990., "20k"),
+                (20000., "20k"),
+            ],
+        );
+        assert_table(
+            &FrequencyFormatter { suffix: "Hz" },
+            &[(60., "60Hz"), (2500., "2.5kHz")],
+        );
+    }
+
+    #[test]
+    fn plain() {
+        assert_table(
+            &PlainFormatter::default(),
+            &[
+                (0., "0.00"),
+                (-0., "0.00"),
+                (-0.001, "0.00"),
+                (0.25, "0.25"),
+                (-0.5, "-0.50"),
+                (1234.5, "1234.50"),
+            ],
+        );
+        let samples = PlainFormatter {
+            precision: 0,
+            thousands_separator: Some(','),
+            suffix: " samples",
+        };
+        assert_table(
+            &samples,
+            &[
+                (0., "0 samples"),
+                (512., "512 samples"),
+                (1000., "1,000 samples"),
+                (48000., "48,000 samples"),
+                (-1234567., "-1,234,567 samples"),
+                (f32::NAN, "NaN samples"),
+            ],
+        );
+        let grouped = PlainFormatter {
+            precision: 2,
+            thousands_separator: Some(' '),
+            suffix: "",
+        };
+        assert_table(&grouped, &[(12345.678, "12 345.68"), (999.999, "1 000.00")]);
+    }
+
+    #[test]
+    fn closures_and_boxes() {
+        let closure =
+            |value: f32, out: &mut String| out.push_str(if value > 0. { "+" } else { "-" });
+        assert_eq!(formatted(&closure, 1.), "+");
+        let boxed: Box<dyn ValueFormatter> = Box::new(closure);
+        assert_eq!(formatted(&boxed, -1.), "-");
+    }
+
+    #[test]
+    fn appends_to_reused_strings() {
+        let mut out = String::with_capacity(64);
+        let formatter = DecibelFormatter::default();
+        for value in [-0.04, -6.02, 0.] {
+            out.clear();
+            formatter.format(value, &mut out);
+        }
+        assert_eq!(out, "0.0 dB");
+        assert!(out.capacity() >= 64);
+
+        // Only the appended number is checked for its sign
+        out.clear();
+        out.push('-');
+        formatter.format(-0.01, &mut out);
+        assert_eq!(out, "-0.0 dB");
+    }
+
+    #[test]
+    fn defaults() {
+        let mut out = String::new();
+        format_scaled(
+            default_formatter(ValueScaling::Decibels),
+            ValueScaling::Decibels,
+            0.5,
+            &mut out,
+        );
+        assert_eq!(out, "-6.0 dB");
+        out.clear();
+        format_scaled(
+            default_formatter(ValueScaling::Frequency),
+            ValueScaling::Frequency,
+            440.,
+            &mut out,
+        );
+        assert_eq!(out, "440 Hz");
+        out.clear();
+        format_scaled(
+            default_formatter(ValueScaling::Linear),
+            ValueScaling::Linear,
+            0.25,
+            &mut out,
+        );
+        assert_eq!(out, "0.25");
+    }
+}
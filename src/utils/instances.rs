@@ -0,0 +1,420 @@
+//! Sharing buffers between instances of a plug-in.
+//!
+//! A plug-in that is loaded on several tracks can show an overview of all of them
+//! in a single instance, e.g. a bridge of meters. Every instance publishes its
+//! buffer under a unique name, and the overview subscribes to a pattern of names:
+//!
+//! ```
+//! // Inside each instance's `initialize()`
+//! self.publication = Some(instances().publish_as(
+//!     format!("track-peaks-{}", self.id),
+//!     &self.peak_buffer,
+//! ));
+//!
+//! // Inside the overview's editor
+//! let mut subscription = instances().subscribe("track-peaks-*");
+//! if subscription.poll() {
+//!     for source in subscription.sources() {
+//!         println!("{}: {} elements", source.name, source.snapshot.values.len());
+//!     }
+//! }
+//! ```
+//!
+//! Most hosts load all instances into the same process, so the registry is
+//! process-local - instances in different processes, e.g. in a host that sandboxes
+//! each plug-in, don't see each other.
+//!
+//! Subscribers receive [`BufferSnapshot`]s, which are exported at a throttled rate.
+//! They never keep a published buffer alive, and simply stop receiving snapshots of
+//! a buffer once its [`Publication`] or the buffer itself is dropped.
+//!
+//! Publishing happens outside of the audio thread, and the audio thread keeps
+//! writing to the buffer as usual. Subscribers only *try* to lock a buffer, and
+//! keep the last snapshot if it's locked - so together with a
+//! [`StagedBuffer`](super::StagedBuffer), which defers its writes while a buffer is
+//! being exported, the audio thread never waits for a subscriber.
+//!
+//! This module is only available with the `shared-instances` feature enabled.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError, Weak};
+use std::time::{Duration, Instant};
+
+use super::{BufferSnapshot, SnapshotExport};
+
+struct Entry {
+    id: u64,
+    name: String,
+    buffer: Weak<Mutex<dyn SnapshotExport + Send>>,
+}
+
+/// Keeps track of the buffers that instances publish; See the
+/// [module-level documentation](self).
+pub struct InstanceRegistry {
+    entries: Mutex<Vec<Entry>>,
+    next_id: AtomicU64,
+}
+
+static INSTANCES: InstanceRegistry = InstanceRegistry {
+    entries: Mutex::new(Vec::new()),
+    next_id: AtomicU64::new(0),
+};
+
+/// Returns the process-wide [`InstanceRegistry`].
+pub fn instances() -> &'static InstanceRegistry {
+    &INSTANCES
+}
+
+impl InstanceRegistry {
+    /// Publishes a shared buffer under a name, until the returned [`Publication`] is
+    /// dropped.
+    ///
+    /// The registry doesn't keep the buffer alive. Names don't need to be unique,
+    /// but subscribers can't tell apart buffers with the same name - include
+    /// something unique to the instance, e.g. a random id.
+    pub fn publish_as<B>(
+        self: &'static Self,
+        name: impl Into<String>,
+        buffer: &Arc<Mutex<B>>,
+    ) -> Publication
+    where
+        B: SnapshotExport + Send + 'static,
+    {
+        let buffer: Arc<Mutex<dyn SnapshotExport + Send>> = buffer.clone();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.entries().push(Entry {
+            id,
+            name: name.into(),
+            buffer: Arc::downgrade(&buffer),
+        });
+        Publication { registry: self, id }
+    }
+
+    /// Subscribes to the buffers whose names match a pattern, in which `*` matches
+    /// any sequence of characters, e.g. `track-peaks-*`.
+    ///
+    /// Buffers that are published after subscribing are picked up as well.
+    pub fn subscribe(self: &'static Self, pattern: impl Into<String>) -> Subscription {
+        Subscription {
+            registry: self,
+            pattern: pattern.into(),
+            interval: DEFAULT_INTERVAL,
+            last_poll: None,
+            sources: Vec::new(),
+        }
+    }
+
+    /// Returns the names of the buffers that are published right now, in the order
+    /// they were published.
+    pub fn names(self: &Self) -> Vec<String> {
+        let mut entries = self.entries();
+        entries.retain(|entry| entry.buffer.strong_count() > 0);
+        entries.iter().map(|entry| entry.name.clone()).collect()
+    }
+
+    // The published buffers whose names match the pattern, and that are still alive
+    fn matching(
+        self: &Self,
+        pattern: &str,
+    ) -> Vec<(u64, String, Arc<Mutex<dyn SnapshotExport + Send>>)> {
+        let mut entries = self.entries();
+        entries.retain(|entry| entry.buffer.strong_count() > 0);
+        entries
+            .iter()
+            .filter(|entry| matches(pattern, &entry.name))
+            .filter_map(|entry| Some((entry.id, entry.name.clone(), entry.buffer.upgrade()?)))
+            .collect()
+    }
+
+    fn unpublish(self: &Self, id: u64) {
+        self.entries().retain(|entry| entry.id != id);
+    }
+
+    fn entries(self: &Self) -> MutexGuard<'_, Vec<Entry>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Keeps a buffer published, and unpublishes it when it's dropped; See
+/// [`InstanceRegistry::publish_as()`].
+#[must_use = "the buffer is unpublished as soon as the publication is dropped"]
+pub struct Publication {
+    registry: &'static InstanceRegistry,
+    id: u64,
+}
+
+impl Drop for Publication {
+    fn drop(&mut self) {
+        self.registry.unpublish(self.id);
+    }
+}
+
+/// A snapshot of a published buffer, as received by a [`Subscription`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SharedSnapshot {
+    /// The name the buffer was published under.
+    pub name: String,
+    pub snapshot: BufferSnapshot,
+    // Tells apart buffers that were published under the same name
+    id: u64,
+}
+
+/// Receives snapshots of the published buffers whose names match a pattern; See
+/// [`InstanceRegistry::subscribe()`].
+pub struct Subscription {
+    registry: &'static InstanceRegistry,
+    pattern: String,
+    interval: Duration,
+    last_poll: Option<Instant>,
+    sources: Vec<SharedSnapshot>,
+}
+
+// Snapshots copy entire buffers, so they're taken less often than the editor draws
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(50);
+
+impl Subscription {
+    /// Sets the shortest time between two updates of the snapshots, 50 ms by default.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Returns the pattern that names are matched against.
+    pub fn pattern(self: &Self) -> &str {
+        &self.pattern
+    }
+
+    /// Updates the snapshots, unless they were updated less than the
+    /// [interval](Self::with_interval) ago. Returns `true` if they were updated.
+    ///
+    /// Call this once per frame from the editor - exporting snapshots allocates.
+    pub fn poll(self: &mut Self) -> bool {
+        self.poll_at(Instant::now())
+    }
+
+    pub(crate) fn poll_at(self: &mut Self, now: Instant) -> bool {
+        let due = self
+            .last_poll
+            .is_none_or(|last| now.duration_since(last) >= self.interval);
+        if due {
+            self.last_poll = Some(now);
+            self.update();
+        }
+        due
+    }
+
+    /// Updates the snapshots right away.
+    ///
+    /// Buffers that are unpublished or dropped are removed. A buffer that is locked
+    /// at the moment keeps its last snapshot, or is left out until the next update if
+    /// it doesn't have one yet.
+    pub fn update(self: &mut Self) {
+        let matching = self.registry.matching(&self.pattern);
+        let previous = std::mem::take(&mut self.sources);
+        self.sources = matching
+            .into_iter()
+            .filter_map(|(id, name, buffer)| {
+                let snapshot = match buffer.try_lock() {
+                    Ok(buffer) => buffer.export(),
+                    Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner().export(),
+                    Err(TryLockError::WouldBlock) => {
+                        return previous.iter().find(|source| source.id == id).cloned()
+                    }
+                };
+                Some(SharedSnapshot { name, snapshot, id })
+            })
+            .collect();
+    }
+
+    /// Returns the latest snapshot of each matching buffer, in the order they were
+    /// published.
+    pub fn sources(self: &Self) -> &[SharedSnapshot] {
+        &self.sources
+    }
+}
+
+/// Returns `true` if the name matches the pattern, in which `*` matches any
+/// sequence of characters.
+fn matches(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // Without a wildcard, the name needs to match exactly
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{instances, matches, InstanceRegistry};
+    use crate::utils::{PeakBuffer, SnapshotExport, VisualizerBuffer};
+    use std::sync::atomic::AtomicU64;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    // A registry of its own for each test, since tests run in parallel
+    fn registry() -> &'static InstanceRegistry {
+        Box::leak(Box::new(InstanceRegistry {
+            entries: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(0),
+        }))
+    }
+
+    fn peak_buffer(value: f32) -> Arc<Mutex<PeakBuffer>> {
+        let mut buffer = PeakBuffer::new(4, 1.0, 0.0);
+        buffer.set_sample_rate(4.);
+        buffer.enqueue(value);
+        buffer.enqueue(value);
+        Arc::new(Mutex::new(buffer))
+    }
+
+    fn names(registry: &'static InstanceRegistry, pattern: &str) -> Vec<String> {
+        let mut subscription = registry.subscribe(pattern);
+        subscription.update();
+        subscription
+            .sources()
+            .iter()
+            .map(|s| s.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn patterns() {
+        assert!(matches("track-peaks-*", "track-peaks-1f3a"));
+        assert!(matches("track-peaks-*", "track-peaks-"));
+        assert!(!matches("track-peaks-*", "bus-peaks-1f3a"));
+        assert!(matches("*-peaks-*", "bus-peaks-1f3a"));
+        assert!(matches("*", "anything"));
+        assert!(matches("a*b*a", "abba"));
+        assert!(!matches("ab*ba", "aba"));
+        assert!(matches("exact", "exact"));
+        assert!(!matches("exact", "exactly"));
+    }
+
+    #[test]
+    fn subscribe_and_unpublish() {
+        let registry = registry();
+        let (a, b, c) = (peak_buffer(0.25), peak_buffer(0.5), peak_buffer(0.75));
+        let publication_a = registry.publish_as("track-peaks-a", &a);
+        let _publication_b = registry.publish_as("track-peaks-b", &b);
+        let _publication_c = registry.publish_as("bus-peaks-c", &c);
+
+        let mut subscription = registry.subscribe("track-peaks-*");
+        subscription.update();
+        let sources = subscription.sources();
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[0].name, "track-peaks-a");
+        assert_eq!(sources[1].snapshot.values, b.export().values);
+
+        // Unpublishing, or dropping the buffer, removes a source
+        drop(publication_a);
+        assert_eq!(names(registry, "track-peaks-*"), vec!["track-peaks-b"]);
+        drop(b);
+        assert!(names(registry, "track-peaks-*").is_empty());
+        assert_eq!(registry.names(), vec!["bus-peaks-c"]);
+
+        // Later publications are picked up
+        let d = peak_buffer(1.0);
+        let _publication_d = registry.publish_as("track-peaks-d", &d);
+        subscription.update();
+        assert_eq!(subscription.sources()[0].name, "track-peaks-d");
+    }
+
+    #[test]
+    fn locked_buffers_keep_their_snapshot() {
+        let registry = registry();
+        let buffer = peak_buffer(0.5);
+        let _publication = registry.publish_as("track", &buffer);
+        let mut subscription = registry.subscribe("track");
+
+        // Nothing to keep yet
+        let guard = buffer.lock().unwrap();
+        subscription.update();
+        assert!(subscription.sources().is_empty());
+        drop(guard);
+
+        subscription.update();
+        let snapshot = subscription.sources()[0].clone();
+        let mut guard = buffer.lock().unwrap();
+        guard.enqueue(1.0);
+        guard.enqueue(1.0);
+        subscription.update();
+        assert_eq!(subscription.sources(), &[snapshot]);
+    }
+
+    #[test]
+    fn throttled() {
+        let registry = registry();
+        let mut subscription = registry
+            .subscribe("*")
+            .with_interval(Duration::from_millis(50));
+        let start = Instant::now();
+        assert!(subscription.poll_at(start));
+        assert!(!subscription.poll_at(start + Duration::from_millis(49)));
+        assert!(subscription.poll_at(start + Duration::from_millis(50)));
+        assert!(!subscription.poll_at(start + Duration::from_millis(60)));
+    }
+
+    #[test]
+    fn races_with_publishers() {
+        let registry = registry();
+        let publishers: Vec<_> = (0..4)
+            .map(|i| {
+                thread::spawn(move || {
+                    for round in 0..200 {
+                        let buffer = peak_buffer(0.5);
+                        let publication =
+                            registry.publish_as(format!("track-{}-{}", i, round), &buffer);
+                        // Vanish in either order
+                        if round % 2 == 0 {
+                            drop(buffer);
+                            drop(publication);
+                        } else {
+                            drop(publication);
+                            drop(buffer);
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        let mut subscription = registry.subscribe("track-*");
+        while !publishers.iter().all(|publisher| publisher.is_finished()) {
+            subscription.update();
+            for source in subscription.sources() {
+                assert!(source.name.starts_with("track-"));
+                assert_eq!(source.snapshot.values.len(), 4);
+            }
+        }
+        for publisher in publishers {
+            publisher.join().unwrap();
+        }
+
+        subscription.update();
+        assert!(subscription.sources().is_empty());
+        assert!(registry.names().is_empty());
+    }
+
+    #[test]
+    fn process_wide() {
+        let buffer = peak_buffer(0.5);
+        let _publication = instances().publish_as("process-wide-test", &buffer);
+        assert!(instances()
+            .names()
+            .contains(&"process-wide-test".to_string()));
+    }
+}
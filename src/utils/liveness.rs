@@ -0,0 +1,402 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Tells whether the audio thread's half of a buffer is still alive.
+///
+/// Some hosts destroy the plug-in before its editor has fully closed, so views can
+/// keep drawing for a while after the half that writes to a buffer is gone. The
+/// halves that views read from stay valid when this happens - they keep returning
+/// the last data that was published. A `ProducerWatch` tells a view that this data
+/// is no longer live, e.g. so that it can be drawn dimmed.
+///
+/// A watch is obtained from the writing half, and is cheap to clone:
+///
+/// ```
+/// let watch = self.staged_peaks.watch();
+///
+/// // Inside the editor
+/// if !watch.is_producer_alive() {
+///     // Draw a disconnected state
+/// }
+/// ```
+#[derive(Clone)]
+pub struct ProducerWatch {
+    alive: Arc<AtomicBool>,
+}
+
+impl ProducerWatch {
+    /// Returns `true` as long as the writing half hasn't been dropped.
+    ///
+    /// Once this returns `false`, everything the writing half published before it
+    /// was dropped is visible to readers, and nothing else will be published.
+    pub fn is_producer_alive(self: &Self) -> bool {
+        self.alive.load(Ordering::Acquire)
+    }
+}
+
+/// Held by the writing half of a buffer, and marks it as gone when it's dropped.
+///
+/// The token needs to be dropped after the writing half's last write, so that
+/// readers that see it gone also see that write.
+pub(crate) struct ProducerToken {
+    alive: Arc<AtomicBool>,
+}
+
+impl ProducerToken {
+    pub(crate) fn new() -> Self {
+        Self {
+            alive: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    pub(crate) fn watch(self: &Self) -> ProducerWatch {
+        ProducerWatch {
+            alive: self.alive.clone(),
+        }
+    }
+}
+
+impl Drop for ProducerToken {
+    fn drop(&mut self) {
+        self.alive.store(false, Ordering::Release);
+    }
+}
+
+/// Tells whether a buffer is still being fed, to tell apart the reasons why a view
+/// could appear frozen.
+///
+/// A buffer that was constructed with a heartbeat, e.g. using
+/// [`PeakBuffer::with_heartbeat()`](super::PeakBuffer::with_heartbeat), counts the
+/// elements it enqueues, and records the number of samples it had processed when it
+/// enqueued the last one, along with whether the host's transport was playing. A
+/// view reads both in two atomic loads, without locking the buffer - see
+/// [`StaleIndicatorModifiers`](crate::visualizers::StaleIndicatorModifiers).
+///
+/// The heartbeat is cheap to clone, and all clones read the same buffer.
+#[derive(Clone, Default)]
+pub struct Heartbeat {
+    counters: Arc<HeartbeatCounters>,
+}
+
+#[derive(Default)]
+struct HeartbeatCounters {
+    enqueued: AtomicU64,
+    // The sample count of the last enqueue, with PLAYING set while the transport plays
+    position: AtomicU64,
+}
+
+const PLAYING: u64 = 1 << 63;
+
+// Heartbeats are equal if they're clones of each other, so that buffers can still be
+// compared
+impl PartialEq for Heartbeat {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.counters, &other.counters)
+    }
+}
+
+/// A [`Heartbeat`] at one point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HeartbeatReading {
+    /// The number of elements the buffer enqueued.
+    pub enqueued: u64,
+    /// The number of samples the buffer had processed when it enqueued the last
+    /// element.
+    pub last_sample: u64,
+    /// Whether the host's transport was playing when the last element was enqueued.
+    pub playing: bool,
+}
+
+impl Heartbeat {
+    /// Constructs a new `Heartbeat`, which hasn't seen any elements yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records an enqueued element; Only the buffer that owns the heartbeat calls
+    /// this.
+    pub(crate) fn beat(self: &Self, samples: u64, playing: bool) {
+        let playing = if playing { PLAYING } else { 0 };
+        self.counters
+            .position
+            .store((samples & !PLAYING) | playing, Ordering::Relaxed);
+        self.counters.enqueued.fetch_add(1, Ordering::Release);
+    }
+
+    /// Returns the current state of the heartbeat.
+    pub fn read(self: &Self) -> HeartbeatReading {
+        let enqueued = self.counters.enqueued.load(Ordering::Acquire);
+        let position = self.counters.position.load(Ordering::Relaxed);
+        HeartbeatReading {
+            enqueued,
+            last_sample: position & !PLAYING,
+            playing: position & PLAYING != 0,
+        }
+    }
+}
+
+/// Decides whether a [`Heartbeat`] went stale, from readings taken once per frame.
+///
+/// A heartbeat is stale once it hasn't changed for longer than the threshold while
+/// the transport was playing - i.e. the host stopped calling the plug-in's
+/// `process()`, even though it claims to be playing. It's fresh again as soon as it
+/// changes. A stopped transport never counts as stale, since many hosts stop
+/// processing along with it. When the editor itself stalls, the heartbeat has
+/// changed by the next frame, so it stays fresh.
+#[derive(Debug, Clone)]
+pub struct StalenessTracker {
+    threshold: Duration,
+    // The last reading that differed from the one before, and when it was taken
+    last_change: Option<(u64, Instant)>,
+    stale: bool,
+}
+
+impl StalenessTracker {
+    /// Constructs a new `StalenessTracker`, which starts out fresh.
+    ///
+    /// * `threshold` - How long the heartbeat needs to stay unchanged to go stale,
+    ///   e.g. 500 ms
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            last_change: None,
+            stale: false,
+        }
+    }
+
+    /// Updates the tracker with a reading taken at `now`, and returns whether the
+    /// heartbeat is stale.
+    pub fn update(self: &mut Self, now: Instant, reading: HeartbeatReading) -> bool {
+        match self.last_change {
+            Some((enqueued, since)) if enqueued == reading.enqueued => {
+                self.stale = reading.playing && now.duration_since(since) > self.threshold;
+            }
+            _ => {
+                self.last_change = Some((reading.enqueued, now));
+                self.stale = false;
+            }
+        }
+        self.stale
+    }
+
+    /// Returns whether the heartbeat was stale at the last update.
+    pub fn is_stale(self: &Self) -> bool {
+        self.stale
+    }
+}
+
+/// Decides whether a view went idle, because no audible audio reached its buffer for
+/// a while; See [`IdleModifiers`](crate::visualizers::IdleModifiers).
+///
+/// The tracker is updated once per frame, with the number of elements the buffer
+/// has enqueued so far and whether any of the elements that are new since the last
+/// update were audible; [`fresh()`](Self::fresh) tells how many of them are new.
+/// It goes idle once nothing audible arrived for the whole period, and is active
+/// again as soon as something does - so brief silences between notes never make a
+/// view flicker. A host that stops processing enqueues nothing at all, so its views
+/// go idle as well.
+#[derive(Debug, Clone)]
+pub struct IdleTracker {
+    idle_after: Duration,
+    // The number of elements the buffer had enqueued at the last update
+    enqueued: Option<u64>,
+    // When the last audible element arrived, or when tracking started
+    last_signal: Option<Instant>,
+    idle_since: Option<Instant>,
+}
+
+impl IdleTracker {
+    /// Constructs a new `IdleTracker`, which starts out active.
+    ///
+    /// * `idle_after` - How long the buffer needs to stay silent to go idle, e.g. 2 s
+    pub fn new(idle_after: Duration) -> Self {
+        Self {
+            idle_after,
+            enqueued: None,
+            last_signal: None,
+            idle_since: None,
+        }
+    }
+
+    /// Returns how many of the buffer's newest elements were enqueued since the last
+    /// update.
+    ///
+    /// Before the first update, or for buffers that don't count the elements they
+    /// enqueue, every element counts as new; The result is then `usize::MAX`, and
+    /// needs to be clamped to the buffer's length.
+    pub fn fresh(self: &Self, enqueued: Option<u64>) -> usize {
+        match (self.enqueued, enqueued) {
+            (Some(last), Some(enqueued)) => enqueued
+                .checked_sub(last)
+                .map_or(usize::MAX, |fresh| fresh.try_into().unwrap_or(usize::MAX)),
+            _ => usize::MAX,
+        }
+    }
+
+    /// Updates the tracker at `now`, and returns whether the view is idle.
+    ///
+    /// * `enqueued` - The number of elements the buffer has enqueued so far, e.g. from
+    ///   [`VisualizerBuffer::enqueued()`](super::VisualizerBuffer::enqueued)
+    /// * `audible` - Whether any of the [fresh](Self::fresh) elements were audible
+    pub fn update(self: &mut Self, now: Instant, enqueued: Option<u64>, audible: bool) -> bool {
+        self.enqueued = enqueued;
+        let last_signal = *self.last_signal.get_or_insert(now);
+        if audible {
+            self.last_signal = Some(now);
+            self.idle_since = None;
+        } else if self.idle_since.is_none()
+            && now.saturating_duration_since(last_signal) >= self.idle_after
+        {
+            self.idle_since = Some(now);
+        }
+        self.is_idle()
+    }
+
+    /// Returns whether the view was idle at the last update.
+    pub fn is_idle(self: &Self) -> bool {
+        self.idle_since.is_some()
+    }
+
+    /// Returns when the view went idle, or `None` while it's active.
+    pub fn idle_since(self: &Self) -> Option<Instant> {
+        self.idle_since
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Heartbeat, HeartbeatReading, IdleTracker, StalenessTracker};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn readings() {
+        let heartbeat = Heartbeat::new();
+        assert_eq!(heartbeat.read(), HeartbeatReading::default());
+
+        heartbeat.clone().beat(512, true);
+        heartbeat.beat(1024, true);
+        assert_eq!(
+            heartbeat.read(),
+            HeartbeatReading {
+                enqueued: 2,
+                last_sample: 1024,
+                playing: true,
+            }
+        );
+        heartbeat.beat(1536, false);
+        assert!(!heartbeat.read().playing);
+    }
+
+    #[test]
+    fn fresh_stale_fresh() {
+        let heartbeat = Heartbeat::new();
+        let mut tracker = StalenessTracker::new(Duration::from_millis(500));
+        let start = Instant::now();
+        let frame = |n: u64| start + Duration::from_millis(n * 16);
+
+        // Audio runs for a second, enqueueing an element every other frame
+        let mut samples = 0;
+        for n in 0..60 {
+            if n % 2 == 0 {
+                samples += 1024;
+                heartbeat.beat(samples, true);
+            }
+            assert!(!tracker.update(frame(n), heartbeat.read()), "frame {}", n);
+        }
+
+        // The host stops processing, while the transport still claims to be playing;
+        // The last element was enqueued in frame 58, and frame 90 is the first one
+        // more than 500 ms later
+        let stale = (60..120).find(|n| tracker.update(frame(*n), heartbeat.read()));
+        assert_eq!(stale, Some(90));
+        assert!(tracker.is_stale());
+
+        // Processing resumes
+        heartbeat.beat(samples + 1024, true);
+        assert!(!tracker.update(frame(120), heartbeat.read()));
+        assert!(!tracker.is_stale());
+    }
+
+    #[test]
+    fn stopped_transport_and_stalled_editor() {
+        let heartbeat = Heartbeat::new();
+        let mut tracker = StalenessTracker::new(Duration::from_millis(500));
+        let start = Instant::now();
+
+        // A stopped transport never goes stale
+        heartbeat.beat(1024, false);
+        tracker.update(start, heartbeat.read());
+        assert!(!tracker.update(start + Duration::from_secs(5), heartbeat.read()));
+
+        // Neither does an editor that misses frames while the audio keeps running
+        heartbeat.beat(2048, true);
+        assert!(!tracker.update(start + Duration::from_secs(6), heartbeat.read()));
+        heartbeat.beat(3072, true);
+        assert!(!tracker.update(start + Duration::from_secs(8), heartbeat.read()));
+    }
+
+    #[test]
+    fn idle_after_silence() {
+        let mut tracker = IdleTracker::new(Duration::from_secs(2));
+        let start = Instant::now();
+        let frame = |n: u64| start + Duration::from_millis(n * 16);
+
+        // A note, and a silence of a second between two notes, stay active
+        let mut enqueued = 0;
+        for n in 0..250 {
+            enqueued += 1;
+            let audible = n < 60 || n >= 120;
+            assert!(
+                !tracker.update(frame(n), Some(enqueued), audible),
+                "frame {}",
+                n
+            );
+        }
+
+        // The music stops after frame 249, and frame 374 is two seconds later
+        let idle = (250..500).find(|n| {
+            enqueued += 1;
+            tracker.update(frame(*n), Some(enqueued), false)
+        });
+        assert_eq!(idle, Some(374));
+        assert_eq!(tracker.idle_since(), Some(frame(374)));
+
+        // Idle only starts once, and the first audible element ends it
+        assert!(tracker.update(frame(500), Some(enqueued + 1), false));
+        assert_eq!(tracker.idle_since(), Some(frame(374)));
+        assert!(!tracker.update(frame(501), Some(enqueued + 2), true));
+        assert_eq!(tracker.idle_since(), None);
+        assert!(!tracker.update(frame(502), Some(enqueued + 3), false));
+    }
+
+    #[test]
+    fn fresh_elements() {
+        let mut tracker = IdleTracker::new(Duration::from_secs(2));
+        let start = Instant::now();
+
+        // Everything is new at first, and for buffers that don't count their elements
+        assert_eq!(tracker.fresh(Some(40)), usize::MAX);
+        tracker.update(start, Some(40), false);
+        assert_eq!(tracker.fresh(Some(43)), 3);
+        assert_eq!(tracker.fresh(Some(40)), 0);
+        assert_eq!(tracker.fresh(None), usize::MAX);
+
+        // A buffer that was replaced by a new one starts counting from zero
+        assert_eq!(tracker.fresh(Some(2)), usize::MAX);
+    }
+
+    #[test]
+    fn idle_without_elements() {
+        let mut tracker = IdleTracker::new(Duration::from_millis(500));
+        let start = Instant::now();
+
+        // The first update starts the clock, even when the view opens on silence
+        assert!(!tracker.update(start, Some(10), false));
+        assert!(!tracker.update(start + Duration::from_millis(499), Some(10), false));
+        assert!(tracker.update(start + Duration::from_millis(500), Some(10), false));
+
+        // A host that stops processing enqueues nothing, and stays idle
+        assert!(tracker.update(start + Duration::from_secs(10), Some(10), false));
+    }
+}
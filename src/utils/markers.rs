@@ -0,0 +1,496 @@
+use std::error::Error;
+use std::fmt;
+
+/// The most characters a marker's label can have; Longer labels are cut off.
+pub const MAX_LABEL_CHARS: usize = 32;
+/// The most markers a [`MarkerStore`] keeps; Adding another one drops the oldest.
+pub const MAX_MARKERS: usize = 256;
+
+// The version of the serialized format, which is written before everything else
+const FORMAT_VERSION: &str = "1";
+
+/// A labeled marker, pinned to a moment in a buffer's history.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Marker {
+    /// The moment the marker is pinned to, as the number of samples that the buffer
+    /// had been fed by then; See [`sample_clock()`](super::VisualizerBuffer::sample_clock).
+    pub position: u64,
+    /// A short label, like "ad break".
+    pub label: String,
+    /// The index of the marker's color, within the colors the
+    /// [`Graph`](crate::visualizers::Graph) is given.
+    pub color: u8,
+}
+
+/// Annotations that are pinned to moments in a buffer's history, e.g. "ad break"
+/// or "mic change" on a loudness graph during a long monitoring session.
+///
+/// Each [`Marker`] stores the absolute position of its moment in samples, counted
+/// by the buffer's [`sample_clock()`](super::VisualizerBuffer::sample_clock). As
+/// the buffer scrolls, the marker moves along with the moment it's pinned to, and
+/// once the moment has scrolled out of the buffer, the marker is
+/// [evicted](Self::evict). A [`Graph`](crate::visualizers::Graph) draws the markers
+/// of a store, and lets them be added and removed by clicking it; See
+/// [`markers()`](crate::visualizers::GraphModifiers::markers).
+///
+/// # Sample rates
+///
+/// The positions are counted at the store's [sample rate](Self::sample_rate). When
+/// the sample rate changes, the buffer rescales its count to the new rate, so that
+/// it still measures the same amount of time, and the store rescales each position
+/// the same way - multiplied by the new rate over the old one, and rounded to the
+/// nearest sample. A marker thus stays the same number of seconds away from now,
+/// give or take half a sample. A graph keeps the store at its buffer's sample rate.
+///
+/// # Persisting
+///
+/// The store [serializes](Self::serialize) to a compact string, which can be
+/// persisted along with the plug-in's parameters, like a
+/// [summary](super::PeakBuffer::summarize):
+///
+/// ```
+/// // Inside the plug-in's Params, next to the editor state
+/// #[persist = "markers"]
+/// markers: Mutex<String>,
+///
+/// // Whenever the editor closes
+/// *params.markers.lock().unwrap() = marker_store.lock().unwrap().serialize();
+///
+/// // Whenever the editor opens
+/// if let Ok(markers) = MarkerStore::parse(&params.markers.lock().unwrap()) {
+///     *marker_store.lock().unwrap() = markers;
+/// }
+/// ```
+///
+/// Positions are only meaningful as long as the buffer keeps counting. A marker
+/// that lies ahead of the count was placed before the count restarted, e.g. before
+/// the plug-in was reloaded, and is evicted as well.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MarkerStore {
+    // Sorted by position, from the oldest to the newest
+    markers: Vec<Marker>,
+    sample_rate: f32,
+    // The label and the color of the next marker that is added by clicking a graph
+    next_label: String,
+    next_color: u8,
+}
+
+impl MarkerStore {
+    /// Constructs a new, empty `MarkerStore`, counting positions at the given
+    /// sample rate.
+    ///
+    /// The sample rate can be 0 if it isn't known yet, in which case the store
+    /// takes on the rate it's [set](Self::set_sample_rate) to first.
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the sample rate that the positions are counted at.
+    pub fn sample_rate(self: &Self) -> f32 {
+        self.sample_rate
+    }
+
+    /// Sets the sample rate that the positions are counted at, rescaling every
+    /// position to it; See [Sample rates](Self#sample-rates).
+    ///
+    /// A sample rate that isn't positive and finite is ignored.
+    pub fn set_sample_rate(self: &mut Self, sample_rate: f32) {
+        if !(sample_rate > 0. && sample_rate.is_finite()) || sample_rate == self.sample_rate {
+            return;
+        }
+        for marker in &mut self.markers {
+            marker.position = rescale_samples(marker.position, self.sample_rate, sample_rate);
+        }
+        self.sample_rate = sample_rate;
+    }
+
+    /// Adds a marker, and returns its index.
+    ///
+    /// The label is cut off after [`MAX_LABEL_CHARS`] characters. If the store
+    /// already holds [`MAX_MARKERS`] markers, the oldest one is dropped.
+    pub fn add(self: &mut Self, position: u64, label: &str, color: u8) -> usize {
+        if self.markers.len() >= MAX_MARKERS {
+            self.markers.remove(0);
+        }
+        let index = self
+            .markers
+            .partition_point(|marker| marker.position <= position);
+        self.markers.insert(
+            index,
+            Marker {
+                position,
+                label: label.chars().take(MAX_LABEL_CHARS).collect(),
+                color,
+            },
+        );
+        index
+    }
+
+    /// Removes the marker at the index, and returns it.
+    pub fn remove(self: &mut Self, index: usize) -> Option<Marker> {
+        (index < self.markers.len()).then(|| self.markers.remove(index))
+    }
+
+    /// Removes every marker.
+    pub fn clear(self: &mut Self) {
+        self.markers.clear();
+    }
+
+    /// Returns the markers, from the oldest to the newest.
+    pub fn markers(self: &Self) -> &[Marker] {
+        &self.markers
+    }
+
+    /// Returns the number of markers.
+    pub fn len(self: &Self) -> usize {
+        self.markers.len()
+    }
+
+    /// Returns `true` if there are no markers.
+    pub fn is_empty(self: &Self) -> bool {
+        self.markers.is_empty()
+    }
+
+    /// Removes the markers that have scrolled out of a buffer, and returns how many
+    /// were removed.
+    ///
+    /// * `now` - The buffer's current [`sample_clock()`](super::VisualizerBuffer::sample_clock)
+    /// * `retained` - The number of samples the buffer spans
+    pub fn evict(self: &mut Self, now: u64, retained: u64) -> usize {
+        let len = self.markers.len();
+        self.markers
+            .retain(|marker| marker.position <= now && now - marker.position <= retained);
+        len - self.markers.len()
+    }
+
+    /// Sets the label and the color of the markers that are added by clicking a
+    /// graph, e.g. from a text box in the editor.
+    pub fn set_next_label(self: &mut Self, label: &str, color: u8) {
+        self.next_label = label.chars().take(MAX_LABEL_CHARS).collect();
+        self.next_color = color;
+    }
+
+    /// Returns the label and the color of the markers that are added by clicking a
+    /// graph.
+    pub fn next_label(self: &Self) -> (&str, u8) {
+        (&self.next_label, self.next_color)
+    }
+
+    /// Serializes the markers and the sample rate to a compact string, which
+    /// [`parse()`](Self::parse) reads back.
+    ///
+    /// The string is made up of records separated by `;` - the format's version, the
+    /// sample rate, and a `position,color,label` record for each marker, with any
+    /// `;` or `\` inside the labels escaped by a `\`. The next label isn't included.
+    pub fn serialize(self: &Self) -> String {
+        let mut text = format!("{};{}", FORMAT_VERSION, self.sample_rate);
+        for marker in &self.markers {
+            text.push_str(&format!(";{},{},", marker.position, marker.color));
+            for c in marker.label.chars() {
+                if c == ';' || c == '\\' {
+                    text.push('\\');
+                }
+                text.push(c);
+            }
+        }
+        text
+    }
+
+    /// Reads markers from a string returned by [`serialize()`](Self::serialize).
+    ///
+    /// An empty string is read as an empty store, so that a persisted field that was
+    /// never written doesn't fail.
+    pub fn parse(text: &str) -> Result<Self, MarkerParseError> {
+        if text.is_empty() {
+            return Ok(Self::default());
+        }
+        let records = split_records(text);
+        if records[0] != FORMAT_VERSION {
+            return Err(MarkerParseError::UnsupportedVersion(records[0].clone()));
+        }
+        let sample_rate = records
+            .get(1)
+            .and_then(|rate| rate.parse::<f32>().ok())
+            .filter(|rate| *rate >= 0. && rate.is_finite())
+            .ok_or(MarkerParseError::InvalidSampleRate)?;
+
+        let mut store = Self::new(sample_rate);
+        for (index, record) in records.iter().skip(2).enumerate() {
+            let mut fields = record.splitn(3, ',');
+            let marker = (|| {
+                let position = fields.next()?.parse::<u64>().ok()?;
+                let color = fields.next()?.parse::<u8>().ok()?;
+                Some((position, color, fields.next()?))
+            })();
+            let (position, color, label) = marker.ok_or(MarkerParseError::InvalidMarker(index))?;
+            store.add(position, label, color);
+        }
+        Ok(store)
+    }
+}
+
+/// An error that occurred while parsing a [`MarkerStore`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarkerParseError {
+    /// The string was written with a version of the format that can't be read.
+    UnsupportedVersion(String),
+    /// The sample rate is missing, or isn't a finite, non-negative number.
+    InvalidSampleRate,
+    /// The marker at the index is missing its position, its color or its label.
+    InvalidMarker(usize),
+}
+
+impl fmt::Display for MarkerParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarkerParseError::UnsupportedVersion(version) => {
+                write!(f, "unsupported marker format version {:?}", version)
+            }
+            MarkerParseError::InvalidSampleRate => write!(f, "invalid marker sample rate"),
+            MarkerParseError::InvalidMarker(index) => write!(f, "invalid marker {}", index),
+        }
+    }
+}
+
+impl Error for MarkerParseError {}
+
+/// Rescales a number of samples from one sample rate to another, so that it spans
+/// the same amount of time; See [Sample rates](MarkerStore#sample-rates).
+///
+/// The count is kept as it is if either rate isn't positive and finite, e.g. before
+/// the first sample rate is known.
+pub(crate) fn rescale_samples(samples: u64, from: f32, to: f32) -> u64 {
+    let valid = |rate: f32| rate > 0. && rate.is_finite();
+    if !valid(from) || !valid(to) {
+        return samples;
+    }
+    (samples as f64 * to as f64 / from as f64).round() as u64
+}
+
+/// Returns the number of seconds between a marker's position and now, or `None` if
+/// the position lies ahead of now.
+///
+/// * `sample_rate` - The rate the position is counted at
+/// * `clock` - The buffer's current [`sample_clock()`](super::VisualizerBuffer::sample_clock)
+#[cfg_attr(not(feature = "vizia"), allow(dead_code))]
+pub(crate) fn marker_age(position: u64, sample_rate: f32, clock: (u64, f32)) -> Option<f32> {
+    let (now, clock_rate) = clock;
+    if !(sample_rate > 0. && clock_rate > 0.) {
+        return None;
+    }
+    let age = now as f64 / clock_rate as f64 - position as f64 / sample_rate as f64;
+    // Rounding the positions to whole samples may leave a marker placed right now a
+    // fraction of a sample ahead
+    (age > -0.5 / clock_rate as f64).then_some(age.max(0.) as f32)
+}
+
+// Splits a serialized store at the unescaped semicolons, unescaping each record
+fn split_records(text: &str) -> Vec<String> {
+    let mut records = vec![String::new()];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => records.last_mut().unwrap().extend(chars.next()),
+            ';' => records.push(String::new()),
+            c => records.last_mut().unwrap().push(c),
+        }
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        marker_age, rescale_samples, MarkerParseError, MarkerStore, MAX_LABEL_CHARS, MAX_MARKERS,
+    };
+    use crate::utils::{PeakBuffer, VisualizerBuffer};
+
+    #[test]
+    fn round_trip() {
+        let mut store = MarkerStore::new(48000.);
+        store.add(480_000, "ad break", 2);
+        store.add(96_000, "mic change", 0);
+        // Separators and escapes inside labels
+        store.add(1_000_000_000_000, r"a;b\c,d", 255);
+        store.add(5, "", 1);
+        store.add(7, "Ünïcode ✓", 3);
+
+        let text = store.serialize();
+        assert_eq!(MarkerStore::parse(&text), Ok(store.clone()));
+        assert!(text.starts_with("1;48000;5,1,;7,3,"));
+        assert!(text.ends_with(r"1000000000000,255,a\;b\\c,d"));
+
+        // Empty stores, including one that was never written
+        assert_eq!(
+            MarkerStore::parse(&MarkerStore::new(44100.).serialize()),
+            Ok(MarkerStore::new(44100.))
+        );
+        assert_eq!(MarkerStore::parse(""), Ok(MarkerStore::default()));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            MarkerStore::parse("2;48000"),
+            Err(MarkerParseError::UnsupportedVersion("2".to_owned()))
+        );
+        assert_eq!(
+            MarkerStore::parse("1"),
+            Err(MarkerParseError::InvalidSampleRate)
+        );
+        assert_eq!(
+            MarkerStore::parse("1;-48000"),
+            Err(MarkerParseError::InvalidSampleRate)
+        );
+        assert_eq!(
+            MarkerStore::parse("1;48000;5,0,ok;x,0,broken"),
+            Err(MarkerParseError::InvalidMarker(1))
+        );
+        assert_eq!(
+            MarkerStore::parse("1;48000;5,256,color"),
+            Err(MarkerParseError::InvalidMarker(0))
+        );
+        assert_eq!(
+            MarkerStore::parse("1;48000;5,0"),
+            Err(MarkerParseError::InvalidMarker(0))
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let mut store = MarkerStore::new(48000.);
+        let label = "x".repeat(MAX_LABEL_CHARS + 10);
+        store.add(0, &label, 0);
+        assert_eq!(store.markers()[0].label.len(), MAX_LABEL_CHARS);
+
+        for position in 1..MAX_MARKERS as u64 + 5 {
+            store.add(position, "", 0);
+        }
+        assert_eq!(store.len(), MAX_MARKERS);
+        assert_eq!(store.markers()[0].position, 5);
+    }
+
+    #[test]
+    fn sorted_by_position() {
+        let mut store = MarkerStore::new(48000.);
+        assert_eq!(store.add(300, "c", 0), 0);
+        assert_eq!(store.add(100, "a", 0), 0);
+        assert_eq!(store.add(200, "b", 0), 1);
+        let labels = store.markers().iter().map(|m| m.label.as_str());
+        assert_eq!(labels.collect::<Vec<_>>(), vec!["a", "b", "c"]);
+
+        assert_eq!(store.remove(1).map(|m| m.label), Some("b".to_owned()));
+        assert_eq!(store.remove(5), None);
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn evicted_once_out_of_window() {
+        // A buffer spanning 1 second at 100 Hz, with markers every 0.25 seconds
+        let mut buffer = PeakBuffer::new(10, 1.0, 0.0);
+        buffer.set_sample_rate(100.);
+        let mut store = MarkerStore::new(100.);
+        for i in 0..200 {
+            if i % 25 == 0 {
+                store.add(buffer.sample_clock().unwrap().0, &i.to_string(), 0);
+            }
+            buffer.enqueue(0.5);
+        }
+        let retained = 100;
+
+        // After 2 seconds, the markers of the first second have scrolled out
+        let (now, _) = buffer.sample_clock().unwrap();
+        assert_eq!(now, 200);
+        assert_eq!(store.evict(now, retained), 4);
+        let labels = store.markers().iter().map(|m| m.label.as_str());
+        assert_eq!(labels.collect::<Vec<_>>(), vec!["100", "125", "150", "175"]);
+
+        // One at a time, as they age out
+        for _ in 0..30 {
+            buffer.enqueue(0.5);
+        }
+        assert_eq!(store.evict(buffer.sample_clock().unwrap().0, retained), 2);
+        assert_eq!(store.markers()[0].label, "150");
+
+        // Markers ahead of the count were placed before it restarted
+        store.add(1_000_000, "previous session", 0);
+        assert_eq!(store.evict(230, retained), 1);
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn sample_rate_changes() {
+        let mut buffer = PeakBuffer::new(100, 10.0, 0.0);
+        buffer.set_sample_rate(48000.);
+        let mut store = MarkerStore::new(48000.);
+        buffer.enqueue_slice(&vec![0.; 48000 * 3]);
+
+        // Half a second ago
+        let (now, rate) = buffer.sample_clock().unwrap();
+        store.add(now - 24000, "ad break", 0);
+        assert_eq!(
+            marker_age(store.markers()[0].position, 48000., (now, rate)),
+            Some(0.5)
+        );
+
+        // The buffer and the store are rescaled alike, so the marker stays half a
+        // second behind, and moves on at the new rate
+        for rate in [44100., 96000., 22050., 48000.] {
+            buffer.set_sample_rate(rate);
+            store.set_sample_rate(rate);
+            let clock = buffer.sample_clock().unwrap();
+            let age = marker_age(store.markers()[0].position, rate, clock).unwrap();
+            assert!((age - 0.5).abs() <= 1. / rate, "{} at {} Hz", age, rate);
+        }
+        buffer.set_sample_rate(44100.);
+        store.set_sample_rate(44100.);
+        buffer.enqueue_slice(&vec![0.; 11025]);
+        let age = marker_age(
+            store.markers()[0].position,
+            store.sample_rate(),
+            buffer.sample_clock().unwrap(),
+        )
+        .unwrap();
+        assert!((age - 0.75).abs() <= 1. / 44100., "{}", age);
+
+        // Before the store follows, its positions are still read at its own rate
+        buffer.set_sample_rate(96000.);
+        let age = marker_age(
+            store.markers()[0].position,
+            store.sample_rate(),
+            buffer.sample_clock().unwrap(),
+        )
+        .unwrap();
+        assert!((age - 0.75).abs() <= 1. / 44100., "{}", age);
+    }
+
+    #[test]
+    fn rescaling() {
+        assert_eq!(rescale_samples(48000, 48000., 44100.), 44100);
+        assert_eq!(rescale_samples(3, 48000., 44100.), 3);
+        assert_eq!(rescale_samples(1, 44100., 96000.), 2);
+        // Without a known rate, the count stays as it is
+        assert_eq!(rescale_samples(1234, 0., 48000.), 1234);
+
+        // A store without a rate takes on the first one
+        let mut store = MarkerStore::new(0.);
+        store.add(1000, "", 0);
+        store.set_sample_rate(48000.);
+        assert_eq!(store.markers()[0].position, 1000);
+        store.set_sample_rate(96000.);
+        assert_eq!(store.markers()[0].position, 2000);
+        store.set_sample_rate(f32::NAN);
+        assert_eq!(store.sample_rate(), 96000.);
+    }
+
+    #[test]
+    fn ages() {
+        assert_eq!(marker_age(100, 100., (300, 100.)), Some(2.));
+        assert_eq!(marker_age(300, 100., (300, 100.)), Some(0.));
+        assert_eq!(marker_age(400, 100., (300, 100.)), None);
+        assert_eq!(marker_age(100, 0., (300, 100.)), None);
+    }
+}
@@ -1,15 +1,74 @@
 //! Generic utility functions and structures.
 
-mod buffers;
+mod auto_range;
+mod buffer_tap;
+pub mod buffers;
+mod config_error;
+mod decay;
+mod diagnostics;
+mod feature_extractor;
+mod format;
+#[cfg(feature = "shared-instances")]
+mod instances;
+mod liveness;
+mod markers;
+mod multiband_buffer;
+#[cfg(test)]
+mod no_alloc;
+mod normalized_read;
+mod range_segments;
+#[cfg(feature = "vizia")]
+mod revision;
+mod shared_range;
+mod shared_read;
+mod slot_subscription;
+mod smoothing;
+#[cfg(feature = "vizia")]
+mod snapshot_set;
 mod spectrum;
+mod staged_buffer;
+mod try_lock_buffer;
+mod visualizer_config;
 
+pub use auto_range::*;
+pub use buffer_tap::{BufferTap, TapConsumer, TapMode};
 pub use buffers::*;
+pub use config_error::ConfigError;
+pub use decay::*;
+pub use diagnostics::{Diagnostics, DiagnosticsSnapshot};
+pub use feature_extractor::{Feature, FeatureExtractor, Features};
+#[cfg(feature = "vizia")]
+pub(crate) use format::{default_formatter, format_scaled};
+pub use format::{DecibelFormatter, FrequencyFormatter, PlainFormatter, ValueFormatter};
+#[cfg(feature = "shared-instances")]
+pub use instances::{instances, InstanceRegistry, Publication, SharedSnapshot, Subscription};
+pub use liveness::{Heartbeat, HeartbeatReading, IdleTracker, ProducerWatch, StalenessTracker};
+#[cfg(feature = "vizia")]
+pub(crate) use markers::marker_age;
+pub use markers::{Marker, MarkerParseError, MarkerStore, MAX_LABEL_CHARS, MAX_MARKERS};
+pub use multiband_buffer::MultibandPeakBuffer;
+pub use normalized_read::*;
+pub use range_segments::{RangeSegments, BREAK_GAP};
+#[cfg(feature = "vizia")]
+pub use revision::{BufferRevision, VisualizerLens};
+pub use shared_range::SharedRange;
+pub use shared_read::{SharedRead, SharedWrite};
+pub use slot_subscription::{SlotMessage, SlotReceiver, SlotRecvError};
+pub use smoothing::*;
+#[cfg(feature = "vizia")]
+pub use snapshot_set::SnapshotSet;
 pub use spectrum::*;
+pub use staged_buffer::*;
+pub use try_lock_buffer::{ChunkReduce, TryLockBuffer};
+pub use visualizer_config::VisualizerConfig;
 
 use nih_plug::util::db_to_gain;
-use nih_plug_vizia::vizia::binding::Res;
-use nih_plug_vizia::vizia::context::{Context, EventContext};
-use nih_plug_vizia::vizia::entity::Entity;
+#[cfg(feature = "vizia")]
+use nih_plug_vizia::vizia::{
+    binding::Res,
+    context::{Context, EventContext},
+    entity::Entity,
+};
 
 /// Analogous to VIZIA's own ValueScaling.
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -48,7 +107,7 @@ impl ValueScaling {
     pub fn value_to_normalized(&self, value: f32, min: f32, max: f32) -> f32 {
         let unmap = |x: f32| -> f32 { (x - min) / (max - min) };
 
-        match self {
+        let normalized = match self {
             ValueScaling::Linear => unmap(value),
 
             ValueScaling::Power(exponent) => unmap(value).powf(1.0 / *exponent),
@@ -63,8 +122,14 @@ impl ValueScaling {
                 const CONVERSION_FACTOR: f32 = std::f32::consts::LOG10_E * 20.0;
                 value.ln() * CONVERSION_FACTOR
             }),
+        };
+
+        // NaN can't be clamped, so map it to the bottom of the range instead
+        if normalized.is_nan() {
+            0.
+        } else {
+            normalized.clamp(0., 1.)
         }
-        .clamp(0., 1.)
     }
 
     pub fn value_to_normalized_optional(&self, value: f32, min: f32, max: f32) -> Option<f32> {
@@ -95,6 +160,7 @@ impl ValueScaling {
 }
 
 // We can't use impl_res_simple!() since we're using nih_plug's version of VIZIA
+#[cfg(feature = "vizia")]
 impl Res<ValueScaling> for ValueScaling {
     fn get_val(&self, _: &Context) -> ValueScaling {
         *self
@@ -0,0 +1,463 @@
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+use std::sync::{Arc, Mutex};
+
+use super::buffers::MAX_CHUNK_SIZE;
+use super::{PeakBuffer, VisualizerBuffer};
+
+/// Splits the incoming audio into frequency bands, and feeds a [`PeakBuffer`] per
+/// band.
+///
+/// Multiband processors usually want a level graph per band. Instead of splitting
+/// the bands inside the plug-in and keeping a buffer per band in sync, a
+/// `MultibandPeakBuffer` is fed the full signal on the audio thread, splits it using
+/// 4th-order Linkwitz-Riley crossovers, and enqueues each band into its own shared
+/// `PeakBuffer`.
+///
+/// All bands are clones of the same `PeakBuffer` and are fed the same number of
+/// samples, so their elements line up. The editor reads each band through the
+/// buffer returned by [`band()`](Self::band), just like any other shared buffer.
+///
+/// ```
+/// // Inside the plug-in's constructor, splitting at 120 Hz, 2 kHz and 8 kHz
+/// let bands = MultibandPeakBuffer::new(
+///     &[120.0, 2000.0, 8000.0],
+///     PeakBuffer::new(800, 10.0, 50.0),
+/// );
+///
+/// // Inside the editor, a graph per band
+/// Graph::new(cx, Data::low_band, (-32.0, 8.0), ValueScaling::Decibels);
+/// ```
+///
+/// The bands are only meant to be looked at - they aren't phase-aligned, so they
+/// don't sum back up to the input.
+#[derive(Debug)]
+pub struct MultibandPeakBuffer {
+    bands: Vec<Arc<Mutex<PeakBuffer>>>,
+    crossovers: Vec<f32>,
+    // One crossover per split, from the lowest to the highest frequency
+    filters: Vec<Crossover>,
+    sample_rate: f32,
+    // The samples of each band for the chunk that is being enqueued
+    scratch: Vec<Vec<f32>>,
+}
+
+impl MultibandPeakBuffer {
+    /// Constructs a new `MultibandPeakBuffer`.
+    ///
+    /// * `crossovers` - The frequencies (in Hz) the bands are split at; `n` crossovers
+    ///   make `n + 1` bands
+    /// * `band` - The buffer that each band is a clone of
+    ///
+    /// The buffer needs to be provided a sample rate after initialization - do this by
+    /// calling [`set_sample_rate`](Self::set_sample_rate) inside your
+    /// [`initialize()`](nih_plug::plugin::Plugin::initialize) function.
+    pub fn new(crossovers: &[f32], band: PeakBuffer) -> Self {
+        let num_bands = crossovers.len() + 1;
+        let mut buffer = Self {
+            bands: (0..num_bands)
+                .map(|_| Arc::new(Mutex::new(band.clone())))
+                .collect(),
+            crossovers: Vec::new(),
+            filters: vec![Crossover::default(); crossovers.len()],
+            sample_rate: 0.,
+            // Not `vec![..; num_bands]`, whose clones of the vector don't keep its
+            // capacity, so that enqueueing never needs to grow them
+            scratch: (0..num_bands)
+                .map(|_| Vec::with_capacity(MAX_CHUNK_SIZE))
+                .collect(),
+        };
+        buffer.crossovers = sorted(crossovers);
+        buffer.design_filters();
+        buffer
+    }
+
+    /// Returns the shared buffer of the band at the index, from the lowest to the
+    /// highest band.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no band at the index.
+    pub fn band(self: &Self, index: usize) -> Arc<Mutex<PeakBuffer>> {
+        self.bands[index].clone()
+    }
+
+    /// Returns the shared buffers of all bands, from the lowest to the highest band.
+    pub fn bands(self: &Self) -> &[Arc<Mutex<PeakBuffer>>] {
+        &self.bands
+    }
+
+    /// Returns the number of bands.
+    pub fn num_bands(self: &Self) -> usize {
+        self.bands.len()
+    }
+
+    /// Returns the crossover frequencies, in ascending order.
+    pub fn crossovers(self: &Self) -> &[f32] {
+        &self.crossovers
+    }
+
+    /// Moves the crossovers to the given frequencies, and clears all bands.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of crossovers changes - the editor holds on to the
+    /// buffer of each band, so the bands can't be added or removed.
+    pub fn set_crossovers(self: &mut Self, crossovers: &[f32]) {
+        assert_eq!(
+            crossovers.len(),
+            self.crossovers.len(),
+            "The number of crossovers of a MultibandPeakBuffer can't change"
+        );
+        self.crossovers = sorted(crossovers);
+        self.design_filters();
+        self.clear();
+    }
+
+    /// Sets the sample rate of all bands, and redesigns the crossovers for it.
+    ///
+    /// This clears all bands.
+    pub fn set_sample_rate(self: &mut Self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.design_filters();
+        for band in self.bands.iter() {
+            if let Ok(mut band) = band.lock() {
+                band.set_sample_rate(sample_rate);
+            }
+        }
+    }
+
+    /// Splits a sample and enqueues it into all bands.
+    ///
+    /// This locks the buffer of every band, so prefer
+    /// [`enqueue_buffer()`](Self::enqueue_buffer) for entire blocks.
+    pub fn enqueue(self: &mut Self, value: f32) {
+        let bands = &self.bands;
+        split(&mut self.filters, value, |band, value| {
+            if let Ok(mut buffer) = bands[band].lock() {
+                buffer.enqueue(value);
+            }
+        });
+    }
+
+    /// Splits an entire [`Buffer`](`nih_plug::buffer::Buffer`) and enqueues it into
+    /// all bands, mono-summing it if no channel is specified.
+    ///
+    /// The buffer of each band is locked once per chunk of up to 512 samples.
+    pub fn enqueue_buffer(
+        self: &mut Self,
+        buffer: &mut nih_plug::buffer::Buffer,
+        channel: Option<usize>,
+    ) {
+        let num_samples = buffer.samples();
+        let channels = buffer.as_slice_immutable();
+        let sample = |i: usize| match channel {
+            Some(channel) => channels[channel][i],
+            None => channels.iter().map(|channel| channel[i]).sum::<f32>() / channels.len() as f32,
+        };
+
+        let mut start = 0;
+        while start < num_samples {
+            let end = (start + MAX_CHUNK_SIZE).min(num_samples);
+            let scratch = &mut self.scratch;
+            scratch.iter_mut().for_each(Vec::clear);
+            for i in start..end {
+                split(&mut self.filters, sample(i), |band, value| {
+                    scratch[band].push(value)
+                });
+            }
+
+            for (band, samples) in self.bands.iter().zip(self.scratch.iter()) {
+                if let Ok(mut band) = band.lock() {
+                    for value in samples {
+                        band.enqueue(*value);
+                    }
+                }
+            }
+            start = end;
+        }
+    }
+
+    /// Catches up all bands on samples that were never enqueued; See
+    /// [`PeakBuffer::catch_up()`].
+    pub fn catch_up(self: &mut Self, elapsed_samples: u64) {
+        for band in self.bands.iter() {
+            if let Ok(mut band) = band.lock() {
+                band.catch_up(elapsed_samples);
+            }
+        }
+    }
+
+    /// Clears all bands, along with the state of the crossovers.
+    pub fn clear(self: &mut Self) {
+        self.filters.iter_mut().for_each(Crossover::reset);
+        for band in self.bands.iter() {
+            if let Ok(mut band) = band.lock() {
+                band.clear();
+            }
+        }
+    }
+
+    fn design_filters(self: &mut Self) {
+        for (filter, frequency) in self.filters.iter_mut().zip(self.crossovers.iter()) {
+            *filter = Crossover::new(*frequency, self.sample_rate);
+        }
+    }
+}
+
+fn sorted(crossovers: &[f32]) -> Vec<f32> {
+    let mut crossovers = crossovers.to_vec();
+    crossovers.sort_by(f32::total_cmp);
+    crossovers
+}
+
+/// Passes a sample through the crossovers, calling `f` with each band's index and
+/// sample.
+///
+/// The lowest band is split off first; The high-passed rest is split further by the
+/// next crossover.
+fn split(filters: &mut [Crossover], value: f32, mut f: impl FnMut(usize, f32)) {
+    let mut rest = value;
+    for (band, filter) in filters.iter_mut().enumerate() {
+        f(band, filter.low_pass(rest));
+        rest = filter.high_pass(rest);
+    }
+    f(filters.len(), rest);
+}
+
+/// A 4th-order Linkwitz-Riley crossover, made of two cascaded Butterworth biquads per
+/// side.
+#[derive(Debug, Clone, Copy, Default)]
+struct Crossover {
+    low: [Biquad; 2],
+    high: [Biquad; 2],
+}
+
+impl Crossover {
+    fn new(frequency: f32, sample_rate: f32) -> Self {
+        // Without a sample rate, everything passes through the lowest band
+        if sample_rate <= 0. {
+            return Self::default();
+        }
+        let frequency = frequency.clamp(1., sample_rate * 0.49);
+        let low = Biquad::low_pass(frequency, sample_rate);
+        let high = Biquad::high_pass(frequency, sample_rate);
+        Self {
+            low: [low; 2],
+            high: [high; 2],
+        }
+    }
+
+    fn low_pass(self: &mut Self, value: f32) -> f32 {
+        self.low
+            .iter_mut()
+            .fold(value, |value, biquad| biquad.process(value))
+    }
+
+    fn high_pass(self: &mut Self, value: f32) -> f32 {
+        self.high
+            .iter_mut()
+            .fold(value, |value, biquad| biquad.process(value))
+    }
+
+    fn reset(self: &mut Self) {
+        self.low
+            .iter_mut()
+            .chain(self.high.iter_mut())
+            .for_each(Biquad::reset);
+    }
+}
+
+/// A biquad filter in transposed direct form II, with coefficients from the RBJ
+/// audio EQ cookbook.
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Default for Biquad {
+    /// A filter that passes everything through unchanged.
+    fn default() -> Self {
+        Self::normalized(1., 0., 0., 1., 0., 0.)
+    }
+}
+
+impl Biquad {
+    fn low_pass(frequency: f32, sample_rate: f32) -> Self {
+        let (cos, alpha) = Self::prewarp(frequency, sample_rate);
+        let b = (1. - cos) / 2.;
+        Self::normalized(b, 2. * b, b, 1. + alpha, -2. * cos, 1. - alpha)
+    }
+
+    fn high_pass(frequency: f32, sample_rate: f32) -> Self {
+        let (cos, alpha) = Self::prewarp(frequency, sample_rate);
+        let b = (1. + cos) / 2.;
+        Self::normalized(b, -2. * b, b, 1. + alpha, -2. * cos, 1. - alpha)
+    }
+
+    // Returns the cosine of the angular frequency, and the alpha for a Butterworth Q
+    fn prewarp(frequency: f32, sample_rate: f32) -> (f32, f32) {
+        let w0 = 2. * PI * frequency / sample_rate;
+        (w0.cos(), w0.sin() / (2. * FRAC_1_SQRT_2))
+    }
+
+    fn normalized(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+            z1: 0.,
+            z2: 0.,
+        }
+    }
+
+    fn process(self: &mut Self, value: f32) -> f32 {
+        let output = self.b0 * value + self.z1;
+        self.z1 = self.b1 * value - self.a1 * output + self.z2;
+        self.z2 = self.b2 * value - self.a2 * output;
+        output
+    }
+
+    fn reset(self: &mut Self) {
+        self.z1 = 0.;
+        self.z2 = 0.;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MultibandPeakBuffer;
+    use crate::utils::buffers::test_support::{contents, with_buffer};
+    use crate::utils::{PeakBuffer, VisualizerBuffer};
+    use std::f32::consts::PI;
+
+    const SAMPLE_RATE: f32 = 48000.;
+
+    fn buffer() -> MultibandPeakBuffer {
+        let mut buffer =
+            MultibandPeakBuffer::new(&[120., 2000., 8000.], PeakBuffer::new(20, 1.0, 0.0));
+        buffer.set_sample_rate(SAMPLE_RATE);
+        buffer
+    }
+
+    // The sum of 32 sines spread across the band, with pseudo-random phases
+    fn band_limited_noise(low: f32, high: f32, sample_rate: f32, samples: usize) -> Vec<f32> {
+        let mut state = 0x9e37_79b9_u32;
+        let partials: Vec<(f32, f32)> = (0..32)
+            .map(|i| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                let frequency = low + (high - low) * i as f32 / 31.;
+                (frequency, state as f32 / u32::MAX as f32 * 2. * PI)
+            })
+            .collect();
+        (0..samples)
+            .map(|i| {
+                let t = i as f32 / sample_rate;
+                partials
+                    .iter()
+                    .map(|(frequency, phase)| (2. * PI * frequency * t + phase).sin())
+                    .sum::<f32>()
+                    / 32.
+            })
+            .collect()
+    }
+
+    // The mean peak of each band, over the second half of the buffer
+    fn band_levels(buffer: &MultibandPeakBuffer) -> Vec<f32> {
+        buffer
+            .bands()
+            .iter()
+            .map(|band| {
+                let band = band.lock().unwrap();
+                let peaks = contents(&*band);
+                let settled = &peaks[peaks.len() / 2..];
+                settled.iter().sum::<f32>() / settled.len() as f32
+            })
+            .collect()
+    }
+
+    fn assert_lands_in(levels: &[f32], expected: usize) {
+        for (band, level) in levels.iter().enumerate() {
+            if band != expected {
+                // At least 12 dB below the band the noise lies in
+                assert!(
+                    *level < levels[expected] / 4.,
+                    "band {} at {} vs. band {} at {}",
+                    band,
+                    level,
+                    expected,
+                    levels[expected]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn energy_lands_in_band() {
+        let bands = [(20., 60.), (400., 800.), (3500., 5000.), (14000., 20000.)];
+        for (expected, (low, high)) in bands.into_iter().enumerate() {
+            let mut buffer = buffer();
+            let mut channels = vec![band_limited_noise(low, high, SAMPLE_RATE, 48000)];
+            with_buffer(&mut channels, |b| buffer.enqueue_buffer(b, None));
+            assert_lands_in(&band_levels(&buffer), expected);
+        }
+    }
+
+    #[test]
+    fn bands_stay_aligned() {
+        let mut buffer = buffer();
+        let mut channels = vec![band_limited_noise(400., 800., SAMPLE_RATE, 30000)];
+        with_buffer(&mut channels, |b| buffer.enqueue_buffer(b, None));
+
+        let fill = |band: usize| {
+            let band = buffer.band(band);
+            let band = band.lock().unwrap();
+            (0..band.len()).filter(|i| band[*i] > 0.).count()
+        };
+        assert!((0..4).all(|band| fill(band) == fill(0)));
+    }
+
+    #[test]
+    fn sample_rate_redesigns_filters() {
+        // Crossovers designed for half the sample rate would lie at 16 kHz instead
+        let mut buffer = buffer();
+        buffer.set_sample_rate(2. * SAMPLE_RATE);
+        let mut channels = vec![band_limited_noise(10000., 14000., 2. * SAMPLE_RATE, 96000)];
+        with_buffer(&mut channels, |b| buffer.enqueue_buffer(b, None));
+        assert_lands_in(&band_levels(&buffer), 3);
+    }
+
+    #[test]
+    fn set_crossovers() {
+        let mut buffer = buffer();
+        let noise = band_limited_noise(3500., 5000., SAMPLE_RATE, 48000);
+        for sample in noise.iter() {
+            buffer.enqueue(*sample);
+        }
+
+        // Moving the crossovers clears the bands, and the noise lands elsewhere
+        buffer.set_crossovers(&[10000., 120., 2000.]);
+        assert_eq!(buffer.crossovers(), &[120., 2000., 10000.]);
+        assert!(band_levels(&buffer).iter().all(|level| *level == 0.));
+
+        for sample in noise.iter() {
+            buffer.enqueue(*sample);
+        }
+        assert_lands_in(&band_levels(&buffer), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn crossover_count_is_fixed() {
+        buffer().set_crossovers(&[120., 2000.]);
+    }
+}
@@ -0,0 +1,362 @@
+//! Makes sure that nothing the audio thread calls allocates or frees memory.
+//!
+//! Every buffer is driven through each of its audio-thread paths - enqueueing single
+//! samples, blocks, slices, interleaved and planar audio, publishing and pushing to
+//! taps - across the configurations that take different branches: blocks that wrap
+//! around the ring, elements that span a fractional number of samples, blocks larger
+//! than a chunk, silence, and every optional kind of tracking. Each call runs inside
+//! [`assert_no_alloc()`], so the first allocation fails the test along with the name
+//! of the call.
+//!
+//! The signals are allocated up front, outside of the checked calls.
+
+use std::sync::{Arc, Mutex};
+
+use crate::alloc_counter::assert_no_alloc;
+
+use super::buffers::test_support::{interleave, test_signal, with_buffer};
+use super::{
+    Aggregation, BalanceBuffer, BallisticsPreset, BufferMode, BufferTap, ChannelDownmix,
+    CountBuffer, Feature, FeatureExtractor, HistogramBuffer, MinimaBuffer, MultibandPeakBuffer,
+    NoteEventBuffer, PeakBuffer, RMSBuffer, RingBuffer, SpectrumInput, StagedBuffer, TapMode,
+    TieredBuffer, TryLockBuffer, VisualizerBuffer, WaveformBuffer,
+};
+
+// Single samples, blocks shorter and longer than the buffers, a block that just
+// exceeds a chunk, and a huge block from an offline render
+const BLOCK_SIZES: [usize; 6] = [1, 7, 64, 512, 513, 8192];
+
+// Sample rates at which the elements span a whole and a fractional number of samples
+const SAMPLE_RATES: [f32; 2] = [1000., 44100.];
+
+const DOWNMIXES: [ChannelDownmix; 3] = [
+    ChannelDownmix::Mean,
+    ChannelDownmix::MaxAbs,
+    ChannelDownmix::Channel(1),
+];
+
+/// Drives a buffer through every enqueueing path of [`VisualizerBuffer`], with
+/// signals as well as silence.
+fn assert_enqueue_paths<B>(name: &str, buffer: &mut B)
+where
+    B: VisualizerBuffer<f32>,
+{
+    for samples in BLOCK_SIZES {
+        let mut signal = test_signal(2, samples);
+        let mut silence = vec![vec![0.; samples]; 2];
+        let interleaved = interleave(&signal);
+
+        for channels in [&mut signal, &mut silence] {
+            with_buffer(channels, |block| {
+                assert_no_alloc(
+                    format_args!("{}::enqueue_buffer(None) of {} samples", name, samples),
+                    || buffer.enqueue_buffer(block, None),
+                );
+                assert_no_alloc(
+                    format_args!("{}::enqueue_buffer(Some) of {} samples", name, samples),
+                    || buffer.enqueue_buffer(block, Some(1)),
+                );
+            });
+        }
+
+        assert_no_alloc(
+            format_args!("{}::enqueue() of {} samples", name, samples),
+            || {
+                for sample in signal[0].iter() {
+                    buffer.enqueue(*sample);
+                }
+            },
+        );
+
+        let planar = [signal[0].as_slice(), signal[1].as_slice()];
+        for downmix in DOWNMIXES {
+            assert_no_alloc(
+                format_args!(
+                    "{}::enqueue_interleaved({:?}) of {} samples",
+                    name, downmix, samples
+                ),
+                || buffer.enqueue_interleaved(&interleaved, 2, downmix),
+            );
+            assert_no_alloc(
+                format_args!(
+                    "{}::enqueue_planar({:?}) of {} samples",
+                    name, downmix, samples
+                ),
+                || buffer.enqueue_planar(&planar, downmix),
+            );
+        }
+    }
+}
+
+/// Runs [`assert_enqueue_paths()`] on a new buffer at each sample rate.
+fn assert_buffer<B>(name: &str, new: impl Fn() -> B, set_sample_rate: impl Fn(&mut B, f32))
+where
+    B: VisualizerBuffer<f32>,
+{
+    for sample_rate in SAMPLE_RATES {
+        let mut buffer = new();
+        set_sample_rate(&mut buffer, sample_rate);
+        assert_enqueue_paths(name, &mut buffer);
+    }
+}
+
+#[test]
+fn peak_buffer() {
+    let configurations: [(&str, fn() -> PeakBuffer); 9] = [
+        ("PeakBuffer", || PeakBuffer::new(32, 1.0, 50.0)),
+        ("PeakBuffer (mean)", || {
+            PeakBuffer::with_aggregation(32, 1.0, 0.0, Aggregation::Mean)
+        }),
+        ("PeakBuffer (percentile)", || {
+            PeakBuffer::with_aggregation(32, 1.0, 0.0, Aggregation::AbsPercentile(90.))
+        }),
+        ("PeakBuffer (ballistics)", || {
+            PeakBuffer::new(32, 1.0, 0.0).with_ballistics(BallisticsPreset::PpmType1)
+        }),
+        ("PeakBuffer (tracking)", || {
+            PeakBuffer::new(32, 1.0, 50.0)
+                .with_transport_tracking()
+                .with_tag_tracking()
+                .with_heartbeat()
+        }),
+        ("PeakBuffer (overload)", || {
+            let mut buffer = PeakBuffer::new(32, 1.0, 50.0).with_overload_threshold(-6.0);
+            buffer.set_overload_auto_reset(Some(0.01));
+            buffer
+        }),
+        ("PeakBuffer (capture)", || {
+            let mut buffer = PeakBuffer::new(32, 1.0, 50.0).with_mode(BufferMode::Capture);
+            buffer.arm_on_threshold(0.5, 0.25);
+            buffer
+        }),
+        ("PeakBuffer (density)", || {
+            PeakBuffer::with_density(100.0, 0.37, 50.0)
+        }),
+        ("PeakBuffer (offline)", || {
+            let mut buffer = PeakBuffer::new(32, 1.0, 50.0);
+            buffer.set_offline(true);
+            buffer
+        }),
+    ];
+    for (name, new) in configurations {
+        assert_buffer(name, new, |buffer, sample_rate| {
+            buffer.set_sample_rate(sample_rate);
+            // Called once per block by plug-ins that track these
+            assert_no_alloc("PeakBuffer::set_transport_playing()", || {
+                buffer.set_transport_playing(false)
+            });
+            assert_no_alloc("PeakBuffer::set_tag()", || buffer.set_tag(3));
+        });
+    }
+
+    let mut buffer = PeakBuffer::new(32, 1.0, 50.0);
+    buffer.set_sample_rate(44100.);
+    let signal = test_signal(1, 8192);
+    assert_no_alloc("PeakBuffer::enqueue_slice()", || {
+        buffer.enqueue_slice(&signal[0])
+    });
+
+    // Gaps inside an element, across a few elements, and far longer than the buffer
+    for (name, new) in configurations {
+        let mut buffer = new();
+        buffer.set_sample_rate(44100.);
+        buffer.enqueue_slice(&signal[0]);
+        for gap in [7, 4096, 44100 * 600] {
+            assert_no_alloc(
+                format_args!("{}::catch_up() of {} samples", name, gap),
+                || buffer.catch_up(gap),
+            );
+        }
+    }
+}
+
+#[test]
+fn peak_buffer_taps() {
+    for mode in [TapMode::Samples, TapMode::Slots] {
+        let (tap, mut consumer) = BufferTap::new(256, mode);
+        let mut buffer = PeakBuffer::new(32, 1.0, 50.0);
+        buffer.set_sample_rate(1000.);
+        buffer.set_tap(Some(tap));
+        // Overflows the tap's queue
+        assert_enqueue_paths("PeakBuffer (tapped)", &mut buffer);
+        assert!(consumer.try_recv().is_some());
+    }
+}
+
+#[test]
+fn other_buffers() {
+    assert_buffer(
+        "MinimaBuffer",
+        || MinimaBuffer::new(32, 1.0, 50.0),
+        MinimaBuffer::set_sample_rate,
+    );
+    assert_buffer(
+        "WaveformBuffer",
+        || WaveformBuffer::new(32, 1.0).with_heartbeat(),
+        WaveformBuffer::set_sample_rate,
+    );
+    // More elements than samples, so that every sample fills several of them
+    assert_buffer(
+        "WaveformBuffer (raw)",
+        || WaveformBuffer::new(4096, 0.01),
+        WaveformBuffer::set_sample_rate,
+    );
+    assert_buffer(
+        "WaveformBuffer (capture)",
+        || WaveformBuffer::new(32, 1.0).with_mode(BufferMode::Capture),
+        WaveformBuffer::set_sample_rate,
+    );
+    assert_buffer(
+        "RMSBuffer",
+        || RMSBuffer::new(32, 1.0, 20.0),
+        RMSBuffer::set_sample_rate,
+    );
+    assert_buffer(
+        "BalanceBuffer",
+        || BalanceBuffer::new(32, 1.0),
+        BalanceBuffer::set_sample_rate,
+    );
+    assert_buffer(
+        "CountBuffer",
+        || CountBuffer::new(32, 1.0, 0.5),
+        CountBuffer::set_sample_rate,
+    );
+    assert_buffer(
+        "TieredBuffer",
+        || TieredBuffer::new(32, 1.0, 16, 8.0, 50.0),
+        TieredBuffer::set_sample_rate,
+    );
+    assert_buffer("RingBuffer", || RingBuffer::<f32>::new(32), |_, _| {});
+    for gate_silence in [false, true] {
+        assert_buffer(
+            "HistogramBuffer",
+            || {
+                let mut buffer = HistogramBuffer::new(64, 100.0);
+                buffer.set_silence_gating(gate_silence);
+                buffer
+            },
+            HistogramBuffer::set_sample_rate,
+        );
+    }
+}
+
+#[test]
+fn staged_buffer() {
+    let shared = Arc::new(Mutex::new(PeakBuffer::new(32, 1.0, 50.0)));
+    shared.lock().unwrap().set_sample_rate(1000.);
+    // Smaller than the largest block, so that it publishes early
+    let mut staged = StagedBuffer::new(shared.clone(), 1024);
+    staged.set_divider(2);
+
+    for samples in BLOCK_SIZES {
+        let mut signal = test_signal(2, samples);
+        with_buffer(&mut signal, |block| {
+            assert_no_alloc(
+                format_args!("StagedBuffer block of {} samples", samples),
+                || {
+                    staged.begin_block().enqueue_buffer(block, None);
+                    staged.begin_block().enqueue_buffer(block, Some(0));
+                },
+            );
+
+            // The editor is reading the buffer, so the publish after the second block
+            // is deferred
+            let guard = shared.lock().unwrap();
+            assert_no_alloc("a deferred StagedBuffer publish", || {
+                staged.begin_block().enqueue(0.5);
+                staged.begin_block().enqueue(0.5);
+            });
+            drop(guard);
+        });
+    }
+}
+
+#[test]
+fn try_lock_buffer() {
+    let shared = Arc::new(Mutex::new(PeakBuffer::new(32, 1.0, 50.0)));
+    shared.lock().unwrap().set_sample_rate(1000.);
+    // Smaller than the largest blocks, so that they're reduced to larger chunks, and
+    // older blocks are dropped
+    let mut try_lock = TryLockBuffer::new(shared.clone(), 64);
+
+    for samples in BLOCK_SIZES {
+        let mut signal = test_signal(2, samples);
+        with_buffer(&mut signal, |block| {
+            // The editor is reading the buffer, so the blocks are stashed
+            let guard = shared.lock().unwrap();
+            assert_no_alloc(
+                format_args!("a stashed TryLockBuffer block of {} samples", samples),
+                || {
+                    try_lock.enqueue_buffer_try(block, None);
+                    try_lock.enqueue_buffer_try(block, Some(0));
+                },
+            );
+            drop(guard);
+
+            assert_no_alloc(
+                format_args!("a drained TryLockBuffer block of {} samples", samples),
+                || try_lock.enqueue_buffer_try(block, None),
+            );
+        });
+    }
+}
+
+#[test]
+fn split_buffers() {
+    let mut bands = MultibandPeakBuffer::new(&[200.0, 2000.0], PeakBuffer::new(32, 1.0, 50.0));
+    bands.set_sample_rate(44100.);
+    let mut features = FeatureExtractor::new(32, 1.0, Feature::ALL);
+    features.set_sample_rate(44100.);
+
+    for samples in BLOCK_SIZES {
+        let mut signal = test_signal(2, samples);
+        with_buffer(&mut signal, |block| {
+            assert_no_alloc(
+                format_args!(
+                    "MultibandPeakBuffer::enqueue_buffer() of {} samples",
+                    samples
+                ),
+                || bands.enqueue_buffer(block, None),
+            );
+            assert_no_alloc(
+                format_args!("FeatureExtractor::enqueue_buffer() of {} samples", samples),
+                || features.enqueue_buffer(block, None),
+            );
+        });
+        assert_no_alloc("MultibandPeakBuffer::enqueue()", || bands.enqueue(0.5));
+        assert_no_alloc("FeatureExtractor::enqueue()", || features.enqueue(0.5));
+    }
+}
+
+#[test]
+fn note_events() {
+    let mut buffer = NoteEventBuffer::new(32, 1.0, 8);
+    buffer.set_sample_rate(1000.);
+    // Clones get the same room for events as the original
+    let mut clone = buffer.clone();
+
+    for buffer in [&mut buffer, &mut clone] {
+        assert_no_alloc("NoteEventBuffer", || {
+            // More events than fit, so that the oldest ones are dropped
+            for note in 0..20 {
+                buffer.enqueue_note(note as u32, 60 + note, 0.5, note % 2 == 0);
+            }
+            buffer.advance(64);
+        });
+    }
+}
+
+#[test]
+fn spectrum() {
+    let (mut input, _output) = SpectrumInput::new(2, 50.0);
+    input.update_sample_rate(44100.);
+    for samples in BLOCK_SIZES {
+        let mut signal = test_signal(2, samples);
+        with_buffer(&mut signal, |block| {
+            assert_no_alloc(
+                format_args!("SpectrumInput::compute() of {} samples", samples),
+                || input.compute(block),
+            );
+        });
+    }
+}
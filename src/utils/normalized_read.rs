@@ -0,0 +1,437 @@
+use super::{RangeSegments, ValueScaling, VisualizerBuffer};
+
+/// Reads the contents of a buffer as normalized display positions.
+///
+/// Views need to map the raw values inside a buffer to a `0..1` range before
+/// they can draw them. `NormalizedRead` does this using a range and a
+/// [`ValueScaling`], so that every view - including your own custom ones - maps
+/// values the exact same way. Values outside of the range are clamped, and
+/// non-finite values are mapped onto the range instead of producing invalid
+/// coordinates - infinities to the edge they point to, and `NaN` to the bottom
+/// edge.
+///
+/// Since clamping loses whether a value went beyond the range, the normalization
+/// can also tell which edge of the range a value lies beyond, using the value from
+/// before it was clamped; See [`off_scale()`](Self::off_scale).
+///
+/// # Example
+///
+/// ```
+/// let binding = self.buffer.get(cx);
+/// let ring_buf = binding.lock().unwrap();
+/// let values = NormalizedRead::new(&*ring_buf, (-32.0, 8.0), ValueScaling::Decibels);
+///
+/// for (i, value) in values.normalized_iter().enumerate() {
+///     // `value` is between 0 and 1
+/// }
+/// ```
+pub struct NormalizedRead<'a, B: ?Sized> {
+    buffer: &'a B,
+    range: (f32, f32),
+    scaling: ValueScaling,
+    segments: Option<&'a RangeSegments>,
+}
+
+/// The edge of the range that a value lies beyond; See
+/// [`NormalizedRead::off_scale()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OffScale {
+    /// The value lies beyond the end of the range, e.g. above 0 dBFS on a graph that
+    /// tops out at 0 dB.
+    Over,
+    /// The value lies beyond the start of the range.
+    Under,
+}
+
+// How far a value may lie beyond an edge, relative to the range, before it's off
+// the scale; This keeps a value at the edge, like a gain converted from the edge in
+// decibels, from being flagged by a rounding error
+const OFF_SCALE_TOLERANCE: f64 = 1e-5;
+
+impl<'a, B: ?Sized> NormalizedRead<'a, B> {
+    /// Constructs a new `NormalizedRead` over a buffer.
+    ///
+    /// * `buffer` - The buffer to read from; Usually, this is the guard of a locked buffer
+    /// * `range` - The minimum and maximum values that can be displayed, relative to the scaling
+    /// * `scaling` - The scaling that is used to map values into the range
+    pub fn new(buffer: &'a B, range: (f32, f32), scaling: ValueScaling) -> Self {
+        Self {
+            buffer,
+            range,
+            scaling,
+            segments: None,
+        }
+    }
+
+    /// Maps values onto a broken axis made of [`RangeSegments`] instead of the range,
+    /// if there are any.
+    ///
+    /// The range becomes the one that spans all segments.
+    pub fn with_segments(mut self, segments: Option<&'a RangeSegments>) -> Self {
+        if let Some(segments) = segments {
+            self.range = segments.range();
+        }
+        self.segments = segments;
+        self
+    }
+
+    /// Returns the segments that values are mapped onto, if the axis is broken.
+    pub fn segments(&self) -> Option<&'a RangeSegments> {
+        self.segments
+    }
+
+    /// Returns the buffer that is being read from.
+    pub fn buffer(&self) -> &'a B {
+        self.buffer
+    }
+
+    /// Returns the range that values are mapped from.
+    pub fn range(&self) -> (f32, f32) {
+        self.range
+    }
+
+    /// Returns the scaling that is used to map values.
+    pub fn scaling(&self) -> ValueScaling {
+        self.scaling
+    }
+
+    /// Normalizes a single value in the same way the buffer's values are normalized.
+    pub fn normalize_value(&self, value: f32) -> f32 {
+        match self.segments {
+            Some(segments) => segments.value_to_normalized(self.scaling, value),
+            None => self
+                .scaling
+                .value_to_normalized(value, self.range.0, self.range.1),
+        }
+    }
+
+    /// Returns the edge of the range that a value lies beyond, if any.
+    ///
+    /// The value is compared before it's clamped, so this tells apart a value at the
+    /// edge from one that went over it. A value right at the edge is on the scale,
+    /// and `NaN` is on neither side. Infinities lie beyond the edge they point to -
+    /// including silence on a [`ValueScaling::Decibels`] scale, which is -inf dB. On
+    /// a broken axis, the range spans all segments, so a value inside a break is on
+    /// the scale.
+    pub fn off_scale(&self, value: f32) -> Option<OffScale> {
+        let level = match self.scaling {
+            ValueScaling::Decibels => 20. * (value as f64).log10(),
+            _ => value as f64,
+        };
+        let (start, end) = (self.range.0 as f64, self.range.1 as f64);
+        if start == end {
+            return None;
+        }
+        // Where the value lies relative to the range, unclamped, which works for
+        // every scaling since they all map the range's edges to 0 and 1
+        let position = (level - start) / (end - start);
+        if position > 1. + OFF_SCALE_TOLERANCE {
+            Some(OffScale::Over)
+        } else if position < -OFF_SCALE_TOLERANCE {
+            Some(OffScale::Under)
+        } else {
+            None
+        }
+    }
+
+    /// Normalizes a single value like [`normalize_value()`](Self::normalize_value),
+    /// along with the edge of the range it lies beyond, if it was clamped to one.
+    pub fn normalize_flagged(&self, value: f32) -> (f32, Option<OffScale>) {
+        (self.normalize_value(value), self.off_scale(value))
+    }
+
+    /// Normalizes a single value like [`normalize_value()`](Self::normalize_value),
+    /// or returns `None` if it lies beyond the range or is `NaN`.
+    ///
+    /// Marks placed along an axis, like the labels of a unit ruler, use this to be
+    /// left out instead of piling up at an edge.
+    pub fn normalize_on_scale(&self, value: f32) -> Option<f32> {
+        (!value.is_nan() && self.off_scale(value).is_none()).then(|| self.normalize_value(value))
+    }
+
+    /// Normalizes a level that is expressed in the units of the range.
+    ///
+    /// This differs from [`normalize_value()`](Self::normalize_value) for
+    /// [`ValueScaling::Decibels`], where buffer values are gain multipliers but the
+    /// range - and thus levels such as fill anchors - is specified in decibels.
+    pub fn normalize_level(&self, level: f32) -> f32 {
+        let scaling = self.level_scaling();
+        match self.segments {
+            Some(segments) => segments.value_to_normalized(scaling, level),
+            None => scaling.value_to_normalized(level, self.range.0, self.range.1),
+        }
+    }
+
+    /// Returns the normalized bounds of the part of the axis a normalized position
+    /// lies in - the segment it's in, or nearest to, if the axis is broken, and the
+    /// entire axis otherwise.
+    pub fn segment_bounds(&self, normalized: f32) -> (f32, f32) {
+        match self.segments {
+            Some(segments) => segments.segment_bounds(self.level_scaling(), normalized),
+            None => (0., 1.),
+        }
+    }
+
+    // The scaling of levels, which are expressed in the units of the range
+    fn level_scaling(&self) -> ValueScaling {
+        match self.scaling {
+            ValueScaling::Decibels => ValueScaling::Linear,
+            scaling => scaling,
+        }
+    }
+}
+
+impl<'a, B> NormalizedRead<'a, B>
+where
+    B: VisualizerBuffer<f32, Output = f32> + ?Sized,
+{
+    /// Returns the length of the underlying buffer.
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns `true` if the underlying buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.buffer.len() == 0
+    }
+
+    /// Returns the normalized value at the given index.
+    ///
+    /// Indices that are out of range are mapped to the bottom of the range, just like
+    /// non-finite values, instead of panicking.
+    pub fn normalized(&self, index: usize) -> f32 {
+        self.get(index).unwrap_or(0.)
+    }
+
+    /// Returns the normalized value at the given index, or `None` if the index is out
+    /// of range.
+    pub fn get(&self, index: usize) -> Option<f32> {
+        self.buffer
+            .get(index)
+            .map(|value| self.normalize_value(*value))
+    }
+
+    /// Returns the normalized value at the given index, along with the edge of the
+    /// range the raw value lies beyond, or `None` if the index is out of range; See
+    /// [`off_scale()`](Self::off_scale).
+    pub fn get_flagged(&self, index: usize) -> Option<(f32, Option<OffScale>)> {
+        self.buffer
+            .get(index)
+            .map(|value| self.normalize_flagged(*value))
+    }
+
+    /// Returns an iterator over all normalized values, from the oldest to the newest.
+    pub fn normalized_iter(&self) -> impl DoubleEndedIterator<Item = f32> + ExactSizeIterator + '_ {
+        (0..self.len()).map(move |i| self.normalized(i))
+    }
+}
+
+impl<'a, B> NormalizedRead<'a, B>
+where
+    B: VisualizerBuffer<f32, Output = (f32, f32)> + ?Sized,
+{
+    /// Returns the length of the underlying buffer.
+    pub fn pair_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns the normalized pair of values at the given index.
+    ///
+    /// This is used for buffers such as the [`WaveformBuffer`](super::WaveformBuffer),
+    /// which store a minimum and a maximum for each element.
+    ///
+    /// Indices that are out of range are mapped to the bottom of the range, instead of
+    /// panicking.
+    pub fn normalized_pair(&self, index: usize) -> (f32, f32) {
+        self.get_pair(index).unwrap_or((0., 0.))
+    }
+
+    /// Returns the normalized pair of values at the given index, or `None` if the
+    /// index is out of range.
+    pub fn get_pair(&self, index: usize) -> Option<(f32, f32)> {
+        self.buffer
+            .get(index)
+            .map(|(min, max)| (self.normalize_value(*min), self.normalize_value(*max)))
+    }
+
+    /// Returns an iterator over all normalized pairs, from the oldest to the newest.
+    pub fn normalized_pair_iter(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = (f32, f32)> + ExactSizeIterator + '_ {
+        (0..self.pair_len()).map(move |i| self.normalized_pair(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NormalizedRead, OffScale};
+    use crate::utils::{PeakBuffer, RangeSegments, ValueScaling};
+
+    fn buffer_with(values: &[f32]) -> PeakBuffer {
+        let mut buffer = PeakBuffer::new(values.len(), 1.0, 50.0);
+        for (i, value) in values.iter().enumerate() {
+            buffer[i] = *value;
+        }
+        buffer
+    }
+
+    #[test]
+    fn linear() {
+        let buffer = buffer_with(&[-1.0, 0.0, 0.5, 1.0]);
+        let values = NormalizedRead::new(&buffer, (-1.0, 1.0), ValueScaling::Linear);
+
+        assert_eq!(values.len(), 4);
+        assert_eq!(
+            values.normalized_iter().collect::<Vec<_>>(),
+            vec![0.0, 0.5, 0.75, 1.0]
+        );
+    }
+
+    #[test]
+    fn on_scale() {
+        let values = NormalizedRead::new(&(), (-60., 0.), ValueScaling::Decibels);
+        assert_eq!(values.normalize_on_scale(1.0), Some(1.0));
+        assert!(values.normalize_on_scale(0.001).unwrap() < 1e-4);
+        assert_eq!(values.normalize_on_scale(2.0), None);
+        assert_eq!(values.normalize_on_scale(0.0), None);
+        assert_eq!(values.normalize_on_scale(f32::NAN), None);
+
+        let values = NormalizedRead::new(&(), (20., 20_000.), ValueScaling::Frequency);
+        assert!((values.normalize_on_scale(632.455_5).unwrap() - 0.5).abs() < 1e-4);
+        assert_eq!(values.normalize_on_scale(10.), None);
+    }
+
+    #[test]
+    fn segmented() {
+        // -80..-60 dB and -12..0 dB, with the gains of -70, -40 and -6 dB
+        let buffer = buffer_with(&[0.000_316_227_8, 0.01, 0.501_187_2]);
+        let segments = RangeSegments::new(&[(-80., -60.), (-12., 0.)]);
+        let values = NormalizedRead::new(&buffer, (-1., 1.), ValueScaling::Decibels)
+            .with_segments(Some(&segments));
+        assert_eq!(values.range(), (-80., 0.));
+
+        let normalized = values.normalized_iter().collect::<Vec<_>>();
+        let lower_end = values.normalize_level(-60.);
+        assert!((normalized[0] - lower_end / 2.).abs() < 1e-4);
+        // -40 dB lies inside the break, closer to the lower segment
+        assert_eq!(normalized[1], lower_end);
+        assert_eq!(values.segment_bounds(normalized[1]), (0., lower_end));
+        let upper_start = values.normalize_level(-12.);
+        assert!((normalized[2] - (upper_start + 1.) / 2.).abs() < 1e-4);
+        assert_eq!(values.segment_bounds(normalized[2]), (upper_start, 1.));
+
+        // Without segments, the whole axis is one part
+        let values = NormalizedRead::new(&buffer, (-80., 0.), ValueScaling::Decibels);
+        assert_eq!(values.segment_bounds(0.3), (0., 1.));
+    }
+
+    #[test]
+    fn clamping() {
+        let buffer = buffer_with(&[-10.0, 10.0, f32::NAN, f32::INFINITY, f32::NEG_INFINITY]);
+        let values = NormalizedRead::new(&buffer, (-1.0, 1.0), ValueScaling::Linear);
+
+        assert_eq!(
+            values.normalized_iter().collect::<Vec<_>>(),
+            vec![0.0, 1.0, 0.0, 1.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn off_scale() {
+        let buffer = buffer_with(&[1.0, 1.000_1, 100.0, -0.5, -3.0]);
+        let values = NormalizedRead::new(&buffer, (-1.0, 1.0), ValueScaling::Linear);
+        let flags = (0..5)
+            .map(|i| values.get_flagged(i).unwrap())
+            .collect::<Vec<_>>();
+        // Exactly at, just above, and far above the end of the range
+        assert_eq!(flags[0], (1.0, None));
+        assert_eq!(flags[1], (1.0, Some(OffScale::Over)));
+        assert_eq!(flags[2], (1.0, Some(OffScale::Over)));
+        assert_eq!(flags[3], (0.25, None));
+        assert_eq!(flags[4], (0.0, Some(OffScale::Under)));
+        assert_eq!(values.get_flagged(5), None);
+
+        assert_eq!(values.off_scale(-1.0), None);
+        assert_eq!(values.off_scale(f32::INFINITY), Some(OffScale::Over));
+        assert_eq!(values.off_scale(f32::NEG_INFINITY), Some(OffScale::Under));
+        assert_eq!(values.off_scale(f32::NAN), None);
+
+        // A degenerate range has no sides
+        let values = NormalizedRead::new(&buffer, (0.0, 0.0), ValueScaling::Linear);
+        assert_eq!(values.off_scale(1.0), None);
+    }
+
+    #[test]
+    fn off_scale_decibels() {
+        // An intersample over on a scale that tops out at 0 dB
+        let buffer = buffer_with(&[1.0, 1.12, 0.0]);
+        let values = NormalizedRead::new(&buffer, (-24.0, 0.0), ValueScaling::Decibels);
+        assert_eq!(values.get_flagged(0), Some((1.0, None)));
+        assert_eq!(values.get_flagged(1), Some((1.0, Some(OffScale::Over))));
+        // Silence lies below any scale in decibels
+        assert_eq!(values.get_flagged(2), Some((0.0, Some(OffScale::Under))));
+
+        // The gain of an edge in decibels is on the scale, despite rounding
+        let values = NormalizedRead::new(&buffer, (-24.0, -6.0), ValueScaling::Decibels);
+        assert_eq!(values.off_scale(10f32.powf(-6.0 / 20.0)), None);
+        assert_eq!(values.off_scale(10f32.powf(-24.0 / 20.0)), None);
+
+        // A value inside a break of a broken axis is on the scale
+        let segments = RangeSegments::new(&[(-80., -60.), (-12., 0.)]);
+        let values = NormalizedRead::new(&buffer, (-1., 1.), ValueScaling::Decibels)
+            .with_segments(Some(&segments));
+        assert_eq!(values.off_scale(0.01), None);
+        assert_eq!(values.off_scale(1.12), Some(OffScale::Over));
+    }
+
+    #[test]
+    fn decibels() {
+        // 1.0 is 0dB, 0.5 is roughly -6dB
+        let buffer = buffer_with(&[1.0, 0.5, 0.0, -1.0]);
+        let values = NormalizedRead::new(&buffer, (-12.0, 0.0), ValueScaling::Decibels);
+
+        assert_eq!(values.normalized(0), 1.0);
+        assert!((values.normalized(1) - 0.4983).abs() < 1e-3);
+        // Silence is -inf dB, which is clamped to the bottom of the range
+        assert_eq!(values.normalized(2), 0.0);
+        // Negative values are treated as their absolute value by the buffers, but
+        // if they do end up here, they must not produce NaN coordinates
+        assert_eq!(values.normalized(3), 0.0);
+    }
+
+    #[test]
+    fn range_boundaries() {
+        let buffer = buffer_with(&[-32.0, 8.0]);
+        let values = NormalizedRead::new(&buffer, (-32.0, 8.0), ValueScaling::Linear);
+
+        assert_eq!(values.normalized(0), 0.0);
+        assert_eq!(values.normalized(1), 1.0);
+
+        // A degenerate range must not produce NaN coordinates
+        let values = NormalizedRead::new(&buffer, (0.0, 0.0), ValueScaling::Linear);
+        assert!(values.normalized_iter().all(|v| (0.0..=1.0).contains(&v)));
+    }
+
+    #[test]
+    fn out_of_range() {
+        let buffer = buffer_with(&[1.0, 0.5]);
+        let values = NormalizedRead::new(&buffer, (0.0, 1.0), ValueScaling::Linear);
+
+        assert_eq!(values.get(1), Some(0.5));
+        assert_eq!(values.get(2), None);
+        assert_eq!(values.normalized(2), 0.0);
+    }
+
+    #[test]
+    fn levels() {
+        let buffer = buffer_with(&[1.0]);
+
+        // Levels are in the units of the range, so 0dB is near the top here
+        let values = NormalizedRead::new(&buffer, (-32.0, 8.0), ValueScaling::Decibels);
+        assert_eq!(values.normalize_level(0.0), 0.8);
+        assert_eq!(values.normalize_level(-40.0), 0.0);
+
+        let values = NormalizedRead::new(&buffer, (-1.0, 1.0), ValueScaling::Linear);
+        assert_eq!(values.normalize_level(0.0), 0.5);
+    }
+}
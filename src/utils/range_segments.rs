@@ -0,0 +1,369 @@
+use super::ValueScaling;
+
+/// The part of the axis that each break between two segments takes up.
+pub const BREAK_GAP: f32 = 0.04;
+
+/// A value axis made of several disjoint ranges, which are placed next to each other
+/// with a small break in between.
+///
+/// A limiter might want to show its input peaks around -6..0 dB along with the
+/// noise floor around -70 dB - a range of -80..0 dB wastes most of the graph on the
+/// levels in between. With the segments `[(-80., -60.), (-12., 0.)]`, the graph
+/// only shows those two ranges, and draws a marker where the axis is broken.
+///
+/// Each segment takes up a share of the axis that is proportional to its extent -
+/// in octaves for [`ValueScaling::Frequency`], and in the units of the range
+/// otherwise - and uses the scaling within itself. Every break takes up
+/// [`BREAK_GAP`] of the axis. Values that fall inside the omitted parts of the
+/// range are clamped to the nearest edge of a segment.
+///
+/// The segments are given in the units of the range, like a view's range. They
+/// are sorted, and overlapping segments are merged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeSegments {
+    segments: Vec<(f32, f32)>,
+}
+
+/// A segment, along with where it's placed on the normalized axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Placed {
+    min: f32,
+    max: f32,
+    start: f32,
+    len: f32,
+}
+
+impl Placed {
+    fn end(self: &Self) -> f32 {
+        self.start + self.len
+    }
+}
+
+impl RangeSegments {
+    /// Constructs new `RangeSegments` from the minimum and maximum of each segment.
+    ///
+    /// Segments that aren't finite, or that have no extent, are left out. Without
+    /// any segments left, this fails a debug assertion, and falls back to a single
+    /// range of `0..1` in release builds.
+    pub fn new(segments: &[(f32, f32)]) -> Self {
+        let mut sorted = segments
+            .iter()
+            .filter(|(a, b)| a.is_finite() && b.is_finite() && a != b)
+            .map(|(a, b)| (a.min(*b), a.max(*b)))
+            .collect::<Vec<_>>();
+        sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut merged: Vec<(f32, f32)> = Vec::with_capacity(sorted.len());
+        for (min, max) in sorted {
+            match merged.last_mut() {
+                Some(last) if min <= last.1 => last.1 = last.1.max(max),
+                _ => merged.push((min, max)),
+            }
+        }
+        debug_assert!(
+            !merged.is_empty(),
+            "Range segments need a segment with an extent"
+        );
+        if merged.is_empty() {
+            merged.push((0., 1.));
+        }
+        Self { segments: merged }
+    }
+
+    /// Returns the segments, from the lowest to the highest.
+    pub fn segments(self: &Self) -> &[(f32, f32)] {
+        &self.segments
+    }
+
+    /// Returns the range from the bottom of the lowest segment to the top of the
+    /// highest one.
+    pub fn range(self: &Self) -> (f32, f32) {
+        (self.segments[0].0, self.segments[self.segments.len() - 1].1)
+    }
+
+    /// Returns the edges of the segments, from the lowest to the highest.
+    pub fn edges(self: &Self) -> impl Iterator<Item = f32> + '_ {
+        self.segments.iter().flat_map(|(min, max)| [*min, *max])
+    }
+
+    /// Returns `true` if the value lies inside one of the segments, and isn't
+    /// clamped.
+    ///
+    /// Like [`value_to_normalized()`](Self::value_to_normalized), the value is a gain
+    /// for [`ValueScaling::Decibels`]. The edges are included with a small tolerance,
+    /// so that an edge that was converted to a gain and back still lies inside.
+    pub fn contains(self: &Self, scaling: ValueScaling, value: f32) -> bool {
+        const TOLERANCE: f32 = 1e-4;
+
+        let value = in_range_units(scaling, value);
+        self.segments.iter().any(|(min, max)| {
+            let tolerance = (max - min) * TOLERANCE;
+            (min - tolerance..=max + tolerance).contains(&value)
+        })
+    }
+
+    /// Maps a value onto the normalized axis, in the same way as
+    /// [`ValueScaling::value_to_normalized()`] maps it onto a single range.
+    ///
+    /// Values inside a break are clamped to the nearest edge, and non-finite values
+    /// are mapped to the bottom of the axis.
+    pub fn value_to_normalized(self: &Self, scaling: ValueScaling, value: f32) -> f32 {
+        let units = in_range_units(scaling, value);
+        if units.is_nan() {
+            return 0.;
+        }
+
+        let mut previous: Option<Placed> = None;
+        for segment in self.layout(scaling) {
+            if units < segment.min {
+                return match previous {
+                    // Inside the break, closer to the segment below
+                    Some(below) if units - below.max <= segment.min - units => below.end(),
+                    _ => segment.start,
+                };
+            }
+            if units <= segment.max {
+                let within = scaling.value_to_normalized(value, segment.min, segment.max);
+                return segment.start + segment.len * within;
+            }
+            previous = Some(segment);
+        }
+        1.
+    }
+
+    /// Maps a normalized position back to the value it displays, e.g. to inspect the
+    /// value below the cursor; The inverse of
+    /// [`value_to_normalized()`](Self::value_to_normalized).
+    ///
+    /// Positions inside a break are mapped to the nearest edge.
+    pub fn normalized_to_value(self: &Self, scaling: ValueScaling, normalized: f32) -> f32 {
+        let mut previous: Option<Placed> = None;
+        for segment in self.layout(scaling) {
+            if normalized < segment.start {
+                return match previous {
+                    Some(below) if normalized - below.end() <= segment.start - normalized => {
+                        below.max
+                    }
+                    _ => segment.min,
+                };
+            }
+            if normalized <= segment.end() {
+                let within = match segment.len > 0. {
+                    true => (normalized - segment.start) / segment.len,
+                    false => 0.,
+                };
+                return scaling.normalized_to_value(within, segment.min, segment.max);
+            }
+            previous = Some(segment);
+        }
+        self.range().1
+    }
+
+    /// Returns the normalized bounds of the segment that contains the normalized
+    /// position, or of the nearest segment if it lies inside a break.
+    ///
+    /// Views use this to keep a fill inside the segment of its anchor.
+    pub fn segment_bounds(self: &Self, scaling: ValueScaling, normalized: f32) -> (f32, f32) {
+        let mut previous: Option<Placed> = None;
+        for segment in self.layout(scaling) {
+            if normalized <= segment.end() {
+                let nearest = match previous {
+                    Some(below) if normalized - below.end() < segment.start - normalized => below,
+                    _ => segment,
+                };
+                return (nearest.start, nearest.end());
+            }
+            previous = Some(segment);
+        }
+        previous.map_or((0., 1.), |last| (last.start, last.end()))
+    }
+
+    /// Returns the normalized start and end of each break, from the lowest to the
+    /// highest.
+    pub fn breaks(self: &Self, scaling: ValueScaling) -> impl Iterator<Item = (f32, f32)> + '_ {
+        let starts = self.layout(scaling).map(|segment| segment.start).skip(1);
+        self.layout(scaling)
+            .map(|segment| segment.end())
+            .zip(starts)
+    }
+
+    /// Places each segment on the normalized axis.
+    fn layout(self: &Self, scaling: ValueScaling) -> impl Iterator<Item = Placed> + '_ {
+        let breaks = (self.segments.len() - 1) as f32;
+        // Leaves at least half of the axis to the segments
+        let gap = BREAK_GAP.min(0.5 / breaks.max(1.));
+        let available = 1. - gap * breaks;
+
+        let total = self
+            .segments
+            .iter()
+            .map(|segment| weight(scaling, *segment))
+            .sum::<f32>();
+        let share = move |segment: (f32, f32)| match total > 0. {
+            true => weight(scaling, segment) / total,
+            false => 1. / self.segments.len() as f32,
+        };
+
+        let mut start = 0.;
+        self.segments.iter().map(move |(min, max)| {
+            let len = available * share((*min, *max));
+            let placed = Placed {
+                min: *min,
+                max: *max,
+                start,
+                len,
+            };
+            start += len + gap;
+            placed
+        })
+    }
+}
+
+/// Converts a value into the units of the range, which are decibels for a gain.
+fn in_range_units(scaling: ValueScaling, value: f32) -> f32 {
+    match scaling {
+        ValueScaling::Decibels => {
+            const CONVERSION_FACTOR: f32 = std::f32::consts::LOG10_E * 20.0;
+            value.ln() * CONVERSION_FACTOR
+        }
+        _ => value,
+    }
+}
+
+/// Returns how much of the axis a segment takes up, relative to the others.
+fn weight(scaling: ValueScaling, (min, max): (f32, f32)) -> f32 {
+    let weight = match scaling {
+        ValueScaling::Frequency => (max / min).log2(),
+        _ => max - min,
+    };
+    if weight.is_finite() && weight > 0. {
+        weight
+    } else {
+        0.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RangeSegments, BREAK_GAP};
+    use crate::utils::ValueScaling;
+    use nih_plug::util::db_to_gain;
+
+    fn limiter() -> RangeSegments {
+        RangeSegments::new(&[(-12., 0.), (-80., -60.)])
+    }
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn sorted_and_merged() {
+        assert_eq!(limiter().segments(), &[(-80., -60.), (-12., 0.)]);
+        assert_eq!(limiter().range(), (-80., 0.));
+        assert_eq!(
+            limiter().edges().collect::<Vec<_>>(),
+            vec![-80., -60., -12., 0.]
+        );
+
+        let merged = RangeSegments::new(&[(0., 2.), (1., 3.), (5., 4.), (f32::NAN, 1.)]);
+        assert_eq!(merged.segments(), &[(0., 3.), (4., 5.)]);
+    }
+
+    #[test]
+    fn piecewise_mapping() {
+        let segments = limiter();
+        let linear = ValueScaling::Linear;
+        // 20 and 12 dB share the rest of the axis after the break
+        let lower = (1. - BREAK_GAP) * 20. / 32.;
+        let upper_start = lower + BREAK_GAP;
+
+        assert_eq!(segments.value_to_normalized(linear, -80.), 0.);
+        assert_near(segments.value_to_normalized(linear, -70.), lower / 2.);
+        assert_near(segments.value_to_normalized(linear, -60.), lower);
+        assert_near(segments.value_to_normalized(linear, -12.), upper_start);
+        assert_near(
+            segments.value_to_normalized(linear, -6.),
+            (upper_start + 1.) / 2.,
+        );
+        assert_eq!(segments.value_to_normalized(linear, 0.), 1.);
+
+        // Inside the break, values clamp to the nearest edge
+        assert_near(segments.value_to_normalized(linear, -50.), lower);
+        assert_near(segments.value_to_normalized(linear, -20.), upper_start);
+        // Outside the range, and non-finite values
+        assert_eq!(segments.value_to_normalized(linear, -100.), 0.);
+        assert_eq!(segments.value_to_normalized(linear, 6.), 1.);
+        assert_eq!(segments.value_to_normalized(linear, f32::NAN), 0.);
+
+        // Gains are mapped in decibels
+        let decibels = ValueScaling::Decibels;
+        assert_near(
+            segments.value_to_normalized(decibels, db_to_gain(-70.)),
+            lower / 2.,
+        );
+        assert_near(
+            segments.value_to_normalized(decibels, db_to_gain(-40.)),
+            lower,
+        );
+        assert_eq!(segments.value_to_normalized(decibels, 0.), 0.);
+        assert!(segments.contains(decibels, db_to_gain(-6.)));
+        assert!(!segments.contains(decibels, db_to_gain(-40.)));
+    }
+
+    #[test]
+    fn inverse_mapping() {
+        let segments = limiter();
+        let linear = ValueScaling::Linear;
+
+        // Round trips on both sides of each edge
+        for value in [-80., -79.9, -70., -60.1, -60., -12., -11.9, -6., -0.1, 0.] {
+            let normalized = segments.value_to_normalized(linear, value);
+            assert_near(segments.normalized_to_value(linear, normalized), value);
+        }
+
+        // Positions inside the break are mapped to the nearest edge
+        let (start, end) = segments.breaks(linear).next().unwrap();
+        assert_near(end - start, BREAK_GAP);
+        assert_eq!(segments.normalized_to_value(linear, start + 0.01), -60.);
+        assert_eq!(segments.normalized_to_value(linear, end - 0.01), -12.);
+        assert_eq!(segments.normalized_to_value(linear, -1.), -80.);
+        assert_eq!(segments.normalized_to_value(linear, 2.), 0.);
+
+        // A frequency axis is split by octaves, and mapped logarithmically inside
+        let bands = RangeSegments::new(&[(20., 80.), (1000., 4000.)]);
+        let frequency = ValueScaling::Frequency;
+        assert_near(
+            bands.value_to_normalized(frequency, 40.),
+            (1. - BREAK_GAP) / 4.,
+        );
+        for value in [20., 40., 80., 1000., 2000., 4000.] {
+            let normalized = bands.value_to_normalized(frequency, value);
+            assert!((bands.normalized_to_value(frequency, normalized) - value).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn segment_bounds() {
+        let segments = limiter();
+        let linear = ValueScaling::Linear;
+        let (start, end) = segments.breaks(linear).next().unwrap();
+
+        assert_eq!(segments.segment_bounds(linear, 0.), (0., start));
+        assert_eq!(segments.segment_bounds(linear, start), (0., start));
+        assert_eq!(segments.segment_bounds(linear, start + 0.01), (0., start));
+        assert_eq!(segments.segment_bounds(linear, end - 0.01), (end, 1.));
+        assert_eq!(segments.segment_bounds(linear, 1.), (end, 1.));
+
+        // A single segment spans the whole axis
+        let single = RangeSegments::new(&[(-60., 0.)]);
+        assert_eq!(single.breaks(linear).count(), 0);
+        assert_eq!(single.segment_bounds(linear, 0.5), (0., 1.));
+        assert_near(single.value_to_normalized(linear, -30.), 0.5);
+    }
+}
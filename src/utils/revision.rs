@@ -0,0 +1,263 @@
+use nih_plug_vizia::vizia::binding::{Data, Lens, LensExt, Map};
+
+use super::{SharedRead, VisualizerBuffer};
+
+/// A shared buffer, along with the [generation](VisualizerBuffer::generation) it
+/// was at when it was read.
+///
+/// VIZIA compares the data behind a lens to decide whether bindings need to be
+/// rebuilt. A shared buffer always compares equal to itself, since it's the same
+/// `Arc` from one frame to the next - so VIZIA never notices that its contents
+/// changed. A `BufferRevision` compares the generations instead, so bindings to it
+/// are rebuilt exactly when the buffer was written to, and never while it's
+/// unchanged. Use [`revision()`](VisualizerLens::revision) to get a lens to it.
+///
+/// The buffer can be shared in any way that implements [`SharedRead`]. Buffers
+/// that don't keep track of their generation are never treated as changed, as
+/// long as the revision refers to the same buffer; All of the crate's buffers keep
+/// track of it.
+///
+/// The graph, graph group, split graph, meter, sparkline, oscilloscope, histogram
+/// and a scrolling grid bind to the revision of their buffers, and redraw when it
+/// changes. The waveform, lissajous, activity strip and spectrum analyzer don't:
+/// Their data - a `Vec<f32>`, stereo pairs, note events and a triple-buffered
+/// spectrum - isn't a [`VisualizerBuffer<f32>`](VisualizerBuffer) that keeps a
+/// generation, so they're redrawn along with the rest of the editor.
+pub struct BufferRevision<S> {
+    buffer: S,
+    // The address of the shared buffer, which tells revisions of different buffers
+    // apart
+    address: usize,
+    generation: Option<u64>,
+}
+
+// Deriving `Clone` would require the handle's value to be `Clone`
+impl<S: Clone> Clone for BufferRevision<S> {
+    fn clone(&self) -> Self {
+        Self {
+            buffer: self.buffer.clone(),
+            address: self.address,
+            generation: self.generation,
+        }
+    }
+}
+
+impl<S> BufferRevision<S>
+where
+    S: SharedRead,
+    S::Value: VisualizerBuffer<f32>,
+{
+    /// Reads the current generation of a shared buffer.
+    ///
+    /// Buffers that derive their contents from others are
+    /// [refreshed](VisualizerBuffer::refresh) first, if the handle allows it, so
+    /// that the generation is the one of their current contents.
+    pub fn new(buffer: &S) -> Self {
+        let (address, generation) = {
+            let guard = buffer.read_shared(|buffer| buffer.refresh());
+            (
+                &*guard as *const S::Value as *const () as usize,
+                guard.generation(),
+            )
+        };
+        Self {
+            buffer: buffer.clone(),
+            address,
+            generation,
+        }
+    }
+}
+
+impl<S> BufferRevision<S> {
+    /// Returns the shared buffer.
+    pub fn buffer(self: &Self) -> &S {
+        &self.buffer
+    }
+
+    /// Returns the generation the buffer was at when it was read, or `None` if it
+    /// doesn't keep track of it.
+    pub fn generation(self: &Self) -> Option<u64> {
+        self.generation
+    }
+}
+
+impl<S: Clone + 'static> Data for BufferRevision<S> {
+    fn same(&self, other: &Self) -> bool {
+        self.address == other.address && self.generation == other.generation
+    }
+}
+
+/// Turns a lens to a shared buffer into a lens to its [`BufferRevision`].
+///
+/// ```
+/// Binding::new(cx, Data::peak_buffer.revision(), |cx, revision| {
+///     // Rebuilt whenever the peak buffer is written to
+///     let newest = revision.get(cx).buffer().lock().unwrap().get_newest(0).copied();
+///     Label::new(cx, &format!("{:.1} dB", util::gain_to_db(newest.unwrap_or(0.0))));
+/// });
+/// ```
+///
+/// Implemented for every lens whose target is a [`SharedRead`] handle to a
+/// [`VisualizerBuffer`].
+pub trait VisualizerLens: Lens {
+    /// Returns a lens to the buffer's [`BufferRevision`].
+    fn revision(self) -> Map<Self, BufferRevision<Self::Target>>;
+}
+
+impl<L> VisualizerLens for L
+where
+    L: Lens,
+    L::Target: SharedRead + 'static,
+    <L::Target as SharedRead>::Value: VisualizerBuffer<f32>,
+{
+    fn revision(self) -> Map<Self, BufferRevision<Self::Target>> {
+        self.map(BufferRevision::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BufferRevision;
+    use crate::utils::{
+        HistogramBuffer, PeakBuffer, RingBuffer, SharedRead, VisualizerBuffer, WaveformBuffer,
+    };
+    use nih_plug_vizia::vizia::binding::Data;
+    use std::ops::{Index, IndexMut};
+    use std::sync::{Arc, Mutex, RwLock};
+
+    // Reads the buffer's revision once per frame, and counts the frames in which a
+    // binding to it would have been rebuilt, just like VIZIA compares it
+    fn rebuilds<S>(buffer: &S, frames: usize, mut write: impl FnMut(usize, &S)) -> usize
+    where
+        S: SharedRead + 'static,
+        S::Value: VisualizerBuffer<f32>,
+    {
+        let mut last = BufferRevision::new(buffer);
+        let mut rebuilds = 0;
+        for frame in 0..frames {
+            write(frame, buffer);
+            let revision = BufferRevision::new(buffer);
+            if !revision.same(&last) {
+                rebuilds += 1;
+            }
+            last = revision;
+        }
+        rebuilds
+    }
+
+    // Enough samples to finish at least one element
+    fn write(buffer: &Arc<Mutex<PeakBuffer>>) {
+        let mut buffer = buffer.lock().unwrap();
+        buffer.enqueue(0.5);
+        buffer.enqueue(0.5);
+    }
+
+    fn peak_buffer() -> Arc<Mutex<PeakBuffer>> {
+        let mut buffer = PeakBuffer::new(16, 1.0, 0.0);
+        buffer.set_sample_rate(16.);
+        Arc::new(Mutex::new(buffer))
+    }
+
+    #[test]
+    fn rebuilds_only_when_written() {
+        let buffer = peak_buffer();
+
+        // Audio that's running writes every frame
+        let running = rebuilds(&buffer, 100, |_, buffer| write(buffer));
+        assert_eq!(running, 100);
+
+        // Paused audio doesn't write at all
+        assert_eq!(rebuilds(&buffer, 100, |_, _| {}), 0);
+
+        // Audio that only writes every fourth frame
+        let sparse = rebuilds(&buffer, 100, |frame, buffer| {
+            if frame % 4 == 0 {
+                write(buffer);
+            }
+        });
+        assert_eq!(sparse, 25);
+    }
+
+    #[test]
+    fn different_buffers() {
+        let revision = BufferRevision::new(&peak_buffer());
+        assert!(revision.same(&revision.clone()));
+        assert!(!revision.same(&BufferRevision::new(&peak_buffer())));
+    }
+
+    #[test]
+    fn waveforms_and_histograms() {
+        let mut waveform = WaveformBuffer::new(16, 1.0);
+        waveform.set_sample_rate(16.);
+        let waveform = Arc::new(RwLock::new(waveform));
+        assert_eq!(rebuilds(&waveform, 10, |_, _| {}), 0);
+        let written = rebuilds(&waveform, 10, |_, waveform| {
+            let mut waveform = waveform.write().unwrap();
+            waveform.enqueue(0.5);
+            waveform.enqueue(-0.5);
+        });
+        assert_eq!(written, 10);
+
+        let histogram = Arc::new(Mutex::new(HistogramBuffer::new(16, 1.0)));
+        assert_eq!(rebuilds(&histogram, 10, |_, _| {}), 0);
+        let written = rebuilds(&histogram, 10, |_, histogram| {
+            histogram.lock().unwrap().enqueue(0.5)
+        });
+        assert_eq!(written, 10);
+    }
+
+    // A buffer that doesn't keep track of its generation
+    struct Untracked(RingBuffer<f32>);
+
+    impl Index<usize> for Untracked {
+        type Output = f32;
+
+        fn index(&self, index: usize) -> &f32 {
+            &self.0[index]
+        }
+    }
+    impl IndexMut<usize> for Untracked {
+        fn index_mut(&mut self, index: usize) -> &mut f32 {
+            &mut self.0[index]
+        }
+    }
+
+    impl VisualizerBuffer<f32> for Untracked {
+        fn enqueue(self: &mut Self, value: f32) {
+            self.0.enqueue(value);
+        }
+        fn enqueue_buffer(
+            self: &mut Self,
+            buffer: &mut nih_plug::buffer::Buffer,
+            channel: Option<usize>,
+        ) {
+            self.0.enqueue_buffer(buffer, channel);
+        }
+        fn clear(self: &mut Self) {
+            self.0.clear();
+        }
+        fn grow(self: &mut Self, size: usize) {
+            self.0.grow(size);
+        }
+        fn shrink(self: &mut Self, size: usize) {
+            self.0.shrink(size);
+        }
+        fn len(self: &Self) -> usize {
+            self.0.len()
+        }
+    }
+
+    #[test]
+    fn without_generation() {
+        // Revisions of the same buffer are the same, even though its changes can't
+        // be told apart
+        let buffer = Arc::new(Mutex::new(Untracked(RingBuffer::new(16))));
+        let revision = BufferRevision::new(&buffer);
+        assert_eq!(revision.generation(), None);
+        assert!(revision.same(&revision.clone()));
+        assert_eq!(rebuilds(&buffer, 10, |_, _| {}), 0);
+
+        let other = Arc::new(Mutex::new(Untracked(RingBuffer::new(16))));
+        assert!(!revision.same(&BufferRevision::new(&other)));
+    }
+}
@@ -0,0 +1,129 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// A range that is written on one thread and displayed by views on another, without
+/// a lock.
+///
+/// When the displayed range is computed on the audio thread - e.g. an auto-range in
+/// your DSP, or a range that follows a smoothed parameter - sharing it as an
+/// `Arc<Mutex<(f32, f32)>>` would make the audio thread wait for the editor. A
+/// `SharedRange` stores both ends of the range in a single atomic instead, so that
+/// writing is a single store, and reading is a single load. Since both ends are
+/// written at once, a reader never sees the minimum of one write together with the
+/// maximum of another.
+///
+/// The handle is cheap to clone, and all clones share the same range. Views follow
+/// it using [`follow_shared_range()`](crate::visualizers::AutoRangeModifiers::follow_shared_range),
+/// or [`UnitRuler::follow_shared()`](crate::visualizers::UnitRuler::follow_shared).
+///
+/// ```
+/// let range = SharedRange::new((-32.0, 8.0));
+///
+/// // Inside process()
+/// self.range.set((self.floor.next(), 8.0));
+///
+/// // Inside the editor
+/// Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+///     .follow_shared_range(range.clone());
+/// ```
+#[derive(Clone)]
+pub struct SharedRange {
+    // The minimum's bits in the upper half, and the maximum's bits in the lower half
+    range: Arc<AtomicU64>,
+}
+
+impl SharedRange {
+    /// Constructs a new `SharedRange` holding the given range.
+    pub fn new(range: (f32, f32)) -> Self {
+        Self {
+            range: Arc::new(AtomicU64::new(pack(range))),
+        }
+    }
+
+    /// Replaces the range.
+    ///
+    /// This never locks or allocates, so it can be called from the audio thread.
+    pub fn set(self: &Self, range: (f32, f32)) {
+        self.range.store(pack(range), Ordering::Release);
+    }
+
+    /// Returns the range that was set last.
+    pub fn get(self: &Self) -> (f32, f32) {
+        unpack(self.range.load(Ordering::Acquire))
+    }
+}
+
+impl Default for SharedRange {
+    fn default() -> Self {
+        Self::new((0., 1.))
+    }
+}
+
+impl std::fmt::Debug for SharedRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SharedRange").field(&self.get()).finish()
+    }
+}
+
+fn pack((min, max): (f32, f32)) -> u64 {
+    ((min.to_bits() as u64) << 32) | max.to_bits() as u64
+}
+
+fn unpack(range: u64) -> (f32, f32) {
+    (
+        f32::from_bits((range >> 32) as u32),
+        f32::from_bits(range as u32),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SharedRange;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn round_trip() {
+        let range = SharedRange::new((-32., 8.));
+        assert_eq!(range.get(), (-32., 8.));
+
+        range.set((f32::NEG_INFINITY, -0.));
+        let (min, max) = range.clone().get();
+        assert_eq!(min, f32::NEG_INFINITY);
+        assert!(max == 0. && max.is_sign_negative());
+    }
+
+    #[test]
+    fn never_torn() {
+        // Every write keeps the maximum at the minimum plus 40, so a pair made of two
+        // different writes would have a different span
+        let range = SharedRange::new((0., 40.));
+        let done = Arc::new(AtomicBool::new(false));
+
+        let readers: Vec<_> = (0..3)
+            .map(|_| {
+                let (range, done) = (range.clone(), done.clone());
+                std::thread::spawn(move || {
+                    // Reads at least once, even if the writes are done already
+                    loop {
+                        let (min, max) = range.get();
+                        assert_eq!(max - min, 40., "torn range ({}, {})", min, max);
+                        if done.load(Ordering::Relaxed) {
+                            break;
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for i in 0..200_000 {
+            let min = -((i % 1000) as f32);
+            range.set((min, min + 40.));
+        }
+        done.store(true, Ordering::Relaxed);
+
+        for reader in readers {
+            reader.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::ops::Deref;
+use std::sync::{
+    Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, TryLockError, TryLockResult,
+};
+
+/// A handle to a value that is written on one thread and read by views on another,
+/// e.g. an `Arc<Mutex<PeakBuffer>>`.
+///
+/// Views that display a buffer read it through this trait, so they don't depend on
+/// how it's shared. A buffer can be shared in two ways out of the box:
+///
+/// * `Arc<Mutex<B>>` - The default. Every view that draws the buffer locks it, so
+///   views that draw the same buffer wait for each other.
+/// * `Arc<RwLock<B>>` - Views only take the read lock, so any number of them can
+///   read the buffer at once. The audio thread takes the write lock for as long as
+///   it enqueues a block. Use this when several views - e.g. a graph, a meter and a
+///   sparkline - draw the same buffer.
+///
+/// A lock that was poisoned by a panic on another thread is recovered, so that the
+/// editor keeps drawing the last contents instead of panicking as well.
+///
+/// ```
+/// // Inside the plug-in
+/// peak_buffer: Arc::new(RwLock::new(PeakBuffer::new(800, 10.0, 50.0))),
+///
+/// // Inside process()
+/// self.peak_buffer.write().unwrap().enqueue_buffer(buffer, None);
+///
+/// // Inside the editor, all four views read the buffer at the same time
+/// Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels);
+/// Meter::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels, Orientation::Vertical);
+/// Sparkline::new(cx, Data::peak_buffer);
+/// ```
+///
+/// # Refreshing
+///
+/// Views [refresh](super::VisualizerBuffer::refresh) a buffer before reading it,
+/// which needs exclusive access. Behind a mutex, the buffer is refreshed while it's
+/// locked anyway; Behind an `RwLock`, it isn't refreshed at all, as that would take
+/// the write lock on every frame. Buffers that are fed audio don't need refreshing,
+/// but buffers that derive their contents from others, like the
+/// [`DerivedBuffer`](super::DerivedBuffer), need to be shared behind a mutex.
+///
+/// # Implementing
+///
+/// Other handles - e.g. one that reads a buffer without any lock - implement this by
+/// returning a guard that dereferences to the value. Whether `prepare` is called is
+/// up to the handle; It should be called whenever the handle has exclusive access
+/// to the value.
+pub trait SharedRead: Clone {
+    /// The value that is shared, usually a buffer.
+    type Value: ?Sized;
+    /// Grants access to the value for as long as it's alive.
+    type Guard<'a>: Deref<Target = Self::Value>
+    where
+        Self: 'a;
+
+    /// Returns the value for reading, after calling `prepare` on it if the handle has
+    /// exclusive access to it.
+    fn read_shared(self: &Self, prepare: impl FnOnce(&mut Self::Value)) -> Self::Guard<'_>;
+}
+
+impl<V: ?Sized> SharedRead for Arc<Mutex<V>> {
+    type Value = V;
+    type Guard<'a>
+        = MutexGuard<'a, V>
+    where
+        V: 'a;
+
+    fn read_shared(self: &Self, prepare: impl FnOnce(&mut V)) -> MutexGuard<'_, V> {
+        let mut guard = self.lock().unwrap_or_else(PoisonError::into_inner);
+        prepare(&mut guard);
+        guard
+    }
+}
+
+impl<V: ?Sized> SharedRead for Arc<RwLock<V>> {
+    type Value = V;
+    type Guard<'a>
+        = RwLockReadGuard<'a, V>
+    where
+        V: 'a;
+
+    fn read_shared(self: &Self, _prepare: impl FnOnce(&mut V)) -> RwLockReadGuard<'_, V> {
+        self.read().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A [`SharedRead`] handle that can also be written through, e.g. by the audio
+/// thread, or by a view that changes a buffer's settings in response to input.
+///
+/// Implemented for `Arc<Mutex<B>>` and `Arc<RwLock<B>>`, where writing takes the
+/// mutex or the write lock. Like reading, writing recovers a poisoned lock.
+pub trait SharedWrite: SharedRead {
+    /// Writes to the value, waiting for exclusive access to it.
+    fn write_shared<R>(self: &Self, write: impl FnOnce(&mut Self::Value) -> R) -> R;
+
+    /// Writes to the value if it isn't being accessed right now, and returns `None`
+    /// without waiting otherwise.
+    fn try_write_shared<R>(self: &Self, write: impl FnOnce(&mut Self::Value) -> R) -> Option<R>;
+}
+
+impl<V: ?Sized> SharedWrite for Arc<Mutex<V>> {
+    fn write_shared<R>(self: &Self, write: impl FnOnce(&mut V) -> R) -> R {
+        write(&mut self.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    fn try_write_shared<R>(self: &Self, write: impl FnOnce(&mut V) -> R) -> Option<R> {
+        recovered(self.try_lock()).map(|mut guard| write(&mut guard))
+    }
+}
+
+impl<V: ?Sized> SharedWrite for Arc<RwLock<V>> {
+    fn write_shared<R>(self: &Self, write: impl FnOnce(&mut V) -> R) -> R {
+        write(&mut self.write().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    fn try_write_shared<R>(self: &Self, write: impl FnOnce(&mut V) -> R) -> Option<R> {
+        recovered(self.try_write()).map(|mut guard| write(&mut guard))
+    }
+}
+
+// The guard of a lock that was taken, even if it was poisoned
+fn recovered<G>(result: TryLockResult<G>) -> Option<G> {
+    match result {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SharedRead, SharedWrite};
+    use std::sync::{Arc, Mutex, RwLock};
+    use std::thread;
+
+    #[test]
+    fn prepares_with_exclusive_access() {
+        let mutex = Arc::new(Mutex::new(vec![1]));
+        assert_eq!(*mutex.read_shared(|values| values.push(2)), vec![1, 2]);
+
+        // Readers of an RwLock don't get to change the value
+        let rw_lock = Arc::new(RwLock::new(vec![1]));
+        assert_eq!(*rw_lock.read_shared(|values| values.push(2)), vec![1]);
+    }
+
+    #[test]
+    fn concurrent_readers() {
+        let rw_lock = Arc::new(RwLock::new(0.5));
+        let first = rw_lock.read_shared(|_| {});
+        let second = rw_lock.read_shared(|_| {});
+        assert_eq!(*first + *second, 1.0);
+
+        // The writer waits for both of them
+        assert!(rw_lock.try_write().is_err());
+        drop((first, second));
+        *rw_lock.write().unwrap() = 0.25;
+        assert_eq!(*rw_lock.read_shared(|_| {}), 0.25);
+    }
+
+    #[test]
+    fn writes_without_waiting() {
+        let mutex = Arc::new(Mutex::new(1));
+        let rw_lock = Arc::new(RwLock::new(1));
+        assert_eq!(mutex.try_write_shared(|value| *value += 1), Some(()));
+        assert_eq!(rw_lock.write_shared(|value| *value + 1), 2);
+
+        // A reader holds the value, so the writer doesn't get to it
+        {
+            let _reader = mutex.read_shared(|_| {});
+            assert_eq!(mutex.try_write_shared(|value| *value += 1), None);
+        }
+        {
+            let _reader = rw_lock.read_shared(|_| {});
+            assert_eq!(rw_lock.try_write_shared(|value| *value += 1), None);
+        }
+        assert_eq!(*mutex.read_shared(|_| {}), 2);
+        assert_eq!(*rw_lock.read_shared(|_| {}), 1);
+    }
+
+    #[test]
+    fn recovers_from_poisoning() {
+        let mutex = Arc::new(Mutex::new(1));
+        let rw_lock = Arc::new(RwLock::new(1));
+        {
+            let (mutex, rw_lock) = (mutex.clone(), rw_lock.clone());
+            let writer = thread::spawn(move || {
+                let _mutex = mutex.lock().unwrap();
+                let mut value = rw_lock.write().unwrap();
+                *value = 2;
+                panic!("the writer panics while holding both locks");
+            });
+            assert!(writer.join().is_err());
+        }
+        assert!(mutex.is_poisoned() && rw_lock.is_poisoned());
+
+        assert_eq!(*mutex.read_shared(|_| {}), 1);
+        assert_eq!(*rw_lock.read_shared(|_| {}), 2);
+        assert_eq!(mutex.try_write_shared(|value| *value), Some(1));
+        assert_eq!(rw_lock.write_shared(|value| *value), 2);
+    }
+}
@@ -0,0 +1,355 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Receives the elements a buffer finalizes, for renderers that draw the buffer's
+/// data without any of Cyma's views.
+///
+/// Obtain a receiver from [`PeakBuffer::subscribe()`](super::PeakBuffer::subscribe).
+/// Each finalized element arrives as a [`SlotMessage`], which carries its value along
+/// with a sequence number that increases by one per element, so the number of an
+/// element tells where it lies relative to the others.
+///
+/// All receivers of a buffer read from the same queue, which holds the newest 1024
+/// elements. Finalizing an element only writes to it - it never allocates, locks or
+/// waits for a receiver. A receiver that falls behind by more than the queue holds
+/// misses the oldest elements, which [`try_recv()`](Self::try_recv) reports as
+/// [`SlotRecvError::Lagged`] before it continues with the oldest element that is
+/// still queued.
+///
+/// ```
+/// let mut receiver = peak_buffer.lock().unwrap().subscribe();
+///
+/// std::thread::spawn(move || loop {
+///     match receiver.recv_timeout(Duration::from_millis(100)) {
+///         Ok(SlotMessage { sequence, value }) => { /* upload to the GPU */ }
+///         Err(SlotRecvError::Lagged(missed)) => { /* redraw from scratch */ }
+///         Err(SlotRecvError::Empty) => continue,
+///         // The buffer was dropped
+///         Err(SlotRecvError::Disconnected) => break,
+///     }
+/// });
+/// ```
+pub struct SlotReceiver {
+    queue: Arc<SlotQueue>,
+    // The sequence number of the next element to receive
+    next: u64,
+}
+
+/// An element that a buffer finalized.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlotMessage {
+    /// The number of the element, counting all elements the buffer finalized.
+    pub sequence: u64,
+    /// The value of the element.
+    pub value: f32,
+}
+
+/// The reasons why a [`SlotReceiver`] didn't receive an element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotRecvError {
+    /// No element was finalized since the last one was received.
+    Empty,
+    /// The receiver fell behind, and this number of elements were overwritten
+    /// before they could be received.
+    Lagged(u64),
+    /// The buffer was dropped, and all of its elements were received.
+    Disconnected,
+}
+
+impl fmt::Display for SlotRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SlotRecvError::Empty => write!(f, "no element was finalized"),
+            SlotRecvError::Lagged(missed) => write!(f, "receiver lagged by {} elements", missed),
+            SlotRecvError::Disconnected => write!(f, "the buffer was dropped"),
+        }
+    }
+}
+
+impl std::error::Error for SlotRecvError {}
+
+// The number of elements the queue holds before the oldest ones are overwritten
+const CAPACITY: usize = 1024;
+
+// How long a receiver sleeps in between checks while it waits for an element
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+// Like the queue of a `BufferTap`, each slot holds the value's bits along with the
+// lower 32 bits of its sequence number, so that a receiver can tell whether a slot
+// was written yet, or already overwritten
+struct SlotQueue {
+    slots: Box<[AtomicU64]>,
+    // The sequence number of the next element
+    written: AtomicU64,
+    // Cleared once the buffer is dropped
+    connected: AtomicBool,
+}
+
+impl SlotQueue {
+    fn new(start: u64) -> Self {
+        Self {
+            // Every slot starts out behind the first element, so no slot counts as
+            // written yet
+            slots: (0..CAPACITY)
+                .map(|_| AtomicU64::new(pack(start.wrapping_sub(1), 0.)))
+                .collect(),
+            written: AtomicU64::new(start),
+            connected: AtomicBool::new(true),
+        }
+    }
+}
+
+/// Publishes the elements a buffer finalizes to its [`SlotReceiver`]s.
+///
+/// The queue is only allocated once the first receiver subscribes. Since receivers
+/// subscribe to a particular buffer, cloning a buffer doesn't clone its queue.
+#[derive(Default)]
+pub(crate) struct SlotPublisher {
+    queue: OnceLock<Arc<SlotQueue>>,
+    // The sequence number of the next element, counted whether or not anyone
+    // subscribed
+    sequence: u64,
+}
+
+impl SlotPublisher {
+    pub(crate) fn subscribe(self: &Self) -> SlotReceiver {
+        let queue = self
+            .queue
+            .get_or_init(|| Arc::new(SlotQueue::new(self.sequence)))
+            .clone();
+        let next = queue.written.load(Ordering::Acquire);
+        SlotReceiver { queue, next }
+    }
+
+    /// Publishes an element that was just finalized.
+    pub(crate) fn publish(self: &mut Self, value: f32) {
+        let sequence = self.sequence;
+        self.sequence += 1;
+        if let Some(queue) = self.queue.get() {
+            let slots = &queue.slots;
+            slots[(sequence % slots.len() as u64) as usize]
+                .store(pack(sequence, value), Ordering::Release);
+            queue.written.store(self.sequence, Ordering::Release);
+        }
+    }
+}
+
+impl Clone for SlotPublisher {
+    fn clone(&self) -> Self {
+        Self {
+            queue: OnceLock::new(),
+            sequence: self.sequence,
+        }
+    }
+}
+
+impl Drop for SlotPublisher {
+    fn drop(&mut self) {
+        if let Some(queue) = self.queue.get() {
+            queue.connected.store(false, Ordering::Release);
+        }
+    }
+}
+
+impl SlotReceiver {
+    /// Returns the next element, or why there is none.
+    ///
+    /// After reporting [`SlotRecvError::Lagged`], the next call returns the oldest
+    /// element that is still queued.
+    pub fn try_recv(self: &mut Self) -> Result<SlotMessage, SlotRecvError> {
+        let slots = &self.queue.slots;
+        let (sequence, value) =
+            unpack(slots[(self.next % slots.len() as u64) as usize].load(Ordering::Acquire));
+        // The difference between the slot's sequence number and the one that is being
+        // received, which is negative if the slot wasn't written yet, and positive if
+        // it was overwritten
+        match sequence.wrapping_sub(self.next as u32) as i32 {
+            0 => {
+                let message = SlotMessage {
+                    sequence: self.next,
+                    value,
+                };
+                self.next += 1;
+                Ok(message)
+            }
+            ahead if ahead > 0 => {
+                // Skip to the oldest element that is still inside the queue
+                let written = self.queue.written.load(Ordering::Acquire);
+                let oldest = written.saturating_sub(slots.len() as u64);
+                // The sequence number of the newest element may not be visible yet,
+                // in which case the slot is skipped on its own
+                let missed = oldest.saturating_sub(self.next).max(1);
+                self.next += missed;
+                Err(SlotRecvError::Lagged(missed))
+            }
+            _ if !self.is_connected() => {
+                // Elements published right before the buffer was dropped are still
+                // received
+                match self.queue.written.load(Ordering::Acquire) > self.next {
+                    true => self.try_recv(),
+                    false => Err(SlotRecvError::Disconnected),
+                }
+            }
+            _ => Err(SlotRecvError::Empty),
+        }
+    }
+
+    /// Waits for the next element, for up to `timeout`.
+    ///
+    /// Returns [`SlotRecvError::Empty`] if no element was finalized in time. Since
+    /// finalizing an element never wakes up a receiver, this checks for new elements
+    /// every millisecond while it waits.
+    pub fn recv_timeout(self: &mut Self, timeout: Duration) -> Result<SlotMessage, SlotRecvError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.try_recv() {
+                Err(SlotRecvError::Empty) => {}
+                result => return result,
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(SlotRecvError::Empty);
+            }
+            std::thread::sleep(POLL_INTERVAL.min(deadline - now));
+        }
+    }
+
+    /// Returns the sequence number of the next element the receiver returns.
+    pub fn next_sequence(self: &Self) -> u64 {
+        self.next
+    }
+
+    /// Returns `true` as long as the buffer hasn't been dropped.
+    pub fn is_connected(self: &Self) -> bool {
+        self.queue.connected.load(Ordering::Acquire)
+    }
+}
+
+impl fmt::Debug for SlotReceiver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SlotReceiver")
+            .field("next", &self.next)
+            .field("connected", &self.is_connected())
+            .finish()
+    }
+}
+
+fn pack(sequence: u64, value: f32) -> u64 {
+    ((sequence as u32 as u64) << 32) | value.to_bits() as u64
+}
+
+fn unpack(slot: u64) -> (u32, f32) {
+    ((slot >> 32) as u32, f32::from_bits(slot as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SlotMessage, SlotPublisher, SlotReceiver, SlotRecvError, CAPACITY};
+    use crate::utils::{PeakBuffer, VisualizerBuffer};
+
+    fn drain(receiver: &mut SlotReceiver) -> Vec<SlotMessage> {
+        std::iter::from_fn(|| receiver.try_recv().ok()).collect()
+    }
+
+    fn message(sequence: u64, value: f32) -> SlotMessage {
+        SlotMessage { sequence, value }
+    }
+
+    #[test]
+    fn multiple_subscribers() {
+        // 4 samples per element
+        let mut buffer = PeakBuffer::new(8, 1.0, 0.0);
+        buffer.set_sample_rate(32.);
+
+        let mut first = buffer.subscribe();
+        assert_eq!(first.try_recv(), Err(SlotRecvError::Empty));
+        for i in 0..9 {
+            buffer.enqueue(i as f32);
+        }
+        // A receiver only gets the elements that were finalized after it subscribed
+        let mut second = buffer.subscribe();
+        assert_eq!(second.next_sequence(), 2);
+        for i in 9..13 {
+            buffer.enqueue(i as f32);
+        }
+
+        assert_eq!(
+            drain(&mut first),
+            vec![message(0, 3.), message(1, 7.), message(2, 11.)]
+        );
+        assert_eq!(drain(&mut second), vec![message(2, 11.)]);
+        assert_eq!(first.try_recv(), Err(SlotRecvError::Empty));
+        assert_eq!(second.try_recv(), Err(SlotRecvError::Empty));
+    }
+
+    #[test]
+    fn lags_after_stall() {
+        let mut publisher = SlotPublisher::default();
+        let mut stalled = publisher.subscribe();
+        let mut live = publisher.subscribe();
+
+        for i in 0..CAPACITY + 10 {
+            publisher.publish(i as f32);
+            if i < 5 {
+                assert_eq!(live.try_recv(), Ok(message(i as u64, i as f32)));
+            }
+        }
+        // The stalled receiver is told how many elements it missed, and then continues
+        // with the oldest one that is still queued
+        assert_eq!(stalled.try_recv(), Err(SlotRecvError::Lagged(10)));
+        assert_eq!(stalled.try_recv(), Ok(message(10, 10.)));
+        let rest = drain(&mut stalled);
+        assert_eq!(rest.len(), CAPACITY - 1);
+        assert!(rest.windows(2).all(|w| w[1].sequence == w[0].sequence + 1));
+        assert_eq!(stalled.try_recv(), Err(SlotRecvError::Empty));
+
+        // The receiver that kept up for a while only missed what it fell behind on
+        assert_eq!(live.try_recv(), Err(SlotRecvError::Lagged(5)));
+        assert_eq!(live.try_recv(), Ok(message(10, 10.)));
+
+        // Once caught up, nothing else is missed
+        publisher.publish(-1.);
+        assert_eq!(stalled.try_recv(), Ok(message(CAPACITY as u64 + 10, -1.)));
+    }
+
+    #[test]
+    fn dropped_receivers() {
+        let mut buffer = PeakBuffer::new(8, 1.0, 0.0);
+        buffer.set_sample_rate(32.);
+        let mut reference = buffer.clone();
+
+        let mut kept = buffer.subscribe();
+        drop(buffer.subscribe());
+        for i in 0..20 {
+            buffer.enqueue(i as f32);
+            reference.enqueue(i as f32);
+            if i == 10 {
+                drop(buffer.subscribe());
+            }
+        }
+        // The buffer isn't affected by its receivers, whether or not they were dropped
+        for i in 0..8 {
+            assert_eq!(buffer[i], reference[i]);
+        }
+        assert_eq!(drain(&mut kept).len(), 4);
+
+        // Clones aren't published to
+        let mut clone = buffer.clone();
+        for i in 0..8 {
+            clone.enqueue(i as f32);
+        }
+        assert_eq!(kept.try_recv(), Err(SlotRecvError::Empty));
+
+        // Elements that were finalized before the buffer was dropped are still received.
+        // The first silent sample finalizes the element holding the samples 16 to 19
+        for _ in 0..4 {
+            buffer.enqueue(0.0);
+        }
+        drop(buffer);
+        assert!(!kept.is_connected());
+        assert_eq!(kept.try_recv(), Ok(message(4, 19.)));
+        assert_eq!(kept.try_recv(), Err(SlotRecvError::Disconnected));
+    }
+}
@@ -0,0 +1,180 @@
+use std::cmp::Ordering;
+
+/// Smoothing that is applied to the values of a buffer when they are displayed.
+///
+/// Smoothing never alters the data stored inside the buffer - it is applied
+/// whenever the values are read, e.g. by a [`Graph`](crate::visualizers::Graph).
+///
+/// The window is centered around each value. Near the ends of the buffer, the
+/// window shrinks instead of being padded, so the edges don't fade in or out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Smoothing {
+    /// Averages each value with its neighbours, within a window of `slots` values.
+    MovingAverage { slots: usize },
+    /// Takes the median of each value and its neighbours, within a window of
+    /// `slots` values.
+    ///
+    /// Compared to the moving average, this preserves steps while removing short
+    /// spikes.
+    Median { slots: usize },
+}
+
+/// Reusable storage for applying a [`Smoothing`].
+///
+/// Keep this around between draws, so that smoothing doesn't need to allocate
+/// once the buffers have grown to the right size.
+#[derive(Debug, Clone, Default)]
+pub struct SmoothingScratch {
+    input: Vec<f32>,
+    output: Vec<f32>,
+    window: Vec<f32>,
+}
+
+impl Smoothing {
+    /// Returns the width of the window, in slots.
+    pub fn slots(&self) -> usize {
+        match self {
+            Smoothing::MovingAverage { slots } => *slots,
+            Smoothing::Median { slots } => *slots,
+        }
+    }
+
+    /// Smooths the given values, returning the smoothed values.
+    ///
+    /// The returned slice has the same length as the input and is stored inside
+    /// the provided `scratch`.
+    pub fn apply<'a>(
+        &self,
+        values: impl IntoIterator<Item = f32>,
+        scratch: &'a mut SmoothingScratch,
+    ) -> &'a [f32] {
+        let SmoothingScratch {
+            input,
+            output,
+            window,
+        } = scratch;
+
+        input.clear();
+        input.extend(values);
+        output.clear();
+
+        let len = input.len();
+        // For even window sizes, the window extends one more slot into the future
+        let before = self.slots().saturating_sub(1) / 2;
+        let after = self.slots() / 2;
+
+        match self {
+            Smoothing::MovingAverage { .. } => {
+                // Running sum over the window, which only ever moves forwards
+                let mut sum = 0.0f64;
+                let (mut start, mut end) = (0, 0);
+
+                for i in 0..len {
+                    let lo = i.saturating_sub(before);
+                    let hi = (i + after + 1).min(len);
+
+                    while end < hi {
+                        sum += input[end] as f64;
+                        end += 1;
+                    }
+                    while start < lo {
+                        sum -= input[start] as f64;
+                        start += 1;
+                    }
+
+                    output.push((sum / (hi - lo) as f64) as f32);
+                }
+            }
+            Smoothing::Median { .. } => {
+                for i in 0..len {
+                    let lo = i.saturating_sub(before);
+                    let hi = (i + after + 1).min(len);
+
+                    window.clear();
+                    window.extend_from_slice(&input[lo..hi]);
+
+                    output.push(median(window));
+                }
+            }
+        }
+
+        output
+    }
+}
+
+fn median(window: &mut [f32]) -> f32 {
+    let len = window.len();
+    let mid = len / 2;
+    let (lower, upper, _) = window.select_nth_unstable_by(mid, total_cmp);
+    let upper = *upper;
+
+    if len % 2 == 1 {
+        upper
+    } else {
+        // The lower middle is the largest value below the upper middle
+        let lower = lower.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        (lower + upper) / 2.
+    }
+}
+
+fn total_cmp(a: &f32, b: &f32) -> Ordering {
+    a.total_cmp(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Smoothing, SmoothingScratch};
+
+    #[test]
+    fn moving_average() {
+        let mut scratch = SmoothingScratch::default();
+
+        let smoothed = Smoothing::MovingAverage { slots: 3 }
+            .apply([3., 0., 0., 0., 0., 6.], &mut scratch)
+            .to_vec();
+
+        // The window shrinks at the edges instead of being padded with zeros
+        assert_eq!(smoothed, vec![1.5, 1., 0., 0., 2., 3.]);
+    }
+
+    #[test]
+    fn moving_average_step() {
+        let mut scratch = SmoothingScratch::default();
+
+        let smoothed = Smoothing::MovingAverage { slots: 4 }
+            .apply([0., 0., 0., 0., 4., 4., 4., 4.], &mut scratch)
+            .to_vec();
+
+        assert_eq!(smoothed, vec![0., 0., 1., 2., 3., 4., 4., 4.]);
+    }
+
+    #[test]
+    fn median() {
+        let mut scratch = SmoothingScratch::default();
+
+        // Short spikes are removed, but steps are preserved
+        let smoothed = Smoothing::Median { slots: 3 }
+            .apply([0., 9., 0., 0., 5., 5., 5.], &mut scratch)
+            .to_vec();
+
+        assert_eq!(smoothed, vec![4.5, 0., 0., 0., 5., 5., 5.]);
+    }
+
+    #[test]
+    fn identity() {
+        let mut scratch = SmoothingScratch::default();
+        let values = [1., 5., 2., 8.];
+
+        assert_eq!(
+            Smoothing::MovingAverage { slots: 1 }.apply(values, &mut scratch),
+            &values
+        );
+        assert_eq!(
+            Smoothing::Median { slots: 0 }.apply(values, &mut scratch),
+            &values
+        );
+        assert!(Smoothing::Median { slots: 3 }
+            .apply([], &mut scratch)
+            .is_empty());
+    }
+}
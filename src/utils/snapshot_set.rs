@@ -0,0 +1,222 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use nih_plug_vizia::vizia::prelude::{Context, TimerAction};
+
+/// Captures several shared buffers at the same instant, once per frame.
+///
+/// When an editor draws several views - e.g. an input graph, a gain reduction graph
+/// and an output graph - each view locks its buffer at a slightly different time
+/// during the frame. While the signal changes quickly, their traces then show data
+/// from different instants, and visibly tear against each other.
+///
+/// A `SnapshotSet` holds a copy of each buffer that is added to it, which the views
+/// draw from instead of the buffer itself. Once per frame, all copies are updated
+/// in one pass: Every buffer is locked before any of them is copied, so the copies
+/// all show the buffers as they were at the same instant.
+///
+/// ```
+/// let mut snapshots = SnapshotSet::new();
+/// let input = snapshots.add(self.input_buffer.clone());
+/// let gain_reduction = snapshots.add(self.gain_reduction_buffer.clone());
+///
+/// // Inside the editor
+/// snapshots.capture_every_frame(cx);
+/// Graph::new(cx, Data::input, (-32.0, 8.0), ValueScaling::Decibels);
+/// Graph::new(cx, Data::gain_reduction, (-32.0, 8.0), ValueScaling::Decibels);
+/// ```
+///
+/// The copies are shared buffers of the same type as the buffers themselves, so
+/// every view can draw from them - construct the view with a lens to the copy in
+/// place of the lens to the buffer.
+///
+/// The buffers are locked in the order they were added. If the audio thread ever
+/// holds the locks of several of them at once, it needs to lock them in the same
+/// order.
+#[derive(Clone, Default)]
+pub struct SnapshotSet {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    members: Vec<Box<dyn Member>>,
+    captures: u64,
+}
+
+// A buffer, along with the copy that views draw from
+struct Copied<B> {
+    source: Arc<Mutex<B>>,
+    copy: Arc<Mutex<B>>,
+}
+
+trait Member: Send {
+    /// Locks the buffer, captures the remaining members while holding the lock, and
+    /// copies the buffer.
+    fn capture(&self, rest: &[Box<dyn Member>]);
+}
+
+impl<B: Clone + Send> Member for Copied<B> {
+    fn capture(&self, rest: &[Box<dyn Member>]) {
+        let source = lock(&self.source);
+        // Once the last member is locked, all of them are
+        if let Some((next, rest)) = rest.split_first() {
+            next.capture(rest);
+        }
+        lock(&self.copy).clone_from(&source);
+    }
+}
+
+// A poisoned buffer is still worth drawing
+fn lock<B>(buffer: &Mutex<B>) -> MutexGuard<'_, B> {
+    match buffer.lock() {
+        Ok(buffer) => buffer,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+impl SnapshotSet {
+    /// Constructs a new, empty `SnapshotSet`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a shared buffer to the set, and returns the copy of it that views should
+    /// draw from.
+    ///
+    /// The copy starts out as a copy of the buffer as it is now.
+    pub fn add<B>(self: &Self, buffer: Arc<Mutex<B>>) -> Arc<Mutex<B>>
+    where
+        B: Clone + Send + 'static,
+    {
+        let copy = Arc::new(Mutex::new(lock(&buffer).clone()));
+        lock(&self.inner).members.push(Box::new(Copied {
+            source: buffer,
+            copy: copy.clone(),
+        }));
+        copy
+    }
+
+    /// Updates the copies of all buffers at once.
+    ///
+    /// This locks every buffer and copies it, which allocates, so it should only be
+    /// called from the editor.
+    pub fn capture(self: &Self) {
+        let mut inner = lock(&self.inner);
+        if let Some((first, rest)) = inner.members.split_first() {
+            first.capture(rest);
+        }
+        inner.captures = inner.captures.wrapping_add(1);
+    }
+
+    /// Returns the number of times the set was captured.
+    pub fn captures(self: &Self) -> u64 {
+        lock(&self.inner).captures
+    }
+
+    /// Captures the set once per frame, for as long as the editor is open.
+    ///
+    /// Call this once when building the editor, before the views that draw from the
+    /// copies.
+    pub fn capture_every_frame(self: &Self, cx: &mut Context) {
+        let set = self.clone();
+        let timer = cx.add_timer(FRAME_INTERVAL, None, move |_, action| {
+            if let TimerAction::Tick(_) = action {
+                set.capture();
+            }
+        });
+        cx.start_timer(timer);
+    }
+}
+
+// The same interval the rulers poll their ranges at
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+#[cfg(test)]
+mod tests {
+    use super::SnapshotSet;
+    use crate::utils::{PeakBuffer, VisualizerBuffer};
+    use std::sync::{Arc, Mutex};
+
+    // One element per sample, without decay, so each element is the sample itself
+    fn peak_buffer() -> Arc<Mutex<PeakBuffer>> {
+        let mut buffer = PeakBuffer::new(16, 1.0, 0.0);
+        buffer.set_sample_rate(16.);
+        Arc::new(Mutex::new(buffer))
+    }
+
+    fn newest(buffer: &Mutex<PeakBuffer>) -> f32 {
+        *buffer.lock().unwrap().get_newest(0).unwrap()
+    }
+
+    #[test]
+    fn copies_only_when_captured() {
+        let set = SnapshotSet::new();
+        let buffer = peak_buffer();
+        let copy = set.add(buffer.clone());
+
+        buffer.lock().unwrap().enqueue(0.5);
+        buffer.lock().unwrap().enqueue(0.5);
+        assert_eq!(newest(&copy), 0.);
+
+        set.capture();
+        assert_eq!(newest(&copy), 0.5);
+        assert_eq!(set.captures(), 1);
+    }
+
+    #[test]
+    fn scripted_generations() {
+        let set = SnapshotSet::new();
+        let buffers = [peak_buffer(), peak_buffer(), peak_buffer()];
+        let copies = buffers.clone().map(|buffer| set.add(buffer));
+
+        // Each write generation enqueues its number into every buffer, and only some
+        // generations are followed by a capture
+        let mut captured = 0.;
+        for generation in 1..=40 {
+            for buffer in &buffers {
+                let mut buffer = buffer.lock().unwrap();
+                buffer.enqueue(generation as f32 / 64.);
+                buffer.enqueue(generation as f32 / 64.);
+            }
+            if generation % 3 == 0 || generation % 7 == 0 {
+                set.capture();
+                captured = generation as f32 / 64.;
+            }
+
+            for copy in &copies {
+                assert_eq!(newest(copy), captured);
+            }
+        }
+    }
+
+    #[test]
+    fn coherent_with_writer_thread() {
+        let set = SnapshotSet::new();
+        let buffers = [peak_buffer(), peak_buffer(), peak_buffer()];
+        let copies = buffers.clone().map(|buffer| set.add(buffer));
+
+        // The writer holds the locks of all buffers while it writes a generation, in
+        // the order they were added
+        let writer = std::thread::spawn(move || {
+            for generation in 1..=20_000 {
+                let mut locked = buffers.each_ref().map(|buffer| buffer.lock().unwrap());
+                for buffer in locked.iter_mut() {
+                    buffer.enqueue(generation as f32);
+                    buffer.enqueue(generation as f32);
+                }
+            }
+        });
+
+        while !writer.is_finished() {
+            set.capture();
+            let generations = copies.each_ref().map(|copy| newest(copy));
+            assert!(
+                generations.iter().all(|g| *g == generations[0]),
+                "torn snapshot: {:?}",
+                generations
+            );
+        }
+        writer.join().unwrap();
+    }
+}
@@ -25,15 +25,24 @@ use std::f32;
 use std::sync::Arc;
 use triple_buffer::TripleBuffer;
 
+use super::liveness::ProducerToken;
+use super::{Diagnostics, ProducerWatch};
+
 pub const SPECTRUM_WINDOW_SIZE: usize = 2048;
 const SPECTRUM_WINDOW_OVERLAP: usize = 2;
 
 /// The amplitudes of all frequency bins in a windowed FFT output.
 pub type Spectrum = [f32; SPECTRUM_WINDOW_SIZE / 2 + 1];
 /// A receiver for a spectrum computed by [`SpectrumInput`].
+///
+/// The output stays valid after the input is dropped, and keeps returning the last
+/// spectrum the input computed.
 pub struct SpectrumOutput {
+    #[cfg_attr(not(feature = "vizia"), allow(dead_code))]
     pub(crate) output: triple_buffer::Output<Spectrum>,
+    #[cfg_attr(not(feature = "vizia"), allow(dead_code))]
     pub(crate) sample_rate: f32,
+    producer: ProducerWatch,
 }
 
 /// Continuously compute spectrums and send them to the connected [`SpectrumOutput`].
@@ -56,6 +65,13 @@ pub struct SpectrumInput {
     compensated_window_function: Vec<f32>,
 
     complex_fft_buffer: Vec<Complex32>,
+
+    /// Counts the spectrums that were replaced before the output read them.
+    diagnostics: Diagnostics,
+
+    /// Tells the output that the input is gone once it's dropped. This is declared
+    /// last so that it's dropped after the triple buffer's input.
+    producer: ProducerToken,
 }
 
 impl SpectrumInput {
@@ -88,13 +104,17 @@ impl SpectrumInput {
                 .map(|x| x / SPECTRUM_WINDOW_SIZE as f32)
                 .collect(),
             complex_fft_buffer: vec![Complex32::default(); SPECTRUM_WINDOW_SIZE / 2 + 1],
+            diagnostics: Diagnostics::default(),
+            producer: ProducerToken::new(),
         };
+        let producer = input.producer.watch();
 
         (
             input,
             SpectrumOutput {
                 output: triple_buffer_output,
                 sample_rate: 44100.0,
+                producer,
             },
         )
     }
@@ -113,6 +133,12 @@ impl SpectrumInput {
         self.smoothing_decay_weight = 0.25f64.powf(decay_samples.recip()) as f32
     }
 
+    /// Attach a [`Diagnostics`] handle, which counts the spectrums that were replaced
+    /// before the [`SpectrumOutput`] read them as overwritten.
+    pub fn set_diagnostics(&mut self, diagnostics: Diagnostics) {
+        self.diagnostics = diagnostics;
+    }
+
     /// Compute the spectrum for a buffer and send it to the corresponding output pair.
     pub fn compute(&mut self, buffer: &Buffer) {
         self.stft.process_analyze_only(
@@ -149,8 +175,69 @@ impl SpectrumInput {
                     }
                 }
 
+                if !self.triple_buffer_input.consumed() {
+                    self.diagnostics.overwritten(1);
+                }
                 self.triple_buffer_input.write(self.spectrum_result_buffer);
             },
         );
     }
 }
+
+impl SpectrumOutput {
+    /// Returns `true` as long as the [`SpectrumInput`] hasn't been dropped.
+    ///
+    /// Some hosts destroy the plug-in before its editor is closed. Afterwards, the
+    /// output keeps returning the last spectrum, which the
+    /// [`SpectrumAnalyzer`](crate::visualizers::SpectrumAnalyzer) draws dimmed.
+    pub fn is_producer_alive(&self) -> bool {
+        self.producer.is_producer_alive()
+    }
+
+    /// Returns a [`ProducerWatch`] for the [`SpectrumInput`].
+    pub fn watch(&self) -> ProducerWatch {
+        self.producer.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SpectrumInput, SPECTRUM_WINDOW_SIZE};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[test]
+    fn outlives_input() {
+        for seed in 0..8 {
+            let (mut input, mut output) = SpectrumInput::new(2, 10.0);
+            let watch = output.watch();
+
+            // The writer publishes spectrums whose bins are all equal, and is dropped
+            // after a random number of them
+            let count = StdRng::seed_from_u64(seed).gen_range(1..20_000);
+            let writer = std::thread::spawn(move || {
+                for n in 1..=count {
+                    input
+                        .triple_buffer_input
+                        .write([n as f32; SPECTRUM_WINDOW_SIZE / 2 + 1]);
+                }
+                count as f32
+            });
+
+            let mut last = 0.0;
+            while output.is_producer_alive() {
+                let spectrum = output.output.read();
+                // A torn spectrum would mix bins from different writes
+                assert!(spectrum.iter().all(|bin| *bin == spectrum[0]));
+                assert!(spectrum[0] >= last);
+                last = spectrum[0];
+            }
+            let count = writer.join().unwrap();
+
+            // Once the input is gone, the last spectrum is returned from then on
+            assert!(!watch.is_producer_alive());
+            for _ in 0..3 {
+                assert!(output.output.read().iter().all(|bin| *bin == count));
+            }
+        }
+    }
+}
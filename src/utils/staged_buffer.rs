@@ -0,0 +1,411 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::liveness::ProducerToken;
+use super::{Diagnostics, ProducerWatch, SharedWrite, VisualizerBuffer};
+
+/// Writes to a shared buffer in batches, instead of locking it for every block.
+///
+/// Enqueueing into an `Arc<Mutex<B>>` directly locks the mutex for every process
+/// block, even though the editor only reads the buffer at around 60 Hz. A
+/// `StagedBuffer` is owned by the audio thread and collects the incoming samples
+/// in a staging area without any synchronization. At the end of a block, the
+/// staged samples are *published* - enqueued into the shared buffer in one
+/// short critical section.
+///
+/// Samples can only be enqueued through the [`StagedBlock`] returned by
+/// [`begin_block()`](Self::begin_block), which publishes when it is dropped, so
+/// publishing can't be forgotten:
+///
+/// ```
+/// fn process(&mut self, buffer: &mut Buffer, ...) -> ProcessStatus {
+///     self.staged_peaks.begin_block().enqueue_buffer(buffer, None);
+///     ProcessStatus::Normal
+/// }
+/// ```
+///
+/// Using a divider, the shared buffer is only published to every `n` blocks. If
+/// the editor is holding the lock during a publish, the publish is deferred to the
+/// next block rather than blocking the audio thread - unless the staging area is
+/// full, in which case it waits, so that no samples are ever lost. For the same
+/// reason, the samples that are still staged when the `StagedBuffer` is dropped are
+/// published right away. The shared buffer stays valid afterwards, and a
+/// [`ProducerWatch`] tells the editor that it no longer receives new data.
+///
+/// The shared buffer can be an `Arc<Mutex<B>>`, or an `Arc<RwLock<B>>` if several
+/// views read it at once; See [`SharedRead`](super::SharedRead). Behind an
+/// `RwLock`, a publish is deferred while any view holds the read lock.
+///
+/// To never wait for the editor, at the cost of a coarser view of the blocks that
+/// arrive while it holds the lock, use a [`TryLockBuffer`](super::TryLockBuffer).
+pub struct StagedBuffer<B, T = f32, S = Arc<Mutex<B>>>
+where
+    B: VisualizerBuffer<T>,
+    S: SharedWrite<Value = B>,
+{
+    shared: S,
+    staging: Vec<T>,
+    divider: usize,
+    // Blocks since the last publish
+    blocks: usize,
+    publishes: usize,
+    // Counts the publishes that had to wait, or were deferred
+    diagnostics: Diagnostics,
+    // Dropped after the final publish in `drop()`
+    producer: ProducerToken,
+}
+
+impl<B, T, S> StagedBuffer<B, T, S>
+where
+    B: VisualizerBuffer<T>,
+    T: Copy,
+    S: SharedWrite<Value = B>,
+{
+    /// Constructs a new `StagedBuffer` that publishes to the given shared buffer.
+    ///
+    /// * `shared` - The buffer that the editor reads from, behind either an
+    ///   `Arc<Mutex<B>>` or an `Arc<RwLock<B>>`
+    /// * `capacity` - The number of samples that can be staged; Usually, this is the
+    ///   maximum block size times the divider
+    ///
+    /// The staging area is allocated up front. Enqueueing never allocates - once it
+    /// is full, the staged samples are published early.
+    pub fn new(shared: S, capacity: usize) -> Self {
+        Self {
+            shared,
+            staging: Vec::with_capacity(capacity.max(1)),
+            divider: 1,
+            blocks: 0,
+            publishes: 0,
+            diagnostics: Diagnostics::default(),
+            producer: ProducerToken::new(),
+        }
+    }
+
+    /// Sets after how many blocks the staged samples are published.
+    ///
+    /// With a divider of 1 (the default), they are published after every block.
+    pub fn set_divider(self: &mut Self, divider: usize) {
+        self.divider = divider.max(1);
+    }
+
+    /// Attaches a [`Diagnostics`] handle, which counts the publishes that had to wait
+    /// for the lock, and the ones that were deferred because the lock was held.
+    ///
+    /// A deferred publish counts as a contended lock that didn't wait.
+    pub fn with_diagnostics(mut self, diagnostics: Diagnostics) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
+
+    /// Returns the shared buffer that is being published to.
+    pub fn shared(self: &Self) -> &S {
+        &self.shared
+    }
+
+    /// Returns a [`ProducerWatch`], which tells the editor whether this
+    /// `StagedBuffer` is still alive.
+    pub fn watch(self: &Self) -> ProducerWatch {
+        self.producer.watch()
+    }
+
+    /// Begins a new block. The returned [`StagedBlock`] publishes the staged
+    /// samples when it is dropped, depending on the divider.
+    pub fn begin_block(self: &mut Self) -> StagedBlock<'_, B, T, S> {
+        StagedBlock { staged: self }
+    }
+
+    /// Publishes all staged samples right away, waiting for the lock if needed.
+    pub fn publish(self: &mut Self) {
+        let shared = self.shared.clone();
+        // The lock is tried first, so an uncontended publish doesn't read the clock
+        if shared
+            .try_write_shared(|buffer| self.publish_into(buffer))
+            .is_none()
+        {
+            let start = Instant::now();
+            shared.write_shared(|buffer| self.publish_into(buffer));
+            self.diagnostics.waited(start.elapsed());
+        }
+    }
+
+    /// Returns the number of staged samples that haven't been published yet.
+    pub fn staged_len(self: &Self) -> usize {
+        self.staging.len()
+    }
+
+    /// Returns how many times the shared buffer has been published to.
+    ///
+    /// Each publish locks the shared buffer once.
+    pub fn publish_count(self: &Self) -> usize {
+        self.publishes
+    }
+
+    fn stage(self: &mut Self, value: T) {
+        if self.staging.len() == self.staging.capacity() {
+            self.publish();
+        }
+        self.staging.push(value);
+    }
+
+    fn end_block(self: &mut Self) {
+        self.blocks += 1;
+        if self.blocks < self.divider || self.staging.is_empty() {
+            return;
+        }
+
+        let shared = self.shared.clone();
+        if shared
+            .try_write_shared(|buffer| self.publish_into(buffer))
+            .is_none()
+        {
+            // The editor is reading the buffer - try again after the next block
+            self.diagnostics.waited(Duration::ZERO);
+        }
+    }
+
+    fn publish_into(self: &mut Self, buffer: &mut B) {
+        for value in self.staging.drain(..) {
+            buffer.enqueue(value);
+        }
+        self.blocks = 0;
+        self.publishes += 1;
+    }
+}
+
+impl<B, T, S> Drop for StagedBuffer<B, T, S>
+where
+    B: VisualizerBuffer<T>,
+    S: SharedWrite<Value = B>,
+{
+    fn drop(&mut self) {
+        if self.staging.is_empty() {
+            return;
+        }
+
+        // The plug-in is going away, so waiting for the editor is fine here
+        let staging = &mut self.staging;
+        self.shared.write_shared(|buffer| {
+            for value in staging.drain(..) {
+                buffer.enqueue(value);
+            }
+        });
+    }
+}
+
+/// A block of samples that is being staged inside a [`StagedBuffer`].
+///
+/// The staged samples are published when this is dropped.
+pub struct StagedBlock<'a, B, T = f32, S = Arc<Mutex<B>>>
+where
+    B: VisualizerBuffer<T>,
+    T: Copy,
+    S: SharedWrite<Value = B>,
+{
+    staged: &'a mut StagedBuffer<B, T, S>,
+}
+
+impl<'a, B, T, S> StagedBlock<'a, B, T, S>
+where
+    B: VisualizerBuffer<T>,
+    T: Copy,
+    S: SharedWrite<Value = B>,
+{
+    /// Stages a single value.
+    pub fn enqueue(self: &mut Self, value: T) {
+        self.staged.stage(value);
+    }
+}
+
+impl<'a, B, S> StagedBlock<'a, B, f32, S>
+where
+    B: VisualizerBuffer<f32>,
+    S: SharedWrite<Value = B>,
+{
+    /// Stages an entire [`Buffer`](`nih_plug::buffer::Buffer`), mono-summing it if
+    /// no channel is specified.
+    pub fn enqueue_buffer(
+        self: &mut Self,
+        buffer: &mut nih_plug::buffer::Buffer,
+        channel: Option<usize>,
+    ) {
+        match channel {
+            Some(channel) => {
+                for sample in buffer.as_slice()[channel].iter() {
+                    self.staged.stage(*sample);
+                }
+            }
+            None => {
+                // The same expression as the buffers' own, so that staging is
+                // bit-identical to enqueueing the block directly
+                for sample in buffer.iter_samples() {
+                    self.staged.stage(
+                        (1. / (&sample).len() as f32) * sample.into_iter().map(|x| *x).sum::<f32>(),
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl<'a, B, T, S> Drop for StagedBlock<'a, B, T, S>
+where
+    B: VisualizerBuffer<T>,
+    T: Copy,
+    S: SharedWrite<Value = B>,
+{
+    fn drop(&mut self) {
+        self.staged.end_block();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StagedBuffer;
+    use crate::utils::buffers::test_support::with_buffer;
+    use crate::utils::{PeakBuffer, RingBuffer, VisualizerBuffer};
+    use std::sync::{Arc, Mutex, RwLock};
+
+    fn peak_buffer() -> PeakBuffer {
+        let mut buffer = PeakBuffer::new(64, 1.0, 50.0);
+        buffer.set_sample_rate(640.);
+        buffer
+    }
+
+    fn contents(buffer: &PeakBuffer) -> Vec<f32> {
+        (0..buffer.len()).map(|i| buffer[i]).collect()
+    }
+
+    fn signal(i: usize) -> f32 {
+        ((i * 7919) % 1000) as f32 / 1000.
+    }
+
+    #[test]
+    fn no_lost_or_duplicated_slots() {
+        let shared = Arc::new(Mutex::new(peak_buffer()));
+        let mut staged = StagedBuffer::new(shared.clone(), 3 * 37);
+        staged.set_divider(3);
+
+        let mut direct = peak_buffer();
+        let mut i = 0;
+
+        // Uneven block sizes, so that slots straddle publish boundaries
+        for block in 0..60 {
+            let len = 1 + (block * 13) % 37;
+            {
+                let mut staged_block = staged.begin_block();
+                for _ in 0..len {
+                    staged_block.enqueue(signal(i));
+                    direct.enqueue(signal(i));
+                    i += 1;
+                }
+            }
+
+            if block % 3 == 2 {
+                assert_eq!(staged.staged_len(), 0);
+                assert_eq!(contents(&shared.lock().unwrap()), contents(&direct));
+            }
+        }
+
+        assert_eq!(staged.publish_count(), 20);
+    }
+
+    #[test]
+    fn mono_sum_matches_enqueue_buffer() {
+        // Three channels, whose averages aren't exact
+        let mut channels = (0..3)
+            .map(|c| (0..100).map(|i| signal(i + c)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        let shared = Arc::new(Mutex::new(RingBuffer::<f32>::new(64)));
+        let mut staged = StagedBuffer::new(shared.clone(), 100);
+        with_buffer(&mut channels, |buffer| {
+            staged.begin_block().enqueue_buffer(buffer, None)
+        });
+        staged.publish();
+
+        let mut direct = RingBuffer::<f32>::new(64);
+        with_buffer(&mut channels, |buffer| direct.enqueue_buffer(buffer, None));
+        assert_eq!(*shared.lock().unwrap(), direct);
+    }
+
+    #[test]
+    fn publish_deferred_while_locked() {
+        let shared = Arc::new(Mutex::new(peak_buffer()));
+        let mut staged = StagedBuffer::new(shared.clone(), 1000);
+
+        {
+            let _editor = shared.lock().unwrap();
+            staged.begin_block().enqueue(1.0);
+        }
+        assert_eq!(staged.staged_len(), 1);
+        assert_eq!(staged.publish_count(), 0);
+
+        staged.begin_block().enqueue(0.5);
+        assert_eq!(staged.staged_len(), 0);
+        assert_eq!(staged.publish_count(), 1);
+    }
+
+    #[test]
+    fn publishes_into_rw_lock() {
+        let shared = Arc::new(RwLock::new(peak_buffer()));
+        let mut staged = StagedBuffer::new(shared.clone(), 1000);
+
+        let mut direct = peak_buffer();
+        {
+            // Any view that reads the buffer defers the publish
+            let _editor = shared.read().unwrap();
+            staged.begin_block().enqueue(1.0);
+        }
+        direct.enqueue(1.0);
+        assert_eq!(staged.staged_len(), 1);
+
+        staged.begin_block().enqueue(0.5);
+        direct.enqueue(0.5);
+        assert_eq!(staged.publish_count(), 1);
+        assert_eq!(contents(&shared.read().unwrap()), contents(&direct));
+    }
+
+    #[test]
+    fn overflow_publishes_early() {
+        let shared = Arc::new(Mutex::new(peak_buffer()));
+        let mut staged = StagedBuffer::new(shared.clone(), 16);
+        staged.set_divider(100);
+
+        let mut direct = peak_buffer();
+        {
+            let mut block = staged.begin_block();
+            for i in 0..40 {
+                block.enqueue(signal(i));
+                direct.enqueue(signal(i));
+            }
+        }
+        staged.publish();
+
+        assert_eq!(staged.publish_count(), 3);
+        assert_eq!(contents(&shared.lock().unwrap()), contents(&direct));
+    }
+
+    #[test]
+    fn publishes_when_dropped() {
+        let shared = Arc::new(Mutex::new(peak_buffer()));
+        let mut staged = StagedBuffer::new(shared.clone(), 1000);
+        let watch = staged.watch();
+
+        let mut direct = peak_buffer();
+        {
+            let _editor = shared.lock().unwrap();
+            let mut block = staged.begin_block();
+            for i in 0..40 {
+                block.enqueue(signal(i));
+                direct.enqueue(signal(i));
+            }
+        }
+        assert_eq!(staged.staged_len(), 40);
+        assert!(watch.is_producer_alive());
+
+        drop(staged);
+        assert!(!watch.is_producer_alive());
+        assert_eq!(contents(&shared.lock().unwrap()), contents(&direct));
+    }
+}
@@ -0,0 +1,555 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, TryLockError};
+use std::time::{Duration, Instant};
+
+use nih_plug::buffer::Buffer;
+
+use super::{
+    Diagnostics, MinimaBuffer, PeakBuffer, RMSBuffer, TieredBuffer, VisualizerBuffer,
+    WaveformBuffer,
+};
+
+// The number of samples that are reduced to a single summary by default
+const DEFAULT_CHUNK_SIZE: usize = 16;
+
+/// Feeds a shared buffer without ever blocking the audio thread.
+///
+/// Even with short critical sections, an editor that holds a buffer's lock while
+/// its thread is preempted can stall the audio thread long enough to cause an xrun
+/// at small block sizes. A `TryLockBuffer` is owned by the audio thread, and only
+/// ever *tries* to lock the shared buffer. If the lock is held, the block is stashed
+/// in an overflow, which is drained into the shared buffer the next time the lock is
+/// free - before that block is enqueued, so the order is kept.
+///
+/// ```
+/// fn process(&mut self, buffer: &mut Buffer, ...) -> ProcessStatus {
+///     self.peaks.enqueue_buffer_try(buffer, None);
+///     ProcessStatus::Normal
+/// }
+/// ```
+///
+/// # The overflow
+///
+/// To keep it small, the overflow reduces each stashed block to a summary of every
+/// [chunk](Self::set_chunk_size) of samples, and replays each summary as the same
+/// number of samples when it's drained. The buffer receives as many samples as it
+/// would have, so its timing is kept, and its decay is applied in the same order.
+///
+/// The summary is chosen by the buffer, through [`ChunkReduce`], so that it keeps
+/// what the buffer aggregates - the peak of a [`PeakBuffer`], the minimum of a
+/// [`MinimaBuffer`], the energy of an [`RMSBuffer`], and both extremes of a
+/// [`WaveformBuffer`]. If the chunks lie inside the elements, the drained elements
+/// are the same as they would have been. Otherwise, a chunk that straddles two
+/// elements counts into both; For a `PeakBuffer`, the drained elements are then
+/// never lower than they would have been, and never higher than the element before
+/// or after would have been.
+///
+/// The overflow holds a fixed number of chunks, and is only touched by the audio
+/// thread, so stashing and draining it never allocate or synchronize. A block that
+/// spans more chunks than the overflow holds is reduced to larger chunks. Once the
+/// overflow is full, the **oldest** blocks are dropped to make room for the newest
+/// one, since those are about to scroll out of the view anyway. Dropped blocks are
+/// counted by [`dropped_blocks()`](Self::dropped_blocks), and by the attached
+/// [`Diagnostics`].
+pub struct TryLockBuffer<B>
+where
+    B: ChunkReduce,
+{
+    shared: Arc<Mutex<B>>,
+    // The summary of each chunk inside the overflow, along with the number of
+    // samples it stands for, from the oldest to the newest
+    chunks: VecDeque<((f32, f32), u32)>,
+    // The number of chunks each block inside the overflow was reduced to
+    blocks: VecDeque<usize>,
+    capacity: usize,
+    chunk_size: usize,
+    max_wait: Duration,
+    dropped: u64,
+    // Counts the locks that had to wait or were skipped, and the dropped blocks
+    diagnostics: Diagnostics,
+}
+
+impl<B> TryLockBuffer<B>
+where
+    B: ChunkReduce,
+{
+    /// Constructs a new `TryLockBuffer` that enqueues into the given shared buffer.
+    ///
+    /// * `shared` - The buffer that the editor reads from
+    /// * `capacity` - The number of chunks the overflow holds; With the default
+    ///   chunk size of 16 samples, a capacity of 1024 holds around 0.37 seconds of
+    ///   audio at 44.1 kHz before blocks are dropped
+    ///
+    /// The overflow is allocated up front.
+    pub fn new(shared: Arc<Mutex<B>>, capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            shared,
+            chunks: VecDeque::with_capacity(capacity),
+            blocks: VecDeque::with_capacity(capacity),
+            capacity,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            max_wait: Duration::ZERO,
+            dropped: 0,
+            diagnostics: Diagnostics::default(),
+        }
+    }
+
+    /// Sets the number of samples each chunk of a stashed block is reduced to a
+    /// summary of.
+    ///
+    /// Smaller chunks are more precise, but the overflow holds less audio. As long as
+    /// the chunks are shorter than the buffer's elements, each summary ends up at
+    /// most one element off.
+    pub fn set_chunk_size(self: &mut Self, chunk_size: usize) {
+        self.chunk_size = chunk_size.max(1);
+    }
+
+    /// Sets how long enqueueing keeps trying to take the lock before the block is
+    /// stashed.
+    ///
+    /// By default, this is 0, and a held lock stashes the block right away. A short
+    /// wait, in the order of microseconds, rides out editors that only hold the lock
+    /// briefly - at the cost of spinning on the audio thread while it waits.
+    pub fn set_max_wait(self: &mut Self, max_wait: Duration) {
+        self.max_wait = max_wait;
+    }
+
+    /// Attaches a [`Diagnostics`] handle, which counts the locks that had to wait,
+    /// the blocks that were stashed, and the ones that were dropped.
+    ///
+    /// A stashed block counts as a contended lock.
+    pub fn with_diagnostics(mut self, diagnostics: Diagnostics) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
+
+    /// Returns the shared buffer that is being enqueued into.
+    pub fn shared(self: &Self) -> &Arc<Mutex<B>> {
+        &self.shared
+    }
+
+    /// Enqueues an entire [`Buffer`], mono-summing it if no channel is specified,
+    /// without waiting for the lock of the shared buffer.
+    ///
+    /// Returns `true` if the block - along with the overflow - was enqueued into the
+    /// shared buffer, or `false` if it was stashed in the overflow.
+    ///
+    /// The overflow mono-sums blocks the same way as the buffers, which ignores a
+    /// [`ChannelSource`](super::ChannelSource) that's set on the shared buffer while
+    /// the lock is held.
+    pub fn enqueue_buffer_try(
+        self: &mut Self,
+        buffer: &mut Buffer,
+        channel: Option<usize>,
+    ) -> bool {
+        let shared = self.shared.clone();
+        let mut waiting: Option<Instant> = None;
+        let result = loop {
+            match shared.try_lock() {
+                Ok(guard) => break Some(guard),
+                Err(TryLockError::Poisoned(poisoned)) => break Some(poisoned.into_inner()),
+                Err(TryLockError::WouldBlock) => {
+                    // A wait of 0 doesn't need to read the clock
+                    if self.max_wait.is_zero() {
+                        break None;
+                    }
+                    if waiting.get_or_insert_with(Instant::now).elapsed() >= self.max_wait {
+                        break None;
+                    }
+                    std::hint::spin_loop();
+                }
+            }
+        };
+        if result.is_none() || waiting.is_some() {
+            self.diagnostics
+                .waited(waiting.map_or(Duration::ZERO, |start| start.elapsed()));
+        }
+
+        match result {
+            Some(mut shared) => {
+                self.drain_into(&mut shared);
+                shared.enqueue_buffer(buffer, channel);
+                true
+            }
+            None => {
+                self.stash(buffer, channel);
+                false
+            }
+        }
+    }
+
+    /// Drains the overflow into the shared buffer right away, waiting for the lock if
+    /// needed.
+    pub fn flush(self: &mut Self) {
+        let shared = self.shared.clone();
+        let mut buffer = match self.diagnostics.lock(&shared) {
+            Ok(buffer) => buffer,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        self.drain_into(&mut buffer);
+    }
+
+    /// Returns the number of blocks inside the overflow, which haven't been enqueued
+    /// into the shared buffer yet.
+    pub fn overflow_len(self: &Self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Returns the number of blocks that were dropped from the overflow, because it
+    /// was full.
+    pub fn dropped_blocks(self: &Self) -> u64 {
+        self.dropped
+    }
+
+    fn stash(self: &mut Self, buffer: &mut Buffer, channel: Option<usize>) {
+        let channels = buffer.as_slice_immutable();
+        let samples = buffer.samples();
+        if samples == 0 || channels.is_empty() {
+            return;
+        }
+        let sample = |i: usize| match channel {
+            Some(channel) => channels[channel][i],
+            // The same sum as the buffers', so that a chunk's summary is bit-identical
+            None => (1. / channels.len() as f32) * channels.iter().map(|c| c[i]).sum::<f32>(),
+        };
+
+        // A block that spans more chunks than the overflow holds gets larger ones
+        let stride = self.chunk_size.max(samples.div_ceil(self.capacity));
+        let count = samples.div_ceil(stride);
+        while self.chunks.len() + count > self.capacity {
+            let Some(oldest) = self.blocks.pop_front() else {
+                break;
+            };
+            self.chunks.drain(..oldest);
+            self.dropped += 1;
+            self.diagnostics.dropped_blocks(1);
+        }
+
+        for start in (0..samples).step_by(stride) {
+            let end = (start + stride).min(samples);
+            let reduced = B::reduce_chunk((start..end).map(sample));
+            self.chunks.push_back((reduced, (end - start) as u32));
+        }
+        self.blocks.push_back(count);
+    }
+
+    fn drain_into(self: &mut Self, buffer: &mut B) {
+        for (reduced, samples) in self.chunks.drain(..) {
+            for i in 0..samples {
+                buffer.enqueue(B::replay_chunk(reduced, i));
+            }
+        }
+        self.blocks.clear();
+    }
+}
+
+impl<B> Drop for TryLockBuffer<B>
+where
+    B: ChunkReduce,
+{
+    fn drop(&mut self) {
+        if self.blocks.is_empty() {
+            return;
+        }
+
+        // The plug-in is going away, so waiting for the editor is fine here
+        let shared = self.shared.clone();
+        let mut buffer = match shared.lock() {
+            Ok(buffer) => buffer,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        self.drain_into(&mut buffer);
+    }
+}
+
+/// A buffer that a [`TryLockBuffer`] can feed, which decides how the chunks of a
+/// stashed block are summarized.
+///
+/// Each chunk is reduced to a pair of values, and replayed from them as the same
+/// number of samples. The summary needs to keep what the buffer aggregates, so that
+/// replaying it leaves the buffer's elements the same.
+pub trait ChunkReduce: VisualizerBuffer<f32> {
+    /// Reduces a chunk of samples, which is never empty, to the values it's replayed
+    /// from.
+    fn reduce_chunk(chunk: impl Iterator<Item = f32>) -> (f32, f32);
+
+    /// Returns sample `i` of the samples a chunk is replayed as.
+    fn replay_chunk(reduced: (f32, f32), i: u32) -> f32;
+}
+
+// The sample that's furthest from 0, which keeps its sign
+fn signed_peak(chunk: impl Iterator<Item = f32>) -> (f32, f32) {
+    let peak = chunk.fold(0f32, |peak, x| if x.abs() > peak.abs() { x } else { peak });
+    (peak, peak)
+}
+
+impl ChunkReduce for PeakBuffer {
+    fn reduce_chunk(chunk: impl Iterator<Item = f32>) -> (f32, f32) {
+        signed_peak(chunk)
+    }
+    fn replay_chunk(reduced: (f32, f32), _: u32) -> f32 {
+        reduced.0
+    }
+}
+
+impl ChunkReduce for TieredBuffer {
+    fn reduce_chunk(chunk: impl Iterator<Item = f32>) -> (f32, f32) {
+        signed_peak(chunk)
+    }
+    fn replay_chunk(reduced: (f32, f32), _: u32) -> f32 {
+        reduced.0
+    }
+}
+
+impl ChunkReduce for MinimaBuffer {
+    // The sample that's closest to 0
+    fn reduce_chunk(chunk: impl Iterator<Item = f32>) -> (f32, f32) {
+        let minimum = chunk.fold(f32::MAX, |minimum, x| minimum.min(x.abs()));
+        (minimum, minimum)
+    }
+    fn replay_chunk(reduced: (f32, f32), _: u32) -> f32 {
+        reduced.0
+    }
+}
+
+impl ChunkReduce for RMSBuffer {
+    // The mean square, so that the replayed chunk has the same energy
+    fn reduce_chunk(chunk: impl Iterator<Item = f32>) -> (f32, f32) {
+        let (sum, len) = chunk.fold((0f32, 0u32), |(sum, len), x| (sum + x * x, len + 1));
+        let mean_square = sum / len.max(1) as f32;
+        (mean_square, mean_square)
+    }
+    fn replay_chunk(reduced: (f32, f32), _: u32) -> f32 {
+        reduced.0.sqrt()
+    }
+}
+
+impl ChunkReduce for WaveformBuffer {
+    // The lowest and the highest sample, which are replayed in turns, so that an
+    // element that receives at least two of a chunk's samples gets both
+    fn reduce_chunk(chunk: impl Iterator<Item = f32>) -> (f32, f32) {
+        chunk.fold((f32::MAX, f32::MIN), |(min, max), x| {
+            (min.min(x), max.max(x))
+        })
+    }
+    fn replay_chunk(reduced: (f32, f32), i: u32) -> f32 {
+        match i % 2 {
+            0 => reduced.0,
+            _ => reduced.1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChunkReduce, TryLockBuffer};
+    use crate::utils::buffers::test_support::{contents, test_signal, with_buffer};
+    use crate::utils::WaveformBuffer;
+    use crate::utils::{Diagnostics, MinimaBuffer, PeakBuffer, RMSBuffer, VisualizerBuffer};
+    use std::sync::{Arc, Mutex};
+
+    // 10 samples per element
+    fn peak_buffer() -> PeakBuffer {
+        let mut buffer = PeakBuffer::new(64, 1.0, 50.0);
+        buffer.set_sample_rate(640.);
+        buffer
+    }
+
+    // Feeds the same blocks to a `TryLockBuffer` and to a buffer directly, holding
+    // the lock during the blocks for which `held` returns true
+    fn feed<B: ChunkReduce>(
+        try_lock: &mut TryLockBuffer<B>,
+        direct: &mut B,
+        lengths: &[usize],
+        held: impl Fn(usize) -> bool,
+    ) {
+        for (block, len) in lengths.iter().enumerate() {
+            let mut channels = test_signal(2, *len);
+            with_buffer(&mut channels, |b| {
+                let editor = held(block).then(|| try_lock.shared().clone());
+                let _guard = editor.as_ref().map(|shared| shared.lock().unwrap());
+                assert_eq!(try_lock.enqueue_buffer_try(b, None), !held(block));
+                direct.enqueue_buffer(b, None);
+            });
+        }
+    }
+
+    #[test]
+    fn drains_exactly_with_aligned_chunks() {
+        let shared = Arc::new(Mutex::new(peak_buffer()));
+        let mut try_lock = TryLockBuffer::new(shared.clone(), 256);
+        try_lock.set_chunk_size(5);
+        let mut direct = peak_buffer();
+
+        // The lock is held across several blocks in the middle
+        let lengths = [20, 35, 15, 40, 5, 25, 30, 10];
+        feed(&mut try_lock, &mut direct, &lengths, |block| {
+            (2..6).contains(&block)
+        });
+        assert_eq!(try_lock.overflow_len(), 0);
+        assert_eq!(try_lock.dropped_blocks(), 0);
+
+        let shared = shared.lock().unwrap();
+        assert_eq!(shared.enqueued(), direct.enqueued());
+        assert_eq!(contents(&*shared), contents(&direct));
+    }
+
+    #[test]
+    fn drains_minima_exactly() {
+        let new = || {
+            let mut buffer = MinimaBuffer::new(64, 1.0, 50.0);
+            buffer.set_sample_rate(640.);
+            buffer
+        };
+        let shared = Arc::new(Mutex::new(new()));
+        let mut try_lock = TryLockBuffer::new(shared.clone(), 256);
+        try_lock.set_chunk_size(5);
+        let mut direct = new();
+
+        // The minima aren't replaced by the peaks of the chunks
+        let lengths = [20, 35, 15, 40, 5, 25, 30, 10];
+        feed(&mut try_lock, &mut direct, &lengths, |block| {
+            (2..6).contains(&block)
+        });
+        assert_eq!(contents(&*shared.lock().unwrap()), contents(&direct));
+    }
+
+    #[test]
+    fn drains_waveform_extremes() {
+        let new = || {
+            let mut buffer = WaveformBuffer::new(64, 1.0);
+            buffer.set_sample_rate(640.);
+            buffer
+        };
+        let shared = Arc::new(Mutex::new(new()));
+        let mut try_lock = TryLockBuffer::new(shared.clone(), 256);
+        try_lock.set_chunk_size(5);
+        let mut direct = new();
+
+        // Both the lowest and the highest sample of each element are kept
+        let lengths = [20, 35, 15, 40, 5, 25, 30, 10];
+        feed(&mut try_lock, &mut direct, &lengths, |block| {
+            (2..6).contains(&block)
+        });
+        assert_eq!(contents(&*shared.lock().unwrap()), contents(&direct));
+    }
+
+    #[test]
+    fn drains_rms_energy() {
+        // 10 samples per element and per window
+        let new = || {
+            let mut buffer = RMSBuffer::new(64, 1.0, 15.625);
+            buffer.set_sample_rate(640.);
+            buffer
+        };
+        let shared = Arc::new(Mutex::new(new()));
+        let mut try_lock = TryLockBuffer::new(shared.clone(), 256);
+        try_lock.set_chunk_size(5);
+        let mut direct = new();
+
+        let lengths = [20, 35, 15, 40, 5, 25, 30, 10];
+        feed(&mut try_lock, &mut direct, &lengths, |block| {
+            (2..6).contains(&block)
+        });
+        let (drained, direct) = (contents(&*shared.lock().unwrap()), contents(&direct));
+        for i in 0..drained.len() {
+            assert!(
+                (drained[i] - direct[i]).abs() < 1e-4,
+                "{}: {} != {}",
+                i,
+                drained[i],
+                direct[i]
+            );
+        }
+    }
+
+    #[test]
+    fn drains_within_tolerance() {
+        let shared = Arc::new(Mutex::new(peak_buffer()));
+        let mut try_lock = TryLockBuffer::new(shared.clone(), 256);
+        try_lock.set_chunk_size(7);
+        let mut direct = peak_buffer();
+
+        // Chunks straddle the elements
+        let lengths = [13, 29, 3, 41, 17, 8, 33, 11, 19];
+        feed(&mut try_lock, &mut direct, &lengths, |block| {
+            (1..7).contains(&block)
+        });
+
+        let shared = shared.lock().unwrap();
+        assert_eq!(shared.enqueued(), direct.enqueued());
+        let (drained, direct) = (contents(&*shared), contents(&direct));
+        for i in 0..drained.len() {
+            let neighbors = &direct[i.saturating_sub(1)..(i + 2).min(direct.len())];
+            let highest = neighbors.iter().copied().fold(0f32, f32::max);
+            assert!(
+                drained[i] >= direct[i],
+                "{}: {} < {}",
+                i,
+                drained[i],
+                direct[i]
+            );
+            assert!(drained[i] <= highest, "{}: {} > {}", i, drained[i], highest);
+        }
+    }
+
+    #[test]
+    fn drops_oldest_blocks() {
+        let diagnostics = Diagnostics::new();
+        let shared = Arc::new(Mutex::new(peak_buffer()));
+        let mut try_lock =
+            TryLockBuffer::new(shared.clone(), 8).with_diagnostics(diagnostics.clone());
+        try_lock.set_chunk_size(5);
+
+        // 4 chunks per block, so the overflow holds 2 of them
+        let mut direct = peak_buffer();
+        let lengths = [20; 5];
+        {
+            let _editor = shared.lock().unwrap();
+            let mut channels = test_signal(2, 20);
+            for _ in lengths {
+                with_buffer(&mut channels, |b| try_lock.enqueue_buffer_try(b, None));
+            }
+        }
+        assert_eq!(try_lock.overflow_len(), 2);
+        assert_eq!(try_lock.dropped_blocks(), 3);
+        if Diagnostics::is_enabled() {
+            assert_eq!(diagnostics.snapshot().dropped_blocks, 3);
+            assert_eq!(diagnostics.snapshot().contended_locks, 5);
+        }
+
+        // Only the newest two blocks arrive
+        try_lock.flush();
+        let mut channels = test_signal(2, 20);
+        for _ in 0..2 {
+            with_buffer(&mut channels, |b| direct.enqueue_buffer(b, None));
+        }
+        assert_eq!(contents(&*shared.lock().unwrap()), contents(&direct));
+    }
+
+    #[test]
+    fn large_blocks_get_larger_chunks() {
+        let shared = Arc::new(Mutex::new(peak_buffer()));
+        let mut try_lock = TryLockBuffer::new(shared.clone(), 8);
+        let mut direct = peak_buffer();
+
+        // 1000 samples in 8 chunks of 125
+        feed(&mut try_lock, &mut direct, &[1000, 10], |block| block == 0);
+        assert_eq!(try_lock.dropped_blocks(), 0);
+        assert_eq!(shared.lock().unwrap().enqueued(), direct.enqueued());
+    }
+
+    #[test]
+    fn drains_when_dropped() {
+        let shared = Arc::new(Mutex::new(peak_buffer()));
+        let mut try_lock = TryLockBuffer::new(shared.clone(), 64);
+        try_lock.set_chunk_size(5);
+        let mut direct = peak_buffer();
+
+        feed(&mut try_lock, &mut direct, &[30, 30], |_| true);
+        assert_eq!(try_lock.overflow_len(), 2);
+        drop(try_lock);
+        assert_eq!(contents(&*shared.lock().unwrap()), contents(&direct));
+    }
+}
@@ -0,0 +1,349 @@
+use super::config_error::{check_decay, check_duration, check_range, ConfigError};
+use super::decay::decay_ms_from_db_per_second;
+use super::{
+    BalanceBuffer, BallisticsPreset, CountBuffer, MinimaBuffer, PeakBuffer, RMSBuffer,
+    WaveformBuffer,
+};
+#[cfg(feature = "vizia")]
+use crate::visualizers::{Grid, UnitRuler};
+
+/// Derives the settings of an editor's buffers, grids and rulers from what the
+/// editor displays, so that all of them are set up consistently.
+///
+/// A buffer's size, duration and decay interact - the size needs to cover the
+/// width of the view it's displayed on, and the decay depends on the meter
+/// standard it follows. `VisualizerConfig` applies the same rules to every buffer:
+///
+/// * **Size** - 2 elements per physical pixel of the target width at the largest
+///   UI scale, so the trace stays smooth at every scale the editor is shown at.
+///   The size is then multiplied by the resize headroom - 1.25 by default - so
+///   that it still covers the view once the editor is resized a little wider.
+/// * **Decay** - Given by the [ballistics preset](Self::with_ballistics) if one is set,
+///   as the time its release takes to fall by 12 dB. Without a preset, it's 50 ms.
+/// * **Marks** - The grid lines and ruler labels lie at every multiple of the
+///   [mark step](Self::with_marks) inside the range, so they always line up.
+///
+/// Construct one configuration for your editor, and use it both in your plug-in and
+/// inside the editor:
+///
+/// ```
+/// pub(crate) fn visualizer_config() -> VisualizerConfig {
+///     VisualizerConfig::new(800.0, 10.0).with_ui_scale(1.0, 2.0)
+/// }
+///
+/// // lib.rs
+/// peak_buffer: Arc::new(Mutex::new(editor::visualizer_config().peak_buffer())),
+///
+/// // editor.rs
+/// let config = visualizer_config();
+/// Grid::new(cx, ValueScaling::Linear, config.range(), config.grid_marks(), Orientation::Horizontal);
+/// UnitRuler::new(cx, config.range(), ValueScaling::Linear, config.ruler_marks(), Orientation::Vertical);
+/// ```
+///
+/// The settings are only checked by [`validate()`](Self::validate) - the buffers
+/// that are constructed from an invalid configuration clamp their settings, just
+/// like when they are constructed directly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VisualizerConfig {
+    width: f32,
+    scale: (f32, f32),
+    headroom: f32,
+    duration: f32,
+    decay: f32,
+    ballistics: Option<BallisticsPreset>,
+    range: (f32, f32),
+    step: f32,
+}
+
+// The number of elements for each physical pixel of the display
+const ELEMENTS_PER_PIXEL: f64 = 2.;
+
+impl VisualizerConfig {
+    /// Constructs a new `VisualizerConfig`.
+    ///
+    /// * `width` - The width of the view the buffers are displayed on, in logical pixels
+    /// * `duration` - The duration (in seconds) of the audio data inside the buffers
+    ///
+    /// It starts out at a UI scale of 1, a resize headroom of 1.25, a decay of 50 ms,
+    /// and a range of -32 to +8 dB with a mark every 6 dB.
+    pub fn new(width: f32, duration: f32) -> Self {
+        Self {
+            width,
+            scale: (1., 1.),
+            headroom: 1.25,
+            duration,
+            decay: 50.,
+            ballistics: None,
+            range: (-32., 8.),
+            step: 6.,
+        }
+    }
+
+    /// Sets the range of UI scales the editor is shown at, e.g. `(1.0, 2.0)` for an
+    /// editor that can be scaled up to twice its size, or that may be shown on a
+    /// high-DPI display.
+    pub fn with_ui_scale(mut self, min: f32, max: f32) -> Self {
+        self.scale = (min, max);
+        self
+    }
+
+    /// Sets how much wider than the target width the buffers can be displayed, as
+    /// a factor of at least 1.
+    pub fn with_resize_headroom(mut self, headroom: f32) -> Self {
+        self.headroom = headroom;
+        self
+    }
+
+    /// Sets the duration (in seconds) of the audio data inside the buffers.
+    pub fn with_duration(mut self, duration: f32) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Sets the decay in milliseconds, which is used unless a
+    /// [ballistics preset](Self::with_ballistics) is set.
+    pub fn with_decay(mut self, decay: f32) -> Self {
+        self.decay = decay;
+        self
+    }
+
+    /// Sets the ballistics of the peak buffers, which also determine the decay of
+    /// the other buffers.
+    pub fn with_ballistics(mut self, preset: BallisticsPreset) -> Self {
+        self.ballistics = Some(preset);
+        self
+    }
+
+    /// Sets the range the grids and rulers cover, in the units of their marks.
+    pub fn with_range(mut self, range: (f32, f32)) -> Self {
+        self.range = range;
+        self
+    }
+
+    /// Sets the step between the marks of the grids and rulers, which lie at every
+    /// multiple of it.
+    pub fn with_marks(mut self, step: f32) -> Self {
+        self.step = step;
+        self
+    }
+
+    /// Returns the configuration, or a [`ConfigError`] if any of its settings is
+    /// invalid.
+    pub fn validate(self: Self) -> Result<Self, ConfigError> {
+        check_duration("visualizer config", "width", self.width)?;
+        check_duration("visualizer config", "minimum UI scale", self.scale.0)?;
+        check_duration("visualizer config", "maximum UI scale", self.scale.1)?;
+        if self.scale.1 < self.scale.0 {
+            return Err(ConfigError::OutOfRange {
+                buffer: "visualizer config",
+                setting: "maximum UI scale",
+                value: self.scale.1,
+                min: self.scale.0,
+                max: f32::INFINITY,
+            });
+        }
+        if !(self.headroom >= 1. && self.headroom.is_finite()) {
+            return Err(ConfigError::OutOfRange {
+                buffer: "visualizer config",
+                setting: "resize headroom",
+                value: self.headroom,
+                min: 1.,
+                max: f32::INFINITY,
+            });
+        }
+        check_duration("visualizer config", "duration", self.duration)?;
+        check_decay("visualizer config", self.decay())?;
+        check_range("visualizer config", self.range)?;
+        check_duration("visualizer config", "mark step", self.step)?;
+        Ok(self)
+    }
+
+    /// Returns the number of elements of the buffers.
+    pub fn size(self: &Self) -> usize {
+        let elements = self.width as f64
+            * self.scale.1 as f64
+            * ELEMENTS_PER_PIXEL
+            * self.headroom.max(1.) as f64;
+        // Allows for the rounding error of the factors, so that a whole number of
+        // elements isn't rounded up to the next one
+        ((elements - 1e-6).ceil() as usize).max(1)
+    }
+
+    /// Returns the duration (in seconds) of the audio data inside the buffers.
+    pub fn duration(self: &Self) -> f32 {
+        self.duration
+    }
+
+    /// Returns the decay in milliseconds, which follows the release of the
+    /// ballistics preset, if one is set.
+    pub fn decay(self: &Self) -> f32 {
+        match self.ballistics {
+            Some(preset) => decay_ms_from_db_per_second(preset.release()),
+            None => self.decay,
+        }
+    }
+
+    /// Returns the ballistics preset of the peak buffers, if set.
+    pub fn ballistics(self: &Self) -> Option<BallisticsPreset> {
+        self.ballistics
+    }
+
+    /// Returns the range the grids and rulers cover.
+    pub fn range(self: &Self) -> (f32, f32) {
+        self.range
+    }
+
+    /// Constructs a [`PeakBuffer`], which follows the ballistics preset if one is set.
+    pub fn peak_buffer(self: &Self) -> PeakBuffer {
+        match self.ballistics {
+            Some(preset) => PeakBuffer::new(self.size(), self.duration, 0.).with_ballistics(preset),
+            None => PeakBuffer::new(self.size(), self.duration, self.decay),
+        }
+    }
+
+    /// Constructs a [`MinimaBuffer`].
+    pub fn minima_buffer(self: &Self) -> MinimaBuffer {
+        MinimaBuffer::new(self.size(), self.duration, self.decay())
+    }
+
+    /// Constructs a [`WaveformBuffer`].
+    pub fn waveform_buffer(self: &Self) -> WaveformBuffer {
+        WaveformBuffer::new(self.size(), self.duration)
+    }
+
+    /// Constructs an [`RMSBuffer`] whose RMS window spans `rms_duration` milliseconds.
+    pub fn rms_buffer(self: &Self, rms_duration: f32) -> RMSBuffer {
+        RMSBuffer::new(self.size(), self.duration, rms_duration)
+    }
+
+    /// Constructs a [`BalanceBuffer`].
+    pub fn balance_buffer(self: &Self) -> BalanceBuffer {
+        BalanceBuffer::new(self.size(), self.duration)
+    }
+
+    /// Constructs a [`CountBuffer`] that counts the samples reaching the threshold,
+    /// whose elements line up with those of the [peak buffer](Self::peak_buffer).
+    pub fn count_buffer(self: &Self, threshold: f32) -> CountBuffer {
+        CountBuffer::new(self.size(), self.duration, threshold)
+    }
+
+    /// Returns the lines of a [`Grid`] at every mark.
+    #[cfg(feature = "vizia")]
+    pub fn grid_marks(self: &Self) -> Vec<f32> {
+        Grid::marks_from_anchor(0., self.step, self.range)
+    }
+
+    /// Returns the lines of a [minor](crate::visualizers::GridModifiers::minor)
+    /// [`Grid`] halfway in between the marks.
+    #[cfg(feature = "vizia")]
+    pub fn minor_grid_marks(self: &Self) -> Vec<f32> {
+        Grid::marks_from_anchor(self.step / 2., self.step, self.range)
+    }
+
+    /// Returns the labels of a [`UnitRuler`] at every mark, in decibels.
+    #[cfg(feature = "vizia")]
+    pub fn ruler_marks(self: &Self) -> Vec<(f32, String)> {
+        UnitRuler::marks_from_anchor(0., self.step, self.range, |value| format!("{}db", value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VisualizerConfig;
+    use crate::utils::{BallisticsPreset, ConfigError, VisualizerBuffer};
+
+    #[test]
+    fn derived_sizes() {
+        // 2 elements per pixel, plus a quarter for resizing
+        assert_eq!(VisualizerConfig::new(800., 10.).size(), 2000);
+        assert_eq!(
+            VisualizerConfig::new(744., 10.)
+                .with_ui_scale(1., 2.)
+                .size(),
+            3720
+        );
+        assert_eq!(
+            VisualizerConfig::new(300., 4.)
+                .with_ui_scale(0.75, 1.5)
+                .with_resize_headroom(1.)
+                .size(),
+            900
+        );
+        // Partial elements are rounded up
+        assert_eq!(VisualizerConfig::new(333.3, 1.).size(), 834);
+        assert_eq!(VisualizerConfig::new(0.1, 1.).size(), 1);
+
+        let config = VisualizerConfig::new(400., 5.).with_ui_scale(1., 1.5);
+        assert_eq!(config.peak_buffer().len(), 1500);
+        assert_eq!(config.minima_buffer().len(), 1500);
+        assert_eq!(config.waveform_buffer().len(), 1500);
+        assert_eq!(config.balance_buffer().len(), 1500);
+        assert_eq!(config.count_buffer(1.).len(), 1500);
+        assert_eq!(config.rms_buffer(300.).len(), 1500);
+        assert_eq!(config.peak_buffer().duration(), Some(5.));
+        assert_eq!(
+            config.with_duration(2.).waveform_buffer().duration(),
+            Some(2.)
+        );
+    }
+
+    #[test]
+    fn derived_decays() {
+        let config = VisualizerConfig::new(800., 10.);
+        assert_eq!(config.decay(), 50.);
+        assert_eq!(config.with_decay(300.).decay(), 300.);
+        assert_eq!(config.peak_buffer().ballistics(), None);
+
+        // A PPM Type II falls by 24 dB in 2.8 seconds
+        let config = config
+            .with_decay(300.)
+            .with_ballistics(BallisticsPreset::PpmType2);
+        assert!((config.decay() - 1404.807).abs() < 0.01);
+        assert_eq!(
+            config.peak_buffer().ballistics(),
+            Some(BallisticsPreset::PpmType2)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "vizia")]
+    fn derived_marks() {
+        let config = VisualizerConfig::new(800., 10.);
+        assert_eq!(config.range(), (-32., 8.));
+        assert_eq!(
+            config.grid_marks(),
+            vec![-30., -24., -18., -12., -6., 0., 6.]
+        );
+        let labels = config.ruler_marks();
+        assert_eq!(labels.len(), 7);
+        assert_eq!(labels[0], (-30., "-30db".to_string()));
+        assert_eq!(labels[5], (0., "0db".to_string()));
+
+        let config = config.with_marks(12.);
+        assert_eq!(config.grid_marks(), vec![-24., -12., 0.]);
+        assert_eq!(config.minor_grid_marks(), vec![-30., -18., -6., 6.]);
+
+        let config = config.with_range((-60., 0.)).with_marks(20.);
+        assert_eq!(config.grid_marks(), vec![-60., -40., -20., 0.]);
+    }
+
+    #[test]
+    fn validation() {
+        let config = VisualizerConfig::new(800., 10.);
+        assert_eq!(config.validate(), Ok(config));
+
+        let setting = |config: VisualizerConfig| match config.validate().unwrap_err() {
+            ConfigError::NonFinite { setting, .. }
+            | ConfigError::OutOfRange { setting, .. }
+            | ConfigError::TooSmall { setting, .. } => setting,
+        };
+        assert_eq!(setting(VisualizerConfig::new(0., 10.)), "width");
+        assert_eq!(setting(VisualizerConfig::new(800., f32::NAN)), "duration");
+        assert_eq!(setting(config.with_ui_scale(0., 1.)), "minimum UI scale");
+        assert_eq!(setting(config.with_ui_scale(2., 1.)), "maximum UI scale");
+        assert_eq!(setting(config.with_resize_headroom(0.5)), "resize headroom");
+        assert_eq!(setting(config.with_decay(-1.)), "decay");
+        assert_eq!(setting(config.with_range((8., -32.))), "range maximum");
+        assert_eq!(setting(config.with_marks(0.)), "mark step");
+    }
+}
@@ -0,0 +1,191 @@
+use std::cell::RefCell;
+
+use nih_plug_vizia::vizia::{prelude::*, vg};
+
+use super::elements;
+use super::time_cursor::{cursor_age, cursor_time, draw_time_cursor, CursorLink};
+use super::{draw_throttled, FrameThrottle, ThrottleModifiers, TimeCursor, TimeCursorModifiers};
+use crate::geometry::{note_bars, NoteBar};
+use crate::utils::{NoteEventBuffer, SharedRead};
+
+/// Piano-roll strip that displays the recent note activity stored inside a
+/// [`NoteEventBuffer`].
+///
+/// Each note is drawn as a horizontal bar, from its note-on to its note-off event.
+/// Its vertical position is determined by its pitch within the note range, and its
+/// opacity by its velocity. Notes that are still held extend to the newest end of
+/// the strip, and notes whose note-on has already scrolled out of the strip start
+/// at its oldest end.
+///
+/// The strip scrolls in sync with a [`Graph`](super::Graph) whose buffer has the
+/// same size and duration as the `NoteEventBuffer`.
+///
+/// # Example
+///
+/// ```
+/// ActivityStrip::new(cx, Data::note_buffer, (36, 84))
+///     .color(Color::rgb(255, 160, 60))
+///     .height(Pixels(64.0));
+/// ```
+///
+/// # Styling
+///
+/// Stylesheets select it as `cyma-activity-strip`. Its `color` is the notes.
+pub struct ActivityStrip<L>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = NoteEventBuffer>,
+{
+    buffer: L,
+    note_range: (u8, u8),
+    // The notes of the current frame, which keep their allocation between frames
+    bars: RefCell<Vec<NoteBar>>,
+    throttle: FrameThrottle,
+    // The cursor that is shared with other views, if linked
+    time_cursor: Option<CursorLink>,
+}
+
+enum ActivityStripEvents {
+    UpdateNoteRange((u8, u8)),
+}
+
+impl<L> ActivityStrip<L>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = NoteEventBuffer>,
+{
+    /// Creates a new `ActivityStrip`.
+    ///
+    /// * `note_range` - The lowest and highest MIDI notes that are displayed
+    pub fn new(cx: &mut Context, buffer: L, note_range: impl Res<(u8, u8)>) -> Handle<Self> {
+        Self {
+            buffer,
+            note_range: note_range.get_val(cx),
+            bars: RefCell::new(Vec::new()),
+            throttle: FrameThrottle::default(),
+            time_cursor: None,
+        }
+        .build(cx, |_| {})
+        .note_range(note_range)
+    }
+}
+
+impl<L> View for ActivityStrip<L>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = NoteEventBuffer>,
+{
+    fn element(&self) -> Option<&'static str> {
+        Some(elements::ACTIVITY_STRIP)
+    }
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|e, _| match e {
+            ActivityStripEvents::UpdateNoteRange(r) => self.note_range = *r,
+        });
+
+        if let Some(link) = &self.time_cursor {
+            event.map(|window_event, _| match window_event {
+                WindowEvent::MouseMove(cursor_x, _) => {
+                    let bounds = cx.bounds();
+                    let time = (cursor_x - bounds.x) / bounds.w;
+                    let duration = self.buffer.get(cx).read_shared(|_| {}).duration();
+                    link.hover(cursor_age(time, (0., duration)));
+                }
+                WindowEvent::MouseLeave => link.leave(),
+                _ => {}
+            });
+        }
+    }
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        draw_throttled(cx, canvas, &self.throttle, |cx, canvas| {
+            let bounds = cx.bounds();
+
+            let x = bounds.x;
+            let y = bounds.y;
+            let w = bounds.w;
+            let h = bounds.h;
+
+            let binding = self.buffer.get(cx);
+            let buffer = binding.read_shared(|_| {});
+
+            if buffer.is_empty() {
+                return;
+            }
+
+            let (low, high) = (
+                self.note_range.0.min(self.note_range.1),
+                self.note_range.0.max(self.note_range.1),
+            );
+            let color: vg::Color = cx.font_color().into();
+
+            let mut bars = self.bars.borrow_mut();
+            note_bars(&buffer, (low, high), &mut bars);
+
+            // The bars are ordered by velocity, so that each opacity is filled at once
+            for run in bars.chunk_by(|a, b| a.velocity == b.velocity) {
+                let mut path = vg::Path::new();
+                for bar in run {
+                    let (left, top, width, height) =
+                        bar.rect(bounds.into(), (low, high), buffer.len());
+                    path.rect(left, top, width, height);
+                }
+
+                let mut color = color;
+                color.a *= run[0].velocity;
+                canvas.fill_path(&path, &vg::Paint::color(color));
+            }
+
+            // The shared cursor, while another linked view is hovered
+            let cursor = self
+                .time_cursor
+                .as_ref()
+                .and_then(|link| link.age())
+                .and_then(|age| cursor_time(age, (0., buffer.duration())));
+            if let Some(time) = cursor {
+                draw_time_cursor(cx, canvas, (x + w * time, y), (x + w * time, y + h));
+            }
+        });
+    }
+}
+
+pub trait ActivityStripModifiers {
+    /// Sets the lowest and highest MIDI notes that are displayed.
+    fn note_range(self, note_range: impl Res<(u8, u8)>) -> Self;
+}
+
+impl<'a, L> ActivityStripModifiers for Handle<'a, ActivityStrip<L>>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = NoteEventBuffer>,
+{
+    fn note_range(mut self, note_range: impl Res<(u8, u8)>) -> Self {
+        let e = self.entity();
+
+        note_range.set_or_bind(self.context(), e, move |cx, r| {
+            (*cx).emit_to(e, ActivityStripEvents::UpdateNoteRange(r));
+        });
+
+        self
+    }
+}
+
+impl<'a, L> TimeCursorModifiers for Handle<'a, ActivityStrip<L>>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = NoteEventBuffer>,
+{
+    fn time_cursor<C: Lens<Target = TimeCursor>>(mut self, cursor: C) -> Self {
+        let link = CursorLink::new(&mut self, cursor);
+        self.modify(|strip| strip.time_cursor = Some(link))
+    }
+}
+
+impl<'a, L> ThrottleModifiers for Handle<'a, ActivityStrip<L>>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = NoteEventBuffer>,
+{
+    fn max_fps(self, max_fps: f32) -> Self {
+        self.modify(|view| view.throttle.set_max_fps(max_fps))
+    }
+}
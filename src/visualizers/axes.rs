@@ -0,0 +1,161 @@
+use nih_plug_vizia::vizia::prelude::{BoundingBox, Orientation};
+
+use super::GraphDirection;
+
+/// Maps the time and value axes of a [`Graph`](super::Graph) or a
+/// [`Grid`](super::Grid) onto their bounds.
+///
+/// Positions on both axes are given as fractions. Time runs from 0 (the oldest
+/// element) to 1 (the newest), and values run from 0 (the bottom of the range) to 1
+/// (the top). By default, time runs from left to right and values rise upwards.
+/// With a horizontal value axis, values grow to the right and time runs from top to
+/// bottom instead. Mirroring flips the result horizontally, for right-to-left
+/// layouts, and a top-down [`GraphDirection`] flips the value axis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Axes {
+    pub(crate) bounds: BoundingBox,
+    pub(crate) value_axis: Orientation,
+    pub(crate) mirror_x: bool,
+    pub(crate) direction: GraphDirection,
+}
+
+impl Axes {
+    pub(crate) fn new(bounds: BoundingBox, value_axis: Orientation, mirror_x: bool) -> Self {
+        Self {
+            bounds,
+            value_axis,
+            mirror_x,
+            direction: GraphDirection::BottomUp,
+        }
+    }
+
+    /// Runs the value axis in the given direction.
+    pub(crate) fn with_direction(self, direction: GraphDirection) -> Self {
+        Self { direction, ..self }
+    }
+
+    /// Returns the point at a position in time and a normalized value.
+    pub(crate) fn point(self: &Self, time: f32, value: f32) -> (f32, f32) {
+        let value = self.direction.apply(value);
+        let (fx, fy) = match self.value_axis {
+            Orientation::Vertical => (time, 1. - value),
+            Orientation::Horizontal => (value, time),
+        };
+        let fx = if self.mirror_x { 1. - fx } else { fx };
+        (
+            self.bounds.x + self.bounds.w * fx,
+            self.bounds.y + self.bounds.h * fy,
+        )
+    }
+
+    /// Returns the rectangle spanning the given times and normalized values, as its
+    /// left, top, width and height.
+    pub(crate) fn rect(self: &Self, time: (f32, f32), value: (f32, f32)) -> (f32, f32, f32, f32) {
+        let (x0, y0) = self.point(time.0, value.0);
+        let (x1, y1) = self.point(time.1, value.1);
+        (x0.min(x1), y0.min(y1), (x1 - x0).abs(), (y1 - y0).abs())
+    }
+
+    /// Returns the position in time below a point, which lies outside of 0 to 1 if
+    /// the point is outside of the bounds.
+    pub(crate) fn time_at(self: &Self, point: (f32, f32)) -> f32 {
+        match self.value_axis {
+            Orientation::Vertical => {
+                let fx = (point.0 - self.bounds.x) / self.bounds.w;
+                if self.mirror_x {
+                    1. - fx
+                } else {
+                    fx
+                }
+            }
+            Orientation::Horizontal => (point.1 - self.bounds.y) / self.bounds.h,
+        }
+    }
+
+    /// Returns the size of the time axis, e.g. to tell how far apart elements are.
+    pub(crate) fn time_extent(self: &Self) -> f32 {
+        match self.value_axis {
+            Orientation::Vertical => self.bounds.w,
+            Orientation::Horizontal => self.bounds.h,
+        }
+    }
+
+    /// Returns the size of the value axis, e.g. to lay out segments along it.
+    pub(crate) fn value_extent(self: &Self) -> f32 {
+        match self.value_axis {
+            Orientation::Vertical => self.bounds.h,
+            Orientation::Horizontal => self.bounds.w,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Axes;
+    use crate::visualizers::GraphDirection;
+    use nih_plug_vizia::vizia::prelude::{BoundingBox, Orientation};
+
+    const BOUNDS: BoundingBox = BoundingBox {
+        x: 10.,
+        y: 20.,
+        w: 200.,
+        h: 100.,
+    };
+
+    #[test]
+    fn default_axes() {
+        let axes = Axes::new(BOUNDS, Orientation::Vertical, false);
+        // The oldest element at the bottom of the range, and the newest at the top
+        assert_eq!(axes.point(0., 0.), (10., 120.));
+        assert_eq!(axes.point(1., 1.), (210., 20.));
+        assert_eq!(axes.point(0.5, 0.25), (110., 95.));
+        assert_eq!(axes.time_at((110., 50.)), 0.5);
+        assert_eq!(axes.rect((0.5, 1.), (0., 1.)), (110., 20., 100., 100.));
+    }
+
+    #[test]
+    fn horizontal_value_axis() {
+        let axes = Axes::new(BOUNDS, Orientation::Horizontal, false);
+        // Values grow to the right, and time runs downwards
+        assert_eq!(axes.point(0., 0.), (10., 20.));
+        assert_eq!(axes.point(1., 1.), (210., 120.));
+        assert_eq!(axes.point(0.5, 0.25), (60., 70.));
+        assert_eq!(axes.time_at((60., 70.)), 0.5);
+        assert_eq!(axes.time_extent(), 100.);
+        assert_eq!(axes.value_extent(), 200.);
+    }
+
+    #[test]
+    fn mirrored() {
+        let axes = Axes::new(BOUNDS, Orientation::Vertical, true);
+        // The newest element on the left
+        assert_eq!(axes.point(1., 0.), (10., 120.));
+        assert_eq!(axes.point(0.25, 1.), (160., 20.));
+        assert_eq!(axes.time_at((160., 20.)), 0.25);
+        assert_eq!(axes.rect((0.5, 1.), (0., 1.)), (10., 20., 100., 100.));
+
+        let axes = Axes::new(BOUNDS, Orientation::Horizontal, true);
+        assert_eq!(axes.point(0.5, 0.25), (160., 70.));
+    }
+
+    #[test]
+    fn top_down() {
+        let axes =
+            Axes::new(BOUNDS, Orientation::Vertical, false).with_direction(GraphDirection::TopDown);
+        // The bottom of the range at the top, and values growing downwards
+        assert_eq!(axes.point(0., 0.), (10., 20.));
+        assert_eq!(axes.point(1., 1.), (210., 120.));
+        assert_eq!(axes.point(0.5, 0.25), (110., 45.));
+        // Time is unaffected
+        assert_eq!(axes.time_at((110., 45.)), 0.5);
+        assert_eq!(axes.rect((0.5, 1.), (0., 0.25)), (110., 20., 100., 25.));
+
+        // Values grow to the left along a horizontal axis, unless it's mirrored too
+        let axes = Axes::new(BOUNDS, Orientation::Horizontal, false)
+            .with_direction(GraphDirection::TopDown);
+        assert_eq!(axes.point(0.5, 0.25), (160., 70.));
+        let axes = Axes::new(BOUNDS, Orientation::Horizontal, true)
+            .with_direction(GraphDirection::TopDown);
+        assert_eq!(axes.point(0.5, 0.25), (60., 70.));
+    }
+}
@@ -0,0 +1,190 @@
+use std::time::{Duration, Instant};
+
+/// How long a view fades out its contents after a clear, unless told otherwise.
+const DEFAULT_DURATION: Duration = Duration::from_millis(250);
+
+pub trait ClearFadeModifiers {
+    /// Fades out what the view showed when its buffer is cleared, instead of
+    /// snapping to an empty view in a single frame.
+    ///
+    /// Clears happen when the sample rate changes, or when the plug-in resets its
+    /// buffers, and look like a glitch without this. The view keeps the last frame it
+    /// drew before the clear, and draws it sinking towards the level it's filled from
+    /// while fading out, for a quarter of a second. The buffer itself is cleared right
+    /// away - new elements are drawn at full opacity on top of the fading contents,
+    /// and everything that is read from the buffer is correct immediately.
+    ///
+    /// Only buffers that count their [clears](crate::utils::VisualizerBuffer::clears)
+    /// are faded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+    ///     .fade_clears(true)
+    ///     .color(Color::rgba(0, 0, 0, 160));
+    /// ```
+    fn fade_clears(self, fade: bool) -> Self;
+    /// Fades out clears like [`fade_clears()`](Self::fade_clears), over the given
+    /// duration.
+    fn clear_fade_duration(self, duration: Duration) -> Self;
+}
+
+/// The fade of a view's contents after its buffer was cleared; See
+/// [`ClearFadeModifiers`].
+///
+/// Each frame, the view passes the buffer's clear count along with the points it
+/// drew in the previous frame. When the count changed, those points become the
+/// ghost, which the view draws underneath its live contents until the fade ends.
+pub(crate) struct ClearFade<P> {
+    duration: Duration,
+    // The clear count the buffer reported in the previous frame
+    clears: Option<u64>,
+    // When the current fade started, if any
+    started: Option<Instant>,
+    // The points that were drawn before the clear; Swapped with the view's points,
+    // so that starting a fade doesn't allocate
+    ghost: Vec<P>,
+}
+
+impl<P> Default for ClearFade<P> {
+    fn default() -> Self {
+        Self::new(DEFAULT_DURATION)
+    }
+}
+
+impl<P> ClearFade<P> {
+    pub(crate) fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            clears: None,
+            started: None,
+            ghost: Vec::new(),
+        }
+    }
+
+    pub(crate) fn set_duration(self: &mut Self, duration: Duration) {
+        self.duration = duration;
+    }
+
+    /// Starts a fade if the buffer was cleared since the previous frame.
+    ///
+    /// `shown` holds the points the view drew in the previous frame, and is about to
+    /// be refilled; When a fade starts, they're kept as its ghost. A clear during a
+    /// fade keeps fading the same ghost, since the previous frame only showed what
+    /// arrived after the first clear.
+    pub(crate) fn observe(self: &mut Self, now: Instant, clears: Option<u64>, shown: &mut Vec<P>) {
+        let cleared = matches!((self.clears, clears), (Some(last), Some(clears)) if last != clears);
+        self.clears = clears;
+        if cleared && self.progress(now).is_none() && !shown.is_empty() {
+            std::mem::swap(&mut self.ghost, shown);
+            self.started = Some(now);
+        }
+    }
+
+    /// Returns how far the fade is at `now`, from 0 to 1, or `None` if the view
+    /// isn't fading.
+    pub(crate) fn progress(self: &Self, now: Instant) -> Option<f32> {
+        let elapsed = now.saturating_duration_since(self.started?);
+        (elapsed < self.duration).then(|| elapsed.as_secs_f32() / self.duration.as_secs_f32())
+    }
+
+    /// Returns the ghost and the fade's progress at `now`, or `None` if the view
+    /// isn't fading.
+    pub(crate) fn ghost(self: &Self, now: Instant) -> Option<(&[P], f32)> {
+        self.progress(now)
+            .map(|progress| (self.ghost.as_slice(), progress))
+    }
+}
+
+/// Moves a normalized value of the ghost towards the level the view is filled from,
+/// as the fade progresses.
+pub(crate) fn sink(value: f32, floor: f32, progress: f32) -> f32 {
+    value + (floor - value) * progress
+}
+
+/// The opacity of the ghost, as the fade progresses.
+pub(crate) fn ghost_alpha(progress: f32) -> f32 {
+    1. - progress
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ghost_alpha, sink, ClearFade};
+    use std::time::{Duration, Instant};
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn fades_after_clear() {
+        let start = Instant::now();
+        let mut fade = ClearFade::new(ms(250));
+
+        // The first frame only learns the count
+        let mut shown = vec![0.5, 0.75];
+        fade.observe(start, Some(3), &mut shown);
+        assert!(fade.ghost(start).is_none());
+
+        // Unchanged counts don't fade
+        fade.observe(start + ms(16), Some(3), &mut shown);
+        assert!(fade.ghost(start + ms(16)).is_none());
+        assert_eq!(shown, vec![0.5, 0.75]);
+
+        // A clear keeps the previous frame as the ghost
+        let cleared = start + ms(32);
+        fade.observe(cleared, Some(4), &mut shown);
+        let (ghost, progress) = fade.ghost(cleared).unwrap();
+        assert_eq!((ghost, progress), (&[0.5, 0.75][..], 0.));
+
+        let (_, progress) = fade.ghost(cleared + ms(125)).unwrap();
+        assert!((progress - 0.5).abs() < 1e-6);
+        assert!((ghost_alpha(progress) - 0.5).abs() < 1e-6);
+        assert!((sink(0.75, 0.25, progress) - 0.5).abs() < 1e-6);
+
+        // The genuinely empty state once the fade ends
+        assert!(fade.ghost(cleared + ms(250)).is_none());
+        assert!(fade.ghost(cleared + ms(1000)).is_none());
+    }
+
+    #[test]
+    fn clears_during_fade() {
+        let start = Instant::now();
+        let mut fade = ClearFade::new(ms(250));
+        fade.observe(start, Some(0), &mut vec![]);
+        fade.observe(start, Some(1), &mut vec![1.0]);
+
+        // The previous frame already showed the cleared buffer, so the ghost is kept
+        let mut shown = vec![0.0];
+        fade.observe(start + ms(100), Some(2), &mut shown);
+        let (ghost, progress) = fade.ghost(start + ms(100)).unwrap();
+        assert_eq!(ghost, &[1.0]);
+        assert!((progress - 0.4).abs() < 1e-6);
+        assert_eq!(shown, vec![0.0]);
+
+        // After the fade, the next clear fades the frame before it
+        fade.observe(start + ms(300), Some(3), &mut shown);
+        assert_eq!(fade.ghost(start + ms(300)).unwrap().0, &[0.0]);
+    }
+
+    #[test]
+    fn without_clears() {
+        let start = Instant::now();
+
+        // Buffers that don't count clears, and views that drew nothing, don't fade
+        let mut fade = ClearFade::new(ms(250));
+        fade.observe(start, None, &mut vec![1.0]);
+        fade.observe(start, None, &mut vec![1.0]);
+        assert!(fade.ghost(start).is_none());
+        fade.observe(start, Some(0), &mut vec![]);
+        fade.observe(start, Some(1), &mut vec![]);
+        assert!(fade.ghost(start).is_none());
+
+        // Neither does a fade without a duration
+        let mut fade = ClearFade::new(Duration::ZERO);
+        fade.observe(start, Some(0), &mut vec![1.0]);
+        fade.observe(start, Some(1), &mut vec![1.0]);
+        assert!(fade.ghost(start).is_none());
+    }
+}
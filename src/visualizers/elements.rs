@@ -0,0 +1,298 @@
+//! The element names and classes that stylesheets can target Cyma's views with.
+//!
+//! Every view has an element name starting with `cyma-`, so that its selectors
+//! don't collide with the elements of vizia or your own views:
+//!
+//! | View | Element | Reads |
+//! | --- | --- | --- |
+//! | [`Graph`](super::Graph) | `cyma-graph` | `color`, `background-color` |
+//! | [`GraphGroup`](super::GraphGroup) | `cyma-graph-group` | - |
+//! | [`SplitGraph`](super::SplitGraph) | `cyma-split-graph` | `color`, `background-color` |
+//! | [`SpectrumAnalyzer`](super::SpectrumAnalyzer) | `cyma-spectrum-analyzer` | `color`, `background-color` |
+//! | [`Histogram`](super::Histogram) | `cyma-histogram` | `color`, `background-color` |
+//! | [`Oscilloscope`](super::Oscilloscope) | `cyma-oscilloscope` | `color`, `background-color` |
+//! | [`Meter`](super::Meter) | `cyma-meter` | `color`, `background-color` |
+//! | [`Sparkline`](super::Sparkline) | `cyma-sparkline` | `color` |
+//! | [`Grid`](super::Grid) | `cyma-grid` | `color` |
+//! | [`UnitRuler`](super::UnitRuler) | `cyma-unit-ruler` | `color`, `font-size` |
+//! | [`Legend`](super::Legend) | `cyma-legend` | `color`, `font-size` |
+//! | A [`Legend`](super::Legend)'s entries | `cyma-legend-entry` | `color`, `font-size` |
+//! | [`Lissajous`](super::Lissajous) | `cyma-lissajous` | `color` |
+//! | [`LissajousGrid`](super::LissajousGrid) | `cyma-lissajous-grid` | `color`, `background-color` |
+//! | [`Waveform`](super::Waveform) | `cyma-waveform` | `color` |
+//! | [`ActivityStrip`](super::ActivityStrip) | `cyma-activity-strip` | `color` |
+//!
+//! The names are also available as constants, e.g. [`GRAPH`]. They're part of the
+//! public API, and only change in a breaking release.
+//!
+//! Views can be given one of the [variant classes](VariantModifiers), which the
+//! [themes](crate::theme) style along with the elements:
+//!
+//! ```css
+//! cyma-graph.secondary {
+//!     color: rgba(255, 255, 255, 0.3);
+//! }
+//! ```
+//!
+//! # Migrating
+//!
+//! Before, the elements were named without the `cyma-` prefix, e.g. `graph` and
+//! `unit-ruler`. vizia matches elements by a single name, so the old names no
+//! longer match. Until you've renamed the selectors in your stylesheets,
+//! [`migrate_stylesheet()`] renames them when the stylesheet is added:
+//!
+//! ```
+//! cx.add_stylesheet(cyma::visualizers::elements::migrate_stylesheet(include_str!("style.css")))
+//!     .expect("Failed to load the stylesheet");
+//! ```
+//!
+//! This will be removed in the next release.
+
+use nih_plug_vizia::vizia::prelude::{Handle, StyleModifiers, View};
+
+pub const GRAPH: &str = "cyma-graph";
+pub const GRAPH_GROUP: &str = "cyma-graph-group";
+pub const SPLIT_GRAPH: &str = "cyma-split-graph";
+pub const SPECTRUM_ANALYZER: &str = "cyma-spectrum-analyzer";
+pub const HISTOGRAM: &str = "cyma-histogram";
+pub const OSCILLOSCOPE: &str = "cyma-oscilloscope";
+pub const METER: &str = "cyma-meter";
+pub const SPARKLINE: &str = "cyma-sparkline";
+pub const GRID: &str = "cyma-grid";
+pub const UNIT_RULER: &str = "cyma-unit-ruler";
+pub const LEGEND: &str = "cyma-legend";
+pub const LEGEND_ENTRY: &str = "cyma-legend-entry";
+pub const LISSAJOUS: &str = "cyma-lissajous";
+pub const LISSAJOUS_GRID: &str = "cyma-lissajous-grid";
+pub const WAVEFORM: &str = "cyma-waveform";
+pub const ACTIVITY_STRIP: &str = "cyma-activity-strip";
+
+/// The class of a view that is drawn behind or alongside another one of its kind,
+/// e.g. the input of a gain reduction graph; See [`VariantModifiers::secondary()`].
+pub const SECONDARY_CLASS: &str = "secondary";
+/// The class of a view that is meant to stand out; See
+/// [`VariantModifiers::accent()`].
+pub const ACCENT_CLASS: &str = "accent";
+/// The class of a grid that draws minor lines; See
+/// [`GridModifiers::minor()`](super::GridModifiers::minor).
+pub const MINOR_CLASS: &str = "minor";
+
+// Every element, along with the name it had before the prefix
+pub(crate) const RENAMED: [(&str, &str); 16] = [
+    (GRAPH, "graph"),
+    (GRAPH_GROUP, "graph-group"),
+    (SPLIT_GRAPH, "split-graph"),
+    (SPECTRUM_ANALYZER, "spectrum-analyzer"),
+    (HISTOGRAM, "histogram"),
+    (OSCILLOSCOPE, "oscilloscope"),
+    (METER, "meter"),
+    (SPARKLINE, "sparkline"),
+    (GRID, "grid"),
+    (UNIT_RULER, "unit-ruler"),
+    (LEGEND, "legend"),
+    (LEGEND_ENTRY, "legend-entry"),
+    (LISSAJOUS, "lissajous"),
+    (LISSAJOUS_GRID, "lissajous-grid"),
+    (WAVEFORM, "waveform"),
+    (ACTIVITY_STRIP, "activity-strip"),
+];
+
+pub trait VariantModifiers {
+    /// Marks the view as secondary, e.g. the input graph behind a gain reduction
+    /// graph, which the themes draw in a subtler color.
+    ///
+    /// This adds the [`SECONDARY_CLASS`].
+    fn secondary(self) -> Self;
+    /// Marks the view as an accent, e.g. the output meter of a channel strip, which
+    /// the themes draw in a brighter color.
+    ///
+    /// This adds the [`ACCENT_CLASS`].
+    fn accent(self) -> Self;
+}
+
+impl<'a, V: View> VariantModifiers for Handle<'a, V> {
+    fn secondary(self) -> Self {
+        self.class(SECONDARY_CLASS)
+    }
+
+    fn accent(self) -> Self {
+        self.class(ACCENT_CLASS)
+    }
+}
+
+/// Renames the elements that a stylesheet written for an older version of Cyma
+/// selects, e.g. `graph` to `cyma-graph`; See [Migrating](self#migrating).
+///
+/// Only type selectors are renamed - classes, IDs, pseudo-classes and everything
+/// inside the declaration blocks are kept, so `.graph` or `display: grid` stay as
+/// they are.
+pub fn migrate_stylesheet(css: &str) -> String {
+    let mut out = String::with_capacity(css.len() + 64);
+    let mut depth = 0usize;
+    let mut comment = false;
+    let mut rest = css;
+    while let Some(c) = rest.chars().next() {
+        if comment {
+            if rest.starts_with("*/") {
+                comment = false;
+                out.push_str("*/");
+                rest = &rest[2..];
+            } else {
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+            continue;
+        }
+        if rest.starts_with("/*") {
+            comment = true;
+            out.push_str("/*");
+            rest = &rest[2..];
+            continue;
+        }
+
+        if is_ident(c) {
+            let len = rest.find(|c| !is_ident(c)).unwrap_or(rest.len());
+            let ident = &rest[..len];
+            let selects_element =
+                depth == 0 && !matches!(out.chars().last(), Some('.' | '#' | ':' | '@' | '-'));
+            match RENAMED.iter().find(|(_, old)| *old == ident) {
+                Some((new, _)) if selects_element => out.push_str(new),
+                _ => out.push_str(ident),
+            }
+            rest = &rest[len..];
+            continue;
+        }
+
+        match c {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{migrate_stylesheet, RENAMED};
+
+    // The documented element of each view, along with its source; A view that
+    // returns anything else from `element()` fails here, so renames are deliberate
+    const VIEWS: [(&str, &str, &str); 16] = [
+        ("cyma-graph", "GRAPH", include_str!("graph.rs")),
+        (
+            "cyma-graph-group",
+            "GRAPH_GROUP",
+            include_str!("graph_group.rs"),
+        ),
+        (
+            "cyma-split-graph",
+            "SPLIT_GRAPH",
+            include_str!("split_graph.rs"),
+        ),
+        (
+            "cyma-spectrum-analyzer",
+            "SPECTRUM_ANALYZER",
+            include_str!("spectrum_analyzer.rs"),
+        ),
+        ("cyma-histogram", "HISTOGRAM", include_str!("histogram.rs")),
+        (
+            "cyma-oscilloscope",
+            "OSCILLOSCOPE",
+            include_str!("oscilloscope.rs"),
+        ),
+        ("cyma-meter", "METER", include_str!("meter.rs")),
+        ("cyma-sparkline", "SPARKLINE", include_str!("sparkline.rs")),
+        ("cyma-grid", "GRID", include_str!("grid.rs")),
+        (
+            "cyma-unit-ruler",
+            "UNIT_RULER",
+            include_str!("unit_ruler.rs"),
+        ),
+        ("cyma-legend", "LEGEND", include_str!("legend.rs")),
+        (
+            "cyma-legend-entry",
+            "LEGEND_ENTRY",
+            include_str!("legend.rs"),
+        ),
+        ("cyma-lissajous", "LISSAJOUS", include_str!("lissajous.rs")),
+        (
+            "cyma-lissajous-grid",
+            "LISSAJOUS_GRID",
+            include_str!("lissajous.rs"),
+        ),
+        ("cyma-waveform", "WAVEFORM", include_str!("waveform.rs")),
+        (
+            "cyma-activity-strip",
+            "ACTIVITY_STRIP",
+            include_str!("activity_strip.rs"),
+        ),
+    ];
+
+    #[test]
+    fn documented_elements() {
+        let docs = include_str!("elements.rs");
+        for ((element, constant, source), (new, _)) in VIEWS.iter().zip(RENAMED) {
+            assert_eq!(*element, new);
+            assert!(
+                source.contains(&format!("Some(elements::{})", constant)),
+                "{} doesn't return {}",
+                element,
+                constant
+            );
+            assert!(
+                docs.contains(&format!("| `{}` |", element)),
+                "{} isn't documented",
+                element
+            );
+        }
+
+        // Views only return the constants
+        for (_, _, source) in VIEWS {
+            for line in source.lines().filter(|line| line.contains("Some(\"")) {
+                assert!(!line.trim().starts_with("Some(\""), "{:?}", line);
+            }
+        }
+    }
+
+    #[test]
+    fn migrates_type_selectors() {
+        let css = "\
+/* A graph behind a grid */
+.dark graph, split-graph:hover {
+    color: #fff;
+}
+
+grid.minor > unit-ruler {
+    display: grid;
+}
+
+.graph #meter cyma-meter legend-entry {}
+";
+        assert_eq!(
+            migrate_stylesheet(css),
+            "\
+/* A graph behind a grid */
+.dark cyma-graph, cyma-split-graph:hover {
+    color: #fff;
+}
+
+cyma-grid.minor > cyma-unit-ruler {
+    display: grid;
+}
+
+.graph #meter cyma-meter cyma-legend-entry {}
+"
+        );
+
+        // Stylesheets that are already migrated stay the same
+        let migrated = migrate_stylesheet(css);
+        assert_eq!(migrate_stylesheet(&migrated), migrated);
+    }
+}
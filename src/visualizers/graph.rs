@@ -1,8 +1,33 @@
-use super::{FillFrom, FillModifiers, RangeModifiers};
-use crate::utils::{ValueScaling, VisualizerBuffer};
+use super::clear_fade::{ghost_alpha, sink, ClearFade, ClearFadeModifiers};
+use super::elements;
+use super::graph_group::PathBatches;
+use super::idle::{build_idle, draw_scanline, IdleEvents, IdleState};
+use super::liveness::build_stale_indicator;
+use super::overlay::{readout_rect, readout_spacing};
+use super::padding::InnerPadding;
+use super::plot_area::PlotArea;
+use super::quality::{coarsen_points, draw_measured};
+use super::time_cursor::{cursor_age, cursor_time, draw_time_cursor, CursorLink};
+use super::{
+    draw_capture_progress, draw_throttled, redraw_on_write, refill, AdaptiveQualityModifiers,
+    AutoRangeModifiers, AxisModifiers, Degradation, FillFrom, FillModifiers, FrameStats,
+    FrameThrottle, GraphDirection, IdleModifiers, IdleStyle, OverlayRegistry, OverlaySlot,
+    PaddingModifiers, RangeModifiers, RangeSource, ReadoutPosition, StaleIndicatorModifiers,
+    ThrottleModifiers, TimeCursor, TimeCursorModifiers,
+};
+use crate::geometry::graph_points;
+use crate::utils::{
+    default_formatter, format_scaled, marker_age, scaled_extent, AutoRange, Heartbeat, MarkerStore,
+    NormalizedRead, OffScale, RangeSegments, SharedRange, SharedRead, Smoothing, SmoothingScratch,
+    ValueFormatter, ValueScaling, VisualizerBuffer, VisualizerLens,
+};
 
 use nih_plug_vizia::vizia::{prelude::*, vg};
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::ops::Range;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 /// Real-time graph displaying information that is stored inside a buffer
 ///
@@ -22,25 +47,306 @@ use std::sync::{Arc, Mutex};
 ///
 /// The graph displays the range from -32.0dB to 8dB. It scales the values as
 /// decibels, and a stroke and fill (background) color is provided.
+///
+/// The buffer can be shared as an `Arc<Mutex<B>>` or, when several views draw it,
+/// as an `Arc<RwLock<B>>`; See [`SharedRead`].
+///
+/// # Styling
+///
+/// Stylesheets select it as `cyma-graph`. Its `color` is the trace, and its
+/// `background-color` the fill below it. With
+/// [`secondary()`](super::VariantModifiers::secondary) or
+/// [`accent()`](super::VariantModifiers::accent), the themes draw it in a subtler or
+/// a brighter color.
 pub struct Graph<L, I>
 where
-    L: Lens<Target = Arc<Mutex<I>>>,
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
     I: VisualizerBuffer<f32> + 'static,
 {
     buffer: L,
     range: (f32, f32),
     scaling: ValueScaling,
     fill_from: FillFrom,
+    smoothing: Option<Smoothing>,
+    smoothing_scratch: RefCell<SmoothingScratch>,
+    bipolar: Option<Bipolar>,
+    zero_line: Option<Color>,
+    interactive: bool,
+    // The slot below the cursor, while hovering an interactive graph
+    hovered_slot: Option<usize>,
+    // The cursor that is shared with other views, if linked
+    time_cursor: Option<CursorLink>,
+    auto_range: Option<LiveRange>,
+    smooth_scrolling: bool,
+    throttle: FrameThrottle,
+    // Reused from one frame to the next, so that drawing doesn't allocate it
+    points: RefCell<Vec<(f32, f32)>>,
+    // Fades out the previous points after the buffer is cleared, if enabled
+    clear_fade: Option<RefCell<ClearFade<(f32, f32)>>>,
+    // The tint of the elements during which the host's transport was stopped
+    transport_shading: Option<Color>,
+    // The tint behind the elements of each tag, and the color of the dividers
+    // between runs of different tags
+    tag_tints: Vec<(u8, Color)>,
+    tag_dividers: Option<Color>,
+    // The color of the band one standard deviation around each element's mean
+    deviation_band: Option<Color>,
+    // Replaces the range with a broken axis, if set
+    segments: Option<RangeSegments>,
+    // Reused from one frame to the next, like the points
+    band: RefCell<Vec<Option<(f32, f32, f32)>>>,
+    // The markers of the elements beyond the end and the start of the range, if any
+    over_range: Option<(OffScaleStyle, Color)>,
+    under_range: Option<(OffScaleStyle, Color)>,
+    // The edge each point lies beyond, reused from one frame to the next
+    off_scale: RefCell<Vec<Option<OffScale>>>,
+    // The annotations pinned to moments of the buffer's history, the colors their
+    // indices pick from, and whether clicking adds and removes them
+    markers: Option<Arc<Mutex<MarkerStore>>>,
+    marker_colors: Vec<Color>,
+    editable_markers: bool,
+    // The last position of the cursor, which a click adds a marker at
+    marker_cursor: (f32, f32),
+    // The labels the markers were last placed with, to skip unchanged updates
+    marker_labels: Vec<MarkerLabel>,
+    readout: Option<Readout>,
+    // Whether the last readout update was skipped, while the readout is slowed down
+    readout_skipped: bool,
+    // Formats the readout and the tooltip, instead of the default for the scaling
+    formatter: Option<Box<dyn ValueFormatter>>,
+    // Reused for formatting, and the text the readout shows, so that an unchanged
+    // readout doesn't allocate
+    text: String,
+    readout_text: String,
+    quality: Option<FrameStats>,
+    idle: Option<IdleState>,
+    value_axis: Orientation,
+    mirror_x: bool,
+    direction: GraphDirection,
+    padding: InnerPadding,
+    style: GraphStyle,
+    // The colors of the segments above each level, in ascending order
+    level_colors: Vec<(f32, Color)>,
+    partial_segments: PartialSegments,
+    // Reused from one frame to the next, like the points
+    lit_segments: RefCell<Vec<(usize, bool)>>,
+}
+
+/// How a [`Graph`] draws its values; See [`graph_style()`](GraphModifiers::graph_style).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum GraphStyle {
+    /// A continuous trace, filled towards the fill level.
+    #[default]
+    Line,
+    /// Discrete segments, like the LEDs of a hardware meter.
+    ///
+    /// Each element is drawn as a stack of segments, from the fill level towards its
+    /// value. The segments are laid out from the fill level, so a value lights up
+    /// the same segments no matter how large the graph is drawn.
+    Segments {
+        /// The height of each segment along the value axis, in logical pixels.
+        segment_height_px: f32,
+        /// The space between two segments, in logical pixels.
+        gap_px: f32,
+    },
+}
+
+/// What a [`Graph`] that is drawn as [`GraphStyle::Segments`] does with the segment
+/// that a value lies inside of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PartialSegments {
+    /// The segment stays dark, so only the segments below the value light up.
+    #[default]
+    Drop,
+    /// The segment lights up dimmed.
+    Dim,
+}
+
+/// The opacity of a partially covered segment, relative to a fully covered one.
+const DIMMED_SEGMENT_ALPHA: f32 = 0.35;
+
+/// How a [`Graph`] marks the elements whose values lie beyond its range; See
+/// [`over_range_style()`](GraphModifiers::over_range_style).
+///
+/// The trace is flattened against the edge of the range, so each run of elements
+/// beyond it gets one marker.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OffScaleStyle {
+    /// A triangle on the edge, pointing off the scale, in the middle of the run.
+    Triangle {
+        /// The height and width of the triangle, in logical pixels.
+        size_px: f32,
+    },
+    /// A stroke along the edge, across the entire run.
+    Stroke {
+        /// The width of the stroke, in logical pixels.
+        width_px: f32,
+    },
+}
+
+/// A readout of the newest value, anchored to a corner of the graph.
+struct Readout {
+    position: ReadoutPosition,
+    // In logical pixels
+    inset: f32,
+    // Where the readout's area is registered, if anywhere
+    overlay: Option<OverlaySlot>,
+}
+
+/// The size of the readout's text box, in logical pixels.
+const READOUT_SIZE: (f32, f32) = (72.0, 20.0);
+
+enum LiveRange {
+    // The graph updates the range with its values whenever it is drawn
+    Drive(Arc<Mutex<AutoRange>>),
+    Follow(RangeSource),
+}
+
+struct Bipolar {
+    zero: f32,
+    positive: Color,
+    negative: Color,
 }
 
 enum GraphEvents {
     UpdateRange((f32, f32)),
     UpdateScaling(ValueScaling),
+    SyncReadout,
+    SyncMarkers,
+}
+
+/// The text box of an interactive graph.
+#[derive(Lens, Default)]
+struct GraphTooltip {
+    text: String,
+    visible: bool,
+    left: Units,
+    right: Units,
+    top: Units,
+    bottom: Units,
+}
+
+enum GraphTooltipEvents {
+    Show {
+        text: String,
+        // The cursor position relative to the graph, and the graph's size, in logical pixels
+        cursor: (f32, f32),
+        size: (f32, f32),
+    },
+    Hide,
+}
+
+impl Model for GraphTooltip {
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|e, _| match e {
+            GraphTooltipEvents::Show { text, cursor, size } => {
+                const OFFSET: f32 = 8.0;
+
+                // The box always extends towards the center of the graph, so it
+                // doesn't get cut off near the edges
+                let ((left, right), (top, bottom)) = (
+                    tooltip_anchor(cursor.0, size.0, OFFSET),
+                    tooltip_anchor(cursor.1, size.1, OFFSET),
+                );
+                self.text = text.clone();
+                self.visible = true;
+                self.left = left;
+                self.right = right;
+                self.top = top;
+                self.bottom = bottom;
+            }
+            GraphTooltipEvents::Hide => self.visible = false,
+        });
+    }
+}
+
+/// The labels of a graph's markers.
+#[derive(Lens, Default)]
+struct GraphMarkers {
+    labels: Vec<MarkerLabel>,
+}
+
+/// A marker's label, placed inside the graph.
+#[derive(Clone, Data, Debug, PartialEq)]
+struct MarkerLabel {
+    text: String,
+    color: Color,
+    // In logical pixels, relative to the graph
+    left: f32,
+    top: f32,
+}
+
+enum GraphMarkerEvents {
+    Set(Vec<MarkerLabel>),
+}
+
+impl Model for GraphMarkers {
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|e, _| match e {
+            GraphMarkerEvents::Set(labels) => self.labels = labels.clone(),
+        });
+    }
+}
+
+// The font size and the height of a marker's label, and how far it's placed from
+// the marker's line, in logical pixels
+const MARKER_FONT_SIZE: f32 = 11.0;
+const MARKER_ROW_HEIGHT: f32 = 16.0;
+const MARKER_LABEL_OFFSET: f32 = 3.0;
+// How far from a marker's line a right click still removes it, in logical pixels
+const MARKER_HIT_RADIUS: f32 = 6.0;
+
+/// The text box of a graph's readout.
+#[derive(Lens)]
+struct GraphReadout {
+    text: String,
+    left: Units,
+    right: Units,
+    top: Units,
+    bottom: Units,
+}
+
+enum GraphReadoutEvents {
+    SetText(String),
+    Place(ReadoutPosition, f32),
+}
+
+impl GraphReadout {
+    fn new(position: ReadoutPosition, inset: f32) -> Self {
+        let (left, right, top, bottom) = readout_spacing(position, inset);
+        Self {
+            text: String::new(),
+            left,
+            right,
+            top,
+            bottom,
+        }
+    }
+}
+
+impl Model for GraphReadout {
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|e, _| match e {
+            GraphReadoutEvents::SetText(text) => {
+                if *text != self.text {
+                    self.text = text.clone();
+                }
+            }
+            GraphReadoutEvents::Place(position, inset) => {
+                *self = Self {
+                    text: std::mem::take(&mut self.text),
+                    ..Self::new(*position, *inset)
+                };
+            }
+        });
+    }
 }
 
 impl<L, I> Graph<L, I>
 where
-    L: Lens<Target = Arc<Mutex<I>>>,
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
     I: VisualizerBuffer<f32, Output = f32> + 'static,
 {
     pub fn new(
@@ -50,89 +356,1324 @@ where
         scaling: impl Res<ValueScaling> + Clone,
     ) -> Handle<Self> {
         Self {
-            buffer,
+            buffer: buffer.clone(),
             range: range.get_val(cx),
             scaling: scaling.get_val(cx),
             fill_from: FillFrom::Bottom,
+            smoothing: None,
+            smoothing_scratch: RefCell::new(SmoothingScratch::default()),
+            bipolar: None,
+            zero_line: None,
+            interactive: false,
+            hovered_slot: None,
+            time_cursor: None,
+            auto_range: None,
+            smooth_scrolling: false,
+            throttle: FrameThrottle::default(),
+            points: RefCell::new(Vec::new()),
+            clear_fade: None,
+            transport_shading: None,
+            tag_tints: Vec::new(),
+            tag_dividers: None,
+            deviation_band: None,
+            segments: None,
+            band: RefCell::new(Vec::new()),
+            over_range: None,
+            under_range: None,
+            off_scale: RefCell::new(Vec::new()),
+            markers: None,
+            marker_colors: Vec::new(),
+            editable_markers: false,
+            marker_cursor: (0., 0.),
+            marker_labels: Vec::new(),
+            readout: None,
+            readout_skipped: false,
+            formatter: None,
+            text: String::new(),
+            readout_text: String::new(),
+            quality: None,
+            idle: None,
+            value_axis: Orientation::Vertical,
+            mirror_x: false,
+            direction: GraphDirection::BottomUp,
+            padding: InnerPadding::default(),
+            style: GraphStyle::Line,
+            level_colors: Vec::new(),
+            partial_segments: PartialSegments::Drop,
+            lit_segments: RefCell::new(Vec::new()),
         }
-        .build(cx, |_| {})
+        .build(cx, |cx| redraw_on_write(cx, buffer))
         .range(range)
         .scaling(scaling)
     }
+
+    /// Returns the formatter of the readout and the tooltip.
+    fn formatter(self: &Self) -> &dyn ValueFormatter {
+        match &self.formatter {
+            Some(formatter) => &**formatter,
+            None => default_formatter(self.scaling),
+        }
+    }
+
+    /// Returns the range the graph was last drawn with, which an auto range changes.
+    fn drawn_range(self: &Self) -> (f32, f32) {
+        match &self.auto_range {
+            Some(LiveRange::Drive(auto_range)) => auto_range.lock().unwrap().range(),
+            Some(LiveRange::Follow(source)) => source.get(),
+            None => self.range,
+        }
+    }
+
+    fn is_degraded(self: &Self, degradation: Degradation) -> bool {
+        self.quality
+            .as_ref()
+            .is_some_and(|stats| stats.is_degraded(degradation))
+    }
+
+    /// Returns the color a marker's index picks, if the graph was given any colors.
+    fn marker_color(self: &Self, index: u8) -> Option<Color> {
+        let len = self.marker_colors.len();
+        (len > 0).then(|| self.marker_colors[index as usize % len])
+    }
+
+    /// Returns the buffer's sample clock along with its duration, if it has both.
+    fn marker_clock(self: &Self, cx: &mut EventContext) -> Option<((u64, f32), f32)> {
+        let binding = self.buffer.get(cx);
+        let ring_buf = binding.read_shared(|buffer| buffer.refresh());
+        ring_buf.sample_clock().zip(ring_buf.duration())
+    }
+
+    /// Evicts the markers that scrolled out of the buffer, and places the labels of
+    /// the others.
+    fn sync_markers(self: &mut Self, cx: &mut EventContext) {
+        let Some(store) = &self.markers else {
+            return;
+        };
+        let mut labels = Vec::new();
+        if let Some(((now, rate), duration)) = self.marker_clock(cx) {
+            let mut store = store.lock().unwrap();
+            // The store follows the buffer's sample rate, and forgets what scrolled out
+            store.set_sample_rate(rate);
+            store.evict(now, (duration as f64 * rate as f64) as u64);
+
+            // Labels are only shown while the time axis runs horizontally
+            let scale = cx.scale_factor();
+            let bounds = cx.bounds();
+            let area = PlotArea::new(bounds, self.padding, scale);
+            let axes = area
+                .axes(self.value_axis, self.mirror_x)
+                .with_direction(self.direction);
+            if self.value_axis == Orientation::Vertical {
+                for marker in store.markers().iter().filter(|m| !m.label.is_empty()) {
+                    let time = marker_age(marker.position, store.sample_rate(), (now, rate))
+                        .and_then(|age| cursor_time(age, (0., duration)));
+                    if let Some(time) = time {
+                        labels.push(MarkerLabel {
+                            text: marker.label.clone(),
+                            color: self
+                                .marker_color(marker.color)
+                                .unwrap_or(Color::rgb(230, 230, 230)),
+                            left: ((axes.point(time, 0.).0 - bounds.x) / scale
+                                + MARKER_LABEL_OFFSET)
+                                .round(),
+                            top: 0.,
+                        });
+                    }
+                }
+            }
+
+            // Labels that would overlap are stacked below each other, as far down as
+            // the graph reaches
+            labels.sort_by(|a, b| a.left.total_cmp(&b.left));
+            let spans = labels
+                .iter()
+                .map(|label| (label.left, label.left + marker_label_width(&label.text)))
+                .collect::<Vec<_>>();
+            let top = (area.rect.y - bounds.y) / scale;
+            let rows = ((area.rect.h / scale / MARKER_ROW_HEIGHT).floor() as usize).max(1);
+            labels = labels
+                .into_iter()
+                .zip(stack_labels(&spans))
+                .filter(|(_, row)| *row < rows)
+                .map(|(label, row)| MarkerLabel {
+                    top: top + row as f32 * MARKER_ROW_HEIGHT,
+                    ..label
+                })
+                .collect();
+        }
+
+        if labels != self.marker_labels {
+            self.marker_labels.clone_from(&labels);
+            cx.emit(GraphMarkerEvents::Set(labels));
+        }
+    }
+
+    /// Adds a marker where the graph is clicked, and removes the one closest to a
+    /// right click.
+    fn edit_markers(self: &mut Self, cx: &mut EventContext, event: &mut Event) {
+        let Some(store) = self.markers.clone() else {
+            return;
+        };
+        let axes = PlotArea::new(cx.bounds(), self.padding, cx.scale_factor())
+            .axes(self.value_axis, self.mirror_x)
+            .with_direction(self.direction);
+        let reach = MARKER_HIT_RADIUS * cx.scale_factor() / axes.time_extent().max(1.);
+
+        let mut clicked = None;
+        event.map(|window_event, meta| match window_event {
+            WindowEvent::MouseMove(cursor_x, cursor_y) => {
+                self.marker_cursor = (*cursor_x, *cursor_y)
+            }
+            WindowEvent::MouseDown(button @ (MouseButton::Left | MouseButton::Right)) => {
+                clicked = Some(*button);
+                meta.consume();
+            }
+            _ => {}
+        });
+        let Some(button) = clicked else {
+            return;
+        };
+        let Some(((now, rate), duration)) = self.marker_clock(cx) else {
+            return;
+        };
+
+        let time = axes.time_at(self.marker_cursor);
+        let mut store = store.lock().unwrap();
+        store.set_sample_rate(rate);
+        if button == MouseButton::Left {
+            let Some(age) = cursor_age(time, (0., duration)) else {
+                return;
+            };
+            let position = now.saturating_sub((age as f64 * rate as f64).round() as u64);
+            let (label, color) = store.next_label();
+            let label = label.to_owned();
+            store.add(position, &label, color);
+        } else {
+            let Some(index) = nearest_marker(&store, time, (now, rate), duration, reach) else {
+                return;
+            };
+            store.remove(index);
+        }
+        cx.emit(GraphEvents::SyncMarkers);
+        cx.needs_redraw();
+    }
 }
 
 impl<L, I> View for Graph<L, I>
 where
-    L: Lens<Target = Arc<Mutex<I>>>,
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
     I: VisualizerBuffer<f32, Output = f32> + 'static,
 {
     fn element(&self) -> Option<&'static str> {
-        Some("graph")
+        Some(elements::GRAPH)
     }
-    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
         event.map(|e, _| match e {
             GraphEvents::UpdateRange(v) => self.range = *v,
             GraphEvents::UpdateScaling(s) => self.scaling = *s,
+            GraphEvents::SyncReadout => {
+                if self.is_degraded(Degradation::SlowReadout) {
+                    self.readout_skipped = !self.readout_skipped;
+                    if self.readout_skipped {
+                        return;
+                    }
+                }
+                let newest = {
+                    let binding = self.buffer.get(cx);
+                    let ring_buf = binding.read_shared(|buffer| buffer.refresh());
+                    ring_buf
+                        .len()
+                        .checked_sub(1)
+                        .and_then(|i| ring_buf.get(i).copied())
+                };
+                if let Some(value) = newest {
+                    let mut text = std::mem::take(&mut self.text);
+                    text.clear();
+                    tooltip_text(self.formatter(), value, self.scaling, None, None, &mut text);
+                    if text != self.readout_text {
+                        self.readout_text.clone_from(&text);
+                        cx.emit(GraphReadoutEvents::SetText(text.clone()));
+                    }
+                    self.text = text;
+                }
+            }
+            GraphEvents::SyncMarkers => self.sync_markers(cx),
+        });
+        event.map(|e, _| {
+            if let IdleEvents::Check = e {
+                let Some(idle) = &mut self.idle else {
+                    return;
+                };
+                let changed = {
+                    let binding = self.buffer.get(cx);
+                    let ring_buf = binding.read_shared(|buffer| buffer.refresh());
+                    let values = NormalizedRead::new(&*ring_buf, self.range, self.scaling)
+                        .with_segments(self.segments.as_ref());
+                    let anchor = match &self.bipolar {
+                        Some(bipolar) => values.normalize_level(bipolar.zero),
+                        None => self.fill_from.normalized(&values),
+                    };
+                    idle.check(Instant::now(), &values, anchor)
+                };
+                if let Some(idle) = changed {
+                    cx.emit(IdleEvents::Changed(idle));
+                }
+            }
+        });
+
+        if self.editable_markers {
+            self.edit_markers(cx, event);
+        }
+
+        // Any linked graph places the shared cursor, whether it's interactive or not
+        if let Some(link) = &self.time_cursor {
+            event.map(|window_event, _| match window_event {
+                WindowEvent::MouseMove(cursor_x, cursor_y) => {
+                    let axes = PlotArea::new(cx.bounds(), self.padding, cx.scale_factor())
+                        .axes(self.value_axis, self.mirror_x)
+                        .with_direction(self.direction);
+                    let time = axes.time_at((*cursor_x, *cursor_y));
+                    let duration = self.buffer.get(cx).read_shared(|_| {}).duration();
+                    link.hover(duration.and_then(|duration| cursor_age(time, (0., duration))));
+                }
+                WindowEvent::MouseLeave => link.leave(),
+                _ => {}
+            });
+        }
+
+        if !self.interactive {
+            return;
+        }
+        event.map(|window_event, _| match window_event {
+            WindowEvent::MouseMove(cursor_x, cursor_y) => {
+                let bounds = cx.bounds();
+                let scale = cx.scale_factor();
+
+                // Only hold the lock for as long as it takes to read a single slot
+                let hovered = {
+                    let binding = self.buffer.get(cx);
+                    let ring_buf = binding.read_shared(|buffer| buffer.refresh());
+                    let axes = PlotArea::new(bounds, self.padding, scale)
+                        .axes(self.value_axis, self.mirror_x)
+                        .with_direction(self.direction);
+                    let time = axes.time_at((*cursor_x, *cursor_y));
+                    slot_at(time, 0., 1., ring_buf.len()).and_then(|slot| {
+                        let age = ring_buf.duration().map(|duration| {
+                            (ring_buf.len() - 1 - slot) as f32 * duration / ring_buf.len() as f32
+                        });
+                        let value = *ring_buf.get(slot)?;
+                        // The tooltip shows the true value, even where the trace is
+                        // flattened against an edge
+                        let off_scale =
+                            NormalizedRead::new(&*ring_buf, self.drawn_range(), self.scaling)
+                                .with_segments(self.segments.as_ref())
+                                .off_scale(value);
+                        Some((slot, value, age, off_scale))
+                    })
+                };
+
+                match hovered {
+                    Some((slot, value, age, off_scale)) => {
+                        self.hovered_slot = Some(slot);
+                        let mut text = std::mem::take(&mut self.text);
+                        text.clear();
+                        tooltip_text(
+                            self.formatter(),
+                            value,
+                            self.scaling,
+                            off_scale,
+                            age,
+                            &mut text,
+                        );
+                        cx.emit(GraphTooltipEvents::Show {
+                            text: text.clone(),
+                            cursor: ((cursor_x - bounds.x) / scale, (cursor_y - bounds.y) / scale),
+                            size: (bounds.w / scale, bounds.h / scale),
+                        });
+                        self.text = text;
+                    }
+                    None => {
+                        self.hovered_slot = None;
+                        cx.emit(GraphTooltipEvents::Hide);
+                    }
+                }
+                cx.needs_redraw();
+            }
+            WindowEvent::MouseLeave => {
+                self.hovered_slot = None;
+                cx.emit(GraphTooltipEvents::Hide);
+                cx.needs_redraw();
+            }
+            _ => {}
         });
     }
     fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
-        let bounds = cx.bounds();
+        draw_measured(self.quality.as_ref(), || self.draw_graph(cx, canvas));
+    }
+}
+
+impl<L, I> Graph<L, I>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    fn draw_graph(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        // The readout's area is registered even when drawing is throttled, so that
+        // other views always know where it is
+        if let Some(Readout {
+            position,
+            inset,
+            overlay: Some(slot),
+        }) = &self.readout
+        {
+            let scale = cx.scale_factor();
+            slot.set(readout_rect(
+                cx.bounds(),
+                *position,
+                inset * scale,
+                (READOUT_SIZE.0 * scale, READOUT_SIZE.1 * scale),
+            ));
+        }
 
-        let x = bounds.x;
-        let y = bounds.y;
-        let w = bounds.w;
-        let h = bounds.h;
+        draw_throttled(cx, canvas, &self.throttle, |cx, canvas| {
+            let line_width = cx.scale_factor();
+            let axes = PlotArea::new(cx.bounds(), self.padding, cx.scale_factor())
+                .axes(self.value_axis, self.mirror_x)
+                .with_direction(self.direction);
 
-        let line_width = cx.scale_factor();
+            let binding = self.buffer.get(cx);
+            let ring_buf = binding.read_shared(|buffer| buffer.refresh());
 
-        let mut stroke = vg::Path::new();
-        let binding = self.buffer.get(cx);
-        let ring_buf = &(binding.lock().unwrap());
+            if ring_buf.len() == 0 {
+                return;
+            }
 
-        let mut peak = self
-            .scaling
-            .value_to_normalized(ring_buf[0], self.range.0, self.range.1);
+            // Smoothing is applied to the raw values, before they are normalized
+            let mut smoothing_scratch = self.smoothing_scratch.borrow_mut();
+            let smoothed = self.smoothing.map(|smoothing| {
+                smoothing.apply(
+                    (0..ring_buf.len()).filter_map(|i| ring_buf.get(i).copied()),
+                    &mut smoothing_scratch,
+                )
+            });
 
-        stroke.move_to(x, y + h * (1. - peak));
+            let range = match &self.auto_range {
+                Some(LiveRange::Drive(auto_range)) => {
+                    let extent = match smoothed {
+                        Some(smoothed) => scaled_extent(smoothed.iter().copied(), self.scaling),
+                        None => {
+                            let values = (0..ring_buf.len()).filter_map(|i| ring_buf.get(i));
+                            scaled_extent(values.copied(), self.scaling)
+                        }
+                    };
+                    auto_range.lock().unwrap().update_now(extent)
+                }
+                Some(LiveRange::Follow(source)) => source.get(),
+                None => self.range,
+            };
+            let values = NormalizedRead::new(&*ring_buf, range, self.scaling)
+                .with_segments(self.segments.as_ref());
 
-        for i in 1..ring_buf.len() {
-            // Normalize peak value
-            peak = self
-                .scaling
-                .value_to_normalized(ring_buf[i], self.range.0, self.range.1);
+            // Positions in time are fractions of the time axis
+            let width_delta = 1. / values.len() as f32;
 
-            // Draw peak as a new point
-            stroke.line_to(
-                x + (w / ring_buf.len() as f32) * i as f32,
-                y + h * (1. - peak),
-            );
+            // While smooth scrolling, the element that is being accumulated is drawn as
+            // an extra point, and the trace is shifted by its progress
+            let partial = if self.smooth_scrolling {
+                ring_buf.partial()
+            } else {
+                None
+            };
+            let shift = partial.map_or(0., |(progress, _)| width_delta * progress);
+
+            // The points of the previous frame, if the buffer was cleared since then,
+            // are kept to fade them out
+            let now = Instant::now();
+            let mut points = self.points.borrow_mut();
+            let clear_fade = self.clear_fade.as_ref().map(|fade| {
+                let mut fade = fade.borrow_mut();
+                fade.observe(now, ring_buf.clears(), &mut points);
+                fade
+            });
+
+            // Each point's position in time and its normalized value
+            graph_points(&mut points, &values, smoothed, partial, -shift, 1.);
+
+            // The crosshair is snapped to the point of the hovered slot, so the points
+            // are only coarsened while the graph isn't hovered
+            let coarse = self.style == GraphStyle::Line
+                && self.hovered_slot.is_none()
+                && self.is_degraded(Degradation::CoarseDecimation);
+            if coarse {
+                let anchor = match &self.bipolar {
+                    Some(bipolar) => values.normalize_level(bipolar.zero),
+                    None => self.fill_from.normalized(&values),
+                };
+                coarsen_points(&mut points, anchor);
+            }
+
+            if let Some(color) = self.transport_shading {
+                let mut shading = vg::Path::new();
+                for run in stopped_runs(|i| ring_buf.transport_playing(i), values.len()) {
+                    let start = width_delta * run.start as f32 - shift;
+                    let (x, y, w, h) =
+                        axes.rect((start, start + width_delta * run.len() as f32), (0., 1.));
+                    shading.rect(x, y, w, h);
+                }
+                canvas.fill_path(&shading, &vg::Paint::color(color.into()));
+            }
+
+            if !self.tag_tints.is_empty() || self.tag_dividers.is_some() {
+                let mut tints = PathBatches::default();
+                let mut dividers = vg::Path::new();
+                for (tag, run) in tag_runs(|i| ring_buf.tag(i), values.len()) {
+                    let start = width_delta * run.start as f32 - shift;
+                    if let Some((_, color)) = self.tag_tints.iter().find(|(t, _)| *t == tag) {
+                        let (x, y, w, h) =
+                            axes.rect((start, start + width_delta * run.len() as f32), (0., 1.));
+                        if let Some(path) = tints.path((*color).into()) {
+                            path.rect(x, y, w, h);
+                        }
+                    }
+                    // The oldest run doesn't start at a change
+                    if run.start > 0 {
+                        let ((x0, y0), (x1, y1)) = (axes.point(start, 0.), axes.point(start, 1.));
+                        dividers.move_to(x0, y0);
+                        dividers.line_to(x1, y1);
+                    }
+                }
+                for (color, path) in tints.iter() {
+                    canvas.fill_path(path, &vg::Paint::color(color));
+                }
+                if let Some(color) = self.tag_dividers {
+                    canvas.stroke_path(
+                        &dividers,
+                        &vg::Paint::color(color.into()).with_line_width(line_width),
+                    );
+                }
+            }
+
+            // Elements restored from a summary are drawn dimmed, until they scroll out
+            let mut restored = (0..values.len())
+                .take_while(|i| ring_buf.is_restored(*i))
+                .count();
+            if coarse {
+                restored = (restored + 1) / 2;
+            }
+            let (restored_points, live_points) = split_restored(&points, restored);
+
+            let trace = |points: &[(f32, f32)]| {
+                let mut stroke = vg::Path::new();
+                for (i, (time, peak)) in points.iter().copied().enumerate() {
+                    let (px, py) = axes.point(time, peak);
+                    if i == 0 {
+                        stroke.move_to(px, py);
+                    } else {
+                        // Draw peak as a new point
+                        stroke.line_to(px, py);
+                    }
+                }
+                stroke
+            };
+
+            // The contents from before a clear sink towards the fill level while they
+            // fade, underneath everything that arrived since
+            if let Some((ghost, progress)) = clear_fade.as_ref().and_then(|fade| fade.ghost(now)) {
+                let floor = match &self.bipolar {
+                    Some(bipolar) => values.normalize_level(bipolar.zero),
+                    None => self.fill_from.normalized(&values),
+                };
+                let mut fill = vg::Path::new();
+                for (i, (time, v)) in fill_outline(ghost, floor, 0., 1.).enumerate() {
+                    let (px, py) = axes.point(time, sink(v, floor, progress));
+                    if i == 0 {
+                        fill.move_to(px, py);
+                    } else {
+                        fill.line_to(px, py);
+                    }
+                }
+                fill.close();
+
+                let mut background: vg::Color = cx.background_color().into();
+                background.a *= ghost_alpha(progress);
+                canvas.fill_path(&fill, &vg::Paint::color(background));
+
+                let mut stroke = vg::Path::new();
+                for (i, (time, v)) in ghost.iter().copied().enumerate() {
+                    let (px, py) = axes.point(time, sink(v, floor, progress));
+                    if i == 0 {
+                        stroke.move_to(px, py);
+                    } else {
+                        stroke.line_to(px, py);
+                    }
+                }
+                let mut color: vg::Color = cx.font_color().into();
+                color.a *= ghost_alpha(progress);
+                canvas.stroke_path(
+                    &stroke,
+                    &vg::Paint::color(color).with_line_width(line_width),
+                );
+            }
+
+            // The band around the mean is drawn beneath the trace itself
+            if let Some(color) = self.deviation_band {
+                let mut band = self.band.borrow_mut();
+                refill(
+                    &mut band,
+                    (0..values.len()).map(|i| {
+                        // Normalizing clamps the bounds to the graph, and puts those
+                        // that can't be displayed - like a negative gain in decibels -
+                        // on its bottom edge
+                        let (mean, deviation) = ring_buf.deviation(i)?;
+                        Some((
+                            width_delta * i as f32 - shift,
+                            values.normalize_value(mean - deviation),
+                            values.normalize_value(mean + deviation),
+                        ))
+                    }),
+                );
+                let mut path = vg::Path::new();
+                band_regions(&band, |point| match point {
+                    RegionPoint::Start(time, v) => {
+                        let (px, py) = axes.point(time, v);
+                        path.move_to(px, py)
+                    }
+                    RegionPoint::Line(time, v) => {
+                        let (px, py) = axes.point(time, v);
+                        path.line_to(px, py)
+                    }
+                    RegionPoint::Close => path.close(),
+                });
+                canvas.fill_path(&path, &vg::Paint::color(color.into()));
+            }
+
+            // A zigzag across the graph marks each break of a broken axis
+            if let Some(segments) = &self.segments {
+                let mut markers = vg::Path::new();
+                for (start, end) in segments.breaks(self.scaling) {
+                    for (i, (time, v)) in break_marker(start, end).enumerate() {
+                        let (px, py) = axes.point(time, v);
+                        if i == 0 {
+                            markers.move_to(px, py);
+                        } else {
+                            markers.line_to(px, py);
+                        }
+                    }
+                }
+                let mut color: vg::Color = cx.font_color().into();
+                color.a *= BREAK_ALPHA;
+                canvas.stroke_path(
+                    &markers,
+                    &vg::Paint::color(color).with_line_width(line_width),
+                );
+            }
+
+            match self.style {
+                GraphStyle::Line => {
+                    match &self.bipolar {
+                        Some(bipolar) => {
+                            let zero = values.normalize_level(bipolar.zero);
+
+                            // One path for each side of the zero line
+                            let mut positive = vg::Path::new();
+                            let mut negative = vg::Path::new();
+                            bipolar_regions(points.iter().copied(), zero, |above, point| {
+                                let path = if above { &mut positive } else { &mut negative };
+                                match point {
+                                    RegionPoint::Start(time, v) => {
+                                        let (px, py) = axes.point(time, v);
+                                        path.move_to(px, py)
+                                    }
+                                    RegionPoint::Line(time, v) => {
+                                        let (px, py) = axes.point(time, v);
+                                        path.line_to(px, py)
+                                    }
+                                    RegionPoint::Close => path.close(),
+                                }
+                            });
+
+                            canvas.fill_path(&positive, &vg::Paint::color(bipolar.positive.into()));
+                            canvas.fill_path(&negative, &vg::Paint::color(bipolar.negative.into()));
+
+                            if let Some(color) = self.zero_line {
+                                let mut zero_line = vg::Path::new();
+                                let ((x0, y0), (x1, y1)) =
+                                    (axes.point(0., zero), axes.point(1., zero));
+                                zero_line.move_to(x0, y0);
+                                zero_line.line_to(x1, y1);
+                                canvas.stroke_path(
+                                    &zero_line,
+                                    &vg::Paint::color(color.into()).with_line_width(line_width),
+                                );
+                            }
+                        }
+                        None => {
+                            let anchor = self.fill_from.normalized(&values);
+                            // On a broken axis, only the anchor's segment is filled
+                            let (low, high) = values.segment_bounds(anchor);
+                            let fill = |points: &[(f32, f32)], start: f32, len: f32| {
+                                let mut fill = vg::Path::new();
+                                for (i, (time, v)) in
+                                    fill_outline(points, anchor, start, len).enumerate()
+                                {
+                                    let (px, py) = axes.point(time, v.clamp(low, high));
+                                    if i == 0 {
+                                        fill.move_to(px, py);
+                                    } else {
+                                        fill.line_to(px, py);
+                                    }
+                                }
+                                fill.close();
+                                fill
+                            };
+
+                            let mut color: vg::Color = cx.background_color().into();
+                            match restored_points.last() {
+                                Some((boundary, _)) => {
+                                    canvas.fill_path(
+                                        &fill(live_points, *boundary, 1. - boundary),
+                                        &vg::Paint::color(color),
+                                    );
+                                    color.a *= RESTORED_ALPHA;
+                                    canvas.fill_path(
+                                        &fill(restored_points, 0., *boundary),
+                                        &vg::Paint::color(color),
+                                    );
+                                }
+                                None => canvas.fill_path(
+                                    &fill(live_points, 0., 1.),
+                                    &vg::Paint::color(color),
+                                ),
+                            }
+                        }
+                    }
+
+                    let mut color: vg::Color = cx.font_color().into();
+                    canvas.stroke_path(
+                        &trace(live_points),
+                        &vg::Paint::color(color).with_line_width(line_width),
+                    );
+                    if !restored_points.is_empty() {
+                        color.a *= RESTORED_ALPHA;
+                        canvas.stroke_path(
+                            &trace(restored_points),
+                            &vg::Paint::color(color).with_line_width(line_width),
+                        );
+                    }
+                }
+                GraphStyle::Segments {
+                    segment_height_px,
+                    gap_px,
+                } => {
+                    // Segments are laid out in normalized values, so that they only
+                    // depend on the graph's size in logical pixels
+                    let extent = axes.value_extent() / cx.scale_factor();
+                    let pitch = (segment_height_px + gap_px) / extent;
+                    let height = segment_height_px / extent;
+                    let anchor = self.fill_from.normalized(&values);
+                    let default_color: vg::Color = cx.font_color().into();
+
+                    let mut batches = PathBatches::default();
+                    let mut lit = self.lit_segments.borrow_mut();
+                    for direction in [1., -1.] {
+                        refill(
+                            &mut lit,
+                            points.iter().map(|(_, v)| {
+                                lit_segments((v - anchor) * direction, pitch, height)
+                            }),
+                        );
+                        segment_runs(&lit, self.partial_segments, |segment, run, dimmed| {
+                            let start = anchor + direction * segment as f32 * pitch;
+                            let end = (start + direction * height).clamp(0., 1.);
+                            let mut color = segment_color(
+                                &self.level_colors,
+                                |level| values.normalize_level(level),
+                                (start + end) / 2.,
+                                default_color,
+                            );
+                            if dimmed {
+                                color.a *= DIMMED_SEGMENT_ALPHA;
+                            }
+                            let time = (points[run.start].0, points[run.end - 1].0 + width_delta);
+                            let (x, y, w, h) = axes.rect(time, (start, end));
+                            if let Some(path) = batches.path(color) {
+                                path.rect(x, y, w, h);
+                            }
+                        });
+                    }
+                    for (color, path) in batches.iter() {
+                        canvas.fill_path(path, &vg::Paint::color(color));
+                    }
+                }
+            }
+
+            // Values beyond the range are flattened against its edges, so the elements
+            // they belong to are marked there
+            if self.over_range.is_some() || self.under_range.is_some() {
+                let mut flags = self.off_scale.borrow_mut();
+                off_scale_flags(&mut flags, &values, partial);
+                for (side, marker) in [
+                    (OffScale::Over, self.over_range),
+                    (OffScale::Under, self.under_range),
+                ] {
+                    let Some((style, color)) = marker else {
+                        continue;
+                    };
+                    let edge = match side {
+                        OffScale::Over => 1.,
+                        OffScale::Under => 0.,
+                    };
+                    let mut path = vg::Path::new();
+                    for run in off_scale_runs(&flags, side) {
+                        let start = width_delta * run.start as f32 - shift;
+                        let end = width_delta * (run.end - 1) as f32 - shift;
+                        match style {
+                            OffScaleStyle::Triangle { size_px } => {
+                                let time = (start + end) / 2.;
+                                let apex = axes.point(time, edge);
+                                let toward = axes.point(time, 0.5);
+                                let size = size_px * cx.scale_factor();
+                                if let Some(corners) = off_scale_triangle(apex, toward, size) {
+                                    path.move_to(corners[0].0, corners[0].1);
+                                    path.line_to(corners[1].0, corners[1].1);
+                                    path.line_to(corners[2].0, corners[2].1);
+                                    path.close();
+                                }
+                            }
+                            OffScaleStyle::Stroke { .. } => {
+                                // Each element spans half the distance to its neighbours
+                                let from = (start - width_delta / 2.).clamp(0., 1.);
+                                let to = (end + width_delta / 2.).clamp(0., 1.);
+                                let ((x0, y0), (x1, y1)) =
+                                    (axes.point(from, edge), axes.point(to, edge));
+                                path.move_to(x0, y0);
+                                path.line_to(x1, y1);
+                            }
+                        }
+                    }
+                    match style {
+                        OffScaleStyle::Triangle { .. } => {
+                            canvas.fill_path(&path, &vg::Paint::color(color.into()))
+                        }
+                        OffScaleStyle::Stroke { width_px } => canvas.stroke_path(
+                            &path,
+                            &vg::Paint::color(color.into())
+                                .with_line_width(width_px * cx.scale_factor()),
+                        ),
+                    }
+                }
+            }
+
+            // The markers, at the moments of the buffer's history they're pinned to
+            if let Some(store) = &self.markers {
+                let store = store.lock().unwrap();
+                let clock = ring_buf.sample_clock().zip(ring_buf.duration());
+                for marker in store.markers() {
+                    let time = clock.and_then(|(clock, duration)| {
+                        marker_age(marker.position, store.sample_rate(), clock)
+                            .and_then(|age| cursor_time(age, (0., duration)))
+                    });
+                    let Some(time) = time else {
+                        continue;
+                    };
+                    let (from, to) = (axes.point(time, 0.), axes.point(time, 1.));
+                    let mut line = vg::Path::new();
+                    line.move_to(from.0, from.1);
+                    line.line_to(to.0, to.1);
+                    let color: vg::Color = match self.marker_color(marker.color) {
+                        Some(color) => color.into(),
+                        None => cx.font_color().into(),
+                    };
+                    canvas.stroke_path(&line, &vg::Paint::color(color).with_line_width(line_width));
+                }
+            }
+
+            // The crosshair of an interactive graph, snapped to the hovered point
+            if let Some((time, peak)) = self.hovered_slot.and_then(|slot| points.get(slot).copied())
+            {
+                let mut crosshair = vg::Path::new();
+                for (from, to) in [
+                    (axes.point(time, 0.), axes.point(time, 1.)),
+                    (axes.point(0., peak), axes.point(1., peak)),
+                ] {
+                    crosshair.move_to(from.0, from.1);
+                    crosshair.line_to(to.0, to.1);
+                }
+
+                let mut color: vg::Color = cx.font_color().into();
+                color.a *= 0.5;
+                canvas.stroke_path(
+                    &crosshair,
+                    &vg::Paint::color(color).with_line_width(line_width),
+                );
+            }
+
+            // The shared cursor, while another linked view is hovered
+            let cursor = self
+                .time_cursor
+                .as_ref()
+                .and_then(|link| link.age())
+                .zip(ring_buf.duration())
+                .and_then(|(age, duration)| cursor_time(age, (0., duration)));
+            if let Some(time) = cursor {
+                draw_time_cursor(cx, canvas, axes.point(time, 0.), axes.point(time, 1.));
+            }
+
+            draw_capture_progress(cx, canvas, ring_buf.capture_progress());
+
+            let scanline = self
+                .idle
+                .as_ref()
+                .and_then(|idle| idle.scanline(Instant::now()));
+            if let Some(time) = scanline {
+                draw_scanline(cx, canvas, axes.point(time, 0.), axes.point(time, 1.));
+            }
+        });
+    }
+}
+
+/// Fills `flags` with the edge of the range that each point's raw value lies
+/// beyond, in the same order as [`graph_points()`](crate::geometry::graph_points).
+///
+/// The flags are taken from the buffer's values rather than the points, since those
+/// were already clamped to the range.
+fn off_scale_flags<B>(
+    flags: &mut Vec<Option<OffScale>>,
+    values: &NormalizedRead<B>,
+    partial: Option<(f32, f32)>,
+) where
+    B: VisualizerBuffer<f32, Output = f32> + ?Sized,
+{
+    refill(
+        flags,
+        (0..values.len())
+            .map(|i| values.get_flagged(i).and_then(|(_, flag)| flag))
+            .chain(partial.map(|(_, value)| values.off_scale(value))),
+    );
+}
+
+/// Returns the runs of neighbouring points that lie beyond the given edge of the
+/// range.
+fn off_scale_runs(
+    flags: &[Option<OffScale>],
+    side: OffScale,
+) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut start = None;
+    (0..=flags.len()).filter_map(move |i| {
+        let beyond = flags.get(i) == Some(&Some(side));
+        match (start, beyond) {
+            (None, true) => {
+                start = Some(i);
+                None
+            }
+            (Some(run_start), false) => {
+                start = None;
+                Some(run_start..i)
+            }
+            _ => None,
+        }
+    })
+}
+
+/// Returns the corners of a triangle with its apex on the edge of the graph,
+/// pointing away from `toward`, or `None` if the two coincide.
+///
+/// All positions and the size are in physical pixels.
+fn off_scale_triangle(apex: (f32, f32), toward: (f32, f32), size: f32) -> Option<[(f32, f32); 3]> {
+    let (dx, dy) = (toward.0 - apex.0, toward.1 - apex.1);
+    let length = (dx * dx + dy * dy).sqrt();
+    if !(length > 0.) {
+        return None;
+    }
+    // Inwards along the value axis, and half the width across it
+    let (ix, iy) = (dx / length * size, dy / length * size);
+    let (hx, hy) = (-iy / 2., ix / 2.);
+    Some([
+        apex,
+        (apex.0 + ix + hx, apex.1 + iy + hy),
+        (apex.0 + ix - hx, apex.1 + iy - hy),
+    ])
+}
+
+/// Returns the outline of the fill: the points of the trace, followed by the anchor
+/// at the right and the left edge.
+///
+/// The anchor is a normalized level, as returned by `FillFrom::normalized()`.
+fn fill_outline(
+    points: &[(f32, f32)],
+    anchor: f32,
+    x: f32,
+    w: f32,
+) -> impl Iterator<Item = (f32, f32)> + '_ {
+    points.iter().copied().chain([(x + w, anchor), (x, anchor)])
+}
+
+// The opacity of elements that were restored from a summary, relative to live ones
+const RESTORED_ALPHA: f32 = 0.4;
+
+// The opacity of the markers of an axis break, relative to the trace, and the
+// number of times each marker zigzags up and down across the graph
+const BREAK_ALPHA: f32 = 0.5;
+const BREAK_TEETH: usize = 24;
+
+/// Returns the points of the zigzag that marks a break of the value axis, which
+/// runs across the whole time axis inside the break's middle half.
+fn break_marker(start: f32, end: f32) -> impl Iterator<Item = (f32, f32)> {
+    let (low, high) = (start + (end - start) / 4., end - (end - start) / 4.);
+    (0..=2 * BREAK_TEETH).map(move |i| {
+        let time = i as f32 / (2 * BREAK_TEETH) as f32;
+        (time, if i % 2 == 0 { low } else { high })
+    })
+}
+
+/// Splits the points into the ones of restored elements and the live ones.
+///
+/// Both halves share the point at the boundary, so that the trace stays connected.
+/// If no element was restored, the first half is empty.
+fn split_restored(points: &[(f32, f32)], restored: usize) -> (&[(f32, f32)], &[(f32, f32)]) {
+    if restored == 0 || points.is_empty() {
+        return (&[], points);
+    }
+    let boundary = restored.min(points.len() - 1);
+    (&points[..=boundary], &points[boundary..])
+}
+
+/// Returns the runs of elements during which the host's transport was stopped.
+fn stopped_runs(
+    playing: impl Fn(usize) -> Option<bool>,
+    len: usize,
+) -> impl Iterator<Item = Range<usize>> {
+    let mut start = None;
+    (0..=len).filter_map(move |i| {
+        let stopped = i < len && playing(i) == Some(false);
+        match (start, stopped) {
+            (None, true) => {
+                start = Some(i);
+                None
+            }
+            (Some(run_start), false) => {
+                start = None;
+                Some(run_start..i)
+            }
+            _ => None,
         }
+    })
+}
 
-        let mut fill = stroke.clone();
-        let fill_from_n = match self.fill_from {
-            FillFrom::Top => 0.0,
-            FillFrom::Bottom => 1.0,
-            FillFrom::Value(val) => {
-                1.0 - ValueScaling::Linear.value_to_normalized(val, self.range.0, self.range.1)
+/// Returns the runs of neighbouring elements that share the same tag, along with
+/// the tag.
+fn tag_runs(
+    tag: impl Fn(usize) -> Option<u8>,
+    len: usize,
+) -> impl Iterator<Item = (u8, Range<usize>)> {
+    let mut run: Option<(u8, usize)> = None;
+    (0..=len).filter_map(move |i| {
+        let next = if i < len { tag(i) } else { None };
+        match run {
+            Some((run_tag, _)) if next == Some(run_tag) => None,
+            _ => {
+                let finished = run.map(|(run_tag, start)| (run_tag, start..i));
+                run = next.map(|next| (next, i));
+                finished
             }
-        };
+        }
+    })
+}
+
+/// Returns the number of segments that lie entirely between the fill level and a
+/// value, and whether the value lies inside of the next one.
+///
+/// All arguments are normalized: `distance` runs from the fill level to the value,
+/// `pitch` is the distance from one segment to the next, and `height` is the height
+/// of each segment. Values that lie past the fill level light up no segments.
+fn lit_segments(distance: f32, pitch: f32, height: f32) -> (usize, bool) {
+    // Values on the edge of a segment don't light up the next one
+    const EPSILON: f32 = 1e-5;
 
-        fill.line_to(x + w, y + h * fill_from_n);
-        fill.line_to(x, y + h * fill_from_n);
-        fill.close();
+    if !(distance > EPSILON && pitch > 0.) {
+        return (0, false);
+    }
+    let gap = pitch - height;
+    let full = ((distance + gap + EPSILON) / pitch).floor() as usize;
+    (full, distance > full as f32 * pitch + EPSILON)
+}
 
-        canvas.fill_path(&fill, &vg::Paint::color(cx.background_color().into()));
+/// Visits the segments that light up, merging the same segment of neighbouring
+/// elements into a single run.
+///
+/// `lit` holds each element's result of [`lit_segments()`]. The visitor is called
+/// with the index of the segment, counted from the fill level, the elements it
+/// spans, and whether it's dimmed.
+fn segment_runs(
+    lit: &[(usize, bool)],
+    partial_segments: PartialSegments,
+    mut visit: impl FnMut(usize, Range<usize>, bool),
+) {
+    let dim = partial_segments == PartialSegments::Dim;
+    // Whether an element lights up a segment, and whether it's dimmed
+    let state = |(full, partial): (usize, bool), segment: usize| match segment.cmp(&full) {
+        std::cmp::Ordering::Less => Some(false),
+        std::cmp::Ordering::Equal if partial && dim => Some(true),
+        _ => None,
+    };
 
-        canvas.stroke_path(
-            &stroke,
-            &vg::Paint::color(cx.font_color().into()).with_line_width(line_width),
-        );
+    let segments = lit
+        .iter()
+        .map(|(full, partial)| full + (*partial && dim) as usize)
+        .max()
+        .unwrap_or(0);
+    for segment in 0..segments {
+        let mut run: Option<(usize, bool)> = None;
+        for i in 0..=lit.len() {
+            let next = lit.get(i).and_then(|lit| state(*lit, segment));
+            if let Some((start, dimmed)) = run {
+                if next != Some(dimmed) {
+                    visit(segment, start..i, dimmed);
+                    run = None;
+                }
+            }
+            if run.is_none() {
+                run = next.map(|dimmed| (i, dimmed));
+            }
+        }
+    }
+}
+
+/// Returns the color of the highest level that lies at or below the normalized
+/// value, or the default color if there is none.
+fn segment_color(
+    level_colors: &[(f32, Color)],
+    normalize: impl Fn(f32) -> f32,
+    value: f32,
+    default: vg::Color,
+) -> vg::Color {
+    level_colors
+        .iter()
+        .rev()
+        .find(|(level, _)| normalize(*level) <= value)
+        .map_or(default, |(_, color)| (*color).into())
+}
+
+/// Maps a cursor's x position back to the slot whose point is drawn closest to it.
+///
+/// This is the inverse of the draw mapping, where each slot gets one point.
+/// Returns `None` if the cursor is outside of the graph, or the buffer is empty.
+fn slot_at(cursor_x: f32, x: f32, w: f32, len: usize) -> Option<usize> {
+    if len == 0 || cursor_x < x || cursor_x > x + w {
+        return None;
+    }
+    let width_delta = w / len as f32;
+    Some((((cursor_x - x) / width_delta).round() as usize).min(len - 1))
+}
+
+/// Appends a slot's value, formatted according to the scaling, along with the edge
+/// of the range it lies beyond and its age.
+fn tooltip_text(
+    formatter: &dyn ValueFormatter,
+    value: f32,
+    scaling: ValueScaling,
+    off_scale: Option<OffScale>,
+    age: Option<f32>,
+    out: &mut String,
+) {
+    format_scaled(formatter, scaling, value, out);
+    match off_scale {
+        Some(OffScale::Over) => out.push_str(" (over range)"),
+        Some(OffScale::Under) => out.push_str(" (under range)"),
+        None => {}
+    }
+    if let Some(age) = age {
+        let _ = write!(out, ", {:.1} s ago", age);
+    }
+}
+
+/// Positions the tooltip on one axis, next to the cursor but always on the side
+/// facing the center of the graph.
+///
+/// Returns the spacing before and after the tooltip.
+fn tooltip_anchor(cursor: f32, size: f32, offset: f32) -> (Units, Units) {
+    if cursor < size / 2. {
+        (Pixels((cursor + offset).max(0.)), Stretch(1.0))
+    } else {
+        (Stretch(1.0), Pixels((size - cursor + offset).max(0.)))
+    }
+}
+
+/// Returns roughly how wide a marker's label is, in logical pixels.
+///
+/// The label isn't laid out until after it's placed, so its width is estimated from
+/// the average width of a character.
+fn marker_label_width(text: &str) -> f32 {
+    text.chars().count() as f32 * MARKER_FONT_SIZE * 0.6 + 4.
+}
+
+/// Returns the row each label is placed in, so that labels don't overlap.
+///
+/// The labels are given as their horizontal spans, from left to right. Each one is
+/// placed in the topmost row where it clears the label before it, or in a new row
+/// below the others.
+fn stack_labels(spans: &[(f32, f32)]) -> Vec<usize> {
+    let mut row_ends: Vec<f32> = Vec::new();
+    spans
+        .iter()
+        .map(|(start, end)| {
+            let row = row_ends
+                .iter()
+                .position(|row_end| row_end <= start)
+                .unwrap_or(row_ends.len());
+            match row_ends.get_mut(row) {
+                Some(row_end) => *row_end = *end,
+                None => row_ends.push(*end),
+            }
+            row
+        })
+        .collect()
+}
+
+/// Returns the index of the marker closest to a position in time, if any lies
+/// within `reach` of it.
+///
+/// * `clock` - The buffer's current sample clock
+/// * `duration` - The duration of the buffer, in seconds
+fn nearest_marker(
+    store: &MarkerStore,
+    time: f32,
+    clock: (u64, f32),
+    duration: f32,
+    reach: f32,
+) -> Option<usize> {
+    store
+        .markers()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, marker)| {
+            let marker_time = marker_age(marker.position, store.sample_rate(), clock)
+                .and_then(|age| cursor_time(age, (0., duration)))?;
+            let distance = (marker_time - time).abs();
+            (distance <= reach).then_some((index, distance))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(index, _)| index)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RegionPoint {
+    Start(f32, f32),
+    Line(f32, f32),
+    Close,
+}
+
+/// Splits a trace into closed regions above and below the zero level.
+///
+/// `points` are pairs of x positions and normalized values. Each region starts and
+/// ends on the zero level - if the trace crosses it between two points, the x
+/// position of the crossing is interpolated. Points that lie exactly on the zero
+/// level don't belong to any region.
+fn bipolar_regions(
+    points: impl Iterator<Item = (f32, f32)>,
+    zero: f32,
+    mut visit: impl FnMut(bool, RegionPoint),
+) {
+    let side = |v: f32| {
+        if v > zero {
+            Some(true)
+        } else if v < zero {
+            Some(false)
+        } else {
+            None
+        }
+    };
+
+    let mut previous: Option<(f32, f32)> = None;
+    let mut current: Option<bool> = None;
+
+    for (x, v) in points {
+        let next = side(v);
+
+        match (previous, current, next) {
+            // Still on the same side, or still on the zero line
+            (Some(_), current, next) if current == next => {
+                if let Some(above) = next {
+                    visit(above, RegionPoint::Line(x, v));
+                }
+            }
+            // The trace starts inside a region
+            (None, _, Some(above)) => {
+                visit(above, RegionPoint::Start(x, zero));
+                visit(above, RegionPoint::Line(x, v));
+            }
+            // Leaving the zero line
+            (Some((px, _)), None, Some(above)) => {
+                visit(above, RegionPoint::Start(px, zero));
+                visit(above, RegionPoint::Line(x, v));
+            }
+            // Ending up on the zero line, or crossing it
+            (Some((px, pv)), Some(above), next) => {
+                let crossing = match next {
+                    Some(_) => px + (x - px) * (zero - pv) / (v - pv),
+                    None => x,
+                };
+                visit(above, RegionPoint::Line(crossing, zero));
+                visit(above, RegionPoint::Close);
+
+                if let Some(next) = next {
+                    visit(next, RegionPoint::Start(crossing, zero));
+                    visit(next, RegionPoint::Line(x, v));
+                }
+            }
+            _ => {}
+        }
+
+        previous = Some((x, v));
+        current = next;
+    }
+
+    // The trace ends inside a region
+    if let (Some((px, _)), Some(above)) = (previous, current) {
+        visit(above, RegionPoint::Line(px, zero));
+        visit(above, RegionPoint::Close);
+    }
+}
+
+/// Outlines the regions of a deviation band.
+///
+/// `band` holds each element's x position and the bounds of its band, if it has
+/// one. Each run of consecutive elements that have a band becomes one closed
+/// region, along the upper bounds and back along the lower ones. Runs of a single
+/// element, and runs that have no width anywhere, don't cover any area and are
+/// skipped.
+fn band_regions(band: &[Option<(f32, f32, f32)>], mut visit: impl FnMut(RegionPoint)) {
+    let mut i = 0;
+    while i < band.len() {
+        let start = i;
+        while i < band.len() && band[i].is_some() {
+            i += 1;
+        }
+        let run = band[start..i].iter().flatten();
+        if i - start >= 2 && run.clone().any(|(_, low, high)| high > low) {
+            for (j, (x, _, high)) in run.clone().copied().enumerate() {
+                visit(if j == 0 {
+                    RegionPoint::Start(x, high)
+                } else {
+                    RegionPoint::Line(x, high)
+                });
+            }
+            for (x, low, _) in run.rev().copied() {
+                visit(RegionPoint::Line(x, low));
+            }
+            visit(RegionPoint::Close);
+        }
+        // Skips the element without a band that ended the run
+        i += 1;
     }
 }
 
 impl<'a, L, I> FillModifiers for Handle<'a, Graph<L, I>>
 where
-    L: Lens<Target = Arc<Mutex<I>>>,
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
     I: VisualizerBuffer<f32, Output = f32> + 'static,
 {
     /// Allows for the graph to be filled from the top instead of the bottom.
@@ -181,7 +1722,8 @@ where
 
 impl<'a, L, I> RangeModifiers for Handle<'a, Graph<L, I>>
 where
-    L: Lens<Target = Arc<Mutex<I>>>,
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
     I: VisualizerBuffer<f32, Output = f32> + 'static,
 {
     fn range(mut self, range: impl Res<(f32, f32)>) -> Self {
@@ -203,3 +1745,1183 @@ where
         self
     }
 }
+
+pub trait GraphModifiers {
+    fn with_smoothing(self, smoothing: Smoothing) -> Self;
+    fn bipolar(self, zero_value: f32, positive_color: Color, negative_color: Color) -> Self;
+    fn zero_line(self, color: Color) -> Self;
+    fn interactive(self, interactive: bool) -> Self;
+    fn auto_range(self, auto_range: Arc<Mutex<AutoRange>>) -> Self;
+    fn smooth_scrolling(self, smooth_scrolling: bool) -> Self;
+    fn with_transport_shading(self, color: Color) -> Self;
+    fn with_tag_tints(self, tints: impl IntoIterator<Item = (u8, Color)>) -> Self;
+    fn with_tag_dividers(self, color: Color) -> Self;
+    fn with_deviation_band(self, color: Color) -> Self;
+    fn range_segments(self, segments: &[(f32, f32)]) -> Self;
+    fn over_range_style(self, style: OffScaleStyle, color: Color) -> Self;
+    fn under_range_style(self, style: OffScaleStyle, color: Color) -> Self;
+    fn readout(self, position: ReadoutPosition, inset: f32) -> Self;
+    fn readout_overlay(self, registry: &OverlayRegistry) -> Self;
+    fn graph_style(self, style: GraphStyle) -> Self;
+    fn level_colors(self, level_colors: impl IntoIterator<Item = (f32, Color)>) -> Self;
+    fn partial_segments(self, partial_segments: PartialSegments) -> Self;
+    fn value_formatter(self, formatter: impl ValueFormatter + 'static) -> Self;
+    fn markers(self, store: Arc<Mutex<MarkerStore>>) -> Self;
+    fn marker_colors(self, colors: impl IntoIterator<Item = Color>) -> Self;
+    fn editable_markers(self, editable: bool) -> Self;
+}
+impl<'a, L, I> GraphModifiers for Handle<'a, Graph<L, I>>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = I> + 'static,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    /// Smooths the displayed values using a moving average or a median.
+    ///
+    /// The smoothing is only applied when drawing - the buffer itself is left
+    /// untouched. When stacking several graphs, each one can be smoothed (or not)
+    /// individually.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+    ///     .with_smoothing(Smoothing::MovingAverage { slots: 5 })
+    ///     .color(Color::rgba(0, 0, 0, 160));
+    /// ```
+    fn with_smoothing(self, smoothing: Smoothing) -> Self {
+        self.modify(|graph| graph.smoothing = Some(smoothing))
+    }
+    /// Displays bipolar data, such as correlation or mid/side balance.
+    ///
+    /// The graph is filled from the zero value, using one color for the area above
+    /// it and another for the area below it. The line itself is still drawn in the
+    /// graph's color, and the regular fill is replaced.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Graph::new(cx, Data::correlation, (-1.0, 1.0), ValueScaling::Linear)
+    ///     .bipolar(0.0, Color::rgba(0, 255, 0, 60), Color::rgba(255, 0, 0, 60))
+    ///     .zero_line(Color::rgb(80, 80, 80))
+    ///     .color(Color::rgb(160, 160, 160));
+    /// ```
+    fn bipolar(self, zero_value: f32, positive_color: Color, negative_color: Color) -> Self {
+        self.modify(|graph| {
+            graph.bipolar = Some(Bipolar {
+                zero: zero_value,
+                positive: positive_color,
+                negative: negative_color,
+            })
+        })
+    }
+    /// Draws the zero line of a [`bipolar()`](Self::bipolar) graph in the given color.
+    fn zero_line(self, color: Color) -> Self {
+        self.modify(|graph| graph.zero_line = Some(color))
+    }
+    /// Shows the value and age of the slot below the cursor while hovering the
+    /// graph.
+    ///
+    /// A crosshair marks the hovered point, next to a text box like "-7.2 dB, 3.4 s
+    /// ago". The value is formatted according to the graph's scaling, and the age is
+    /// only shown for buffers with a duration. A value beyond the range is followed
+    /// by "(over range)" or "(under range)". Only mouse movement shows the tooltip, so
+    /// it never appears on touch input.
+    ///
+    /// The text box can be styled using the `graph > label` selector.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+    ///     .interactive(true)
+    ///     .color(Color::rgba(0, 0, 0, 160));
+    /// ```
+    fn interactive(self, interactive: bool) -> Self {
+        let mut build_tooltip = false;
+        let mut handle = self.modify(|graph| {
+            build_tooltip = interactive && !graph.interactive;
+            graph.interactive = interactive;
+        });
+
+        if build_tooltip {
+            let e = handle.entity();
+            handle.context().with_current(e, |cx| {
+                GraphTooltip::default().build(cx);
+                Label::new(cx, GraphTooltip::text)
+                    .display(GraphTooltip::visible)
+                    .position_type(PositionType::SelfDirected)
+                    .left(GraphTooltip::left)
+                    .right(GraphTooltip::right)
+                    .top(GraphTooltip::top)
+                    .bottom(GraphTooltip::bottom)
+                    .child_space(Pixels(4.))
+                    .font_size(12.)
+                    .color(Color::rgb(230, 230, 230))
+                    .background_color(Color::rgba(16, 16, 16, 200))
+                    .hoverable(false);
+            });
+        }
+
+        handle
+    }
+    /// Fits the displayed range to the values of the graph, using an
+    /// [`AutoRange`].
+    ///
+    /// Whenever the graph is drawn, it updates the range with the minimum and
+    /// maximum of its values. The range expands immediately, and contracts slowly.
+    /// The graph's own range is ignored while an `AutoRange` is set.
+    ///
+    /// Each `AutoRange` should only be driven by one graph. Other views, like a
+    /// [`Grid`](super::Grid) or a [`UnitRuler`](super::UnitRuler::follow), can follow
+    /// the same range so they stay in line with the graph.
+    ///
+    /// # Example
+    ///
+    /// Here, `auto_range` is an `Arc<Mutex<AutoRange>>`.
+    ///
+    /// ```
+    /// Grid::new(
+    ///     cx,
+    ///     ValueScaling::Linear,
+    ///     (-32., 8.),
+    ///     vec![0.0, -6.0, -12.0, -18.0, -24.0],
+    ///     Orientation::Horizontal,
+    /// )
+    /// .follow_range(auto_range.clone());
+    /// Graph::new(cx, Data::sidechain_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+    ///     .auto_range(auto_range.clone())
+    ///     .color(Color::rgba(0, 0, 0, 160));
+    /// ```
+    fn auto_range(self, auto_range: Arc<Mutex<AutoRange>>) -> Self {
+        self.modify(|graph| graph.auto_range = Some(LiveRange::Drive(auto_range)))
+    }
+    /// Scrolls the graph smoothly in between the buffer's elements.
+    ///
+    /// With few elements, a new one is only finished every so often, which makes the
+    /// graph step sideways. With smooth scrolling, the element that is currently
+    /// being accumulated is drawn as an extra point at the right edge, and the trace
+    /// is shifted by its progress. This only has an effect for buffers that
+    /// accumulate samples, such as the [`PeakBuffer`](crate::utils::PeakBuffer).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+    ///     .smooth_scrolling(true)
+    ///     .color(Color::rgba(0, 0, 0, 160));
+    /// ```
+    fn smooth_scrolling(self, smooth_scrolling: bool) -> Self {
+        self.modify(|graph| graph.smooth_scrolling = smooth_scrolling)
+    }
+    /// Tints the elements during which the host's transport was stopped, so that
+    /// silence can be told apart from a stopped transport while monitoring.
+    ///
+    /// The buffer needs to track the transport - see
+    /// [`PeakBuffer::with_transport_tracking()`](crate::utils::PeakBuffer::with_transport_tracking).
+    /// Otherwise, nothing is drawn.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+    ///     .with_transport_shading(Color::rgba(255, 255, 255, 12))
+    ///     .color(Color::rgba(0, 0, 0, 160));
+    /// ```
+    fn with_transport_shading(self, color: Color) -> Self {
+        self.modify(|graph| graph.transport_shading = Some(color))
+    }
+    /// Tints the elements by the tag that was set during them, so that e.g. the
+    /// processed and the bypassed signal can be told apart in a before/after
+    /// comparison.
+    ///
+    /// Elements whose tag has no tint are left as they are. The buffer needs to track
+    /// tags - see [`PeakBuffer::with_tag_tracking()`](crate::utils::PeakBuffer::with_tag_tracking).
+    /// Otherwise, nothing is drawn.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// // Tag 1 is set while the plug-in is bypassed
+    /// Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+    ///     .with_tag_tints([(1, Color::rgba(255, 160, 0, 24))])
+    ///     .color(Color::rgba(0, 0, 0, 160));
+    /// ```
+    fn with_tag_tints(self, tints: impl IntoIterator<Item = (u8, Color)>) -> Self {
+        let tints = tints.into_iter().collect::<Vec<_>>();
+        self.modify(|graph| graph.tag_tints = tints)
+    }
+    /// Draws a thin line across the graph wherever the tag changes from one element
+    /// to the next; See [`with_tag_tints()`](Self::with_tag_tints).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+    ///     .with_tag_dividers(Color::rgba(255, 255, 255, 80))
+    ///     .color(Color::rgba(0, 0, 0, 160));
+    /// ```
+    fn with_tag_dividers(self, color: Color) -> Self {
+        self.modify(|graph| graph.tag_dividers = Some(color))
+    }
+    /// Shades a band one standard deviation around each element's mean, beneath the
+    /// trace, which shows how steady the measurement is - a narrow band around a
+    /// loudness trace means the level barely moves inside each element.
+    ///
+    /// The band is clamped to the graph. The buffer needs to track the deviation -
+    /// see [`PeakBuffer::with_deviation_tracking()`](crate::utils::PeakBuffer::with_deviation_tracking).
+    /// Otherwise, nothing is drawn.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// // PeakBuffer::with_aggregation(800, 10.0, 0.0, Aggregation::Mean)
+    /// //     .with_deviation_tracking()
+    /// Graph::new(cx, Data::mean_buffer, (0.0, 1.0), ValueScaling::Linear)
+    ///     .with_deviation_band(Color::rgba(255, 255, 255, 40))
+    ///     .color(Color::rgba(255, 255, 255, 160));
+    /// ```
+    fn with_deviation_band(self, color: Color) -> Self {
+        self.modify(|graph| graph.deviation_band = Some(color))
+    }
+    /// Breaks the value axis into several ranges, which are placed next to each other
+    /// with a zigzag marking each break - e.g. to show a limiter's peaks around 0 dB
+    /// along with the noise floor, without wasting the graph on the levels in between.
+    ///
+    /// The segments are given in the units of the range, and replace it - along with
+    /// an [`auto_range()`](Self::auto_range). Values inside a break are clamped to
+    /// the nearest segment, and the fill only covers the segment of its
+    /// [anchor](super::FillModifiers::fill_from_value). Give a [`Grid`](super::Grid) the
+    /// same segments, and a [`UnitRuler`](super::UnitRuler) that's built using
+    /// [`UnitRuler::segmented()`](super::UnitRuler::segmented), so that they line up.
+    /// See [`RangeSegments`] for how the axis is laid out.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Graph::new(cx, Data::peak_buffer, (-80.0, 0.0), ValueScaling::Decibels)
+    ///     .range_segments(&[(-80., -60.), (-12., 0.)])
+    ///     .color(Color::rgba(255, 255, 255, 160));
+    /// ```
+    fn range_segments(self, segments: &[(f32, f32)]) -> Self {
+        let segments = RangeSegments::new(segments);
+        self.modify(|graph| graph.segments = Some(segments))
+    }
+    /// Marks the elements whose values lie above the range, e.g. intersample overs
+    /// on a graph that tops out at 0 dB.
+    ///
+    /// Values beyond the range are clamped to its edge, so the trace alone can't tell
+    /// a value at the edge from one that went over it. The marker is drawn on the
+    /// edge, in the given color, and scrolls along with the elements. While hovering
+    /// an [interactive](Self::interactive) graph, the tooltip shows the true value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Graph::new(cx, Data::peak_buffer, (-32.0, 0.0), ValueScaling::Decibels)
+    ///     .over_range_style(OffScaleStyle::Triangle { size_px: 6.0 }, Color::rgb(255, 80, 80))
+    ///     .color(Color::rgba(255, 255, 255, 160));
+    /// ```
+    fn over_range_style(self, style: OffScaleStyle, color: Color) -> Self {
+        self.modify(|graph| graph.over_range = Some((style, color)))
+    }
+    /// Marks the elements whose values lie below the range, like
+    /// [`over_range_style()`](Self::over_range_style).
+    ///
+    /// On a [`ValueScaling::Decibels`] scale, silence lies below any range.
+    fn under_range_style(self, style: OffScaleStyle, color: Color) -> Self {
+        self.modify(|graph| graph.under_range = Some((style, color)))
+    }
+    /// Shows the newest value in a corner of the graph, like "-7.2 dB".
+    ///
+    /// The value is formatted according to the graph's scaling, and updated whenever
+    /// the buffer is written to. The readout is moved inwards from the corner by
+    /// `inset` logical pixels.
+    ///
+    /// The text box can be styled using the `graph > label` selector. To keep other
+    /// views clear of it, register it using
+    /// [`readout_overlay()`](Self::readout_overlay).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+    ///     .readout(ReadoutPosition::TopRight, 8.0)
+    ///     .color(Color::rgba(0, 0, 0, 160));
+    /// ```
+    fn readout(self, position: ReadoutPosition, inset: f32) -> Self {
+        let mut build_readout = None;
+        let mut handle = self.modify(|graph| match &mut graph.readout {
+            Some(readout) => {
+                readout.position = position;
+                readout.inset = inset;
+            }
+            None => {
+                build_readout = Some(graph.buffer.clone());
+                graph.readout = Some(Readout {
+                    position,
+                    inset,
+                    overlay: None,
+                });
+            }
+        });
+
+        let e = handle.entity();
+        let Some(buffer) = build_readout else {
+            handle
+                .context()
+                .emit_to(e, GraphReadoutEvents::Place(position, inset));
+            return handle;
+        };
+
+        handle.context().with_current(e, |cx| {
+            GraphReadout::new(position, inset).build(cx);
+            Label::new(cx, GraphReadout::text)
+                .position_type(PositionType::SelfDirected)
+                .left(GraphReadout::left)
+                .right(GraphReadout::right)
+                .top(GraphReadout::top)
+                .bottom(GraphReadout::bottom)
+                .width(Pixels(READOUT_SIZE.0))
+                .height(Pixels(READOUT_SIZE.1))
+                .child_space(Pixels(4.))
+                .font_size(12.)
+                .color(Color::rgb(230, 230, 230))
+                .background_color(Color::rgba(16, 16, 16, 200))
+                .hoverable(false);
+
+            // Synced whenever the buffer was written to, rather than on every frame
+            Binding::new(cx, buffer.clone().revision(), move |cx, _| {
+                cx.emit_to(e, GraphEvents::SyncReadout);
+            });
+        });
+
+        handle
+    }
+    /// Registers the area of the graph's [`readout()`](Self::readout) with an
+    /// [`OverlayRegistry`], so that other views can stay clear of it.
+    ///
+    /// The area is updated whenever the graph is drawn. This has no effect until a
+    /// readout is shown.
+    fn readout_overlay(self, registry: &OverlayRegistry) -> Self {
+        let slot = registry.slot();
+        self.modify(|graph| {
+            if let Some(readout) = &mut graph.readout {
+                readout.overlay = Some(slot);
+            }
+        })
+    }
+    /// Draws the graph as a continuous trace, or as discrete segments like the LEDs of
+    /// a hardware meter; See [`GraphStyle`].
+    ///
+    /// Segments are filled in the graph's color, or in the colors of the levels they
+    /// lie above - see [`level_colors()`](Self::level_colors). They replace the
+    /// trace and its fill, including a [`bipolar()`](Self::bipolar) fill. Segments of
+    /// the same color are drawn at once, so that their number doesn't add draw calls.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+    ///     .graph_style(GraphStyle::Segments {
+    ///         segment_height_px: 3.0,
+    ///         gap_px: 1.0,
+    ///     })
+    ///     .partial_segments(PartialSegments::Dim);
+    /// ```
+    fn graph_style(self, style: GraphStyle) -> Self {
+        self.modify(|graph| graph.style = style)
+    }
+    /// Colors the segments of a graph that is drawn as [`GraphStyle::Segments`] by
+    /// their level, e.g. in green, yellow and red bands.
+    ///
+    /// Each segment takes the color of the highest level that lies below its center.
+    /// Levels are given in the units of the graph's range, like its fill level.
+    /// Segments below the lowest level keep the graph's color.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+    ///     .graph_style(GraphStyle::Segments {
+    ///         segment_height_px: 3.0,
+    ///         gap_px: 1.0,
+    ///     })
+    ///     .level_colors([
+    ///         (-32.0, Color::rgb(80, 200, 120)),
+    ///         (-12.0, Color::rgb(230, 200, 80)),
+    ///         (0.0, Color::rgb(230, 80, 80)),
+    ///     ]);
+    /// ```
+    fn level_colors(self, level_colors: impl IntoIterator<Item = (f32, Color)>) -> Self {
+        let mut level_colors = level_colors.into_iter().collect::<Vec<_>>();
+        level_colors.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self.modify(|graph| graph.level_colors = level_colors)
+    }
+    /// Sets whether a segment that a value lies inside of stays dark, or lights up
+    /// dimmed; See [`PartialSegments`].
+    fn partial_segments(self, partial_segments: PartialSegments) -> Self {
+        self.modify(|graph| graph.partial_segments = partial_segments)
+    }
+    /// Formats the values of the [`readout()`](Self::readout) and the tooltip of an
+    /// [`interactive()`](Self::interactive) graph; See [`ValueFormatter`].
+    ///
+    /// Values are passed in the units of the graph's range, so a graph that uses
+    /// [`ValueScaling::Decibels`] passes decibels. By default, decibels are shown with
+    /// one decimal, frequencies in whole hertz, and other values with two decimals.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Graph::new(cx, Data::loudness_buffer, (-40.0, 0.0), ValueScaling::Linear)
+    ///     .readout(ReadoutPosition::TopRight, 8.0)
+    ///     .value_formatter(DecibelFormatter {
+    ///         precision: 1,
+    ///         suffix: " LUFS",
+    ///     });
+    /// ```
+    fn value_formatter(self, formatter: impl ValueFormatter + 'static) -> Self {
+        self.modify(|graph| graph.formatter = Some(Box::new(formatter)))
+    }
+    /// Draws the markers of a [`MarkerStore`], each at the moment of the buffer's
+    /// history it's pinned to.
+    ///
+    /// Each marker is a line across the graph in its color, with its label at the
+    /// top of the graph; Labels that would overlap are stacked below each other. The
+    /// markers scroll along with the buffer, and are evicted from the store once
+    /// they have scrolled out of it. The graph keeps the store at its buffer's
+    /// sample rate.
+    ///
+    /// Only buffers that keep a [sample clock](VisualizerBuffer::sample_clock), like
+    /// the [`PeakBuffer`](crate::utils::PeakBuffer) and the
+    /// [`RMSBuffer`](crate::utils::RMSBuffer), show markers. Labels are only shown
+    /// while the time axis runs horizontally.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// // Inside the plug-in, next to the buffer
+    /// marker_store: Arc::new(Mutex::new(MarkerStore::new(0.0))),
+    ///
+    /// Graph::new(cx, Data::loudness_buffer, (-40.0, 0.0), ValueScaling::Decibels)
+    ///     .markers(editor_data.marker_store.clone())
+    ///     .marker_colors([Color::rgb(255, 200, 80), Color::rgb(120, 200, 255)])
+    ///     .editable_markers(true);
+    /// ```
+    fn markers(self, store: Arc<Mutex<MarkerStore>>) -> Self {
+        let mut build_labels = false;
+        let mut handle = self.modify(|graph| {
+            build_labels = graph.markers.is_none();
+            graph.markers = Some(store);
+        });
+        if !build_labels {
+            return handle;
+        }
+
+        let e = handle.entity();
+        handle.context().with_current(e, |cx| {
+            GraphMarkers::default().build(cx);
+            Binding::new(cx, GraphMarkers::labels, |cx, labels| {
+                for label in labels.get(cx) {
+                    Label::new(cx, label.text)
+                        .position_type(PositionType::SelfDirected)
+                        .left(Pixels(label.left))
+                        .top(Pixels(label.top))
+                        .height(Pixels(MARKER_ROW_HEIGHT))
+                        .child_space(Pixels(2.))
+                        .font_size(MARKER_FONT_SIZE)
+                        .color(label.color)
+                        .background_color(Color::rgba(16, 16, 16, 200))
+                        .hoverable(false);
+                }
+            });
+
+            let timer = cx.add_timer(Duration::from_millis(16), None, move |cx, action| {
+                if let TimerAction::Tick(_) = action {
+                    cx.emit_to(e, GraphEvents::SyncMarkers);
+                }
+            });
+            cx.start_timer(timer);
+        });
+
+        handle
+    }
+    /// Sets the colors that the markers' color indices pick from, wrapping around
+    /// past the last one.
+    ///
+    /// Without any colors, the markers are drawn in the graph's color.
+    fn marker_colors(self, colors: impl IntoIterator<Item = Color>) -> Self {
+        let colors = colors.into_iter().collect();
+        self.modify(|graph| graph.marker_colors = colors)
+    }
+    /// Lets the [`markers()`](Self::markers) be edited by clicking the graph.
+    ///
+    /// A click adds a marker at the moment below the cursor, with the store's
+    /// [next label](MarkerStore::set_next_label), and a right click removes the
+    /// marker closest to the cursor.
+    fn editable_markers(self, editable: bool) -> Self {
+        self.modify(|graph| graph.editable_markers = editable)
+    }
+}
+
+impl<'a, L, I> AutoRangeModifiers for Handle<'a, Graph<L, I>>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    fn follow_range(self, auto_range: Arc<Mutex<AutoRange>>) -> Self {
+        self.modify(|graph| {
+            graph.auto_range = Some(LiveRange::Follow(RangeSource::Auto(auto_range)))
+        })
+    }
+    fn follow_shared_range(self, range: SharedRange) -> Self {
+        self.modify(|graph| graph.auto_range = Some(LiveRange::Follow(RangeSource::Shared(range))))
+    }
+}
+
+impl<'a, L, I> TimeCursorModifiers for Handle<'a, Graph<L, I>>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    fn time_cursor<C: Lens<Target = TimeCursor>>(mut self, cursor: C) -> Self {
+        let link = CursorLink::new(&mut self, cursor);
+        self.modify(|graph| graph.time_cursor = Some(link))
+    }
+}
+
+impl<'a, L, I> AxisModifiers for Handle<'a, Graph<L, I>>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    fn value_axis(self, orientation: Orientation) -> Self {
+        self.modify(|graph| graph.value_axis = orientation)
+    }
+    fn mirror_x(self, mirror: bool) -> Self {
+        self.modify(|graph| graph.mirror_x = mirror)
+    }
+    /// Sets which end of the value axis the range starts at; See
+    /// [`AxisModifiers`].
+    ///
+    /// The graph is still filled from the bottom of its range, which now lies at the
+    /// top edge, and the stroke follows the values - so a top-down graph hangs down
+    /// from the top, with the stroke along the lower edge of its fill. Fill levels
+    /// and the zero line of a bipolar graph move along with the values.
+    fn direction(self, direction: GraphDirection) -> Self {
+        self.modify(|graph| graph.direction = direction)
+    }
+}
+
+impl<'a, L, I> PaddingModifiers for Handle<'a, Graph<L, I>>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    fn inner_padding(self, top: f32, right: f32, bottom: f32, left: f32) -> Self {
+        self.modify(|graph| graph.padding = InnerPadding::new(top, right, bottom, left))
+    }
+    fn uniform_inner_padding(self, padding: f32) -> Self {
+        self.modify(|graph| graph.padding = InnerPadding::uniform(padding))
+    }
+}
+
+impl<'a, L, I> ThrottleModifiers for Handle<'a, Graph<L, I>>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    fn max_fps(self, max_fps: f32) -> Self {
+        self.modify(|view| view.throttle.set_max_fps(max_fps))
+    }
+}
+
+impl<'a, L, I> AdaptiveQualityModifiers for Handle<'a, Graph<L, I>>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    fn adaptive_quality(self, stats: &FrameStats) -> Self {
+        let stats = stats.clone();
+        self.modify(|view| view.quality = Some(stats))
+    }
+}
+
+impl<'a, L, I> StaleIndicatorModifiers for Handle<'a, Graph<L, I>>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    fn stale_indicator(mut self, heartbeat: Heartbeat, threshold: Duration) -> Self {
+        let entity = self.entity();
+        build_stale_indicator(self.context(), entity, heartbeat, threshold);
+        self
+    }
+}
+
+impl<'a, L, I> IdleModifiers for Handle<'a, Graph<L, I>>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    fn idle_after(mut self, after: Duration, style: IdleStyle) -> Self {
+        let entity = self.entity();
+        build_idle(self.context(), entity, style);
+        self.modify(|graph| graph.idle = Some(IdleState::new(after, style)))
+    }
+}
+
+impl<'a, L, I> ClearFadeModifiers for Handle<'a, Graph<L, I>>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    fn fade_clears(self, fade: bool) -> Self {
+        self.modify(|graph| graph.clear_fade = fade.then(|| RefCell::new(ClearFade::default())))
+    }
+    fn clear_fade_duration(self, duration: Duration) -> Self {
+        self.modify(|graph| {
+            graph
+                .clear_fade
+                .get_or_insert_with(|| RefCell::new(ClearFade::default()))
+                .get_mut()
+                .set_duration(duration)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        band_regions, bipolar_regions, break_marker, fill_outline, lit_segments, nearest_marker,
+        off_scale_flags, off_scale_runs, off_scale_triangle, segment_color, segment_runs, slot_at,
+        split_restored, stack_labels, stopped_runs, tag_runs, tooltip_anchor, tooltip_text,
+        PartialSegments, RegionPoint,
+    };
+    use crate::alloc_counter::allocations;
+    use crate::geometry::graph_points;
+    use crate::utils::{
+        default_formatter, FrequencyFormatter, MarkerStore, NormalizedRead, OffScale, PeakBuffer,
+        Smoothing, SmoothingScratch, ValueFormatter, ValueScaling, VisualizerBuffer,
+    };
+    use crate::visualizers::quality::coarsen_points;
+    use crate::visualizers::FillFrom;
+    use nih_plug_vizia::vizia::prelude::{Color, Pixels, Stretch};
+    use nih_plug_vizia::vizia::vg;
+    use std::fmt::Write;
+    use RegionPoint::*;
+
+    fn regions(values: &[f32]) -> Vec<(bool, RegionPoint)> {
+        let mut regions = Vec::new();
+        bipolar_regions(
+            values.iter().enumerate().map(|(i, v)| (i as f32, *v)),
+            0.5,
+            |above, point| regions.push((above, point)),
+        );
+        regions
+    }
+
+    #[test]
+    fn one_side() {
+        assert_eq!(
+            regions(&[0.6, 0.8, 0.7]),
+            vec![
+                (true, Start(0., 0.5)),
+                (true, Line(0., 0.6)),
+                (true, Line(1., 0.8)),
+                (true, Line(2., 0.7)),
+                (true, Line(2., 0.5)),
+                (true, Close),
+            ]
+        );
+    }
+
+    #[test]
+    fn crossing() {
+        // Crosses the zero line a quarter of the way between the two points
+        assert_eq!(
+            regions(&[0.625, 0.125]),
+            vec![
+                (true, Start(0., 0.5)),
+                (true, Line(0., 0.625)),
+                (true, Line(0.25, 0.5)),
+                (true, Close),
+                (false, Start(0.25, 0.5)),
+                (false, Line(1., 0.125)),
+                (false, Line(1., 0.5)),
+                (false, Close),
+            ]
+        );
+    }
+
+    #[test]
+    fn zeros() {
+        // Consecutive zeros end the region, and a new one starts after them
+        assert_eq!(
+            regions(&[0.7, 0.5, 0.5, 0.3]),
+            vec![
+                (true, Start(0., 0.5)),
+                (true, Line(0., 0.7)),
+                (true, Line(1., 0.5)),
+                (true, Close),
+                (false, Start(2., 0.5)),
+                (false, Line(3., 0.3)),
+                (false, Line(3., 0.5)),
+                (false, Close),
+            ]
+        );
+
+        assert!(regions(&[0.5, 0.5, 0.5]).is_empty());
+        assert!(regions(&[]).is_empty());
+    }
+
+    fn band(band: &[Option<(f32, f32, f32)>]) -> Vec<RegionPoint> {
+        let mut regions = Vec::new();
+        band_regions(band, |point| regions.push(point));
+        regions
+    }
+
+    #[test]
+    fn band_outlines() {
+        // One region per run, up along the upper bounds and back along the lower ones
+        assert_eq!(
+            band(&[
+                Some((0., 0.4, 0.6)),
+                Some((1., 0.3, 0.5)),
+                None,
+                Some((3., 0.2, 0.2)),
+                Some((4., 0.1, 0.3)),
+            ]),
+            vec![
+                Start(0., 0.6),
+                Line(1., 0.5),
+                Line(1., 0.3),
+                Line(0., 0.4),
+                Close,
+                Start(3., 0.2),
+                Line(4., 0.3),
+                Line(4., 0.1),
+                Line(3., 0.2),
+                Close,
+            ]
+        );
+
+        // Single elements and bands without any width don't cover an area
+        assert!(band(&[Some((0., 0.4, 0.6)), None, Some((2., 0.4, 0.6))]).is_empty());
+        assert!(band(&[Some((0., 0.5, 0.5)), Some((1., 0.5, 0.5))]).is_empty());
+        assert!(band(&[None, None]).is_empty());
+        assert!(band(&[]).is_empty());
+    }
+
+    #[test]
+    fn break_markers() {
+        let marker = break_marker(0.5, 0.75).collect::<Vec<_>>();
+        assert_eq!(marker.first(), Some(&(0., 0.5625)));
+        assert_eq!(marker.last(), Some(&(1., 0.5625)));
+        // It zigzags inside the middle half of the break
+        assert!(marker.iter().all(|(_, v)| *v == 0.5625 || *v == 0.6875));
+        assert!(marker.windows(2).all(|pair| pair[0].1 != pair[1].1));
+    }
+
+    #[test]
+    fn band_clamped() {
+        // A band reaching past the range, or below 0 in decibels, ends at the edges
+        let values = NormalizedRead::new(&(), (-24., 0.), ValueScaling::Decibels);
+        let (mean, deviation) = (0.5f32, 0.75f32);
+        assert_eq!(values.normalize_value(mean - deviation), 0.);
+        assert_eq!(values.normalize_value(mean + deviation), 1.);
+        let values = NormalizedRead::new(&(), (0., 1.), ValueScaling::Linear);
+        assert_eq!(values.normalize_value(f32::NAN), 0.);
+    }
+
+    #[test]
+    fn hovered_slot() {
+        // 4 slots drawn at x = 10, 35, 60 and 85
+        assert_eq!(slot_at(10., 10., 100., 4), Some(0));
+        assert_eq!(slot_at(47., 10., 100., 4), Some(1));
+        assert_eq!(slot_at(49., 10., 100., 4), Some(2));
+        // Past the last point, up until the right edge
+        assert_eq!(slot_at(110., 10., 100., 4), Some(3));
+
+        assert_eq!(slot_at(9., 10., 100., 4), None);
+        assert_eq!(slot_at(111., 10., 100., 4), None);
+        assert_eq!(slot_at(50., 10., 100., 0), None);
+    }
+
+    #[test]
+    fn tooltip_formatting() {
+        let text = |formatter: &dyn ValueFormatter, value, scaling, age| {
+            let mut out = String::new();
+            tooltip_text(formatter, value, scaling, None, age, &mut out);
+            out
+        };
+        let default = default_formatter;
+        assert_eq!(
+            text(
+                default(ValueScaling::Decibels),
+                0.5,
+                ValueScaling::Decibels,
+                Some(3.4)
+            ),
+            "-6.0 dB, 3.4 s ago"
+        );
+        assert_eq!(
+            text(
+                default(ValueScaling::Frequency),
+                440.,
+                ValueScaling::Frequency,
+                None
+            ),
+            "440 Hz"
+        );
+        assert_eq!(
+            text(
+                default(ValueScaling::Linear),
+                0.25,
+                ValueScaling::Linear,
+                None
+            ),
+            "0.25"
+        );
+        // A unity gain reads 0 dB, not -0 dB
+        assert_eq!(
+            text(
+                default(ValueScaling::Decibels),
+                1.,
+                ValueScaling::Decibels,
+                None
+            ),
+            "0.0 dB"
+        );
+
+        let frequency = FrequencyFormatter { suffix: "Hz" };
+        assert_eq!(
+            text(&frequency, 2500., ValueScaling::Frequency, None),
+            "2.5kHz"
+        );
+        // Formatters are passed the values in decibels
+        let signed = |value: f32, out: &mut String| {
+            let _ = write!(out, "{:+.1}", value);
+        };
+        assert_eq!(
+            text(&signed, 0.5, ValueScaling::Decibels, Some(1.)),
+            "-6.0, 1.0 s ago"
+        );
+
+        // A value beyond the range shows that the trace was flattened
+        let mut out = String::new();
+        tooltip_text(
+            default(ValueScaling::Decibels),
+            1.12,
+            ValueScaling::Decibels,
+            Some(OffScale::Over),
+            Some(0.5),
+            &mut out,
+        );
+        assert_eq!(out, "1.0 dB (over range), 0.5 s ago");
+    }
+
+    #[test]
+    fn off_scale_markers() {
+        // 0 dB at the top, with an intersample over, a run of clipping and silence
+        let mut buffer = PeakBuffer::new(6, 1.0, 0.0);
+        for (i, value) in [1.0, 1.12, 0.5, 2.0, 4.0, 0.0].into_iter().enumerate() {
+            buffer[i] = value;
+        }
+        let values = NormalizedRead::new(&buffer, (-24.0, 0.0), ValueScaling::Decibels);
+        let mut flags = Vec::new();
+        off_scale_flags(&mut flags, &values, Some((0.5, 1.5)));
+        assert_eq!(
+            flags,
+            vec![
+                None,
+                Some(OffScale::Over),
+                None,
+                Some(OffScale::Over),
+                Some(OffScale::Over),
+                Some(OffScale::Under),
+                Some(OffScale::Over),
+            ]
+        );
+        assert_eq!(
+            off_scale_runs(&flags, OffScale::Over).collect::<Vec<_>>(),
+            vec![1..2, 3..5, 6..7]
+        );
+        assert_eq!(
+            off_scale_runs(&flags, OffScale::Under).collect::<Vec<_>>(),
+            vec![5..6]
+        );
+        assert_eq!(off_scale_runs(&[], OffScale::Over).count(), 0);
+    }
+
+    #[test]
+    fn off_scale_triangles() {
+        // On the top edge of a bottom-up graph, pointing up
+        assert_eq!(
+            off_scale_triangle((50., 0.), (50., 40.), 8.),
+            Some([(50., 0.), (46., 8.), (54., 8.)])
+        );
+        // On the left edge of a horizontal one, pointing left
+        assert_eq!(
+            off_scale_triangle((0., 30.), (20., 30.), 4.),
+            Some([(0., 30.), (4., 32.), (4., 28.)])
+        );
+        assert_eq!(off_scale_triangle((5., 5.), (5., 5.), 4.), None);
+    }
+
+    #[test]
+    fn tooltip_stays_inside() {
+        assert_eq!(tooltip_anchor(10., 200., 8.), (Pixels(18.), Stretch(1.0)));
+        assert_eq!(tooltip_anchor(190., 200., 8.), (Stretch(1.0), Pixels(18.)));
+    }
+
+    #[test]
+    fn steady_state_doesnt_allocate() {
+        let mut buffer = PeakBuffer::new(800, 1.0, 50.0);
+        buffer.set_sample_rate(48000.);
+        for i in 0..48000 {
+            buffer.enqueue((i as f32 * 0.01).sin());
+        }
+
+        let smoothing = Smoothing::Median { slots: 5 };
+        let (mut points, mut scratch) = (Vec::new(), SmoothingScratch::default());
+        let mut draw = |buffer: &PeakBuffer| {
+            let values = NormalizedRead::new(buffer, (-32., 8.), ValueScaling::Decibels);
+            let smoothed = smoothing.apply((0..buffer.len()).map(|i| buffer[i]), &mut scratch);
+            graph_points(
+                &mut points,
+                &values,
+                Some(smoothed),
+                buffer.partial(),
+                0.,
+                400.,
+            );
+            coarsen_points(&mut points, 0.5);
+        };
+        // The first frame grows the scratch vectors to the buffer's length
+        draw(&buffer);
+        for _ in 0..10 {
+            buffer.enqueue(0.5);
+            assert_eq!(allocations(|| draw(&buffer)), 0);
+        }
+    }
+
+    #[test]
+    fn transport_runs() {
+        let playing = [true, false, false, true, false, true, false, false];
+        let runs = |len| stopped_runs(|i| playing.get(i).copied(), len).collect::<Vec<_>>();
+        assert_eq!(runs(8), vec![1..3, 4..5, 6..8]);
+        assert_eq!(runs(7), vec![1..3, 4..5, 6..7]);
+        assert!(stopped_runs(|_| None, 8).next().is_none());
+    }
+
+    #[test]
+    fn runs_of_tags() {
+        let tags = [0, 0, 1, 1, 1, 0, 2, 2];
+        let runs = |len| tag_runs(|i| tags.get(i).copied(), len).collect::<Vec<_>>();
+        assert_eq!(runs(8), vec![(0, 0..2), (1, 2..5), (0, 5..6), (2, 6..8)]);
+        assert_eq!(runs(3), vec![(0, 0..2), (1, 2..3)]);
+        assert!(tag_runs(|_| None, 8).next().is_none());
+    }
+
+    fn fill_anchor(level: f32, range: (f32, f32), scaling: ValueScaling) -> Vec<(f32, f32)> {
+        let points = [(0., 0.25), (50., 0.75)];
+        let values = NormalizedRead::new(&(), range, scaling);
+        let anchor = FillFrom::Value(level).normalized(&values);
+        fill_outline(&points, anchor, 0., 100.).collect()
+    }
+
+    #[test]
+    fn fill_anchor_edges() {
+        for scaling in [ValueScaling::Linear, ValueScaling::Decibels] {
+            let anchor = |level| fill_anchor(level, (-48., 0.), scaling)[2].1;
+
+            // Just inside, exactly at, and beyond the bottom edge
+            assert!(anchor(-47.9) > 0. && anchor(-47.9) < 0.01);
+            assert_eq!(anchor(-48.), 0.);
+            assert_eq!(anchor(-60.), 0.);
+            assert_eq!(anchor(f32::NEG_INFINITY), 0.);
+
+            // And the top edge
+            assert!(anchor(-0.1) < 1. && anchor(-0.1) > 0.99);
+            assert_eq!(anchor(0.), 1.);
+            assert_eq!(anchor(12.), 1.);
+            assert_eq!(anchor(f32::INFINITY), 1.);
+        }
+    }
+
+    #[test]
+    fn fill_outline_stays_in_view() {
+        // The trace, closed along the anchor from the right to the left edge
+        assert_eq!(
+            fill_anchor(12., (-48., 0.), ValueScaling::Linear),
+            vec![(0., 0.25), (50., 0.75), (100., 1.), (0., 1.)]
+        );
+
+        // Moving the range past the anchor never flips the fill to the other side
+        let mut previous = 0.;
+        for i in 0..=100 {
+            let max = 10. - i as f32 * 0.2;
+            let outline = fill_anchor(0., (max - 48., max), ValueScaling::Linear);
+            let anchor = outline[2].1;
+            assert!(outline.iter().all(|(_, v)| (0.0..=1.0).contains(v)));
+            assert!(anchor >= previous);
+            previous = anchor;
+        }
+        assert_eq!(previous, 1.);
+
+        // Neither does a range that collapses halfway through a transition
+        assert_eq!(fill_anchor(0., (-6., -6.), ValueScaling::Linear)[2].1, 1.);
+        assert_eq!(fill_anchor(-12., (-6., -6.), ValueScaling::Linear)[2].1, 0.);
+    }
+
+    #[test]
+    #[cfg(feature = "debug-tools")]
+    fn replayed_fill_outline_stays_in_view() {
+        use crate::utils::{replay, ReplayLog};
+
+        let log = ReplayLog::parse(include_bytes!(
+            "../../testdata/replay/fill_from_outline.cyrl"
+        ))
+        .unwrap();
+        assert!(!log.is_truncated());
+        let mut buffer = PeakBuffer::new(100, 1.0, 0.0);
+        buffer.set_sample_rate(1000.);
+        replay(&log, &mut buffer);
+
+        let mut points = Vec::new();
+        for i in 0..=100 {
+            let max = 10. - i as f32 * 0.2;
+            for range in [(max - 48., max), (max, max)] {
+                let values = NormalizedRead::new(&buffer, range, ValueScaling::Decibels);
+                graph_points(&mut points, &values, None, buffer.partial(), 0., 100.);
+                let anchor = FillFrom::Value(0.).normalized(&values);
+                assert!(
+                    fill_outline(&points, anchor, 0., 100.).all(|(_, v)| (0.0..=1.0).contains(&v))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn restored_points_split() {
+        let points: Vec<(f32, f32)> = (0..5).map(|i| (i as f32, 0.5)).collect();
+
+        assert_eq!(split_restored(&points, 0), (&[][..], &points[..]));
+        assert_eq!(split_restored(&points, 2), (&points[..=2], &points[2..]));
+        // Fully restored, all but the last point are dimmed
+        assert_eq!(split_restored(&points, 8), (&points[..], &points[4..]));
+        assert_eq!(split_restored(&[], 3), (&[][..], &[][..]));
+    }
+
+    #[test]
+    fn lit_segment_edges() {
+        // Segments of 0.2, with gaps of 0.05
+        let lit = |distance| lit_segments(distance, 0.25, 0.2);
+
+        assert_eq!(lit(0.), (0, false));
+        assert_eq!(lit(-0.5), (0, false));
+        assert_eq!(lit(0.1), (0, true));
+        // Right on the top edge of a segment, and inside of the gap above it
+        assert_eq!(lit(0.2), (1, false));
+        assert_eq!(lit(0.22), (1, false));
+        assert_eq!(lit(0.25), (1, false));
+        assert_eq!(lit(0.3), (1, true));
+        assert_eq!(lit(1.0), (4, false));
+        assert_eq!(lit(0.5), (2, false));
+        assert_eq!(lit_segments(0.5, 0., 0.), (0, false));
+    }
+
+    #[test]
+    fn segments_merge_into_runs() {
+        let lit = [(2, false), (2, true), (1, true), (0, false)];
+        let runs = |partial_segments| {
+            let mut runs = Vec::new();
+            segment_runs(&lit, partial_segments, |segment, range, dimmed| {
+                runs.push((segment, range, dimmed))
+            });
+            runs
+        };
+
+        assert_eq!(
+            runs(PartialSegments::Drop),
+            [(0, 0..3, false), (1, 0..2, false)]
+        );
+        assert_eq!(
+            runs(PartialSegments::Dim),
+            [
+                (0, 0..3, false),
+                (1, 0..2, false),
+                (1, 2..3, true),
+                (2, 1..2, true),
+            ]
+        );
+        segment_runs(&[], PartialSegments::Dim, |_, _, _| panic!());
+    }
+
+    #[test]
+    fn segment_colors() {
+        let green = Color::rgb(0, 255, 0);
+        let red = Color::rgb(255, 0, 0);
+        let default = vg::Color::rgb(0, 0, 0);
+        let levels = [(-12., green), (0., red)];
+        let normalize = |level: f32| (level + 24.) / 32.;
+
+        assert_eq!(segment_color(&levels, normalize, 0.2, default), default);
+        assert_eq!(
+            segment_color(&levels, normalize, 0.375, default),
+            green.into()
+        );
+        assert_eq!(
+            segment_color(&levels, normalize, 0.7, default),
+            green.into()
+        );
+        assert_eq!(segment_color(&levels, normalize, 0.75, default), red.into());
+        assert_eq!(segment_color(&[], normalize, 1., default), default);
+    }
+
+    #[test]
+    fn marker_label_rows() {
+        // Labels that clear the one before them stay in the top row
+        assert_eq!(
+            stack_labels(&[(0., 40.), (50., 90.), (90., 120.)]),
+            vec![0, 0, 0]
+        );
+        // Overlapping labels are stacked, and move back up once there's room
+        assert_eq!(
+            stack_labels(&[(0., 40.), (10., 50.), (20., 60.), (45., 80.), (70., 90.)]),
+            vec![0, 1, 2, 0, 1]
+        );
+        assert_eq!(stack_labels(&[]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn nearest_markers() {
+        // A buffer spanning 10 seconds at 100 Hz, 20 seconds in
+        let clock = (2000, 100.);
+        let mut store = MarkerStore::new(100.);
+        store.add(1500, "5 s ago", 0);
+        store.add(1900, "1 s ago", 0);
+        store.add(1950, "0.5 s ago", 0);
+        // Scrolled out, and ahead of the clock
+        store.add(500, "", 0);
+        store.add(2500, "", 0);
+
+        assert_eq!(nearest_marker(&store, 0.5, clock, 10., 0.01), Some(1));
+        assert_eq!(nearest_marker(&store, 0.52, clock, 10., 0.01), None);
+        // The closest of two within reach
+        assert_eq!(nearest_marker(&store, 0.92, clock, 10., 0.05), Some(2));
+        assert_eq!(nearest_marker(&store, 0.94, clock, 10., 0.05), Some(3));
+        assert_eq!(nearest_marker(&store, 0., clock, 10., 0.05), None);
+        assert_eq!(nearest_marker(&store, 1., clock, 10., 0.01), None);
+    }
+}
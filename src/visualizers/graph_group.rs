@@ -0,0 +1,586 @@
+use super::elements;
+use super::padding::InnerPadding;
+use super::plot_area::PlotArea;
+use super::{
+    draw_throttled, redraw_on_write, FillFrom, FillModifiers, FrameThrottle, RangeModifiers,
+    ThrottleModifiers,
+};
+use crate::geometry::trace_points;
+use crate::utils::{NormalizedRead, SharedRead, ValueScaling, VisualizerBuffer};
+
+use nih_plug_vizia::vizia::{prelude::*, vg};
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Several graphs that share the same bounds, drawn using as few draw calls as
+/// possible.
+///
+/// Stacking many [`Graph`](super::Graph)s on top of each other issues two draw
+/// calls per graph. A `GraphGroup` instead collects the geometry of all of its
+/// traces into one path per color, so traces that share a fill or stroke color
+/// are drawn in a single call. Traces with a transparent color skip that pass
+/// entirely.
+///
+/// Since traces with the same fill color are filled as one path, overlapping
+/// fills of the same color don't add up like they would with separate graphs.
+///
+/// # Example
+///
+/// Here's a group of three peak graphs with individual stroke colors and a shared
+/// fill, which takes 4 draw calls instead of 6.
+///
+/// ```
+/// GraphGroup::new(cx, (-32.0, 8.0), ValueScaling::Decibels)
+///     .trace(Data::low_band, Color::rgb(255, 80, 80), Color::rgba(255, 255, 255, 30))
+///     .trace(Data::mid_band, Color::rgb(80, 255, 80), Color::rgba(255, 255, 255, 30))
+///     .trace(Data::high_band, Color::rgb(80, 80, 255), Color::rgba(255, 255, 255, 30));
+/// ```
+///
+/// # Styling
+///
+/// Stylesheets select it as `cyma-graph-group`. It draws nothing itself - its graphs
+/// are styled as `cyma-graph`.
+pub struct GraphGroup {
+    traces: Vec<Trace>,
+    range: (f32, f32),
+    scaling: ValueScaling,
+    fill_from: FillFrom,
+    // Reused between draws, so that reading the traces doesn't allocate
+    values: RefCell<Vec<f32>>,
+    draw_calls: Cell<usize>,
+    throttle: FrameThrottle,
+}
+
+struct Trace {
+    // Reads the normalized values of the trace's buffer
+    read: Box<dyn Fn(&mut DrawContext, (f32, f32), ValueScaling, &mut Vec<f32>)>,
+    stroke: Color,
+    fill: Color,
+    // Hidden traces are skipped; Traces without a style are always visible
+    visibility: Option<TraceVisibility>,
+}
+
+/// The name and colors of a trace, shared between a [`GraphGroup`] and a
+/// [`Legend`](super::Legend), so that the two can't drift apart.
+///
+/// A style without a stroke color is assigned one from the [palette](palette_color),
+/// based on its position - the n-th trace of a group and the n-th entry of a legend
+/// get the same color, so pass the styles to both in the same order.
+///
+/// Each style has a [`TraceVisibility`], which is shared by its clones. A legend can
+/// toggle it, hiding the trace inside the group.
+///
+/// # Example
+///
+/// ```
+/// let styles = vec![
+///     TraceStyle::new("Low"),
+///     TraceStyle::new("Mid"),
+///     TraceStyle::new("High").stroke(Color::rgb(255, 255, 255)),
+/// ];
+///
+/// GraphGroup::new(cx, (-32.0, 8.0), ValueScaling::Decibels)
+///     .styled_trace(Data::low_band, &styles[0])
+///     .styled_trace(Data::mid_band, &styles[1])
+///     .styled_trace(Data::high_band, &styles[2]);
+///
+/// Legend::new(cx, &styles, Orientation::Horizontal).toggles_visibility(true);
+/// ```
+#[derive(Clone, Debug)]
+pub struct TraceStyle {
+    name: String,
+    stroke: Option<Color>,
+    fill: Color,
+    visibility: TraceVisibility,
+}
+
+impl TraceStyle {
+    /// Creates a new, visible style with the given name, an automatic stroke color,
+    /// and no fill.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            stroke: None,
+            fill: Color::transparent(),
+            visibility: TraceVisibility::default(),
+        }
+    }
+
+    /// Sets the color of the trace's line, instead of picking one from the palette.
+    pub fn stroke(mut self, stroke: Color) -> Self {
+        self.stroke = Some(stroke);
+        self
+    }
+
+    /// Sets the color of the area below the trace's line.
+    pub fn fill(mut self, fill: Color) -> Self {
+        self.fill = fill;
+        self
+    }
+
+    /// Returns the name of the trace.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the color of the trace's line, given the trace's position.
+    pub fn stroke_color(&self, index: usize) -> Color {
+        self.stroke.unwrap_or_else(|| palette_color(index))
+    }
+
+    /// Returns the color of the area below the trace's line.
+    pub fn fill_color(&self) -> Color {
+        self.fill
+    }
+
+    /// Returns the handle that shows or hides the trace.
+    pub fn visibility(&self) -> &TraceVisibility {
+        &self.visibility
+    }
+}
+
+/// Whether a trace is shown, shared between a [`TraceStyle`] and its clones.
+///
+/// The handle is cheap to clone, and can be toggled from anywhere - e.g. by
+/// clicking a [`Legend`](super::Legend) entry.
+#[derive(Clone, Debug)]
+pub struct TraceVisibility(Arc<AtomicBool>);
+
+impl TraceVisibility {
+    /// Returns `true` if the trace is shown.
+    pub fn is_visible(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Shows or hides the trace.
+    pub fn set_visible(&self, visible: bool) {
+        self.0.store(visible, Ordering::Relaxed);
+    }
+
+    /// Shows the trace if it was hidden, and hides it otherwise. Returns whether the
+    /// trace is now shown.
+    pub fn toggle(&self) -> bool {
+        !self.0.fetch_xor(true, Ordering::Relaxed)
+    }
+}
+
+impl Default for TraceVisibility {
+    fn default() -> Self {
+        Self(Arc::new(AtomicBool::new(true)))
+    }
+}
+
+// A qualitative palette that stays distinguishable with color vision deficiencies
+// (Okabe & Ito), in the order the colors are assigned
+const PALETTE: [(u8, u8, u8); 8] = [
+    (230, 159, 0),
+    (86, 180, 233),
+    (0, 158, 115),
+    (240, 228, 66),
+    (0, 114, 178),
+    (213, 94, 0),
+    (204, 121, 167),
+    (153, 153, 153),
+];
+
+/// Returns the color that is automatically assigned to the trace at the index.
+///
+/// The colors come from a small qualitative palette, and repeat after 8 traces.
+pub fn palette_color(index: usize) -> Color {
+    let (r, g, b) = PALETTE[index % PALETTE.len()];
+    Color::rgb(r, g, b)
+}
+
+enum GraphGroupEvents {
+    UpdateRange((f32, f32)),
+    UpdateScaling(ValueScaling),
+}
+
+impl GraphGroup {
+    /// Creates a new, empty `GraphGroup`. Add traces to it using
+    /// [`trace()`](GraphGroupModifiers::trace).
+    pub fn new(
+        cx: &mut Context,
+        range: impl Res<(f32, f32)> + Clone,
+        scaling: impl Res<ValueScaling> + Clone,
+    ) -> Handle<Self> {
+        Self {
+            traces: Vec::new(),
+            range: range.get_val(cx),
+            scaling: scaling.get_val(cx),
+            fill_from: FillFrom::Bottom,
+            values: RefCell::new(Vec::new()),
+            draw_calls: Cell::new(0),
+            throttle: FrameThrottle::default(),
+        }
+        .build(cx, |_| {})
+        .range(range)
+        .scaling(scaling)
+    }
+
+    /// Returns the number of draw calls that were issued during the last draw.
+    ///
+    /// This is meant for debugging and profiling.
+    pub fn draw_calls(&self) -> usize {
+        self.draw_calls.get()
+    }
+}
+
+impl View for GraphGroup {
+    fn element(&self) -> Option<&'static str> {
+        Some(elements::GRAPH_GROUP)
+    }
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|e, _| match e {
+            GraphGroupEvents::UpdateRange(v) => self.range = *v,
+            GraphGroupEvents::UpdateScaling(s) => self.scaling = *s,
+        });
+    }
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        draw_throttled(cx, canvas, &self.throttle, |cx, canvas| {
+            // The same area a `Graph` maps its values onto, so that both line up
+            let bounds =
+                PlotArea::new(cx.bounds(), InnerPadding::default(), cx.scale_factor()).rect;
+            let line_width = cx.scale_factor();
+
+            let (fills, strokes) = self.batch(bounds, |trace, values| {
+                (trace.read)(cx, self.range, self.scaling, values)
+            });
+
+            for (color, path) in fills.iter() {
+                canvas.fill_path(path, &vg::Paint::color(color));
+            }
+            for (color, path) in strokes.iter() {
+                canvas.stroke_path(path, &vg::Paint::color(color).with_line_width(line_width));
+            }
+        });
+    }
+}
+
+impl GraphGroup {
+    // Collects the geometry of the visible traces into one fill and one stroke path
+    // per color, and counts the draw calls it takes to draw them
+    fn batch(
+        self: &Self,
+        bounds: BoundingBox,
+        mut read: impl FnMut(&Trace, &mut Vec<f32>),
+    ) -> (PathBatches, PathBatches) {
+        let fill_from_n = 1.0
+            - self
+                .fill_from
+                .normalized(&NormalizedRead::new(&(), self.range, self.scaling));
+
+        let mut fills = PathBatches::default();
+        let mut strokes = PathBatches::default();
+        let mut values = self.values.borrow_mut();
+
+        for trace in self.traces.iter() {
+            if let Some(visibility) = &trace.visibility {
+                if !visibility.is_visible() {
+                    continue;
+                }
+            }
+            values.clear();
+            read(trace, &mut values);
+
+            add_trace(
+                &values,
+                bounds,
+                fill_from_n,
+                fills.path(trace.fill.into()),
+                strokes.path(trace.stroke.into()),
+            );
+        }
+
+        self.draw_calls.set(fills.len() + strokes.len());
+        (fills, strokes)
+    }
+}
+
+/// One path per color.
+#[derive(Default)]
+pub(crate) struct PathBatches {
+    batches: Vec<(vg::Color, vg::Path)>,
+}
+
+impl PathBatches {
+    /// Returns the path for the given color, or `None` if the color is transparent.
+    pub(crate) fn path(&mut self, color: vg::Color) -> Option<&mut vg::Path> {
+        if color.a == 0.0 {
+            return None;
+        }
+
+        let index = match self.batches.iter().position(|(c, _)| *c == color) {
+            Some(index) => index,
+            None => {
+                self.batches.push((color, vg::Path::new()));
+                self.batches.len() - 1
+            }
+        };
+
+        Some(&mut self.batches[index].1)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (vg::Color, &vg::Path)> {
+        self.batches.iter().map(|(color, path)| (*color, path))
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.batches.len()
+    }
+}
+
+/// Adds the geometry of a trace to the fill and stroke paths, as separate subpaths.
+fn add_trace(
+    values: &[f32],
+    bounds: BoundingBox,
+    fill_from_n: f32,
+    fill: Option<&mut vg::Path>,
+    stroke: Option<&mut vg::Path>,
+) {
+    if values.is_empty() {
+        return;
+    }
+
+    let BoundingBox { x, y, w, h } = bounds;
+    let points = trace_points(values, bounds.into());
+
+    if let Some(stroke) = stroke {
+        for (i, (px, py)) in points.clone().enumerate() {
+            if i == 0 {
+                stroke.move_to(px, py);
+            } else {
+                stroke.line_to(px, py);
+            }
+        }
+    }
+
+    if let Some(fill) = fill {
+        for (i, (px, py)) in points.enumerate() {
+            if i == 0 {
+                fill.move_to(px, py);
+            } else {
+                fill.line_to(px, py);
+            }
+        }
+        fill.line_to(x + w, y + h * fill_from_n);
+        fill.line_to(x, y + h * fill_from_n);
+        fill.close();
+    }
+}
+
+pub trait GraphGroupModifiers {
+    /// Adds a trace that displays the data inside a [`VisualizerBuffer`].
+    ///
+    /// * `buffer` - The buffer to display, just like a [`Graph`](super::Graph)'s
+    /// * `stroke` - The color of the trace's line
+    /// * `fill` - The color of the area below the line; Use a transparent color to skip the fill
+    fn trace<L, I>(self, buffer: L, stroke: Color, fill: Color) -> Self
+    where
+        L: Lens,
+        L::Target: SharedRead<Value = I>,
+        I: VisualizerBuffer<f32, Output = f32> + 'static;
+
+    /// Adds a trace that displays the data inside a [`VisualizerBuffer`], using the
+    /// colors of a [`TraceStyle`].
+    ///
+    /// The trace is hidden while the style's [`TraceVisibility`] is.
+    fn styled_trace<L, I>(self, buffer: L, style: &TraceStyle) -> Self
+    where
+        L: Lens,
+        L::Target: SharedRead<Value = I>,
+        I: VisualizerBuffer<f32, Output = f32> + 'static;
+}
+
+impl<'a> GraphGroupModifiers for Handle<'a, GraphGroup> {
+    fn trace<L, I>(mut self, buffer: L, stroke: Color, fill: Color) -> Self
+    where
+        L: Lens,
+        L::Target: SharedRead<Value = I>,
+        I: VisualizerBuffer<f32, Output = f32> + 'static,
+    {
+        let e = self.entity();
+        let watched = buffer.clone();
+        self.context()
+            .with_current(e, |cx| redraw_on_write(cx, watched));
+        self.modify(|group| group.traces.push(Trace::new(buffer, stroke, fill, None)))
+    }
+
+    fn styled_trace<L, I>(mut self, buffer: L, style: &TraceStyle) -> Self
+    where
+        L: Lens,
+        L::Target: SharedRead<Value = I>,
+        I: VisualizerBuffer<f32, Output = f32> + 'static,
+    {
+        let e = self.entity();
+        let watched = buffer.clone();
+        self.context()
+            .with_current(e, |cx| redraw_on_write(cx, watched));
+        self.modify(|group| {
+            let stroke = style.stroke_color(group.traces.len());
+            let visibility = Some(style.visibility().clone());
+            group
+                .traces
+                .push(Trace::new(buffer, stroke, style.fill_color(), visibility))
+        })
+    }
+}
+
+impl Trace {
+    fn new<L, I>(buffer: L, stroke: Color, fill: Color, visibility: Option<TraceVisibility>) -> Self
+    where
+        L: Lens,
+        L::Target: SharedRead<Value = I>,
+        I: VisualizerBuffer<f32, Output = f32> + 'static,
+    {
+        Self {
+            read: Box::new(move |cx, range, scaling, out| {
+                let binding = buffer.get(cx);
+                let ring_buf = binding.read_shared(|buffer| buffer.refresh());
+                out.extend(NormalizedRead::new(&*ring_buf, range, scaling).normalized_iter());
+            }),
+            stroke,
+            fill,
+            visibility,
+        }
+    }
+}
+
+impl<'a> FillModifiers for Handle<'a, GraphGroup> {
+    /// Fills all traces from the top instead of the bottom.
+    fn fill_from_max(self) -> Self {
+        self.modify(|group| {
+            group.fill_from = FillFrom::Top;
+        })
+    }
+    /// Fills all traces from any desired level.
+    fn fill_from_value(self, level: f32) -> Self {
+        self.modify(|group| {
+            group.fill_from = FillFrom::Value(level);
+        })
+    }
+}
+
+impl<'a> RangeModifiers for Handle<'a, GraphGroup> {
+    fn range(mut self, range: impl Res<(f32, f32)>) -> Self {
+        let e = self.entity();
+
+        range.set_or_bind(self.context(), e, move |cx, r| {
+            (*cx).emit_to(e, GraphGroupEvents::UpdateRange(r));
+        });
+
+        self
+    }
+    fn scaling(mut self, scaling: impl Res<ValueScaling>) -> Self {
+        let e = self.entity();
+
+        scaling.set_or_bind(self.context(), e, move |cx, s| {
+            (*cx).emit_to(e, GraphGroupEvents::UpdateScaling(s))
+        });
+
+        self
+    }
+}
+
+impl<'a> ThrottleModifiers for Handle<'a, GraphGroup> {
+    fn max_fps(self, max_fps: f32) -> Self {
+        self.modify(|view| view.throttle.set_max_fps(max_fps))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        palette_color, FillFrom, FrameThrottle, GraphGroup, Trace, TraceStyle, TraceVisibility,
+        PALETTE,
+    };
+    use crate::utils::ValueScaling;
+    use nih_plug_vizia::vizia::prelude::{BoundingBox, Color};
+    use std::cell::{Cell, RefCell};
+
+    const BOUNDS: BoundingBox = BoundingBox {
+        x: 10.,
+        y: 20.,
+        w: 100.,
+        h: 100.,
+    };
+
+    // Draws a group of traces with the given stroke and fill colors the way its
+    // view does, and returns the number of draw calls it reported
+    fn draw_calls(traces: &[(Color, Color, Option<TraceVisibility>)]) -> usize {
+        let group = GraphGroup {
+            traces: traces
+                .iter()
+                .map(|(stroke, fill, visibility)| Trace {
+                    read: Box::new(|_, _, _, _| {}),
+                    stroke: *stroke,
+                    fill: *fill,
+                    visibility: visibility.clone(),
+                })
+                .collect(),
+            range: (0., 1.),
+            scaling: ValueScaling::Linear,
+            fill_from: FillFrom::Bottom,
+            values: RefCell::new(Vec::new()),
+            draw_calls: Cell::new(0),
+            throttle: FrameThrottle::default(),
+        };
+        group.batch(BOUNDS, |_, values| {
+            values.extend_from_slice(&[0.0, 0.5, 1.0, 0.5])
+        });
+        group.draw_calls()
+    }
+
+    #[test]
+    fn batching() {
+        let fill = Color::rgba(255, 255, 255, 60);
+        let strokes = (0..6)
+            .map(|i| Color::rgb(40 * i, 255, 255))
+            .collect::<Vec<_>>();
+
+        // Six separate graphs would take 12 draw calls; A shared fill takes 7
+        let traces = strokes.iter().map(|s| (*s, fill, None)).collect::<Vec<_>>();
+        assert_eq!(draw_calls(&traces), 7);
+
+        // With matching strokes, it's only 2
+        assert_eq!(draw_calls(&vec![(strokes[0], fill, None); 6]), 2);
+
+        // Transparent fills are skipped entirely
+        let transparent = Color::rgba(0, 0, 0, 0);
+        assert_eq!(draw_calls(&vec![(strokes[0], transparent, None); 6]), 1);
+
+        // So are hidden traces
+        let hidden = TraceVisibility::default();
+        hidden.set_visible(false);
+        let mut traces = vec![(strokes[0], fill, None), (strokes[1], fill, None)];
+        traces.push((strokes[2], fill, Some(hidden)));
+        assert_eq!(draw_calls(&traces), 3);
+    }
+
+    #[test]
+    fn automatic_colors() {
+        // Distinct within the palette, and repeating after it
+        for (i, color) in PALETTE.iter().enumerate() {
+            assert!(PALETTE[i + 1..].iter().all(|other| other != color));
+        }
+        assert_eq!(palette_color(9), palette_color(1));
+
+        // Explicit colors take precedence
+        let white = Color::rgb(255, 255, 255);
+        assert_eq!(TraceStyle::new("Low").stroke_color(3), palette_color(3));
+        assert_eq!(TraceStyle::new("Low").stroke(white).stroke_color(3), white);
+    }
+
+    #[test]
+    fn shared_visibility() {
+        let style = TraceStyle::new("Low");
+        let legend_copy = style.clone();
+        assert!(style.visibility().is_visible());
+
+        assert!(!legend_copy.visibility().toggle());
+        assert!(!style.visibility().is_visible());
+        assert!(style.visibility().toggle());
+        assert!(legend_copy.visibility().is_visible());
+    }
+}
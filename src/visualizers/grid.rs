@@ -1,8 +1,21 @@
 use nih_plug_vizia::vizia::{prelude::*, vg};
 
-use crate::utils::ValueScaling;
+use crate::utils::{
+    AutoRange, NormalizedRead, RangeSegments, SharedRange, SharedRead, ValueScaling,
+    VisualizerBuffer,
+};
+use std::sync::{Arc, Mutex};
 
-use super::RangeModifiers;
+use super::axes::Axes;
+use super::elements;
+use super::padding::InnerPadding;
+use super::plot_area::PlotArea;
+use super::quality::draw_measured;
+use super::{
+    draw_clipped, marks, redraw_on_write, AdaptiveQualityModifiers, AutoRangeModifiers,
+    AxisModifiers, Degradation, FrameStats, GraphDirection, PaddingModifiers, RangeModifiers,
+    RangeSource,
+};
 
 /// Generic grid backdrop that displays either horizontal or vertical lines.
 ///
@@ -32,11 +45,49 @@ use super::RangeModifiers;
 ///
 /// Note that both the `Graph` and `Grid` have the same range, which is necessary
 /// for them to scale correctly.
+///
+/// A vertical grid can also mark time instead, scrolling along with a buffer using
+/// [`scroll_with()`](GridModifiers::scroll_with).
+///
+/// # Styling
+///
+/// Stylesheets select it as `cyma-grid`. Its `color` is the lines; Grids that draw
+/// [minor](GridModifiers::minor) lines have the `minor` class.
 pub struct Grid {
     scaling: ValueScaling,
     range: (f32, f32),
     lines: Vec<f32>,
     orientation: Orientation,
+    followed_range: Option<RangeSource>,
+    // Replaces the range with a broken axis, if set
+    segments: Option<RangeSegments>,
+    scroll: Option<TimeScroll>,
+    value_axis: Orientation,
+    mirror_x: bool,
+    direction: GraphDirection,
+    padding: InnerPadding,
+    // Whether the grid draws minor lines, which may be skipped to save time
+    minor: bool,
+    quality: Option<FrameStats>,
+}
+
+/// Vertical lines that are attached to moments in time, and scroll along with a
+/// buffer.
+struct TimeScroll {
+    // Reads how far the buffer has scrolled
+    read: Box<dyn Fn(&mut DrawContext) -> ScrollPosition>,
+    // The time between two lines, in seconds
+    interval: f32,
+}
+
+/// How far a buffer has scrolled, read from it in one go.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ScrollPosition {
+    // The number of elements that were ever enqueued, plus the progress of the
+    // element that is being accumulated
+    elements: f64,
+    len: usize,
+    duration: f32,
 }
 
 enum GridEvents {
@@ -57,75 +108,163 @@ impl Grid {
             range: range.get_val(cx),
             lines: lines.get_val(cx),
             orientation,
+            followed_range: None,
+            segments: None,
+            scroll: None,
+            value_axis: Orientation::Vertical,
+            mirror_x: false,
+            direction: GraphDirection::BottomUp,
+            padding: InnerPadding::default(),
+            minor: false,
+            quality: None,
         }
         .build(cx, |_| {})
         .range(range)
         .scaling(scaling)
     }
+
+    /// Returns the lines of the form `anchor + k * step` that lie inside the range.
+    ///
+    /// The anchor doesn't need to lie inside the range, so e.g. a line every 10 dB
+    /// from 0 dB inside a range of -96 to +24 dB is:
+    ///
+    /// ```
+    /// Grid::new(
+    ///     cx,
+    ///     ValueScaling::Linear,
+    ///     (-96., 24.),
+    ///     Grid::marks_from_anchor(0., 10., (-96., 24.)),
+    ///     Orientation::Horizontal,
+    /// );
+    /// ```
+    ///
+    /// A [`UnitRuler`](super::UnitRuler::marks_from_anchor) generates the same marks.
+    pub fn marks_from_anchor(anchor: f32, step: f32, range: (f32, f32)) -> Vec<f32> {
+        marks::marks_from_anchor(anchor, step, range)
+    }
+
+    /// Returns the given lines as they are; Lines outside the range are not drawn.
+    pub fn marks_explicit(lines: Vec<f32>) -> Vec<f32> {
+        marks::marks_explicit(lines)
+    }
 }
 
 impl View for Grid {
     fn element(&self) -> Option<&'static str> {
-        Some("grid")
+        Some(elements::GRID)
     }
     fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
-        let bounds = cx.bounds();
-
-        let x = bounds.x;
-        let y = bounds.y;
-        let w = bounds.w;
-        let h = bounds.h;
+        draw_measured(self.quality.as_ref(), || self.draw_lines(cx, canvas));
+    }
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|e, _| match e {
+            GridEvents::UpdateRange(v) => self.range = *v,
+            GridEvents::UpdateScaling(v) => self.scaling = *v,
+        });
+    }
+}
 
-        let line_width = cx.scale_factor();
+impl Grid {
+    fn draw_lines(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let skipped = self.minor
+            && self
+                .quality
+                .as_ref()
+                .is_some_and(|stats| stats.is_degraded(Degradation::SkipMinorGrid));
+        if skipped {
+            return;
+        }
 
-        canvas.stroke_path(
-            &{
-                let mut path = vg::Path::new();
+        draw_clipped(cx, canvas, |cx, canvas| {
+            let line_width = cx.scale_factor();
+            let area = PlotArea::new(cx.bounds(), self.padding, cx.scale_factor());
+            let axes = area
+                .axes(self.value_axis, self.mirror_x)
+                .with_direction(self.direction);
 
-                match self.orientation {
-                    Orientation::Horizontal => {
-                        for y_line in self.lines.iter() {
-                            let y_line = self.scaling.value_to_normalized(
-                                *y_line,
-                                self.range.0,
-                                self.range.1,
-                            );
+            let range = match &self.followed_range {
+                Some(source) => source.get(),
+                None => self.range,
+            };
+            // Lines inside the breaks of a broken axis aren't drawn
+            let visible = |line: &&f32| {
+                self.segments
+                    .as_ref()
+                    .is_none_or(|segments| segments.contains(self.scaling, **line))
+            };
+            let values =
+                NormalizedRead::new(&(), range, self.scaling).with_segments(self.segments.as_ref());
+            let normalized = |line: &f32| values.normalize_value(*line);
 
-                            path.move_to(x, y + h * (1. - y_line));
-                            path.line_to(x + w, y + h * (1. - y_line));
+            let mut path = vg::Path::new();
+            let mut line = |(from, to): ((f32, f32), (f32, f32))| {
+                path.move_to(from.0, from.1);
+                path.line_to(to.0, to.1);
 
-                            path.close();
-                        }
+                path.close();
+            };
+            match (self.orientation, &self.scroll) {
+                (Orientation::Vertical, Some(scroll)) => {
+                    let position = (scroll.read)(cx);
+                    for x_line in scrolling_lines(position, scroll.interval) {
+                        line(line_points(&area, &axes, self.orientation, x_line));
                     }
-                    Orientation::Vertical => {
-                        for x_line in self.lines.iter() {
-                            let x_line = self.scaling.value_to_normalized(
-                                *x_line,
-                                self.range.0,
-                                self.range.1,
-                            );
-
-                            path.move_to(x + w * x_line, y);
-                            path.line_to(x + w * x_line, y + h);
-
-                            path.close();
-                        }
+                }
+                _ => {
+                    for position in self.lines.iter().filter(visible).map(normalized) {
+                        line(line_points(&area, &axes, self.orientation, position));
                     }
-                };
+                }
+            };
 
-                path
-            },
-            &vg::Paint::color(cx.font_color().into()).with_line_width(line_width),
-        );
-    }
-    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
-        event.map(|e, _| match e {
-            GridEvents::UpdateRange(v) => self.range = *v,
-            GridEvents::UpdateScaling(v) => self.scaling = *v,
+            canvas.stroke_path(
+                &path,
+                &vg::Paint::color(cx.font_color().into()).with_line_width(line_width),
+            );
         });
     }
 }
 
+/// Returns the endpoints of a line at a normalized position, snapped to the
+/// [`PlotArea`] so that it lines up with the labels of a
+/// [`UnitRuler`](super::UnitRuler).
+///
+/// Horizontal lines are drawn at a value, and vertical lines at a position in time.
+pub(crate) fn line_points(
+    area: &PlotArea,
+    axes: &Axes,
+    orientation: Orientation,
+    position: f32,
+) -> ((f32, f32), (f32, f32)) {
+    let (from, to) = match orientation {
+        Orientation::Horizontal => (axes.point(0., position), axes.point(1., position)),
+        Orientation::Vertical => (axes.point(position, 0.), axes.point(position, 1.)),
+    };
+    (area.snap_point(from), area.snap_point(to))
+}
+
+/// Returns the normalized x positions of the lines that are `interval` seconds
+/// apart, from the newest to the oldest.
+///
+/// The lines are attached to the elements of the buffer, in the same place a
+/// [`Graph`](super::Graph) draws them while it scrolls smoothly. As the buffer
+/// scrolls, they glide to the left, and new lines enter from the right.
+fn scrolling_lines(position: ScrollPosition, interval: f32) -> impl Iterator<Item = f32> {
+    let len = position.len as f64;
+    // The number of elements between two lines
+    let step = interval as f64 * len / position.duration as f64;
+    let valid = step.is_finite() && step > 0. && len > 0.;
+    // How many elements ago the newest line was
+    let first = match valid {
+        true => position.elements.rem_euclid(step),
+        false => f64::INFINITY,
+    };
+    (0..)
+        .map(move |k| first + k as f64 * step)
+        .take_while(move |age| *age <= len)
+        .map(move |age| (1. - age / len) as f32)
+}
+
 impl<'a> RangeModifiers for Handle<'a, Grid> {
     fn range(mut self, range: impl Res<(f32, f32)>) -> Self {
         let e = self.entity();
@@ -146,3 +285,208 @@ impl<'a> RangeModifiers for Handle<'a, Grid> {
         self
     }
 }
+
+impl<'a> PaddingModifiers for Handle<'a, Grid> {
+    fn inner_padding(self, top: f32, right: f32, bottom: f32, left: f32) -> Self {
+        self.modify(|grid| grid.padding = InnerPadding::new(top, right, bottom, left))
+    }
+    fn uniform_inner_padding(self, padding: f32) -> Self {
+        self.modify(|grid| grid.padding = InnerPadding::uniform(padding))
+    }
+}
+
+impl<'a> AxisModifiers for Handle<'a, Grid> {
+    /// Sets the direction the value axis runs in; See [`AxisModifiers`].
+    ///
+    /// The grid's orientation is the direction of its lines while the value axis is
+    /// vertical - so a horizontal grid marks values, and a vertical one marks time
+    /// or the values of a horizontal axis. With a horizontal value axis, the lines
+    /// are rotated along with it.
+    fn value_axis(self, orientation: Orientation) -> Self {
+        self.modify(|grid| grid.value_axis = orientation)
+    }
+    fn mirror_x(self, mirror: bool) -> Self {
+        self.modify(|grid| grid.mirror_x = mirror)
+    }
+    fn direction(self, direction: GraphDirection) -> Self {
+        self.modify(|grid| grid.direction = direction)
+    }
+}
+
+impl<'a> AutoRangeModifiers for Handle<'a, Grid> {
+    fn follow_range(self, auto_range: Arc<Mutex<AutoRange>>) -> Self {
+        self.modify(|grid| grid.followed_range = Some(RangeSource::Auto(auto_range)))
+    }
+    fn follow_shared_range(self, range: SharedRange) -> Self {
+        self.modify(|grid| grid.followed_range = Some(RangeSource::Shared(range)))
+    }
+}
+
+pub trait GridModifiers {
+    /// Turns the vertical lines into time marks that are `interval` seconds apart, and
+    /// scroll along with the buffer.
+    ///
+    /// Static lines on a scrolling [`Graph`](super::Graph) look like the data is
+    /// sliding underneath them. Instead, each line stays attached to a moment in
+    /// time - e.g. "5 seconds ago" - gliding to the left as new data comes in, with
+    /// new lines entering from the right. Pass the same buffer as the graph's.
+    ///
+    /// The buffer is only read to find out how far it has scrolled, and can be
+    /// shared in any way that implements [`SharedRead`]. A buffer that isn't
+    /// time-based, or doesn't count the elements it enqueued, draws no lines. This
+    /// has no effect on a horizontal grid, and replaces the lines it was created with.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// ZStack::new(cx, |cx| {
+    ///     Grid::new(cx, ValueScaling::Linear, (0., 1.), vec![], Orientation::Vertical)
+    ///         .scroll_with(Data::peak_buffer, 1.0)
+    ///         .color(Color::rgb(40, 40, 40));
+    ///
+    ///     Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+    ///         .color(Color::rgba(255, 255, 255, 160));
+    /// });
+    /// ```
+    fn scroll_with<L, B>(self, buffer: L, interval: f32) -> Self
+    where
+        L: Lens,
+        L::Target: SharedRead<Value = B>,
+        B: VisualizerBuffer<f32> + 'static,
+        B::Output: Sized;
+
+    /// Marks the grid as drawing minor lines.
+    ///
+    /// This adds the `minor` class, which the [themes](crate::theme) draw in a
+    /// subtler color, and lets an [`AdaptiveQuality`](super::AdaptiveQuality)
+    /// controller skip the grid when drawing takes too long.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Grid::new(cx, ValueScaling::Linear, (-32., 8.), vec![-6.0, -18.0, -30.0], Orientation::Horizontal)
+    ///     .minor()
+    ///     .adaptive_quality(&stats);
+    /// ```
+    fn minor(self) -> Self;
+
+    /// Breaks the value axis into several ranges, in the same way as a
+    /// [`Graph`](super::Graph)'s [`range_segments()`](super::GraphModifiers::range_segments).
+    ///
+    /// This replaces the range. Lines inside the breaks aren't drawn.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Grid::new(cx, ValueScaling::Linear, (-80., 0.), vec![-70.0, -6.0], Orientation::Horizontal)
+    ///     .range_segments(&[(-80., -60.), (-12., 0.)]);
+    /// ```
+    fn range_segments(self, segments: &[(f32, f32)]) -> Self;
+}
+
+impl<'a> GridModifiers for Handle<'a, Grid> {
+    fn scroll_with<L, B>(mut self, buffer: L, interval: f32) -> Self
+    where
+        L: Lens,
+        L::Target: SharedRead<Value = B>,
+        B: VisualizerBuffer<f32> + 'static,
+        B::Output: Sized,
+    {
+        // The lines move whenever the buffer scrolls
+        let e = self.entity();
+        let watched = buffer.clone();
+        self.context()
+            .with_current(e, |cx| redraw_on_write(cx, watched));
+        let read = move |cx: &mut DrawContext| {
+            let binding = buffer.get(cx);
+            let buffer = binding.read_shared(|_| {});
+            let enqueued = buffer.enqueued();
+            let progress = buffer.partial().map_or(0., |(progress, _)| progress);
+            ScrollPosition {
+                elements: enqueued.map_or(f64::NAN, |e| e as f64 + progress as f64),
+                len: buffer.len(),
+                duration: buffer.duration().unwrap_or(f32::NAN),
+            }
+        };
+        self.modify(|grid| {
+            grid.scroll = Some(TimeScroll {
+                read: Box::new(read),
+                interval,
+            })
+        })
+    }
+
+    fn minor(self) -> Self {
+        self.modify(|grid| grid.minor = true)
+            .class(elements::MINOR_CLASS)
+    }
+
+    fn range_segments(self, segments: &[(f32, f32)]) -> Self {
+        let segments = RangeSegments::new(segments);
+        self.modify(|grid| grid.segments = Some(segments))
+    }
+}
+
+impl<'a> AdaptiveQualityModifiers for Handle<'a, Grid> {
+    fn adaptive_quality(self, stats: &FrameStats) -> Self {
+        let stats = stats.clone();
+        self.modify(|grid| grid.quality = Some(stats))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scrolling_lines, ScrollPosition};
+
+    fn lines(elements: f64, interval: f32) -> Vec<f32> {
+        let position = ScrollPosition {
+            elements,
+            len: 100,
+            duration: 10.,
+        };
+        scrolling_lines(position, interval).collect()
+    }
+
+    fn assert_near(actual: Vec<f32>, expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (a - e).abs() < 1e-5,
+                "expected {:?}, got {:?}",
+                expected,
+                actual
+            );
+        }
+    }
+
+    #[test]
+    fn glides_and_wraps() {
+        // A line every 2.5 seconds, so every 25 elements
+        assert_near(lines(1000., 2.5), &[1., 0.75, 0.5, 0.25, 0.]);
+        // Half an element later, every line moved half an element to the left
+        assert_near(lines(1000.5, 2.5), &[0.995, 0.745, 0.495, 0.245]);
+        // Once a line has left on the left, the next one enters on the right
+        assert_near(lines(1024., 2.5), &[0.76, 0.51, 0.26, 0.01]);
+        assert_near(lines(1025., 2.5), &[1., 0.75, 0.5, 0.25, 0.]);
+    }
+
+    #[test]
+    fn stays_attached_far_in() {
+        // After hours of audio, the lines are still exactly where they belong
+        let elements = 36_000_000.;
+        assert_near(lines(elements + 12.5, 2.5), &[0.875, 0.625, 0.375, 0.125]);
+    }
+
+    #[test]
+    fn invalid_positions_draw_nothing() {
+        assert!(lines(f64::NAN, 1.).is_empty());
+        assert!(lines(10., 0.).is_empty());
+        assert!(lines(10., -1.).is_empty());
+        let empty = ScrollPosition {
+            elements: 10.,
+            len: 0,
+            duration: 10.,
+        };
+        assert_eq!(scrolling_lines(empty, 1.).count(), 0);
+    }
+}
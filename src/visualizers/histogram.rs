@@ -1,8 +1,17 @@
-use super::{FillFrom, FillModifiers, RangeModifiers};
-use crate::utils::{HistogramBuffer, ValueScaling, VisualizerBuffer};
+use super::elements;
+use super::padding::InnerPadding;
+use super::plot_area::PlotArea;
+use super::{
+    draw_throttled, redraw_on_write, FillFrom, FillModifiers, FrameThrottle, PaddingModifiers,
+    RangeModifiers, ThrottleModifiers,
+};
+use crate::geometry::histogram_outline;
+use crate::utils::{
+    HistogramBuffer, SharedRange, SharedRead, SharedWrite, ValueScaling, VisualizerBuffer,
+};
 
 use nih_plug_vizia::vizia::{prelude::*, vg};
-use std::sync::{Arc, Mutex};
+use std::cell::RefCell;
 
 /// Real-time histogram displaying information that is stored inside a [`HistogramBuffer`]
 ///
@@ -19,14 +28,47 @@ use std::sync::{Arc, Mutex};
 ///
 /// The histogram displays the range from -32.0dB to 8dB.
 /// it decays as 0.1 TODO, and a stroke and fill (background) color is provided.
+///
+/// The range can be zoomed into using the mouse; See
+/// [`zoomable()`](HistogramModifiers::zoomable). Zooming re-bins the buffer, so the
+/// histogram writes to it as well as reading it; See [`SharedWrite`].
+///
+/// # Styling
+///
+/// Stylesheets select it as `cyma-histogram`. Its `color` is the outline, and its
+/// `background-color` the fill.
 pub struct Histogram<L>
 where
-    L: Lens<Target = Arc<Mutex<HistogramBuffer>>>,
+    L: Lens,
+    L::Target: SharedWrite<Value = HistogramBuffer>,
 {
     buffer: L,
     range: (f32, f32),
+    throttle: FrameThrottle,
+    // The buffer's bins, re-binned to one bin per physical pixel; Reused from one
+    // frame to the next, so that drawing doesn't allocate it
+    bins: RefCell<Vec<f32>>,
+    padding: InnerPadding,
+    zoom: Option<Zoom>,
+    shared_range: Option<SharedRange>,
+    on_range_change: Option<Box<dyn Fn(&mut EventContext, (f32, f32))>>,
+}
+
+/// The zoom interaction of a histogram; See [`HistogramModifiers::zoomable()`].
+struct Zoom {
+    limits: (f32, f32),
+    // The vertical position of the cursor, in physical pixels
+    cursor_y: f32,
+    // Where a drag started, and the range at that point
+    drag: Option<(f32, (f32, f32))>,
 }
 
+/// How much one line of scrolling zooms in.
+const ZOOM_PER_LINE: f32 = 0.85;
+
+/// The smallest range that can be zoomed into, in dB.
+const MIN_ZOOM_SPAN: f32 = 1.0;
+
 enum HistogramEvents {
     UpdateRange((f32, f32)),
     // UpdateDecay(f32),
@@ -34,88 +76,268 @@ enum HistogramEvents {
 
 impl<L> Histogram<L>
 where
-    L: Lens<Target = Arc<Mutex<HistogramBuffer>>>,
+    L: Lens,
+    L::Target: SharedWrite<Value = HistogramBuffer>,
 {
     pub fn new(cx: &mut Context, buffer: L, range: impl Res<(f32, f32)> + Clone) -> Handle<Self> {
         Self {
-            buffer,
+            buffer: buffer.clone(),
             range: range.get_val(cx),
+            throttle: FrameThrottle::default(),
+            bins: RefCell::new(Vec::new()),
+            padding: InnerPadding::default(),
+            zoom: None,
+            shared_range: None,
+            on_range_change: None,
         }
-        .build(cx, |_| {})
+        .build(cx, |cx| redraw_on_write(cx, buffer))
         .range(range)
     }
+
+    /// Re-bins the buffer into a range that was zoomed into using the mouse, and
+    /// reports it.
+    fn zoom_to(self: &mut Self, cx: &mut EventContext, range: (f32, f32)) {
+        if range == self.range {
+            return;
+        }
+        self.range = range;
+        self.buffer
+            .get(cx)
+            .write_shared(|buffer| buffer.zoom_range(range));
+        if let Some(shared_range) = &self.shared_range {
+            shared_range.set(range);
+        }
+        if let Some(callback) = &self.on_range_change {
+            callback(cx, range);
+        }
+        cx.needs_redraw();
+    }
 }
 
 impl<L> View for Histogram<L>
 where
-    L: Lens<Target = Arc<Mutex<HistogramBuffer>>>,
+    L: Lens,
+    L::Target: SharedWrite<Value = HistogramBuffer>,
 {
     fn element(&self) -> Option<&'static str> {
-        Some("histogram")
+        Some(elements::HISTOGRAM)
     }
     fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
         event.map(|e, _| match e {
             HistogramEvents::UpdateRange(v) => {
                 self.range = *v;
-                self.buffer.get(cx).lock().unwrap().set_range(*v)
+                self.buffer
+                    .get(cx)
+                    .write_shared(|buffer| buffer.zoom_range(*v));
+                if let Some(shared_range) = &self.shared_range {
+                    shared_range.set(*v);
+                }
             } // HistogramEvents::UpdateDecay(s) => self.decay = *s,
         });
+
+        let Some(zoom) = &mut self.zoom else {
+            return;
+        };
+        let area = PlotArea::new(cx.bounds(), self.padding, cx.scale_factor()).rect;
+        let limits = zoom.limits;
+        let mut zoomed = None;
+        event.map(|window_event, meta| match window_event {
+            WindowEvent::MouseScroll(_, lines) => {
+                // The fraction of the range that stays under the cursor
+                let anchor = 1. - (zoom.cursor_y - area.y) / area.h.max(1.);
+                let factor = ZOOM_PER_LINE.powf(*lines);
+                zoomed = Some(zoomed_range(self.range, anchor, factor, limits));
+                meta.consume();
+            }
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                zoom.drag = Some((zoom.cursor_y, self.range));
+                cx.capture();
+                meta.consume();
+            }
+            WindowEvent::MouseMove(_, cursor_y) => {
+                zoom.cursor_y = *cursor_y;
+                if let Some((start_y, range)) = zoom.drag {
+                    let shift = (cursor_y - start_y) / area.h.max(1.);
+                    zoomed = Some(panned_range(range, shift, limits));
+                }
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if zoom.drag.take().is_some() {
+                    cx.release();
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseDoubleClick(MouseButton::Left) => {
+                zoomed = Some(limits);
+                meta.consume();
+            }
+            _ => {}
+        });
+        if let Some(range) = zoomed {
+            self.zoom_to(cx, range);
+        }
     }
     fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
-        let bounds = cx.bounds();
+        draw_throttled(cx, canvas, &self.throttle, |cx, canvas| {
+            let line_width = cx.scale_factor();
+            let bounds = PlotArea::new(cx.bounds(), self.padding, cx.scale_factor()).rect;
 
-        let line_width = cx.scale_factor();
+            let h = bounds.h;
 
-        let x = bounds.x;
-        let y = bounds.y;
-        let w = bounds.w;
-        let h = bounds.h;
+            let binding = self.buffer.get(cx);
+            let buffer = binding.read_shared(|buffer| buffer.refresh());
+            let nr_bins = buffer.len();
+            if nr_bins < 2 {
+                return;
+            }
+            let above_range = buffer.get(nr_bins - 1).copied().unwrap_or(0.);
 
-        let mut stroke = vg::Path::new();
-        let binding = self.buffer.get(cx);
-        let bins = &(binding.lock().unwrap());
-        let nr_bins = bins.len();
+            // The bins inside the range, re-binned to the view's height, so that the
+            // display doesn't depend on the number of bins
+            let mut bins = self.bins.borrow_mut();
+            buffer.rebinned((h.round() as usize).max(1), &mut bins);
+            drop(buffer);
 
-        let mut largest = 0.0;
-        // don't scale to bins[0]
-        for i in 1..nr_bins {
-            if bins[i] > largest {
-                largest = bins[i];
+            let mut stroke = vg::Path::new();
+            let mut outline = histogram_outline(bounds.into(), above_range, &bins);
+            if let Some((start_x, start_y)) = outline.next() {
+                stroke.move_to(start_x, start_y);
             }
-        }
-
-        // start of the graph
-        stroke.move_to(x + bins[nr_bins - 1] * w, y);
-
-        // the actual histogram
-        if largest > 0.0 {
-            for i in 1..nr_bins {
-                stroke.line_to(
-                    x + (
-                        // scale so the largest value becomes 1.
-                        (bins[nr_bins - i] / largest) * w
-                    ),
-                    y + h * i as f32 / (nr_bins - 1) as f32,
-                );
+            for (px, py) in outline {
+                stroke.line_to(px, py);
             }
-        }
-        // fill in with background color
-        let mut fill = stroke.clone();
-        fill.line_to(x, y + h);
-        fill.line_to(x, y);
-        fill.close();
-        canvas.fill_path(&fill, &vg::Paint::color(cx.background_color().into()));
-
-        canvas.stroke_path(
-            &stroke,
-            &vg::Paint::color(cx.font_color().into()).with_line_width(line_width),
-        );
+            // fill in with background color
+            let mut fill = stroke.clone();
+            fill.line_to(bounds.x, bounds.y + h);
+            fill.line_to(bounds.x, bounds.y);
+            fill.close();
+            canvas.fill_path(&fill, &vg::Paint::color(cx.background_color().into()));
+
+            canvas.stroke_path(
+                &stroke,
+                &vg::Paint::color(cx.font_color().into()).with_line_width(line_width),
+            );
+        });
+    }
+}
+
+/// Zooms a range in or out by `factor`, keeping the level at `anchor` - a fraction
+/// of the range, from its bottom - in place.
+///
+/// The range spans at least [`MIN_ZOOM_SPAN`], and lies within `limits`.
+fn zoomed_range(range: (f32, f32), anchor: f32, factor: f32, limits: (f32, f32)) -> (f32, f32) {
+    let anchor = anchor.clamp(0., 1.);
+    let span = range.1 - range.0;
+    let level = range.0 + anchor * span;
+    let zoomed_span =
+        (span * factor).clamp(MIN_ZOOM_SPAN.min(limits.1 - limits.0), limits.1 - limits.0);
+    let min = level - anchor * zoomed_span;
+    within_limits((min, min + zoomed_span), limits)
+}
+
+/// Moves a range by `shift`, a fraction of its span - upwards for a positive shift,
+/// as when the histogram is dragged downwards - without leaving `limits`.
+fn panned_range(range: (f32, f32), shift: f32, limits: (f32, f32)) -> (f32, f32) {
+    let offset = shift * (range.1 - range.0);
+    within_limits((range.0 + offset, range.1 + offset), limits)
+}
+
+/// Moves a range that's at most as large as `limits` back inside them.
+fn within_limits(range: (f32, f32), limits: (f32, f32)) -> (f32, f32) {
+    if range.0 < limits.0 {
+        (limits.0, limits.0 + (range.1 - range.0))
+    } else if range.1 > limits.1 {
+        (limits.1 - (range.1 - range.0), limits.1)
+    } else {
+        range
+    }
+}
+
+pub trait HistogramModifiers {
+    /// Lets the range be zoomed into using the mouse: Scrolling zooms in and out
+    /// around the cursor, dragging moves the range up and down, and double-clicking
+    /// zooms out to `limits`, which the range never leaves.
+    ///
+    /// The buffer is re-binned using [`HistogramBuffer::zoom_range()`], so the
+    /// histogram stays in place. To keep a
+    /// [`UnitRuler`](super::UnitRuler) or a [`Grid`](super::Grid) in step with it,
+    /// pass them the same [`shared_range()`](Self::shared_range), or store the range
+    /// that's passed to [`on_range_change()`](Self::on_range_change) in a model that
+    /// they're bound to.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let range = SharedRange::new((-32.0, 8.0));
+    ///
+    /// Histogram::new(cx, Data::histogram_buffer, (-32.0, 8.0))
+    ///     .zoomable((-96.0, 24.0))
+    ///     .shared_range(range.clone());
+    ///
+    /// UnitRuler::follow_shared(
+    ///     cx,
+    ///     range,
+    ///     ValueScaling::Linear,
+    ///     UnitRuler::marks_from_anchor(0.0, 6.0, (-96.0, 24.0), |db| format!("{db}db")),
+    ///     Orientation::Vertical,
+    /// );
+    /// ```
+    fn zoomable(self, limits: (f32, f32)) -> Self;
+    /// Writes the range to a [`SharedRange`] whenever it's zoomed into, or changed
+    /// using [`range()`](RangeModifiers::range).
+    fn shared_range(self, range: SharedRange) -> Self;
+    /// Calls `callback` with the new range whenever it's zoomed into using the mouse;
+    /// See [`zoomable()`](Self::zoomable).
+    fn on_range_change<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut EventContext, (f32, f32));
+}
+
+impl<'a, L> HistogramModifiers for Handle<'a, Histogram<L>>
+where
+    L: Lens,
+    L::Target: SharedWrite<Value = HistogramBuffer>,
+{
+    fn zoomable(self, limits: (f32, f32)) -> Self {
+        self.modify(|histogram| {
+            histogram.zoom = Some(Zoom {
+                limits,
+                cursor_y: 0.,
+                drag: None,
+            })
+        })
+    }
+    fn shared_range(self, range: SharedRange) -> Self {
+        self.modify(|histogram| {
+            range.set(histogram.range);
+            histogram.shared_range = Some(range);
+        })
+    }
+    fn on_range_change<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut EventContext, (f32, f32)),
+    {
+        self.modify(|histogram| histogram.on_range_change = Some(Box::new(callback)))
+    }
+}
+
+impl<'a, L> PaddingModifiers for Handle<'a, Histogram<L>>
+where
+    L: Lens,
+    L::Target: SharedWrite<Value = HistogramBuffer>,
+{
+    fn inner_padding(self, top: f32, right: f32, bottom: f32, left: f32) -> Self {
+        self.modify(|histogram| histogram.padding = InnerPadding::new(top, right, bottom, left))
+    }
+    fn uniform_inner_padding(self, padding: f32) -> Self {
+        self.modify(|histogram| histogram.padding = InnerPadding::uniform(padding))
     }
 }
 
 impl<'a, L> FillModifiers for Handle<'a, Histogram<L>>
 where
-    L: Lens<Target = Arc<Mutex<HistogramBuffer>>>,
+    L: Lens,
+    L::Target: SharedWrite<Value = HistogramBuffer>,
 {
     // stubs
     fn fill_from_max(self) -> Self {
@@ -128,7 +350,8 @@ where
 
 impl<'a, L> RangeModifiers for Handle<'a, Histogram<L>>
 where
-    L: Lens<Target = Arc<Mutex<HistogramBuffer>>>,
+    L: Lens,
+    L::Target: SharedWrite<Value = HistogramBuffer>,
 {
     fn range(mut self, range: impl Res<(f32, f32)>) -> Self {
         let e = self.entity();
@@ -159,3 +382,42 @@ where
     // self
     // }
 }
+
+impl<'a, L> ThrottleModifiers for Handle<'a, Histogram<L>>
+where
+    L: Lens,
+    L::Target: SharedWrite<Value = HistogramBuffer>,
+{
+    fn max_fps(self, max_fps: f32) -> Self {
+        self.modify(|view| view.throttle.set_max_fps(max_fps))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{panned_range, zoomed_range, MIN_ZOOM_SPAN};
+
+    const LIMITS: (f32, f32) = (-96., 24.);
+
+    #[test]
+    fn zooming_around_the_cursor() {
+        // The level under the cursor stays in place
+        let range = zoomed_range((-32., 8.), 0.75, 0.5, LIMITS);
+        assert_eq!(range, (-17., 3.));
+        assert_eq!(zoomed_range(range, 0.75, 2., LIMITS), (-32., 8.));
+
+        // Zooming out stops at the limits, and zooming in at the smallest span
+        assert_eq!(zoomed_range((-32., 8.), 0.5, 10., LIMITS), LIMITS);
+        assert_eq!(zoomed_range((-40., 20.), 0.5, 3., LIMITS), LIMITS);
+        let range = zoomed_range((-12., -11.5), 0., 0.1, LIMITS);
+        assert_eq!(range, (-12., -12. + MIN_ZOOM_SPAN));
+    }
+
+    #[test]
+    fn panning_stays_within_the_limits() {
+        assert_eq!(panned_range((-32., 8.), 0.25, LIMITS), (-22., 18.));
+        assert_eq!(panned_range((-32., 8.), -0.5, LIMITS), (-52., -12.));
+        assert_eq!(panned_range((-32., 8.), 1., LIMITS), (-16., 24.));
+        assert_eq!(panned_range((-32., 8.), -10., LIMITS), (-96., -56.));
+    }
+}
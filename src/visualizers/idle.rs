@@ -0,0 +1,238 @@
+use std::time::{Duration, Instant};
+
+use nih_plug_vizia::vizia::{prelude::*, vg};
+
+use super::Frame;
+use crate::utils::{IdleTracker, NormalizedRead, VisualizerBuffer};
+
+/// How often a view checks whether it went idle.
+const CHECK_INTERVAL: Duration = Duration::from_millis(16);
+
+/// How long the scanline takes to sweep across a view.
+const SWEEP_PERIOD: Duration = Duration::from_secs(3);
+
+/// How far an element needs to lie from the level a view is filled from to count
+/// as audible, as a fraction of the view's range.
+const AUDIBLE_THRESHOLD: f32 = 1e-3;
+
+/// How an idle view shows that it's idle; See [`IdleModifiers`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdleStyle {
+    /// Dims the view, using the `.idle-overlay` selector.
+    #[default]
+    Dim,
+    /// A faint line that slowly sweeps across the view, in its font color.
+    Scanline,
+    /// A faint text in the view's center, e.g. "No signal", using the
+    /// `.idle-watermark` selector.
+    Watermark(&'static str),
+}
+
+pub trait IdleModifiers {
+    /// Shows that the view is idle once no audible audio reached its buffer for a
+    /// while, instead of leaving a flat line that could just as well be a frozen
+    /// editor.
+    ///
+    /// An element is audible when it lies away from the level the view is filled
+    /// from - e.g. above the bottom of a peak graph's range. The view goes idle after
+    /// `after` without a single audible element, and is active again as soon as one
+    /// arrives; See [`IdleTracker`].
+    ///
+    /// The buffer's newest elements are checked once per frame. Dimming and the
+    /// watermark are regular views, which are hidden while the view is active; The
+    /// scanline is drawn along with the view, so it moves no more often than the
+    /// view's [frame rate](super::ThrottleModifiers::max_fps) allows. Without this
+    /// modifier, none of this happens.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+    ///     .idle_after(Duration::from_secs(2), IdleStyle::Watermark("No signal"))
+    ///     .color(Color::rgba(0, 0, 0, 160));
+    /// ```
+    fn idle_after(self, after: Duration, style: IdleStyle) -> Self;
+}
+
+pub(crate) enum IdleEvents {
+    /// Sent to the view once per frame.
+    Check,
+    /// Sent by the view to its [`IdleFlag`] when it went idle or active.
+    Changed(bool),
+}
+
+/// Whether a view is idle, which its idle overlays bind to.
+#[derive(Lens)]
+struct IdleFlag {
+    idle: bool,
+}
+
+impl Model for IdleFlag {
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|e, _| {
+            if let IdleEvents::Changed(idle) = e {
+                self.idle = *idle;
+            }
+        });
+    }
+}
+
+/// The idle tracking of a view; See [`IdleModifiers`].
+pub(crate) struct IdleState {
+    style: IdleStyle,
+    tracker: IdleTracker,
+}
+
+impl IdleState {
+    pub(crate) fn new(after: Duration, style: IdleStyle) -> Self {
+        Self {
+            style,
+            tracker: IdleTracker::new(after),
+        }
+    }
+
+    /// Checks the elements the buffer enqueued since the last check, and returns
+    /// whether the view is idle if that changed.
+    ///
+    /// * `anchor` - The normalized level the view is filled from
+    pub(crate) fn check<B>(
+        self: &mut Self,
+        now: Instant,
+        values: &NormalizedRead<B>,
+        anchor: f32,
+    ) -> Option<bool>
+    where
+        B: VisualizerBuffer<f32, Output = f32> + ?Sized,
+    {
+        let enqueued = values.buffer().enqueued();
+        let len = values.len();
+        let fresh = self.tracker.fresh(enqueued).min(len);
+        let audible =
+            (len - fresh..len).any(|i| (values.normalized(i) - anchor).abs() > AUDIBLE_THRESHOLD);
+
+        let was_idle = self.tracker.is_idle();
+        let idle = self.tracker.update(now, enqueued, audible);
+        (idle != was_idle).then_some(idle)
+    }
+
+    /// Returns where the scanline lies at `now`, as a fraction of the view, or
+    /// `None` if the view doesn't show one right now.
+    pub(crate) fn scanline(self: &Self, now: Instant) -> Option<f32> {
+        if self.style != IdleStyle::Scanline {
+            return None;
+        }
+        let elapsed = now.saturating_duration_since(self.tracker.idle_since()?);
+        Some((elapsed.as_secs_f32() / SWEEP_PERIOD.as_secs_f32()).fract())
+    }
+}
+
+/// Builds the overlay of an [`IdleStyle`] into a view, along with the timer that
+/// checks whether the view is idle.
+pub(crate) fn build_idle(cx: &mut Context, entity: Entity, style: IdleStyle) {
+    cx.with_current(entity, |cx| {
+        IdleFlag { idle: false }.build(cx);
+        match style {
+            IdleStyle::Dim => {
+                Element::new(cx)
+                    .class("idle-overlay")
+                    .position_type(PositionType::SelfDirected)
+                    .background_color(Color::rgba(0, 0, 0, 90))
+                    .hoverable(false)
+                    .display(IdleFlag::idle);
+            }
+            IdleStyle::Watermark(text) => {
+                Label::new(cx, text)
+                    .class("idle-watermark")
+                    .position_type(PositionType::SelfDirected)
+                    .space(Stretch(1.0))
+                    .font_size(12.)
+                    .color(Color::rgba(230, 230, 230, 110))
+                    .hoverable(false)
+                    .display(IdleFlag::idle);
+            }
+            IdleStyle::Scanline => {}
+        }
+
+        let timer = cx.add_timer(CHECK_INTERVAL, None, move |cx, action| {
+            if let TimerAction::Tick(_) = action {
+                cx.emit_to(entity, IdleEvents::Check);
+            }
+        });
+        cx.start_timer(timer);
+    });
+}
+
+/// Draws the scanline of an idle view between two points, in the view's font color.
+pub(crate) fn draw_scanline(
+    cx: &mut DrawContext,
+    frame: &mut Frame,
+    from: (f32, f32),
+    to: (f32, f32),
+) {
+    let mut color: vg::Color = cx.font_color().into();
+    color.a *= 0.3;
+    let mut line = vg::Path::new();
+    line.move_to(from.0, from.1);
+    line.line_to(to.0, to.1);
+    frame.stroke_path(
+        &line,
+        &vg::Paint::color(color).with_line_width(cx.scale_factor()),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IdleState, IdleStyle};
+    use crate::utils::{NormalizedRead, RingBuffer, ValueScaling};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn silence_and_signal() {
+        let mut buffer = RingBuffer::<f32>::new(16);
+        let mut state = IdleState::new(Duration::from_millis(500), IdleStyle::Scanline);
+        let start = Instant::now();
+        let mut check = |buffer: &RingBuffer<f32>, ms: u64| {
+            let values = NormalizedRead::new(buffer, (0.0, 1.0), ValueScaling::Linear);
+            state.check(start + Duration::from_millis(ms), &values, 0.0)
+        };
+
+        // A loud element that was enqueued before the editor opened counts, until
+        // the period has passed without another one
+        buffer.enqueue(0.5);
+        assert_eq!(check(&buffer, 0), None);
+        for _ in 0..16 {
+            buffer.enqueue(0.);
+        }
+        assert_eq!(check(&buffer, 400), None);
+        assert_eq!(check(&buffer, 500), Some(true));
+        assert_eq!(check(&buffer, 516), None);
+
+        // The first audible element ends it right away
+        buffer.enqueue(0.01);
+        assert_eq!(check(&buffer, 532), Some(false));
+    }
+
+    #[test]
+    fn scanline_sweeps_while_idle() {
+        let mut buffer = RingBuffer::<f32>::new(4);
+        let mut state = IdleState::new(Duration::from_secs(1), IdleStyle::Scanline);
+        let start = Instant::now();
+        buffer.enqueue(0.);
+        let values = NormalizedRead::new(&buffer, (0.0, 1.0), ValueScaling::Linear);
+
+        state.check(start, &values, 0.0);
+        assert_eq!(state.scanline(start), None);
+        state.check(start + Duration::from_secs(1), &values, 0.0);
+        assert_eq!(state.scanline(start + Duration::from_secs(1)), Some(0.0));
+        assert_eq!(
+            state.scanline(start + Duration::from_millis(2500)),
+            Some(0.5)
+        );
+
+        // Other styles don't draw one
+        let mut dimmed = IdleState::new(Duration::from_secs(1), IdleStyle::Dim);
+        dimmed.check(start, &values, 0.0);
+        dimmed.check(start + Duration::from_secs(1), &values, 0.0);
+        assert_eq!(dimmed.scanline(start + Duration::from_secs(2)), None);
+    }
+}
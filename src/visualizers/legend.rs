@@ -0,0 +1,227 @@
+use super::elements;
+use super::TraceStyle;
+use nih_plug_vizia::vizia::prelude::*;
+use std::ops::Range;
+
+/// Labels the traces of a [`GraphGroup`](super::GraphGroup), showing a swatch of
+/// each trace's color next to its name.
+///
+/// The entries are taken from the same [`TraceStyle`]s as the traces, so the colors
+/// always match - including the automatically assigned ones, as long as the styles
+/// are passed in the same order.
+///
+/// The entries are laid out in a row, or in a column, and can start a new line
+/// after a number of entries using [`wrap_after()`](LegendModifiers::wrap_after).
+/// With [`toggles_visibility()`](LegendModifiers::toggles_visibility), clicking an
+/// entry shows or hides its trace. Hidden traces are drawn faded in the legend.
+///
+/// # Example
+///
+/// ```
+/// Legend::new(cx, &styles, Orientation::Horizontal)
+///     .wrap_after(4)
+///     .toggles_visibility(true)
+///     .font_size(12.)
+///     .color(Color::rgb(160, 160, 160));
+/// ```
+///
+/// # Styling
+///
+/// Stylesheets select it as `cyma-legend`. Each entry is a `cyma-legend-entry`,
+/// whose label inherits the `color` and `font-size`.
+pub struct Legend {}
+
+/// Which traces are hidden, and how the entries are laid out.
+#[derive(Lens)]
+struct LegendState {
+    styles: Vec<TraceStyle>,
+    hidden: Vec<bool>,
+    wrap_after: Option<usize>,
+    toggles: bool,
+}
+
+enum LegendEvents {
+    Toggle(usize),
+    SetToggles(bool),
+    SetWrapAfter(Option<usize>),
+}
+
+impl Model for LegendState {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|e, meta| match e {
+            LegendEvents::Toggle(index) => {
+                if self.toggles {
+                    if let Some(style) = self.styles.get(*index) {
+                        style.visibility().toggle();
+                    }
+                }
+                // The traces may also have been shown or hidden elsewhere
+                self.hidden = hidden(&self.styles);
+                cx.needs_redraw();
+                meta.consume();
+            }
+            LegendEvents::SetToggles(toggles) => self.toggles = *toggles,
+            LegendEvents::SetWrapAfter(wrap_after) => self.wrap_after = *wrap_after,
+        });
+    }
+}
+
+// The opacity of the entries of hidden traces
+const HIDDEN_OPACITY: f32 = 0.4;
+
+impl Legend {
+    /// Creates a legend with one entry for each style.
+    ///
+    /// * `styles` - The styles of the traces, in the order they were added to the group
+    /// * `orientation` - Whether the entries are laid out in a row or in a column
+    pub fn new<'a>(
+        cx: &'a mut Context,
+        styles: &[TraceStyle],
+        orientation: Orientation,
+    ) -> Handle<'a, Self> {
+        let styles = styles.to_vec();
+        Self {}.build(cx, move |cx| {
+            LegendState {
+                hidden: hidden(&styles),
+                styles: styles.clone(),
+                wrap_after: None,
+                toggles: false,
+            }
+            .build(cx);
+
+            Binding::new(cx, LegendState::wrap_after, move |cx, wrap_after| {
+                let lines = lines(styles.len(), wrap_after.get(cx));
+                let build_line = |cx: &mut Context, line: Range<usize>| {
+                    for index in line {
+                        build_entry(cx, &styles[index], index);
+                    }
+                };
+                match orientation {
+                    Orientation::Horizontal => {
+                        VStack::new(cx, |cx| {
+                            for line in lines {
+                                HStack::new(cx, |cx| build_line(cx, line))
+                                    .height(Auto)
+                                    .col_between(Pixels(12.));
+                            }
+                        })
+                        .row_between(Pixels(4.));
+                    }
+                    Orientation::Vertical => {
+                        HStack::new(cx, |cx| {
+                            for line in lines {
+                                VStack::new(cx, |cx| build_line(cx, line))
+                                    .width(Auto)
+                                    .row_between(Pixels(4.));
+                            }
+                        })
+                        .col_between(Pixels(12.));
+                    }
+                }
+            });
+        })
+    }
+}
+
+/// A single entry of a [`Legend`], which reports clicks to it.
+struct LegendEntry {
+    index: usize,
+}
+
+impl View for LegendEntry {
+    fn element(&self) -> Option<&'static str> {
+        Some(elements::LEGEND_ENTRY)
+    }
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| {
+            if let WindowEvent::MouseDown(MouseButton::Left) = window_event {
+                cx.emit(LegendEvents::Toggle(self.index));
+                meta.consume();
+            }
+        });
+    }
+}
+
+fn build_entry(cx: &mut Context, style: &TraceStyle, index: usize) {
+    let swatch = style.stroke_color(index);
+    let name = style.name().to_string();
+    LegendEntry { index }
+        .build(cx, move |cx| {
+            Element::new(cx)
+                .background_color(swatch)
+                .size(Pixels(10.))
+                .top(Stretch(1.))
+                .bottom(Stretch(1.));
+            Label::new(cx, name);
+        })
+        .layout_type(LayoutType::Row)
+        .col_between(Pixels(6.))
+        .width(Auto)
+        .height(Auto)
+        .opacity(
+            LegendState::hidden.map(move |hidden| match hidden.get(index) {
+                Some(true) => HIDDEN_OPACITY,
+                _ => 1.,
+            }),
+        );
+}
+
+fn hidden(styles: &[TraceStyle]) -> Vec<bool> {
+    styles
+        .iter()
+        .map(|style| !style.visibility().is_visible())
+        .collect()
+}
+
+/// Splits the entries into lines of at most `wrap_after` entries each.
+fn lines(len: usize, wrap_after: Option<usize>) -> Vec<Range<usize>> {
+    let per_line = wrap_after.unwrap_or(len).max(1);
+    (0..len)
+        .step_by(per_line)
+        .map(|start| start..(start + per_line).min(len))
+        .collect()
+}
+
+impl View for Legend {
+    fn element(&self) -> Option<&'static str> {
+        Some(elements::LEGEND)
+    }
+}
+
+pub trait LegendModifiers {
+    /// Starts a new line after every `entries` entries - a new row if the legend is
+    /// horizontal, and a new column if it is vertical.
+    fn wrap_after(self, entries: usize) -> Self;
+
+    /// Shows or hides a trace when its entry is clicked, using the style's
+    /// [`TraceVisibility`](super::TraceVisibility).
+    fn toggles_visibility(self, toggles: bool) -> Self;
+}
+
+impl<'a> LegendModifiers for Handle<'a, Legend> {
+    fn wrap_after(mut self, entries: usize) -> Self {
+        let e = self.entity();
+        self.context()
+            .emit_to(e, LegendEvents::SetWrapAfter(Some(entries)));
+        self
+    }
+    fn toggles_visibility(mut self, toggles: bool) -> Self {
+        let e = self.entity();
+        self.context().emit_to(e, LegendEvents::SetToggles(toggles));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lines;
+
+    #[test]
+    fn wrapping() {
+        assert_eq!(lines(5, None), vec![0..5]);
+        assert_eq!(lines(5, Some(2)), vec![0..2, 2..4, 4..5]);
+        assert_eq!(lines(4, Some(4)), vec![0..4]);
+        assert_eq!(lines(3, Some(0)), vec![0..1, 1..2, 2..3]);
+        assert!(lines(0, Some(3)).is_empty());
+    }
+}
@@ -1,23 +1,14 @@
-use crate::utils::RingBuffer;
+use super::elements;
+use super::{draw_clipped, draw_throttled, FrameThrottle, ThrottleModifiers};
+use crate::geometry::lissajous_dots;
+use crate::utils::{RingBuffer, SharedRead};
 
-use lazy_static::lazy_static;
 use nih_plug_vizia::vizia::{
     binding::{Lens, LensExt},
     context::{Context, DrawContext},
     vg,
     view::{Canvas, Handle, View},
 };
-use std::{
-    f32::consts::PI,
-    sync::{Arc, Mutex},
-};
-
-// These will be used to rotate the lissajous such that it is a straight
-// vertical line for mono data and a horizontal line for fully stereo data.
-lazy_static! {
-    static ref TRANSLATE_SIN: f32 = (PI / 4.).sin();
-    static ref TRANSLATE_COS: f32 = (PI / 4.).cos();
-}
 
 /// Lissajous for stereo audio data.
 ///
@@ -78,67 +69,68 @@ lazy_static! {
 /// .border_width(Pixels(1.))
 /// .width(Pixels(200.));
 /// ```
+///
+/// # Styling
+///
+/// Stylesheets select it as `cyma-lissajous`. Its `color` is the dots.
 pub struct Lissajous<L>
 where
-    L: Lens<Target = Arc<Mutex<RingBuffer<(f32, f32)>>>>,
+    L: Lens,
+    L::Target: SharedRead<Value = RingBuffer<(f32, f32)>>,
 {
     buffer: L,
+    throttle: FrameThrottle,
 }
 
 impl<L> Lissajous<L>
 where
-    L: Lens<Target = Arc<Mutex<RingBuffer<(f32, f32)>>>>,
+    L: Lens,
+    L::Target: SharedRead<Value = RingBuffer<(f32, f32)>>,
 {
     pub fn new(cx: &mut Context, buffer: L) -> Handle<Self> {
-        Self { buffer }.build(cx, |_| {})
+        Self {
+            buffer,
+            throttle: FrameThrottle::default(),
+        }
+        .build(cx, |_| {})
     }
 }
 
 impl<L> View for Lissajous<L>
 where
-    L: Lens<Target = Arc<Mutex<RingBuffer<(f32, f32)>>>>,
+    L: Lens,
+    L::Target: SharedRead<Value = RingBuffer<(f32, f32)>>,
 {
     fn element(&self) -> Option<&'static str> {
-        None
+        Some(elements::LISSAJOUS)
     }
     fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
-        let bounds = cx.bounds();
-
-        let x = bounds.x;
-        let y = bounds.y;
-        let w = bounds.w;
-        let h = bounds.h;
-
-        let binding = self.buffer.get(cx);
-        let ring_buf = &(binding.lock().unwrap());
-
-        canvas.fill_path(
-            &{
-                let mut dots = vg::Path::new();
-
-                for i in 0..ring_buf.len() {
-                    let left = ring_buf[i].0.clamp(-1., 1.);
-                    let right = ring_buf[i].1.clamp(-1., 1.);
-
-                    let dot_x = left * *TRANSLATE_COS - right * *TRANSLATE_SIN;
-                    let dot_y = left * *TRANSLATE_SIN + right * *TRANSLATE_COS;
-
-                    dots.rect(
-                        x + w / 2. - dot_x * w / PI,
-                        y + h / 2. - dot_y * h / PI,
-                        1f32,
-                        1f32,
-                    );
-                }
-
-                dots
-            },
-            &vg::Paint::color(cx.font_color().into()),
-        );
+        draw_throttled(cx, canvas, &self.throttle, |cx, canvas| {
+            let binding = self.buffer.get(cx);
+            let ring_buf = &(binding.read_shared(|_| {}));
+
+            canvas.fill_path(
+                &{
+                    let mut dots = vg::Path::new();
+
+                    for (dot_x, dot_y) in lissajous_dots(cx.bounds().into(), ring_buf) {
+                        dots.rect(dot_x, dot_y, 1f32, 1f32);
+                    }
+
+                    dots
+                },
+                &vg::Paint::color(cx.font_color().into()),
+            );
+        });
     }
 }
 
 /// A diamond-shaped grid that can serve as a backdrop for a [`Lissajous`]
+///
+/// # Styling
+///
+/// Stylesheets select it as `cyma-lissajous-grid`. Its `color` is the lines, and its
+/// `background-color` the diamond behind them.
 pub struct LissajousGrid {}
 
 impl LissajousGrid {
@@ -149,50 +141,62 @@ impl LissajousGrid {
 
 impl View for LissajousGrid {
     fn element(&self) -> Option<&'static str> {
-        Some("lissajous")
+        Some(elements::LISSAJOUS_GRID)
     }
     fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
-        let bounds = cx.bounds();
-
-        let x = bounds.x;
-        let y = bounds.y;
-        let w = bounds.w;
-        let h = bounds.h;
-
-        let mut path = vg::Path::new();
-
-        // Diamond shape
-        path.move_to(x + w / 2., y);
-        path.line_to(x + w, y + h / 2.);
-        path.line_to(x + w / 2., y + h);
-        path.line_to(x, y + h / 2.);
-        path.close();
-
-        canvas.fill_path(
-            &(path.clone()),
-            &vg::Paint::color(cx.background_color().into()),
-        );
-
-        // Vertical line
-        path.move_to(x, y + h / 2.);
-        path.line_to(x + w, y + h / 2.);
-        path.close();
-
-        // Horizontal line
-        path.move_to(x + w / 2., y);
-        path.line_to(x + w / 2., y + h);
-        path.close();
-
-        // Diagonal line (Left channel)
-        path.move_to(x + w * 0.25, y + h * 0.25);
-        path.line_to(x + w * 0.75, y + h * 0.75);
-        path.close();
-
-        // Diagonal line (Right channel)
-        path.move_to(x + w * 0.75, y + h * 0.25);
-        path.line_to(x + w * 0.25, y + h * 0.75);
-        path.close();
-
-        canvas.stroke_path(&path, &vg::Paint::color(cx.font_color().into()));
+        draw_clipped(cx, canvas, |cx, canvas| {
+            let bounds = cx.bounds();
+
+            let x = bounds.x;
+            let y = bounds.y;
+            let w = bounds.w;
+            let h = bounds.h;
+
+            let mut path = vg::Path::new();
+
+            // Diamond shape
+            path.move_to(x + w / 2., y);
+            path.line_to(x + w, y + h / 2.);
+            path.line_to(x + w / 2., y + h);
+            path.line_to(x, y + h / 2.);
+            path.close();
+
+            canvas.fill_path(
+                &(path.clone()),
+                &vg::Paint::color(cx.background_color().into()),
+            );
+
+            // Vertical line
+            path.move_to(x, y + h / 2.);
+            path.line_to(x + w, y + h / 2.);
+            path.close();
+
+            // Horizontal line
+            path.move_to(x + w / 2., y);
+            path.line_to(x + w / 2., y + h);
+            path.close();
+
+            // Diagonal line (Left channel)
+            path.move_to(x + w * 0.25, y + h * 0.25);
+            path.line_to(x + w * 0.75, y + h * 0.75);
+            path.close();
+
+            // Diagonal line (Right channel)
+            path.move_to(x + w * 0.75, y + h * 0.25);
+            path.line_to(x + w * 0.25, y + h * 0.75);
+            path.close();
+
+            canvas.stroke_path(&path, &vg::Paint::color(cx.font_color().into()));
+        });
+    }
+}
+
+impl<'a, L> ThrottleModifiers for Handle<'a, Lissajous<L>>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = RingBuffer<(f32, f32)>>,
+{
+    fn max_fps(self, max_fps: f32) -> Self {
+        self.modify(|view| view.throttle.set_max_fps(max_fps))
     }
 }
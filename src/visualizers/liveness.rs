@@ -0,0 +1,131 @@
+use std::time::{Duration, Instant};
+
+use nih_plug_vizia::vizia::prelude::*;
+
+use crate::utils::{Heartbeat, StalenessTracker};
+
+/// How often a [`LivenessMonitor`] reads its heartbeat.
+const CHECK_INTERVAL: Duration = Duration::from_millis(16);
+
+/// The size of a view's stale indicator and its distance to the view's corner, in
+/// logical pixels.
+const INDICATOR_SIZE: f32 = 6.0;
+const INDICATOR_INSET: f32 = 4.0;
+
+/// Keeps track of whether a [`Heartbeat`] went stale, so that the editor can show
+/// it; See [`StalenessTracker`].
+///
+/// The heartbeat is read once per frame, while the editor is open. Build the
+/// monitor inside the editor, and bind to its [`stale`](LivenessMonitor::stale)
+/// lens from any of the views below it:
+///
+/// ```
+/// VStack::new(cx, |cx| {
+///     LivenessMonitor::new(heartbeat.clone(), Duration::from_millis(500)).build(cx);
+///
+///     Label::new(cx, "The host stopped processing")
+///         .display(LivenessMonitor::stale);
+/// });
+/// ```
+///
+/// A view's [`stale_indicator()`](StaleIndicatorModifiers::stale_indicator) builds
+/// a monitor of its own.
+#[derive(Lens)]
+pub struct LivenessMonitor {
+    /// Whether the heartbeat is stale.
+    pub stale: bool,
+    heartbeat: Heartbeat,
+    tracker: StalenessTracker,
+}
+
+enum LivenessEvents {
+    Check,
+}
+
+impl LivenessMonitor {
+    /// Constructs a new `LivenessMonitor`, which starts out fresh.
+    ///
+    /// * `heartbeat` - The heartbeat of the buffer that is monitored
+    /// * `threshold` - How long the heartbeat needs to stay unchanged to go stale
+    pub fn new(heartbeat: Heartbeat, threshold: Duration) -> Self {
+        Self {
+            stale: false,
+            heartbeat,
+            tracker: StalenessTracker::new(threshold),
+        }
+    }
+
+    /// Builds the monitor into the current view, and starts reading the heartbeat.
+    pub fn build(self, cx: &mut Context) {
+        Model::build(self, cx);
+
+        let entity = cx.current();
+        let timer = cx.add_timer(CHECK_INTERVAL, None, move |cx, action| {
+            if let TimerAction::Tick(_) = action {
+                cx.emit_to(entity, LivenessEvents::Check);
+            }
+        });
+        cx.start_timer(timer);
+    }
+}
+
+impl Model for LivenessMonitor {
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|e, _| match e {
+            LivenessEvents::Check => {
+                let stale = self.tracker.update(Instant::now(), self.heartbeat.read());
+                if stale != self.stale {
+                    self.stale = stale;
+                }
+            }
+        });
+    }
+}
+
+pub trait StaleIndicatorModifiers {
+    /// Shows a small hollow dot in the view's top right corner while the buffer it
+    /// displays isn't being fed, even though the host's transport is playing.
+    ///
+    /// This tells apart a host that stopped processing from an editor that stalled,
+    /// when users report that the view froze. The heartbeat is read once per frame,
+    /// which costs two atomic loads; Without an indicator, nothing is read.
+    ///
+    /// * `heartbeat` - The heartbeat of the buffer, e.g. from [`PeakBuffer::heartbeat()`](crate::utils::PeakBuffer::heartbeat)
+    /// * `threshold` - How long the heartbeat needs to stay unchanged to go stale
+    ///
+    /// The dot can be styled using the `.stale-indicator` selector.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+    ///     .stale_indicator(heartbeat.clone(), Duration::from_millis(500))
+    ///     .color(Color::rgba(0, 0, 0, 160));
+    /// ```
+    fn stale_indicator(self, heartbeat: Heartbeat, threshold: Duration) -> Self;
+}
+
+/// Builds a [`LivenessMonitor`] and its indicator into a view.
+pub(crate) fn build_stale_indicator(
+    cx: &mut Context,
+    entity: Entity,
+    heartbeat: Heartbeat,
+    threshold: Duration,
+) {
+    cx.with_current(entity, |cx| {
+        LivenessMonitor::new(heartbeat, threshold).build(cx);
+        Element::new(cx)
+            .class("stale-indicator")
+            .position_type(PositionType::SelfDirected)
+            .left(Stretch(1.0))
+            .right(Pixels(INDICATOR_INSET))
+            .top(Pixels(INDICATOR_INSET))
+            .bottom(Stretch(1.0))
+            .size(Pixels(INDICATOR_SIZE))
+            .border_radius(Percentage(50.0))
+            .border_width(Pixels(1.0))
+            .border_color(Color::rgb(230, 230, 230))
+            .hoverable(false)
+            .display(LivenessMonitor::stale);
+    });
+}
@@ -0,0 +1,117 @@
+//! Generates the values at which a [`Grid`](super::Grid) draws its lines and a
+//! [`UnitRuler`](super::UnitRuler) places its labels.
+//!
+//! Both views generate their marks here, so that a grid and a ruler set up with
+//! the same anchor, step and range always line up.
+
+// Guards against a tiny step generating millions of marks
+const MAX_MARKS: usize = 1024;
+
+// How far outside of the range a mark may fall and still be kept, as a fraction of
+// the step - a step of 0.1 is slightly larger as an `f32`, so a mark at exactly 100
+// would otherwise be dropped
+const TOLERANCE: f64 = 1e-4;
+
+/// Returns every mark of the form `anchor + k * step` that lies inside the range,
+/// in ascending order.
+///
+/// The anchor doesn't need to lie inside the range, and the sign of the step
+/// doesn't matter. Each mark is computed from its integer `k`, rather than by
+/// repeatedly adding the step, so that rounding errors don't accumulate.
+pub(crate) fn marks_from_anchor(anchor: f32, step: f32, range: (f32, f32)) -> Vec<f32> {
+    let (min, max) = (range.0.min(range.1) as f64, range.0.max(range.1) as f64);
+    let (anchor, step) = (anchor as f64, step.abs() as f64);
+
+    if !(anchor.is_finite() && min.is_finite() && max.is_finite()) {
+        return Vec::new();
+    }
+    // Without a step, the anchor is the only mark
+    if step == 0. || !step.is_finite() {
+        return if (min..=max).contains(&anchor) {
+            vec![anchor as f32]
+        } else {
+            Vec::new()
+        };
+    }
+
+    let first = ((min - anchor) / step - TOLERANCE).ceil();
+    let last = ((max - anchor) / step + TOLERANCE).floor();
+    if last < first {
+        return Vec::new();
+    }
+
+    let count = ((last - first) as usize + 1).min(MAX_MARKS);
+    (0..count)
+        // Marks that were kept due to the tolerance are moved onto the range's edge
+        .map(|i| (anchor + (first + i as f64) * step).clamp(min, max) as f32)
+        .collect()
+}
+
+/// Returns the given marks as they are.
+pub(crate) fn marks_explicit(marks: Vec<f32>) -> Vec<f32> {
+    marks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{marks_explicit, marks_from_anchor};
+
+    #[test]
+    fn anchor_inside_range() {
+        assert_eq!(
+            marks_from_anchor(0., 6., (-24., 6.)),
+            vec![-24., -18., -12., -6., 0., 6.]
+        );
+    }
+
+    #[test]
+    fn anchor_outside_range() {
+        // Every 10 dB from 0, inside a range that doesn't contain 0
+        assert_eq!(
+            marks_from_anchor(0., 10., (-96., -35.)),
+            vec![-90., -80., -70., -60., -50., -40.]
+        );
+        assert_eq!(marks_from_anchor(100., 10., (12., 38.)), vec![20., 30.]);
+        // No mark falls inside the range
+        assert_eq!(marks_from_anchor(0., 10., (1., 9.)), Vec::<f32>::new());
+    }
+
+    #[test]
+    fn uneven_steps() {
+        // 10 doesn't divide the range of 120 dB evenly from the range's edges
+        assert_eq!(
+            marks_from_anchor(0., 10., (-96., 24.)),
+            vec![-90., -80., -70., -60., -50., -40., -30., -20., -10., 0., 10., 20.]
+        );
+        assert_eq!(marks_from_anchor(1., 4., (0., 10.)), vec![1., 5., 9.]);
+    }
+
+    #[test]
+    fn negative_step_and_reversed_range() {
+        assert_eq!(
+            marks_from_anchor(0., -10., (24., -96.)),
+            marks_from_anchor(0., 10., (-96., 24.))
+        );
+    }
+
+    #[test]
+    fn no_accumulated_error() {
+        // Adding 0.1 repeatedly drifts away from the exact marks
+        let marks = marks_from_anchor(0., 0.1, (0., 100.));
+        assert_eq!(marks.len(), 1001);
+        assert_eq!(marks[1000], 100.);
+        assert!((marks[573] - 57.3).abs() < 1e-5);
+    }
+
+    #[test]
+    fn degenerate_steps() {
+        assert_eq!(marks_from_anchor(3., 0., (0., 10.)), vec![3.]);
+        assert_eq!(marks_from_anchor(30., 0., (0., 10.)), Vec::<f32>::new());
+        assert_eq!(marks_from_anchor(0., 1e-9, (0., 1.)).len(), 1024);
+    }
+
+    #[test]
+    fn explicit() {
+        assert_eq!(marks_explicit(vec![6., 0., -60.]), vec![6., 0., -60.]);
+    }
+}
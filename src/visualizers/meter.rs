@@ -1,15 +1,24 @@
-use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use nih_plug_vizia::vizia::{prelude::*, vg};
 
-use super::{FillFrom, FillModifiers, RangeModifiers};
-use crate::utils::ValueScaling;
-use crate::utils::VisualizerBuffer;
+use super::elements;
+use super::idle::{build_idle, draw_scanline, IdleEvents, IdleState};
+use super::liveness::build_stale_indicator;
+use super::{
+    draw_throttled, redraw_on_write, FillFrom, FillModifiers, FrameThrottle, IdleModifiers,
+    IdleStyle, RangeModifiers, StaleIndicatorModifiers, ThrottleModifiers,
+};
+use crate::geometry::meter_shape;
+use crate::utils::{Heartbeat, NormalizedRead, SharedRead, ValueScaling, VisualizerBuffer};
 
 /// Meter that displays the data inside a [`VisualizerBuffer`].
 ///
 /// Useful for peak meters, loudness meters, etc.
 ///
+/// Like a [`Graph`](super::Graph), a meter reads its buffer through
+/// [`SharedRead`], so it can be drawn from an `Arc<RwLock<B>>` along with other views.
+///
 /// # Example
 ///
 /// ```
@@ -24,9 +33,17 @@ use crate::utils::VisualizerBuffer;
 /// .height(Pixels(128.0))
 /// .background_color(Color::rgb(100, 100, 100));
 /// ```
+///
+/// # Styling
+///
+/// Stylesheets select it as `cyma-meter`. Its `color` is the level line, and its
+/// `background-color` the fill below it. Like graphs, meters can be
+/// [`secondary()`](super::VariantModifiers::secondary) or an
+/// [`accent()`](super::VariantModifiers::accent).
 pub struct Meter<L, I>
 where
-    L: Lens<Target = Arc<Mutex<I>>>,
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
     I: VisualizerBuffer<f32, Output = f32> + 'static,
 {
     buffer: L,
@@ -34,11 +51,14 @@ where
     scaling: ValueScaling,
     fill_from: FillFrom,
     orientation: Orientation,
+    throttle: FrameThrottle,
+    idle: Option<IdleState>,
 }
 
 impl<L, I> Meter<L, I>
 where
-    L: Lens<Target = Arc<Mutex<I>>>,
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
     I: VisualizerBuffer<f32, Output = f32> + 'static,
 {
     pub fn new(
@@ -49,13 +69,15 @@ where
         orientation: Orientation,
     ) -> Handle<Self> {
         Self {
-            buffer,
+            buffer: buffer.clone(),
             range: range.get_val(cx),
             scaling: scaling.get_val(cx),
             fill_from: FillFrom::Bottom,
             orientation,
+            throttle: FrameThrottle::default(),
+            idle: None,
         }
-        .build(cx, |_| {})
+        .build(cx, |cx| redraw_on_write(cx, buffer))
         .range(range)
         .scaling(scaling)
     }
@@ -68,92 +90,89 @@ enum MeterEvents {
 
 impl<L, I> View for Meter<L, I>
 where
-    L: Lens<Target = Arc<Mutex<I>>>,
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
     I: VisualizerBuffer<f32, Output = f32> + 'static,
 {
     fn element(&self) -> Option<&'static str> {
-        Some("meter")
+        Some(elements::METER)
     }
     fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
-        let bounds = cx.bounds();
-
-        let x = bounds.x;
-        let y = bounds.y;
-        let w = bounds.w;
-        let h = bounds.h;
-
-        let binding = self.buffer.get(cx);
-        let ring_buf = &(binding.lock().unwrap());
-
-        let level = self.scaling.value_to_normalized(
-            ring_buf[ring_buf.len() - 1],
-            self.range.0,
-            self.range.1,
-        );
-
-        let mut path = vg::Path::new();
-        match self.orientation {
-            Orientation::Vertical => {
-                path.move_to(x, y + h * (1. - level));
-                path.line_to(x + w, y + h * (1. - level));
-
-                let mut outline = path.clone();
-                outline.close();
-                canvas.fill_path(&outline, &vg::Paint::color(cx.font_color().into()));
-
-                let fill_from_n = match self.fill_from {
-                    FillFrom::Top => 0.0,
-                    FillFrom::Bottom => 1.0,
-                    FillFrom::Value(val) => {
-                        1.0 - ValueScaling::Linear.value_to_normalized(
-                            val,
-                            self.range.0,
-                            self.range.1,
-                        )
-                    }
-                };
+        draw_throttled(cx, canvas, &self.throttle, |cx, canvas| {
+            let binding = self.buffer.get(cx);
+            let ring_buf = binding.read_shared(|buffer| buffer.refresh());
+            let values = NormalizedRead::new(&*ring_buf, self.range, self.scaling);
 
-                path.line_to(x + w, y + h * fill_from_n);
-                path.line_to(x, y + h * fill_from_n);
-                path.close();
+            let vertical = matches!(self.orientation, Orientation::Vertical);
+            let Some(shape) = meter_shape(cx.bounds().into(), &values, &self.fill_from, vertical)
+            else {
+                return;
+            };
 
-                canvas.fill_path(&path, &vg::Paint::color(cx.background_color().into()));
+            let mut outline = vg::Path::new();
+            outline.move_to(shape.level[0].0, shape.level[0].1);
+            outline.line_to(shape.level[1].0, shape.level[1].1);
+            outline.close();
+            canvas.fill_path(&outline, &vg::Paint::color(cx.font_color().into()));
+
+            let mut fill = vg::Path::new();
+            fill.move_to(shape.fill[0].0, shape.fill[0].1);
+            for (x, y) in &shape.fill[1..] {
+                fill.line_to(*x, *y);
             }
-            Orientation::Horizontal => {
-                path.move_to(x + w * level, y);
-                path.line_to(x + w * level, y + h);
-
-                let mut outline = path.clone();
-                outline.close();
-                canvas.fill_path(&outline, &vg::Paint::color(cx.font_color().into()));
-
-                let fill_from_n = match self.fill_from {
-                    FillFrom::Top => 1.0,
-                    FillFrom::Bottom => 0.0,
-                    FillFrom::Value(val) => {
-                        ValueScaling::Linear.value_to_normalized(val, self.range.0, self.range.1)
+            fill.close();
+            canvas.fill_path(&fill, &vg::Paint::color(cx.background_color().into()));
+
+            // The scanline sweeps along the meter, from its bottom or left end
+            let scanline = self
+                .idle
+                .as_ref()
+                .and_then(|idle| idle.scanline(Instant::now()));
+            if let Some(level) = scanline {
+                let bounds = cx.bounds();
+                let (from, to) = match self.orientation {
+                    Orientation::Vertical => {
+                        let y = bounds.y + bounds.h * (1. - level);
+                        ((bounds.x, y), (bounds.x + bounds.w, y))
+                    }
+                    Orientation::Horizontal => {
+                        let x = bounds.x + bounds.w * level;
+                        ((x, bounds.y), (x, bounds.y + bounds.h))
                     }
                 };
-
-                path.line_to(x + w * fill_from_n, y + h);
-                path.line_to(x + w * fill_from_n, y);
-                path.close();
-
-                canvas.fill_path(&path, &vg::Paint::color(cx.background_color().into()));
+                draw_scanline(cx, canvas, from, to);
             }
-        };
+        });
     }
-    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
         event.map(|e, _| match e {
             MeterEvents::UpdateRange(v) => self.range = *v,
             MeterEvents::UpdateScaling(v) => self.scaling = *v,
         });
+        event.map(|e, _| {
+            if let IdleEvents::Check = e {
+                let Some(idle) = &mut self.idle else {
+                    return;
+                };
+                let changed = {
+                    let binding = self.buffer.get(cx);
+                    let ring_buf = binding.read_shared(|buffer| buffer.refresh());
+                    let values = NormalizedRead::new(&*ring_buf, self.range, self.scaling);
+                    let anchor = self.fill_from.normalized(&values);
+                    idle.check(Instant::now(), &values, anchor)
+                };
+                if let Some(idle) = changed {
+                    cx.emit(IdleEvents::Changed(idle));
+                }
+            }
+        });
     }
 }
 
 impl<'a, L, I> FillModifiers for Handle<'a, Meter<L, I>>
 where
-    L: Lens<Target = Arc<Mutex<I>>>,
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
     I: VisualizerBuffer<f32, Output = f32> + 'static,
 {
     /// Allows for the meter to be filled from the maximum instead of the minimum value.
@@ -202,7 +221,8 @@ where
 
 impl<'a, L, I> RangeModifiers for Handle<'a, Meter<L, I>>
 where
-    L: Lens<Target = Arc<Mutex<I>>>,
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
     I: VisualizerBuffer<f32, Output = f32> + 'static,
 {
     fn range(mut self, range: impl Res<(f32, f32)>) -> Self {
@@ -224,3 +244,40 @@ where
         self
     }
 }
+
+impl<'a, L, I> ThrottleModifiers for Handle<'a, Meter<L, I>>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    fn max_fps(self, max_fps: f32) -> Self {
+        self.modify(|view| view.throttle.set_max_fps(max_fps))
+    }
+}
+
+impl<'a, L, I> StaleIndicatorModifiers for Handle<'a, Meter<L, I>>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    fn stale_indicator(mut self, heartbeat: Heartbeat, threshold: Duration) -> Self {
+        let entity = self.entity();
+        build_stale_indicator(self.context(), entity, heartbeat, threshold);
+        self
+    }
+}
+
+impl<'a, L, I> IdleModifiers for Handle<'a, Meter<L, I>>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    fn idle_after(mut self, after: Duration, style: IdleStyle) -> Self {
+        let entity = self.entity();
+        build_idle(self.context(), entity, style);
+        self.modify(|meter| meter.idle = Some(IdleState::new(after, style)))
+    }
+}
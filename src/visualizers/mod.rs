@@ -1,27 +1,66 @@
 //! Views which visualize the audio running through your plug-in.
 
+mod activity_strip;
+mod axes;
+mod clear_fade;
+pub mod elements;
 mod graph;
+mod graph_group;
 mod grid;
 mod histogram;
+mod idle;
+mod legend;
 mod lissajous;
+mod liveness;
+mod marks;
 mod meter;
 mod oscilloscope;
+mod overlay;
+mod padding;
+mod plot_area;
+mod quality;
+mod sparkline;
 mod spectrum_analyzer;
+mod split_graph;
+mod throttle;
+mod time_cursor;
 mod unit_ruler;
 mod waveform;
 
+pub use activity_strip::*;
+pub use clear_fade::ClearFadeModifiers;
+pub use elements::VariantModifiers;
 pub use graph::*;
+pub use graph_group::*;
 pub use grid::*;
 pub use histogram::*;
+pub use idle::{IdleModifiers, IdleStyle};
+pub use legend::*;
 pub use lissajous::*;
+pub use liveness::*;
 pub use meter::*;
 pub use oscilloscope::*;
+pub use overlay::*;
+pub use quality::{
+    AdaptiveQuality, AdaptiveQualityModifiers, Degradation, DegradationOverride, FrameStats,
+};
+pub use sparkline::*;
 pub use spectrum_analyzer::*;
+pub use split_graph::*;
+pub use throttle::*;
+pub use time_cursor::{TimeCursor, TimeCursorModifiers};
 pub use unit_ruler::*;
 pub use waveform::*;
 
-use super::utils::ValueScaling;
-use nih_plug_vizia::vizia::binding::Res;
+pub(crate) use super::geometry::{refill, FillFrom};
+
+use super::utils::{
+    AutoRange, SharedRange, SharedRead, ValueScaling, VisualizerBuffer, VisualizerLens,
+};
+use nih_plug_vizia::vizia::binding::{Binding, Lens, Res};
+use nih_plug_vizia::vizia::prelude::{BoundingBox, Canvas, Context, DrawContext, Orientation};
+use nih_plug_vizia::vizia::vg;
+use std::sync::{Arc, Mutex};
 
 pub trait RangeModifiers {
     /// Sets the minimum and maximum values that can be displayed by the view
@@ -34,10 +73,116 @@ pub trait RangeModifiers {
     fn scaling(self, scaling: impl Res<ValueScaling>) -> Self;
 }
 
-pub(crate) enum FillFrom {
-    Top,
-    Bottom,
-    Value(f32),
+pub trait AxisModifiers {
+    /// Sets the direction the view's value axis runs in.
+    ///
+    /// By default, the value axis is vertical - values rise upwards, and time runs
+    /// from left to right. With [`Orientation::Horizontal`], values grow to the
+    /// right, and time runs from top to bottom, e.g. for a horizontal meter strip.
+    fn value_axis(self, orientation: Orientation) -> Self;
+    /// Flips the view horizontally, e.g. so that time runs from right to left in a
+    /// right-to-left layout.
+    fn mirror_x(self, mirror: bool) -> Self;
+    /// Sets which end of the value axis the range starts at.
+    ///
+    /// With [`GraphDirection::TopDown`], the bottom of the range lies at the top of
+    /// the view and values grow downwards - e.g. for gain reduction, which hangs
+    /// down from the top. Give a [`Grid`] and [`UnitRuler`] the same direction as
+    /// the view they belong to, so that their lines and labels stay lined up with
+    /// it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// // The gain reduction in decibels, from 0 dB at the top to 24 dB at the bottom
+    /// Graph::new(cx, Data::reduction_buffer, (0.0, 24.0), ValueScaling::Linear)
+    ///     .direction(GraphDirection::TopDown);
+    /// Grid::new(cx, ValueScaling::Linear, (0., 24.), vec![6., 12., 18.], Orientation::Horizontal)
+    ///     .direction(GraphDirection::TopDown);
+    /// ```
+    fn direction(self, direction: GraphDirection) -> Self;
+}
+
+/// Which end of the value axis a view's range starts at; See
+/// [`direction()`](AxisModifiers::direction).
+///
+/// Fill levels, such as [`fill_from_value()`](FillModifiers::fill_from_value) or the
+/// zero line of a [bipolar](GraphModifiers::bipolar) graph, are values, so they move
+/// along with the range. A graph is filled from the bottom of its range by default,
+/// which is the top edge of a top-down graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GraphDirection {
+    /// Values rise upwards, or grow to the right along a horizontal value axis.
+    #[default]
+    BottomUp,
+    /// Values grow downwards, or to the left along a horizontal value axis.
+    TopDown,
+}
+
+impl GraphDirection {
+    /// Returns where a normalized value lies along the value axis, from its bottom
+    /// (0) to its top (1).
+    pub(crate) fn apply(self: &Self, value: f32) -> f32 {
+        match self {
+            GraphDirection::BottomUp => value,
+            GraphDirection::TopDown => 1. - value,
+        }
+    }
+}
+
+pub trait PaddingModifiers {
+    /// Insets the area the view maps its values onto from its bounds, in logical
+    /// pixels.
+    ///
+    /// Values are mapped onto the inset area, so the edges of the range lie inside
+    /// the view instead of on its border. Strokes along the edges are always drawn
+    /// entirely inside the view. Give a [`Grid`] and [`UnitRuler`] the same padding
+    /// as the view they belong to, so that their lines and labels stay lined up
+    /// with it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+    ///     .inner_padding(8.0, 0.0, 8.0, 0.0);
+    /// Grid::new(cx, ValueScaling::Linear, (-32., 8.), lines, Orientation::Horizontal)
+    ///     .inner_padding(8.0, 0.0, 8.0, 0.0);
+    /// ```
+    fn inner_padding(self, top: f32, right: f32, bottom: f32, left: f32) -> Self;
+    /// Insets the area the view maps its values onto by the same amount on every
+    /// side; See [`inner_padding()`](Self::inner_padding).
+    fn uniform_inner_padding(self, padding: f32) -> Self;
+}
+
+pub trait AutoRangeModifiers {
+    /// Displays the range of an [`AutoRange`] instead of a fixed range.
+    ///
+    /// The range is driven by a [`Graph`] using
+    /// [`auto_range()`](GraphModifiers::auto_range), and read whenever the view is
+    /// drawn - so the view tracks the range as it animates.
+    fn follow_range(self, auto_range: Arc<Mutex<AutoRange>>) -> Self;
+
+    /// Displays the range of a [`SharedRange`] instead of a fixed range.
+    ///
+    /// The range is usually written by the audio thread, and read without a lock
+    /// whenever the view is drawn.
+    fn follow_shared_range(self, range: SharedRange) -> Self;
+}
+
+/// A range that a view reads whenever it is drawn, instead of a fixed range.
+#[derive(Clone)]
+pub(crate) enum RangeSource {
+    Auto(Arc<Mutex<AutoRange>>),
+    Shared(SharedRange),
+}
+
+impl RangeSource {
+    pub(crate) fn get(&self) -> (f32, f32) {
+        match self {
+            RangeSource::Auto(auto_range) => auto_range.lock().unwrap().range(),
+            RangeSource::Shared(range) => range.get(),
+        }
+    }
 }
 
 pub trait FillModifiers {
@@ -47,3 +192,143 @@ pub trait FillModifiers {
     /// Allows for the view to be filled from any desired level.
     fn fill_from_value(self, level: f32) -> Self;
 }
+
+/// Redraws the view that is being built whenever the buffer behind the lens is
+/// written to.
+///
+/// The binding is to the buffer's [`BufferRevision`](crate::utils::BufferRevision),
+/// so it fires exactly when the buffer's generation changes, and never while it's
+/// unchanged - e.g. while the audio is paused.
+pub(crate) fn redraw_on_write<L>(cx: &mut Context, buffer: L)
+where
+    L: Lens,
+    L::Target: SharedRead + 'static,
+    <L::Target as SharedRead>::Value: VisualizerBuffer<f32>,
+{
+    Binding::new(cx, buffer.revision(), |cx, _| cx.needs_redraw());
+}
+
+/// Draws a view, restricted to its own bounds and the active clip region.
+///
+/// Without this, paths that extend past the view - e.g. a zoomed in oscilloscope,
+/// or values outside of the range - would be drawn on top of neighbouring views, or
+/// outside of a scroll view. Nothing is drawn if the view is clipped entirely, or if
+/// its bounds are degenerate - e.g. while an editor is laid out without a window.
+pub(crate) fn draw_clipped(
+    cx: &mut DrawContext,
+    canvas: &mut Canvas,
+    draw: impl FnOnce(&mut DrawContext, &mut Canvas),
+) {
+    let Some(clip) = clip_rect(cx.bounds(), cx.clip_region()) else {
+        return;
+    };
+
+    canvas.save();
+    canvas.intersect_scissor(clip.x, clip.y, clip.w, clip.h);
+    draw(cx, canvas);
+    canvas.restore();
+}
+
+/// Draws how far a buffer is through capturing a take along the bottom of a view,
+/// or nothing if it isn't capturing one; See
+/// [`capture_progress()`](crate::utils::VisualizerBuffer::capture_progress).
+///
+/// The full width is tinted while the buffer is armed, and the captured part is
+/// drawn in the view's font color.
+pub(crate) fn draw_capture_progress(
+    cx: &mut DrawContext,
+    frame: &mut Frame,
+    progress: Option<f32>,
+) {
+    let Some(progress) = progress else {
+        return;
+    };
+    let bounds = cx.bounds();
+    let height = 2. * cx.scale_factor();
+    let top = bounds.y + bounds.h - height;
+
+    let mut color: vg::Color = cx.font_color().into();
+    let mut track = vg::Path::new();
+    track.rect(bounds.x, top, bounds.w, height);
+    color.a *= 0.25;
+    frame.fill_path(&track, &vg::Paint::color(color));
+
+    let mut bar = vg::Path::new();
+    bar.rect(bounds.x, top, bounds.w * progress.clamp(0., 1.), height);
+    frame.fill_path(&bar, &vg::Paint::color(cx.font_color().into()));
+}
+
+/// Returns the intersection of a view's bounds and the clip region, or `None` if
+/// they don't overlap or either of them isn't a finite, non-empty area.
+fn clip_rect(bounds: BoundingBox, clip: BoundingBox) -> Option<BoundingBox> {
+    if !usable(bounds) || !usable(clip) {
+        return None;
+    }
+
+    let left = bounds.x.max(clip.x);
+    let top = bounds.y.max(clip.y);
+    let right = (bounds.x + bounds.w).min(clip.x + clip.w);
+    let bottom = (bounds.y + bounds.h).min(clip.y + clip.h);
+
+    (right > left && bottom > top).then_some(BoundingBox {
+        x: left,
+        y: top,
+        w: right - left,
+        h: bottom - top,
+    })
+}
+
+// `f32::max()` and `f32::min()` ignore NaNs, so they need to be caught up front
+fn usable(rect: BoundingBox) -> bool {
+    [rect.x, rect.y, rect.w, rect.h]
+        .iter()
+        .all(|v| v.is_finite())
+        && rect.w > 0.
+        && rect.h > 0.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::clip_rect;
+    use nih_plug_vizia::vizia::prelude::BoundingBox;
+
+    fn rect(x: f32, y: f32, w: f32, h: f32) -> BoundingBox {
+        BoundingBox { x, y, w, h }
+    }
+
+    #[test]
+    fn clipping() {
+        // Scrolled halfway out of the top of a scroll view
+        assert_eq!(
+            clip_rect(rect(0., -50., 100., 100.), rect(0., 0., 200., 300.)),
+            Some(rect(0., 0., 100., 50.))
+        );
+        // Entirely inside
+        assert_eq!(
+            clip_rect(rect(10., 10., 50., 50.), rect(0., 0., 200., 300.)),
+            Some(rect(10., 10., 50., 50.))
+        );
+        // Scrolled out of view, or just touching the edge
+        assert_eq!(
+            clip_rect(rect(0., 300., 100., 100.), rect(0., 0., 200., 300.)),
+            None
+        );
+        assert_eq!(
+            clip_rect(rect(-100., 0., 100., 100.), rect(0., 0., 200., 300.)),
+            None
+        );
+    }
+
+    #[test]
+    fn degenerate_bounds() {
+        let clip = rect(0., 0., 200., 300.);
+        assert_eq!(clip_rect(rect(10., 10., 0., 50.), clip), None);
+        assert_eq!(clip_rect(rect(10., 10., 50., -5.), clip), None);
+        assert_eq!(clip_rect(rect(f32::NAN, 10., 50., 50.), clip), None);
+        assert_eq!(clip_rect(rect(10., 10., f32::INFINITY, 50.), clip), None);
+        assert_eq!(
+            clip_rect(rect(10., 10., 50., 50.), rect(0., 0., f32::NAN, 300.)),
+            None
+        );
+    }
+}
@@ -1,9 +1,25 @@
-use std::sync::{Arc, Mutex};
+use std::cell::RefCell;
+use std::ops::Range;
+use std::time::{Duration, Instant};
 
 use nih_plug_vizia::vizia::{prelude::*, vg};
 
-use super::RangeModifiers;
-use crate::utils::{ValueScaling, VisualizerBuffer, WaveformBuffer};
+use super::clear_fade::{ghost_alpha, sink, ClearFade, ClearFadeModifiers};
+use super::elements;
+use super::liveness::build_stale_indicator;
+use super::padding::InnerPadding;
+use super::plot_area::PlotArea;
+use super::quality::{coarsen_pairs, draw_measured};
+use super::time_cursor::{cursor_age, cursor_time, draw_time_cursor, CursorLink};
+use super::{
+    draw_capture_progress, draw_throttled, redraw_on_write, refill, AdaptiveQualityModifiers,
+    Degradation, Frame, FrameStats, FrameThrottle, PaddingModifiers, RangeModifiers,
+    StaleIndicatorModifiers, ThrottleModifiers, TimeCursor, TimeCursorModifiers,
+};
+use crate::geometry::waveform_points;
+use crate::utils::{
+    Heartbeat, NormalizedRead, SharedRead, ValueScaling, VisualizerBuffer, WaveformBuffer,
+};
 
 /// Waveform display for real-time input.
 ///
@@ -25,30 +41,113 @@ use crate::utils::{ValueScaling, VisualizerBuffer, WaveformBuffer};
 /// .background_color(Color::rgba(255, 255, 255, 120));
 /// ```
 ///
+///
+/// # Styling
+///
+/// Stylesheets select it as `cyma-oscilloscope`. Its `color` is the outline of the
+/// waveform, and its `background-color` its fill.
 pub struct Oscilloscope<B>
 where
-    B: Lens<Target = Arc<Mutex<WaveformBuffer>>>,
+    B: Lens,
+    B::Target: SharedRead<Value = WaveformBuffer>,
 {
     buffer: B,
     range: (f32, f32),
     scaling: ValueScaling,
+    smooth_scrolling: bool,
+    style: WaveformStyle,
+    color_mode: ColorMode,
+    throttle: FrameThrottle,
+    // The position of the scrubbed window within the history, if not live
+    view_position: Option<f32>,
+    // The width of the scrubbed window in seconds, or a quarter of the history
+    view_window: Option<f32>,
+    // The cursor that is shared with other views, if linked
+    time_cursor: Option<CursorLink>,
+    // Reused from one frame to the next, so that drawing doesn't allocate them
+    points: RefCell<Vec<(f32, (f32, f32))>>,
+    segments: RefCell<Vec<Range<usize>>>,
+    slice: RefCell<Vec<(f32, f32)>>,
+    split: RefCell<Vec<(f32, (f32, f32))>>,
+    stops: RefCell<Vec<(f32, vg::Color)>>,
+    // Fades out the previous points after the buffer is cleared, if enabled
+    clear_fade: Option<RefCell<ClearFade<(f32, (f32, f32))>>>,
+    padding: InnerPadding,
+    quality: Option<FrameStats>,
+}
+
+/// Determines how the [`Oscilloscope`] draws the waveform.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum WaveformStyle {
+    /// Fills a single shape between the minima and the maxima with the background
+    /// color, and strokes its outline with the color.
+    ///
+    /// Whenever a minimum lies above its maximum - e.g. after the buffer was
+    /// cleared, or when the history contains NaN values - the shape intersects
+    /// itself, which shows up as artifacts in the fill.
+    #[default]
+    Filled,
+    /// Strokes the minima and the maxima as two separate lines with the color, without
+    /// a fill.
+    Outline,
+    /// Fills the waveform like [`Filled`](Self::Filled), but swaps inverted pairs and
+    /// leaves out pairs that aren't finite, so the fill never intersects itself.
+    ///
+    /// This is the recommended style for buffers that may contain inconsistent
+    /// pairs.
+    FilledSafe,
+}
+
+/// Determines how the [`Oscilloscope`] colors the fill of the waveform.
+///
+/// The outline is always stroked with the view's color, and the
+/// [`Outline`](WaveformStyle::Outline) style, which has no fill, isn't colored.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum ColorMode {
+    /// Fills the waveform with the view's background color.
+    #[default]
+    Uniform,
+    /// Fills the excursions above and below the center line with separate colors,
+    /// e.g. to check asymmetric waveforms like kick drums.
+    ///
+    /// Elements that straddle the center line are split at it. A color that is
+    /// `None` falls back to the view's styling: The positive side takes the
+    /// `background-color`, and the negative side takes the `color` at the
+    /// background color's opacity.
+    Polarity {
+        positive: Option<Color>,
+        negative: Option<Color>,
+    },
+    /// Fills the waveform with a vertical gradient, keyed on the absolute amplitude,
+    /// e.g. grey when quiet and an accent color when loud.
+    ///
+    /// Each stop is an amplitude, in the units of the oscilloscope's range, along
+    /// with its color; It's mirrored around the center line. Without stops, this
+    /// falls back to the view's `background-color`.
+    Level(Vec<(f32, Color)>),
 }
 
 enum OscilloscopeEvents {
     UpdateRange((f32, f32)),
     UpdateScaling(ValueScaling),
+    UpdateViewPosition(f32),
 }
 
+// The height of the minimap strip underneath a scrubbed waveform, in logical pixels
+const MINIMAP_HEIGHT: f32 = 6.;
+
 impl<B> Oscilloscope<B>
 where
-    B: Lens<Target = Arc<Mutex<WaveformBuffer>>>,
+    B: Lens,
+    B::Target: SharedRead<Value = WaveformBuffer>,
 {
     /// Creates a new Oscilloscope.
     ///
     /// Takes in a `buffer`, which should be used to store the peak values. You
     /// need to write to it inside your plugin code, thread-safely send it to
     /// the editor thread, and then pass it into this oscilloscope. Which is
-    /// also why it is behind an `Arc<Mutex>`.
+    /// also why it is behind an `Arc<Mutex>` or an `Arc<RwLock>`; See
+    /// [`SharedRead`].
     pub fn new(
         cx: &mut Context,
         buffer: B,
@@ -56,11 +155,26 @@ where
         scaling: impl Res<ValueScaling>,
     ) -> Handle<Self> {
         Self {
-            buffer,
+            buffer: buffer.clone(),
             range: range.get_val(cx),
             scaling: scaling.get_val(cx),
+            smooth_scrolling: false,
+            style: WaveformStyle::default(),
+            color_mode: ColorMode::default(),
+            throttle: FrameThrottle::default(),
+            view_position: None,
+            view_window: None,
+            time_cursor: None,
+            points: RefCell::new(Vec::new()),
+            segments: RefCell::new(Vec::new()),
+            slice: RefCell::new(Vec::new()),
+            split: RefCell::new(Vec::new()),
+            stops: RefCell::new(Vec::new()),
+            clear_fade: None,
+            padding: InnerPadding::default(),
+            quality: None,
         }
-        .build(cx, |_| {})
+        .build(cx, |cx| redraw_on_write(cx, buffer))
         .range(range)
         .scaling(scaling)
     }
@@ -68,78 +182,425 @@ where
 
 impl<B> View for Oscilloscope<B>
 where
-    B: Lens<Target = Arc<Mutex<WaveformBuffer>>>,
+    B: Lens,
+    B::Target: SharedRead<Value = WaveformBuffer>,
 {
     fn element(&self) -> Option<&'static str> {
-        Some("oscilloscope")
+        Some(elements::OSCILLOSCOPE)
     }
     fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
-        let bounds = cx.bounds();
+        draw_measured(self.quality.as_ref(), || self.draw_waveform(cx, canvas));
+    }
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|e, _| match e {
+            OscilloscopeEvents::UpdateRange(v) => self.range = *v,
+            OscilloscopeEvents::UpdateScaling(v) => self.scaling = *v,
+            OscilloscopeEvents::UpdateViewPosition(v) => self.view_position = Some(*v),
+        });
 
-        let x = bounds.x;
-        let y = bounds.y;
-        let w = bounds.w;
-        let h = bounds.h;
+        if let Some(link) = &self.time_cursor {
+            event.map(|window_event, _| match window_event {
+                WindowEvent::MouseMove(cursor_x, _) => {
+                    let bounds = PlotArea::new(cx.bounds(), self.padding, cx.scale_factor()).rect;
+                    let time = (cursor_x - bounds.x) / bounds.w;
+                    let duration = self.buffer.get(cx).read_shared(|_| {}).duration();
+                    link.hover(duration.and_then(|duration| cursor_age(time, self.span(duration))));
+                }
+                WindowEvent::MouseLeave => link.leave(),
+                _ => {}
+            });
+        }
+    }
+}
 
-        let mut fill = vg::Path::new();
+impl<B> Oscilloscope<B>
+where
+    B: Lens,
+    B::Target: SharedRead<Value = WaveformBuffer>,
+{
+    fn draw_waveform(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        draw_throttled(cx, canvas, &self.throttle, |cx, canvas| {
+            let line_width = cx.scale_factor();
+            let bounds = PlotArea::new(cx.bounds(), self.padding, cx.scale_factor()).rect;
 
-        let binding = self.buffer.get(cx);
-        let ring_buf = &(binding.lock().unwrap());
+            let x = bounds.x;
+            let y = bounds.y;
+            let w = bounds.w;
+            let h = bounds.h;
 
-        let width_delta = w / ring_buf.len() as f32;
+            let binding = self.buffer.get(cx);
+            let ring_buf = binding.read_shared(|buffer| buffer.refresh());
+            let values = NormalizedRead::new(&*ring_buf, self.range, self.scaling);
 
-        // Local minima (bottom part of waveform)
-        let mut py = self
-            .scaling
-            .value_to_normalized(ring_buf[0].0, self.range.0, self.range.1);
-        fill.move_to(x, y + h * (1. - py) + 1.);
-        for i in 1..ring_buf.len() {
-            py = self
-                .scaling
-                .value_to_normalized(ring_buf[i].0, self.range.0, self.range.1);
+            let len = values.pair_len();
+            if len == 0 {
+                return;
+            }
 
-            fill.line_to(x + width_delta * i as f32, y + h * (1. - py) + 1.);
-        }
+            // The points of the previous frame, if the buffer was cleared since then,
+            // are kept to fade them out
+            let now = Instant::now();
+            let mut points = self.points.borrow_mut();
+            let clear_fade = self.clear_fade.as_ref().map(|fade| {
+                let mut fade = fade.borrow_mut();
+                fade.observe(now, ring_buf.clears(), &mut points);
+                fade
+            });
 
-        let bottom_stroke = fill.clone();
-        let mut top_stroke = vg::Path::new();
+            let duration = ring_buf.duration().unwrap_or(0.);
+            let (h, right) = match self.view_position {
+                // The window around the scrubbed position, with the minimap underneath
+                Some(_) => {
+                    let (newest, oldest) = self.span(duration);
+                    if oldest <= newest {
+                        return;
+                    }
 
-        // Local maxima (top part of waveform)
-        py = self.scaling.value_to_normalized(
-            ring_buf[ring_buf.len() - 1].1,
-            self.range.0,
-            self.range.1,
-        );
-        fill.line_to(x + w, y + h * (1. - py) + 1.);
-        top_stroke.move_to(x + w, y + h * (1. - py) + 1.);
-        for i in 1..ring_buf.len() {
-            py = self.scaling.value_to_normalized(
-                ring_buf[ring_buf.len() - i].1,
-                self.range.0,
-                self.range.1,
-            );
-
-            fill.line_to(x + w - width_delta * i as f32, y + h * (1. - py) + 1.);
-            top_stroke.line_to(x + w - width_delta * i as f32, y + h * (1. - py) + 1.);
+                    let mut slice = self.slice.borrow_mut();
+                    ring_buf.slice_seconds(newest, oldest, &mut slice);
+                    let width_delta = w * duration / (len as f32 * (oldest - newest));
+                    scrub_points(&mut points, &values, &slice, x + w, width_delta);
+
+                    let minimap = MINIMAP_HEIGHT * cx.scale_factor();
+                    draw_minimap(
+                        cx,
+                        canvas,
+                        (x, y + h - minimap, w, minimap),
+                        (newest / duration, oldest / duration),
+                    );
+                    (h - minimap, x + w)
+                }
+                None => {
+                    let width_delta = w / len as f32;
+
+                    // While smooth scrolling, the element that is being accumulated is
+                    // drawn as an extra pair, and the waveform is shifted by its progress
+                    let partial = if self.smooth_scrolling {
+                        ring_buf.partial()
+                    } else {
+                        None
+                    };
+                    let shift = partial.map_or(0., |(progress, _)| width_delta * progress);
+                    let x = x - shift;
+
+                    // Each pair's x position and its normalized values
+                    waveform_points(&mut points, &values, partial, x, width_delta);
+                    (h, x + width_delta * points.len() as f32)
+                }
+            };
+            if self
+                .quality
+                .as_ref()
+                .is_some_and(|stats| stats.is_degraded(Degradation::CoarseDecimation))
+            {
+                coarsen_pairs(&mut points);
+            }
+            let len = points.len();
+            if len == 0 {
+                return;
+            }
+
+            let py = |v: f32| y + h * (1. - v);
+            // Like a graph's, the waveform's outline takes the view's color, and its fill
+            // the view's background color
+            let stroke_paint = vg::Paint::color(cx.font_color().into()).with_line_width(line_width);
+            let background: vg::Color = cx.background_color().into();
+
+            // Adds the shape between the minima and the maxima of a run of pairs
+            let filled = |path: &mut vg::Path, run: &[(f32, (f32, f32))]| {
+                // Local minima (bottom part of waveform)
+                path.move_to(run[0].0, py(run[0].1 .0));
+                for (px, (min, _)) in run.iter().skip(1) {
+                    path.line_to(*px, py(*min));
+                }
+
+                // Local maxima (top part of waveform)
+                path.line_to(right, py(run[run.len() - 1].1 .1));
+                for (px, (_, max)) in run.iter().skip(1).rev() {
+                    path.line_to(*px, py(*max));
+                }
+                path.close();
+            };
+            let filled_safe = |path: &mut vg::Path, run: &[(f32, (f32, f32))]| {
+                path.move_to(run[0].0, py(run[0].1 .0));
+                for (px, (min, _)) in run.iter().skip(1) {
+                    path.line_to(*px, py(*min));
+                }
+                for (px, (_, max)) in run.iter().rev() {
+                    path.line_to(*px, py(*max));
+                }
+                path.close();
+            };
+
+            // The waveform from before a clear collapses onto the center line while it
+            // fades, underneath everything that arrived since
+            if let Some((ghost, progress)) = clear_fade.as_ref().and_then(|fade| fade.ghost(now)) {
+                let center = values.normalize_value(0.);
+                let sunk = |v: f32| py(sink(v, center, progress));
+                let mut ghost_path = vg::Path::new();
+                for (i, (px, (min, _))) in ghost.iter().enumerate() {
+                    if i == 0 {
+                        ghost_path.move_to(*px, sunk(*min));
+                    } else {
+                        ghost_path.line_to(*px, sunk(*min));
+                    }
+                }
+                for (px, (_, max)) in ghost.iter().rev() {
+                    ghost_path.line_to(*px, sunk(*max));
+                }
+                ghost_path.close();
+
+                let alpha = ghost_alpha(progress);
+                let mut fill = background;
+                fill.a *= alpha;
+                let mut stroke: vg::Color = cx.font_color().into();
+                stroke.a *= alpha;
+                if self.style != WaveformStyle::Outline {
+                    canvas.fill_path(&ghost_path, &vg::Paint::color(fill));
+                }
+                canvas.stroke_path(
+                    &ghost_path,
+                    &vg::Paint::color(stroke).with_line_width(line_width),
+                );
+            }
+
+            let mut segments = self.segments.borrow_mut();
+            let shape: &dyn Fn(&mut vg::Path, &[(f32, (f32, f32))]) = match self.style {
+                WaveformStyle::Filled => {
+                    segments.clear();
+                    segments.push(0..len);
+                    &filled
+                }
+                WaveformStyle::FilledSafe => {
+                    sanitize_pairs(&mut points, &mut segments);
+                    &filled_safe
+                }
+                WaveformStyle::Outline => {
+                    let mut stroke = vg::Path::new();
+                    for trace in [0, 1] {
+                        for (i, (px, pair)) in points.iter().enumerate() {
+                            let v = if trace == 0 { pair.0 } else { pair.1 };
+                            if i == 0 {
+                                stroke.move_to(*px, py(v));
+                            } else {
+                                stroke.line_to(*px, py(v));
+                            }
+                        }
+                    }
+                    canvas.stroke_path(&stroke, &stroke_paint);
+                    self.draw_cursor(cx, canvas, duration, (x, y, w, h));
+                    draw_capture_progress(cx, canvas, ring_buf.capture_progress());
+                    return;
+                }
+            };
+
+            let mut fill = vg::Path::new();
+            for segment in segments.iter() {
+                shape(&mut fill, &points[segment.clone()]);
+            }
+            match &self.color_mode {
+                ColorMode::Uniform => canvas.fill_path(&fill, &vg::Paint::color(background)),
+                ColorMode::Polarity { positive, negative } => {
+                    let zero = values.normalize_value(0.);
+                    let mut negative_fallback: vg::Color = cx.font_color().into();
+                    negative_fallback.a = background.a;
+                    let mut split = self.split.borrow_mut();
+                    for (above, color) in [
+                        (true, positive.map_or(background, Into::into)),
+                        (false, negative.map_or(negative_fallback, Into::into)),
+                    ] {
+                        let mut side = vg::Path::new();
+                        for segment in segments.iter() {
+                            polarity_points(&points[segment.clone()], zero, above, &mut split);
+                            shape(&mut side, &split);
+                        }
+                        canvas.fill_path(&side, &vg::Paint::color(color));
+                    }
+                }
+                ColorMode::Level(stops) => {
+                    let mut positions = self.stops.borrow_mut();
+                    level_stops(&mut positions, stops, |v| py(values.normalize_value(v)));
+                    let paint = match (positions.first().copied(), positions.last().copied()) {
+                        (Some((top, _)), Some((bottom, _))) if bottom > top => {
+                            // The positions are turned into offsets in place
+                            for (y, _) in positions.iter_mut() {
+                                *y = (*y - top) / (bottom - top);
+                            }
+                            vg::Paint::linear_gradient_stops(x, top, x, bottom, &positions)
+                        }
+                        (Some((_, color)), _) => vg::Paint::color(color),
+                        _ => vg::Paint::color(background),
+                    };
+                    canvas.fill_path(&fill, &paint);
+                }
+            }
+            canvas.stroke_path(&fill, &stroke_paint);
+
+            self.draw_cursor(cx, canvas, duration, (x, y, w, h));
+            draw_capture_progress(cx, canvas, ring_buf.capture_progress());
+        });
+    }
+
+    /// Returns the newest and the oldest age (in seconds) that are shown, given the
+    /// buffer's duration.
+    fn span(self: &Self, duration: f32) -> (f32, f32) {
+        match self.view_position {
+            Some(position) => {
+                let window = self.view_window.unwrap_or(duration / 4.);
+                scrub_window(position, window, duration)
+            }
+            None => (0., duration),
         }
+    }
 
-        fill.close();
-        canvas.fill_path(
-            &fill,
-            &vg::Paint::color(cx.font_color().into()).with_line_width(0.),
-        );
+    /// Draws the shared cursor across the waveform, while another linked view is
+    /// hovered.
+    fn draw_cursor(
+        self: &Self,
+        cx: &mut DrawContext,
+        frame: &mut Frame,
+        duration: f32,
+        (x, y, w, h): (f32, f32, f32, f32),
+    ) {
+        let cursor = self
+            .time_cursor
+            .as_ref()
+            .and_then(|link| link.age())
+            .and_then(|age| cursor_time(age, self.span(duration)));
+        if let Some(time) = cursor {
+            draw_time_cursor(cx, frame, (x + w * time, y), (x + w * time, y + h));
+        }
     }
-    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
-        event.map(|e, _| match e {
-            OscilloscopeEvents::UpdateRange(v) => self.range = *v,
-            OscilloscopeEvents::UpdateScaling(v) => self.scaling = *v,
+}
+
+pub trait OscilloscopeModifiers {
+    /// Scrolls the waveform smoothly in between the buffer's elements.
+    ///
+    /// The element that is currently being accumulated is drawn at the right edge,
+    /// and the waveform is shifted by its progress, instead of stepping sideways
+    /// whenever an element is finished.
+    fn smooth_scrolling(self, smooth_scrolling: bool) -> Self;
+
+    /// Sets how the waveform is drawn; See [`WaveformStyle`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Oscilloscope::new(cx, Data::oscilloscope_buffer, (-1.2, 1.2), ValueScaling::Linear)
+    ///     .style(WaveformStyle::FilledSafe)
+    ///     .color(Color::rgba(0, 0, 0, 0))
+    ///     .background_color(Color::rgba(255, 255, 255, 120));
+    /// ```
+    fn style(self, style: WaveformStyle) -> Self;
+
+    /// Sets how the fill of the waveform is colored; See [`ColorMode`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Oscilloscope::new(cx, Data::oscilloscope_buffer, (-1.2, 1.2), ValueScaling::Linear)
+    ///     .color_mode(ColorMode::Polarity {
+    ///         positive: Some(Color::rgba(80, 200, 120, 160)),
+    ///         negative: Some(Color::rgba(230, 120, 80, 160)),
+    ///     });
+    ///
+    /// Oscilloscope::new(cx, Data::oscilloscope_buffer, (-1.2, 1.2), ValueScaling::Linear)
+    ///     .color_mode(ColorMode::Level(vec![
+    ///         (0.0, Color::rgba(160, 160, 160, 120)),
+    ///         (1.0, Color::rgba(255, 140, 40, 200)),
+    ///     ]));
+    /// ```
+    fn color_mode(self, color_mode: ColorMode) -> Self;
+
+    /// Shows a window of the retained history around a position, instead of the live
+    /// edge.
+    ///
+    /// The position ranges from 0 (the newest audio) to 1 (the oldest audio in the
+    /// buffer), and is usually bound to a parameter. The window is centered
+    /// `position * duration` seconds in the past, and stays inside of the history near
+    /// its edges. A minimap strip underneath the waveform shows where the window lies
+    /// within the history.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Oscilloscope::new(cx, Data::oscilloscope_buffer, (-1.2, 1.2), ValueScaling::Linear)
+    ///     .view_position(Data::params.map(|p| p.scrub.value()))
+    ///     .view_window(0.5);
+    /// ```
+    fn view_position(self, position: impl Res<f32>) -> Self;
+
+    /// Sets the width (in seconds) of the window that is shown around the
+    /// [`view_position()`](Self::view_position).
+    ///
+    /// By default, the window spans a quarter of the buffer's duration.
+    fn view_window(self, seconds: f32) -> Self;
+}
+
+impl<'a, B> OscilloscopeModifiers for Handle<'a, Oscilloscope<B>>
+where
+    B: Lens,
+    B::Target: SharedRead<Value = WaveformBuffer>,
+{
+    fn smooth_scrolling(self, smooth_scrolling: bool) -> Self {
+        self.modify(|oscilloscope| oscilloscope.smooth_scrolling = smooth_scrolling)
+    }
+    fn style(self, style: WaveformStyle) -> Self {
+        self.modify(|oscilloscope| oscilloscope.style = style)
+    }
+    fn color_mode(self, color_mode: ColorMode) -> Self {
+        self.modify(|oscilloscope| oscilloscope.color_mode = color_mode)
+    }
+    fn view_position(mut self, position: impl Res<f32>) -> Self {
+        let e = self.entity();
+
+        position.set_or_bind(self.context(), e, move |cx, p| {
+            (*cx).emit_to(e, OscilloscopeEvents::UpdateViewPosition(p));
         });
+
+        self
+    }
+    fn view_window(self, seconds: f32) -> Self {
+        self.modify(|oscilloscope| oscilloscope.view_window = Some(seconds))
+    }
+}
+
+impl<'a, B> TimeCursorModifiers for Handle<'a, Oscilloscope<B>>
+where
+    B: Lens,
+    B::Target: SharedRead<Value = WaveformBuffer>,
+{
+    fn time_cursor<C: Lens<Target = TimeCursor>>(mut self, cursor: C) -> Self {
+        let link = CursorLink::new(&mut self, cursor);
+        self.modify(|oscilloscope| oscilloscope.time_cursor = Some(link))
+    }
+}
+
+impl<'a, B> ClearFadeModifiers for Handle<'a, Oscilloscope<B>>
+where
+    B: Lens,
+    B::Target: SharedRead<Value = WaveformBuffer>,
+{
+    fn fade_clears(self, fade: bool) -> Self {
+        self.modify(|oscilloscope| {
+            oscilloscope.clear_fade = fade.then(|| RefCell::new(ClearFade::default()))
+        })
+    }
+    fn clear_fade_duration(self, duration: Duration) -> Self {
+        self.modify(|oscilloscope| {
+            oscilloscope
+                .clear_fade
+                .get_or_insert_with(|| RefCell::new(ClearFade::default()))
+                .get_mut()
+                .set_duration(duration)
+        })
     }
 }
 
 impl<'a, B> RangeModifiers for Handle<'a, Oscilloscope<B>>
 where
-    B: Lens<Target = Arc<Mutex<WaveformBuffer>>>,
+    B: Lens,
+    B::Target: SharedRead<Value = WaveformBuffer>,
 {
     fn range(mut self, range: impl Res<(f32, f32)>) -> Self {
         let e = self.entity();
@@ -160,3 +621,384 @@ where
         self
     }
 }
+
+/// Fills `points` with the pairs of a slice of the history, so that the newest pair
+/// ends at the right edge.
+fn scrub_points(
+    points: &mut Vec<(f32, (f32, f32))>,
+    values: &NormalizedRead<WaveformBuffer>,
+    slice: &[(f32, f32)],
+    right: f32,
+    width_delta: f32,
+) {
+    let left = right - width_delta * slice.len() as f32;
+    refill(
+        points,
+        slice.iter().enumerate().map(|(i, (min, max))| {
+            (
+                left + width_delta * i as f32,
+                (values.normalize_value(*min), values.normalize_value(*max)),
+            )
+        }),
+    );
+}
+
+/// Returns the span of the scrubbed window, as its newest and oldest point in
+/// seconds in the past.
+///
+/// The window is centered `position * duration` seconds in the past, and moved
+/// inwards near the edges of the history.
+fn scrub_window(position: f32, window: f32, duration: f32) -> (f32, f32) {
+    let duration = duration.max(0.);
+    let window = window.clamp(0., duration);
+    let center = position.clamp(0., 1.) * duration;
+    let newest = (center - window / 2.).clamp(0., duration - window);
+    (newest, newest + window)
+}
+
+/// Draws the strip that shows where the scrubbed window lies within the history,
+/// with the newest audio on the right.
+///
+/// * `span` - The newest and oldest point of the window, as fractions of the history
+fn draw_minimap(
+    cx: &mut DrawContext,
+    frame: &mut Frame,
+    (x, y, w, h): (f32, f32, f32, f32),
+    span: (f32, f32),
+) {
+    let mut color: vg::Color = cx.font_color().into();
+    let mut history = vg::Path::new();
+    history.rect(x, y, w, h);
+    color.a *= 0.25;
+    frame.fill_path(&history, &vg::Paint::color(color));
+
+    let mut window = vg::Path::new();
+    window.rect(x + w * (1. - span.1), y, w * (span.1 - span.0), h);
+    frame.fill_path(&window, &vg::Paint::color(cx.font_color().into()));
+}
+
+/// Swaps the minimum and maximum of inverted pairs, and fills `segments` with the
+/// runs of pairs that are finite.
+///
+/// Each run can be filled as a single shape without intersecting itself.
+fn sanitize_pairs(points: &mut [(f32, (f32, f32))], segments: &mut Vec<Range<usize>>) {
+    segments.clear();
+    let mut start = None;
+
+    for (i, (_, (min, max))) in points.iter_mut().enumerate() {
+        if min.is_finite() && max.is_finite() {
+            if min > max {
+                std::mem::swap(min, max);
+            }
+            start.get_or_insert(i);
+        } else if let Some(start) = start.take() {
+            segments.push(start..i);
+        }
+    }
+    if let Some(start) = start {
+        segments.push(start..points.len());
+    }
+}
+
+/// Fills `split` with the part of a run of pairs that lies on one side of the zero
+/// line, so that its minima and maxima are clamped to the zero line.
+///
+/// Wherever the minima or the maxima cross the zero line in between two pairs, a
+/// pair is inserted at the crossing, so that an element that straddles the zero line
+/// is split at it, and the two sides meet exactly.
+fn polarity_points(
+    points: &[(f32, (f32, f32))],
+    zero: f32,
+    above: bool,
+    split: &mut Vec<(f32, (f32, f32))>,
+) {
+    let clamp = |v: f32| if above { v.max(zero) } else { v.min(zero) };
+    let crossing = |from: f32, to: f32| {
+        ((from - zero) * (to - zero) < 0.).then(|| (zero - from) / (to - from))
+    };
+
+    split.clear();
+    for (i, &(px, (min, max))) in points.iter().enumerate() {
+        if let Some(&(prev_x, (prev_min, prev_max))) = i.checked_sub(1).map(|i| &points[i]) {
+            let mut crossings = [crossing(prev_min, min), crossing(prev_max, max)];
+            if crossings[0] > crossings[1] {
+                crossings.swap(0, 1);
+            }
+            for t in crossings.into_iter().flatten() {
+                let lerp = |from: f32, to: f32| from + (to - from) * t;
+                split.push((
+                    lerp(prev_x, px),
+                    (clamp(lerp(prev_min, min)), clamp(lerp(prev_max, max))),
+                ));
+            }
+        }
+        split.push((px, (clamp(min), clamp(max))));
+    }
+}
+
+/// Fills `positions` with the gradient stops for a [`ColorMode::Level`], as their y
+/// positions from the top down, along with their colors.
+///
+/// Each stop is mirrored around the zero line; `y` maps a value to its y position.
+/// Of the stops that end up at the same position, the first one is kept.
+fn level_stops(
+    positions: &mut Vec<(f32, vg::Color)>,
+    stops: &[(f32, Color)],
+    y: impl Fn(f32) -> f32,
+) {
+    positions.clear();
+    let mirrored = stops.iter().flat_map(|(amplitude, color)| {
+        let amplitude = amplitude.abs();
+        [(y(amplitude), *color), (y(-amplitude), *color)]
+    });
+    // Inserted in order, since sorting could allocate - there are only a few stops
+    for (y, color) in mirrored.filter(|(y, _)| y.is_finite()) {
+        let index = positions.partition_point(|(other, _)| *other < y);
+        if positions.get(index).is_none_or(|(other, _)| *other != y) {
+            positions.insert(index, (y, color.into()));
+        }
+    }
+}
+
+impl<'a, B> PaddingModifiers for Handle<'a, Oscilloscope<B>>
+where
+    B: Lens,
+    B::Target: SharedRead<Value = WaveformBuffer>,
+{
+    fn inner_padding(self, top: f32, right: f32, bottom: f32, left: f32) -> Self {
+        self.modify(|oscilloscope| {
+            oscilloscope.padding = InnerPadding::new(top, right, bottom, left)
+        })
+    }
+    fn uniform_inner_padding(self, padding: f32) -> Self {
+        self.modify(|oscilloscope| oscilloscope.padding = InnerPadding::uniform(padding))
+    }
+}
+
+impl<'a, B> ThrottleModifiers for Handle<'a, Oscilloscope<B>>
+where
+    B: Lens,
+    B::Target: SharedRead<Value = WaveformBuffer>,
+{
+    fn max_fps(self, max_fps: f32) -> Self {
+        self.modify(|view| view.throttle.set_max_fps(max_fps))
+    }
+}
+
+impl<'a, B> AdaptiveQualityModifiers for Handle<'a, Oscilloscope<B>>
+where
+    B: Lens,
+    B::Target: SharedRead<Value = WaveformBuffer>,
+{
+    fn adaptive_quality(self, stats: &FrameStats) -> Self {
+        let stats = stats.clone();
+        self.modify(|view| view.quality = Some(stats))
+    }
+}
+
+impl<'a, B> StaleIndicatorModifiers for Handle<'a, Oscilloscope<B>>
+where
+    B: Lens,
+    B::Target: SharedRead<Value = WaveformBuffer>,
+{
+    fn stale_indicator(mut self, heartbeat: Heartbeat, threshold: Duration) -> Self {
+        let entity = self.entity();
+        build_stale_indicator(self.context(), entity, heartbeat, threshold);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{level_stops, polarity_points, sanitize_pairs, scrub_points, scrub_window};
+    use crate::alloc_counter::allocations;
+    use crate::geometry::waveform_points;
+    use crate::utils::{NormalizedRead, ValueScaling, VisualizerBuffer, WaveformBuffer};
+    use crate::visualizers::quality::coarsen_pairs;
+    use nih_plug_vizia::vizia::{prelude::Color, vg};
+    use std::ops::Range;
+
+    fn segments(points: &mut [(f32, (f32, f32))]) -> Vec<Range<usize>> {
+        let mut segments = Vec::new();
+        sanitize_pairs(points, &mut segments);
+        segments
+    }
+
+    fn points(pairs: &[(f32, f32)]) -> Vec<(f32, (f32, f32))> {
+        pairs
+            .iter()
+            .enumerate()
+            .map(|(i, pair)| (i as f32, *pair))
+            .collect()
+    }
+
+    #[test]
+    fn swaps_inverted_pairs() {
+        let mut points = points(&[(0.2, 0.8), (0.9, 0.1), (0.5, 0.5), (0.7, 0.3)]);
+        assert_eq!(segments(&mut points), vec![0..4]);
+        assert_eq!(
+            points.iter().map(|(_, pair)| *pair).collect::<Vec<_>>(),
+            vec![(0.2, 0.8), (0.1, 0.9), (0.5, 0.5), (0.3, 0.7)]
+        );
+        assert!(points.iter().all(|(_, (min, max))| min <= max));
+    }
+
+    #[test]
+    fn splits_at_invalid_pairs() {
+        let mut points = points(&[
+            (f32::NAN, 0.5),
+            (0.2, 0.8),
+            (0.8, 0.2),
+            (0.1, f32::INFINITY),
+            (f32::NAN, f32::NAN),
+            (0.4, 0.6),
+        ]);
+        assert_eq!(segments(&mut points), vec![1..3, 5..6]);
+        assert_eq!(points[2].1, (0.2, 0.8));
+    }
+
+    #[test]
+    fn no_valid_pairs() {
+        let mut points = points(&[(f32::NAN, f32::NAN), (f32::INFINITY, 0.0)]);
+        assert!(segments(&mut points).is_empty());
+        assert!(segments(&mut []).is_empty());
+    }
+
+    #[test]
+    fn steady_state_doesnt_allocate() {
+        let mut buffer = WaveformBuffer::new(800, 1.0);
+        buffer.set_sample_rate(48000.);
+        for i in 0..48000 {
+            buffer.enqueue((i as f32 * 0.01).sin());
+        }
+
+        let (mut points, mut segments, mut split) = (Vec::new(), Vec::new(), Vec::new());
+        let mut draw = |buffer: &WaveformBuffer| {
+            let values = NormalizedRead::new(buffer, (-1.2, 1.2), ValueScaling::Linear);
+            waveform_points(&mut points, &values, buffer.partial(), 0., 0.5);
+            sanitize_pairs(&mut points, &mut segments);
+            for above in [true, false] {
+                for segment in segments.iter() {
+                    polarity_points(&points[segment.clone()], 0.5, above, &mut split);
+                }
+            }
+            coarsen_pairs(&mut points);
+        };
+        // The first frame grows the scratch vectors to the buffer's length
+        draw(&buffer);
+        for _ in 0..10 {
+            buffer.enqueue(0.5);
+            assert_eq!(allocations(|| draw(&buffer)), 0);
+        }
+
+        // Scrubbing through the history reuses its own scratch vectors
+        let (mut slice, mut points) = (Vec::new(), Vec::new());
+        let mut scrub = |buffer: &WaveformBuffer| {
+            let values = NormalizedRead::new(buffer, (-1.2, 1.2), ValueScaling::Linear);
+            let (newest, oldest) = scrub_window(0.5, 0.25, 1.0);
+            buffer.slice_seconds(newest, oldest, &mut slice);
+            scrub_points(&mut points, &values, &slice, 400., 2.);
+        };
+        scrub(&buffer);
+        for _ in 0..10 {
+            buffer.enqueue(0.5);
+            assert_eq!(allocations(|| scrub(&buffer)), 0);
+        }
+    }
+
+    #[test]
+    fn scrub_window_clamps() {
+        // Centered on the position
+        assert_eq!(scrub_window(0.5, 2., 10.), (4., 6.));
+        // Moved inwards near the live edge and the oldest audio
+        assert_eq!(scrub_window(0., 2., 10.), (0., 2.));
+        assert_eq!(scrub_window(0.05, 2., 10.), (0., 2.));
+        assert_eq!(scrub_window(1., 2., 10.), (8., 10.));
+        assert_eq!(scrub_window(1.5, 2., 10.), (8., 10.));
+        // A window wider than the history shows all of it
+        assert_eq!(scrub_window(0.3, 20., 10.), (0., 10.));
+    }
+
+    #[test]
+    fn scrubbed_points() {
+        // 10 elements of 0.1 seconds
+        let mut buffer = WaveformBuffer::new(10, 1.0);
+        buffer.set_sample_rate(40.);
+        for i in 0..200 {
+            buffer.enqueue((i % 8) as f32 / 8.);
+        }
+
+        let (newest, oldest) = scrub_window(0.5, 0.4, 1.0);
+        let mut slice = Vec::new();
+        buffer.slice_seconds(newest, oldest, &mut slice);
+        assert_eq!(slice, vec![buffer[3], buffer[4], buffer[5], buffer[6]]);
+
+        // The window fills the view, ending at its right edge
+        let values = NormalizedRead::new(&buffer, (0., 1.), ValueScaling::Linear);
+        let mut points = Vec::new();
+        scrub_points(&mut points, &values, &slice, 100., 25.);
+        let xs: Vec<f32> = points.iter().map(|(x, _)| *x).collect();
+        assert_eq!(xs, vec![0., 25., 50., 75.]);
+        assert_eq!(points[0].1, buffer[3]);
+    }
+
+    #[test]
+    fn polarity_splits_at_zero() {
+        // The second pair straddles the zero line, the minima cross it on the way there,
+        // and the maxima cross it on the way to the third pair
+        let points = points(&[(0.75, 1.0), (0.25, 0.75), (0.125, 0.25)]);
+        let mut above = Vec::new();
+        polarity_points(&points, 0.5, true, &mut above);
+        assert_eq!(
+            above,
+            vec![
+                (0., (0.75, 1.0)),
+                (0.5, (0.5, 0.875)),
+                (1., (0.5, 0.75)),
+                (1.5, (0.5, 0.5)),
+                (2., (0.5, 0.5)),
+            ]
+        );
+
+        let mut below = Vec::new();
+        polarity_points(&points, 0.5, false, &mut below);
+        assert_eq!(
+            below,
+            vec![
+                (0., (0.5, 0.5)),
+                (0.5, (0.5, 0.5)),
+                (1., (0.25, 0.5)),
+                (1.5, (0.1875, 0.5)),
+                (2., (0.125, 0.25)),
+            ]
+        );
+    }
+
+    #[test]
+    fn level_stops_are_mirrored() {
+        let grey = Color::rgb(128, 128, 128);
+        let red = Color::rgb(255, 0, 0);
+        // A range of (-1, 1) that's 100 pixels tall
+        let y = |v: f32| 50. - v * 50.;
+        let mut stops = Vec::new();
+        level_stops(&mut stops, &[(1.0, red), (0.0, grey)], y);
+        assert_eq!(
+            stops.iter().map(|(y, _)| *y).collect::<Vec<_>>(),
+            vec![0., 50., 100.]
+        );
+        let colors: Vec<vg::Color> = vec![red.into(), grey.into(), red.into()];
+        assert_eq!(stops.iter().map(|(_, c)| *c).collect::<Vec<_>>(), colors);
+
+        // The first of two stops at the same level is kept
+        let green = Color::rgb(0, 255, 0);
+        level_stops(&mut stops, &[(0.5, green), (0.5, red)], y);
+        let colors: Vec<vg::Color> = vec![green.into(), green.into()];
+        assert_eq!(stops.iter().map(|(_, c)| *c).collect::<Vec<_>>(), colors);
+
+        // Once grown, refilling the stops reuses their allocation
+        let levels = [(1.0, red), (0.5, green), (0.0, grey)];
+        level_stops(&mut stops, &levels, y);
+        assert_eq!(allocations(|| level_stops(&mut stops, &levels, y)), 0);
+        assert_eq!(stops.len(), 5);
+    }
+}
@@ -0,0 +1,205 @@
+use nih_plug_vizia::vizia::prelude::{BoundingBox, Pixels, Stretch, Units};
+use std::sync::{Arc, Mutex};
+
+/// The corner of a view that a readout is anchored to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadoutPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Keeps track of the areas that overlays, like a [`Graph`](super::Graph)'s
+/// readout, cover inside an editor.
+///
+/// Overlays register their area once per frame, and other views can stay clear of
+/// it - a [`UnitRuler`](super::UnitRuler) that
+/// [avoids overlays](super::UnitRulerModifiers::avoid_overlays) hides the labels
+/// that would be drawn below one.
+///
+/// ```
+/// let overlays = OverlayRegistry::new();
+///
+/// ZStack::new(cx, |cx| {
+///     Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+///         .readout(ReadoutPosition::TopRight, 8.0)
+///         .readout_overlay(&overlays);
+///     UnitRuler::new(cx, (-32.0, 8.0), ValueScaling::Linear, labels, Orientation::Vertical)
+///         .avoid_overlays(overlays.clone())
+///         .left(Stretch(1.0))
+///         .width(Pixels(32.));
+/// });
+/// ```
+///
+/// The registry is cheap to clone, and all clones share the same areas.
+#[derive(Clone, Default)]
+pub struct OverlayRegistry {
+    rects: Arc<Mutex<Vec<Option<BoundingBox>>>>,
+}
+
+/// An overlay's entry in an [`OverlayRegistry`].
+///
+/// The overlay's area is removed from the registry when the slot is dropped.
+pub struct OverlaySlot {
+    registry: OverlayRegistry,
+    index: usize,
+}
+
+impl OverlayRegistry {
+    /// Constructs a new, empty `OverlayRegistry`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an entry for a new overlay, which doesn't cover anything until its area
+    /// is set.
+    pub fn slot(self: &Self) -> OverlaySlot {
+        let mut rects = self.rects.lock().unwrap();
+        rects.push(None);
+        OverlaySlot {
+            registry: self.clone(),
+            index: rects.len() - 1,
+        }
+    }
+
+    /// Returns the areas that are currently covered, in physical pixels.
+    pub fn rects(self: &Self) -> Vec<BoundingBox> {
+        self.rects
+            .lock()
+            .unwrap()
+            .iter()
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    /// Returns `true` if the given area overlaps any of the covered areas.
+    pub fn overlaps(self: &Self, rect: BoundingBox) -> bool {
+        self.rects()
+            .into_iter()
+            .any(|covered| intersects(rect, covered))
+    }
+}
+
+impl OverlaySlot {
+    /// Sets the area the overlay covers, in physical pixels.
+    pub fn set(self: &Self, rect: BoundingBox) {
+        self.registry.rects.lock().unwrap()[self.index] = Some(rect);
+    }
+
+    /// Marks the overlay as not covering anything, e.g. while it's hidden.
+    pub fn clear(self: &Self) {
+        self.registry.rects.lock().unwrap()[self.index] = None;
+    }
+}
+
+impl Drop for OverlaySlot {
+    fn drop(&mut self) {
+        if let Ok(mut rects) = self.registry.rects.lock() {
+            rects[self.index] = None;
+        }
+    }
+}
+
+/// Returns `true` if two areas overlap; Areas that only touch don't.
+pub(crate) fn intersects(a: BoundingBox, b: BoundingBox) -> bool {
+    a.x < b.x + b.w && b.x < a.x + a.w && a.y < b.y + b.h && b.y < a.y + a.h
+}
+
+/// Returns the area of a readout of the given size, anchored to a corner of the
+/// view's bounds and moved inwards by the inset.
+pub(crate) fn readout_rect(
+    bounds: BoundingBox,
+    position: ReadoutPosition,
+    inset: f32,
+    size: (f32, f32),
+) -> BoundingBox {
+    let x = match position {
+        ReadoutPosition::TopLeft | ReadoutPosition::BottomLeft => bounds.x + inset,
+        ReadoutPosition::TopRight | ReadoutPosition::BottomRight => {
+            bounds.x + bounds.w - inset - size.0
+        }
+    };
+    let y = match position {
+        ReadoutPosition::TopLeft | ReadoutPosition::TopRight => bounds.y + inset,
+        ReadoutPosition::BottomLeft | ReadoutPosition::BottomRight => {
+            bounds.y + bounds.h - inset - size.1
+        }
+    };
+    BoundingBox {
+        x,
+        y,
+        w: size.0,
+        h: size.1,
+    }
+}
+
+/// Returns the left, right, top and bottom spacing that places a readout in the
+/// same spot as [`readout_rect()`].
+pub(crate) fn readout_spacing(
+    position: ReadoutPosition,
+    inset: f32,
+) -> (Units, Units, Units, Units) {
+    let (left, right) = match position {
+        ReadoutPosition::TopLeft | ReadoutPosition::BottomLeft => (Pixels(inset), Stretch(1.0)),
+        ReadoutPosition::TopRight | ReadoutPosition::BottomRight => (Stretch(1.0), Pixels(inset)),
+    };
+    let (top, bottom) = match position {
+        ReadoutPosition::TopLeft | ReadoutPosition::TopRight => (Pixels(inset), Stretch(1.0)),
+        ReadoutPosition::BottomLeft | ReadoutPosition::BottomRight => (Stretch(1.0), Pixels(inset)),
+    };
+    (left, right, top, bottom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{intersects, readout_rect, OverlayRegistry, ReadoutPosition};
+    use nih_plug_vizia::vizia::prelude::BoundingBox;
+
+    fn rect(x: f32, y: f32, w: f32, h: f32) -> BoundingBox {
+        BoundingBox { x, y, w, h }
+    }
+
+    #[test]
+    fn readout_corners() {
+        let bounds = rect(100., 50., 400., 200.);
+        let size = (72., 20.);
+        assert_eq!(
+            readout_rect(bounds, ReadoutPosition::TopLeft, 8., size),
+            rect(108., 58., 72., 20.)
+        );
+        assert_eq!(
+            readout_rect(bounds, ReadoutPosition::TopRight, 8., size),
+            rect(420., 58., 72., 20.)
+        );
+        assert_eq!(
+            readout_rect(bounds, ReadoutPosition::BottomLeft, 8., size),
+            rect(108., 222., 72., 20.)
+        );
+        assert_eq!(
+            readout_rect(bounds, ReadoutPosition::BottomRight, 0., size),
+            rect(428., 230., 72., 20.)
+        );
+    }
+
+    #[test]
+    fn slots() {
+        let registry = OverlayRegistry::new();
+        let first = registry.slot();
+        let second = registry.slot();
+        assert!(registry.rects().is_empty());
+
+        first.set(rect(0., 0., 10., 10.));
+        second.set(rect(20., 0., 10., 10.));
+        assert!(registry.overlaps(rect(5., 5., 10., 10.)));
+        // Touching isn't overlapping
+        assert!(!registry.overlaps(rect(10., 0., 10., 10.)));
+        assert!(!intersects(rect(0., 0., 10., 10.), rect(0., 10., 10., 10.)));
+
+        drop(first);
+        second.clear();
+        assert!(registry.rects().is_empty());
+        assert!(!registry.overlaps(rect(5., 5., 10., 10.)));
+    }
+}
@@ -0,0 +1,116 @@
+use nih_plug_vizia::vizia::prelude::{BoundingBox, Orientation};
+
+/// The space between a view's bounds and the area its values are mapped onto, in
+/// logical pixels; See [`PaddingModifiers`](super::PaddingModifiers).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct InnerPadding {
+    pub(crate) top: f32,
+    pub(crate) right: f32,
+    pub(crate) bottom: f32,
+    pub(crate) left: f32,
+}
+
+impl InnerPadding {
+    pub(crate) fn new(top: f32, right: f32, bottom: f32, left: f32) -> Self {
+        Self {
+            top,
+            right,
+            bottom,
+            left,
+        }
+    }
+
+    pub(crate) fn uniform(padding: f32) -> Self {
+        Self::new(padding, padding, padding, padding)
+    }
+
+    /// Returns the padding along one axis only, e.g. for a ruler whose labels span
+    /// its entire width.
+    pub(crate) fn along(self: &Self, orientation: Orientation) -> Self {
+        match orientation {
+            Orientation::Vertical => Self::new(self.top, 0., self.bottom, 0.),
+            Orientation::Horizontal => Self::new(0., self.right, 0., self.left),
+        }
+    }
+
+    /// Returns the area that values are mapped onto.
+    ///
+    /// * `bounds` - The view's bounds, in physical pixels
+    /// * `scale` - The window's scale factor
+    /// * `stroke_width` - The width of the view's strokes, in physical pixels
+    ///
+    /// Besides the padding, the area is inset by half of the stroke width on every
+    /// side, so that a stroke along the edge of the range is drawn entirely inside the
+    /// view, instead of being cut in half by its bounds.
+    pub(crate) fn plot_rect(
+        self: &Self,
+        bounds: BoundingBox,
+        scale: f32,
+        stroke_width: f32,
+    ) -> BoundingBox {
+        let half_stroke = stroke_width / 2.;
+        let left = self.left * scale + half_stroke;
+        let top = self.top * scale + half_stroke;
+        let right = self.right * scale + half_stroke;
+        let bottom = self.bottom * scale + half_stroke;
+        BoundingBox {
+            x: bounds.x + left,
+            y: bounds.y + top,
+            w: (bounds.w - left - right).max(0.),
+            h: (bounds.h - top - bottom).max(0.),
+        }
+    }
+}
+
+/// The width of the views' strokes, in logical pixels.
+pub(crate) const STROKE_WIDTH: f32 = 1.0;
+
+#[cfg(test)]
+mod tests {
+    use super::InnerPadding;
+    use crate::visualizers::axes::Axes;
+    use nih_plug_vizia::vizia::prelude::{BoundingBox, Orientation};
+
+    const BOUNDS: BoundingBox = BoundingBox {
+        x: 10.,
+        y: 20.,
+        w: 200.,
+        h: 100.,
+    };
+
+    #[test]
+    fn full_scale_lies_inside_stroke() {
+        for (padding, scale) in [(0., 1.), (4., 1.), (4., 2.), (6.5, 1.5)] {
+            let stroke_width = scale;
+            let rect = InnerPadding::uniform(padding).plot_rect(BOUNDS, scale, stroke_width);
+            let axes = Axes::new(rect, Orientation::Vertical, false);
+
+            // The top of the range, and the bottom
+            let (_, top) = axes.point(1., 1.);
+            assert_eq!(top - BOUNDS.y, padding * scale + stroke_width / 2.);
+            let (_, bottom) = axes.point(1., 0.);
+            assert_eq!(
+                BOUNDS.y + BOUNDS.h - bottom,
+                padding * scale + stroke_width / 2.
+            );
+        }
+    }
+
+    #[test]
+    fn sides() {
+        let rect = InnerPadding::new(1., 2., 3., 4.).plot_rect(BOUNDS, 2., 2.);
+        assert_eq!(
+            rect,
+            BoundingBox {
+                x: 19.,
+                y: 23.,
+                w: 186.,
+                h: 90.,
+            }
+        );
+
+        // Padding larger than the view leaves nothing to draw on
+        let rect = InnerPadding::uniform(80.).plot_rect(BOUNDS, 1., 1.);
+        assert_eq!((rect.w, rect.h), (0., 0.));
+    }
+}
@@ -0,0 +1,255 @@
+use nih_plug_vizia::vizia::prelude::{BoundingBox, Orientation};
+
+use super::axes::Axes;
+use super::padding::{InnerPadding, STROKE_WIDTH};
+
+/// The area that a view maps its values onto, snapped to physical pixels.
+///
+/// Views that are stacked on top of each other, like a [`Grid`](super::Grid), a
+/// [`Graph`](super::Graph) and a [`UnitRuler`](super::UnitRuler) inside a `ZStack`,
+/// need to agree on where each value lies down to the pixel - a grid line that is a
+/// pixel off from its label, or from the top of the range, is glaring. So every view
+/// computes its area here, the same way:
+///
+/// 1. The [padding](super::PaddingModifiers) and half of a stroke are taken off the
+///    view's bounds, as in [`InnerPadding::plot_rect()`].
+/// 2. Each edge of the area is snapped to the [nearest crisp position](Self::snap),
+///    once.
+/// 3. Everything is derived from the snapped area - the points of a trace are mapped
+///    onto it as they are, and marks like grid lines and ruler labels are snapped
+///    again, so that they are crisp as well.
+///
+/// Since both the edges and the marks are snapped using the same rule, the top and
+/// bottom of the range always coincide with the marks that lie on them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct PlotArea {
+    pub(crate) rect: BoundingBox,
+    // The fraction that crisp positions lie at, either 0 or 0.5
+    offset: f32,
+}
+
+impl PlotArea {
+    /// Computes the area of a view.
+    ///
+    /// * `bounds` - The view's bounds, in physical pixels
+    /// * `padding` - The view's padding, in logical pixels
+    /// * `scale` - The window's scale factor
+    pub(crate) fn new(bounds: BoundingBox, padding: InnerPadding, scale: f32) -> Self {
+        let stroke_width = STROKE_WIDTH * scale;
+        let rect = padding.plot_rect(bounds, scale, stroke_width);
+        let area = Self {
+            rect,
+            offset: crisp_offset(stroke_width),
+        };
+        let (left, top) = (area.snap(rect.x), area.snap(rect.y));
+        let right = area.snap(rect.x + rect.w).max(left);
+        let bottom = area.snap(rect.y + rect.h).max(top);
+        Self {
+            rect: BoundingBox {
+                x: left,
+                y: top,
+                w: right - left,
+                h: bottom - top,
+            },
+            ..area
+        }
+    }
+
+    /// Returns the [`Axes`] that map positions onto the area.
+    pub(crate) fn axes(self: &Self, value_axis: Orientation, mirror_x: bool) -> Axes {
+        Axes::new(self.rect, value_axis, mirror_x)
+    }
+
+    /// Snaps a coordinate, in physical pixels, to the nearest position at which a
+    /// stroke is drawn crisply.
+    ///
+    /// A stroke that covers an odd number of pixels is crisp when it's centered on a
+    /// pixel, and one that covers an even number of pixels when it's centered between
+    /// two of them.
+    pub(crate) fn snap(self: &Self, coordinate: f32) -> f32 {
+        (coordinate - self.offset).round() + self.offset
+    }
+
+    /// Snaps both coordinates of a point; See [`snap()`](Self::snap).
+    pub(crate) fn snap_point(self: &Self, (x, y): (f32, f32)) -> (f32, f32) {
+        (self.snap(x), self.snap(y))
+    }
+}
+
+// The fraction that crisp positions lie at, for a stroke of the width in physical
+// pixels
+fn crisp_offset(stroke_width: f32) -> f32 {
+    let pixels = stroke_width.round().max(1.) as u32;
+    if pixels % 2 == 1 {
+        0.5
+    } else {
+        0.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PlotArea;
+    use crate::visualizers::grid::line_points;
+    use crate::visualizers::padding::InnerPadding;
+    use crate::visualizers::unit_ruler::tick_positions;
+    use crate::visualizers::GraphDirection;
+    use nih_plug_vizia::vizia::prelude::{BoundingBox, Orientation};
+
+    const SCALES: [f32; 4] = [1.0, 1.25, 1.5, 2.0];
+
+    // The bounds of the views of a `ZStack` that is `height` logical pixels high, and
+    // of a ruler to its right
+    fn stacked_bounds(width: f32, height: f32, scale: f32) -> (BoundingBox, BoundingBox) {
+        let stack = BoundingBox {
+            x: (16. * scale).round(),
+            y: (24. * scale).round(),
+            w: (width * scale).round(),
+            h: (height * scale).round(),
+        };
+        let ruler = BoundingBox {
+            x: stack.x + stack.w + (8. * scale).round(),
+            w: (32. * scale).round(),
+            ..stack
+        };
+        (stack, ruler)
+    }
+
+    #[test]
+    fn edges_are_crisp() {
+        for scale in SCALES {
+            let area = PlotArea::new(
+                stacked_bounds(203., 117., scale).0,
+                InnerPadding::uniform(3.5),
+                scale,
+            );
+            let rect = area.rect;
+            for edge in [rect.x, rect.y, rect.x + rect.w, rect.y + rect.h] {
+                assert_eq!(area.snap(edge), edge, "scale {}", scale);
+            }
+        }
+
+        // A one pixel stroke is crisp at the center of a pixel, and a two pixel one
+        // between two of them
+        let area = PlotArea::new(
+            stacked_bounds(100., 100., 1.).0,
+            InnerPadding::default(),
+            1.,
+        );
+        assert_eq!((area.snap(10.2), area.snap(10.9)), (10.5, 10.5));
+        let area = PlotArea::new(
+            stacked_bounds(100., 100., 2.).0,
+            InnerPadding::default(),
+            2.,
+        );
+        assert_eq!((area.snap(10.2), area.snap(10.9)), (10., 11.));
+    }
+
+    #[test]
+    fn grid_ruler_and_graph_line_up() {
+        // Marks at the edges of the range, and at fractions that rarely fall on a pixel
+        let marks: Vec<f32> = (0..=24).map(|i| i as f32 / 24.).chain([0.3, 0.7]).collect();
+        let padding = InnerPadding::new(7.5, 3., 5.25, 2.);
+
+        for scale in SCALES {
+            for width in [100., 203., 317.3, 640.] {
+                for height in [80., 117., 240.5, 401.] {
+                    let (stack, ruler_bounds) = stacked_bounds(width, height, scale);
+                    let grid = PlotArea::new(stack, padding, scale);
+                    let graph = grid.axes(Orientation::Vertical, false);
+                    let ruler =
+                        PlotArea::new(ruler_bounds, padding.along(Orientation::Vertical), scale);
+                    let ticks = tick_positions(
+                        &ruler,
+                        &marks,
+                        Orientation::Vertical,
+                        false,
+                        GraphDirection::BottomUp,
+                    );
+
+                    for (mark, tick) in marks.iter().zip(&ticks) {
+                        let (from, to) = line_points(&grid, &graph, Orientation::Horizontal, *mark);
+                        assert_eq!(
+                            (from.1, to.1),
+                            (*tick, *tick),
+                            "{} at scale {}, {}x{}",
+                            mark,
+                            scale,
+                            width,
+                            height
+                        );
+                    }
+
+                    // The range's endpoints lie exactly on the outermost marks
+                    assert_eq!(graph.point(0.5, 1.).1, ticks[24]);
+                    assert_eq!(graph.point(0.5, 0.).1, ticks[0]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn vertical_lines_line_up_with_horizontal_ruler() {
+        let marks = [0., 0.25, 0.4, 1.];
+        for scale in SCALES {
+            let (stack, _) = stacked_bounds(317.3, 117., scale);
+            let ruler_bounds = BoundingBox {
+                y: stack.y + stack.h,
+                h: (24. * scale).round(),
+                ..stack
+            };
+            let padding = InnerPadding::uniform(4.25);
+            let grid = PlotArea::new(stack, padding, scale);
+            let axes = grid.axes(Orientation::Vertical, false);
+            let ruler = PlotArea::new(ruler_bounds, padding.along(Orientation::Horizontal), scale);
+            let ticks = tick_positions(
+                &ruler,
+                &marks,
+                Orientation::Horizontal,
+                false,
+                GraphDirection::BottomUp,
+            );
+            for (mark, tick) in marks.iter().zip(&ticks) {
+                let (from, to) = line_points(&grid, &axes, Orientation::Vertical, *mark);
+                assert_eq!((from.0, to.0), (*tick, *tick));
+            }
+        }
+    }
+
+    #[test]
+    fn top_down_grid_lines_up_with_ruler() {
+        // A gain reduction display, from 0 dB at the top to 24 dB at the bottom
+        let marks = [0., 0.25, 0.5, 0.75, 1.];
+        let padding = InnerPadding::new(4., 0., 4., 0.);
+        for scale in SCALES {
+            let (stack, ruler_bounds) = stacked_bounds(203., 117., scale);
+            let grid = PlotArea::new(stack, padding, scale);
+            let axes = grid
+                .axes(Orientation::Vertical, false)
+                .with_direction(GraphDirection::TopDown);
+            let ruler = PlotArea::new(ruler_bounds, padding.along(Orientation::Vertical), scale);
+            let ticks = tick_positions(
+                &ruler,
+                &marks,
+                Orientation::Vertical,
+                false,
+                GraphDirection::TopDown,
+            );
+            for (mark, tick) in marks.iter().zip(&ticks) {
+                let (from, to) = line_points(&grid, &axes, Orientation::Horizontal, *mark);
+                assert_eq!(
+                    (from.1, to.1),
+                    (*tick, *tick),
+                    "{} at scale {}",
+                    mark,
+                    scale
+                );
+            }
+
+            // The 6 dB mark lies a quarter of the way down
+            assert_eq!(ticks[0], grid.rect.y);
+            assert_eq!(ticks[1], grid.snap(grid.rect.y + grid.rect.h * 0.25));
+            assert_eq!(ticks[4], grid.rect.y + grid.rect.h);
+        }
+    }
+}
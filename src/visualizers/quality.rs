@@ -0,0 +1,513 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use nih_plug_vizia::vizia::prelude::*;
+
+/// How often an [`AdaptiveQuality`] controller reads its [`FrameStats`].
+const CHECK_INTERVAL: Duration = Duration::from_millis(16);
+
+/// The number of checks that the draw time is averaged over, and that the
+/// controller waits for after changing the quality.
+const WINDOW: usize = 30;
+
+/// The quality is only restored once the average draw time drops below this
+/// fraction of the budget, so that it doesn't flip back and forth around it.
+const RESTORE_RATIO: f32 = 0.5;
+
+/// A way for views to draw less, which an [`AdaptiveQuality`] controller enables
+/// when drawing takes too long.
+///
+/// The controller enables them one at a time, in the order of their declaration,
+/// and restores them in the reverse order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Degradation {
+    /// [`Graph`](super::Graph)s and [`Oscilloscope`](super::Oscilloscope)s merge
+    /// every two neighbouring points into one, keeping the more extreme value.
+    CoarseDecimation,
+    /// [`minor()`](super::GridModifiers::minor) [`Grid`](super::Grid)s aren't drawn.
+    SkipMinorGrid,
+    /// A [`Graph`](super::Graph)'s [`readout()`](super::GraphModifiers::readout) is
+    /// updated half as often.
+    SlowReadout,
+}
+
+impl Degradation {
+    /// All degradations, in the order they are enabled in.
+    pub const ALL: [Degradation; 3] = [
+        Degradation::CoarseDecimation,
+        Degradation::SkipMinorGrid,
+        Degradation::SlowReadout,
+    ];
+
+    fn bit(self: Self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// Whether an [`AdaptiveQuality`] controller may enable a [`Degradation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DegradationOverride {
+    /// The controller enables the degradation when drawing takes too long.
+    #[default]
+    Auto,
+    /// The degradation is always enabled.
+    Always,
+    /// The degradation is never enabled.
+    Never,
+}
+
+/// The draw times of the views that report to an [`AdaptiveQuality`] controller,
+/// along with the degradations it enabled.
+///
+/// Cloning the stats shares them. Views report to the stats they were given using
+/// [`adaptive_quality()`](AdaptiveQualityModifiers::adaptive_quality); Views without
+/// stats don't measure anything.
+#[derive(Debug, Clone, Default)]
+pub struct FrameStats(Arc<FrameStatsInner>);
+
+#[derive(Debug, Default)]
+struct FrameStatsInner {
+    // The time spent drawing since the last check, in nanoseconds
+    draw_time: AtomicU64,
+    draws: AtomicU32,
+    // The bits of the enabled degradations
+    degradations: AtomicU8,
+}
+
+impl FrameStats {
+    /// Adds the time a view took to draw.
+    ///
+    /// Cyma's views call this at the end of their `draw()`; Custom views can report
+    /// their draw times as well.
+    pub fn record(self: &Self, draw_time: Duration) {
+        let nanos = draw_time.as_nanos().min(u64::MAX as u128) as u64;
+        self.0.draw_time.fetch_add(nanos, Ordering::Relaxed);
+        self.0.draws.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns whether the degradation is enabled.
+    pub fn is_degraded(self: &Self, degradation: Degradation) -> bool {
+        self.0.degradations.load(Ordering::Relaxed) & degradation.bit() != 0
+    }
+
+    /// Returns the time spent drawing since the last call, or `None` if nothing was
+    /// drawn.
+    fn take(self: &Self) -> Option<Duration> {
+        let draws = self.0.draws.swap(0, Ordering::Relaxed);
+        let draw_time = self.0.draw_time.swap(0, Ordering::Relaxed);
+        (draws > 0).then(|| Duration::from_nanos(draw_time))
+    }
+
+    fn set_degradations(self: &Self, bits: u8) {
+        self.0.degradations.store(bits, Ordering::Relaxed);
+    }
+}
+
+/// Draws a view, and reports the time it took to the stats, if there are any.
+pub(crate) fn draw_measured(stats: Option<&FrameStats>, draw: impl FnOnce()) {
+    match stats {
+        Some(stats) => {
+            let start = Instant::now();
+            draw();
+            stats.record(start.elapsed());
+        }
+        None => draw(),
+    }
+}
+
+/// Lowers the quality of the views that report to it when drawing takes longer
+/// than a budget, and restores it once there is headroom again.
+///
+/// The views' draw times are summed up once per frame, and averaged over the last
+/// 30 frames. Whenever the average exceeds the budget, the controller enables the
+/// next [`Degradation`]; Once it drops below half of the budget, the last one is
+/// restored. After each change, the controller waits for 30 frames, so that the
+/// average reflects the new quality before it changes again.
+///
+/// Build the controller inside the editor, and hand its [`stats()`](Self::stats) to
+/// the views that should adapt:
+///
+/// ```
+/// let quality = AdaptiveQuality::new(Duration::from_millis(6))
+///     .with_override(Degradation::SlowReadout, DegradationOverride::Never);
+/// let stats = quality.stats();
+/// quality.build(cx);
+///
+/// Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+///     .adaptive_quality(&stats);
+/// ```
+///
+/// Without a controller, the views don't measure anything.
+#[derive(Lens)]
+pub struct AdaptiveQuality {
+    /// The number of automatic degradations that are enabled.
+    pub level: usize,
+    stats: FrameStats,
+    controller: QualityController,
+}
+
+enum QualityEvents {
+    Check,
+}
+
+impl AdaptiveQuality {
+    /// Constructs a new `AdaptiveQuality` controller, which starts out at full
+    /// quality.
+    ///
+    /// * `budget` - How long the views may take to draw per frame, in total
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            level: 0,
+            stats: FrameStats::default(),
+            controller: QualityController::new(budget),
+        }
+    }
+
+    /// Overrides whether the controller may enable a degradation.
+    pub fn with_override(
+        mut self,
+        degradation: Degradation,
+        degradation_override: DegradationOverride,
+    ) -> Self {
+        self.controller
+            .set_override(degradation, degradation_override);
+        self.stats.set_degradations(self.controller.degradations());
+        self
+    }
+
+    /// Returns the stats that the views report to.
+    pub fn stats(self: &Self) -> FrameStats {
+        self.stats.clone()
+    }
+
+    /// Builds the controller into the current view, and starts reading the stats.
+    pub fn build(self, cx: &mut Context) {
+        Model::build(self, cx);
+
+        let entity = cx.current();
+        let timer = cx.add_timer(CHECK_INTERVAL, None, move |cx, action| {
+            if let TimerAction::Tick(_) = action {
+                cx.emit_to(entity, QualityEvents::Check);
+            }
+        });
+        cx.start_timer(timer);
+    }
+}
+
+impl Model for AdaptiveQuality {
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|e, _| match e {
+            QualityEvents::Check => {
+                let Some(draw_time) = self.stats.take() else {
+                    return;
+                };
+                if self.controller.update(draw_time) {
+                    self.stats.set_degradations(self.controller.degradations());
+                    self.level = self.controller.level();
+                }
+            }
+        });
+    }
+}
+
+/// The state machine behind an [`AdaptiveQuality`] controller.
+#[derive(Debug, Clone)]
+struct QualityController {
+    // In seconds
+    budget: f32,
+    overrides: [DegradationOverride; Degradation::ALL.len()],
+    level: usize,
+    // The draw times since the last change, in seconds
+    times: [f32; WINDOW],
+    next: usize,
+    filled: usize,
+}
+
+impl QualityController {
+    fn new(budget: Duration) -> Self {
+        Self {
+            budget: budget.as_secs_f32(),
+            overrides: Default::default(),
+            level: 0,
+            times: [0.; WINDOW],
+            next: 0,
+            filled: 0,
+        }
+    }
+
+    fn set_override(
+        self: &mut Self,
+        degradation: Degradation,
+        degradation_override: DegradationOverride,
+    ) {
+        self.overrides[degradation as usize] = degradation_override;
+        self.level = self.level.min(self.max_level());
+    }
+
+    fn level(self: &Self) -> usize {
+        self.level
+    }
+
+    // The number of degradations that the controller enables by itself
+    fn max_level(self: &Self) -> usize {
+        self.automatic().count()
+    }
+
+    fn automatic(self: &Self) -> impl Iterator<Item = Degradation> + '_ {
+        Degradation::ALL
+            .into_iter()
+            .filter(|d| self.overrides[*d as usize] == DegradationOverride::Auto)
+    }
+
+    /// Returns the bits of the enabled degradations.
+    fn degradations(self: &Self) -> u8 {
+        let always = Degradation::ALL
+            .into_iter()
+            .filter(|d| self.overrides[*d as usize] == DegradationOverride::Always);
+        always
+            .chain(self.automatic().take(self.level))
+            .fold(0, |bits, d| bits | d.bit())
+    }
+
+    /// Adds the time it took to draw a frame, and returns whether the level changed.
+    fn update(self: &mut Self, draw_time: Duration) -> bool {
+        self.times[self.next] = draw_time.as_secs_f32();
+        self.next = (self.next + 1) % WINDOW;
+        self.filled = (self.filled + 1).min(WINDOW);
+        if self.filled < WINDOW {
+            return false;
+        }
+
+        let average = self.times.iter().sum::<f32>() / WINDOW as f32;
+        let level = if average > self.budget {
+            (self.level + 1).min(self.max_level())
+        } else if average < self.budget * RESTORE_RATIO {
+            self.level.saturating_sub(1)
+        } else {
+            self.level
+        };
+        if level == self.level {
+            return false;
+        }
+
+        // The draw times at the old level don't count towards the new one
+        self.level = level;
+        self.filled = 0;
+        true
+    }
+}
+
+/// Merges every two neighbouring points of a graph into one, at the first one's
+/// position, keeping the value that lies farther from the anchor.
+///
+/// The newest point is kept as it is, so the trace still reaches the right edge.
+pub(crate) fn coarsen_points(points: &mut Vec<(f32, f32)>, anchor: f32) {
+    let len = points.len();
+    if len < 3 {
+        return;
+    }
+    let newest = points[len - 1];
+    let mut kept = 0;
+    for i in (0..len - 1).step_by(2) {
+        let (time, a) = points[i];
+        let value = match points.get(i + 1).filter(|_| i + 1 < len - 1) {
+            Some((_, b)) if (b - anchor).abs() > (a - anchor).abs() => *b,
+            _ => a,
+        };
+        points[kept] = (time, value);
+        kept += 1;
+    }
+    points[kept] = newest;
+    points.truncate(kept + 1);
+}
+
+/// Merges every two neighbouring pairs of a waveform into one, at the first one's
+/// position, spanning both of their minima and maxima.
+pub(crate) fn coarsen_pairs(points: &mut Vec<(f32, (f32, f32))>) {
+    let len = points.len();
+    let mut kept = 0;
+    for i in (0..len).step_by(2) {
+        let (x, (min, max)) = points[i];
+        points[kept] = match points.get(i + 1) {
+            Some((_, (next_min, next_max))) => (x, (min.min(*next_min), max.max(*next_max))),
+            None => (x, (min, max)),
+        };
+        kept += 1;
+    }
+    points.truncate(kept);
+}
+
+pub trait AdaptiveQualityModifiers {
+    /// Reports the view's draw times to an [`AdaptiveQuality`] controller, and lets
+    /// it lower the view's quality when drawing takes too long.
+    ///
+    /// Measuring the draw time costs two reads of the clock per frame; Without
+    /// stats, the view doesn't measure anything.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+    ///     .adaptive_quality(&stats)
+    ///     .color(Color::rgba(0, 0, 0, 160));
+    /// ```
+    fn adaptive_quality(self, stats: &FrameStats) -> Self;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        coarsen_pairs, coarsen_points, Degradation, DegradationOverride, FrameStats,
+        QualityController, WINDOW,
+    };
+    use std::time::Duration;
+
+    const BUDGET: Duration = Duration::from_millis(8);
+
+    // Feeds the draw times into the controller, and returns its level after each one
+    fn levels(controller: &mut QualityController, times: &[(u64, usize)]) -> Vec<usize> {
+        times
+            .iter()
+            .flat_map(|(ms, frames)| std::iter::repeat(*ms).take(*frames))
+            .map(|ms| {
+                controller.update(Duration::from_millis(ms));
+                controller.level()
+            })
+            .collect()
+    }
+
+    // The frames at which the level changed, along with the new level
+    fn transitions(levels: &[usize]) -> Vec<(usize, usize)> {
+        let mut last = 0;
+        levels
+            .iter()
+            .enumerate()
+            .filter_map(|(frame, level)| {
+                let changed = *level != last;
+                last = *level;
+                changed.then_some((frame, *level))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn within_budget() {
+        let mut controller = QualityController::new(BUDGET);
+        let levels = levels(&mut controller, &[(7, 200)]);
+        assert!(levels.iter().all(|level| *level == 0));
+        assert_eq!(controller.degradations(), 0);
+    }
+
+    #[test]
+    fn degrades_one_step_per_window() {
+        let mut controller = QualityController::new(BUDGET);
+        let levels = levels(&mut controller, &[(12, 5 * WINDOW)]);
+        assert_eq!(
+            transitions(&levels),
+            vec![(WINDOW - 1, 1), (2 * WINDOW - 1, 2), (3 * WINDOW - 1, 3)]
+        );
+        assert_eq!(
+            controller.degradations(),
+            Degradation::ALL.iter().fold(0, |bits, d| bits | d.bit())
+        );
+    }
+
+    #[test]
+    fn spikes_are_averaged_out() {
+        // A single slow frame every 10 frames stays below the budget on average
+        let mut controller = QualityController::new(BUDGET);
+        let pattern: Vec<(u64, usize)> = (0..20).flat_map(|_| [(30, 1), (4, 9)]).collect();
+        let levels = levels(&mut controller, &pattern);
+        assert!(levels.iter().all(|level| *level == 0));
+    }
+
+    #[test]
+    fn restores_with_hysteresis() {
+        let mut controller = QualityController::new(BUDGET);
+        let levels = levels(
+            &mut controller,
+            &[
+                (12, 2 * WINDOW),
+                // Below the budget, but not below half of it
+                (5, 3 * WINDOW),
+                // Plenty of headroom, which takes 8 frames to pull the average down
+                (1, 3 * WINDOW),
+            ],
+        );
+        assert_eq!(
+            transitions(&levels),
+            vec![
+                (WINDOW - 1, 1),
+                (2 * WINDOW - 1, 2),
+                (5 * WINDOW + 7, 1),
+                (6 * WINDOW + 7, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn overrides() {
+        let mut controller = QualityController::new(BUDGET);
+        controller.set_override(Degradation::CoarseDecimation, DegradationOverride::Never);
+        controller.set_override(Degradation::SlowReadout, DegradationOverride::Always);
+        assert_eq!(controller.degradations(), Degradation::SlowReadout.bit());
+
+        // Only the minor grid lines are left to the controller
+        let levels = levels(&mut controller, &[(12, 3 * WINDOW)]);
+        assert_eq!(transitions(&levels), vec![(WINDOW - 1, 1)]);
+        assert_eq!(
+            controller.degradations(),
+            Degradation::SkipMinorGrid.bit() | Degradation::SlowReadout.bit()
+        );
+
+        // Taking a degradation away from the controller lowers its level
+        controller.set_override(Degradation::SkipMinorGrid, DegradationOverride::Never);
+        assert_eq!(controller.level(), 0);
+        assert_eq!(controller.degradations(), Degradation::SlowReadout.bit());
+    }
+
+    #[test]
+    fn stats() {
+        let stats = FrameStats::default();
+        assert_eq!(stats.take(), None);
+
+        let view = stats.clone();
+        view.record(Duration::from_millis(2));
+        view.record(Duration::from_millis(3));
+        assert_eq!(stats.take(), Some(Duration::from_millis(5)));
+        assert_eq!(stats.take(), None);
+
+        stats.set_degradations(Degradation::SkipMinorGrid.bit());
+        assert!(view.is_degraded(Degradation::SkipMinorGrid));
+        assert!(!view.is_degraded(Degradation::CoarseDecimation));
+    }
+
+    #[test]
+    fn coarse_points() {
+        let mut points = vec![(0., 0.5), (1., 0.9), (2., 0.2), (3., 0.4), (4., 0.6)];
+        coarsen_points(&mut points, 0.5);
+        assert_eq!(points, vec![(0., 0.9), (2., 0.2), (4., 0.6)]);
+
+        // With an even number of points, the one before the newest stands alone
+        let mut points = vec![(0., 0.1), (1., 0.3), (2., 0.7), (3., 0.5)];
+        coarsen_points(&mut points, 0.);
+        assert_eq!(points, vec![(0., 0.3), (2., 0.7), (3., 0.5)]);
+    }
+
+    #[test]
+    fn coarse_pairs() {
+        let mut points = vec![
+            (0., (-0.2, 0.3)),
+            (1., (-0.5, 0.1)),
+            (2., (0.0, 0.4)),
+            (3., (-0.1, 0.2)),
+            (4., (-0.3, 0.3)),
+        ];
+        coarsen_pairs(&mut points);
+        assert_eq!(
+            points,
+            vec![(0., (-0.5, 0.3)), (2., (-0.1, 0.4)), (4., (-0.3, 0.3))]
+        );
+    }
+}
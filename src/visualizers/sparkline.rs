@@ -0,0 +1,192 @@
+use std::cell::RefCell;
+
+use nih_plug_vizia::vizia::{prelude::*, vg};
+
+use super::elements;
+use super::{draw_throttled, redraw_on_write, FrameThrottle, ThrottleModifiers};
+use crate::geometry::{normalized, sparkline_points};
+use crate::utils::{SharedRead, VisualizerBuffer};
+
+/// The space between the trace and the edges of a sparkline, in logical pixels.
+const MARGIN: f32 = 1.5;
+
+/// The radius of the dot at the newest value, in logical pixels.
+const DOT_RADIUS: f32 = 1.5;
+
+/// Tiny trend line for dense UIs, e.g. the recent modulation depth next to a
+/// parameter.
+///
+/// A sparkline draws the contents of a [`VisualizerBuffer`] as a single thin line,
+/// without a grid, a fill or a fixed range: the range follows the values the buffer
+/// currently holds. The trace is decimated to at most one point per pixel, keeping
+/// the peaks, so drawing costs about the same no matter how large the buffer is -
+/// a `60x14` sparkline draws 60 points. Many sparklines can be shown at once.
+/// A buffer behind an `Arc<RwLock<B>>` lets them read it without waiting for each
+/// other; See [`SharedRead`].
+///
+/// # Example
+///
+/// ```
+/// Sparkline::new(cx, Data::modulation_buffer)
+///     .baseline(0.0, Color::rgb(120, 220, 140), Color::rgb(230, 110, 90))
+///     .newest_dot(true)
+///     .width(Pixels(60.0))
+///     .height(Pixels(14.0));
+/// ```
+///
+/// # Styling
+///
+/// Stylesheets select it as `cyma-sparkline`. Its `color` is the line.
+pub struct Sparkline<L, I>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    buffer: L,
+    baseline: Option<Baseline>,
+    newest_dot: bool,
+    throttle: FrameThrottle,
+    // Reused from one frame to the next, so that drawing doesn't allocate it
+    points: RefCell<Vec<(f32, f32)>>,
+}
+
+/// The level a sparkline is compared to; See [`baseline()`](SparklineModifiers::baseline).
+#[derive(Clone, Copy)]
+struct Baseline {
+    value: f32,
+    above: Color,
+    below: Color,
+}
+
+impl<L, I> Sparkline<L, I>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    pub fn new(cx: &mut Context, buffer: L) -> Handle<Self> {
+        Self {
+            buffer: buffer.clone(),
+            baseline: None,
+            newest_dot: false,
+            throttle: FrameThrottle::default(),
+            points: RefCell::new(Vec::new()),
+        }
+        .build(cx, |cx| redraw_on_write(cx, buffer))
+    }
+}
+
+impl<L, I> View for Sparkline<L, I>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    fn element(&self) -> Option<&'static str> {
+        Some(elements::SPARKLINE)
+    }
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        draw_throttled(cx, canvas, &self.throttle, |cx, canvas| {
+            let scale = cx.scale_factor();
+            let bounds = cx.bounds();
+            let margin = MARGIN * scale;
+            let (x, y) = (bounds.x + margin, bounds.y + margin);
+            let w = (bounds.w - 2. * margin).max(0.);
+            let h = (bounds.h - 2. * margin).max(0.);
+
+            let mut points = self.points.borrow_mut();
+            let extent = {
+                let binding = self.buffer.get(cx);
+                let ring_buf = binding.read_shared(|buffer| buffer.refresh());
+                sparkline_points(
+                    &mut points,
+                    &*ring_buf,
+                    w as usize,
+                    self.baseline.map(|baseline| baseline.value),
+                )
+            };
+            let (Some(extent), Some(newest)) = (extent, points.last().copied()) else {
+                return;
+            };
+            let position = |(fx, fy): (f32, f32)| (x + w * fx, y + h * (1. - fy));
+
+            let mut color: vg::Color = cx.font_color().into();
+            if let Some(baseline) = &self.baseline {
+                let level = normalized(extent, baseline.value);
+
+                // A hairline, centered on a physical pixel
+                let line_y = (position((0., level)).1 - 0.5).round() + 0.5;
+                let mut line = vg::Path::new();
+                line.move_to(x, line_y);
+                line.line_to(x + w, line_y);
+                let mut hairline = color;
+                hairline.a *= 0.4;
+                canvas.stroke_path(&line, &vg::Paint::color(hairline).with_line_width(1.));
+
+                color = match newest.1 >= level {
+                    true => baseline.above,
+                    false => baseline.below,
+                }
+                .into();
+            }
+
+            let mut trace = vg::Path::new();
+            let (start_x, start_y) = position(points[0]);
+            trace.move_to(start_x, start_y);
+            for point in points.iter().skip(1) {
+                let (px, py) = position(*point);
+                trace.line_to(px, py);
+            }
+            canvas.stroke_path(&trace, &vg::Paint::color(color).with_line_width(scale));
+
+            if self.newest_dot {
+                let (px, py) = position(newest);
+                let mut dot = vg::Path::new();
+                dot.circle(px, py, DOT_RADIUS * scale);
+                canvas.fill_path(&dot, &vg::Paint::color(color));
+            }
+        });
+    }
+}
+
+pub trait SparklineModifiers {
+    /// Draws a hairline at a value, and colors the line by whether the newest value
+    /// lies above or below it.
+    ///
+    /// The range always includes the baseline, so the hairline stays visible.
+    fn baseline(self, value: f32, above: Color, below: Color) -> Self;
+    /// Draws a dot at the newest value.
+    fn newest_dot(self, newest_dot: bool) -> Self;
+}
+
+impl<'a, L, I> SparklineModifiers for Handle<'a, Sparkline<L, I>>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    fn baseline(self, value: f32, above: Color, below: Color) -> Self {
+        self.modify(|sparkline| {
+            sparkline.baseline = Some(Baseline {
+                value,
+                above,
+                below,
+            })
+        })
+    }
+    fn newest_dot(self, newest_dot: bool) -> Self {
+        self.modify(|sparkline| sparkline.newest_dot = newest_dot)
+    }
+}
+
+impl<'a, L, I> ThrottleModifiers for Handle<'a, Sparkline<L, I>>
+where
+    L: Lens,
+    L::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    fn max_fps(self, max_fps: f32) -> Self {
+        self.modify(|view| view.throttle.set_max_fps(max_fps))
+    }
+}
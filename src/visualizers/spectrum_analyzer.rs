@@ -1,9 +1,17 @@
+use std::cell::RefCell;
+
 use nih_plug_vizia::vizia::prelude::*;
 use nih_plug_vizia::vizia::vg;
-use std::sync::{Arc, Mutex};
 
-use crate::utils::SpectrumOutput;
-use crate::utils::ValueScaling;
+use super::elements;
+use super::{draw_throttled, FrameThrottle, ThrottleModifiers};
+use crate::geometry::{spectrum_points, SpectrumScale};
+use crate::utils::{SharedWrite, SpectrumOutput, ValueScaling};
+
+pub use crate::geometry::SpectrumAnalyzerVariant;
+
+// The opacity of the spectrum after the SpectrumInput was dropped
+const DISCONNECTED_OPACITY: f32 = 0.4;
 
 /// Spectrum analyzer that shows the magnitude of each frequency bin inside a
 /// [`SpectrumOutput`].
@@ -101,7 +109,9 @@ use crate::utils::ValueScaling;
 /// ```
 ///
 /// Now, upon creation, you can clone a reference to the `Arc<Mutex>>` and send it
-/// off to the editor.
+/// off to the editor. An `Arc<RwLock>` works as well; Either way, the analyzer takes
+/// the newest spectrum out of the output when it draws, so it writes to it - see
+/// [`SharedWrite`].
 ///
 /// ```
 /// fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
@@ -214,22 +224,24 @@ use crate::utils::ValueScaling;
 /// .border_color(Color::rgb(80, 80, 80))
 /// .border_width(Pixels(1.));
 /// ```
-pub struct SpectrumAnalyzer {
-    spectrum: Arc<Mutex<SpectrumOutput>>,
+///
+/// # Styling
+///
+/// Stylesheets select it as `cyma-spectrum-analyzer`. Its `color` is the trace, and
+/// its `background-color` the fill below it.
+pub struct SpectrumAnalyzer<S> {
+    spectrum: S,
     variant: SpectrumAnalyzerVariant,
-    frequency_scaling: ValueScaling,
-    frequency_range: (f32, f32),
-    magnitude_scaling: ValueScaling,
-    magnitude_range: (f32, f32),
-    slope: Option<f32>,
-}
-
-pub enum SpectrumAnalyzerVariant {
-    BAR,
-    LINE,
+    scale: SpectrumScale,
+    throttle: FrameThrottle,
+    // Reused from one frame to the next, so that drawing doesn't allocate it
+    points: RefCell<Vec<(f32, f32)>>,
 }
 
-impl SpectrumAnalyzer {
+impl<S> SpectrumAnalyzer<S>
+where
+    S: SharedWrite<Value = SpectrumOutput> + 'static,
+{
     pub fn new<LSpectrum>(
         cx: &mut Context,
         spectrum: LSpectrum,
@@ -240,173 +252,126 @@ impl SpectrumAnalyzer {
         magnitude_range: (f32, f32),
     ) -> Handle<Self>
     where
-        LSpectrum: Lens<Target = Arc<Mutex<SpectrumOutput>>>,
+        LSpectrum: Lens<Target = S>,
     {
         Self {
             spectrum: spectrum.get(cx),
             variant,
-            frequency_scaling,
-            frequency_range,
-            magnitude_scaling,
-            magnitude_range,
-            slope: None,
+            scale: SpectrumScale {
+                frequency_scaling,
+                frequency_range,
+                magnitude_scaling,
+                magnitude_range,
+                slope: None,
+            },
+            throttle: FrameThrottle::default(),
+            points: RefCell::new(Vec::new()),
         }
         .build(cx, |_cx| ())
     }
 }
 
-impl View for SpectrumAnalyzer {
+impl<S> View for SpectrumAnalyzer<S>
+where
+    S: SharedWrite<Value = SpectrumOutput> + 'static,
+{
     fn element(&self) -> Option<&'static str> {
-        Some("spectrum-analyzer")
+        Some(elements::SPECTRUM_ANALYZER)
     }
 
     fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
-        let bounds = cx.bounds();
-
-        let x = bounds.x;
-        let y = bounds.y;
-        let w = bounds.w;
-        let h = bounds.h;
+        draw_throttled(cx, canvas, &self.throttle, |cx, canvas| {
+            let bounds = cx.bounds();
 
-        let mut spectrum = self.spectrum.lock().unwrap();
-        let half_nyquist = spectrum.sample_rate / 2.;
-        let spectrum_output = spectrum.output.read();
-
-        let foreground =
-            vg::Paint::color(cx.font_color().into()).with_line_width(cx.scale_factor());
-        let background =
-            vg::Paint::color(cx.background_color().into()).with_line_width(cx.scale_factor());
-
-        match &self.variant {
-            SpectrumAnalyzerVariant::BAR => {
-                let mut path = vg::Path::new();
-
-                // This will be used to normalize the magnitudes if a slope gets applied to them
-                let magnitude_slope_divisor = if self.slope.is_some() {
-                    half_nyquist.log2().powf(self.slope.unwrap()) / self.slope.unwrap()
-                } else {
-                    0.
-                };
-
-                for (bin_idx, magnitude) in spectrum_output.iter().enumerate() {
-                    let freq = (bin_idx as f32 / spectrum_output.len() as f32) * half_nyquist;
-
-                    // Normalize frequency
-                    let freq_normalized = self.frequency_scaling.value_to_normalized(
-                        freq,
-                        self.frequency_range.0,
-                        self.frequency_range.1,
-                    );
-
-                    // Normalize magnitude and apply slope if one is set
-                    let magnitude_normalized = if self.slope.is_some() {
-                        self.magnitude_scaling.value_to_normalized(
-                            *magnitude
-                                * ((freq + 1.).log2().powf(self.slope.unwrap())
-                                    / magnitude_slope_divisor),
-                            self.magnitude_range.0,
-                            self.magnitude_range.1,
-                        )
-                    } else {
-                        self.magnitude_scaling.value_to_normalized(
-                            *magnitude,
-                            self.magnitude_range.0,
-                            self.magnitude_range.1,
-                        )
-                    };
-
-                    path.move_to(
-                        x + (w * freq_normalized),
-                        y + (h * (1.0 - magnitude_normalized)),
-                    );
-                    path.line_to(x + (w * freq_normalized), y + h);
-                }
+            let x = bounds.x;
+            let y = bounds.y;
+            let w = bounds.w;
+            let h = bounds.h;
 
-                canvas.stroke_path(&path, &foreground);
-            }
-            SpectrumAnalyzerVariant::LINE => {
-                let mut line = vg::Path::new();
-
-                let mut magnitude_normalized = self.magnitude_scaling.value_to_normalized(
-                    spectrum_output[1],
-                    self.magnitude_range.0,
-                    self.magnitude_range.1,
+            let mut points = self.points.borrow_mut();
+            // Taking the newest spectrum out of the output needs exclusive access to it
+            let producer_alive = self.spectrum.write_shared(|spectrum| {
+                let half_nyquist = spectrum.sample_rate / 2.;
+                let producer_alive = spectrum.is_producer_alive();
+                spectrum_points(
+                    &mut points,
+                    spectrum.output.read(),
+                    half_nyquist,
+                    &self.variant,
+                    &self.scale,
                 );
+                producer_alive
+            });
+            if points.is_empty() {
+                return;
+            }
+            let position = |(fx, fy): (f32, f32)| (x + w * fx, y + h * (1. - fy));
 
-                line.move_to(x, y + (h * (1.0 - magnitude_normalized)));
-
-                // This will be used to normalize the magnitudes if a slope gets applied to them
-                let magnitude_slope_divisor = if self.slope.is_some() {
-                    half_nyquist.log2().powf(self.slope.unwrap()) / self.slope.unwrap()
-                } else {
-                    0.
-                };
-
-                for (bin_idx, magnitude) in spectrum_output.iter().skip(1).enumerate() {
-                    let freq = (bin_idx as f32 / spectrum_output.len() as f32) * half_nyquist;
-
-                    // Normalize magnitude and apply slope if one is set
-                    magnitude_normalized = if self.slope.is_some() {
-                        self.magnitude_scaling.value_to_normalized(
-                            *magnitude
-                                * ((freq + 1.).log2().powf(self.slope.unwrap())
-                                    / magnitude_slope_divisor),
-                            self.magnitude_range.0,
-                            self.magnitude_range.1,
-                        )
-                    } else {
-                        self.magnitude_scaling.value_to_normalized(
-                            *magnitude,
-                            self.magnitude_range.0,
-                            self.magnitude_range.1,
-                        )
-                    };
+            // Once the plug-in is gone, the last spectrum is drawn dimmed
+            let mut font_color: vg::Color = cx.font_color().into();
+            if !producer_alive {
+                font_color.a *= DISCONNECTED_OPACITY;
+            }
+            let foreground = vg::Paint::color(font_color).with_line_width(cx.scale_factor());
+            let background =
+                vg::Paint::color(cx.background_color().into()).with_line_width(cx.scale_factor());
 
-                    // Skip frequencies that are out of range
-                    if freq < self.frequency_range.0 {
-                        line.move_to(x, y + (h * (1.0 - magnitude_normalized)));
-                        continue;
-                    }
-                    if freq > self.frequency_range.1 {
-                        break;
+            match &self.variant {
+                SpectrumAnalyzerVariant::BAR => {
+                    let mut path = vg::Path::new();
+                    for point in points.iter() {
+                        let (px, py) = position(*point);
+                        path.move_to(px, py);
+                        path.line_to(px, y + h);
                     }
 
-                    // Normalize frequency
-                    let freq_normalized = self.frequency_scaling.value_to_normalized(
-                        freq,
-                        self.frequency_range.0,
-                        self.frequency_range.1,
-                    );
-
-                    line.line_to(
-                        x + (w * freq_normalized),
-                        y + (h * (1.0 - magnitude_normalized)),
-                    );
+                    canvas.stroke_path(&path, &foreground);
                 }
+                SpectrumAnalyzerVariant::LINE => {
+                    let mut line = vg::Path::new();
+                    let (start_x, start_y) = position(points[0]);
+                    line.move_to(start_x, start_y);
+                    for point in points.iter().skip(1) {
+                        let (px, py) = position(*point);
+                        line.line_to(px, py);
+                    }
 
-                let mut fill = line.clone();
-                fill.line_to(x + w, y + h);
-                fill.line_to(x, y + h);
+                    let mut fill = line.clone();
+                    fill.line_to(x + w, y + h);
+                    fill.line_to(x, y + h);
 
-                fill.close();
+                    fill.close();
 
-                canvas.fill_path(&fill, &background);
-                canvas.stroke_path(&line, &foreground);
+                    canvas.fill_path(&fill, &background);
+                    canvas.stroke_path(&line, &foreground);
+                }
             }
-        }
+        });
     }
 }
 
 pub trait SpectrumAnalyzerModifiers {
     fn with_slope(self, slope: f32) -> Self;
 }
-impl SpectrumAnalyzerModifiers for Handle<'_, SpectrumAnalyzer> {
+impl<S> SpectrumAnalyzerModifiers for Handle<'_, SpectrumAnalyzer<S>>
+where
+    S: SharedWrite<Value = SpectrumOutput> + 'static,
+{
     /// Sets a slope in db/oct.
     ///
     /// Useful for spectrum analyzers that need to emphasize the highs more, in order to
     /// match a certain noise profile. For example, you can set the slope to 4.5 db/oct
     /// to approximate the spectral profile of brownian noise.
     fn with_slope(self, slope: f32) -> Self {
-        self.modify(|spectrum| spectrum.slope = Some(slope))
+        self.modify(|spectrum| spectrum.scale.slope = Some(slope))
+    }
+}
+
+impl<'a, S> ThrottleModifiers for Handle<'a, SpectrumAnalyzer<S>>
+where
+    S: SharedWrite<Value = SpectrumOutput> + 'static,
+{
+    fn max_fps(self, max_fps: f32) -> Self {
+        self.modify(|view| view.throttle.set_max_fps(max_fps))
     }
 }
@@ -0,0 +1,238 @@
+use super::elements;
+use super::padding::InnerPadding;
+use super::plot_area::PlotArea;
+use super::{draw_throttled, redraw_on_write, FrameThrottle, RangeModifiers, ThrottleModifiers};
+use crate::geometry::split_points;
+use crate::utils::{NormalizedRead, SharedRead, ValueScaling, VisualizerBuffer};
+
+use nih_plug_vizia::vizia::{prelude::*, vg};
+
+/// Mirrored graph that displays two buffers in a single view, one going up and the
+/// other going down from a shared center line.
+///
+/// This is most commonly used for channel levels, with the left channel in the
+/// upper half and the right channel in the lower half. Both halves share the same
+/// range and scaling - the bottom of the range lies on the center line, and the
+/// top of the range at the top and bottom edges of the view. Both halves are
+/// filled towards the center.
+///
+/// If the two buffers momentarily have different lengths, e.g. while one of them
+/// is being resized, only the slots they have in common are drawn.
+///
+/// # Example
+///
+/// Here's a split peak graph for a stereo signal, using one
+/// [`PeakBuffer`](crate::utils::PeakBuffer) per channel. A
+/// [mirrored](super::UnitRuler::mirrored) unit ruler can be placed next to it.
+///
+/// ```
+/// SplitGraph::new(
+///     cx,
+///     Data::left_peak_buffer,
+///     Data::right_peak_buffer,
+///     (-32.0, 8.0),
+///     ValueScaling::Decibels,
+/// )
+/// .center_line(Color::rgb(80, 80, 80))
+/// .color(Color::rgba(255, 255, 255, 160))
+/// .background_color(Color::rgba(255, 255, 255, 60));
+/// ```
+///
+/// # Styling
+///
+/// Stylesheets select it as `cyma-split-graph`. Its `color` is the trace, and its
+/// `background-color` the fill below it.
+pub struct SplitGraph<U, D, I>
+where
+    U: Lens,
+    U::Target: SharedRead<Value = I>,
+    D: Lens,
+    D::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32> + 'static,
+{
+    upper: U,
+    lower: D,
+    range: (f32, f32),
+    scaling: ValueScaling,
+    center_line: Option<Color>,
+    throttle: FrameThrottle,
+}
+
+enum SplitGraphEvents {
+    UpdateRange((f32, f32)),
+    UpdateScaling(ValueScaling),
+}
+
+impl<U, D, I> SplitGraph<U, D, I>
+where
+    U: Lens,
+    U::Target: SharedRead<Value = I>,
+    D: Lens,
+    D::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    /// Creates a new `SplitGraph`.
+    ///
+    /// * `upper` - The buffer that is displayed in the upper half, going up
+    /// * `lower` - The buffer that is displayed in the lower half, going down
+    pub fn new(
+        cx: &mut Context,
+        upper: U,
+        lower: D,
+        range: impl Res<(f32, f32)> + Clone,
+        scaling: impl Res<ValueScaling> + Clone,
+    ) -> Handle<Self> {
+        Self {
+            upper: upper.clone(),
+            lower: lower.clone(),
+            range: range.get_val(cx),
+            scaling: scaling.get_val(cx),
+            center_line: None,
+            throttle: FrameThrottle::default(),
+        }
+        .build(cx, |cx| {
+            redraw_on_write(cx, upper);
+            redraw_on_write(cx, lower);
+        })
+        .range(range)
+        .scaling(scaling)
+    }
+}
+
+impl<U, D, I> View for SplitGraph<U, D, I>
+where
+    U: Lens,
+    U::Target: SharedRead<Value = I>,
+    D: Lens,
+    D::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    fn element(&self) -> Option<&'static str> {
+        Some(elements::SPLIT_GRAPH)
+    }
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|e, _| match e {
+            SplitGraphEvents::UpdateRange(v) => self.range = *v,
+            SplitGraphEvents::UpdateScaling(s) => self.scaling = *s,
+        });
+    }
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        draw_throttled(cx, canvas, &self.throttle, |cx, canvas| {
+            // The same area a `Graph` maps its values onto, so that both line up
+            let bounds =
+                PlotArea::new(cx.bounds(), InnerPadding::default(), cx.scale_factor()).rect;
+
+            let x = bounds.x;
+            let w = bounds.w;
+            let center = bounds.y + bounds.h / 2.;
+
+            let line_width = cx.scale_factor();
+
+            let upper_binding = self.upper.get(cx);
+            let lower_binding = self.lower.get(cx);
+            let upper_buf = upper_binding.read_shared(|buffer| buffer.refresh());
+            let lower_buf = lower_binding.read_shared(|buffer| buffer.refresh());
+
+            let upper = NormalizedRead::new(&*upper_buf, self.range, self.scaling);
+            let lower = NormalizedRead::new(&*lower_buf, self.range, self.scaling);
+            let Some(halves) = split_points(bounds.into(), &upper, &lower) else {
+                return;
+            };
+
+            let mut stroke = vg::Path::new();
+            let mut fill = vg::Path::new();
+            for points in halves {
+                for (i, (px, py)) in points.clone().enumerate() {
+                    if i == 0 {
+                        stroke.move_to(px, py);
+                    } else {
+                        stroke.line_to(px, py);
+                    }
+                }
+
+                // Each half is filled towards the center line
+                fill.move_to(x, center);
+                for (px, py) in points {
+                    fill.line_to(px, py);
+                }
+                fill.line_to(x + w, center);
+                fill.close();
+            }
+
+            canvas.fill_path(&fill, &vg::Paint::color(cx.background_color().into()));
+            canvas.stroke_path(
+                &stroke,
+                &vg::Paint::color(cx.font_color().into()).with_line_width(line_width),
+            );
+
+            if let Some(color) = self.center_line {
+                let mut center_line = vg::Path::new();
+                center_line.move_to(x, center);
+                center_line.line_to(x + w, center);
+                canvas.stroke_path(
+                    &center_line,
+                    &vg::Paint::color(color.into()).with_line_width(line_width),
+                );
+            }
+        });
+    }
+}
+
+pub trait SplitGraphModifiers {
+    /// Draws the shared center line in the given color.
+    fn center_line(self, color: Color) -> Self;
+}
+
+impl<'a, U, D, I> SplitGraphModifiers for Handle<'a, SplitGraph<U, D, I>>
+where
+    U: Lens,
+    U::Target: SharedRead<Value = I>,
+    D: Lens,
+    D::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    fn center_line(self, color: Color) -> Self {
+        self.modify(|graph| graph.center_line = Some(color))
+    }
+}
+
+impl<'a, U, D, I> RangeModifiers for Handle<'a, SplitGraph<U, D, I>>
+where
+    U: Lens,
+    U::Target: SharedRead<Value = I>,
+    D: Lens,
+    D::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    fn range(mut self, range: impl Res<(f32, f32)>) -> Self {
+        let e = self.entity();
+
+        range.set_or_bind(self.context(), e, move |cx, r| {
+            (*cx).emit_to(e, SplitGraphEvents::UpdateRange(r));
+        });
+
+        self
+    }
+    fn scaling(mut self, scaling: impl Res<ValueScaling>) -> Self {
+        let e = self.entity();
+
+        scaling.set_or_bind(self.context(), e, move |cx, s| {
+            (*cx).emit_to(e, SplitGraphEvents::UpdateScaling(s))
+        });
+
+        self
+    }
+}
+
+impl<'a, U, D, I> ThrottleModifiers for Handle<'a, SplitGraph<U, D, I>>
+where
+    U: Lens,
+    U::Target: SharedRead<Value = I>,
+    D: Lens,
+    D::Target: SharedRead<Value = I>,
+    I: VisualizerBuffer<f32, Output = f32> + 'static,
+{
+    fn max_fps(self, max_fps: f32) -> Self {
+        self.modify(|view| view.throttle.set_max_fps(max_fps))
+    }
+}
@@ -0,0 +1,242 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+use nih_plug_vizia::vizia::{prelude::*, vg};
+
+use super::draw_clipped;
+
+// The bits of the default maximum frame rate; 0.0 means unlimited
+static DEFAULT_MAX_FPS: AtomicU32 = AtomicU32::new(0);
+
+// Frames that arrive slightly early still count as due, so that e.g. a 30 fps limit
+// paints every other frame of a 60 Hz display instead of every third one
+const TOLERANCE: Duration = Duration::from_millis(2);
+
+/// Sets the highest rate at which visualizers rebuild their paths, in frames per
+/// second, for all views that don't set their own using
+/// [`max_fps()`](ThrottleModifiers::max_fps).
+///
+/// Slow-moving views, like a loudness graph, look the same at 30 fps as they do at
+/// the display's frame rate, while taking a fraction of the time to draw. Set this
+/// once when creating the editor. A rate of 0 - the default - doesn't limit the
+/// frame rate.
+///
+/// ```
+/// create_vizia_editor(editor_state, ViziaTheming::default(), move |cx, _| {
+///     cyma::visualizers::set_default_max_fps(30.0);
+///     // ...
+/// })
+/// ```
+pub fn set_default_max_fps(max_fps: f32) {
+    DEFAULT_MAX_FPS.store(max_fps.max(0.).to_bits(), Ordering::Relaxed);
+}
+
+/// Returns the default maximum frame rate; See [`set_default_max_fps()`].
+pub fn default_max_fps() -> f32 {
+    f32::from_bits(DEFAULT_MAX_FPS.load(Ordering::Relaxed))
+}
+
+pub trait ThrottleModifiers {
+    /// Limits how often the view rebuilds its paths, in frames per second.
+    ///
+    /// In between, the view paints the paths it built last, without reading its
+    /// buffer. Resizing the view always rebuilds them. A rate of 0 doesn't limit the
+    /// frame rate, even if a [default](set_default_max_fps) is set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// Graph::new(cx, Data::loudness_buffer, (-40.0, 0.0), ValueScaling::Linear)
+    ///     .max_fps(30.0)
+    ///     .color(Color::rgba(0, 0, 0, 160));
+    /// ```
+    fn max_fps(self, max_fps: f32) -> Self;
+}
+
+/// The paths a view drew, which can be painted again without rebuilding them.
+///
+/// Views draw into a frame just like they would draw onto the canvas.
+#[derive(Default)]
+pub(crate) struct Frame {
+    commands: Vec<(vg::Path, vg::Paint, Operation)>,
+}
+
+enum Operation {
+    Fill,
+    Stroke,
+}
+
+impl Frame {
+    pub(crate) fn fill_path(&mut self, path: &vg::Path, paint: &vg::Paint) {
+        self.commands
+            .push((path.clone(), paint.clone(), Operation::Fill));
+    }
+
+    pub(crate) fn stroke_path(&mut self, path: &vg::Path, paint: &vg::Paint) {
+        self.commands
+            .push((path.clone(), paint.clone(), Operation::Stroke));
+    }
+
+    fn paint(&self, canvas: &mut Canvas) {
+        for (path, paint, operation) in self.commands.iter() {
+            match operation {
+                Operation::Fill => canvas.fill_path(path, paint),
+                Operation::Stroke => canvas.stroke_path(path, paint),
+            }
+        }
+    }
+}
+
+/// Limits how often a view rebuilds its paths; See [`ThrottleModifiers`].
+#[derive(Default)]
+pub(crate) struct FrameThrottle {
+    max_fps: Option<f32>,
+    state: RefCell<ThrottleState>,
+}
+
+#[derive(Default)]
+struct ThrottleState {
+    last_paint: Option<Instant>,
+    bounds: Option<BoundingBox>,
+    frame: Frame,
+}
+
+impl FrameThrottle {
+    pub(crate) fn set_max_fps(self: &mut Self, max_fps: f32) {
+        self.max_fps = Some(max_fps);
+    }
+
+    fn interval(self: &Self) -> Option<Duration> {
+        let max_fps = self.max_fps.unwrap_or_else(default_max_fps);
+        (max_fps > 0.).then(|| Duration::from_secs_f32(max_fps.recip()))
+    }
+}
+
+impl ThrottleState {
+    /// Returns whether the paths need to be rebuilt at `now`, and if so, counts the
+    /// frame as painted.
+    ///
+    /// Skipped frames don't move the time of the last paint, so the first frame
+    /// after the interval has passed is always painted.
+    fn should_paint(
+        self: &mut Self,
+        interval: Option<Duration>,
+        now: Instant,
+        bounds: BoundingBox,
+    ) -> bool {
+        let due = match (interval, self.last_paint) {
+            (Some(interval), Some(last_paint)) if self.bounds == Some(bounds) => {
+                now.saturating_duration_since(last_paint) + TOLERANCE >= interval
+            }
+            _ => true,
+        };
+        if due {
+            self.last_paint = Some(now);
+            self.bounds = Some(bounds);
+        }
+        due
+    }
+}
+
+/// Draws a view like [`draw_clipped()`], rebuilding its paths no more often than
+/// the throttle allows.
+pub(crate) fn draw_throttled(
+    cx: &mut DrawContext,
+    canvas: &mut Canvas,
+    throttle: &FrameThrottle,
+    draw: impl FnOnce(&mut DrawContext, &mut Frame),
+) {
+    draw_clipped(cx, canvas, |cx, canvas| {
+        let mut state = throttle.state.borrow_mut();
+        if state.should_paint(throttle.interval(), Instant::now(), cx.bounds()) {
+            state.frame.commands.clear();
+            draw(cx, &mut state.frame);
+        }
+        state.frame.paint(canvas);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ThrottleState;
+    use nih_plug_vizia::vizia::prelude::BoundingBox;
+    use std::time::{Duration, Instant};
+
+    const BOUNDS: BoundingBox = BoundingBox {
+        x: 0.,
+        y: 0.,
+        w: 100.,
+        h: 50.,
+    };
+
+    // Feeds frames at the display rate into a throttle, and returns which ones were
+    // painted
+    fn cadence(display_hz: f64, max_fps: Option<f32>, frames: usize) -> Vec<bool> {
+        let start = Instant::now();
+        let interval = max_fps.map(|max_fps| Duration::from_secs_f32(max_fps.recip()));
+        let mut state = ThrottleState::default();
+        (0..frames)
+            .map(|i| {
+                let now = start + Duration::from_secs_f64(i as f64 / display_hz);
+                state.should_paint(interval, now, BOUNDS)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unlimited() {
+        assert!(cadence(60., None, 10).iter().all(|painted| *painted));
+    }
+
+    #[test]
+    fn half_rate() {
+        assert_eq!(
+            cadence(60., Some(30.), 6),
+            vec![true, false, true, false, true, false]
+        );
+    }
+
+    #[test]
+    fn uneven_rate() {
+        // At 144 Hz, a frame is due after 5 frames, or 34.7 ms
+        let painted = cadence(144., Some(30.), 16);
+        assert_eq!(
+            painted
+                .iter()
+                .enumerate()
+                .filter(|(_, painted)| **painted)
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>(),
+            vec![0, 5, 10, 15]
+        );
+    }
+
+    #[test]
+    fn skipped_frames_paint_once_due() {
+        let start = Instant::now();
+        let interval = Some(Duration::from_millis(100));
+        let mut state = ThrottleState::default();
+
+        assert!(state.should_paint(interval, start, BOUNDS));
+        // Skipped frames leave the last paint untouched
+        for ms in [10, 40, 90] {
+            assert!(!state.should_paint(interval, start + Duration::from_millis(ms), BOUNDS));
+        }
+        assert!(state.should_paint(interval, start + Duration::from_millis(100), BOUNDS));
+        // A long pause paints right away
+        assert!(state.should_paint(interval, start + Duration::from_secs(5), BOUNDS));
+    }
+
+    #[test]
+    fn resizing_paints() {
+        let start = Instant::now();
+        let interval = Some(Duration::from_millis(100));
+        let mut state = ThrottleState::default();
+
+        assert!(state.should_paint(interval, start, BOUNDS));
+        let resized = BoundingBox { w: 120., ..BOUNDS };
+        assert!(state.should_paint(interval, start + Duration::from_millis(10), resized));
+        assert!(!state.should_paint(interval, start + Duration::from_millis(20), resized));
+    }
+}
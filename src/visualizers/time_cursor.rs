@@ -0,0 +1,206 @@
+use nih_plug_vizia::vizia::{prelude::*, vg};
+use std::sync::{Arc, Mutex};
+
+use super::Frame;
+
+/// A cursor in time that is shared by the views of an editor, so that hovering one
+/// of them marks the same moment in all of them.
+///
+/// The cursor is stored as an age - the number of seconds before now - along with
+/// the view that placed it. Whichever view is hovered places the cursor, and clears
+/// it again once the mouse leaves. Every other view draws it as a line at the
+/// position where that age lies along its own time axis, so views with different
+/// durations stay in agreement. Views that don't reach back as far as the cursor
+/// don't draw it.
+///
+/// Store the cursor inside your editor's model, and link each view to it using
+/// [`time_cursor()`](TimeCursorModifiers::time_cursor):
+///
+/// ```
+/// #[derive(Lens)]
+/// struct Data {
+///     peak_buffer: Arc<Mutex<PeakBuffer>>,
+///     oscilloscope_buffer: Arc<Mutex<WaveformBuffer>>,
+///     cursor: TimeCursor,
+/// }
+///
+/// Graph::new(cx, Data::peak_buffer, (-32.0, 8.0), ValueScaling::Decibels)
+///     .time_cursor(Data::cursor);
+/// Oscilloscope::new(cx, Data::oscilloscope_buffer, (-1.2, 1.2), ValueScaling::Linear)
+///     .time_cursor(Data::cursor);
+/// ```
+///
+/// The cursor is cheap to clone, and all clones share the same position.
+#[derive(Clone, Default)]
+pub struct TimeCursor {
+    cursor: Arc<Mutex<Option<(f32, Entity)>>>,
+}
+
+impl TimeCursor {
+    /// Constructs a new `TimeCursor`, which isn't placed anywhere.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Places the cursor `age` seconds before now, on behalf of the given view.
+    pub fn set(self: &Self, age: f32, source: Entity) {
+        *self.cursor.lock().unwrap() = Some((age, source));
+    }
+
+    /// Removes the cursor, unless another view has placed it since the given view
+    /// did.
+    pub fn clear(self: &Self, source: Entity) {
+        let mut cursor = self.cursor.lock().unwrap();
+        if cursor.is_some_and(|(_, placed_by)| placed_by == source) {
+            *cursor = None;
+        }
+    }
+
+    /// Returns the cursor's age in seconds, along with the view that placed it.
+    pub fn get(self: &Self) -> Option<(f32, Entity)> {
+        *self.cursor.lock().unwrap()
+    }
+
+    /// Returns the cursor's age in seconds, if it's placed.
+    pub fn age(self: &Self) -> Option<f32> {
+        self.get().map(|(age, _)| age)
+    }
+}
+
+impl std::fmt::Debug for TimeCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("TimeCursor").field(&self.age()).finish()
+    }
+}
+
+pub trait TimeCursorModifiers {
+    /// Links the view to a [`TimeCursor`] inside your model.
+    ///
+    /// Hovering the view places the cursor at the age below the mouse, and the view
+    /// draws the cursor while another linked view is hovered.
+    fn time_cursor<C: Lens<Target = TimeCursor>>(self, cursor: C) -> Self;
+}
+
+/// A view's link to a [`TimeCursor`].
+pub(crate) struct CursorLink {
+    cursor: TimeCursor,
+    view: Entity,
+}
+
+impl CursorLink {
+    pub(crate) fn new<V: View, C: Lens<Target = TimeCursor>>(
+        handle: &mut Handle<V>,
+        cursor: C,
+    ) -> Self {
+        let view = handle.entity();
+        Self {
+            cursor: cursor.get(&*handle.context()),
+            view,
+        }
+    }
+
+    /// Places the cursor at an age, or clears it if the mouse isn't above the view's
+    /// time axis.
+    pub(crate) fn hover(self: &Self, age: Option<f32>) {
+        match age {
+            Some(age) => self.cursor.set(age, self.view),
+            None => self.cursor.clear(self.view),
+        }
+    }
+
+    /// Clears the cursor, if the view placed it.
+    pub(crate) fn leave(self: &Self) {
+        self.cursor.clear(self.view);
+    }
+
+    /// Returns the cursor's age, if another view placed it.
+    pub(crate) fn age(self: &Self) -> Option<f32> {
+        self.cursor
+            .get()
+            .and_then(|(age, source)| (source != self.view).then_some(age))
+    }
+}
+
+/// Returns the position of a cursor along a view's time axis, from its oldest (0) to
+/// its newest end (1), or `None` if the view doesn't show that age.
+///
+/// * `span` - The newest and the oldest age (in seconds) the view shows, e.g.
+///   `(0.0, duration)` for a view of the live history
+pub(crate) fn cursor_time(age: f32, span: (f32, f32)) -> Option<f32> {
+    let (newest, oldest) = span;
+    if !(oldest > newest) || !(age >= newest && age <= oldest) {
+        return None;
+    }
+    Some(1. - (age - newest) / (oldest - newest))
+}
+
+/// Returns the age below a position along a view's time axis, or `None` if the
+/// position lies outside of it; The inverse of [`cursor_time()`].
+pub(crate) fn cursor_age(time: f32, span: (f32, f32)) -> Option<f32> {
+    let (newest, oldest) = span;
+    if !(oldest > newest) || !(0. ..=1.).contains(&time) {
+        return None;
+    }
+    Some(newest + (1. - time) * (oldest - newest))
+}
+
+/// Draws the shared time cursor between two points, in the view's font color.
+pub(crate) fn draw_time_cursor(
+    cx: &mut DrawContext,
+    frame: &mut Frame,
+    from: (f32, f32),
+    to: (f32, f32),
+) {
+    let mut color: vg::Color = cx.font_color().into();
+    color.a *= 0.5;
+    let mut line = vg::Path::new();
+    line.move_to(from.0, from.1);
+    line.line_to(to.0, to.1);
+    frame.stroke_path(
+        &line,
+        &vg::Paint::color(color).with_line_width(cx.scale_factor()),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cursor_age, cursor_time};
+
+    #[test]
+    fn age_to_time() {
+        // The same age lies at a different position in views of different durations
+        for (duration, expected) in [(10., Some(0.8)), (4., Some(0.5)), (2., Some(0.))] {
+            assert_eq!(cursor_time(2., (0., duration)), expected);
+        }
+        // Views with a shorter duration don't show it at all
+        assert_eq!(cursor_time(2., (0., 1.)), None);
+        assert_eq!(cursor_time(2., (0., 1.999)), None);
+
+        // Now is at the newest end
+        assert_eq!(cursor_time(0., (0., 10.)), Some(1.));
+        assert_eq!(cursor_time(-0.5, (0., 10.)), None);
+
+        // A scrubbed window only shows the ages inside of it
+        assert_eq!(cursor_time(5., (4., 6.)), Some(0.5));
+        assert_eq!(cursor_time(3., (4., 6.)), None);
+        assert_eq!(cursor_time(7., (4., 6.)), None);
+
+        // Without a duration, no age is shown
+        assert_eq!(cursor_time(0., (0., 0.)), None);
+        assert_eq!(cursor_time(f32::NAN, (0., 10.)), None);
+    }
+
+    #[test]
+    fn time_to_age() {
+        assert_eq!(cursor_age(0.75, (0., 10.)), Some(2.5));
+        assert_eq!(cursor_age(0.5, (4., 6.)), Some(5.));
+        assert_eq!(cursor_age(1.2, (0., 10.)), None);
+        assert_eq!(cursor_age(0.5, (0., 0.)), None);
+
+        // Hovering one view places the cursor where the other views draw it
+        let age = cursor_age(0.75, (0., 4.)).unwrap();
+        assert_eq!(cursor_time(age, (0., 4.)), Some(0.75));
+        assert_eq!(cursor_time(age, (0., 10.)), Some(0.9));
+        assert_eq!(cursor_time(age, (0., 0.5)), None);
+    }
+}
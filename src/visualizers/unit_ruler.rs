@@ -1,5 +1,15 @@
-use crate::utils::ValueScaling;
+use super::elements;
+use super::padding::InnerPadding;
+use super::plot_area::PlotArea;
+use super::{marks, GraphDirection, OverlayRegistry, PaddingModifiers, RangeSource};
+use crate::utils::{
+    default_formatter, format_scaled, AutoRange, NormalizedRead, RangeSegments, SharedRange,
+    ValueFormatter, ValueScaling,
+};
+use nih_plug::util::db_to_gain;
 use nih_plug_vizia::vizia::prelude::*;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 /// Generic ruler that shows markers for certain values.
 ///
@@ -27,53 +37,897 @@ use nih_plug_vizia::vizia::prelude::*;
 /// .width(Pixels(32.))
 /// .height(Pixels(128.));
 /// ```
+///
+/// # Styling
+///
+/// Stylesheets select it as `cyma-unit-ruler`. Its labels inherit its `color` and
+/// `font-size`.
 pub struct UnitRuler {}
 
+/// The range of a ruler that follows an [`AutoRange`] or a [`SharedRange`].
+#[derive(Lens)]
+struct FollowedRange {
+    range: (f32, f32),
+    source: RangeSource,
+}
+
+enum FollowedRangeEvents {
+    Sync,
+}
+
+impl Model for FollowedRange {
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|e, _| match e {
+            FollowedRangeEvents::Sync => self.range = self.source.get(),
+        });
+    }
+}
+
+/// How the labels of a [`UnitRuler`] are rotated.
+///
+/// Each label is rotated around its marker, so it stays centered on it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LabelRotation {
+    /// The labels are drawn upright.
+    #[default]
+    None,
+    /// The labels are rotated by 90° clockwise, reading from top to bottom.
+    Clockwise,
+    /// The labels are rotated by 90° counterclockwise (or 270° clockwise), reading
+    /// from bottom to top.
+    CounterClockwise,
+}
+
+impl LabelRotation {
+    fn degrees(self: &Self) -> f32 {
+        match self {
+            LabelRotation::None => 0.,
+            LabelRotation::Clockwise => 90.,
+            LabelRotation::CounterClockwise => 270.,
+        }
+    }
+}
+
+/// How a ruler's labels are placed, which rebuilds them whenever it changes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct LabelStyle {
+    rotation: LabelRotation,
+    mirror_x: bool,
+    direction: GraphDirection,
+    // Only the padding along the ruler is used
+    padding: InnerPadding,
+    // The ruler's bounds and the window's scale factor, once it was laid out
+    geometry: Option<(BoundingBox, f32)>,
+}
+
+impl Data for LabelStyle {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+/// The layout of a ruler's labels, and the parts of the ruler that are covered by
+/// overlays, along which labels are hidden.
+#[derive(Lens)]
+struct RulerLayout {
+    orientation: Orientation,
+    style: LabelStyle,
+    // Open ranges of normalized positions
+    blocked: Vec<(f32, f32)>,
+}
+
+enum RulerLayoutEvents {
+    Sync(Vec<BoundingBox>),
+    SetRotation(LabelRotation),
+    SetMirrorX(bool),
+    SetDirection(GraphDirection),
+    SetPadding(InnerPadding),
+    SetGeometry(BoundingBox, f32),
+}
+
+impl Model for RulerLayout {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|e, _| match e {
+            RulerLayoutEvents::Sync(overlays) => {
+                let scale = cx.scale_factor();
+                let padding = self.style.padding.along(self.orientation);
+                let blocked = blocked_spans(
+                    PlotArea::new(cx.bounds(), padding, scale).rect,
+                    overlays,
+                    self.orientation,
+                    label_extent(self.orientation, self.style.rotation) * cx.scale_factor(),
+                );
+                if blocked != self.blocked {
+                    self.blocked = blocked;
+                }
+            }
+            RulerLayoutEvents::SetRotation(rotation) => self.style.rotation = *rotation,
+            RulerLayoutEvents::SetMirrorX(mirror_x) => self.style.mirror_x = *mirror_x,
+            RulerLayoutEvents::SetDirection(direction) => self.style.direction = *direction,
+            RulerLayoutEvents::SetPadding(padding) => self.style.padding = *padding,
+            RulerLayoutEvents::SetGeometry(bounds, scale) => {
+                let geometry = Some((*bounds, *scale));
+                if geometry != self.style.geometry {
+                    self.style.geometry = geometry;
+                }
+            }
+        });
+    }
+}
+
+/// The approximate size of a label along the ruler, in logical pixels.
+///
+/// Across the ruler, a label is assumed to take up the ruler's entire width or
+/// height.
+fn label_extent(orientation: Orientation, rotation: LabelRotation) -> f32 {
+    // Rotated labels run along a vertical ruler, and across a horizontal one
+    let along = match rotation {
+        LabelRotation::None => orientation == Orientation::Horizontal,
+        _ => orientation == Orientation::Vertical,
+    };
+    if along {
+        40.0
+    } else {
+        16.0
+    }
+}
+
 impl UnitRuler {
     pub fn new<'a>(
         cx: &'a mut Context,
         range: (f32, f32),
         scaling: ValueScaling,
-        values: Vec<(f32, &'static str)>,
+        values: Vec<(f32, impl ToString)>,
         orientation: Orientation,
     ) -> Handle<'a, Self> {
+        let positions = normalized_values(range, scaling, labeled(values));
         Self {}.build(cx, |cx| {
-            let normalized_values = values
-                .into_iter()
-                .filter_map(|v| {
-                    // Normalize the value according to the provided scaling, within the provided range
-                    scaling
-                        .value_to_normalized_optional(v.0, range.0, range.1)
-                        // If it is not in range, discard it by returning a `None`, which filter_map filters out
-                        .map(|value| (value, v.1))
-                })
-                .collect::<Vec<(f32, &'static str)>>();
-            ZStack::new(cx, |cx| {
-                for value in normalized_values {
-                    match orientation {
-                        Orientation::Vertical => {
-                            Label::new(cx, value.1)
-                                .top(Percentage(100. - value.0 * 100.))
-                                .transform(Transform::TranslateY(LengthOrPercentage::Percentage(
-                                    -50.,
-                                )));
-                        }
-                        Orientation::Horizontal => {
-                            Label::new(cx, value.1)
-                                .left(Percentage(value.0 * 100.))
-                                .transform(Transform::TranslateX(LengthOrPercentage::Percentage(
-                                    -50.,
-                                )));
-                        }
-                    }
+            build_layout(cx, orientation);
+            build_labels(cx, positions, orientation);
+        })
+    }
+
+    /// Creates a ruler for a mirrored view, such as a [`SplitGraph`](super::SplitGraph).
+    ///
+    /// The bottom of the range lies at the center of the ruler, and each marker is
+    /// shown twice - once in each direction, increasing outwards.
+    ///
+    /// ```
+    /// UnitRuler::mirrored(
+    ///     cx,
+    ///     (-32.0, 8.0),
+    ///     ValueScaling::Linear,
+    ///     vec![(0.0, "0db"), (-12.0, "-12db"), (-24.0, "-24db")],
+    ///     Orientation::Vertical,
+    /// )
+    /// .font_size(12.)
+    /// .width(Pixels(32.));
+    /// ```
+    pub fn mirrored<'a>(
+        cx: &'a mut Context,
+        range: (f32, f32),
+        scaling: ValueScaling,
+        values: Vec<(f32, impl ToString)>,
+        orientation: Orientation,
+    ) -> Handle<'a, Self> {
+        let positions = mirror(normalized_values(range, scaling, labeled(values)));
+        Self {}.build(cx, |cx| {
+            build_layout(cx, orientation);
+            build_labels(cx, positions, orientation);
+        })
+    }
+
+    /// Creates a ruler for a broken value axis, which lines up with a
+    /// [`Graph`](super::Graph) or [`Grid`](super::Grid) that has the same
+    /// [`range_segments()`](super::GraphModifiers::range_segments).
+    ///
+    /// Markers inside the breaks are hidden. Each edge of a segment is labeled, so
+    /// that it's clear where the axis jumps - using the default format for the
+    /// scaling, unless one of the markers already lies on it.
+    ///
+    /// ```
+    /// UnitRuler::segmented(
+    ///     cx,
+    ///     &[(-80., -60.), (-12., 0.)],
+    ///     ValueScaling::Linear,
+    ///     vec![(-70.0, "-70db"), (-6.0, "-6db"), (0.0, "0db")],
+    ///     Orientation::Vertical,
+    /// )
+    /// .font_size(12.)
+    /// .width(Pixels(32.));
+    /// ```
+    pub fn segmented<'a>(
+        cx: &'a mut Context,
+        segments: &[(f32, f32)],
+        scaling: ValueScaling,
+        values: Vec<(f32, impl ToString)>,
+        orientation: Orientation,
+    ) -> Handle<'a, Self> {
+        let segments = RangeSegments::new(segments);
+        let positions = segmented_values(&segments, scaling, labeled(values));
+        Self {}.build(cx, |cx| {
+            build_layout(cx, orientation);
+            build_labels(cx, positions, orientation);
+        })
+    }
+
+    /// Creates a ruler that follows the range of an [`AutoRange`], which is driven by
+    /// a [`Graph`](super::Graph).
+    ///
+    /// The range is polled once per frame, and the markers are moved whenever it
+    /// changes. Markers outside of the current range are hidden.
+    ///
+    /// ```
+    /// UnitRuler::follow(
+    ///     cx,
+    ///     auto_range.clone(),
+    ///     ValueScaling::Linear,
+    ///     vec![(0.0, "0db"), (-12.0, "-12db"), (-24.0, "-24db"), (-36.0, "-36db")],
+    ///     Orientation::Vertical,
+    /// )
+    /// .font_size(12.)
+    /// .width(Pixels(32.));
+    /// ```
+    pub fn follow<'a>(
+        cx: &'a mut Context,
+        auto_range: Arc<Mutex<AutoRange>>,
+        scaling: ValueScaling,
+        values: Vec<(f32, impl ToString)>,
+        orientation: Orientation,
+    ) -> Handle<'a, Self> {
+        Self::follow_source(
+            cx,
+            RangeSource::Auto(auto_range),
+            scaling,
+            values,
+            orientation,
+        )
+    }
+
+    /// Creates a ruler that follows a [`SharedRange`], which is usually written by the
+    /// audio thread.
+    ///
+    /// Just like [`follow()`](Self::follow), the range is polled once per frame, and
+    /// markers outside of the current range are hidden.
+    ///
+    /// ```
+    /// UnitRuler::follow_shared(
+    ///     cx,
+    ///     range.clone(),
+    ///     ValueScaling::Linear,
+    ///     vec![(0.0, "0db"), (-12.0, "-12db"), (-24.0, "-24db"), (-36.0, "-36db")],
+    ///     Orientation::Vertical,
+    /// );
+    /// ```
+    pub fn follow_shared<'a>(
+        cx: &'a mut Context,
+        range: SharedRange,
+        scaling: ValueScaling,
+        values: Vec<(f32, impl ToString)>,
+        orientation: Orientation,
+    ) -> Handle<'a, Self> {
+        Self::follow_source(cx, RangeSource::Shared(range), scaling, values, orientation)
+    }
+
+    fn follow_source<'a>(
+        cx: &'a mut Context,
+        source: RangeSource,
+        scaling: ValueScaling,
+        values: Vec<(f32, impl ToString)>,
+        orientation: Orientation,
+    ) -> Handle<'a, Self> {
+        let values = labeled(values);
+        Self {}.build(cx, |cx| {
+            let range = source.get();
+            FollowedRange { range, source }.build(cx);
+            build_layout(cx, orientation);
+
+            Binding::new(cx, FollowedRange::range, move |cx, range| {
+                let positions = normalized_values(range.get(cx), scaling, values.clone());
+                build_labels(cx, positions, orientation);
+            });
+
+            let ruler = cx.current();
+            let timer = cx.add_timer(Duration::from_millis(16), None, move |cx, action| {
+                if let TimerAction::Tick(_) = action {
+                    cx.emit_to(ruler, FollowedRangeEvents::Sync);
                 }
             });
+            cx.start_timer(timer);
+        })
+    }
+
+    /// Returns the markers of the form `anchor + k * step` that lie inside the range,
+    /// each labeled using `label`.
+    ///
+    /// These are the same values a [`Grid`](super::Grid::marks_from_anchor) generates
+    /// from the same anchor, step and range, so the two always line up.
+    ///
+    /// ```
+    /// UnitRuler::new(
+    ///     cx,
+    ///     (-96.0, 24.0),
+    ///     ValueScaling::Linear,
+    ///     UnitRuler::marks_from_anchor(0.0, 10.0, (-96.0, 24.0), |v| format!("{v}db")),
+    ///     Orientation::Vertical,
+    /// );
+    /// ```
+    pub fn marks_from_anchor(
+        anchor: f32,
+        step: f32,
+        range: (f32, f32),
+        label: impl Fn(f32) -> String,
+    ) -> Vec<(f32, String)> {
+        with_labels(marks::marks_from_anchor(anchor, step, range), label)
+    }
+
+    /// Returns the given markers, each labeled using `label`; Markers outside the
+    /// range are not shown.
+    pub fn marks_explicit(values: Vec<f32>, label: impl Fn(f32) -> String) -> Vec<(f32, String)> {
+        with_labels(marks::marks_explicit(values), label)
+    }
+
+    /// Returns the given markers, each labeled using a [`ValueFormatter`].
+    ///
+    /// Like the readout of a [`Graph`](super::Graph), the formatter is passed the
+    /// values in the units of the range - so with [`ValueScaling::Decibels`], the
+    /// markers are given as gains, and formatted in decibels.
+    ///
+    /// ```
+    /// UnitRuler::new(
+    ///     cx,
+    ///     (20.0, 20_000.0),
+    ///     ValueScaling::Frequency,
+    ///     UnitRuler::marks_formatted(
+    ///         vec![100.0, 1_000.0, 2_500.0, 10_000.0],
+    ///         ValueScaling::Frequency,
+    ///         FrequencyFormatter::default(),
+    ///     ),
+    ///     Orientation::Horizontal,
+    /// );
+    /// ```
+    pub fn marks_formatted(
+        values: Vec<f32>,
+        scaling: ValueScaling,
+        formatter: impl ValueFormatter,
+    ) -> Vec<(f32, String)> {
+        with_labels(marks::marks_explicit(values), |value| {
+            let mut label = String::new();
+            format_scaled(&formatter, scaling, value, &mut label);
+            label
+        })
+    }
+}
+
+fn with_labels(values: Vec<f32>, label: impl Fn(f32) -> String) -> Vec<(f32, String)> {
+    values
+        .into_iter()
+        .map(|value| (value, label(value)))
+        .collect()
+}
+
+fn labeled(values: Vec<(f32, impl ToString)>) -> Vec<(f32, String)> {
+    values
+        .into_iter()
+        .map(|(value, label)| (value, label.to_string()))
+        .collect()
+}
+
+fn normalized_values(
+    range: (f32, f32),
+    scaling: ValueScaling,
+    values: Vec<(f32, String)>,
+) -> Vec<(f32, String)> {
+    values
+        .into_iter()
+        .filter_map(|v| {
+            // Normalize the value according to the provided scaling, within the provided range
+            NormalizedRead::new(&(), range, scaling)
+                .normalize_on_scale(v.0)
+                // If it is not in range, discard it by returning a `None`, which filter_map filters out
+                .map(|value| (value, v.1))
+        })
+        .collect()
+}
+
+fn segmented_values(
+    segments: &RangeSegments,
+    scaling: ValueScaling,
+    values: Vec<(f32, String)>,
+) -> Vec<(f32, String)> {
+    // The edges are in the units of the range, and the values are gains in decibels
+    let value_of = |edge: f32| match scaling {
+        ValueScaling::Decibels => db_to_gain(edge),
+        _ => edge,
+    };
+    let edges = segments
+        .edges()
+        .filter(|edge| !values.iter().any(|(value, _)| *value == value_of(*edge)))
+        .map(|edge| {
+            let mut label = String::new();
+            default_formatter(scaling).format(edge, &mut label);
+            (value_of(edge), label)
+        })
+        .collect::<Vec<_>>();
+
+    let normalized =
+        NormalizedRead::new(&(), segments.range(), scaling).with_segments(Some(segments));
+    values
+        .into_iter()
+        .chain(edges)
+        .filter(|(value, _)| segments.contains(scaling, *value))
+        .map(|(value, label)| (normalized.normalize_value(value), label))
+        .collect()
+}
+
+// Places each normalized value on both sides of the center
+fn mirror(values: Vec<(f32, String)>) -> Vec<(f32, String)> {
+    values
+        .into_iter()
+        .flat_map(|(value, label)| {
+            // The center only needs a single label
+            let lower = (value > 0.0).then(|| (0.5 - value / 2., label.clone()));
+            let upper = (0.5 + value / 2., label);
+            std::iter::once(upper).chain(lower)
+        })
+        .collect()
+}
+
+fn build_layout(cx: &mut Context, orientation: Orientation) {
+    RulerLayout {
+        orientation,
+        style: LabelStyle::default(),
+        blocked: Vec::new(),
+    }
+    .build(cx);
+}
+
+fn build_labels(cx: &mut Context, values: Vec<(f32, String)>, orientation: Orientation) {
+    Binding::new(cx, RulerLayout::style, move |cx, style| {
+        let style = style.get(cx);
+        // Until the ruler is laid out, it isn't known where the labels go
+        let Some((bounds, scale)) = style.geometry else {
+            return;
+        };
+        // The labels are placed along the same area the views map their values onto
+        let area = PlotArea::new(bounds, style.padding.along(orientation), scale);
+        let positions = values
+            .iter()
+            .map(|(position, _)| *position)
+            .collect::<Vec<_>>();
+        let ticks = tick_positions(
+            &area,
+            &positions,
+            orientation,
+            style.mirror_x,
+            style.direction,
+        );
+        ZStack::new(cx, |cx| {
+            for ((position, label), tick) in values.iter().cloned().zip(ticks) {
+                let position = placed(position, orientation, style.mirror_x, style.direction);
+                // Labels below an overlay are hidden
+                let visible =
+                    RulerLayout::blocked.map(move |blocked| !is_blocked(blocked, position));
+                // Rotated around the marker, after centering the label on it
+                let rotate = Transform::Rotate(Angle::Deg(style.rotation.degrees()));
+                match orientation {
+                    Orientation::Vertical => {
+                        Label::new(cx, label)
+                            .top(Pixels((tick - bounds.y) / scale))
+                            .transform(vec![
+                                Transform::TranslateY(LengthOrPercentage::Percentage(-50.)),
+                                rotate,
+                            ])
+                            .display(visible);
+                    }
+                    Orientation::Horizontal => {
+                        Label::new(cx, label)
+                            .left(Pixels((tick - bounds.x) / scale))
+                            .transform(vec![
+                                Transform::TranslateX(LengthOrPercentage::Percentage(-50.)),
+                                rotate,
+                            ])
+                            .display(visible);
+                    }
+                }
+            }
+        });
+    });
+}
+
+/// Returns the positions of the markers at the normalized positions, in physical
+/// pixels along the ruler.
+///
+/// These are snapped to the [`PlotArea`] in the same way as the lines of a
+/// [`Grid`](super::Grid), so each label points exactly at its line.
+pub(crate) fn tick_positions(
+    area: &PlotArea,
+    positions: &[f32],
+    orientation: Orientation,
+    mirror_x: bool,
+    direction: GraphDirection,
+) -> Vec<f32> {
+    positions
+        .iter()
+        .map(|position| direction.apply(*position))
+        .map(|position| match orientation {
+            Orientation::Vertical => {
+                let axes = area.axes(Orientation::Vertical, false);
+                area.snap(axes.point(0., position).1)
+            }
+            Orientation::Horizontal => {
+                let axes = area.axes(Orientation::Vertical, mirror_x);
+                area.snap(axes.point(position, 0.).0)
+            }
         })
+        .collect()
+}
+
+/// Returns where a label at a normalized position is placed along the ruler; Only
+/// a horizontal ruler is mirrored, but both follow the direction.
+fn placed(
+    position: f32,
+    orientation: Orientation,
+    mirror_x: bool,
+    direction: GraphDirection,
+) -> f32 {
+    let position = direction.apply(position);
+    match orientation {
+        Orientation::Horizontal if mirror_x => 1. - position,
+        _ => position,
+    }
+}
+
+fn is_blocked(blocked: &[(f32, f32)], position: f32) -> bool {
+    blocked
+        .iter()
+        .any(|(start, end)| *start < position && position < *end)
+}
+
+/// Returns the area a label at a normalized position takes up, in the same units as
+/// the ruler's bounds.
+fn label_rect(
+    ruler: BoundingBox,
+    position: f32,
+    orientation: Orientation,
+    extent: f32,
+) -> BoundingBox {
+    match orientation {
+        Orientation::Vertical => BoundingBox {
+            x: ruler.x,
+            y: ruler.y + (1. - position) * ruler.h - extent / 2.,
+            w: ruler.w,
+            h: extent,
+        },
+        Orientation::Horizontal => BoundingBox {
+            x: ruler.x + position * ruler.w - extent / 2.,
+            y: ruler.y,
+            w: extent,
+            h: ruler.h,
+        },
+    }
+}
+
+/// Returns the ranges of normalized positions at which a label would overlap one of
+/// the overlays.
+fn blocked_spans(
+    ruler: BoundingBox,
+    overlays: &[BoundingBox],
+    orientation: Orientation,
+    extent: f32,
+) -> Vec<(f32, f32)> {
+    overlays
+        .iter()
+        // Labels span the entire ruler in the other direction, so any overlay that
+        // overlaps the ruler in that direction covers some of them
+        .filter_map(|overlay| match orientation {
+            Orientation::Vertical if ruler.h > 0. && overlaps_x(ruler, overlay) => Some((
+                1. - (overlay.y + overlay.h + extent / 2. - ruler.y) / ruler.h,
+                1. - (overlay.y - extent / 2. - ruler.y) / ruler.h,
+            )),
+            Orientation::Horizontal if ruler.w > 0. && overlaps_y(ruler, overlay) => Some((
+                (overlay.x - extent / 2. - ruler.x) / ruler.w,
+                (overlay.x + overlay.w + extent / 2. - ruler.x) / ruler.w,
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+fn overlaps_x(a: BoundingBox, b: &BoundingBox) -> bool {
+    a.x < b.x + b.w && b.x < a.x + a.w
+}
+
+fn overlaps_y(a: BoundingBox, b: &BoundingBox) -> bool {
+    a.y < b.y + b.h && b.y < a.y + a.h
+}
+
+pub trait UnitRulerModifiers {
+    /// Hides the labels that would be drawn below an overlay, such as a
+    /// [`Graph`](super::Graph)'s [readout](super::GraphModifiers::readout).
+    ///
+    /// The overlays' areas are read from the registry once per frame. Without a
+    /// registry, all labels are shown.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// UnitRuler::new(cx, (-32.0, 8.0), ValueScaling::Linear, labels, Orientation::Vertical)
+    ///     .avoid_overlays(overlays.clone())
+    ///     .width(Pixels(32.));
+    /// ```
+    fn avoid_overlays(self, registry: OverlayRegistry) -> Self;
+
+    /// Rotates each label around its marker, e.g. so that the labels of a
+    /// horizontal ruler read vertically in a narrow channel strip.
+    ///
+    /// The labels are rotated while they're drawn, so their text stays sharp.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// UnitRuler::new(cx, (-60.0, 6.0), ValueScaling::Linear, labels, Orientation::Horizontal)
+    ///     .label_rotation(LabelRotation::CounterClockwise)
+    ///     .height(Pixels(40.));
+    /// ```
+    fn label_rotation(self, rotation: LabelRotation) -> Self;
+
+    /// Flips the labels of a horizontal ruler, so that it lines up with a
+    /// [`Graph`](super::Graph) or [`Grid`](super::Grid) that is mirrored using
+    /// [`mirror_x()`](super::AxisModifiers::mirror_x). This has no effect on a
+    /// vertical ruler.
+    fn mirror_x(self, mirror: bool) -> Self;
+
+    /// Runs the labels in the given direction, so that the ruler lines up with a
+    /// [`Graph`](super::Graph) or [`Grid`](super::Grid) that has the same
+    /// [`direction()`](super::AxisModifiers::direction).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// UnitRuler::new(cx, (0.0, 24.0), ValueScaling::Linear, labels, Orientation::Vertical)
+    ///     .direction(GraphDirection::TopDown)
+    ///     .width(Pixels(32.));
+    /// ```
+    fn direction(self, direction: GraphDirection) -> Self;
+}
+
+impl<'a> UnitRulerModifiers for Handle<'a, UnitRuler> {
+    fn avoid_overlays(mut self, registry: OverlayRegistry) -> Self {
+        let ruler = self.entity();
+        let cx = self.context();
+        let timer = cx.add_timer(Duration::from_millis(16), None, move |cx, action| {
+            if let TimerAction::Tick(_) = action {
+                cx.emit_to(ruler, RulerLayoutEvents::Sync(registry.rects()));
+            }
+        });
+        cx.start_timer(timer);
+        self
+    }
+    fn label_rotation(mut self, rotation: LabelRotation) -> Self {
+        let ruler = self.entity();
+        self.context()
+            .emit_to(ruler, RulerLayoutEvents::SetRotation(rotation));
+        self
+    }
+    fn mirror_x(mut self, mirror: bool) -> Self {
+        let ruler = self.entity();
+        self.context()
+            .emit_to(ruler, RulerLayoutEvents::SetMirrorX(mirror));
+        self
+    }
+    fn direction(mut self, direction: GraphDirection) -> Self {
+        let ruler = self.entity();
+        self.context()
+            .emit_to(ruler, RulerLayoutEvents::SetDirection(direction));
+        self
+    }
+}
+
+impl<'a> PaddingModifiers for Handle<'a, UnitRuler> {
+    /// Places the labels along the same area a view with the same padding maps its
+    /// values onto; See [`PaddingModifiers`].
+    ///
+    /// Only the padding along the ruler is used - the top and bottom of a vertical
+    /// ruler, or the left and right of a horizontal one.
+    fn inner_padding(mut self, top: f32, right: f32, bottom: f32, left: f32) -> Self {
+        let ruler = self.entity();
+        self.context().emit_to(
+            ruler,
+            RulerLayoutEvents::SetPadding(InnerPadding::new(top, right, bottom, left)),
+        );
+        self
+    }
+    fn uniform_inner_padding(self, padding: f32) -> Self {
+        self.inner_padding(padding, padding, padding, padding)
     }
 }
 
 impl View for UnitRuler {
     fn element(&self) -> Option<&'static str> {
-        Some("unit-ruler")
+        Some(elements::UNIT_RULER)
+    }
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, _| {
+            if let WindowEvent::GeometryChanged(_) = window_event {
+                cx.emit(RulerLayoutEvents::SetGeometry(
+                    cx.bounds(),
+                    cx.scale_factor(),
+                ));
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        blocked_spans, is_blocked, label_extent, label_rect, labeled, mirror, placed,
+        segmented_values, LabelRotation, UnitRuler,
+    };
+    use crate::utils::{DecibelFormatter, FrequencyFormatter, RangeSegments, ValueScaling};
+    use crate::visualizers::overlay::{intersects, readout_rect};
+    use crate::visualizers::{GraphDirection, Grid, ReadoutPosition};
+    use nih_plug_vizia::vizia::prelude::{BoundingBox, Orientation};
+
+    fn rect(x: f32, y: f32, w: f32, h: f32) -> BoundingBox {
+        BoundingBox { x, y, w, h }
+    }
+
+    // Lays out labels at every 1/40th of the ruler, and returns the areas of the
+    // labels that are shown
+    fn shown_labels(
+        ruler: BoundingBox,
+        overlays: &[BoundingBox],
+        orientation: Orientation,
+        extent: f32,
+    ) -> Vec<BoundingBox> {
+        let blocked = blocked_spans(ruler, overlays, orientation, extent);
+        (0..=40)
+            .map(|i| i as f32 / 40.)
+            .filter(|position| !is_blocked(&blocked, *position))
+            .map(|position| label_rect(ruler, position, orientation, extent))
+            .collect()
+    }
+
+    #[test]
+    fn mirrored_positions() {
+        assert_eq!(
+            mirror(labeled(vec![(0.0, "0"), (0.5, "1/2"), (1.0, "1")])),
+            labeled(vec![
+                (0.5, "0"),
+                (0.75, "1/2"),
+                (0.25, "1/2"),
+                (1.0, "1"),
+                (0.0, "1"),
+            ])
+        );
+    }
+
+    #[test]
+    fn marks_match_grid() {
+        let marks = UnitRuler::marks_from_anchor(0., 10., (-96., 24.), |v| format!("{v}db"));
+        assert_eq!(
+            marks.iter().map(|(value, _)| *value).collect::<Vec<_>>(),
+            Grid::marks_from_anchor(0., 10., (-96., 24.))
+        );
+        assert_eq!(marks[0], (-90., "-90db".to_string()));
+        assert_eq!(marks[9], (0., "0db".to_string()));
+    }
+
+    #[test]
+    fn segmented_labels() {
+        let segments = RangeSegments::new(&[(-80., -60.), (-12., 0.)]);
+        let labels = segmented_values(
+            &segments,
+            ValueScaling::Linear,
+            labeled(vec![(-70., "-70db"), (-40., "-40db"), (0., "0db")]),
+        );
+        let position = |value| segments.value_to_normalized(ValueScaling::Linear, value);
+
+        // The marker inside the break is hidden, and the edges without a marker of
+        // their own are labeled
+        assert_eq!(
+            labels,
+            labeled(vec![
+                (position(-70.), "-70db"),
+                (position(0.), "0db"),
+                (0., "-80.00"),
+                (position(-60.), "-60.00"),
+                (position(-12.), "-12.00"),
+            ])
+        );
+
+        // Gains are placed in decibels, and their edges labeled in decibels
+        let labels = segmented_values(&segments, ValueScaling::Decibels, Vec::new());
+        let texts = labels
+            .iter()
+            .map(|(_, text)| text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["-80.0 dB", "-60.0 dB", "-12.0 dB", "0.0 dB"]);
+        assert!((labels[1].0 - position(-60.)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn formatted_marks() {
+        let marks = UnitRuler::marks_formatted(
+            vec![100., 2500.],
+            ValueScaling::Frequency,
+            FrequencyFormatter::default(),
+        );
+        assert_eq!(marks, [(100., "100".into()), (2500., "2.5k".into())]);
+
+        // Gains are labeled in decibels
+        let marks = UnitRuler::marks_formatted(
+            vec![1., 0.5],
+            ValueScaling::Decibels,
+            DecibelFormatter {
+                precision: 0,
+                suffix: "db",
+            },
+        );
+        assert_eq!(marks, [(1., "0db".into()), (0.5, "-6db".into())]);
+    }
+
+    #[test]
+    fn labels_avoid_readout() {
+        // A 400x200 graph, with a ruler overlaid on its right edge
+        let graph = rect(0., 0., 400., 200.);
+        let ruler = rect(368., 0., 32., 200.);
+        let readout = readout_rect(graph, ReadoutPosition::TopRight, 8., (72., 20.));
+
+        let shown = shown_labels(ruler, &[readout], Orientation::Vertical, 16.);
+        assert!(shown.iter().all(|label| !intersects(*label, readout)));
+        // Only the labels near the top are hidden
+        assert_eq!(shown.len(), 41 - 7);
+
+        // A readout on the other side of the graph doesn't hide anything
+        let readout = readout_rect(graph, ReadoutPosition::TopLeft, 8., (72., 20.));
+        assert_eq!(
+            shown_labels(ruler, &[readout], Orientation::Vertical, 16.).len(),
+            41
+        );
+
+        // A ruler along the bottom edge
+        let ruler = rect(0., 176., 400., 24.);
+        let readout = readout_rect(graph, ReadoutPosition::BottomLeft, 8., (72., 20.));
+        let shown = shown_labels(ruler, &[readout], Orientation::Horizontal, 40.);
+        assert!(shown.iter().all(|label| !intersects(*label, readout)));
+        assert_eq!(shown.len(), 41 - 10);
+    }
+
+    #[test]
+    fn labels_without_overlays() {
+        let ruler = rect(368., 0., 32., 200.);
+        assert!(blocked_spans(ruler, &[], Orientation::Vertical, 16.).is_empty());
+        assert_eq!(
+            shown_labels(ruler, &[], Orientation::Vertical, 16.).len(),
+            41
+        );
+    }
+
+    #[test]
+    fn rotated_and_mirrored() {
+        // Rotated labels take up more room along a vertical ruler, and less along a
+        // horizontal one
+        for rotation in [LabelRotation::Clockwise, LabelRotation::CounterClockwise] {
+            assert!(
+                label_extent(Orientation::Vertical, rotation)
+                    > label_extent(Orientation::Vertical, LabelRotation::None)
+            );
+            assert!(
+                label_extent(Orientation::Horizontal, rotation)
+                    < label_extent(Orientation::Horizontal, LabelRotation::None)
+            );
+        }
+
+        let up = GraphDirection::BottomUp;
+        assert_eq!(placed(0.25, Orientation::Horizontal, true, up), 0.75);
+        assert_eq!(placed(0.25, Orientation::Horizontal, false, up), 0.25);
+        assert_eq!(placed(0.25, Orientation::Vertical, true, up), 0.25);
+        // Both orientations follow the direction
+        let down = GraphDirection::TopDown;
+        assert_eq!(placed(0.25, Orientation::Vertical, false, down), 0.75);
+        assert_eq!(placed(0.25, Orientation::Horizontal, true, down), 0.25);
     }
 }